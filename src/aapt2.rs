@@ -1,17 +1,60 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use tracing::{debug, info};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead};
+use tracing::{Instrument, debug, info, warn};
 
-use crate::types::{CompileResult, LinkResult};
+use crate::events::{self, BuildEvent};
+use crate::heartbeat::BuildActivity;
+use crate::observer::BuildObserver;
+use crate::types::{Aapt2Limits, CompileFlags, CompileResult, LinkResult};
+
+/// aapt2 output log written to each package's compiled directory
+pub const AAPT2_LOG_FILE: &str = "aapt2.log";
 
 /// Default Android package ID for standard applications
 /// This is used for dynamic resource loading via new Resources()
 pub const DEFAULT_PACKAGE_ID: &str = "0x7f";
 
+/// Number of flat files above which `Auto` mode stops passing files directly
+/// Windows has ~8191 char limit, Unix has ~131072, use conservative threshold
+const LINK_INPUT_THRESHOLD: usize = 100;
+
+/// How flat files are handed to `aapt2 link`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkInputMode {
+    /// Direct arguments for small builds, argument files above the threshold
+    #[default]
+    Auto,
+    /// Every flat file is passed on the command line
+    Direct,
+    /// Flat files are listed in `@argfile`s, one per overlay set
+    Argfile,
+    /// Flat files are packed into stored ZIPs, one per overlay set
+    Zip,
+}
+
 /// Utility for interacting with aapt2
 pub struct Aapt2 {
     aapt2_path: PathBuf,
+    link_input_mode: LinkInputMode,
+    compile_options: BTreeMap<String, CompileFlags>,
+    namespaced: bool,
+    limits: Aapt2Limits,
+    /// Log receiving the output of every invocation as it is produced
+    log_file: Option<Arc<Mutex<File>>>,
+    /// Told about every process and its output, for heartbeats and stall detection
+    activity: Option<Arc<BuildActivity>>,
+    /// Told about every compiled file, with the package it belongs to
+    observer: Option<(Arc<dyn BuildObserver>, String)>,
 }
 
 impl Aapt2 {
@@ -22,7 +65,305 @@ impl Aapt2 {
             None => Self::find_aapt2()?,
         };
 
-        Ok(Self { aapt2_path: path })
+        Ok(Self {
+            aapt2_path: path,
+            link_input_mode: LinkInputMode::default(),
+            compile_options: BTreeMap::new(),
+            namespaced: false,
+            limits: Aapt2Limits::default(),
+            log_file: None,
+            activity: None,
+            observer: None,
+        })
+    }
+
+    /// Set how flat files are passed to aapt2 link
+    pub fn with_link_input_mode(mut self, mode: LinkInputMode) -> Self {
+        self.link_input_mode = mode;
+        self
+    }
+
+    /// Set per-resource-type compile flags, keyed by resource type or "*"
+    pub fn with_compile_options(mut self, options: BTreeMap<String, CompileFlags>) -> Self {
+        self.compile_options = options;
+        self
+    }
+
+    /// Enable namespaced linking: no auto-added overlay resources and
+    /// static libraries keep their own packages
+    pub fn with_namespaced(mut self, namespaced: bool) -> Self {
+        self.namespaced = namespaced;
+        self
+    }
+
+    /// Kill aapt2 invocations that run too long or use too much memory
+    pub fn with_limits(mut self, limits: Aapt2Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Report the processes of every invocation and their output to `activity`
+    pub fn with_activity(mut self, activity: Arc<BuildActivity>) -> Self {
+        self.activity = Some(activity);
+        self
+    }
+
+    /// Report every file compiled for `package` to `observer`
+    #[allow(dead_code)]
+    pub fn with_observer(mut self, observer: Arc<dyn BuildObserver>, package: &str) -> Self {
+        self.observer = Some((observer, package.to_string()));
+        self
+    }
+
+    /// Stream the output of every invocation into `path`, replacing its previous content
+    pub fn set_log_file(&mut self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create aapt2 log {}", path.display()))?;
+        self.log_file = Some(Arc::new(Mutex::new(file)));
+        Ok(())
+    }
+
+    /// Run an aapt2 invocation on `subject` (the file or output it processes)
+    ///
+    /// A process exceeding the limits is killed; its output then reports a failure naming
+    /// the limit and `subject`, so callers handle it like any other aapt2 error.
+    fn run(&self, cmd: &mut Command, subject: &Path) -> std::io::Result<Output> {
+        self.log_invocation(cmd, subject);
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let label = process_label(subject);
+        let pid = child.id();
+        if let Some(activity) = &self.activity {
+            activity.process_started(pid, &label);
+        }
+        let stdout = self.stream_output(child.stdout.take(), pid, format!("{} stdout", label));
+        let stderr = self.stream_output(child.stderr.take(), pid, format!("{} stderr", label));
+
+        let unlimited = self.limits.timeout_secs.is_none() && self.limits.max_memory_mb.is_none();
+        let started = Instant::now();
+        let mut interval = Duration::from_millis(1);
+        let mut killed = None;
+        let status = loop {
+            if unlimited {
+                break child.wait()?;
+            }
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let Some(reason) = self.limits.exceeded(pid, started.elapsed()) {
+                child.kill().ok();
+                killed = Some(reason);
+                break child.wait()?;
+            }
+            std::thread::sleep(interval);
+            interval = (interval * 2).min(Duration::from_millis(50));
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        Ok(self.finish_run(pid, subject, killed, status, stdout, stderr))
+    }
+
+    /// Run an aapt2 invocation like [`run`](Self::run), without blocking the async runtime
+    ///
+    /// Used for links, which run for a long time while other builds share the runtime.
+    async fn run_async(&self, cmd: Command, subject: &Path) -> std::io::Result<Output> {
+        self.log_invocation(&cmd, subject);
+        let mut child = tokio::process::Command::from(cmd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let label = process_label(subject);
+        let pid = child.id().unwrap_or_default();
+        if let Some(activity) = &self.activity {
+            activity.process_started(pid, &label);
+        }
+        let stdout =
+            self.stream_output_async(child.stdout.take(), pid, format!("{} stdout", label));
+        let stderr =
+            self.stream_output_async(child.stderr.take(), pid, format!("{} stderr", label));
+
+        let unlimited = self.limits.timeout_secs.is_none() && self.limits.max_memory_mb.is_none();
+        let started = Instant::now();
+        let mut interval = tokio::time::interval(Duration::from_millis(50));
+        let mut killed = None;
+        let status = loop {
+            if unlimited {
+                break child.wait().await?;
+            }
+            tokio::select! {
+                status = child.wait() => break status?,
+                _ = interval.tick() => {
+                    if let Some(reason) = self.limits.exceeded(pid, started.elapsed()) {
+                        child.start_kill().ok();
+                        killed = Some(reason);
+                        break child.wait().await?;
+                    }
+                }
+            }
+        };
+
+        let stdout = stdout.await.unwrap_or_default();
+        let stderr = stderr.await.unwrap_or_default();
+        Ok(self.finish_run(pid, subject, killed, status, stdout, stderr))
+    }
+
+    /// Write the start of an invocation to the log file
+    fn log_invocation(&self, cmd: &Command, subject: &Path) {
+        let subcommand = cmd
+            .get_args()
+            .next()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(log) = &self.log_file
+            && let Ok(mut log) = log.lock()
+        {
+            writeln!(log, "> aapt2 {} {}", subcommand, subject.display()).ok();
+        }
+    }
+
+    /// Report the end of an invocation and add the reason it was killed to its stderr
+    fn finish_run(
+        &self,
+        pid: u32,
+        subject: &Path,
+        killed: Option<String>,
+        status: ExitStatus,
+        stdout: Vec<u8>,
+        mut stderr: Vec<u8>,
+    ) -> Output {
+        if let Some(activity) = &self.activity {
+            activity.process_finished(pid);
+        }
+        if let Some(reason) = killed {
+            let message = format!(
+                "aapt2 killed after {} while processing {}",
+                reason,
+                subject.display()
+            );
+            warn!("{}", message);
+            if let Some(log) = &self.log_file
+                && let Ok(mut log) = log.lock()
+            {
+                writeln!(log, "{}", message).ok();
+            }
+            stderr.extend_from_slice(format!("\n{}\n", message).as_bytes());
+        }
+        Output {
+            status,
+            stdout,
+            stderr,
+        }
+    }
+
+    /// Collect a child's output pipe in the background, logging each line as it arrives
+    /// Lines go to tracing at debug level and to the log file, prefixed with `label`, and
+    /// are reported as the latest output of process `pid`.
+    fn stream_output(
+        &self,
+        pipe: Option<impl Read + Send + 'static>,
+        pid: u32,
+        label: String,
+    ) -> std::thread::JoinHandle<Vec<u8>> {
+        let output_line = self.output_line(pid, label);
+        // Keep the package span so the output reaches the package log
+        let span = tracing::Span::current();
+        std::thread::spawn(move || {
+            let _entered = span.enter();
+            let mut output = Vec::new();
+            let Some(pipe) = pipe else {
+                return output;
+            };
+            let mut reader = BufReader::new(pipe);
+            let mut line = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                output_line(&line);
+                output.append(&mut line);
+            }
+            output
+        })
+    }
+
+    /// Collect a child's output pipe in a task, like [`stream_output`](Self::stream_output)
+    fn stream_output_async(
+        &self,
+        pipe: Option<impl AsyncRead + Unpin + Send + 'static>,
+        pid: u32,
+        label: String,
+    ) -> tokio::task::JoinHandle<Vec<u8>> {
+        let output_line = self.output_line(pid, label);
+        tokio::spawn(
+            async move {
+                let mut output = Vec::new();
+                let Some(pipe) = pipe else {
+                    return output;
+                };
+                let mut reader = tokio::io::BufReader::new(pipe);
+                let mut line = Vec::new();
+                while matches!(reader.read_until(b'\n', &mut line).await, Ok(n) if n > 0) {
+                    output_line(&line);
+                    output.append(&mut line);
+                }
+                output
+            }
+            .in_current_span(),
+        )
+    }
+
+    /// Handler logging one output line of process `pid`
+    fn output_line(&self, pid: u32, label: String) -> impl Fn(&[u8]) + Send + 'static {
+        let log_file = self.log_file.clone();
+        let activity = self.activity.clone();
+        move |line| {
+            let text = String::from_utf8_lossy(line);
+            let text = text.trim_end();
+            debug!("aapt2 {}: {}", label, text);
+            if let Some(log) = &log_file
+                && let Ok(mut log) = log.lock()
+            {
+                writeln!(log, "{}: {}", label, text).ok();
+            }
+            if let Some(activity) = &activity
+                && !text.is_empty()
+            {
+                activity.process_output(pid, text);
+            }
+        }
+    }
+
+    /// Resolve the extra compile arguments for a resource type
+    /// `None` resolves only the "*" defaults, used when compiling whole directories
+    fn compile_args_for(&self, resource_type: Option<&str>) -> Vec<&'static str> {
+        let defaults = self.compile_options.get("*");
+        let specific = resource_type.and_then(|t| self.compile_options.get(t));
+
+        // Type-specific settings override the "*" defaults flag by flag
+        let pick = |get: fn(&CompileFlags) -> Option<bool>| {
+            specific
+                .and_then(get)
+                .or_else(|| defaults.and_then(get))
+                .unwrap_or(false)
+        };
+
+        let mut args = Vec::new();
+        if pick(|f| f.no_crunch) {
+            args.push("--no-crunch");
+        }
+        if pick(|f| f.pseudo_localize) {
+            args.push("--pseudo-localize");
+        }
+        if pick(|f| f.legacy) {
+            args.push("--legacy");
+        }
+        args
     }
 
     /// Find aapt2 binary in the system
@@ -81,6 +422,66 @@ impl Aapt2 {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
+    /// Resource table of a built package, as printed by `aapt2 dump resources`
+    pub fn dump_resources(&self, package: &Path) -> Result<String> {
+        let output = Command::new(&self.aapt2_path)
+            .arg("dump")
+            .arg("resources")
+            .arg(package)
+            .output()
+            .context("Failed to execute aapt2")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "aapt2 dump resources failed for {}: {}",
+                package.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Tree of a compiled XML file in a built package, as printed by `aapt2 dump xmltree`
+    pub fn dump_xmltree(&self, package: &Path, file: &str) -> Result<String> {
+        let output = Command::new(&self.aapt2_path)
+            .arg("dump")
+            .arg("xmltree")
+            .arg("--file")
+            .arg(file)
+            .arg(package)
+            .output()
+            .context("Failed to execute aapt2")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "aapt2 dump xmltree failed for {} in {}: {}",
+                file,
+                package.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Convert a package between the binary and proto resource formats with `aapt2 convert`
+    pub fn convert(&self, input: &Path, output: &Path, output_format: &str) -> Result<()> {
+        let output_result = Command::new(&self.aapt2_path)
+            .arg("convert")
+            .arg("--output-format")
+            .arg(output_format)
+            .arg("-o")
+            .arg(output)
+            .arg(input)
+            .output()
+            .context("Failed to execute aapt2")?;
+        if !output_result.status.success() {
+            anyhow::bail!(
+                "aapt2 convert failed for {}: {}",
+                input.display(),
+                String::from_utf8_lossy(&output_result.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
     /// Get the aapt2 executable path
     pub fn into_path(self) -> PathBuf {
         self.aapt2_path
@@ -92,16 +493,17 @@ impl Aapt2 {
 
         debug!("Compiling resources from: {}", resource_dir.display());
 
-        let output = Command::new(&self.aapt2_path)
+        let mut command = Command::new(&self.aapt2_path);
+        command
             .arg("compile")
+            .args(self.compile_args_for(None))
             .arg("--dir")
             .arg(resource_dir)
             .arg("-o")
-            .arg(output_dir)
-            .output()
-            .with_context(|| {
-                format!(
-                    "Failed to execute aapt2 compile\n\
+            .arg(output_dir);
+        let output = self.run(&mut command, resource_dir).with_context(|| {
+            format!(
+                "Failed to execute aapt2 compile\n\
                      aapt2 path: {}\n\
                      Resource dir: {}\n\
                      Output dir: {}\n\
@@ -109,11 +511,11 @@ impl Aapt2 {
                      - aapt2 binary not found or not executable\n\
                      - Resource directory does not exist or is not readable\n\
                      - Insufficient permissions to write to output directory",
-                    self.aapt2_path.display(),
-                    resource_dir.display(),
-                    output_dir.display()
-                )
-            })?;
+                self.aapt2_path.display(),
+                resource_dir.display(),
+                output_dir.display()
+            )
+        })?;
 
         let stderr = String::from_utf8_lossy(&output.stderr);
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -148,8 +550,11 @@ impl Aapt2 {
             });
         }
 
-        // Collect all .flat files
-        let flat_files = Self::collect_flat_files(output_dir)?;
+        // Collect all .flat files, sharding large outputs like per-file compilation does
+        let mut flat_files = Self::collect_flat_files(output_dir)?;
+        if flat_files.len() > FLAT_SHARD_THRESHOLD {
+            flat_files = shard_flat_files(output_dir, flat_files)?;
+        }
 
         Ok(CompileResult {
             success: true,
@@ -158,38 +563,102 @@ impl Aapt2 {
         })
     }
 
+    /// Arguments of the `aapt2 compile` call for a single resource file
+    /// Flags are chosen by resource type, e.g. "drawable" for drawable-hdpi/
+    fn compile_file_args(&self, file: &Path, output_dir: &Path) -> Vec<OsString> {
+        let resource_type = file
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.split('-').next());
+        let mut args: Vec<OsString> = vec!["compile".into()];
+        args.extend(
+            self.compile_args_for(resource_type)
+                .into_iter()
+                .map(Into::into),
+        );
+        args.extend(["-o".into(), output_dir.into(), file.into()]);
+        args
+    }
+
+    /// Full command line compiling a single resource file, starting with the aapt2 binary
+    pub fn compile_command_line(&self, file: &Path, output_dir: &Path) -> Vec<String> {
+        std::iter::once(self.aapt2_path.as_os_str().to_os_string())
+            .chain(self.compile_file_args(file, output_dir))
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Report a successfully compiled resource file
+    fn file_compiled(&self, file: &Path) {
+        events::emit(BuildEvent::FileCompiled {
+            file: file.to_path_buf(),
+        });
+        if let Some((observer, package)) = &self.observer {
+            observer.on_file_compiled(package, file);
+        }
+    }
+
     /// Compile individual resource files in parallel
     pub fn compile_files_parallel(
         &self,
         resource_files: &[PathBuf],
         output_dir: &Path,
+    ) -> Result<CompileResult> {
+        self.compile_files(resource_files, output_dir, false)
+    }
+
+    /// Compile individual resource files in parallel, spreading the flat files over
+    /// [`flat_shard`] subdirectories of `output_dir`
+    ///
+    /// Keeps directory listings short for resource sets with tens of thousands of files.
+    pub fn compile_files_sharded(
+        &self,
+        resource_files: &[PathBuf],
+        output_dir: &Path,
+    ) -> Result<CompileResult> {
+        self.compile_files(resource_files, output_dir, true)
+    }
+
+    fn compile_files(
+        &self,
+        resource_files: &[PathBuf],
+        output_dir: &Path,
+        sharded: bool,
     ) -> Result<CompileResult> {
         use rayon::prelude::*;
 
         std::fs::create_dir_all(output_dir)?;
 
+        let span = tracing::Span::current();
         let results: Vec<_> = resource_files
             .par_iter()
             .map(|file| {
+                let _entered = span.enter();
+                let output_dir = &match flat_file_name(file) {
+                    Some(flat_name) if sharded => {
+                        let shard_dir = output_dir.join(flat_shard(&flat_name));
+                        std::fs::create_dir_all(&shard_dir)?;
+                        shard_dir
+                    }
+                    _ => output_dir.to_path_buf(),
+                };
                 // For parallel compilation, we can't use before/after file lists
                 // because other threads are also writing files. Instead, we predict
                 // the flat file name based on the resource file path.
-                let output = Command::new(&self.aapt2_path)
-                    .arg("compile")
-                    .arg("-o")
-                    .arg(output_dir)
-                    .arg(file)
-                    .output()
-                    .with_context(|| {
-                        format!(
-                            "Failed to execute aapt2 compile for {}\n\
+                // Flags are chosen by resource type, e.g. "drawable" for drawable-hdpi/
+                let mut command = Command::new(&self.aapt2_path);
+                command.args(self.compile_file_args(file, output_dir));
+                let output = self.run(&mut command, file).with_context(|| {
+                    format!(
+                        "Failed to execute aapt2 compile for {}\n\
                              aapt2: {}\n\
                              Output: {}",
-                            file.display(),
-                            self.aapt2_path.display(),
-                            output_dir.display()
-                        )
-                    })?;
+                        file.display(),
+                        self.aapt2_path.display(),
+                        output_dir.display()
+                    )
+                })?;
 
                 if !output.status.success() {
                     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -203,36 +672,9 @@ impl Aapt2 {
                     );
                 }
 
-                // Predict the flat file name based on the resource file path
-                // aapt2 creates names like:
-                //   - values_strings.arsc.flat for res/values/strings.xml
-                //   - layout_activity_main.xml.flat for res/layout/activity_main.xml
-                if let Some(parent) = file.parent()
-                    && let Some(parent_name) = parent.file_name().and_then(|n| n.to_str())
-                    && let Some(file_name) = file.file_name().and_then(|n| n.to_str())
-                {
-                    // Try different naming patterns based on resource type
-                    let possible_names = if parent_name.starts_with("values") {
-                        // For values resources: values_strings.arsc.flat
-                        vec![format!(
-                            "{}_{}.arsc.flat",
-                            parent_name,
-                            file.file_stem().and_then(|s| s.to_str()).unwrap_or("")
-                        )]
-                    } else {
-                        // For other resources (layout, drawable, etc.): layout_activity_main.xml.flat
-                        vec![format!("{}_{}.flat", parent_name, file_name)]
-                    };
-
-                    for flat_name in possible_names {
-                        let flat_path = output_dir.join(&flat_name);
-                        if flat_path.exists() {
-                            return Ok(flat_path);
-                        }
-                    }
-                }
+                self.file_compiled(file);
 
-                anyhow::bail!("Could not find compiled flat file for {}", file.display())
+                compiled_flat_file(file, output_dir)
             })
             .collect();
 
@@ -253,18 +695,106 @@ impl Aapt2 {
         })
     }
 
+    /// Compile resource files with one aapt2 invocation per batch of up to `batch_size`
+    /// files of the same resource directory, running batches in parallel
+    ///
+    /// Fewer processes are spawned than with `compile_files_parallel`, but a failing file
+    /// fails its whole batch.
+    pub fn compile_files_batched(
+        &self,
+        resource_files: &[PathBuf],
+        output_dir: &Path,
+        batch_size: usize,
+    ) -> Result<CompileResult> {
+        use rayon::prelude::*;
+
+        std::fs::create_dir_all(output_dir)?;
+
+        // Files of one directory share a resource type and therefore their compile flags
+        let mut by_dir: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+        for file in resource_files {
+            by_dir
+                .entry(file.parent().unwrap_or(Path::new("")))
+                .or_default()
+                .push(file.clone());
+        }
+        let batches: Vec<(&Path, &[PathBuf])> = by_dir
+            .iter()
+            .flat_map(|(dir, files)| files.chunks(batch_size.max(1)).map(move |b| (*dir, b)))
+            .collect();
+
+        let span = tracing::Span::current();
+        let results: Vec<Result<Vec<PathBuf>>> = batches
+            .par_iter()
+            .map(|(dir, batch)| {
+                let _entered = span.enter();
+                let resource_type = dir
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.split('-').next());
+                let mut command = Command::new(&self.aapt2_path);
+                command
+                    .arg("compile")
+                    .args(self.compile_args_for(resource_type))
+                    .arg("-o")
+                    .arg(output_dir)
+                    .args(batch.iter());
+                let output = self.run(&mut command, dir).with_context(|| {
+                    format!(
+                        "Failed to execute aapt2 compile for {} files in {}",
+                        batch.len(),
+                        dir.display()
+                    )
+                })?;
+                if !output.status.success() {
+                    anyhow::bail!(
+                        "Failed to compile {} files in {}\n\
+                         Stderr: {}",
+                        batch.len(),
+                        dir.display(),
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                }
+                batch
+                    .iter()
+                    .map(|file| {
+                        self.file_compiled(file);
+                        compiled_flat_file(file, output_dir)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut flat_files = Vec::new();
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(flats) => flat_files.extend(flats),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        Ok(CompileResult {
+            success: errors.is_empty(),
+            flat_files,
+            errors,
+        })
+    }
+
     /// Compile a single resource file
     #[allow(dead_code)]
     fn compile_single_file(&self, resource_file: &Path, output_dir: &Path) -> Result<PathBuf> {
         // Get existing flat files before compilation
         let before_files = Self::collect_flat_files(output_dir)?;
 
-        let output = Command::new(&self.aapt2_path)
+        let mut command = Command::new(&self.aapt2_path);
+        command
             .arg("compile")
             .arg("-o")
             .arg(output_dir)
-            .arg(resource_file)
-            .output()
+            .arg(resource_file);
+        let output = self
+            .run(&mut command, resource_file)
             .context("Failed to execute aapt2 compile")?;
 
         if !output.status.success() {
@@ -321,10 +851,76 @@ impl Aapt2 {
         )
     }
 
+    /// Link flat files into an aapt2 static library
+    ///
+    /// Shared dependencies linked once this way can be passed to every skin's link as a
+    /// single input instead of re-linking all of their flat files each time.
+    pub async fn link_static_lib(
+        &self,
+        flat_files: &[PathBuf],
+        manifest_path: &Path,
+        android_jar: &Path,
+        output_lib: &Path,
+    ) -> Result<LinkResult> {
+        let mut cmd = Command::new(&self.aapt2_path);
+        cmd.arg("link")
+            .arg("--static-lib")
+            .arg("--manifest")
+            .arg(manifest_path)
+            .arg("-I")
+            .arg(android_jar)
+            .arg("-o")
+            .arg(output_lib)
+            .arg("--auto-add-overlay")
+            .arg("--no-version-vectors")
+            .arg("--keep-raw-values");
+
+        if resolve_link_input_mode(LinkInputMode::Auto, flat_files, &[]) == LinkInputMode::Direct {
+            cmd.args(flat_files);
+        } else {
+            let args_file = output_lib.with_extension("args.txt");
+            write_argfile(&args_file, flat_files)?;
+            cmd.arg(argfile_arg(&args_file));
+        }
+
+        debug!("Executing aapt2 static library link: {:?}", cmd);
+
+        let output = self.run_async(cmd, output_lib).await.with_context(|| {
+            format!(
+                "Failed to execute aapt2 link for static library {}",
+                output_lib.display()
+            )
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Ok(LinkResult {
+                success: false,
+                apk_path: None,
+                errors: vec![format!(
+                    "aapt2 static library link failed for {}:\n{}",
+                    output_lib.display(),
+                    stderr
+                )],
+            });
+        }
+
+        Ok(LinkResult {
+            success: true,
+            apk_path: Some(output_lib.to_path_buf()),
+            errors: vec![],
+        })
+    }
+
     /// Link compiled resources into an APK with overlay support
     /// Base flat files are linked first, then overlay flat files are applied with -R flag
     /// This implements Android's resource priority strategy where later resources override earlier ones
-    pub fn link_with_overlays(
+    ///
+    /// Ordering guarantee: every input mode presents flat files to aapt2 in the same order,
+    /// base files first, then each overlay set in turn, files within a set in slice order.
+    /// When two files define the same resource, the later one wins regardless of whether
+    /// they were passed directly, through argument files, or inside ZIPs.
+    pub async fn link_with_overlays(
         &self,
         base_flat_files: &[PathBuf],
         overlay_flat_files: &[Vec<PathBuf>], // Vec of overlay sets, ordered by priority
@@ -361,11 +957,12 @@ impl Aapt2 {
             compiled_dir,
             assets_dir,
         )
+        .await
     }
 
     /// Link using command line arguments
     /// Uses ZIP file for flat files when count exceeds threshold to avoid command line length limits
-    fn link_with_command_line(
+    async fn link_with_command_line(
         &self,
         base_flat_files: &[PathBuf],
         overlay_flat_files: &[Vec<PathBuf>],
@@ -385,52 +982,72 @@ impl Aapt2 {
         let total_flat_files =
             base_flat_files.len() + overlay_flat_files.iter().map(|v| v.len()).sum::<usize>();
 
-        // Threshold for using ZIP (to avoid command line length issues)
-        // Windows has ~8191 char limit, Unix has ~131072, use conservative threshold
-        const USE_ZIP_THRESHOLD: usize = 100;
-
-        let use_zip = total_flat_files > USE_ZIP_THRESHOLD;
+        let mode =
+            resolve_link_input_mode(self.link_input_mode, base_flat_files, overlay_flat_files);
+        debug!(
+            "Linking {} flat files using {:?} input mode",
+            total_flat_files, mode
+        );
 
-        if use_zip {
-            debug!(
-                "Using ZIP file for {} flat files (exceeds threshold of {})",
-                total_flat_files, USE_ZIP_THRESHOLD
-            );
-            self.link_with_zip(
-                base_flat_files,
-                overlay_flat_files,
-                manifest_path,
-                android_jar,
-                output_apk,
-                package_name,
-                version_code,
-                version_name,
-                stable_ids_file,
-                package_id,
-                min_sdk_version,
-                compiled_dir,
-                assets_dir,
-            )
-        } else {
-            self.link_with_direct_args(
-                base_flat_files,
-                overlay_flat_files,
-                manifest_path,
-                android_jar,
-                output_apk,
-                package_name,
-                version_code,
-                version_name,
-                stable_ids_file,
-                package_id,
-                min_sdk_version,
-                assets_dir,
-            )
+        match mode {
+            LinkInputMode::Zip => {
+                self.link_with_zip(
+                    base_flat_files,
+                    overlay_flat_files,
+                    manifest_path,
+                    android_jar,
+                    output_apk,
+                    package_name,
+                    version_code,
+                    version_name,
+                    stable_ids_file,
+                    package_id,
+                    min_sdk_version,
+                    compiled_dir,
+                    assets_dir,
+                )
+                .await
+            }
+            LinkInputMode::Argfile => {
+                self.link_with_argfile(
+                    base_flat_files,
+                    overlay_flat_files,
+                    manifest_path,
+                    android_jar,
+                    output_apk,
+                    package_name,
+                    version_code,
+                    version_name,
+                    stable_ids_file,
+                    package_id,
+                    min_sdk_version,
+                    compiled_dir,
+                    assets_dir,
+                )
+                .await
+            }
+            LinkInputMode::Auto | LinkInputMode::Direct => {
+                self.link_with_direct_args(
+                    base_flat_files,
+                    overlay_flat_files,
+                    manifest_path,
+                    android_jar,
+                    output_apk,
+                    package_name,
+                    version_code,
+                    version_name,
+                    stable_ids_file,
+                    package_id,
+                    min_sdk_version,
+                    assets_dir,
+                )
+                .await
+            }
         }
     }
 
     /// Link using ZIP file for flat files
-    fn link_with_zip(
+    async fn link_with_zip(
         &self,
         base_flat_files: &[PathBuf],
         overlay_flat_files: &[Vec<PathBuf>],
@@ -446,132 +1063,23 @@ impl Aapt2 {
         compiled_dir: Option<&Path>,
         assets_dir: Option<&Path>,
     ) -> Result<LinkResult> {
-        use std::fs::File;
-        use zip::CompressionMethod;
-        use zip::write::{FileOptions, ZipWriter};
-
         // Create temporary directory for ZIP files
         // Always use package-specific directory to ensure isolation in multi-task builds
-        let temp_dir = if let Some(compiled) = compiled_dir {
-            // Primary: Use compiled directory (package-specific)
-            compiled.join(".temp_zip")
-        } else if let Some(pkg_name) = package_name {
-            // Fallback: Use package name in output directory
-            output_apk
-                .parent()
-                .unwrap()
-                .join(pkg_name.replace('.', "_"))
-                .join(".temp_zip")
-        } else {
-            // Last resort: Use unique directory based on output APK name
-            let apk_stem = output_apk
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown");
-            output_apk
-                .parent()
-                .unwrap()
-                .join(format!(".temp_zip_{}", apk_stem))
-        };
+        let temp_dir = link_temp_dir(output_apk, package_name, compiled_dir, ".temp_zip");
         std::fs::create_dir_all(&temp_dir)?;
 
-        // Helper function to check if ZIP needs recreation
-        let needs_zip_recreation = |zip_path: &Path, flat_files: &[PathBuf]| -> bool {
-            if !zip_path.exists() {
-                return true;
-            }
-
-            // Check if any flat file is newer than the ZIP
-            let zip_modified = match std::fs::metadata(zip_path).and_then(|m| m.modified()) {
-                Ok(time) => time,
-                Err(_) => return true,
-            };
-
-            for flat_file in flat_files {
-                if let Ok(metadata) = std::fs::metadata(flat_file)
-                    && let Ok(modified) = metadata.modified()
-                    && modified > zip_modified
-                {
-                    return true;
-                }
-            }
-
-            false
-        };
-
-        // Create ZIP file for base flat files (with caching)
+        // Create ZIP file for base flat files (reused while the flat set is unchanged)
         // "_s" suffix denotes stored (no-compression) format; old deflate-compressed
         // "base_flats.zip" files will never match and so are never reused.
+        // Static libraries are already archives and are passed alongside the ZIP
+        let (static_libs, base_flats): (Vec<PathBuf>, Vec<PathBuf>) = base_flat_files
+            .iter()
+            .cloned()
+            .partition(|p| is_static_lib(p));
         let base_zip = temp_dir.join("base_flats_s.zip");
+        write_flat_zip(&base_zip, &base_flats, compiled_dir)?;
 
-        if needs_zip_recreation(&base_zip, base_flat_files) {
-            debug!("Creating base ZIP file: {}", base_zip.display());
-            let base_file = File::create(&base_zip)?;
-            let mut base_zip_writer = ZipWriter::new(base_file);
-
-            // Track used filenames to detect duplicates
-            let mut used_names = std::collections::HashSet::new();
-
-            for flat_file in base_flat_files {
-                // Try to create a unique name for this file
-                // Strategy 1: Use relative path from compiled_dir if possible
-                let mut file_name = if let Some(compiled) = compiled_dir {
-                    flat_file
-                        .strip_prefix(compiled)
-                        .ok()
-                        .and_then(|p| p.to_str())
-                        .map(|s| s.to_string())
-                } else {
-                    None
-                };
-
-                // Strategy 2: If that didn't work, try using parent directory + filename
-                if file_name.is_none()
-                    && let (Some(parent), Some(name)) = (flat_file.parent(), flat_file.file_name())
-                    && let (Some(parent_name), Some(file_name_str)) =
-                        (parent.file_name(), name.to_str())
-                    && let Some(parent_str) = parent_name.to_str()
-                {
-                    file_name = Some(format!("{}/{}", parent_str, file_name_str));
-                }
-
-                // Strategy 3: Fallback to just filename
-                let mut final_name = file_name.unwrap_or_else(|| {
-                    flat_file
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown.flat")
-                        .to_string()
-                });
-
-                // Ensure uniqueness by appending counter if needed
-                let base_name = final_name.clone();
-                let mut counter = 1;
-                while used_names.contains(&final_name) {
-                    // Extract extension if present
-                    if let Some(pos) = base_name.rfind('.') {
-                        let (name_part, ext_part) = base_name.split_at(pos);
-                        final_name = format!("{}_{}{}", name_part, counter, ext_part);
-                    } else {
-                        final_name = format!("{}_{}", base_name, counter);
-                    }
-                    counter += 1;
-                }
-                used_names.insert(final_name.clone());
-
-                base_zip_writer.start_file::<_, ()>(
-                    &final_name,
-                    FileOptions::default().compression_method(CompressionMethod::Stored),
-                )?;
-                let content = std::fs::read(flat_file)?;
-                std::io::Write::write_all(&mut base_zip_writer, &content)?;
-            }
-            base_zip_writer.finish()?;
-        } else {
-            debug!("Using cached base ZIP file: {}", base_zip.display());
-        }
-
-        // Create ZIP files for overlay flat files (with caching)
+        // Create ZIP files for overlay flat files (reused while each set is unchanged)
         let mut overlay_zips = Vec::new();
         for (idx, overlay_set) in overlay_flat_files.iter().enumerate() {
             if overlay_set.is_empty() {
@@ -581,132 +1089,28 @@ impl Aapt2 {
             // "_s" suffix denotes stored (no-compression) format; old deflate-compressed
             // "overlay_N.zip" files will never match and so are never reused.
             let overlay_zip = temp_dir.join(format!("overlay_{}_s.zip", idx));
+            write_flat_zip(&overlay_zip, overlay_set, compiled_dir)?;
 
-            if needs_zip_recreation(&overlay_zip, overlay_set) {
-                debug!(
-                    "Creating overlay {} ZIP file: {}",
-                    idx,
-                    overlay_zip.display()
-                );
-                let overlay_file = File::create(&overlay_zip)?;
-                let mut overlay_zip_writer = ZipWriter::new(overlay_file);
-
-                // Track used filenames to detect duplicates
-                let mut used_names = std::collections::HashSet::new();
-
-                for flat_file in overlay_set {
-                    // Try to create a unique name for this file
-                    // Strategy 1: Use relative path from compiled_dir if possible
-                    let mut file_name = if let Some(compiled) = compiled_dir {
-                        flat_file
-                            .strip_prefix(compiled)
-                            .ok()
-                            .and_then(|p| p.to_str())
-                            .map(|s| s.to_string())
-                    } else {
-                        None
-                    };
-
-                    // Strategy 2: If that didn't work, try using parent directory + filename
-                    if file_name.is_none()
-                        && let (Some(parent), Some(name)) =
-                            (flat_file.parent(), flat_file.file_name())
-                        && let (Some(parent_name), Some(file_name_str)) =
-                            (parent.file_name(), name.to_str())
-                        && let Some(parent_str) = parent_name.to_str()
-                    {
-                        file_name = Some(format!("{}/{}", parent_str, file_name_str));
-                    }
-
-                    // Strategy 3: Fallback to just filename
-                    let mut final_name = file_name.unwrap_or_else(|| {
-                        flat_file
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("unknown.flat")
-                            .to_string()
-                    });
-
-                    // Ensure uniqueness by appending counter if needed
-                    let base_name = final_name.clone();
-                    let mut counter = 1;
-                    while used_names.contains(&final_name) {
-                        // Extract extension if present
-                        if let Some(pos) = base_name.rfind('.') {
-                            let (name_part, ext_part) = base_name.split_at(pos);
-                            final_name = format!("{}_{}{}", name_part, counter, ext_part);
-                        } else {
-                            final_name = format!("{}_{}", base_name, counter);
-                        }
-                        counter += 1;
-                    }
-                    used_names.insert(final_name.clone());
-
-                    overlay_zip_writer.start_file::<_, ()>(
-                        &final_name,
-                        FileOptions::default().compression_method(CompressionMethod::Stored),
-                    )?;
-                    let content = std::fs::read(flat_file)?;
-                    std::io::Write::write_all(&mut overlay_zip_writer, &content)?;
-                }
-                overlay_zip_writer.finish()?;
-            } else {
-                debug!(
-                    "Using cached overlay {} ZIP file: {}",
-                    idx,
-                    overlay_zip.display()
-                );
-            }
-
-            overlay_zips.push(overlay_zip);
-        }
+            overlay_zips.push(overlay_zip);
+        }
 
         // Build command with ZIP files
-        let mut cmd = Command::new(&self.aapt2_path);
-        cmd.arg("link")
-            .arg("--manifest")
-            .arg(manifest_path)
-            .arg("-I")
-            .arg(android_jar)
-            .arg("-o")
-            .arg(output_apk)
-            .arg("--auto-add-overlay")
-            .arg("--no-version-vectors")
-            .arg("--keep-raw-values")
-            .arg("--allow-reserved-package-id")
-            .arg("--no-resource-removal");
-
-        if let Some(pkg) = package_name {
-            cmd.arg("--rename-manifest-package").arg(pkg);
-            cmd.arg("--rename-resources-package").arg(pkg);
-        }
-
-        if let Some(code) = version_code {
-            cmd.arg("--version-code").arg(code.to_string());
-        }
-
-        if let Some(name) = version_name {
-            cmd.arg("--version-name").arg(name);
-        }
-
-        if let Some(min_sdk) = min_sdk_version {
-            cmd.arg("--min-sdk-version").arg(min_sdk.to_string());
-        }
-
-        if let Some(stable_ids) = stable_ids_file {
-            cmd.arg("--stable-ids").arg(stable_ids);
-            cmd.arg("--emit-ids").arg(stable_ids);
-        }
-
-        let pkg_id = package_id.unwrap_or(DEFAULT_PACKAGE_ID);
-        cmd.arg("--package-id").arg(pkg_id);
-
-        // Add assets directory if configured
-        if let Some(assets) = assets_dir {
-            cmd.arg("-A").arg(assets);
-        }
+        let mut cmd = self.base_link_command(
+            base_flat_files,
+            manifest_path,
+            android_jar,
+            output_apk,
+            package_name,
+            version_code,
+            version_name,
+            stable_ids_file,
+            package_id,
+            min_sdk_version,
+            assets_dir,
+        );
 
-        // Add base ZIP file
+        // Add static libraries and the base ZIP file
+        cmd.args(&static_libs);
         cmd.arg(&base_zip);
 
         // Add overlay ZIP files with -R flag
@@ -716,7 +1120,7 @@ impl Aapt2 {
 
         debug!("Executing aapt2 link with ZIP files: {:?}", cmd);
 
-        let output = cmd.output().with_context(|| {
+        let output = self.run_async(cmd, output_apk).await.with_context(|| {
             format!(
                 "Failed to execute aapt2 link with ZIP files\n\
                  aapt2 path: {}\n\
@@ -750,8 +1154,10 @@ impl Aapt2 {
         )
     }
 
-    /// Link using direct command line arguments (original method)
-    fn link_with_direct_args(
+    /// Link using `@argfile`s for flat files
+    /// Each overlay set gets its own argument file passed with `-R`, so the per-file
+    /// overlay order is exactly the same as with direct arguments and no flat file is copied
+    async fn link_with_argfile(
         &self,
         base_flat_files: &[PathBuf],
         overlay_flat_files: &[Vec<PathBuf>],
@@ -764,8 +1170,89 @@ impl Aapt2 {
         stable_ids_file: Option<&Path>,
         package_id: Option<&str>,
         min_sdk_version: Option<u32>,
+        compiled_dir: Option<&Path>,
         assets_dir: Option<&Path>,
     ) -> Result<LinkResult> {
+        let args_dir = link_temp_dir(output_apk, package_name, compiled_dir, ".temp_args");
+        std::fs::create_dir_all(&args_dir)?;
+
+        let mut cmd = self.base_link_command(
+            base_flat_files,
+            manifest_path,
+            android_jar,
+            output_apk,
+            package_name,
+            version_code,
+            version_name,
+            stable_ids_file,
+            package_id,
+            min_sdk_version,
+            assets_dir,
+        );
+
+        let base_args = args_dir.join("base_flats.txt");
+        write_argfile(&base_args, base_flat_files)?;
+        cmd.arg(argfile_arg(&base_args));
+
+        // One argument file per overlay set, in priority order
+        for (idx, overlay_set) in overlay_flat_files.iter().enumerate() {
+            if overlay_set.is_empty() {
+                continue;
+            }
+            let overlay_args = args_dir.join(format!("overlay_{}.txt", idx));
+            write_argfile(&overlay_args, overlay_set)?;
+            cmd.arg("-R").arg(argfile_arg(&overlay_args));
+        }
+
+        debug!("Executing aapt2 link with argument files: {:?}", cmd);
+
+        let output = self.run_async(cmd, output_apk).await.with_context(|| {
+            format!(
+                "Failed to execute aapt2 link with argument files\n\
+                 aapt2 path: {}\n\
+                 Manifest: {}\n\
+                 Android JAR: {}\n\
+                 Output: {}",
+                self.aapt2_path.display(),
+                manifest_path.display(),
+                android_jar.display(),
+                output_apk.display()
+            )
+        })?;
+
+        self.process_link_output(
+            output,
+            manifest_path,
+            android_jar,
+            output_apk,
+            package_name,
+            version_code,
+            version_name,
+            stable_ids_file,
+            package_id,
+            base_flat_files,
+            overlay_flat_files,
+            min_sdk_version,
+            assets_dir,
+        )
+    }
+
+    /// Build an aapt2 link command with every flag shared by all input modes
+    /// Flat file inputs are added by the caller
+    fn base_link_command(
+        &self,
+        base_inputs: &[PathBuf],
+        manifest_path: &Path,
+        android_jar: &Path,
+        output_apk: &Path,
+        package_name: Option<&str>,
+        version_code: Option<u32>,
+        version_name: Option<&str>,
+        stable_ids_file: Option<&Path>,
+        package_id: Option<&str>,
+        min_sdk_version: Option<u32>,
+        assets_dir: Option<&Path>,
+    ) -> Command {
         let mut cmd = Command::new(&self.aapt2_path);
         cmd.arg("link")
             .arg("--manifest")
@@ -774,7 +1261,6 @@ impl Aapt2 {
             .arg(android_jar)
             .arg("-o")
             .arg(output_apk)
-            .arg("--auto-add-overlay")
             .arg("--no-version-vectors")
             // Keep resource files in the APK (not just resources.arsc)
             .arg("--keep-raw-values")
@@ -782,6 +1268,11 @@ impl Aapt2 {
             .arg("--allow-reserved-package-id")
             .arg("--no-resource-removal");
 
+        // Namespaced packages only let overlays override resources declared in base inputs
+        if !self.namespaced {
+            cmd.arg("--auto-add-overlay");
+        }
+
         if let Some(pkg) = package_name {
             cmd.arg("--rename-manifest-package").arg(pkg);
             cmd.arg("--rename-resources-package").arg(pkg);
@@ -800,7 +1291,10 @@ impl Aapt2 {
         }
 
         if let Some(stable_ids) = stable_ids_file {
-            cmd.arg("--stable-ids").arg(stable_ids);
+            // The first link has nothing to read yet and only emits the IDs
+            if stable_ids.exists() {
+                cmd.arg("--stable-ids").arg(stable_ids);
+            }
             cmd.arg("--emit-ids").arg(stable_ids);
         }
 
@@ -815,6 +1309,44 @@ impl Aapt2 {
             cmd.arg("-A").arg(assets);
         }
 
+        // Merge static library resources into the skin's own package
+        if !self.namespaced && base_inputs.iter().any(|p| is_static_lib(p)) {
+            cmd.arg("--no-static-lib-packages");
+        }
+
+        cmd
+    }
+
+    /// Link using direct command line arguments (original method)
+    async fn link_with_direct_args(
+        &self,
+        base_flat_files: &[PathBuf],
+        overlay_flat_files: &[Vec<PathBuf>],
+        manifest_path: &Path,
+        android_jar: &Path,
+        output_apk: &Path,
+        package_name: Option<&str>,
+        version_code: Option<u32>,
+        version_name: Option<&str>,
+        stable_ids_file: Option<&Path>,
+        package_id: Option<&str>,
+        min_sdk_version: Option<u32>,
+        assets_dir: Option<&Path>,
+    ) -> Result<LinkResult> {
+        let mut cmd = self.base_link_command(
+            base_flat_files,
+            manifest_path,
+            android_jar,
+            output_apk,
+            package_name,
+            version_code,
+            version_name,
+            stable_ids_file,
+            package_id,
+            min_sdk_version,
+            assets_dir,
+        );
+
         // Add base flat files (normal arguments)
         for flat_file in base_flat_files {
             cmd.arg(flat_file);
@@ -831,7 +1363,7 @@ impl Aapt2 {
         // Debug: print the full command for troubleshooting
         debug!("Executing aapt2 link command: {:?}", cmd);
 
-        let output = cmd.output().with_context(|| {
+        let output = self.run_async(cmd, output_apk).await.with_context(|| {
             format!(
                 "Failed to execute aapt2 link\n\
                  aapt2 path: {}\n\
@@ -983,8 +1515,8 @@ impl Aapt2 {
         })
     }
 
-    /// Collect all .flat files from a directory
-    fn collect_flat_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    /// Collect all .flat files from a directory and its shard subdirectories
+    pub fn collect_flat_files(dir: &Path) -> Result<Vec<PathBuf>> {
         let mut flat_files = Vec::new();
 
         if !dir.exists() {
@@ -997,9 +1529,812 @@ impl Aapt2 {
 
             if path.extension().and_then(|s| s.to_str()) == Some("flat") {
                 flat_files.push(path);
+            } else if entry.file_type()?.is_dir() && is_shard_name(&entry.file_name()) {
+                for shard_entry in std::fs::read_dir(&path)? {
+                    let shard_path = shard_entry?.path();
+                    if shard_path.extension().and_then(|s| s.to_str()) == Some("flat") {
+                        flat_files.push(shard_path);
+                    }
+                }
             }
         }
 
         Ok(flat_files)
     }
 }
+
+/// Number of resource files above which their flat files are spread over shard subdirectories
+pub const FLAT_SHARD_THRESHOLD: usize = 1000;
+
+/// Shard subdirectory holding the flat file named `flat_name`
+///
+/// Two hex digits of a hash of the name, so a resource lands in the same shard on every
+/// build and machine, whether it was compiled on its own or with `--dir`.
+pub fn flat_shard(flat_name: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:02x}", Sha256::digest(flat_name.as_bytes())[0])
+}
+
+/// Whether a directory name is one of the shard names produced by [`flat_shard`]
+fn is_shard_name(name: &std::ffi::OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|n| n.len() == 2 && n.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+}
+
+/// Move the flat files directly in `dir` into shard subdirectories
+/// Used after `aapt2 compile --dir`, which always writes one flat directory.
+fn shard_flat_files(dir: &Path, flat_files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    flat_files
+        .into_iter()
+        .map(|flat_file| {
+            if flat_file.parent() != Some(dir) {
+                return Ok(flat_file);
+            }
+            let name = flat_file.file_name().unwrap_or_default().to_string_lossy();
+            let shard_dir = dir.join(flat_shard(&name));
+            std::fs::create_dir_all(&shard_dir)?;
+            let sharded = shard_dir.join(name.as_ref());
+            std::fs::rename(&flat_file, &sharded)
+                .with_context(|| format!("Failed to move {}", flat_file.display()))?;
+            Ok(sharded)
+        })
+        .collect()
+}
+
+/// Name of the flat file aapt2 compiles the resource `file` to
+///
+/// aapt2 creates names like:
+///   - values_strings.arsc.flat for res/values/strings.xml
+///   - layout_activity_main.xml.flat for res/layout/activity_main.xml
+fn flat_file_name(file: &Path) -> Option<String> {
+    let parent_name = file.parent()?.file_name()?.to_str()?;
+    let file_name = file.file_name()?.to_str()?;
+    Some(if parent_name.starts_with("values") {
+        format!(
+            "{}_{}.arsc.flat",
+            parent_name,
+            file.file_stem().and_then(|s| s.to_str()).unwrap_or("")
+        )
+    } else {
+        format!("{}_{}.flat", parent_name, file_name)
+    })
+}
+
+/// Path the flat file of the resource `file` gets in `output_dir`, within its shard
+/// subdirectory when the output is sharded
+pub fn expected_flat_file(file: &Path, output_dir: &Path, sharded: bool) -> Option<PathBuf> {
+    let flat_name = flat_file_name(file)?;
+    Some(if sharded {
+        output_dir.join(flat_shard(&flat_name)).join(flat_name)
+    } else {
+        output_dir.join(flat_name)
+    })
+}
+
+/// Flat file aapt2 wrote to `output_dir` for the resource `file`
+fn compiled_flat_file(file: &Path, output_dir: &Path) -> Result<PathBuf> {
+    if let Some(flat_name) = flat_file_name(file) {
+        let flat_path = output_dir.join(flat_name);
+        if flat_path.exists() {
+            return Ok(flat_path);
+        }
+    }
+
+    anyhow::bail!("Could not find compiled flat file for {}", file.display())
+}
+
+/// Whether a link input is an aapt2 static library rather than a flat file
+pub fn is_static_lib(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "apk")
+}
+
+/// Decide which input mode to use for a link
+///
+/// aapt2 splits argument files on whitespace, so paths containing whitespace cannot be
+/// listed in an `@argfile`; those links fall back to ZIP inputs.
+fn resolve_link_input_mode(
+    requested: LinkInputMode,
+    base_flat_files: &[PathBuf],
+    overlay_flat_files: &[Vec<PathBuf>],
+) -> LinkInputMode {
+    let total_flat_files =
+        base_flat_files.len() + overlay_flat_files.iter().map(|v| v.len()).sum::<usize>();
+
+    let mode = match requested {
+        LinkInputMode::Auto if total_flat_files > LINK_INPUT_THRESHOLD => LinkInputMode::Argfile,
+        LinkInputMode::Auto => LinkInputMode::Direct,
+        other => other,
+    };
+
+    if mode == LinkInputMode::Argfile
+        && base_flat_files
+            .iter()
+            .chain(overlay_flat_files.iter().flatten())
+            .any(|p| p.to_string_lossy().contains(char::is_whitespace))
+    {
+        warn!(
+            "Flat file paths contain whitespace, using ZIP link inputs instead of argument files"
+        );
+        return LinkInputMode::Zip;
+    }
+
+    mode
+}
+
+/// Name a process is reported and logged under: the file name of its subject
+fn process_label(subject: &Path) -> String {
+    subject
+        .file_name()
+        .unwrap_or(subject.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Directory for temporary link inputs (ZIPs or argument files)
+/// Always package-specific to keep multi-task builds isolated
+fn link_temp_dir(
+    output_apk: &Path,
+    package_name: Option<&str>,
+    compiled_dir: Option<&Path>,
+    name: &str,
+) -> PathBuf {
+    let output_parent = output_apk.parent().unwrap_or_else(|| Path::new("."));
+
+    if let Some(compiled) = compiled_dir {
+        // Primary: Use compiled directory (package-specific)
+        compiled.join(name)
+    } else if let Some(pkg_name) = package_name {
+        // Fallback: Use package name in output directory
+        output_parent.join(pkg_name.replace('.', "_")).join(name)
+    } else {
+        // Last resort: Use unique directory based on output APK name
+        let apk_stem = output_apk
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown");
+        output_parent.join(format!("{}_{}", name, apk_stem))
+    }
+}
+
+/// Write an aapt2 argument file listing the given paths
+/// aapt2 tokenizes argument files on spaces, so paths are space-separated
+fn write_argfile(path: &Path, flat_files: &[PathBuf]) -> Result<()> {
+    let content = flat_files
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write argument file: {}", path.display()))
+}
+
+/// Format an argument file path as an `@path` argument
+fn argfile_arg(path: &Path) -> std::ffi::OsString {
+    let mut arg = std::ffi::OsString::from("@");
+    arg.push(path);
+    arg
+}
+
+/// Version of the flat ZIP layout, part of the signature so older ZIPs are rebuilt
+const FLAT_ZIP_FORMAT: &str = "ordered-v1";
+
+/// Write a stored ZIP of flat files for use as aapt2 link input
+///
+/// aapt2 may visit ZIP entries sorted by name rather than in archive order, so each entry
+/// name is prefixed with its zero-padded position in `flat_files`. Both orders then agree,
+/// and a ZIP input applies its files exactly as the same files passed one by one would.
+///
+/// Flat files are streamed into the archive through a bounded buffer rather than read
+/// whole. A signature of the flat set (paths, sizes and modification times, in order) is
+/// kept next to the ZIP; when it matches, the existing ZIP is reused as-is, so an
+/// incremental build with an unchanged flat set skips rewriting it entirely.
+///
+/// Returns true if an existing ZIP was reused.
+fn write_flat_zip(
+    zip_path: &Path,
+    flat_files: &[PathBuf],
+    compiled_dir: Option<&Path>,
+) -> Result<bool> {
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+    use zip::CompressionMethod;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let signature = flat_set_signature(flat_files);
+    let signature_path = flat_zip_signature_path(zip_path);
+
+    if zip_path.exists()
+        && std::fs::read_to_string(&signature_path).is_ok_and(|existing| existing == signature)
+    {
+        debug!("Using cached ZIP file: {}", zip_path.display());
+        return Ok(true);
+    }
+
+    debug!("Creating ZIP file: {}", zip_path.display());
+
+    // Drop the old signature first so an interrupted write is never mistaken for a valid ZIP
+    if signature_path.exists() {
+        std::fs::remove_file(&signature_path)?;
+    }
+
+    let file = File::create(zip_path)
+        .with_context(|| format!("Failed to create ZIP file: {}", zip_path.display()))?;
+    let mut writer = ZipWriter::new(BufWriter::new(file));
+    let mut used_names = std::collections::HashSet::new();
+    let width = flat_files.len().to_string().len().max(6);
+
+    for (position, flat_file) in flat_files.iter().enumerate() {
+        let entry_name = format!(
+            "{:0width$}_{}",
+            position,
+            flat_zip_entry_name(flat_file, compiled_dir, &mut used_names),
+            width = width
+        );
+        writer.start_file::<_, ()>(
+            &entry_name,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+
+        let mut reader = BufReader::new(
+            File::open(flat_file)
+                .with_context(|| format!("Failed to open flat file: {}", flat_file.display()))?,
+        );
+        std::io::copy(&mut reader, &mut writer)?;
+    }
+    writer.finish()?;
+
+    std::fs::write(&signature_path, signature)?;
+    Ok(false)
+}
+
+/// Path of the signature file kept next to a flat ZIP
+fn flat_zip_signature_path(zip_path: &Path) -> PathBuf {
+    let mut name = zip_path.as_os_str().to_os_string();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// Compute a signature identifying an ordered set of flat files and their current state
+fn flat_set_signature(flat_files: &[PathBuf]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(FLAT_ZIP_FORMAT.as_bytes());
+    for flat_file in flat_files {
+        hasher.update(flat_file.to_string_lossy().as_bytes());
+        hasher.update([0]);
+
+        // Missing files hash as empty so a later appearance invalidates the ZIP
+        if let Ok(metadata) = std::fs::metadata(flat_file) {
+            hasher.update(metadata.len().to_le_bytes());
+            if let Ok(modified) = metadata.modified()
+                && let Ok(since_epoch) = modified.duration_since(std::time::UNIX_EPOCH)
+            {
+                hasher.update(since_epoch.as_nanos().to_le_bytes());
+            }
+        }
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Choose a unique entry name for a flat file inside a link-input ZIP
+fn flat_zip_entry_name(
+    flat_file: &Path,
+    compiled_dir: Option<&Path>,
+    used_names: &mut std::collections::HashSet<String>,
+) -> String {
+    // Strategy 1: Use relative path from compiled_dir if possible
+    let mut file_name = compiled_dir.and_then(|compiled| {
+        flat_file
+            .strip_prefix(compiled)
+            .ok()
+            .and_then(|p| p.to_str())
+            .map(|s| s.to_string())
+    });
+
+    // Strategy 2: If that didn't work, try using parent directory + filename
+    if file_name.is_none()
+        && let (Some(parent), Some(name)) = (flat_file.parent(), flat_file.file_name())
+        && let (Some(parent_name), Some(file_name_str)) = (parent.file_name(), name.to_str())
+        && let Some(parent_str) = parent_name.to_str()
+    {
+        file_name = Some(format!("{}/{}", parent_str, file_name_str));
+    }
+
+    // Strategy 3: Fallback to just filename
+    let mut final_name = file_name.unwrap_or_else(|| {
+        flat_file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown.flat")
+            .to_string()
+    });
+
+    // Ensure uniqueness by appending counter if needed
+    let base_name = final_name.clone();
+    let mut counter = 1;
+    while used_names.contains(&final_name) {
+        // Extract extension if present
+        if let Some(pos) = base_name.rfind('.') {
+            let (name_part, ext_part) = base_name.split_at(pos);
+            final_name = format!("{}_{}{}", name_part, counter, ext_part);
+        } else {
+            final_name = format!("{}_{}", base_name, counter);
+        }
+        counter += 1;
+    }
+    used_names.insert(final_name.clone());
+
+    final_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn zip_entry_names(zip_path: &Path) -> Vec<String> {
+        let archive = zip::ZipArchive::new(std::fs::File::open(zip_path).unwrap()).unwrap();
+        archive.file_names().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_write_flat_zip_reuses_unchanged_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let compiled = temp_dir.path().join("compiled");
+        std::fs::create_dir_all(compiled.join("main")).unwrap();
+        let flat_a = compiled.join("main").join("drawable_a.png.flat");
+        let flat_b = compiled.join("main").join("drawable_b.png.flat");
+        std::fs::write(&flat_a, b"a").unwrap();
+        std::fs::write(&flat_b, b"b").unwrap();
+        let zip_path = temp_dir.path().join("flats_s.zip");
+
+        let flats = vec![flat_a.clone(), flat_b.clone()];
+        assert!(!write_flat_zip(&zip_path, &flats, Some(&compiled)).unwrap());
+        assert!(write_flat_zip(&zip_path, &flats, Some(&compiled)).unwrap());
+
+        // Removing a file from the set must rebuild the ZIP even though no mtime changed
+        let flats = vec![flat_a.clone()];
+        assert!(!write_flat_zip(&zip_path, &flats, Some(&compiled)).unwrap());
+        assert_eq!(
+            zip_entry_names(&zip_path),
+            vec!["000000_main/drawable_a.png.flat"]
+        );
+    }
+
+    #[test]
+    fn test_write_flat_zip_rebuilds_on_content_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let flat = temp_dir.path().join("values_colors.arsc.flat");
+        std::fs::write(&flat, b"old").unwrap();
+        let zip_path = temp_dir.path().join("flats_s.zip");
+
+        assert!(!write_flat_zip(&zip_path, std::slice::from_ref(&flat), None).unwrap());
+        std::fs::write(&flat, b"new content").unwrap();
+        assert!(!write_flat_zip(&zip_path, std::slice::from_ref(&flat), None).unwrap());
+    }
+
+    /// Order in which aapt2 sees the inputs of each link mode, as (source flat, is overlay)
+    ///
+    /// Direct and argfile modes pass paths in slice order. For ZIP mode the ZIPs are read
+    /// back with entries sorted by name (the order aapt2 may use), and mapped back to their
+    /// source flat files by content.
+    fn zip_link_order(
+        temp_dir: &Path,
+        base: &[PathBuf],
+        overlays: &[Vec<PathBuf>],
+    ) -> Vec<(PathBuf, bool)> {
+        use std::io::Read;
+
+        let by_content: std::collections::HashMap<Vec<u8>, PathBuf> = base
+            .iter()
+            .chain(overlays.iter().flatten())
+            .map(|p| (std::fs::read(p).unwrap(), p.clone()))
+            .collect();
+
+        let mut order = Vec::new();
+        let sets = std::iter::once((base, false)).chain(overlays.iter().map(|o| (&o[..], true)));
+        for (idx, (set, is_overlay)) in sets.enumerate() {
+            let zip_path = temp_dir.join(format!("set_{}.zip", idx));
+            write_flat_zip(&zip_path, set, None).unwrap();
+
+            let mut archive =
+                zip::ZipArchive::new(std::fs::File::open(&zip_path).unwrap()).unwrap();
+            let mut names: Vec<String> = archive.file_names().map(|n| n.to_string()).collect();
+            names.sort();
+            for name in names {
+                let mut content = Vec::new();
+                archive
+                    .by_name(&name)
+                    .unwrap()
+                    .read_to_end(&mut content)
+                    .unwrap();
+                order.push((by_content[&content].clone(), is_overlay));
+            }
+        }
+        order
+    }
+
+    #[test]
+    fn test_zip_link_order_matches_direct_args() {
+        let temp_dir = TempDir::new().unwrap();
+        let flat = |dir: &str, name: &str| {
+            let path = temp_dir.path().join(dir).join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, format!("{}/{}", dir, name)).unwrap();
+            path
+        };
+
+        // Names deliberately sort differently from the intended order, and collide across sets
+        let base = vec![
+            flat("aar_0", "values_z.arsc.flat"),
+            flat("aar_0", "values_a.arsc.flat"),
+        ];
+        let overlays = vec![
+            vec![
+                flat("main", "drawable_z.png.flat"),
+                flat("main", "drawable_b.png.flat"),
+                flat("extra", "drawable_b.png.flat"),
+            ],
+            vec![flat("flavor", "values_a.arsc.flat")],
+        ];
+
+        let direct_order: Vec<(PathBuf, bool)> = base
+            .iter()
+            .map(|p| (p.clone(), false))
+            .chain(overlays.iter().flatten().map(|p| (p.clone(), true)))
+            .collect();
+
+        assert_eq!(
+            zip_link_order(temp_dir.path(), &base, &overlays),
+            direct_order
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hanging_compile_is_killed_and_reported() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        let fake_aapt2 = temp_dir.path().join("aapt2");
+        fs::write(&fake_aapt2, "#!/bin/sh\nexec sleep 30\n")?;
+        fs::set_permissions(&fake_aapt2, fs::Permissions::from_mode(0o755))?;
+        let png = temp_dir.path().join("res/drawable/corrupt.png");
+        fs::create_dir_all(png.parent().unwrap())?;
+        fs::write(&png, "not a png")?;
+
+        let aapt2 = Aapt2::new(Some(fake_aapt2))?.with_limits(Aapt2Limits {
+            timeout_secs: Some(1),
+            max_memory_mb: None,
+        });
+        let started = Instant::now();
+        let result = aapt2.compile_files_parallel(
+            std::slice::from_ref(&png),
+            &temp_dir.path().join("compiled"),
+        )?;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!result.success);
+        assert!(
+            result.errors[0].contains(&format!(
+                "aapt2 killed after exceeding the 1s timeout while processing {}",
+                png.display()
+            )),
+            "{:?}",
+            result.errors
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_is_streamed_to_log_file() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        let fake_aapt2 = temp_dir.path().join("aapt2");
+        fs::write(
+            &fake_aapt2,
+            "#!/bin/sh\necho 'compiling'\necho 'error: bad resource' >&2\nexit 1\n",
+        )?;
+        fs::set_permissions(&fake_aapt2, fs::Permissions::from_mode(0o755))?;
+        let png = temp_dir.path().join("res/drawable/icon.png");
+        fs::create_dir_all(png.parent().unwrap())?;
+        fs::write(&png, "not a png")?;
+
+        let log_path = temp_dir.path().join("compiled").join(AAPT2_LOG_FILE);
+        let mut aapt2 = Aapt2::new(Some(fake_aapt2))?;
+        aapt2.set_log_file(&log_path)?;
+        let result = aapt2.compile_files_parallel(
+            std::slice::from_ref(&png),
+            &temp_dir.path().join("compiled"),
+        )?;
+        assert!(!result.success);
+        assert!(result.errors[0].contains("error: bad resource"));
+
+        let log = fs::read_to_string(&log_path)?;
+        assert!(
+            log.contains(&format!("> aapt2 compile {}", png.display())),
+            "{}",
+            log
+        );
+        assert!(log.contains("icon.png stdout: compiling"), "{}", log);
+        assert!(
+            log.contains("icon.png stderr: error: bad resource"),
+            "{}",
+            log
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hanging_link_leaves_runtime_running() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let temp_dir = TempDir::new()?;
+        let fake_aapt2 = temp_dir.path().join("aapt2");
+        fs::write(&fake_aapt2, "#!/bin/sh\necho 'linking'\nexec sleep 30\n")?;
+        fs::set_permissions(&fake_aapt2, fs::Permissions::from_mode(0o755))?;
+        let log_path = temp_dir.path().join(AAPT2_LOG_FILE);
+        let mut aapt2 = Aapt2::new(Some(fake_aapt2))?.with_limits(Aapt2Limits {
+            timeout_secs: Some(1),
+            max_memory_mb: None,
+        });
+        aapt2.set_log_file(&log_path)?;
+
+        // The test runtime has a single thread, shared with the link
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        let output_apk = temp_dir.path().join("skin.apk");
+        let result = aapt2
+            .link_with_overlays(
+                &[temp_dir.path().join("values_colors.arsc.flat")],
+                &[],
+                &temp_dir.path().join("AndroidManifest.xml"),
+                Path::new("/fake/android.jar"),
+                &output_apk,
+                Some("com.test"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(temp_dir.path()),
+                None,
+            )
+            .await?;
+        ticker.abort();
+
+        assert!(ticks.load(Ordering::Relaxed) > 10);
+        assert!(!result.success);
+        assert!(result.errors[0].contains(&format!(
+            "aapt2 killed after exceeding the 1s timeout while processing {}",
+            output_apk.display()
+        )));
+        let log = fs::read_to_string(&log_path)?;
+        assert!(log.contains("skin.apk stdout: linking"), "{}", log);
+        Ok(())
+    }
+
+    /// Fake aapt2 that writes the flat files `compile` would and logs each invocation
+    #[cfg(unix)]
+    fn fake_compiling_aapt2(dir: &Path) -> Result<(PathBuf, PathBuf)> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let fake_aapt2 = dir.join("aapt2");
+        let calls = dir.join("calls.txt");
+        fs::write(
+            &fake_aapt2,
+            format!(
+                "#!/bin/sh\n\
+                 echo \"$@\" >> {}\n\
+                 while [ \"$1\" != \"-o\" ]; do shift; done\n\
+                 out=$2; shift 2\n\
+                 for f in \"$@\"; do\n\
+                 touch \"$out/$(basename $(dirname $f))_$(basename $f).flat\"\n\
+                 done\n",
+                calls.display()
+            ),
+        )?;
+        fs::set_permissions(&fake_aapt2, fs::Permissions::from_mode(0o755))?;
+        Ok((fake_aapt2, calls))
+    }
+
+    /// Create `count` PNG resources in each of the given type directories
+    #[cfg(unix)]
+    fn png_resources(res_dir: &Path, dirs: &[(&str, usize)]) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for (dir, count) in dirs {
+            std::fs::create_dir_all(res_dir.join(dir))?;
+            for i in 0..*count {
+                let file = res_dir.join(format!("{}/icon_{}.png", dir, i));
+                std::fs::write(&file, "png")?;
+                files.push(file);
+            }
+        }
+        Ok(files)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compile_files_batched() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (fake_aapt2, calls) = fake_compiling_aapt2(temp_dir.path())?;
+        let files = png_resources(
+            &temp_dir.path().join("res"),
+            &[("drawable", 3), ("mipmap-hdpi", 2)],
+        )?;
+
+        let compiled = temp_dir.path().join("compiled");
+        let result = Aapt2::new(Some(fake_aapt2))?.compile_files_batched(&files, &compiled, 2)?;
+        assert!(result.success, "{:?}", result.errors);
+        assert_eq!(result.flat_files.len(), 5);
+        assert!(compiled.join("mipmap-hdpi_icon_1.png.flat").exists());
+        // drawable: 2 + 1 files, mipmap-hdpi: 2 files
+        assert_eq!(std::fs::read_to_string(&calls)?.lines().count(), 3);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compile_files_sharded() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (fake_aapt2, _) = fake_compiling_aapt2(temp_dir.path())?;
+        let files = png_resources(&temp_dir.path().join("res"), &[("drawable", 40)])?;
+
+        let compiled = temp_dir.path().join("compiled");
+        let result = Aapt2::new(Some(fake_aapt2))?.compile_files_sharded(&files, &compiled)?;
+        assert!(result.success, "{:?}", result.errors);
+        for flat_file in &result.flat_files {
+            let name = flat_file.file_name().unwrap().to_str().unwrap();
+            assert_eq!(flat_file.parent().unwrap(), compiled.join(flat_shard(name)));
+        }
+        let shards = std::fs::read_dir(&compiled)?.count();
+        assert!(shards > 1 && shards <= 40, "{} shards", shards);
+
+        let mut collected = Aapt2::collect_flat_files(&compiled)?;
+        let mut expected = result.flat_files.clone();
+        collected.sort();
+        expected.sort();
+        assert_eq!(collected, expected);
+
+        // `--dir` output is moved into the same shards
+        let unsharded: Vec<PathBuf> = expected
+            .iter()
+            .map(|flat| {
+                let moved = compiled.join(flat.file_name().unwrap());
+                std::fs::rename(flat, &moved).unwrap();
+                moved
+            })
+            .collect();
+        let mut resharded = shard_flat_files(&compiled, unsharded)?;
+        resharded.sort();
+        assert_eq!(resharded, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_args_for_resource_type() {
+        let aapt2 = Aapt2::new(Some(PathBuf::from("aapt2")))
+            .unwrap()
+            .with_compile_options(BTreeMap::from([
+                (
+                    "*".to_string(),
+                    CompileFlags {
+                        legacy: Some(true),
+                        ..CompileFlags::default()
+                    },
+                ),
+                (
+                    "drawable".to_string(),
+                    CompileFlags {
+                        no_crunch: Some(true),
+                        legacy: Some(false),
+                        ..CompileFlags::default()
+                    },
+                ),
+                (
+                    "values".to_string(),
+                    CompileFlags {
+                        pseudo_localize: Some(true),
+                        ..CompileFlags::default()
+                    },
+                ),
+            ]));
+
+        assert_eq!(
+            aapt2.compile_args_for(Some("drawable")),
+            vec!["--no-crunch"]
+        );
+        assert_eq!(
+            aapt2.compile_args_for(Some("values")),
+            vec!["--pseudo-localize", "--legacy"]
+        );
+        assert_eq!(aapt2.compile_args_for(Some("layout")), vec!["--legacy"]);
+        assert_eq!(aapt2.compile_args_for(None), vec!["--legacy"]);
+    }
+
+    #[test]
+    fn test_is_static_lib() {
+        assert!(is_static_lib(Path::new("/deps/common/static-lib.apk")));
+        assert!(!is_static_lib(Path::new(
+            "/deps/common/values_colors.arsc.flat"
+        )));
+    }
+
+    #[test]
+    fn test_resolve_link_input_mode() {
+        let few: Vec<PathBuf> = (0..3)
+            .map(|i| PathBuf::from(format!("/c/{}.flat", i)))
+            .collect();
+        let many: Vec<PathBuf> = (0..=LINK_INPUT_THRESHOLD)
+            .map(|i| PathBuf::from(format!("/c/{}.flat", i)))
+            .collect();
+
+        assert_eq!(
+            resolve_link_input_mode(LinkInputMode::Auto, &few, &[]),
+            LinkInputMode::Direct
+        );
+        assert_eq!(
+            resolve_link_input_mode(LinkInputMode::Auto, &few, std::slice::from_ref(&many)),
+            LinkInputMode::Argfile
+        );
+        assert_eq!(
+            resolve_link_input_mode(LinkInputMode::Zip, &few, &[]),
+            LinkInputMode::Zip
+        );
+
+        // Whitespace in any path forces ZIP inputs
+        let spaced = vec![PathBuf::from("/my project/a.flat")];
+        assert_eq!(
+            resolve_link_input_mode(LinkInputMode::Argfile, &spaced, &[]),
+            LinkInputMode::Zip
+        );
+    }
+
+    #[test]
+    fn test_write_argfile() {
+        let temp_dir = TempDir::new().unwrap();
+        let args = temp_dir.path().join("overlay_0.txt");
+        write_argfile(
+            &args,
+            &[PathBuf::from("/c/b.flat"), PathBuf::from("/c/a.flat")],
+        )
+        .unwrap();
+
+        // Order is preserved exactly
+        assert_eq!(
+            std::fs::read_to_string(&args).unwrap(),
+            "/c/b.flat /c/a.flat"
+        );
+        assert_eq!(
+            argfile_arg(&args).to_string_lossy(),
+            format!("@{}", args.display())
+        );
+    }
+
+    #[test]
+    fn test_flat_zip_entry_name_uniqueness() {
+        let mut used = std::collections::HashSet::new();
+        let first = flat_zip_entry_name(Path::new("/a/main/x.flat"), None, &mut used);
+        let second = flat_zip_entry_name(Path::new("/b/main/x.flat"), None, &mut used);
+        assert_eq!(first, "main/x.flat");
+        assert_eq!(second, "main/x_1.flat");
+    }
+}
@@ -1,16 +1,45 @@
 use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::path::{Path, PathBuf};
-use tracing::debug;
+use std::io::Read;
+use std::path::Path;
+use tracing::{debug, warn};
+use walkdir::WalkDir;
 
-use crate::types::AarInfo;
+use crate::types::{AarFile, AarInfo, AarResourceFilter};
+
+/// Upper bound on the bytes extracted from a single AAR, guarding against zip bombs
+pub const MAX_EXTRACTED_SIZE: u64 = 1 << 30;
+
+/// Directories some AAR producers use instead of `res/`
+const RES_DIR_ALIASES: [&str; 1] = ["android_res"];
+
+/// Nested archives holding an AAR's resources, e.g. from Bazel
+const RES_ZIP_NAMES: [&str; 2] = ["res.zip", "android_res.zip"];
+
+/// Bytes an AAR may still extract, shared with its nested archives
+struct SizeBudget {
+    limit: u64,
+    used: u64,
+}
 
 /// Utility for handling AAR files
 pub struct AarExtractor;
 
 impl AarExtractor {
     /// Extract AAR file to a directory
+    ///
+    /// Entries escaping the directory (`../`, absolute paths) fail the extraction, symlink
+    /// entries are skipped and Unix permission bits are kept, minus setuid/setgid.
     pub fn extract_aar(aar_path: &Path, extract_dir: &Path) -> Result<AarInfo> {
+        Self::extract_aar_with_limit(aar_path, extract_dir, MAX_EXTRACTED_SIZE)
+    }
+
+    fn extract_aar_with_limit(
+        aar_path: &Path,
+        extract_dir: &Path,
+        max_size: u64,
+    ) -> Result<AarInfo> {
         if !aar_path.exists() {
             anyhow::bail!("AAR file not found: {}", aar_path.display());
         }
@@ -23,32 +52,41 @@ impl AarExtractor {
             extract_dir.display()
         );
 
-        // Extract ZIP
-        let file = File::open(aar_path)
-            .with_context(|| format!("Failed to open AAR file: {}", aar_path.display()))?;
-        let mut archive = zip::ZipArchive::new(file)
-            .with_context(|| format!("Failed to read AAR as ZIP: {}", aar_path.display()))?;
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = extract_dir.join(file.name());
+        let mut budget = SizeBudget {
+            limit: max_size,
+            used: 0,
+        };
+        Self::unzip(
+            aar_path,
+            &aar_path.display().to_string(),
+            extract_dir,
+            &mut budget,
+        )?;
 
-            if file.name().ends_with('/') {
-                std::fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    std::fs::create_dir_all(p)?;
-                }
-                let mut outfile = File::create(&outpath)?;
-                std::io::copy(&mut file, &mut outfile)?;
-            }
+        // Resources normally live in res/, but some producers nest them in a zip or use
+        // another directory name; normalize those into res/
+        let res_dir = extract_dir.join("res");
+        if !res_dir.exists() {
+            Self::normalize_resource_dir(aar_path, extract_dir, &mut budget)?;
         }
+        Ok(Self::extracted_info(
+            aar_path,
+            extract_dir,
+            String::new(),
+            false,
+        ))
+    }
 
-        // Find resource directory and manifest
+    /// Describe an AAR already extracted to `extract_dir`
+    fn extracted_info(
+        aar_path: &Path,
+        extract_dir: &Path,
+        sha256: String,
+        reused: bool,
+    ) -> AarInfo {
         let res_dir = extract_dir.join("res");
         let manifest_path = extract_dir.join("AndroidManifest.xml");
-
-        Ok(AarInfo {
+        AarInfo {
             path: aar_path.to_path_buf(),
             resource_dir: if res_dir.exists() {
                 Some(res_dir)
@@ -61,29 +99,208 @@ impl AarExtractor {
                 None
             },
             extracted_dir: extract_dir.to_path_buf(),
-        })
+            sha256,
+            reused,
+        }
     }
 
-    /// Extract multiple AAR files
-    pub fn extract_aars(aar_paths: &[PathBuf], base_temp_dir: &Path) -> Result<Vec<AarInfo>> {
+    /// Move resources from a nested `res.zip` or a nonstandard directory into `res/`
+    fn normalize_resource_dir(
+        aar_path: &Path,
+        extract_dir: &Path,
+        budget: &mut SizeBudget,
+    ) -> Result<()> {
+        let res_dir = extract_dir.join("res");
+        for name in RES_DIR_ALIASES {
+            let dir = extract_dir.join(name);
+            if dir.is_dir() {
+                debug!("Using {}/ of {} as res/", name, aar_path.display());
+                return std::fs::rename(&dir, &res_dir)
+                    .with_context(|| format!("Failed to move {} to res/", dir.display()));
+            }
+        }
+        for name in RES_ZIP_NAMES {
+            let zip_path = extract_dir.join(name);
+            if !zip_path.is_file() {
+                continue;
+            }
+            debug!("Extracting nested {} of {}", name, aar_path.display());
+            let staging = extract_dir.join(format!("{}.d", name));
+            Self::unzip(
+                &zip_path,
+                &format!("{}!/{}", aar_path.display(), name),
+                &staging,
+                budget,
+            )?;
+            // The zip holds either res/<type>/... or <type>/... directly
+            let nested_res = staging.join("res");
+            let source = if nested_res.is_dir() {
+                nested_res
+            } else {
+                staging.clone()
+            };
+            std::fs::rename(&source, &res_dir).with_context(|| {
+                format!("Failed to move resources of {} to res/", zip_path.display())
+            })?;
+            if staging.exists() {
+                std::fs::remove_dir_all(&staging).ok();
+            }
+            return Ok(());
+        }
+        Ok(())
+    }
+
+    /// Safely extract `zip_path` into `dest`, counting written bytes against `budget`
+    fn unzip(zip_path: &Path, label: &str, dest: &Path, budget: &mut SizeBudget) -> Result<()> {
+        std::fs::create_dir_all(dest)?;
+        let file =
+            File::open(zip_path).with_context(|| format!("Failed to open AAR file: {}", label))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .with_context(|| format!("Failed to read AAR as ZIP: {}", label))?;
+
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let Some(relative) = file.enclosed_name() else {
+                anyhow::bail!(
+                    "AAR {} has an entry outside the extraction directory: {}",
+                    label,
+                    file.name()
+                );
+            };
+            let outpath = dest.join(relative);
+
+            if file.is_symlink() {
+                warn!("Skipping symlink entry {} in AAR {}", file.name(), label);
+                continue;
+            }
+            if file.is_dir() {
+                std::fs::create_dir_all(&outpath)?;
+                continue;
+            }
+
+            if let Some(p) = outpath.parent() {
+                std::fs::create_dir_all(p)?;
+            }
+            let mut outfile = File::create(&outpath)
+                .with_context(|| format!("Failed to create {}", outpath.display()))?;
+            // Count the bytes actually written: declared sizes can lie
+            let remaining = budget.limit - budget.used;
+            let written = std::io::copy(&mut (&mut file).take(remaining + 1), &mut outfile)?;
+            if written > remaining {
+                anyhow::bail!("AAR {} extracts to more than {} bytes", label, budget.limit);
+            }
+            budget.used += written;
+
+            #[cfg(unix)]
+            if let Some(mode) = file.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// SHA-256 of the AAR file
+    pub fn content_hash(aar_path: &Path) -> Result<String> {
+        if !aar_path.exists() {
+            anyhow::bail!("AAR file not found: {}", aar_path.display());
+        }
+        let mut file = File::open(aar_path)
+            .with_context(|| format!("Failed to open AAR file: {}", aar_path.display()))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)
+            .with_context(|| format!("Failed to read AAR file: {}", aar_path.display()))?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Fail unless the AAR's hash matches its pinned checksum, if it has one
+    fn verify_checksum(aar_file: &AarFile, actual: &str) -> Result<()> {
+        let Some(expected) = aar_file.sha256() else {
+            return Ok(());
+        };
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            anyhow::bail!(
+                "Checksum mismatch for AAR {}: expected sha256 {}, got {}",
+                aar_file.path().display(),
+                expected.trim(),
+                actual
+            );
+        }
+        debug!("Verified checksum of {}", aar_file.path().display());
+        Ok(())
+    }
+
+    /// Extract multiple AAR files, verifying pinned checksums first
+    ///
+    /// `reuse(aar, extract_dir, hash)` tells whether the AAR extracted to `extract_dir` by an
+    /// earlier build is still current; such AARs are not extracted again.
+    pub fn extract_aars(
+        aar_files: &[AarFile],
+        base_temp_dir: &Path,
+        reuse: impl Fn(&Path, &Path, &str) -> bool + Sync,
+    ) -> Result<Vec<AarInfo>> {
         use rayon::prelude::*;
 
-        let results: Vec<Result<AarInfo>> = aar_paths
+        let results: Vec<Result<AarInfo>> = aar_files
             .par_iter()
             .enumerate()
-            .map(|(i, aar_path)| {
+            .map(|(i, aar_file)| {
+                let aar_path = aar_file.path();
+                let hash = Self::content_hash(aar_path)?;
+                Self::verify_checksum(aar_file, &hash)?;
                 let aar_name = aar_path
                     .file_stem()
                     .and_then(|s| s.to_str())
                     .unwrap_or("unknown");
                 let extract_dir = base_temp_dir.join(format!("aar_{}_{}", i, aar_name));
-                Self::extract_aar(aar_path, &extract_dir)
+                if reuse(aar_path, &extract_dir, &hash) {
+                    debug!("Reusing extracted AAR {}", extract_dir.display());
+                    return Ok(Self::extracted_info(aar_path, &extract_dir, hash, true));
+                }
+                if extract_dir.exists() {
+                    std::fs::remove_dir_all(&extract_dir).with_context(|| {
+                        format!("Failed to remove stale {}", extract_dir.display())
+                    })?;
+                }
+                let mut info = Self::extract_aar(aar_path, &extract_dir)?;
+                info.sha256 = hash;
+                Ok(info)
             })
             .collect();
 
         results.into_iter().collect()
     }
 
+    /// Delete the extracted resources the filter does not keep
+    /// Returns the number of files removed
+    pub fn filter_resources(aar_info: &AarInfo, filter: &AarResourceFilter) -> Result<usize> {
+        let Some(res_dir) = &aar_info.resource_dir else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for entry in WalkDir::new(res_dir).min_depth(2) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(res_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            if !filter.keeps(&relative) {
+                std::fs::remove_file(entry.path()).with_context(|| {
+                    format!(
+                        "Failed to remove filtered resource {}",
+                        entry.path().display()
+                    )
+                })?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
     /// Clean up extracted AAR directories
     pub fn cleanup_aars(aar_infos: &[AarInfo]) -> Result<()> {
         for info in aar_infos {
@@ -94,3 +311,139 @@ impl AarExtractor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn write_aar(
+        path: &Path,
+        build: impl FnOnce(&mut ZipWriter<File>) -> Result<()>,
+    ) -> Result<()> {
+        let mut writer = ZipWriter::new(File::create(path)?);
+        build(&mut writer)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_rejects_entries_outside_extract_dir() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let aar = temp_dir.path().join("evil.aar");
+        write_aar(&aar, |writer| {
+            writer.start_file("res/values/colors.xml", SimpleFileOptions::default())?;
+            writer.write_all(b"<resources/>")?;
+            writer.start_file("../../escaped.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"pwned")?;
+            Ok(())
+        })?;
+
+        let extract_dir = temp_dir.path().join("out/aar_0");
+        let error = AarExtractor::extract_aar(&aar, &extract_dir).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("entry outside the extraction directory: ../../escaped.txt"),
+            "{}",
+            error
+        );
+        assert!(!temp_dir.path().join("escaped.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_normalizes_nested_resource_layouts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let colors = b"<resources><color name=\"primary\">#fff</color></resources>";
+
+        // Bazel-style: resources in a nested res.zip, with or without a res/ prefix
+        for (idx, prefix) in ["res/", ""].iter().enumerate() {
+            let res_zip = temp_dir.path().join(format!("res_{}.zip", idx));
+            write_aar(&res_zip, |writer| {
+                writer.start_file(
+                    format!("{}values/colors.xml", prefix),
+                    SimpleFileOptions::default(),
+                )?;
+                writer.write_all(colors)?;
+                Ok(())
+            })?;
+            let aar = temp_dir.path().join(format!("nested_{}.aar", idx));
+            write_aar(&aar, |writer| {
+                writer.start_file("AndroidManifest.xml", SimpleFileOptions::default())?;
+                writer.write_all(b"<manifest package=\"com.lib\"/>")?;
+                writer.start_file("res.zip", SimpleFileOptions::default())?;
+                writer.write_all(&std::fs::read(&res_zip)?)?;
+                Ok(())
+            })?;
+
+            let extract_dir = temp_dir.path().join(format!("nested_{}", idx));
+            let info = AarExtractor::extract_aar(&aar, &extract_dir)?;
+            let res_dir = info.resource_dir.expect("resources from res.zip");
+            assert_eq!(res_dir, extract_dir.join("res"));
+            assert_eq!(std::fs::read(res_dir.join("values/colors.xml"))?, colors);
+            assert!(!extract_dir.join("res.zip.d").exists());
+        }
+
+        let aar = temp_dir.path().join("android_res.aar");
+        write_aar(&aar, |writer| {
+            writer.start_file(
+                "android_res/values/colors.xml",
+                SimpleFileOptions::default(),
+            )?;
+            writer.write_all(colors)?;
+            Ok(())
+        })?;
+        let info = AarExtractor::extract_aar(&aar, &temp_dir.path().join("android_res"))?;
+        assert!(
+            info.resource_dir
+                .unwrap()
+                .join("values/colors.xml")
+                .exists()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_skips_symlinks_and_limits_size() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let aar = temp_dir.path().join("lib.aar");
+        write_aar(&aar, |writer| {
+            writer.add_symlink(
+                "res/values/link.xml",
+                "/etc/passwd",
+                SimpleFileOptions::default(),
+            )?;
+            writer.start_file(
+                "res/raw/tool.sh",
+                SimpleFileOptions::default().unix_permissions(0o4755),
+            )?;
+            writer.write_all(&[b'x'; 64])?;
+            Ok(())
+        })?;
+
+        let extract_dir = temp_dir.path().join("aar_0");
+        let info = AarExtractor::extract_aar(&aar, &extract_dir)?;
+        assert!(info.resource_dir.is_some());
+        assert!(
+            extract_dir
+                .join("res/values/link.xml")
+                .symlink_metadata()
+                .is_err()
+        );
+        let tool = extract_dir.join("res/raw/tool.sh");
+        assert_eq!(tool.metadata()?.len(), 64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(tool.metadata()?.permissions().mode() & 0o7777, 0o755);
+        }
+
+        let error = AarExtractor::extract_aar_with_limit(&aar, &temp_dir.path().join("aar_1"), 63)
+            .unwrap_err();
+        assert!(error.to_string().contains("extracts to more than 63 bytes"));
+        Ok(())
+    }
+}
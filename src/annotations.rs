@@ -0,0 +1,379 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config_lint::ConfigFinding;
+use crate::contrast::ContrastViolation;
+use crate::events::parse_diagnostic;
+use crate::types::BuildResult;
+
+/// Default file of the GitLab Code Quality report
+pub const GITLAB_REPORT_FILE: &str = "gl-code-quality-report.json";
+
+/// How build errors and lint findings are reported to the CI system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AnnotationFormat {
+    /// GitHub Actions workflow commands (`::error file=...,line=...::message`) on stdout
+    Github,
+    /// GitLab Code Quality JSON report written to a file
+    Gitlab,
+    /// No annotations
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A build error or lint finding, located in a file where known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub severity: Severity,
+    /// Check that reported it: `build`, `config` or `contrast`
+    pub check: &'static str,
+    /// Package it belongs to, empty for findings shared by several packages
+    pub package: String,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// Distinct messages in order of first appearance, with how often each appeared
+pub fn collapse<'a>(messages: impl IntoIterator<Item = &'a String>) -> Vec<(&'a str, usize)> {
+    let mut collapsed: Vec<(&str, usize)> = Vec::new();
+    for message in messages {
+        match collapsed.iter_mut().find(|(known, _)| *known == message) {
+            Some((_, count)) => *count += 1,
+            None => collapsed.push((message, 1)),
+        }
+    }
+    collapsed
+}
+
+/// Annotations for the errors and warnings of built packages
+///
+/// aapt2 lines such as `res/values/colors.xml:12: error: ...` become annotations on that
+/// file and line; the rest of each error becomes one annotation without a location.
+/// Warnings repeated across packages are reported once, with the count.
+pub fn build_annotations(results: &[(&str, &BuildResult)]) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    for (package, result) in results {
+        for error in &result.errors {
+            let mut remaining = Vec::new();
+            for line in error.lines() {
+                match parse_diagnostic(line.trim()) {
+                    (Some(file), line, message) => annotations.push(Annotation {
+                        severity: Severity::Error,
+                        check: "build",
+                        package: package.to_string(),
+                        file: Some(file),
+                        line: line.filter(|n| *n > 0),
+                        message: message.to_string(),
+                    }),
+                    _ => remaining.push(line),
+                }
+            }
+            let remaining = remaining.join("\n");
+            if !remaining.trim().is_empty() {
+                annotations.push(Annotation {
+                    severity: Severity::Error,
+                    check: "build",
+                    package: package.to_string(),
+                    file: None,
+                    line: None,
+                    message: remaining.trim().to_string(),
+                });
+            }
+        }
+    }
+    let warnings = collapse(results.iter().flat_map(|(_, result)| &result.warnings));
+    for (message, count) in warnings {
+        let (package, message) = match count {
+            1 => {
+                let package = results
+                    .iter()
+                    .find(|(_, result)| result.warnings.iter().any(|w| w == message))
+                    .map_or("", |(package, _)| package);
+                (package.to_string(), message.to_string())
+            }
+            _ => (String::new(), format!("{} (x{})", message, count)),
+        };
+        annotations.push(Annotation {
+            severity: Severity::Warning,
+            check: "build",
+            package,
+            file: None,
+            line: None,
+            message,
+        });
+    }
+    annotations
+}
+
+/// Annotations for the findings of `asb lint`: unused config entries are warnings and
+/// contrast violations errors, both located in the config file
+pub fn lint_annotations(
+    package: &str,
+    config_file: &Path,
+    findings: &[ConfigFinding],
+    violations: &[ContrastViolation],
+) -> Vec<Annotation> {
+    let findings = findings.iter().map(|finding| Annotation {
+        severity: Severity::Warning,
+        check: "config",
+        package: package.to_string(),
+        file: Some(config_file.to_path_buf()),
+        line: None,
+        message: format!("{}: {}", finding.field, finding.message),
+    });
+    let violations = violations.iter().map(|v| Annotation {
+        severity: Severity::Error,
+        check: "contrast",
+        package: package.to_string(),
+        file: Some(config_file.to_path_buf()),
+        line: None,
+        message: format!(
+            "[{}] {}: {} {} on {} {} is {:.2}:1, needs {}:1",
+            v.configuration,
+            v.scope,
+            v.foreground,
+            v.foreground_color,
+            v.background,
+            v.background_color,
+            v.ratio,
+            v.min_ratio
+        ),
+    });
+    findings.chain(violations).collect()
+}
+
+/// Report annotations in `format`
+///
+/// GitHub workflow commands go to stdout, or to stderr with `stderr` (when stdout carries
+/// JSON). The GitLab report lists annotations without a file at `default_file`, usually
+/// the config file, since GitLab needs a location for every issue.
+pub fn emit(
+    format: AnnotationFormat,
+    annotations: &[Annotation],
+    default_file: &Path,
+    report_file: &Path,
+    stderr: bool,
+) -> Result<()> {
+    match format {
+        AnnotationFormat::Github => {
+            for annotation in annotations {
+                if stderr {
+                    eprintln!("{}", github_command(annotation));
+                } else {
+                    println!("{}", github_command(annotation));
+                }
+            }
+        }
+        AnnotationFormat::Gitlab => {
+            let report = gitlab_report(annotations, default_file);
+            fs::write(report_file, serde_json::to_string_pretty(&report)?)
+                .with_context(|| format!("Failed to write {}", report_file.display()))?;
+            info!(
+                "Code Quality report written to {} ({} issues)",
+                report_file.display(),
+                report.len()
+            );
+        }
+        AnnotationFormat::None => {}
+    }
+    Ok(())
+}
+
+/// Path relative to the working directory (the checkout in CI), with forward slashes
+fn relative_path(path: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Escape the message of a GitHub Actions workflow command
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value (`file=...`) of a GitHub Actions workflow command
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// GitHub Actions workflow command for an annotation, titled with its package
+pub fn github_command(annotation: &Annotation) -> String {
+    let mut properties = Vec::new();
+    if let Some(file) = &annotation.file {
+        properties.push(format!("file={}", escape_property(&relative_path(file))));
+    }
+    if let Some(line) = annotation.line {
+        properties.push(format!("line={}", line));
+    }
+    if !annotation.package.is_empty() {
+        properties.push(format!("title={}", escape_property(&annotation.package)));
+    }
+    let command = match annotation.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let properties = if properties.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", properties.join(","))
+    };
+    format!(
+        "::{}{}::{}",
+        command,
+        properties,
+        escape_data(&annotation.message)
+    )
+}
+
+/// Issue of a GitLab Code Quality report
+#[derive(Debug, Serialize)]
+pub struct CodeQualityIssue {
+    pub description: String,
+    pub check_name: String,
+    /// Identifies the issue across pipelines, for GitLab to tell new issues from fixed ones
+    pub fingerprint: String,
+    pub severity: &'static str,
+    pub location: CodeQualityLocation,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeQualityLocation {
+    pub path: String,
+    pub lines: CodeQualityLines,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeQualityLines {
+    pub begin: u32,
+}
+
+/// GitLab Code Quality issues for annotations; errors are `major`, warnings `minor`
+pub fn gitlab_report(annotations: &[Annotation], default_file: &Path) -> Vec<CodeQualityIssue> {
+    use sha2::{Digest, Sha256};
+
+    annotations
+        .iter()
+        .map(|annotation| {
+            let path = relative_path(annotation.file.as_deref().unwrap_or(default_file));
+            let description = if annotation.package.is_empty() {
+                annotation.message.clone()
+            } else {
+                format!("{}: {}", annotation.package, annotation.message)
+            };
+            let check_name = format!("asb-{}", annotation.check);
+            let fingerprint = Sha256::digest(format!("{}\n{}\n{}", check_name, path, description));
+            CodeQualityIssue {
+                description,
+                check_name,
+                fingerprint: format!("{:x}", fingerprint),
+                severity: match annotation.severity {
+                    Severity::Error => "major",
+                    Severity::Warning => "minor",
+                },
+                location: CodeQualityLocation {
+                    path,
+                    lines: CodeQualityLines {
+                        begin: annotation.line.unwrap_or(1),
+                    },
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn result(errors: &[String], warnings: &[&str]) -> BuildResult {
+        BuildResult {
+            success: errors.is_empty(),
+            apk_path: None,
+            errors: errors.to_vec(),
+            warnings: warnings.iter().map(|w| w.to_string()).collect(),
+            build_duration: Duration::ZERO,
+            vcs: None,
+            cached_files: 0,
+            compiled_files: 0,
+            normalization: None,
+        }
+    }
+
+    #[test]
+    fn test_build_annotations_as_github_commands() {
+        let cwd = std::env::current_dir().unwrap();
+        let colors = cwd.join("res/values/colors.xml");
+        let red = result(
+            &[
+                format!(
+                    "{}:12: error: invalid color '#zz'.\naapt2 compile failed",
+                    colors.display()
+                ),
+                "Frozen resource color/accent was removed, 100% sure".to_string(),
+            ],
+            &["AAR filter 'a*' matches none of the AAR files"],
+        );
+        let blue = result(
+            &[],
+            &[
+                "AAR filter 'a*' matches none of the AAR files",
+                "Unused color",
+            ],
+        );
+        let commands: Vec<String> = build_annotations(&[("com.red", &red), ("com.blue", &blue)])
+            .iter()
+            .map(github_command)
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                "::error file=res/values/colors.xml,line=12,title=com.red::invalid color '#zz'.",
+                "::error title=com.red::aapt2 compile failed",
+                "::error title=com.red::Frozen resource color/accent was removed, 100%25 sure",
+                "::warning::AAR filter 'a*' matches none of the AAR files (x2)",
+                "::warning title=com.blue::Unused color",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gitlab_report_locates_every_issue() -> Result<()> {
+        let annotations = lint_annotations(
+            "com.red",
+            Path::new("asb.config.json"),
+            &[ConfigFinding {
+                field: "aarFiles[0]".to_string(),
+                message: "lib.aar has no resources under res/".to_string(),
+            }],
+            &[],
+        );
+        let report = gitlab_report(&annotations, Path::new("asb.config.json"));
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(
+            json[0]["description"],
+            "com.red: aarFiles[0]: lib.aar has no resources under res/"
+        );
+        assert_eq!(json[0]["check_name"], "asb-config");
+        assert_eq!(json[0]["severity"], "minor");
+        assert_eq!(json[0]["location"]["path"], "asb.config.json");
+        assert_eq!(json[0]["location"]["lines"]["begin"], 1);
+        assert_eq!(json[0]["fingerprint"].as_str().unwrap().len(), 64);
+        Ok(())
+    }
+}
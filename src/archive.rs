@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::builder::RESOURCE_TYPES;
+
+/// Marks an extraction that finished, so an interrupted one is never built from
+const COMPLETE_MARKER: &str = ".asb-extracted";
+
+/// Whether `path` is a resource archive (`.zip`, `.tar`, `.tar.gz` or `.tgz`) rather than
+/// a resource directory
+pub fn is_archive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// SHA-256 of a file, read in chunks so large archives are not loaded at once
+fn file_hash(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Unpack `archive` into `dest`; entries escaping `dest` are never written
+fn unpack(archive: &Path, dest: &Path) -> Result<()> {
+    let name = archive.to_string_lossy().to_ascii_lowercase();
+    let file = BufReader::new(File::open(archive)?);
+    if name.ends_with(".zip") {
+        zip::ZipArchive::new(file)?.extract(dest)?;
+    } else if name.ends_with(".tar") {
+        tar::Archive::new(file).unpack(dest)?;
+    } else {
+        tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(dest)?;
+    }
+    Ok(())
+}
+
+/// Root of the resource tree in an extracted archive
+///
+/// Archives often wrap the tree in directories such as `skin-v2/res/`; single directories
+/// that are not resource type directories are descended into.
+fn resource_root(dir: &Path) -> Result<PathBuf> {
+    let mut root = dir.to_path_buf();
+    loop {
+        let entries: Vec<PathBuf> = fs::read_dir(&root)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().is_some_and(|n| n != COMPLETE_MARKER))
+            .collect();
+        let [only] = entries.as_slice() else {
+            return Ok(root);
+        };
+        let name = only.file_name().unwrap_or_default().to_string_lossy();
+        let resource_type = name.split('-').next().unwrap_or(&name);
+        if !only.is_dir() || RESOURCE_TYPES.contains(&resource_type) {
+            return Ok(root);
+        }
+        root = only.clone();
+    }
+}
+
+/// Extract a resource archive below `cache_dir` and return its resource tree
+///
+/// Extractions are keyed by the archive's content hash: an unchanged archive is extracted
+/// once, and a changed one replaces the extraction of its previous content.
+pub fn extract_archive(archive: &Path, cache_dir: &Path) -> Result<PathBuf> {
+    if !archive.is_file() {
+        anyhow::bail!("Resource archive not found: {}", archive.display());
+    }
+    let hash = file_hash(archive)?;
+    let stem = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().replace('.', "_"))
+        .unwrap_or_default();
+    let dest = cache_dir.join(format!("{}-{}", stem, &hash[..16]));
+    if dest.join(COMPLETE_MARKER).is_file() {
+        debug!(
+            "Reusing extraction of {} at {}",
+            archive.display(),
+            dest.display()
+        );
+        return resource_root(&dest);
+    }
+
+    // Extractions of earlier contents of the same archive are stale
+    if cache_dir.is_dir() {
+        for entry in fs::read_dir(cache_dir)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&format!("{}-", stem)) && entry.path() != dest {
+                debug!("Removing stale extraction {}", entry.path().display());
+                fs::remove_dir_all(entry.path()).ok();
+            }
+        }
+    }
+    if dest.exists() {
+        fs::remove_dir_all(&dest)?;
+    }
+    fs::create_dir_all(&dest)?;
+    unpack(archive, &dest).with_context(|| format!("Failed to extract {}", archive.display()))?;
+    fs::write(dest.join(COMPLETE_MARKER), &hash)?;
+    info!("Extracted {} to {}", archive.display(), dest.display());
+    resource_root(&dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn write_tar_gz(path: &Path, files: &[(&str, &str)]) {
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_archive_keyed_by_content() -> Result<()> {
+        let temp = TempDir::new()?;
+        let cache = temp.path().join("archives");
+        let archive = temp.path().join("skin.tar.gz");
+        write_tar_gz(
+            &archive,
+            &[("skin-v2/res/values/colors.xml", "<resources />")],
+        );
+        assert!(is_archive(&archive) && !is_archive(temp.path()));
+
+        let res = extract_archive(&archive, &cache)?;
+        assert!(res.ends_with("skin-v2/res"));
+        assert!(res.join("values/colors.xml").is_file());
+        assert_eq!(extract_archive(&archive, &cache)?, res);
+
+        write_tar_gz(
+            &archive,
+            &[("values/colors.xml", "<resources></resources>")],
+        );
+        let changed = extract_archive(&archive, &cache)?;
+        assert_ne!(changed, res);
+        assert!(changed.join("values/colors.xml").is_file());
+        assert!(!res.exists(), "stale extraction should be removed");
+
+        let zip_path = temp.path().join("handoff.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        zip.start_file(
+            "drawable/logo.xml",
+            zip::write::SimpleFileOptions::default(),
+        )?;
+        zip.write_all(b"<shape />")?;
+        zip.finish()?;
+        let res = extract_archive(&zip_path, &cache)?;
+        assert!(res.join("drawable/logo.xml").is_file());
+        Ok(())
+    }
+}
@@ -0,0 +1,857 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+// Chunk types of the Android resource format (frameworks/base ResourceTypes.h)
+pub(crate) const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_TABLE_TYPE: u16 = 0x0002;
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+
+const UTF8_FLAG: u32 = 1 << 8;
+const NO_ENTRY: u32 = 0xFFFF_FFFF;
+
+// ResTable_type flags
+const TYPE_FLAG_SPARSE: u8 = 0x01;
+const TYPE_FLAG_OFFSET16: u8 = 0x02;
+
+// ResTable_entry flags
+const ENTRY_FLAG_COMPLEX: u16 = 0x0001;
+const ENTRY_FLAG_COMPACT: u16 = 0x0008;
+
+// Res_value data types
+pub const TYPE_NULL: u8 = 0x00;
+pub const TYPE_REFERENCE: u8 = 0x01;
+pub const TYPE_ATTRIBUTE: u8 = 0x02;
+pub const TYPE_STRING: u8 = 0x03;
+pub const TYPE_FLOAT: u8 = 0x04;
+pub const TYPE_DIMENSION: u8 = 0x05;
+pub const TYPE_FRACTION: u8 = 0x06;
+pub const TYPE_DYNAMIC_REFERENCE: u8 = 0x07;
+pub const TYPE_DYNAMIC_ATTRIBUTE: u8 = 0x08;
+pub const TYPE_INT_DEC: u8 = 0x10;
+pub const TYPE_INT_HEX: u8 = 0x11;
+pub const TYPE_INT_BOOLEAN: u8 = 0x12;
+pub const TYPE_INT_COLOR_ARGB8: u8 = 0x1c;
+pub const TYPE_INT_COLOR_RGB8: u8 = 0x1d;
+pub const TYPE_INT_COLOR_ARGB4: u8 = 0x1e;
+pub const TYPE_INT_COLOR_RGB4: u8 = 0x1f;
+
+pub(crate) fn read_u8(data: &[u8], offset: usize) -> Result<u8> {
+    data.get(offset)
+        .copied()
+        .with_context(|| format!("Unexpected end of data at offset {}", offset))
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .with_context(|| format!("Unexpected end of data at offset {}", offset))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .with_context(|| format!("Unexpected end of data at offset {}", offset))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Header of a chunk: type, header size and total size
+pub(crate) fn chunk_header(data: &[u8], offset: usize) -> Result<(u16, usize, usize)> {
+    let chunk_type = read_u16(data, offset)?;
+    let header_size = read_u16(data, offset + 2)? as usize;
+    let size = read_u32(data, offset + 4)? as usize;
+    if size < 8 || header_size < 8 || offset + size > data.len() {
+        anyhow::bail!(
+            "Malformed chunk 0x{:04x} at offset {} (size {})",
+            chunk_type,
+            offset,
+            size
+        );
+    }
+    Ok((chunk_type, header_size, size))
+}
+
+/// Length prefix of a UTF-8 string: one byte, or two when the high bit is set
+fn utf8_length(data: &[u8], offset: usize) -> Result<(usize, usize)> {
+    let first = read_u8(data, offset)? as usize;
+    if first & 0x80 != 0 {
+        let second = read_u8(data, offset + 1)? as usize;
+        Ok((((first & 0x7f) << 8) | second, 2))
+    } else {
+        Ok((first, 1))
+    }
+}
+
+/// Length prefix of a UTF-16 string: one unit, or two when the high bit is set
+fn utf16_length(data: &[u8], offset: usize) -> Result<(usize, usize)> {
+    let first = read_u16(data, offset)? as usize;
+    if first & 0x8000 != 0 {
+        let second = read_u16(data, offset + 2)? as usize;
+        Ok((((first & 0x7fff) << 16) | second, 4))
+    } else {
+        Ok((first, 2))
+    }
+}
+
+/// Strings of a string pool chunk at `offset`; style spans are not kept
+pub(crate) fn parse_string_pool(data: &[u8], offset: usize) -> Result<Vec<String>> {
+    let (chunk_type, _, _) = chunk_header(data, offset)?;
+    if chunk_type != RES_STRING_POOL_TYPE {
+        anyhow::bail!("Expected a string pool at offset {}", offset);
+    }
+    let count = read_u32(data, offset + 8)? as usize;
+    let flags = read_u32(data, offset + 16)?;
+    let strings_start = offset + read_u32(data, offset + 20)? as usize;
+    let utf8 = flags & UTF8_FLAG != 0;
+
+    let mut strings = Vec::with_capacity(count);
+    for index in 0..count {
+        let start = strings_start + read_u32(data, offset + 28 + index * 4)? as usize;
+        let string = if utf8 {
+            let (_, skip) = utf8_length(data, start)?;
+            let (bytes, skip_bytes) = utf8_length(data, start + skip)?;
+            let begin = start + skip + skip_bytes;
+            let bytes = data
+                .get(begin..begin + bytes)
+                .context("String pool entry out of bounds")?;
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            let (units, skip) = utf16_length(data, start)?;
+            let begin = start + skip;
+            let units: Vec<u16> = (0..units)
+                .map(|i| read_u16(data, begin + i * 2))
+                .collect::<Result<_>>()?;
+            String::from_utf16_lossy(&units)
+        };
+        strings.push(string);
+    }
+    Ok(strings)
+}
+
+/// A typed value (`Res_value`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResValue {
+    pub data_type: u8,
+    pub data: u32,
+}
+
+impl ResValue {
+    /// Read the 8-byte `Res_value` at `offset`
+    pub(crate) fn read(data: &[u8], offset: usize) -> Result<Self> {
+        Ok(Self {
+            data_type: read_u8(data, offset + 3)?,
+            data: read_u32(data, offset + 4)?,
+        })
+    }
+}
+
+/// Value of a resource entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryValue {
+    Simple(ResValue),
+    /// Bag of a style, attribute, array or plural: parent reference and (key, value) items
+    Complex {
+        parent: u32,
+        items: Vec<(u32, ResValue)>,
+    },
+}
+
+/// One configuration of one resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceEntry {
+    pub id: u32,
+    pub type_name: String,
+    pub name: String,
+    /// Qualifiers of the configuration, e.g. `night` or `zh-rCN-xxhdpi`; empty for default
+    pub qualifiers: String,
+    pub value: EntryValue,
+}
+
+/// A decoded `resources.arsc`
+#[derive(Debug, Default)]
+pub struct ResourceTable {
+    /// Global string pool holding string values and file paths
+    pub strings: Vec<String>,
+    /// Package IDs and names
+    pub packages: Vec<(u8, String)>,
+    pub entries: Vec<ResourceEntry>,
+}
+
+impl ResourceTable {
+    /// Decode a resource table
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (chunk_type, header_size, size) = chunk_header(data, 0)?;
+        if chunk_type != RES_TABLE_TYPE {
+            anyhow::bail!("Not a resource table (chunk type 0x{:04x})", chunk_type);
+        }
+        let mut table = ResourceTable::default();
+        let mut offset = header_size;
+        while offset < size {
+            let (chunk_type, _, chunk_size) = chunk_header(data, offset)?;
+            match chunk_type {
+                RES_STRING_POOL_TYPE => table.strings = parse_string_pool(data, offset)?,
+                RES_TABLE_PACKAGE_TYPE => table.parse_package(data, offset)?,
+                _ => {}
+            }
+            offset += chunk_size;
+        }
+        Ok(table)
+    }
+
+    fn parse_package(&mut self, data: &[u8], offset: usize) -> Result<()> {
+        let (_, header_size, size) = chunk_header(data, offset)?;
+        let package_id = read_u32(data, offset + 8)? as u8;
+        let name_units: Vec<u16> = (0..128)
+            .map(|i| read_u16(data, offset + 12 + i * 2))
+            .collect::<Result<_>>()?;
+        let name_len = name_units.iter().position(|&u| u == 0).unwrap_or(128);
+        let name = String::from_utf16_lossy(&name_units[..name_len]);
+        let type_strings =
+            parse_string_pool(data, offset + read_u32(data, offset + 268)? as usize)?;
+        let key_strings = parse_string_pool(data, offset + read_u32(data, offset + 276)? as usize)?;
+        self.packages.push((package_id, name));
+
+        let mut chunk = offset + header_size;
+        while chunk < offset + size {
+            let (chunk_type, _, chunk_size) = chunk_header(data, chunk)?;
+            if chunk_type == RES_TABLE_TYPE_TYPE {
+                self.parse_type(data, chunk, package_id, &type_strings, &key_strings)?;
+            }
+            chunk += chunk_size;
+        }
+        Ok(())
+    }
+
+    fn parse_type(
+        &mut self,
+        data: &[u8],
+        offset: usize,
+        package_id: u8,
+        type_strings: &[String],
+        key_strings: &[String],
+    ) -> Result<()> {
+        let (_, header_size, _) = chunk_header(data, offset)?;
+        let type_id = read_u8(data, offset + 8)?;
+        let flags = read_u8(data, offset + 9)?;
+        let entry_count = read_u32(data, offset + 12)? as usize;
+        let entries_start = offset + read_u32(data, offset + 16)? as usize;
+        let qualifiers = config_qualifiers(data, offset + 20)?;
+        let type_name = type_strings
+            .get((type_id as usize).wrapping_sub(1))
+            .with_context(|| format!("Unknown resource type {}", type_id))?
+            .clone();
+
+        // (entry index, offset from entries_start)
+        let index_start = offset + header_size;
+        let mut offsets = Vec::new();
+        if flags & TYPE_FLAG_SPARSE != 0 {
+            for i in 0..entry_count {
+                let index = read_u16(data, index_start + i * 4)? as usize;
+                let entry_offset = read_u16(data, index_start + i * 4 + 2)? as usize * 4;
+                offsets.push((index, entry_offset));
+            }
+        } else if flags & TYPE_FLAG_OFFSET16 != 0 {
+            for i in 0..entry_count {
+                let entry_offset = read_u16(data, index_start + i * 2)?;
+                if entry_offset != 0xFFFF {
+                    offsets.push((i, entry_offset as usize * 4));
+                }
+            }
+        } else {
+            for i in 0..entry_count {
+                let entry_offset = read_u32(data, index_start + i * 4)?;
+                if entry_offset != NO_ENTRY {
+                    offsets.push((i, entry_offset as usize));
+                }
+            }
+        }
+
+        for (index, entry_offset) in offsets {
+            let entry = entries_start + entry_offset;
+            let entry_flags = read_u16(data, entry + 2)?;
+            let (key, value) = if entry_flags & ENTRY_FLAG_COMPACT != 0 {
+                let key = read_u16(data, entry)? as usize;
+                let value = ResValue {
+                    data_type: (entry_flags >> 8) as u8,
+                    data: read_u32(data, entry + 4)?,
+                };
+                (key, EntryValue::Simple(value))
+            } else {
+                let entry_size = read_u16(data, entry)? as usize;
+                let key = read_u32(data, entry + 4)? as usize;
+                if entry_flags & ENTRY_FLAG_COMPLEX != 0 {
+                    let parent = read_u32(data, entry + 8)?;
+                    let count = read_u32(data, entry + 12)? as usize;
+                    let items = (0..count)
+                        .map(|i| {
+                            let item = entry + entry_size + i * 12;
+                            Ok((read_u32(data, item)?, ResValue::read(data, item + 4)?))
+                        })
+                        .collect::<Result<_>>()?;
+                    (key, EntryValue::Complex { parent, items })
+                } else {
+                    (
+                        key,
+                        EntryValue::Simple(ResValue::read(data, entry + entry_size)?),
+                    )
+                }
+            };
+            self.entries.push(ResourceEntry {
+                id: (package_id as u32) << 24 | (type_id as u32) << 16 | index as u32,
+                type_name: type_name.clone(),
+                name: key_strings
+                    .get(key)
+                    .with_context(|| format!("Unknown resource key {}", key))?
+                    .clone(),
+                qualifiers: qualifiers.clone(),
+                value,
+            });
+        }
+        Ok(())
+    }
+
+    /// `type/name` of every resource, by ID
+    pub fn names(&self) -> HashMap<u32, (String, String)> {
+        self.entries
+            .iter()
+            .map(|e| (e.id, (e.type_name.clone(), e.name.clone())))
+            .collect()
+    }
+}
+
+/// Resource names used to turn references back into `@type/name`
+#[derive(Debug, Default)]
+pub struct ResourceNames {
+    /// Resources of the decoded package, by ID
+    pub own: HashMap<u32, (String, String)>,
+    /// Framework resources (package 0x01), read from android.jar
+    pub framework: HashMap<u32, (String, String)>,
+}
+
+impl ResourceNames {
+    /// Reference to the resource with `id`, prefixed with `@` or `?` (for attributes);
+    /// IDs without a known name are written as `@0x7f010000`
+    pub fn reference(&self, prefix: char, id: u32) -> String {
+        if id == 0 {
+            return "@null".to_string();
+        }
+        if let Some((resource_type, name)) = self.own.get(&id) {
+            return format!("{}{}/{}", prefix, resource_type, name);
+        }
+        if let Some((resource_type, name)) = self.framework.get(&id) {
+            return format!("{}android:{}/{}", prefix, resource_type, name);
+        }
+        format!("{}0x{:08x}", prefix, id)
+    }
+
+    /// Name of an attribute used as a style item or XML attribute: `android:textColor` for
+    /// framework attributes, the bare name for the package's own
+    pub fn attribute_name(&self, id: u32) -> Option<String> {
+        if let Some((_, name)) = self.own.get(&id) {
+            return Some(name.clone());
+        }
+        self.framework
+            .get(&id)
+            .map(|(_, name)| format!("android:{}", name))
+    }
+}
+
+/// Complex unit multipliers (`Res_value` radix)
+const RADIX_MULTIPLIERS: [f64; 4] = [
+    1.0 / (1u64 << 8) as f64,
+    1.0 / (1u64 << 15) as f64,
+    1.0 / (1u64 << 23) as f64,
+    1.0 / (1u64 << 31) as f64,
+];
+
+/// Number of a dimension or fraction
+fn complex_value(data: u32) -> f64 {
+    (data & 0xFFFF_FF00) as i32 as f64 * RADIX_MULTIPLIERS[((data >> 4) & 0x3) as usize]
+}
+
+/// Shortest decimal form of a float, with at most 4 decimals
+fn format_number(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Text of a typed value as written in resource XML
+///
+/// String values are looked up in `strings` and returned raw; callers escape them for the
+/// place they are written to.
+pub fn format_value(value: &ResValue, strings: &[String], names: &ResourceNames) -> String {
+    let data = value.data;
+    match value.data_type {
+        TYPE_NULL if data == 1 => "@empty".to_string(),
+        TYPE_NULL => "@null".to_string(),
+        TYPE_REFERENCE | TYPE_DYNAMIC_REFERENCE => names.reference('@', data),
+        TYPE_ATTRIBUTE | TYPE_DYNAMIC_ATTRIBUTE => names.reference('?', data),
+        TYPE_STRING => strings.get(data as usize).cloned().unwrap_or_default(),
+        TYPE_FLOAT => format_number(f32::from_bits(data) as f64),
+        TYPE_DIMENSION => {
+            let unit = match data & 0xF {
+                0 => "px",
+                1 => "dp",
+                2 => "sp",
+                3 => "pt",
+                4 => "in",
+                5 => "mm",
+                _ => "",
+            };
+            format!("{}{}", format_number(complex_value(data)), unit)
+        }
+        TYPE_FRACTION => {
+            let unit = if data & 0xF == 1 { "%p" } else { "%" };
+            format!("{}{}", format_number(complex_value(data) * 100.0), unit)
+        }
+        TYPE_INT_HEX => format!("0x{:x}", data),
+        TYPE_INT_BOOLEAN => (data != 0).to_string(),
+        TYPE_INT_COLOR_ARGB8 => format!("#{:08x}", data),
+        TYPE_INT_COLOR_RGB8 => format!("#{:06x}", data & 0xFF_FFFF),
+        TYPE_INT_COLOR_ARGB4 => format!(
+            "#{:x}{:x}{:x}{:x}",
+            (data >> 28) & 0xF,
+            (data >> 20) & 0xF,
+            (data >> 12) & 0xF,
+            (data >> 4) & 0xF
+        ),
+        TYPE_INT_COLOR_RGB4 => format!(
+            "#{:x}{:x}{:x}",
+            (data >> 20) & 0xF,
+            (data >> 12) & 0xF,
+            (data >> 4) & 0xF
+        ),
+        TYPE_INT_DEC => (data as i32).to_string(),
+        _ => format!("0x{:08x}", data),
+    }
+}
+
+/// Two-letter code, or three letters packed into two bytes, of a locale field
+fn locale_code(bytes: [u8; 2], base: u8) -> String {
+    if bytes[0] == 0 {
+        return String::new();
+    }
+    if bytes[0] & 0x80 == 0 {
+        return bytes.iter().map(|&b| b as char).collect();
+    }
+    let first = bytes[1] & 0x1F;
+    let second = ((bytes[1] & 0xE0) >> 5) | ((bytes[0] & 0x03) << 3);
+    let third = (bytes[0] & 0x7C) >> 2;
+    [first, second, third]
+        .iter()
+        .map(|&c| (c + base) as char)
+        .collect()
+}
+
+/// Qualifier string of the `ResTable_config` at `offset`, in resource directory order
+pub(crate) fn config_qualifiers(data: &[u8], offset: usize) -> Result<String> {
+    let size = read_u32(data, offset)? as usize;
+    let byte = |at: usize| -> u8 {
+        if at < size {
+            data.get(offset + at).copied().unwrap_or(0)
+        } else {
+            0
+        }
+    };
+    let short = |at: usize| -> u16 { u16::from_le_bytes([byte(at), byte(at + 1)]) };
+
+    let mcc = short(4);
+    let mnc = short(6);
+    let language = locale_code([byte(8), byte(9)], b'a');
+    let country = locale_code([byte(10), byte(11)], b'0');
+    let orientation = byte(12);
+    let touchscreen = byte(13);
+    let density = short(14);
+    let keyboard = byte(16);
+    let navigation = byte(17);
+    let input_flags = byte(18);
+    let screen_width = short(20);
+    let screen_height = short(22);
+    let sdk_version = short(24);
+    let screen_layout = byte(28);
+    let ui_mode = byte(29);
+    let smallest_width = short(30);
+    let width_dp = short(32);
+    let height_dp = short(34);
+    let script: String = (36..40)
+        .map(byte)
+        .take_while(|&b| b != 0)
+        .map(|b| b as char)
+        .collect();
+    let variant: String = (40..48)
+        .map(byte)
+        .take_while(|&b| b != 0)
+        .map(|b| b as char)
+        .collect();
+    let screen_layout2 = byte(48);
+    let color_mode = byte(49);
+
+    let mut parts: Vec<String> = Vec::new();
+    if mcc != 0 {
+        parts.push(format!("mcc{:03}", mcc));
+        if mnc != 0 {
+            parts.push(format!("mnc{:02}", mnc));
+        }
+    }
+    if !language.is_empty() {
+        if script.is_empty() && variant.is_empty() {
+            if country.is_empty() {
+                parts.push(language);
+            } else {
+                parts.push(format!("{}-r{}", language, country));
+            }
+        } else {
+            let mut tag = format!("b+{}", language);
+            for part in [script, country, variant] {
+                if !part.is_empty() {
+                    tag.push('+');
+                    tag.push_str(&part);
+                }
+            }
+            parts.push(tag);
+        }
+    }
+    match screen_layout & 0xC0 {
+        0x40 => parts.push("ldltr".to_string()),
+        0x80 => parts.push("ldrtl".to_string()),
+        _ => {}
+    }
+    if smallest_width != 0 {
+        parts.push(format!("sw{}dp", smallest_width));
+    }
+    if width_dp != 0 {
+        parts.push(format!("w{}dp", width_dp));
+    }
+    if height_dp != 0 {
+        parts.push(format!("h{}dp", height_dp));
+    }
+    let lookup = |value: u8, names: &[(u8, &str)]| {
+        names
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, name)| name.to_string())
+    };
+    parts.extend(lookup(
+        screen_layout & 0x0F,
+        &[(1, "small"), (2, "normal"), (3, "large"), (4, "xlarge")],
+    ));
+    parts.extend(lookup(
+        screen_layout & 0x30,
+        &[(0x10, "notlong"), (0x20, "long")],
+    ));
+    parts.extend(lookup(
+        screen_layout2 & 0x03,
+        &[(1, "notround"), (2, "round")],
+    ));
+    parts.extend(lookup(color_mode & 0x03, &[(1, "nowidecg"), (2, "widecg")]));
+    parts.extend(lookup(color_mode & 0x0C, &[(4, "lowdr"), (8, "highdr")]));
+    parts.extend(lookup(
+        orientation,
+        &[(1, "port"), (2, "land"), (3, "square")],
+    ));
+    parts.extend(lookup(
+        ui_mode & 0x0F,
+        &[
+            (2, "desk"),
+            (3, "car"),
+            (4, "television"),
+            (5, "appliance"),
+            (6, "watch"),
+            (7, "vrheadset"),
+        ],
+    ));
+    parts.extend(lookup(
+        ui_mode & 0x30,
+        &[(0x10, "notnight"), (0x20, "night")],
+    ));
+    match density {
+        0 => {}
+        120 => parts.push("ldpi".to_string()),
+        160 => parts.push("mdpi".to_string()),
+        213 => parts.push("tvdpi".to_string()),
+        240 => parts.push("hdpi".to_string()),
+        320 => parts.push("xhdpi".to_string()),
+        480 => parts.push("xxhdpi".to_string()),
+        640 => parts.push("xxxhdpi".to_string()),
+        0xFFFE => parts.push("anydpi".to_string()),
+        0xFFFF => parts.push("nodpi".to_string()),
+        dpi => parts.push(format!("{}dpi", dpi)),
+    }
+    parts.extend(lookup(
+        touchscreen,
+        &[(1, "notouch"), (2, "stylus"), (3, "finger")],
+    ));
+    parts.extend(lookup(
+        input_flags & 0x03,
+        &[(1, "keysexposed"), (2, "keyshidden"), (3, "keyssoft")],
+    ));
+    parts.extend(lookup(
+        keyboard,
+        &[(1, "nokeys"), (2, "qwerty"), (3, "12key")],
+    ));
+    parts.extend(lookup(
+        input_flags & 0x0C,
+        &[(4, "navexposed"), (8, "navhidden")],
+    ));
+    parts.extend(lookup(
+        navigation,
+        &[(1, "nonav"), (2, "dpad"), (3, "trackball"), (4, "wheel")],
+    ));
+    if screen_width != 0 && screen_height != 0 {
+        parts.push(format!("{}x{}", screen_width, screen_height));
+    }
+    if sdk_version != 0 {
+        parts.push(format!("v{}", sdk_version));
+    }
+    Ok(parts.join("-"))
+}
+
+/// Encoders for building resource chunks in tests
+#[cfg(test)]
+pub(crate) mod test_support {
+    /// A UTF-8 string pool chunk
+    pub fn string_pool(strings: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for string in strings {
+            offsets.push(data.len() as u32);
+            let units = string.encode_utf16().count();
+            data.push(units as u8);
+            data.push(string.len() as u8);
+            data.extend_from_slice(string.as_bytes());
+            data.push(0);
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        let header_size = 28u16;
+        let strings_start = header_size as u32 + 4 * strings.len() as u32;
+        let size = strings_start + data.len() as u32;
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&0x0001u16.to_le_bytes());
+        chunk.extend_from_slice(&header_size.to_le_bytes());
+        chunk.extend_from_slice(&size.to_le_bytes());
+        chunk.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&0u32.to_le_bytes());
+        chunk.extend_from_slice(&(1u32 << 8).to_le_bytes());
+        chunk.extend_from_slice(&strings_start.to_le_bytes());
+        chunk.extend_from_slice(&0u32.to_le_bytes());
+        for offset in offsets {
+            chunk.extend_from_slice(&offset.to_le_bytes());
+        }
+        chunk.extend_from_slice(&data);
+        chunk
+    }
+
+    /// A chunk of `chunk_type` with `header` after the 8-byte chunk header and `body` after it
+    pub fn chunk(chunk_type: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let header_size = 8 + header.len();
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&chunk_type.to_le_bytes());
+        chunk.extend_from_slice(&(header_size as u16).to_le_bytes());
+        chunk.extend_from_slice(&((header_size + body.len()) as u32).to_le_bytes());
+        chunk.extend_from_slice(header);
+        chunk.extend_from_slice(body);
+        chunk
+    }
+
+    /// An 8-byte `Res_value`
+    pub fn res_value(data_type: u8, data: u32) -> Vec<u8> {
+        let mut value = vec![8, 0, 0, data_type];
+        value.extend_from_slice(&data.to_le_bytes());
+        value
+    }
+
+    /// A simple entry with key index `key`
+    pub fn simple_entry(key: u32, data_type: u8, data: u32) -> Vec<u8> {
+        let mut entry = vec![8, 0, 0, 0];
+        entry.extend_from_slice(&key.to_le_bytes());
+        entry.extend_from_slice(&res_value(data_type, data));
+        entry
+    }
+
+    /// A bag entry with key index `key` and (attribute, value) items
+    pub fn bag_entry(key: u32, parent: u32, items: &[(u32, u8, u32)]) -> Vec<u8> {
+        let mut entry = vec![16, 0, 1, 0];
+        entry.extend_from_slice(&key.to_le_bytes());
+        entry.extend_from_slice(&parent.to_le_bytes());
+        entry.extend_from_slice(&(items.len() as u32).to_le_bytes());
+        for (attribute, data_type, data) in items {
+            entry.extend_from_slice(&attribute.to_le_bytes());
+            entry.extend_from_slice(&res_value(*data_type, *data));
+        }
+        entry
+    }
+
+    /// A type chunk of `type_id` whose config has only `ui_mode` set; `None` entries are absent
+    pub fn type_chunk(type_id: u8, ui_mode: u8, entries: &[Option<Vec<u8>>]) -> Vec<u8> {
+        let mut config = vec![0u8; 64];
+        config[..4].copy_from_slice(&64u32.to_le_bytes());
+        config[29] = ui_mode;
+
+        let mut offsets = Vec::new();
+        let mut body = Vec::new();
+        for entry in entries {
+            match entry {
+                Some(entry) => {
+                    offsets.extend_from_slice(&(body.len() as u32).to_le_bytes());
+                    body.extend_from_slice(entry);
+                }
+                None => offsets.extend_from_slice(&super::NO_ENTRY.to_le_bytes()),
+            }
+        }
+        let mut header = vec![type_id, 0, 0, 0];
+        header.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        let entries_start = 8 + 12 + 64 + offsets.len() as u32;
+        header.extend_from_slice(&entries_start.to_le_bytes());
+        header.extend_from_slice(&config);
+        let mut content = offsets;
+        content.extend_from_slice(&body);
+        chunk(super::RES_TABLE_TYPE_TYPE, &header, &content)
+    }
+
+    /// A resource table with global `strings` and one package holding `type_chunks`
+    pub fn resource_table(
+        strings: &[&str],
+        package_id: u8,
+        package_name: &str,
+        types: &[&str],
+        keys: &[&str],
+        type_chunks: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let types = string_pool(types);
+        let keys = string_pool(keys);
+        let mut header = Vec::new();
+        header.extend_from_slice(&(package_id as u32).to_le_bytes());
+        let mut name = [0u8; 256];
+        for (i, unit) in package_name.encode_utf16().enumerate() {
+            name[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        header.extend_from_slice(&name);
+        let header_size = 8 + 4 + 256 + 20;
+        header.extend_from_slice(&(header_size as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&((header_size + types.len()) as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        let mut package_body = types;
+        package_body.extend_from_slice(&keys);
+        for type_chunk in type_chunks {
+            package_body.extend_from_slice(type_chunk);
+        }
+        let package = chunk(super::RES_TABLE_PACKAGE_TYPE, &header, &package_body);
+
+        let mut body = string_pool(strings);
+        body.extend(package);
+        chunk(super::RES_TABLE_TYPE, &1u32.to_le_bytes(), &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+
+    /// A table of package 0x7f with `color/primary` (default and night) and `style/Skin`
+    fn table() -> Vec<u8> {
+        resource_table(
+            &[],
+            0x7f,
+            "com.skin",
+            &["color", "style"],
+            &["primary", "Skin"],
+            &[
+                type_chunk(
+                    1,
+                    0,
+                    &[Some(simple_entry(0, TYPE_INT_COLOR_RGB8, 0xff6200ee))],
+                ),
+                type_chunk(
+                    1,
+                    0x20,
+                    &[Some(simple_entry(0, TYPE_INT_COLOR_ARGB8, 0x80000000))],
+                ),
+                type_chunk(
+                    2,
+                    0,
+                    &[Some(bag_entry(
+                        1,
+                        0,
+                        &[(0x0101_0098, TYPE_REFERENCE, 0x7f01_0000)],
+                    ))],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_parse_resource_table() -> Result<()> {
+        let table = ResourceTable::parse(&table())?;
+        assert_eq!(table.packages, vec![(0x7f, "com.skin".to_string())]);
+        let found: Vec<(u32, &str, &str, &str)> = table
+            .entries
+            .iter()
+            .map(|e| {
+                (
+                    e.id,
+                    e.type_name.as_str(),
+                    e.name.as_str(),
+                    e.qualifiers.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0x7f01_0000, "color", "primary", ""),
+                (0x7f01_0000, "color", "primary", "night"),
+                (0x7f02_0000, "style", "Skin", ""),
+            ]
+        );
+
+        let mut names = ResourceNames {
+            own: table.names(),
+            ..Default::default()
+        };
+        names
+            .framework
+            .insert(0x0101_0098, ("attr".to_string(), "textColor".to_string()));
+        let EntryValue::Simple(primary) = table.entries[0].value else {
+            panic!("color/primary is a simple value");
+        };
+        assert_eq!(format_value(&primary, &table.strings, &names), "#6200ee");
+        let EntryValue::Complex { items, .. } = &table.entries[2].value else {
+            panic!("style/Skin is a bag");
+        };
+        assert_eq!(
+            names.attribute_name(items[0].0).unwrap(),
+            "android:textColor"
+        );
+        assert_eq!(
+            format_value(&items[0].1, &table.strings, &names),
+            "@color/primary"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_dimensions() {
+        let names = ResourceNames::default();
+        let value = |data_type, data| format_value(&ResValue { data_type, data }, &[], &names);
+        // 16dp: mantissa 16 with radix 23p0
+        assert_eq!(value(TYPE_DIMENSION, (16 << 8) | 1), "16dp");
+        // 1.5sp: mantissa 1.5 * 2^7 with radix 16p7
+        assert_eq!(value(TYPE_DIMENSION, (192 << 8) | (1 << 4) | 2), "1.5sp");
+        // 50%p: mantissa 0.5 * 2^15 with radix 8p15
+        assert_eq!(value(TYPE_FRACTION, (16384 << 8) | (2 << 4) | 1), "50%p");
+        assert_eq!(value(TYPE_INT_BOOLEAN, 0xFFFF_FFFF), "true");
+        assert_eq!(value(TYPE_INT_DEC, (-2i32) as u32), "-2");
+        assert_eq!(value(TYPE_REFERENCE, 0x7f05_0001), "@0x7f050001");
+    }
+}
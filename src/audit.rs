@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::aapt2::Aapt2;
+use crate::fingerprint::{Fingerprint, sha256};
+use crate::types::BuildConfig;
+use crate::vcs::VcsInfo;
+
+/// File name of the audit log, kept in each output directory
+pub const AUDIT_LOG_FILE: &str = "asb-audit.log";
+
+/// One package build, appended as a line of the audit log
+///
+/// Records are only ever appended, so every package asb produced can be traced back to who
+/// built it, from which config and inputs, and with which toolchain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// End of the build, RFC 3339
+    pub timestamp: String,
+    pub user: String,
+    pub package: String,
+    pub target: String,
+    pub success: bool,
+    pub asb_version: String,
+    /// SHA-256 of the resolved build config
+    pub config_hash: String,
+    /// Combined hash of config, inputs and toolchain, as in the package's fingerprint file
+    pub fingerprint: String,
+    /// SHA-256 of each input file, keyed by path
+    pub inputs: BTreeMap<String, String>,
+    /// Stamps of aapt2 and android.jar, and the aapt2 version
+    pub toolchain: BTreeMap<String, String>,
+    /// SHA-256 of each file the build wrote, keyed by path; empty for failed builds
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsInfo>,
+}
+
+/// Audit log of a package
+pub fn audit_log_path(config: &BuildConfig) -> PathBuf {
+    config.output_dir.join(AUDIT_LOG_FILE)
+}
+
+/// Login name of the user running the build
+fn current_user() -> String {
+    ["USER", "USERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+impl AuditRecord {
+    /// Record a finished build of `config`; outputs are hashed only when it succeeded
+    pub fn new(config: &BuildConfig, success: bool, vcs: Option<VcsInfo>) -> Result<Self> {
+        let fingerprint = Fingerprint::compute(config)?;
+        let mut toolchain = fingerprint.toolchain;
+        if let Ok(version) = Aapt2::new(config.aapt2_path.clone()).and_then(|a| a.version()) {
+            toolchain.insert("aapt2Version".to_string(), version);
+        }
+
+        let mut outputs = BTreeMap::new();
+        if success {
+            for path in config.declared_outputs() {
+                if path.is_file() {
+                    let content = std::fs::read(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    outputs.insert(path.display().to_string(), sha256(&content));
+                }
+            }
+        }
+
+        Ok(Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            user: current_user(),
+            package: config.package_name.clone(),
+            target: config.target(),
+            success,
+            asb_version: fingerprint.asb_version,
+            config_hash: fingerprint.config,
+            fingerprint: fingerprint.fingerprint,
+            inputs: fingerprint.inputs,
+            toolchain,
+            outputs,
+            vcs,
+        })
+    }
+
+    /// Append the record as one JSON line to `path`
+    pub fn append(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+        // One write per line, so records of parallel builds do not interleave
+        file.write_all(format!("{}\n", serde_json::to_string(self)?).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Read every record of the audit log at `path`, skipping lines that do not parse
+pub fn read_records(path: &Path) -> Result<Vec<AuditRecord>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read audit log {}", path.display()))?;
+    let mut records = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Which records `asb audit show` lists
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub package: Option<String>,
+    /// Builds on or after this date
+    pub since: Option<NaiveDate>,
+    /// Builds that wrote a file with this SHA-256, or a prefix of it
+    pub output_hash: Option<String>,
+    pub failed_only: bool,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        if self.package.as_ref().is_some_and(|p| p != &record.package) {
+            return false;
+        }
+        if let Some(since) = self.since {
+            let date = chrono::DateTime::parse_from_rfc3339(&record.timestamp)
+                .map(|t| t.date_naive())
+                .ok();
+            if date.is_none_or(|d| d < since) {
+                return false;
+            }
+        }
+        if let Some(hash) = &self.output_hash {
+            let hash = hash.to_ascii_lowercase();
+            if !record.outputs.values().any(|h| h.starts_with(&hash)) {
+                return false;
+            }
+        }
+        !(self.failed_only && record.success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(package: &str, timestamp: &str, output_hash: Option<&str>) -> AuditRecord {
+        AuditRecord {
+            timestamp: timestamp.to_string(),
+            user: "ci".to_string(),
+            package: package.to_string(),
+            target: format!(":{}", package),
+            success: output_hash.is_some(),
+            asb_version: "2.1.0".to_string(),
+            config_hash: "c0ffee".to_string(),
+            fingerprint: "f00d".to_string(),
+            inputs: BTreeMap::from([("res/values/colors.xml".to_string(), "abc".to_string())]),
+            toolchain: BTreeMap::new(),
+            outputs: output_hash
+                .map(|h| BTreeMap::from([("out/skin.skin".to_string(), h.to_string())]))
+                .unwrap_or_default(),
+            vcs: None,
+        }
+    }
+
+    #[test]
+    fn test_append_and_filter_records() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("out").join(AUDIT_LOG_FILE);
+        let records = [
+            record("com.skin.red", "2026-03-01T10:00:00+08:00", Some("aa11")),
+            record("com.skin.blue", "2026-05-01T10:00:00+08:00", Some("bb22")),
+            record("com.skin.red", "2026-06-01T10:00:00+08:00", None),
+        ];
+        for r in &records {
+            r.append(&path)?;
+        }
+        std::fs::write(&path, std::fs::read_to_string(&path)? + "not json\n")?;
+        let read = read_records(&path)?;
+        assert_eq!(read, records);
+
+        let count = |filter: AuditFilter| read.iter().filter(|r| filter.matches(r)).count();
+        assert_eq!(count(AuditFilter::default()), 3);
+        let red = AuditFilter {
+            package: Some("com.skin.red".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(count(red), 2);
+        let since = AuditFilter {
+            since: Some(NaiveDate::from_ymd_opt(2026, 4, 1).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(count(since), 2);
+        let by_hash = AuditFilter {
+            output_hash: Some("BB".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(count(by_hash), 1);
+        let failed = AuditFilter {
+            failed_only: true,
+            ..Default::default()
+        };
+        assert_eq!(count(failed), 1);
+        Ok(())
+    }
+}
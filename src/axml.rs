@@ -0,0 +1,256 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::arsc::{
+    RES_STRING_POOL_TYPE, ResValue, ResourceNames, chunk_header, format_value, parse_string_pool,
+    read_u16, read_u32,
+};
+use crate::manifest::escape_attr;
+
+// Chunk types of compiled XML (frameworks/base ResourceTypes.h)
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_END_NAMESPACE_TYPE: u16 = 0x0101;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_CDATA_TYPE: u16 = 0x0104;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+
+const NO_INDEX: u32 = 0xFFFF_FFFF;
+
+/// Whether `data` is compiled XML rather than a plain file
+pub fn is_binary_xml(data: &[u8]) -> bool {
+    read_u16(data, 0).is_ok_and(|t| t == RES_XML_TYPE) && read_u16(data, 2).is_ok_and(|s| s == 8)
+}
+
+/// Decode compiled XML back into indented source XML
+///
+/// Attribute values are written as text: references become `@type/name` through `names`,
+/// and attributes whose name was stripped from the string pool are named after their
+/// resource ID.
+pub fn decode_xml(data: &[u8], names: &ResourceNames) -> Result<String> {
+    let (chunk_type, header_size, size) = chunk_header(data, 0)?;
+    if chunk_type != RES_XML_TYPE {
+        anyhow::bail!("Not a compiled XML file (chunk type 0x{:04x})", chunk_type);
+    }
+
+    let mut strings: Vec<String> = Vec::new();
+    let mut resource_ids: Vec<u32> = Vec::new();
+    let mut prefixes: HashMap<String, String> = HashMap::new();
+    let mut pending_namespaces: Vec<(String, String)> = Vec::new();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let mut depth = 0usize;
+    // Whether the innermost open element has children, so it needs a separate end tag
+    let mut open_tags: Vec<bool> = Vec::new();
+
+    let string = |strings: &[String], index: u32| -> String {
+        if index == NO_INDEX {
+            String::new()
+        } else {
+            strings.get(index as usize).cloned().unwrap_or_default()
+        }
+    };
+    let indent = |depth: usize| "    ".repeat(depth);
+
+    let mut offset = header_size;
+    while offset < size {
+        let (chunk_type, chunk_header_size, chunk_size) = chunk_header(data, offset)?;
+        let ext = offset + chunk_header_size;
+        match chunk_type {
+            RES_STRING_POOL_TYPE => strings = parse_string_pool(data, offset)?,
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = (0..(chunk_size - chunk_header_size) / 4)
+                    .map(|i| read_u32(data, ext + i * 4))
+                    .collect::<Result<_>>()?;
+            }
+            RES_XML_START_NAMESPACE_TYPE => {
+                let prefix = string(&strings, read_u32(data, ext)?);
+                let uri = string(&strings, read_u32(data, ext + 4)?);
+                prefixes.insert(uri.clone(), prefix.clone());
+                pending_namespaces.push((prefix, uri));
+            }
+            RES_XML_END_NAMESPACE_TYPE => {}
+            RES_XML_START_ELEMENT_TYPE => {
+                if let Some(has_children) = open_tags.last_mut()
+                    && !*has_children
+                {
+                    *has_children = true;
+                    out.push_str(">\n");
+                }
+                let name = string(&strings, read_u32(data, ext + 4)?);
+                let attribute_start = read_u16(data, ext + 8)? as usize;
+                let attribute_size = read_u16(data, ext + 10)? as usize;
+                let attribute_count = read_u16(data, ext + 12)? as usize;
+
+                out.push_str(&format!("{}<{}", indent(depth), name));
+                for (prefix, uri) in pending_namespaces.drain(..) {
+                    out.push_str(&format!(
+                        "\n{}    xmlns:{}=\"{}\"",
+                        indent(depth),
+                        prefix,
+                        escape_attr(&uri)
+                    ));
+                }
+                for i in 0..attribute_count {
+                    let attribute = ext + attribute_start + i * attribute_size;
+                    let ns = string(&strings, read_u32(data, attribute)?);
+                    let name_index = read_u32(data, attribute + 4)?;
+                    let raw = read_u32(data, attribute + 8)?;
+                    let value = ResValue::read(data, attribute + 12)?;
+
+                    let local = string(&strings, name_index);
+                    let resource_id = resource_ids.get(name_index as usize).copied();
+                    let qualified = match (local.is_empty(), resource_id) {
+                        (true, Some(id)) => names
+                            .attribute_name(id)
+                            .unwrap_or_else(|| format!("attr_0x{:08x}", id)),
+                        _ => match prefixes.get(&ns) {
+                            Some(prefix) => format!("{}:{}", prefix, local),
+                            None => local,
+                        },
+                    };
+                    let text = if raw != NO_INDEX {
+                        string(&strings, raw)
+                    } else {
+                        format_value(&value, &strings, names)
+                    };
+                    out.push_str(&format!(
+                        "\n{}    {}=\"{}\"",
+                        indent(depth),
+                        qualified,
+                        escape_attr(&text)
+                    ));
+                }
+                open_tags.push(false);
+                depth += 1;
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                let name = string(&strings, read_u32(data, ext + 4)?);
+                depth = depth.saturating_sub(1);
+                if open_tags.pop() == Some(true) {
+                    out.push_str(&format!("{}</{}>\n", indent(depth), name));
+                } else {
+                    out.push_str(" />\n");
+                }
+            }
+            RES_XML_CDATA_TYPE => {
+                let text = string(&strings, read_u32(data, ext)?);
+                if !text.trim().is_empty() {
+                    if let Some(has_children) = open_tags.last_mut()
+                        && !*has_children
+                    {
+                        *has_children = true;
+                        out.push_str(">\n");
+                    }
+                    out.push_str(&format!("{}{}\n", indent(depth), escape_attr(text.trim())));
+                }
+            }
+            _ => {}
+        }
+        offset += chunk_size;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arsc::test_support::{chunk, res_value, string_pool};
+    use crate::arsc::{TYPE_INT_BOOLEAN, TYPE_REFERENCE};
+
+    /// Header of a node chunk: line number and comment
+    fn node_header() -> Vec<u8> {
+        let mut header = 1u32.to_le_bytes().to_vec();
+        header.extend_from_slice(&NO_INDEX.to_le_bytes());
+        header
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// `<selector xmlns:android=...><item android:drawable="@drawable/bg"
+    /// android:state_pressed="true" /></selector>`
+    fn selector() -> Vec<u8> {
+        let pool = string_pool(&[
+            "drawable",
+            "state_pressed",
+            "android",
+            "http://schemas.android.com/apk/res/android",
+            "selector",
+            "item",
+        ]);
+        let resource_map = chunk(
+            RES_XML_RESOURCE_MAP_TYPE,
+            &[],
+            &words(&[0x0101_0119, 0x0101_00a7]),
+        );
+        let namespace = words(&[2, 3]);
+        let element = |name: u32, attributes: &[(u32, Vec<u8>)]| {
+            let mut body = words(&[NO_INDEX, name]);
+            body.extend_from_slice(&20u16.to_le_bytes());
+            body.extend_from_slice(&20u16.to_le_bytes());
+            body.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+            body.extend_from_slice(&[0; 6]);
+            for (attribute, value) in attributes {
+                body.extend(words(&[3, *attribute, NO_INDEX]));
+                body.extend_from_slice(value);
+            }
+            chunk(RES_XML_START_ELEMENT_TYPE, &node_header(), &body)
+        };
+        let end = |name: u32| {
+            chunk(
+                RES_XML_END_ELEMENT_TYPE,
+                &node_header(),
+                &words(&[NO_INDEX, name]),
+            )
+        };
+
+        let mut body = pool;
+        body.extend(resource_map);
+        body.extend(chunk(
+            RES_XML_START_NAMESPACE_TYPE,
+            &node_header(),
+            &namespace,
+        ));
+        body.extend(element(4, &[]));
+        body.extend(element(
+            5,
+            &[
+                (0, res_value(TYPE_REFERENCE, 0x7f02_0000)),
+                (1, res_value(TYPE_INT_BOOLEAN, 0xFFFF_FFFF)),
+            ],
+        ));
+        body.extend(end(5));
+        body.extend(end(4));
+        body.extend(chunk(
+            RES_XML_END_NAMESPACE_TYPE,
+            &node_header(),
+            &namespace,
+        ));
+        chunk(RES_XML_TYPE, &[], &body)
+    }
+
+    #[test]
+    fn test_decode_xml() -> Result<()> {
+        let data = selector();
+        assert!(is_binary_xml(&data));
+        assert!(!is_binary_xml(b"<selector />"));
+
+        let mut names = ResourceNames::default();
+        names
+            .own
+            .insert(0x7f02_0000, ("drawable".to_string(), "bg".to_string()));
+        let xml = decode_xml(&data, &names)?;
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <selector\n    xmlns:android=\"http://schemas.android.com/apk/res/android\">\n    \
+             <item\n        android:drawable=\"@drawable/bg\"\n        \
+             android:state_pressed=\"true\" />\n\
+             </selector>\n"
+        );
+        roxmltree::Document::parse(&xml)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::aapt2::{Aapt2, LinkInputMode};
+use crate::builder::{create_minimal_manifest, find_resource_files};
+use crate::types::{BuildConfig, CompileResult};
+
+/// Files per aapt2 invocation in the batched compile strategy
+pub const DEFAULT_BATCH_SIZE: usize = 50;
+
+/// How resource files are handed to `aapt2 compile`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompileStrategy {
+    /// One invocation per resource file, as builds compile
+    PerFile,
+    /// One invocation per batch of files from the same resource directory
+    Batched,
+    /// One `aapt2 compile --dir` invocation per resource directory
+    /// (compiles every file of the directory, including ones builds skip)
+    Dir,
+}
+
+/// Matrix of strategies `asb bench` times
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub strategies: Vec<CompileStrategy>,
+    /// Worker thread counts each compile strategy runs with
+    pub workers: Vec<usize>,
+    pub link_modes: Vec<LinkInputMode>,
+    pub batch_size: usize,
+    /// Runs per combination; the median is reported
+    pub iterations: usize,
+}
+
+/// Timings of one compile strategy with one worker count
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileRun {
+    pub strategy: CompileStrategy,
+    pub workers: usize,
+    pub runs_ms: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Timings of one link input mode
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkRun {
+    pub mode: LinkInputMode,
+    pub runs_ms: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of `asb bench` for one package
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchReport {
+    pub package_name: String,
+    pub resource_dirs: usize,
+    pub resource_files: usize,
+    pub compile: Vec<CompileRun>,
+    pub link: Vec<LinkRun>,
+    /// Config fields to store, from the fastest successful runs
+    pub recommended: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BenchReport {
+    /// Fastest successful compile run
+    pub fn fastest_compile(&self) -> Option<&CompileRun> {
+        self.compile
+            .iter()
+            .filter_map(|run| run.median_ms.map(|ms| (ms, run)))
+            .min_by_key(|(ms, _)| *ms)
+            .map(|(_, run)| run)
+    }
+
+    /// Fastest successful link run
+    pub fn fastest_link(&self) -> Option<&LinkRun> {
+        self.link
+            .iter()
+            .filter_map(|run| run.median_ms.map(|ms| (ms, run)))
+            .min_by_key(|(ms, _)| *ms)
+            .map(|(_, run)| run)
+    }
+}
+
+/// Median of the given timings
+fn median(values: &[u64]) -> Option<u64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len().saturating_sub(1) / 2).copied()
+}
+
+/// Run `iterations` timed runs of `run`, stopping at the first failure
+fn time_runs(iterations: usize, mut run: impl FnMut() -> Result<()>) -> (Vec<u64>, Option<String>) {
+    let mut runs_ms = Vec::new();
+    for _ in 0..iterations.max(1) {
+        let started = Instant::now();
+        if let Err(e) = run() {
+            return (runs_ms, Some(format!("{:#}", e)));
+        }
+        runs_ms.push(started.elapsed().as_millis() as u64);
+    }
+    (runs_ms, None)
+}
+
+/// Turn a failed compile result into an error
+fn compile_outcome(result: Result<CompileResult>) -> Result<Vec<PathBuf>> {
+    let result = result?;
+    if !result.success {
+        anyhow::bail!("{}", result.errors.join("\n"));
+    }
+    Ok(result.flat_files)
+}
+
+/// Time the compile and link strategies of `options` on the resources of `config`
+///
+/// Intermediates go to `<buildDir>/bench/<packageName>` and are removed afterwards; the
+/// project's own build outputs and caches are left alone.
+pub async fn run_bench(config: &BuildConfig, options: &BenchOptions) -> Result<BenchReport> {
+    let aapt2_path = Aapt2::new(config.aapt2_path.clone())?.into_path();
+    let aapt2 = |mode: LinkInputMode| -> Result<Aapt2> {
+        Ok(Aapt2::new(Some(aapt2_path.clone()))?
+            .with_link_input_mode(mode)
+            .with_namespaced(config.namespaced.unwrap_or(false))
+            .with_compile_options(config.compile_options.clone().unwrap_or_default()))
+    };
+
+    // Main resources are the overlay, everything else is linked as base resources
+    let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+    let respect_gitignore = config.respect_gitignore.unwrap_or(false);
+    let mut resource_dirs: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+    for dir in config
+        .additional_resource_dirs
+        .iter()
+        .flatten()
+        .chain(std::iter::once(&config.resource_dir))
+    {
+        if dir.is_dir() {
+            resource_dirs.push((
+                dir.clone(),
+                find_resource_files(dir, follow_symlinks, respect_gitignore, None)?,
+            ));
+        } else {
+            warn!("Skipping missing resource directory {}", dir.display());
+        }
+    }
+    let resource_files = resource_dirs.iter().map(|(_, files)| files.len()).sum();
+    if resource_files == 0 {
+        anyhow::bail!("No resource files found for {}", config.package_name);
+    }
+
+    let bench_dir = config
+        .build_dir
+        .clone()
+        .unwrap_or_else(|| config.output_dir.join(".build"))
+        .join("bench")
+        .join(&config.package_name);
+    if bench_dir.exists() {
+        std::fs::remove_dir_all(&bench_dir)?;
+    }
+    let compiled_dir = |name: &str, idx: usize| bench_dir.join(name).join(idx.to_string());
+
+    let compiler = aapt2(LinkInputMode::default())?;
+    let mut compile = Vec::new();
+    for &strategy in &options.strategies {
+        for &workers in &options.workers {
+            info!("Compiling with {:?} and {} workers", strategy, workers);
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(workers.max(1))
+                .build()?;
+            let (runs_ms, error) = time_runs(options.iterations, || {
+                let out = bench_dir.join("compile");
+                if out.exists() {
+                    std::fs::remove_dir_all(&out)?;
+                }
+                pool.install(|| {
+                    resource_dirs
+                        .par_iter()
+                        .enumerate()
+                        .try_for_each(|(idx, (dir, files))| {
+                            let output_dir = out.join(idx.to_string());
+                            let result = match strategy {
+                                CompileStrategy::PerFile => {
+                                    compiler.compile_files_parallel(files, &output_dir)
+                                }
+                                CompileStrategy::Batched => compiler.compile_files_batched(
+                                    files,
+                                    &output_dir,
+                                    options.batch_size,
+                                ),
+                                CompileStrategy::Dir => compiler.compile_dir(dir, &output_dir),
+                            };
+                            compile_outcome(result).map(drop)
+                        })
+                })
+            });
+            compile.push(CompileRun {
+                strategy,
+                workers,
+                median_ms: if error.is_none() {
+                    median(&runs_ms)
+                } else {
+                    None
+                },
+                runs_ms,
+                error,
+            });
+        }
+    }
+
+    let mut link = Vec::new();
+    if !options.link_modes.is_empty() {
+        let android_jar = config
+            .android_jar
+            .as_ref()
+            .context("android_jar not set. Please configure it or ensure ANDROID_HOME is set.")?;
+        // Link inputs are compiled once, the way builds compile them
+        let mut flat_sets = Vec::new();
+        for (idx, (_, files)) in resource_dirs.iter().enumerate() {
+            let result = compiler.compile_files_parallel(files, &compiled_dir("link-inputs", idx));
+            flat_sets.push(compile_outcome(result).context("Failed to compile link inputs")?);
+        }
+        let overlay = flat_sets.pop().into_iter().collect::<Vec<_>>();
+        let base: Vec<PathBuf> = flat_sets.into_iter().flatten().collect();
+        let manifest = create_minimal_manifest(&config.package_name, &bench_dir)?;
+
+        for &mode in &options.link_modes {
+            info!("Linking with {:?} inputs", mode);
+            let linker = aapt2(mode)?;
+            let link_dir = bench_dir.join(format!("link-{:?}", mode).to_lowercase());
+            std::fs::create_dir_all(&link_dir)?;
+            // Linking is async, so runs are timed here instead of with `time_runs`
+            let mut runs_ms = Vec::new();
+            let mut error = None;
+            for _ in 0..options.iterations.max(1) {
+                let started = Instant::now();
+                let result = linker
+                    .link_with_overlays(
+                        &base,
+                        &overlay,
+                        &manifest,
+                        android_jar,
+                        &link_dir.join("bench.skin"),
+                        Some(&config.package_name),
+                        None,
+                        None,
+                        None,
+                        config.package_id.as_deref(),
+                        None,
+                        Some(&link_dir),
+                        config.assets_dir.as_deref(),
+                    )
+                    .await;
+                match result {
+                    Ok(result) if result.success => {
+                        runs_ms.push(started.elapsed().as_millis() as u64)
+                    }
+                    Ok(result) => {
+                        error = Some(result.errors.join("\n"));
+                        break;
+                    }
+                    Err(e) => {
+                        error = Some(format!("{:#}", e));
+                        break;
+                    }
+                }
+            }
+            link.push(LinkRun {
+                mode,
+                median_ms: if error.is_none() {
+                    median(&runs_ms)
+                } else {
+                    None
+                },
+                runs_ms,
+                error,
+            });
+        }
+    }
+
+    if let Err(e) = std::fs::remove_dir_all(&bench_dir) {
+        warn!("Failed to remove {}: {}", bench_dir.display(), e);
+    }
+
+    let mut report = BenchReport {
+        package_name: config.package_name.clone(),
+        resource_dirs: resource_dirs.len(),
+        resource_files,
+        compile,
+        link,
+        recommended: serde_json::Map::new(),
+    };
+    if let Some(fastest) = report.fastest_link() {
+        let mode = serde_json::to_value(fastest.mode)?;
+        report.recommended.insert("linkInputMode".to_string(), mode);
+    }
+    Ok(report)
+}
+
+/// Short label of a bench run's timing for console output
+pub fn format_timing(median_ms: Option<u64>, error: Option<&str>) -> String {
+    match (median_ms, error) {
+        (Some(ms), _) => format!("{:.2}s", ms as f64 / 1000.0),
+        (None, Some(error)) => format!(
+            "failed: {}",
+            error.lines().next().unwrap_or_default().trim()
+        ),
+        (None, None) => "-".to_string(),
+    }
+}
+
+/// Default worker counts: the CPU core count and twice that, as builds use
+pub fn default_workers() -> Vec<usize> {
+    let cores = num_cpus::get();
+    vec![cores, cores * 2]
+}
+
+/// Directory name a compile strategy is shown with
+pub fn strategy_name(strategy: CompileStrategy) -> String {
+    strategy
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[300, 100, 200]), Some(200));
+        assert_eq!(median(&[400, 100]), Some(100));
+    }
+
+    #[test]
+    fn test_fastest_runs_skip_failures() {
+        let compile = |strategy, workers, median_ms: Option<u64>, error: Option<&str>| CompileRun {
+            strategy,
+            workers,
+            runs_ms: median_ms.into_iter().collect(),
+            median_ms,
+            error: error.map(str::to_string),
+        };
+        let link = |mode, median_ms: Option<u64>| LinkRun {
+            mode,
+            runs_ms: median_ms.into_iter().collect(),
+            median_ms,
+            error: median_ms
+                .is_none()
+                .then(|| "argument list too long".to_string()),
+        };
+        let report = BenchReport {
+            package_name: "com.example.skin".to_string(),
+            resource_dirs: 1,
+            resource_files: 10,
+            compile: vec![
+                compile(CompileStrategy::PerFile, 8, Some(900), None),
+                compile(CompileStrategy::Batched, 8, Some(400), None),
+                compile(CompileStrategy::Dir, 8, None, Some("boom")),
+            ],
+            link: vec![
+                link(LinkInputMode::Direct, None),
+                link(LinkInputMode::Argfile, Some(120)),
+                link(LinkInputMode::Zip, Some(150)),
+            ],
+            recommended: serde_json::Map::new(),
+        };
+        assert_eq!(
+            report.fastest_compile().map(|r| r.strategy),
+            Some(CompileStrategy::Batched)
+        );
+        assert_eq!(
+            report.fastest_link().map(|r| r.mode),
+            Some(LinkInputMode::Argfile)
+        );
+        assert_eq!(format_timing(Some(1500), None), "1.50s");
+        assert_eq!(
+            format_timing(None, Some("argument list too long\nmore")),
+            "failed: argument list too long"
+        );
+    }
+}
@@ -1,15 +1,95 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Semaphore;
 use tracing::{debug, info, warn};
 use walkdir::WalkDir;
 
-use crate::aapt2::Aapt2;
+use crate::aapt2::{
+    AAPT2_LOG_FILE, Aapt2, FLAT_SHARD_THRESHOLD, LinkInputMode, expected_flat_file, is_static_lib,
+};
 use crate::aar::AarExtractor;
-use crate::cache::BuildCache;
+use crate::archive::{extract_archive, is_archive};
+use crate::audit::{AuditRecord, audit_log_path};
+use crate::cache::{BuildCache, BuildDurations};
+use crate::compile_db::{CacheStatus, CompileCommand, write_compile_db};
+use crate::dark_mode::generate_dark_variants;
+use crate::dedupe::{SourceSet, dedupe_identical_resources};
+use crate::disk_space::dir_size;
+use crate::encoding::{EncodingIssue, check_encoding, to_utf8};
+use crate::events::{self, BuildEvent};
+use crate::feature_flags::generate_flag_resources;
+use crate::filenames::{RENAMED_FILES_EXTENSION, check_file_name, stage_renamed_files};
+use crate::fingerprint::Fingerprint;
+use crate::flat_registry::{FlatEntry, FlatRegistry};
+use crate::freeze::{FREEZE_IDS_FILE, check_frozen, merge_frozen_ids, read_ids, write_ids};
+use crate::golden::{GOLDEN_DIFF_EXTENSION, compare_golden_images};
+use crate::heartbeat::{BuildActivity, HeartbeatMonitor};
+use crate::hooks::{HookOutcome, HookStage, run_hooks};
+use crate::host_compat::{COMPAT_REPORT_EXTENSION, check_host_versions};
+use crate::launcher_icons::check_launcher_icons;
+use crate::locales::{check_locales, stage_normalized_locales};
+use crate::manifest::{create_merged_manifest, merge_aar_manifests};
+use crate::observer::{BuildObserver, BuildPhase};
+use crate::owners::{OWNERS_REPORT_EXTENSION, OwnedDir, Owners, ownership_report, wildcard_match};
+use crate::packaging::{PackageOptions, SkinPackager};
+use crate::plugins::{postprocess, preprocess};
+use crate::references::{
+    SymbolTable, did_you_mean, find_duplicate_definitions, find_unresolved,
+    suggest_missing_resources,
+};
+use crate::resource_ignore::{ResourceIgnore, is_ignore_file};
 use crate::resource_priority::ResourcePriority;
-use crate::types::{BuildConfig, BuildResult, CompileResult};
+use crate::resource_rewrite::{REWRITE_AUDIT_EXTENSION, RewriteRules, rewrite_resources};
+use crate::schedule::exclude_unscheduled;
+use crate::size_budget;
+use crate::staging::{normalize_staged_files, stage_resources};
+use crate::tokens::{extract_tokens, generate_token_resources};
+use crate::types::{
+    AarInfo, AarResourceFilter, BuildConfig, BuildResult, CompileResult, ManifestMode,
+    MissingResourceDirPolicy, VersionCodeStrategy, parse_package_id,
+};
+use crate::vcs::{VcsInfo, commit_count};
+use crate::xml_schema::validate_xml;
+
+/// Whether builds draw progress bars; turned off in non-interactive mode
+static PROGRESS_BARS: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable progress bars and spinners for all builds
+pub fn set_progress_bars(enabled: bool) {
+    PROGRESS_BARS.store(enabled, Ordering::Relaxed);
+}
+
+/// Create a progress bar that stays hidden when progress bars are disabled
+fn progress_bar(len: Option<u64>) -> ProgressBar {
+    let pb = match len {
+        Some(len) => ProgressBar::new(len),
+        None => ProgressBar::new_spinner(),
+    };
+    if !PROGRESS_BARS.load(Ordering::Relaxed) {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    pb
+}
+
+/// Run blocking work of an async build, such as waiting for the rayon compile pool or
+/// for hook commands
+/// On a multi-threaded runtime the worker thread hands its other tasks to another worker
+/// first, so builds sharing the runtime keep making progress.
+pub(crate) fn run_blocking<T>(work: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) if runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(work)
+        }
+        _ => work(),
+    }
+}
 
 /// Normalize a resource path by removing version qualifiers
 /// e.g., "res/drawable-v21/icon.xml" -> "res/drawable/icon.xml"
@@ -49,10 +129,62 @@ fn normalize_resource_path(path: &str) -> String {
     format!("res/{}/{}", normalized_type, parts[2..].join("/"))
 }
 
+/// Resource directory types accepted by `--only`
+pub const RESOURCE_TYPES: &[&str] = &[
+    "anim",
+    "animator",
+    "color",
+    "drawable",
+    "font",
+    "interpolator",
+    "layout",
+    "menu",
+    "mipmap",
+    "navigation",
+    "raw",
+    "transition",
+    "values",
+    "xml",
+];
+
+/// Reject resource types that are not resource directory names
+pub fn validate_resource_types(types: &[String]) -> Result<()> {
+    for resource_type in types {
+        if !RESOURCE_TYPES.contains(&resource_type.as_str()) {
+            anyhow::bail!(
+                "Unknown resource type '{}', expected one of: {}",
+                resource_type,
+                RESOURCE_TYPES.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file or directory under `res_dir` belongs to one of `types`
+/// e.g. "res/values-night/colors.xml" is a `values` resource
+fn is_selected_type(res_dir: &Path, path: &Path, types: &[String]) -> bool {
+    let Some(dir) = path
+        .strip_prefix(res_dir)
+        .ok()
+        .and_then(|p| p.components().next())
+        .and_then(|c| c.as_os_str().to_str())
+    else {
+        return false;
+    };
+    let resource_type = dir.split('-').next().unwrap_or(dir);
+    types.iter().any(|t| t == resource_type)
+}
+
 /// Check if the resource directories contain adaptive-icon resources
-fn has_adaptive_icon_resources(resource_dirs: &[PathBuf]) -> bool {
+fn has_adaptive_icon_resources(resource_dirs: &[PathBuf], follow_symlinks: bool) -> bool {
     for res_dir in resource_dirs {
-        for entry in WalkDir::new(res_dir).into_iter().filter_map(|e| e.ok()) {
+        // walkdir reports symlink loops as errors, which are skipped here
+        for entry in WalkDir::new(res_dir)
+            .follow_links(follow_symlinks)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
             let path = entry.path();
             if path.is_file()
                 && let Some(parent) = path.parent()
@@ -71,11 +203,32 @@ fn has_adaptive_icon_resources(resource_dirs: &[PathBuf]) -> bool {
     false
 }
 
+/// Identity of a directory on disk, used to detect symlink cycles and
+/// directories reachable through more than one link
+#[cfg(unix)]
+type DirIdentity = (u64, u64);
+#[cfg(not(unix))]
+type DirIdentity = PathBuf;
+
+/// Resolve the on-disk identity of a directory, following symlinks
+#[cfg(unix)]
+fn dir_identity(path: &Path) -> Option<DirIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Resolve the on-disk identity of a directory, following symlinks
+#[cfg(not(unix))]
+fn dir_identity(path: &Path) -> Option<DirIdentity> {
+    fs::canonicalize(path).ok()
+}
+
 /// Create a minimal AndroidManifest.xml as a cached file in compiled directory
 /// According to requirements, we only need: <manifest package="[package_name]"/>
 /// This is sufficient for resource-only skin packages
 /// The manifest is cached in the compiled_dir to avoid recreation
-fn create_minimal_manifest(package_name: &str, compiled_dir: &Path) -> Result<PathBuf> {
+pub(crate) fn create_minimal_manifest(package_name: &str, compiled_dir: &Path) -> Result<PathBuf> {
     // Cache manifest in compiled directory for persistence alongside .flat files
     fs::create_dir_all(compiled_dir)?;
     let cached_manifest = compiled_dir.join("AndroidManifest.xml");
@@ -99,31 +252,352 @@ fn create_minimal_manifest(package_name: &str, compiled_dir: &Path) -> Result<Pa
     Ok(cached_manifest)
 }
 
+/// Link-relevant configuration of a build, serialized for the incremental cache
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LinkSignature<'a> {
+    package_name: &'a str,
+    package_id: Option<&'a str>,
+    version_code: Option<u32>,
+    version_name: Option<&'a str>,
+    /// SHA-256 of the stable IDs file
+    stable_ids: Option<String>,
+    min_sdk_version: Option<u32>,
+    manifest_mode: ManifestMode,
+    manifest_placeholders: Option<&'a BTreeMap<String, String>>,
+    merge_aar_manifests: bool,
+    namespaced: bool,
+    link_input_mode: Option<LinkInputMode>,
+}
+
+fn link_signature(config: &BuildConfig, min_sdk_version: Option<u32>) -> Result<String> {
+    let stable_ids = match config.stable_ids_path() {
+        Some(path) if path.exists() => Some(format!("{:x}", Sha256::digest(fs::read(&path)?))),
+        _ => None,
+    };
+    Ok(serde_json::to_string(&LinkSignature {
+        package_name: &config.package_name,
+        package_id: config.package_id.as_deref(),
+        version_code: config.version_code,
+        version_name: config.version_name.as_deref(),
+        stable_ids,
+        min_sdk_version,
+        manifest_mode: config.manifest_mode.unwrap_or_default(),
+        manifest_placeholders: config.manifest_placeholders.as_ref(),
+        merge_aar_manifests: config.merge_aar_manifests.unwrap_or(false),
+        namespaced: config.namespaced.unwrap_or(false),
+        link_input_mode: config.link_input_mode,
+    })?)
+}
+
+/// Resolve a precompiled dependency entry into link inputs
+/// Accepts a `.flat` file, a static-lib `.apk`, or a directory containing them (not recursive)
+fn resolve_precompiled_dependency(path: &Path) -> Result<Vec<PathBuf>> {
+    let is_link_input =
+        |p: &Path| is_static_lib(p) || p.extension().is_some_and(|ext| ext == "flat");
+
+    if !path.exists() {
+        anyhow::bail!("Precompiled dependency not found: {}", path.display());
+    }
+
+    if path.is_file() {
+        if !is_link_input(path) {
+            anyhow::bail!(
+                "Precompiled dependency must be a .flat file, a static-lib .apk or a directory: {}",
+                path.display()
+            );
+        }
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_file() && is_link_input(&entry_path) {
+            inputs.push(entry_path);
+        }
+    }
+    if inputs.is_empty() {
+        anyhow::bail!(
+            "Precompiled dependency directory contains no .flat files or static-lib .apk files: {}",
+            path.display()
+        );
+    }
+    // Directory order is platform dependent; sort for a stable link order
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Compute a versionCode from a strategy
+/// `dir` locates the git repository for `git-commit-count`
+fn compute_version_code(strategy: &VersionCodeStrategy, dir: &Path) -> Result<u32> {
+    let (value, source): (u64, String) = match strategy {
+        VersionCodeStrategy::Fixed { value } => return Ok(*value),
+        VersionCodeStrategy::Timestamp => (
+            chrono::Utc::now().timestamp().try_into()?,
+            "timestamp".to_string(),
+        ),
+        VersionCodeStrategy::GitCommitCount { offset } => {
+            let count = commit_count(dir).ok_or_else(|| {
+                anyhow::anyhow!("Failed to count git commits for {}", dir.display())
+            })?;
+            (count + u64::from(*offset), "git commit count".to_string())
+        }
+        VersionCodeStrategy::FromEnv { variable } => {
+            let raw = std::env::var(variable)
+                .with_context(|| format!("Environment variable {} is not set", variable))?;
+            let value = raw.trim().parse().with_context(|| {
+                format!(
+                    "Environment variable {} is not a valid versionCode: {}",
+                    variable, raw
+                )
+            })?;
+            (value, format!("${}", variable))
+        }
+    };
+    u32::try_from(value).with_context(|| {
+        format!(
+            "versionCode {} from {} does not fit in 32 bits",
+            value, source
+        )
+    })
+}
+
+/// Find the resource files a build compiles in a directory, limited to `types` when given
+///
+/// Files excluded by `.asbignore` (and `.gitignore` with `respect_gitignore`) are skipped.
+pub(crate) fn find_resource_files(
+    res_dir: &Path,
+    follow_symlinks: bool,
+    respect_gitignore: bool,
+    types: Option<&[String]>,
+) -> Result<Vec<PathBuf>> {
+    let ignore = ResourceIgnore::load(res_dir, respect_gitignore)?;
+    walk_resource_files(res_dir, follow_symlinks, &ignore, types)
+}
+
+fn walk_resource_files(
+    res_dir: &Path,
+    follow_symlinks: bool,
+    ignore: &ResourceIgnore,
+    types: Option<&[String]>,
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut visited_dirs: HashSet<DirIdentity> = HashSet::new();
+
+    // min_depth(2) skips both the root directory (depth 0) and files directly
+    // under res_dir (depth 1), ensuring only files inside subdirectories are
+    // included. This avoids per-entry canonicalize() syscalls.
+    let mut walker = WalkDir::new(res_dir)
+        .min_depth(2)
+        .follow_links(follow_symlinks)
+        .into_iter();
+
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                if e.loop_ancestor().is_some() {
+                    warn!("Skipping symlink cycle: {}", e);
+                }
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            if ignore.is_ignored(entry.path(), true) {
+                walker.skip_current_dir();
+                continue;
+            }
+            // When following links, the same directory may be reachable through
+            // several paths; only descend into it the first time it is seen
+            if follow_symlinks
+                && let Some(identity) = dir_identity(entry.path())
+                && !visited_dirs.insert(identity)
+            {
+                debug!(
+                    "Skipping already visited directory: {}",
+                    entry.path().display()
+                );
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+
+        if let Some(types) = types
+            && !is_selected_type(res_dir, path, types)
+        {
+            // Files sit directly in their type directory; skip the rest of it
+            if entry.depth() == 2 {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+
+        if is_compiled_file(path) && !ignore.is_ignored(path, false) {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether a file inside a resource type directory is compiled by builds
+fn is_compiled_file(path: &Path) -> bool {
+    // Check if file is in a layout directory and skip it
+    if let Some(parent) = path.parent()
+        && let Some(parent_name) = parent.file_name().and_then(|n| n.to_str())
+    {
+        // Check for layout directories (layout, layout-land, layout-sw600dp, etc.)
+        if parent_name.starts_with("layout") {
+            debug!("Filtering out layout file: {}", path.display());
+            return false;
+        }
+    }
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    // Skip hidden files, system files, and specific resource files
+    if name.starts_with('.') || name == "Thumbs.db" {
+        return false;
+    }
+
+    // Filter out styles.xml, attrs.xml, and strings.xml
+    if name == "styles.xml" || name == "attrs.xml" || name == "strings.xml" {
+        debug!("Filtering out resource file: {}", path.display());
+        return false;
+    }
+    true
+}
+
+/// Resource files of the watched source directories, kept between builds of a long-running
+/// builder and updated from reported file changes instead of walking the directories again
+#[derive(Debug, Default)]
+struct TrackedFiles {
+    /// Watched directories as configured, with their absolute path
+    roots: Vec<(PathBuf, PathBuf)>,
+    /// Discovered files by root; a root without a list is walked on the next build
+    lists: HashMap<PathBuf, DiscoveredFiles>,
+}
+
+#[derive(Debug)]
+struct DiscoveredFiles {
+    files: Vec<PathBuf>,
+    /// Rules the files were discovered with
+    ignore: ResourceIgnore,
+}
+
+impl TrackedFiles {
+    /// Configured path of a reported absolute path, with the root it belongs to
+    fn resolve(&self, path: &Path) -> Option<(PathBuf, PathBuf)> {
+        self.roots.iter().find_map(|(root, absolute)| {
+            let relative = path.strip_prefix(absolute).ok()?;
+            Some((root.clone(), root.join(relative)))
+        })
+    }
+
+    /// Update the file lists for a created, modified or removed path
+    fn apply(&mut self, root: &Path, path: &Path) {
+        let Some(discovered) = self.lists.get_mut(root) else {
+            return;
+        };
+        let ignore_file = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(is_ignore_file);
+        if path.is_dir() || ignore_file {
+            // A directory moved in or changed rules: the files are not known yet
+            self.lists.remove(root);
+        } else if path.is_file() {
+            let depth = path
+                .strip_prefix(root)
+                .map_or(0, |p| p.components().count());
+            if depth >= 2
+                && is_compiled_file(path)
+                && !discovered.ignore.is_ignored(path, false)
+                && !discovered.files.iter().any(|f| f == path)
+            {
+                discovered.files.push(path.to_path_buf());
+            }
+        } else {
+            discovered.files.retain(|file| !file.starts_with(path));
+        }
+    }
+}
+
+/// Semaphores bounding the compile and link stages of configs built in parallel
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimits {
+    /// Permits for preparing and compiling resources
+    builds: Arc<Semaphore>,
+    /// Permits for aapt2 link; a build gives up its build permit while it waits for one
+    links: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new(max_builds: usize, max_links: usize) -> Self {
+        Self {
+            builds: Arc::new(Semaphore::new(max_builds.max(1))),
+            links: Arc::new(Semaphore::new(max_links.max(1))),
+        }
+    }
+}
+
 /// Main builder for Android skin packages
 pub struct SkinBuilder {
     config: BuildConfig,
+    /// Config as given, before the builder resolves anything; build fingerprints hash this
+    requested_config: BuildConfig,
     aapt2: Aapt2,
     cache: Option<BuildCache>,
+    limits: Option<ConcurrencyLimits>,
+    /// Resource compilations of the current build, for the compile database
+    compile_commands: Vec<CompileCommand>,
+    /// Set by `track_file_changes`
+    tracked_files: Option<TrackedFiles>,
+    /// Progress of the current build, for heartbeats and stall detection
+    activity: Arc<BuildActivity>,
+    /// Told about the progress of every build
+    observer: Option<Arc<dyn BuildObserver>>,
 }
 
 impl SkinBuilder {
     /// Create a new SkinBuilder
-    pub fn new(config: BuildConfig) -> Result<Self> {
-        let aapt2 = Aapt2::new(config.aapt2_path.clone())?;
+    pub fn new(mut config: BuildConfig) -> Result<Self> {
+        let requested_config = config.clone();
+        if let Some(package_id) = &config.package_id {
+            parse_package_id(package_id)?;
+        }
+        // An explicit versionCode always wins over a strategy
+        if config.version_code.is_some() {
+            config.version_code_strategy = None;
+        }
+
+        let activity = Arc::new(BuildActivity::new(&config.package_name));
+        let aapt2 = Aapt2::new(config.aapt2_path.clone())?
+            .with_link_input_mode(config.link_input_mode.unwrap_or_default())
+            .with_namespaced(config.namespaced.unwrap_or(false))
+            .with_compile_options(config.compile_options.clone().unwrap_or_default())
+            .with_limits(config.aapt2_limits.unwrap_or_default())
+            .with_activity(activity.clone());
 
         let cache = if config.incremental.unwrap_or(false) {
-            // Determine cache base directory with priority:
-            // 1. cache_dir (deprecated but takes precedence for backward compatibility)
-            // 2. build_dir
-            // 3. default to output_dir/.build
-            let base_cache_dir = config
-                .cache_dir
-                .clone()
-                .or_else(|| config.build_dir.clone())
-                .unwrap_or_else(|| config.output_dir.join(".build"));
-            let cache_dir = base_cache_dir.join(&config.package_name);
-            let cache = BuildCache::new(cache_dir)?;
+            let mut cache = BuildCache::new(config.cache_path())?;
             cache.init()?;
+            cache.set_compile_fingerprint(
+                config
+                    .compile_options
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?,
+            );
             Some(cache)
         } else {
             None
@@ -131,11 +605,83 @@ impl SkinBuilder {
 
         Ok(Self {
             config,
+            requested_config,
             aapt2,
             cache,
+            limits: None,
+            compile_commands: Vec::new(),
+            tracked_files: None,
+            activity,
+            observer: None,
         })
     }
 
+    /// Share compile and link concurrency limits with other builders running in parallel
+    pub fn with_concurrency_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Report build phases, compiled files, warnings and results to `observer`
+    #[allow(dead_code)]
+    pub fn with_observer(mut self, observer: Arc<dyn BuildObserver>) -> Self {
+        self.aapt2 = self
+            .aapt2
+            .with_observer(observer.clone(), &self.config.package_name);
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Record that the build entered `phase`
+    fn enter_phase(&self, phase: BuildPhase) {
+        self.activity.set_phase(&phase.to_string(), None);
+        if let Some(observer) = &self.observer {
+            observer.on_phase_start(&self.config.package_name, phase);
+        }
+    }
+
+    /// Keep discovered resource files and verified cache entries between builds
+    ///
+    /// For long-running builders whose caller watches the source directories: changes must
+    /// then be reported through `apply_file_changes`, and only the reported files are
+    /// rediscovered and hashed again. Returns the absolute directories to watch.
+    pub fn track_file_changes(&mut self) -> Result<Vec<PathBuf>> {
+        let mut roots = Vec::new();
+        for dir in std::iter::once(&self.config.resource_dir)
+            .chain(self.config.additional_resource_dirs.iter().flatten())
+        {
+            if dir.exists() {
+                roots.push((dir.clone(), std::path::absolute(dir)?));
+            }
+        }
+        if let Some(cache) = self.cache.as_mut() {
+            cache.track_changes(roots.iter().map(|(root, _)| root.clone()).collect());
+        }
+        let watched = roots.iter().map(|(_, absolute)| absolute.clone()).collect();
+        self.tracked_files = Some(TrackedFiles {
+            roots,
+            lists: HashMap::new(),
+        });
+        Ok(watched)
+    }
+
+    /// Report created, modified or removed paths (absolute) below the watched directories
+    pub fn apply_file_changes(&mut self, paths: &[PathBuf]) {
+        let Some(tracked) = self.tracked_files.as_mut() else {
+            return;
+        };
+        for path in paths {
+            let Some((root, path)) = tracked.resolve(path) else {
+                continue;
+            };
+            debug!("Changed: {}", path.display());
+            tracked.apply(&root, &path);
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(&path);
+            }
+        }
+    }
+
     /// Returns whether incremental build cache is enabled for this builder.
     pub fn has_cache(&self) -> bool {
         self.cache.is_some()
@@ -143,7 +689,287 @@ impl SkinBuilder {
 
     /// Build the skin package
     pub async fn build(&mut self) -> Result<BuildResult> {
+        let package = self.config.package_name.clone();
+        let started = std::time::Instant::now();
+        events::emit(BuildEvent::BuildStarted {
+            package: package.clone(),
+        });
+        let _heartbeat = HeartbeatMonitor::start(
+            self.activity.clone(),
+            self.config.heartbeat.unwrap_or_default(),
+        );
+
+        let vcs = self.capture_vcs();
+        let pre_build = self.run_hooks(HookStage::PreBuild);
+        let mut result = if !pre_build.errors.is_empty() {
+            Ok(BuildResult {
+                success: false,
+                apk_path: None,
+                errors: pre_build.errors,
+                warnings: pre_build.warnings,
+                build_duration: started.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
+            })
+        } else {
+            match self.resolve_version_code() {
+                Ok(()) => self
+                    .build_package(vcs.as_ref())
+                    .await
+                    .map(|build_result| BuildResult {
+                        vcs,
+                        warnings: [pre_build.warnings, build_result.warnings].concat(),
+                        ..build_result
+                    }),
+                Err(e) => Err(e),
+            }
+        };
+        // Warnings fail the build before anything records it as a release
+        if let Ok(build_result) = &mut result
+            && build_result.success
+            && !build_result.warnings.is_empty()
+            && self.requested_config.warnings_as_errors == Some(true)
+        {
+            build_result.success = false;
+            build_result.errors = std::mem::take(&mut build_result.warnings);
+            if let Some(apk_path) = build_result.apk_path.take() {
+                std::fs::remove_file(&apk_path).ok();
+            }
+        }
+        if let Ok(build_result) = &mut result
+            && build_result.success
+        {
+            let post_build = self.run_hooks(HookStage::PostBuild);
+            build_result.success = post_build.errors.is_empty();
+            build_result.errors.extend(post_build.errors);
+            build_result.warnings.extend(post_build.warnings);
+            // The package on disk was not signed or uploaded, so an earlier fingerprint
+            // must not report it as up to date
+            if !build_result.success {
+                std::fs::remove_file(Fingerprint::path(&self.requested_config)).ok();
+            }
+        }
+        // Only a build that made it through postBuild hooks counts as a release
+        if let Ok(build_result) = &result
+            && build_result.success
+            && let Err(e) = self.record_version_code()
+        {
+            warn!("Failed to record versionCode: {:#}", e);
+        }
+        // A partial `--only` build links cached files that may be stale, so it leaves no fingerprint
+        if let Ok(build_result) = &result
+            && build_result.success
+            && self.requested_config.only_resource_types.is_none()
+        {
+            let config = &self.requested_config;
+            match Fingerprint::compute(config).and_then(|f| f.write(config)) {
+                Ok(path) => debug!("Build fingerprint written to {}", path.display()),
+                Err(e) => warn!("Failed to write build fingerprint: {:#}", e),
+            }
+        }
+        // Recorded after postBuild hooks so output hashes cover the signed package
+        if self.requested_config.audit_log == Some(true) {
+            let config = &self.requested_config;
+            let (success, vcs) = match &result {
+                Ok(build_result) => (build_result.success, build_result.vcs.clone()),
+                Err(_) => (false, None),
+            };
+            let recorded = AuditRecord::new(config, success, vcs)
+                .and_then(|record| record.append(&audit_log_path(config)));
+            if let Err(e) = recorded {
+                let error = format!("Failed to append to the audit log: {:#}", e);
+                match &mut result {
+                    Ok(build_result) => {
+                        build_result.success = false;
+                        build_result.errors.push(error);
+                    }
+                    Err(_) => warn!("{}", error),
+                }
+            }
+        }
+
+        let success = match &result {
+            Ok(build_result) => {
+                for error in &build_result.errors {
+                    events::error_events(Some(&package), error)
+                        .into_iter()
+                        .for_each(events::emit);
+                }
+                build_result.success
+            }
+            Err(e) => {
+                events::error_events(Some(&package), &format!("{:#}", e))
+                    .into_iter()
+                    .for_each(events::emit);
+                false
+            }
+        };
+        events::emit(BuildEvent::BuildFinished {
+            package: package.clone(),
+            success,
+            duration_ms: started.elapsed().as_millis(),
+        });
+        if let Some(observer) = &self.observer {
+            if let Ok(build_result) = &result {
+                for warning in &build_result.warnings {
+                    observer.on_warning(&package, warning);
+                }
+            }
+            observer.on_package_done(&package, &result);
+        }
+
+        result
+    }
+
+    /// Run the config's hooks for a pipeline stage with the package's environment
+    /// Hooks work in scratch directories under the package's build directory, so parallel
+    /// builds of other packages never see their files.
+    fn run_hooks(&self, stage: HookStage) -> HookOutcome {
+        let Some(hooks) = &self.config.hooks else {
+            return HookOutcome::default();
+        };
+        self.enter_phase(BuildPhase::Hooks(stage));
+        let compiled_dir = self.compiled_dir();
+        let absolute = |path: &Path| {
+            std::path::absolute(path)
+                .unwrap_or_else(|_| path.to_path_buf())
+                .display()
+                .to_string()
+        };
+        let mut env = vec![
+            ("ASB_PACKAGE", self.config.package_name.clone()),
+            ("ASB_TARGET", self.config.target()),
+            ("ASB_OUTPUT", absolute(&self.config.output_apk_path())),
+            ("ASB_BUILD_DIR", absolute(&compiled_dir)),
+        ];
+        env.extend(self.config.app.clone().map(|app| ("ASB_APP", app)));
+        env.extend(
+            self.config
+                .flavor
+                .clone()
+                .map(|flavor| ("ASB_FLAVOR", flavor)),
+        );
+        if self.config.stage_resources.unwrap_or(false) {
+            env.push(("ASB_STAGING_DIR", absolute(&compiled_dir.join("staging"))));
+        }
+        // Hooks wait on their process, and exclusive ones on each other, for up to their
+        // timeout; other packages' builds keep the runtime meanwhile
+        run_blocking(|| run_hooks(hooks, stage, &env, &compiled_dir.join("hooks")))
+    }
+
+    /// Directory for this package's intermediate files
+    fn compiled_dir(&self) -> PathBuf {
+        self.config.compiled_dir.clone().unwrap_or_else(|| {
+            self.config
+                .build_dir
+                .clone()
+                .unwrap_or_else(|| self.config.output_dir.join(".build"))
+                .join(&self.config.package_name)
+        })
+    }
+
+    /// Compute versionCode from `versionCodeStrategy`, rejecting values lower than the
+    /// last successful build recorded in the incremental cache
+    fn resolve_version_code(&mut self) -> Result<()> {
+        let Some(strategy) = &self.config.version_code_strategy else {
+            return Ok(());
+        };
+        let version_code = compute_version_code(strategy, &self.config.resource_dir)?;
+
+        match self.cache.as_ref().and_then(|c| c.last_version_code()) {
+            Some(previous) if version_code < previous => anyhow::bail!(
+                "versionCode {} is lower than {} from the previous build of {}",
+                version_code,
+                previous,
+                self.config.package_name
+            ),
+            Some(_) => {}
+            None if self.cache.is_none() => {
+                debug!("Incremental cache disabled, skipping versionCode monotonicity check")
+            }
+            None => {}
+        }
+
+        info!("Using versionCode {}", version_code);
+        self.config.version_code = Some(version_code);
+        Ok(())
+    }
+
+    /// Remember the versionCode of a successful build for the next monotonicity check
+    fn record_version_code(&mut self) -> Result<()> {
+        if self.config.version_code_strategy.is_none() {
+            return Ok(());
+        }
+        if let (Some(cache), Some(version_code)) = (&mut self.cache, self.config.version_code) {
+            cache.set_last_version_code(version_code);
+            cache.save()?;
+        }
+        Ok(())
+    }
+
+    /// Remember phase durations so later multi-config builds can start long builds first
+    fn record_durations(&mut self, durations: BuildDurations) {
+        if let Some(cache) = &mut self.cache {
+            debug!(
+                "Build took {} ms compiling and {} ms linking",
+                durations.compile_ms, durations.link_ms
+            );
+            cache.set_last_durations(durations);
+            if let Err(e) = cache.save() {
+                warn!("Failed to record build durations: {:#}", e);
+            }
+        }
+    }
+
+    /// Remember how much disk space the build left behind, so `diskSpace` checks of later
+    /// builds can estimate what they need
+    fn record_disk_usage(&mut self, output_apk: &Path) {
+        if self.config.disk_space.is_none() {
+            return;
+        }
+        let bytes =
+            dir_size(&self.compiled_dir()) + std::fs::metadata(output_apk).map_or(0, |m| m.len());
+        if let Some(cache) = &mut self.cache {
+            cache.set_last_disk_usage(bytes);
+            if let Err(e) = cache.save() {
+                warn!("Failed to record disk usage: {:#}", e);
+            }
+        }
+    }
+
+    /// Capture git state when enabled and apply the versionName suffix
+    fn capture_vcs(&mut self) -> Option<VcsInfo> {
+        let vcs_config = self.config.vcs.clone()?;
+        if !vcs_config.enabled.unwrap_or(true) {
+            return None;
+        }
+        let info = VcsInfo::capture(&self.config.resource_dir)?;
+
+        if vcs_config.version_name_suffix.unwrap_or(false) {
+            let suffix = info.version_suffix();
+            match &mut self.config.version_name {
+                // Builders can be run more than once; only suffix once
+                Some(version_name) if !version_name.ends_with(&suffix) => {
+                    version_name.push_str(&suffix)
+                }
+                Some(_) => {}
+                None => debug!("No versionName set, skipping VCS version suffix"),
+            }
+        }
+
+        Some(info)
+    }
+
+    async fn build_package(&mut self, vcs: Option<&VcsInfo>) -> Result<BuildResult> {
         let build_start = std::time::Instant::now();
+        self.compile_commands.clear();
+        let build_permit = match &self.limits {
+            Some(limits) => Some(limits.builds.clone().acquire_owned().await?),
+            None => None,
+        };
+        let compile_start = std::time::Instant::now();
 
         // Determine number of phases for progress bar
         let has_aars = self
@@ -152,7 +978,7 @@ impl SkinBuilder {
             .as_ref()
             .is_some_and(|a| !a.is_empty());
         let phases = if has_aars { 4u64 } else { 3u64 };
-        let pb = ProgressBar::new(phases);
+        let pb = progress_bar(Some(phases));
         pb.set_style(
             ProgressStyle::default_bar()
                 .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg}")
@@ -200,6 +1026,122 @@ impl SkinBuilder {
             .unwrap_or_else(|| build_dir.join(&self.config.package_name));
         std::fs::create_dir_all(&compiled_dir)?;
         std::fs::create_dir_all(&self.config.output_dir)?;
+        if let Err(e) = self.aapt2.set_log_file(&compiled_dir.join(AAPT2_LOG_FILE)) {
+            warn!("{:#}", e);
+        }
+
+        // A resource archive is built from its extraction, reused until the archive changes
+        if is_archive(&self.requested_config.resource_dir) {
+            self.config.resource_dir = extract_archive(
+                &self.requested_config.resource_dir,
+                &compiled_dir.join("archives"),
+            )?;
+        }
+
+        // Validate precompiled dependencies before doing any work
+        let mut precompiled_inputs = Vec::new();
+        for path in self.config.precompiled_dependencies.iter().flatten() {
+            precompiled_inputs.push((path.clone(), resolve_precompiled_dependency(path)?));
+        }
+
+        // Feature flags become bool resources
+        let feature_flags_dir = match &self.config.feature_flags {
+            Some(flags) if !flags.is_empty() => {
+                let res_dir = compiled_dir.join("feature_flags_res");
+                let count = generate_flag_resources(flags, &res_dir)?;
+                info!("Generated {} feature flag resources", count);
+                Some(res_dir)
+            }
+            _ => None,
+        };
+
+        // Generate resources from design tokens before compiling
+        let design_token_dir = match &self.config.design_tokens {
+            Some(tokens_file) => {
+                let res_dir = compiled_dir.join("design_tokens_res");
+                let count = generate_token_resources(tokens_file, &res_dir)?;
+                info!(
+                    "Generated {} resources from design tokens {}",
+                    count,
+                    tokens_file.display()
+                );
+                Some(res_dir)
+            }
+            None => None,
+        };
+
+        // Generate night variants from the light resources and the color mapping
+        let dark_mode_dir = match &self.config.dark_mode {
+            Some(dark_mode) => {
+                let mut source_dirs: Vec<PathBuf> = self
+                    .config
+                    .additional_resource_dirs
+                    .clone()
+                    .unwrap_or_default();
+                source_dirs.extend(design_token_dir.clone());
+                source_dirs.push(self.config.resource_dir.clone());
+                let res_dir = compiled_dir.join("dark_mode_res");
+                let count = generate_dark_variants(&source_dirs, dark_mode, &res_dir)?;
+                info!(
+                    "Generated {} dark-mode resources from {}",
+                    count,
+                    dark_mode.color_mapping.display()
+                );
+                Some(res_dir)
+            }
+            None => None,
+        };
+
+        // A missing additional directory drops its resources from the skin
+        let mut warnings = Vec::new();
+        let missing_additional_dirs: Vec<&PathBuf> = self
+            .config
+            .additional_resource_dirs
+            .iter()
+            .flatten()
+            .filter(|dir| !dir.exists())
+            .collect();
+        if !missing_additional_dirs.is_empty() {
+            match self.config.missing_resource_dir_policy.unwrap_or_default() {
+                MissingResourceDirPolicy::Error => {
+                    pb.finish_with_message("Build failed: missing resource directories");
+                    return Ok(BuildResult {
+                        success: false,
+                        apk_path: None,
+                        errors: missing_additional_dirs
+                            .iter()
+                            .map(|dir| {
+                                format!(
+                                    "Additional resource directory not found: {} (set missingResourceDirPolicy to \"warn\" or \"ignore\" to build without it)",
+                                    dir.display()
+                                )
+                            })
+                            .collect(),
+                        warnings,
+                        build_duration: build_start.elapsed(),
+                        vcs: None,
+                        cached_files: 0,
+                        compiled_files: 0,
+                        normalization: None,
+                    });
+                }
+                MissingResourceDirPolicy::Warn => {
+                    for dir in &missing_additional_dirs {
+                        let warning = format!(
+                            "Additional resource directory not found, building without it: {}",
+                            dir.display()
+                        );
+                        warn!("{}", warning);
+                        warnings.push(warning);
+                    }
+                }
+                MissingResourceDirPolicy::Ignore => {
+                    for dir in &missing_additional_dirs {
+                        debug!("Skipping missing resource directory: {}", dir.display());
+                    }
+                }
+            }
+        }
 
         // Extract AAR files if provided - use build_dir for temp files
         let mut aar_infos = Vec::new();
@@ -210,7 +1152,35 @@ impl SkinBuilder {
         {
             pb.set_message("Extracting AARs...");
             info!("Extracting {} AAR files...", aar_files.len());
-            aar_infos = AarExtractor::extract_aars(aar_files, &temp_dir)?;
+            // Incremental builds keep extracted AARs and only extract changed ones again;
+            // the filters are part of the key since they change what an extraction holds
+            let filters_digest = match &self.config.aar_filters {
+                Some(filters) => format!(":{:x}", Sha256::digest(serde_json::to_string(filters)?)),
+                None => String::new(),
+            };
+            let aar_key = |hash: &str| format!("{}{}", hash, filters_digest);
+            let cache = self.cache.as_ref();
+            aar_infos =
+                AarExtractor::extract_aars(aar_files, &temp_dir, |aar, extract_dir, hash| {
+                    cache.is_some_and(|c| c.is_aar_extracted(aar, &aar_key(hash), extract_dir))
+                })?;
+            if let Some(filters) = &self.config.aar_filters {
+                let extracted: Vec<AarInfo> = aar_infos
+                    .iter()
+                    .filter(|aar| !aar.reused)
+                    .cloned()
+                    .collect();
+                self.filter_aar_resources(&extracted, filters)?;
+            }
+            if let Some(cache) = self.cache.as_mut() {
+                for aar_info in &aar_infos {
+                    cache.record_aar(
+                        &aar_info.path,
+                        &aar_key(&aar_info.sha256),
+                        &aar_info.extracted_dir,
+                    );
+                }
+            }
             pb.inc(1);
         }
 
@@ -255,13 +1225,225 @@ impl SkinBuilder {
             }
         }
 
+        // Generated resources rank above additional directories; main resources can still override them
+        let mut generated_idx = self
+            .config
+            .additional_resource_dirs
+            .as_ref()
+            .map_or(0, Vec::len);
+        for (res_dir, dir_name) in [
+            (feature_flags_dir, "feature_flags"),
+            (design_token_dir, "design_tokens"),
+            (dark_mode_dir, "dark_mode"),
+        ] {
+            if let Some(res_dir) = res_dir {
+                resource_dirs_with_priority.push((
+                    res_dir,
+                    ResourcePriority::Additional(generated_idx),
+                    dir_name.to_string(),
+                ));
+                generated_idx += 1;
+            }
+        }
+
         // Sort by priority (lowest to highest) so higher priority resources overwrite lower priority ones
         resource_dirs_with_priority.sort_by_key(|(_, priority, _)| priority.value());
 
+        // Attribute resources to teams from their original locations, before any rewrite
+        let mut owners_report = None;
+        if let Some(owners_path) = &self.config.owners {
+            let owners = Owners::load(owners_path)?;
+            let mut dirs = Vec::new();
+            for (res_dir, priority, dir_name) in &resource_dirs_with_priority {
+                if !res_dir.exists() {
+                    continue;
+                }
+                let source = match (priority, dir_name.as_str()) {
+                    (ResourcePriority::Library(idx), _) => Some(aar_infos[*idx].path.clone()),
+                    (_, "design_tokens") => self.config.design_tokens.clone(),
+                    (_, "dark_mode") => self
+                        .config
+                        .dark_mode
+                        .as_ref()
+                        .map(|d| d.color_mapping.clone()),
+                    _ => None,
+                };
+                dirs.push(OwnedDir {
+                    res_dir: res_dir.clone(),
+                    files: self.find_resource_files(res_dir)?,
+                    source,
+                });
+            }
+            owners_report = Some(ownership_report(&dirs, &owners)?);
+        }
+
+        // Compile from a per-package staging tree that later passes work on, so the source
+        // directories are never compiled directly
+        let staging_root = compiled_dir.join("staging");
+        let mut staged_sources = HashMap::new();
+        let convert_encodings = self.config.convert_encodings.unwrap_or(false);
+        let mut normalization = None;
+        if self.config.stage_resources.unwrap_or(false) || convert_encodings {
+            for (res_dir, _, dir_name) in resource_dirs_with_priority.iter_mut() {
+                let precompiled = self
+                    .config
+                    .precompiled_common_deps
+                    .as_ref()
+                    .is_some_and(|map| map.contains_key(res_dir));
+                if precompiled || !res_dir.exists() {
+                    continue;
+                }
+                let staging_dir = staging_root.join(&*dir_name);
+                let files = self.find_resource_files(res_dir)?;
+                staged_sources.extend(stage_resources(res_dir, &files, &staging_dir)?);
+                std::fs::create_dir_all(&staging_dir)?;
+                *res_dir = staging_dir;
+            }
+            info!(
+                "Staged {} resource files in {}",
+                staged_sources.len(),
+                staging_root.display()
+            );
+            // Reproducible packages need the same staged bytes on every OS
+            if PackageOptions::from_config(&self.config).reproducible {
+                let stats = normalize_staged_files(&staged_sources)?;
+                if stats.line_endings > 0 || stats.permissions > 0 {
+                    info!(
+                        "Normalized line endings of {} and permissions of {} staged files",
+                        stats.line_endings, stats.permissions
+                    );
+                }
+                normalization = Some(stats);
+            }
+        }
+
+        // aapt2 only reads UTF-8 without a byte order mark and its errors for anything else
+        // don't say so; staged copies of UTF-16 and BOM files are converted when configured
+        let mut encoding_errors = Vec::new();
+        let mut converted = 0;
+        for (res_dir, priority, _) in &resource_dirs_with_priority {
+            let precompiled = self
+                .config
+                .precompiled_common_deps
+                .as_ref()
+                .is_some_and(|map| map.contains_key(res_dir));
+            if precompiled || matches!(priority, ResourcePriority::Library(_)) || !res_dir.exists()
+            {
+                continue;
+            }
+            for file in self.find_resource_files(res_dir)? {
+                let Some(issue) = check_encoding(&file)? else {
+                    continue;
+                };
+                let source = staged_sources.get(&file).unwrap_or(&file);
+                if convert_encodings
+                    && res_dir.starts_with(&staging_root)
+                    && let Some(content) = to_utf8(&fs::read(&file)?, issue)
+                {
+                    // The staged file may be a hard link to the source
+                    fs::remove_file(&file)?;
+                    fs::write(&file, content)
+                        .with_context(|| format!("Failed to write {}", file.display()))?;
+                    info!(
+                        "Converted {} from {} to UTF-8",
+                        source.display(),
+                        issue.describe()
+                    );
+                    converted += 1;
+                } else {
+                    let fix = if matches!(issue, EncodingIssue::InvalidUtf8 { .. }) {
+                        "save it as UTF-8"
+                    } else {
+                        "save it as UTF-8 without BOM or set convertEncodings"
+                    };
+                    encoding_errors.push(format!(
+                        "{}: file is {}; aapt2 needs UTF-8 without a byte order mark, {}",
+                        source.display(),
+                        issue.describe(),
+                        fix
+                    ));
+                }
+            }
+        }
+        if converted > 0 {
+            info!("Converted {} resource files to UTF-8", converted);
+        }
+        if !encoding_errors.is_empty() {
+            if !self.has_cache() {
+                AarExtractor::cleanup_aars(&aar_infos)?;
+            }
+            pb.finish_with_message("Build failed: resource files not in UTF-8");
+            return Ok(BuildResult {
+                success: false,
+                apk_path: None,
+                errors: encoding_errors,
+                warnings,
+                build_duration: build_start.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
+            });
+        }
+
+        // Apply aliases and the resource prefix to the skin's own resources before compiling;
+        // library resources are left as they are
+        let rewrite_rules = RewriteRules {
+            prefix: self.config.resource_prefix.clone(),
+            aliases: self.config.aliases.clone().unwrap_or_default(),
+        };
+        let mut rewrite_audit = None;
+        if !rewrite_rules.is_empty() {
+            let mut inputs = Vec::new();
+            for (res_dir, priority, dir_name) in resource_dirs_with_priority.iter_mut() {
+                if matches!(priority, ResourcePriority::Library(_)) || !res_dir.exists() {
+                    continue;
+                }
+                let rewritten_dir = compiled_dir.join("rewritten_res").join(&*dir_name);
+                let files = self.find_resource_files(res_dir)?;
+                inputs.push((res_dir.clone(), files, rewritten_dir.clone()));
+                *res_dir = rewritten_dir;
+            }
+            let audit = rewrite_resources(&inputs, &rewrite_rules)?;
+            for (_, _, rewritten_dir) in &inputs {
+                std::fs::create_dir_all(rewritten_dir)?;
+            }
+            info!(
+                "Renamed {} resources, rewrote references in {} files",
+                audit.renamed.len(),
+                audit.rewritten.len()
+            );
+            rewrite_audit = Some(audit);
+        }
+
+        // Report unresolved references with their location before aapt2 fails on them
+        if self.config.validate_references.unwrap_or(false) {
+            let unresolved =
+                self.check_references(&resource_dirs_with_priority, &precompiled_inputs)?;
+            if !unresolved.is_empty() {
+                if !self.has_cache() {
+                    AarExtractor::cleanup_aars(&aar_infos)?;
+                }
+                pb.finish_with_message("Build failed: unresolved references");
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: unresolved,
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+        }
+
         // Compile resources - each to its own subdirectory to avoid conflicts
         pb.set_message("Compiling resources...");
+        self.enter_phase(BuildPhase::Compile);
         // Use a spinner substyle for indeterminate compilation count
-        let compile_spinner = ProgressBar::new_spinner();
+        let compile_spinner = progress_bar(None);
         compile_spinner.set_style(
             ProgressStyle::default_spinner()
                 .template("{spinner:.green} {msg}")
@@ -275,15 +1457,407 @@ impl SkinBuilder {
         let mut missing_dirs = Vec::new();
         let mut valid_resource_dirs = Vec::new();
 
-        // Track flat files by priority level for proper ordering
-        // Flat files will be collected per directory and ordered by priority
-        let mut flat_files_by_priority: Vec<(ResourcePriority, Vec<PathBuf>, PathBuf)> = Vec::new();
+        // `--only` compiles the given types and links everything else from the cache
+        let only_types = self.config.only_resource_types.clone();
+        let only_types = if only_types.is_some() && !self.has_cache() {
+            warn!("--only needs an incremental build cache, compiling all resource types");
+            None
+        } else {
+            only_types
+        };
+        if let Some(types) = &only_types {
+            info!("Compiling only {} resources", types.join(", "));
+        }
+
+        // Find the files of every directory that gets compiled, so resources copied
+        // unchanged into several source sets are compiled and linked only once
+        let namespaced = self.config.namespaced.unwrap_or(false);
+        let mut source_sets = Vec::new();
+        for (res_dir, priority, _) in &resource_dirs_with_priority {
+            let precompiled = self
+                .config
+                .precompiled_common_deps
+                .as_ref()
+                .is_some_and(|map| map.contains_key(res_dir));
+            if precompiled || !res_dir.exists() {
+                continue;
+            }
+            source_sets.push(SourceSet {
+                res_dir: res_dir.clone(),
+                priority: *priority,
+                files: self.find_resource_files_of_types(res_dir, only_types.as_deref())?,
+            });
+        }
+        // Seasonal files outside their window are left out of the skin's own directories
+        if let Some(rules) = self
+            .config
+            .scheduled_files
+            .as_ref()
+            .filter(|r| !r.is_empty())
+        {
+            let as_of = self.config.as_of_date()?;
+            let mut excluded = 0;
+            for set in &mut source_sets {
+                if matches!(set.priority, ResourcePriority::Library(_)) {
+                    continue;
+                }
+                let dropped = exclude_unscheduled(&set.res_dir, &mut set.files, rules, as_of)?;
+                for file in &dropped {
+                    debug!(
+                        "{} is outside its scheduled window, skipping it",
+                        file.display()
+                    );
+                }
+                excluded += dropped.len();
+            }
+            if excluded > 0 {
+                info!(
+                    "Left out {} scheduled resource files outside their window as of {}",
+                    excluded, as_of
+                );
+            }
+        }
+        // Strict mode reports resources defined twice within one of the skin's own directories
+        if self.config.is_strict() {
+            for set in &source_sets {
+                if !matches!(set.priority, ResourcePriority::Library(_)) {
+                    for warning in find_duplicate_definitions(&set.res_dir, &set.files)? {
+                        warn!("{}", warning);
+                        warnings.push(warning);
+                    }
+                }
+            }
+        }
+        if let Some(budget) = &self.config.size_budget {
+            for set in &source_sets {
+                if !matches!(set.priority, ResourcePriority::Library(_)) {
+                    for warning in size_budget::check_files(budget, &set.files) {
+                        warn!("{}", warning);
+                        warnings.push(warning);
+                    }
+                }
+            }
+        }
+        // Namespaced overlays may only override resources their base declares, so the
+        // base copy of a resource has to stay even when the overlay's is identical
+        let (mut dedupe_sets, kept_sets): (Vec<SourceSet>, Vec<SourceSet>) = source_sets
+            .into_iter()
+            .partition(|set| !namespaced || set.priority != ResourcePriority::Main);
+        let dedupe = dedupe_identical_resources(&mut dedupe_sets)?;
+        if !dedupe.duplicates.is_empty() {
+            for (file, kept) in &dedupe.duplicates {
+                debug!(
+                    "{} is identical to {}, skipping it",
+                    file.display(),
+                    kept.display()
+                );
+            }
+            info!(
+                "Skipped {} resource files identical to higher-priority copies ({:.1} KB not compiled or linked)",
+                dedupe.duplicates.len(),
+                dedupe.bytes as f64 / 1024.0
+            );
+        }
+        let mut files_by_dir: HashMap<PathBuf, Vec<PathBuf>> = dedupe_sets
+            .into_iter()
+            .chain(kept_sets)
+            .map(|set| (set.res_dir, set.files))
+            .collect();
+
+        // Preprocessor plugins may replace the files of every directory
+        if let Some(plugins) = &self.config.plugins {
+            for (res_dir, _, dir_name) in &resource_dirs_with_priority {
+                if let Some(files) = files_by_dir.remove(res_dir) {
+                    let work_root = compiled_dir.join("plugins").join(dir_name);
+                    let files = preprocess(
+                        plugins,
+                        &self.config.package_name,
+                        res_dir,
+                        files,
+                        &work_root,
+                    )?;
+                    files_by_dir.insert(res_dir.clone(), files);
+                }
+            }
+        }
+
+        // Report every schema violation of the skin's own XML at once; aapt2 stops at the
+        // first error of each file
+        if self.config.validate_xml.unwrap_or(false) {
+            let mut violations = Vec::new();
+            for (res_dir, priority, _) in &resource_dirs_with_priority {
+                if let Some(files) = files_by_dir.get(res_dir)
+                    && !matches!(priority, ResourcePriority::Library(_))
+                {
+                    violations.extend(validate_xml(files));
+                }
+            }
+            if !violations.is_empty() {
+                if !self.has_cache() {
+                    AarExtractor::cleanup_aars(&aar_infos)?;
+                }
+                compile_spinner.finish_and_clear();
+                pb.finish_with_message("Build failed: invalid resource XML");
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: violations
+                        .iter()
+                        .map(|violation| {
+                            let source = staged_sources
+                                .get(&violation.file)
+                                .unwrap_or(&violation.file);
+                            format!(
+                                "{}:{}: error: {}.",
+                                source.display(),
+                                violation.line,
+                                violation.message
+                            )
+                        })
+                        .collect(),
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+        }
+
+        // Fail on locale qualifiers aapt2 would reject, then compile copies of the files whose
+        // locale qualifier is not in the configured form
+        let mut invalid_locales = Vec::new();
+        for (res_dir, priority, _) in &resource_dirs_with_priority {
+            if let Some(files) = files_by_dir.get(res_dir)
+                && !matches!(priority, ResourcePriority::Library(_))
+            {
+                invalid_locales.extend(check_locales(files));
+            }
+        }
+        if !invalid_locales.is_empty() {
+            if !self.has_cache() {
+                AarExtractor::cleanup_aars(&aar_infos)?;
+            }
+            compile_spinner.finish_and_clear();
+            pb.finish_with_message("Build failed: invalid locale qualifiers");
+            return Ok(BuildResult {
+                success: false,
+                apk_path: None,
+                errors: invalid_locales,
+                warnings,
+                build_duration: build_start.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
+            });
+        }
+        if let Some(form) = self.config.locale_qualifiers {
+            let mut normalized = 0;
+            for (res_dir, _, dir_name) in &resource_dirs_with_priority {
+                if let Some(files) = files_by_dir.get_mut(res_dir) {
+                    let staging_dir = compiled_dir.join("locale_res").join(dir_name);
+                    for (from, to) in stage_normalized_locales(files, form, &staging_dir)? {
+                        debug!("Compiling {} as {}", from.display(), to.display());
+                        let source = staged_sources.get(&from).cloned().unwrap_or(from);
+                        staged_sources.insert(to, source);
+                        normalized += 1;
+                    }
+                }
+            }
+            if normalized > 0 {
+                info!(
+                    "Compiling {} resource files with {} locale qualifiers",
+                    normalized,
+                    form.name()
+                );
+            }
+        }
+
+        // Overridden launcher icons must come in every density; generated ones are compiled
+        // with the resource directory of the icon they were scaled from
+        if let Some(icons) = &self.config.launcher_icons {
+            let sets: Vec<(&Path, &[PathBuf])> = resource_dirs_with_priority
+                .iter()
+                .filter(|(_, priority, _)| !matches!(priority, ResourcePriority::Library(_)))
+                .filter_map(|(res_dir, _, _)| {
+                    files_by_dir
+                        .get(res_dir)
+                        .map(|files| (res_dir.as_path(), files.as_slice()))
+                })
+                .collect();
+            let check = check_launcher_icons(&sets, icons, &compiled_dir.join("launcher_icons"))?;
+            if !check.errors.is_empty() {
+                if !self.has_cache() {
+                    AarExtractor::cleanup_aars(&aar_infos)?;
+                }
+                compile_spinner.finish_and_clear();
+                pb.finish_with_message("Build failed: incomplete launcher icons");
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: check.errors,
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+            if !check.generated.is_empty() {
+                info!("Generated {} launcher icons", check.generated.len());
+            }
+            for (res_dir, icon) in check.generated {
+                files_by_dir.entry(res_dir).or_default().push(icon);
+            }
+        }
+
+        // Key drawables must still look like their golden images
+        if let Some(golden_images) = &self.config.golden_images {
+            let sets: Vec<(&Path, &[PathBuf])> = resource_dirs_with_priority
+                .iter()
+                .filter(|(_, priority, _)| !matches!(priority, ResourcePriority::Library(_)))
+                .filter_map(|(res_dir, _, _)| {
+                    files_by_dir
+                        .get(res_dir)
+                        .map(|files| (res_dir.as_path(), files.as_slice()))
+                })
+                .collect();
+            let colors = extract_tokens(&self.config)?.colors;
+            let diff_dir = golden_images.diff_dir.clone().unwrap_or_else(|| {
+                self.config
+                    .output_apk_path()
+                    .with_extension(GOLDEN_DIFF_EXTENSION)
+            });
+            let check = compare_golden_images(&sets, golden_images, &colors, &diff_dir)?;
+            if !check.errors.is_empty() {
+                if !self.has_cache() {
+                    AarExtractor::cleanup_aars(&aar_infos)?;
+                }
+                compile_spinner.finish_and_clear();
+                pb.finish_with_message("Build failed: drawables differ from golden images");
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: check.errors,
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+            info!("{} drawables match their golden images", check.compared);
+        }
+
+        // Check the skin's resources against every supported host version
+        let compat_report = match &self.config.host_versions {
+            Some(hosts) => {
+                let mut surface = SymbolTable::default();
+                for (res_dir, priority, _) in &resource_dirs_with_priority {
+                    if let Some(files) = files_by_dir.get(res_dir)
+                        && !matches!(priority, ResourcePriority::Library(_))
+                    {
+                        surface.add_resource_files(res_dir, files)?;
+                    }
+                }
+                let report = check_host_versions(&surface, hosts)?;
+                for host in &report.hosts {
+                    info!("Host {}: {}", host.version, host.summary());
+                    if !host.compatible {
+                        let mut missing = host.missing[..host.missing.len().min(5)].join(", ");
+                        if host.missing.len() > 5 {
+                            missing.push_str(", ...");
+                        }
+                        warnings.push(format!(
+                            "Skin is not compatible with host {}: {} ({})",
+                            host.version,
+                            host.summary(),
+                            missing
+                        ));
+                    }
+                }
+                Some(report)
+            }
+            None => None,
+        };
+
+        // Report every file name aapt2 would reject at once, or compile renamed copies
+        let mut invalid_names = Vec::new();
+        for (res_dir, priority, _) in &resource_dirs_with_priority {
+            if let Some(files) = files_by_dir.get(res_dir)
+                && !matches!(priority, ResourcePriority::Library(_))
+            {
+                invalid_names.extend(files.iter().filter_map(|file| check_file_name(file)));
+            }
+        }
+        let mut renamed_files = None;
+        if !invalid_names.is_empty() {
+            if self.config.auto_fix_filenames.unwrap_or(false) {
+                let mut renamed = Vec::new();
+                for (res_dir, priority, dir_name) in &resource_dirs_with_priority {
+                    if let Some(files) = files_by_dir.get_mut(res_dir)
+                        && !matches!(priority, ResourcePriority::Library(_))
+                    {
+                        // Staged copies are renamed where they are
+                        let staging_dir = if res_dir.starts_with(&staging_root) {
+                            res_dir.clone()
+                        } else {
+                            compiled_dir.join("renamed_res").join(dir_name)
+                        };
+                        renamed.extend(stage_renamed_files(files, &staging_dir)?);
+                    }
+                }
+                for file in renamed.iter_mut() {
+                    if let Some(source) = staged_sources.get(&file.from) {
+                        file.from = source.clone();
+                    }
+                    debug!("Renamed {} to {}", file.from.display(), file.to.display());
+                }
+                info!("Compiling {} renamed resource files", renamed.len());
+                renamed_files = Some(renamed);
+            } else {
+                if !self.has_cache() {
+                    AarExtractor::cleanup_aars(&aar_infos)?;
+                }
+                compile_spinner.finish_and_clear();
+                pb.finish_with_message("Build failed: invalid resource file names");
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: invalid_names
+                        .iter()
+                        .map(|invalid| {
+                            let source = staged_sources.get(&invalid.file).unwrap_or(&invalid.file);
+                            format!(
+                                "{}: file name {}; rename it to {} or set autoFixFilenames",
+                                source.display(),
+                                invalid.reason,
+                                invalid.fixed
+                            )
+                        })
+                        .collect(),
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+        }
+
+        // Flat files of this build, recorded by priority as they are compiled or reused
+        let mut flat_registry = FlatRegistry::new();
 
         for (res_dir, priority, dir_name) in &resource_dirs_with_priority {
             // Check if this resource directory has precompiled flat files
             let precompiled_flat_files = self
                 .config
-                .precompiled_dependencies
+                .precompiled_common_deps
                 .as_ref()
                 .and_then(|map| map.get(res_dir))
                 .cloned();
@@ -297,83 +1871,86 @@ impl SkinBuilder {
                     priority
                 );
 
-                flat_files_by_priority.push((*priority, flat_files, res_dir.clone()));
+                flat_registry.register_precompiled(flat_files, *priority);
                 valid_resource_dirs.push(res_dir.clone());
             } else if res_dir.exists() {
                 // Compile each resource directory to its own subdirectory
                 let module_compiled_dir = compiled_dir.join(dir_name);
                 std::fs::create_dir_all(&module_compiled_dir)?;
 
-                let files = self.find_resource_files(res_dir)?;
-                if !files.is_empty() {
-                    let flat_files = self.compile_all_resources(&files, &module_compiled_dir)?;
-
+                let files = files_by_dir.remove(res_dir).unwrap_or_default();
+                let mut flat_count = if files.is_empty() {
+                    0
+                } else {
+                    run_blocking(|| {
+                        self.compile_all_resources(
+                            &files,
+                            &module_compiled_dir,
+                            *priority,
+                            &mut flat_registry,
+                        )
+                    })?
+                };
+                if let Some(types) = &only_types {
+                    flat_count += self.reuse_cached_flat_files(
+                        res_dir,
+                        &module_compiled_dir,
+                        types,
+                        *priority,
+                        &mut flat_registry,
+                    );
+                }
+                if flat_count > 0 {
                     debug!(
                         "Resource directory {} has priority {:?}, compiled {} files to {}",
                         res_dir.display(),
                         priority,
-                        flat_files.len(),
+                        flat_count,
                         module_compiled_dir.display()
                     );
-                    flat_files_by_priority.push((*priority, flat_files, res_dir.clone()));
                 }
                 valid_resource_dirs.push(res_dir.clone());
+            } else if self.config.is_strict() && *priority == ResourcePriority::Main {
+                let warning = format!("Resource directory not found: {}", res_dir.display());
+                warn!("{}", warning);
+                warnings.push(warning);
+                missing_dirs.push(res_dir.display().to_string());
             } else {
                 info!("Resource directory not found: {}", res_dir.display());
                 missing_dirs.push(res_dir.display().to_string());
             }
         }
 
-        // Collect all flat files organized by priority
-        // Sort by priority to ensure correct order for linking
-        flat_files_by_priority.sort_by_key(|(priority, _, _)| priority.value());
+        // Precompiled dependencies are library inputs ranked above AAR resources
+        for (idx, (path, inputs)) in precompiled_inputs.iter().enumerate() {
+            info!(
+                "Using {} precompiled inputs from {}",
+                inputs.len(),
+                path.display()
+            );
+            flat_registry.register_precompiled(
+                inputs.clone(),
+                ResourcePriority::Library(aar_infos.len() + idx),
+            );
+        }
 
-        // Separate base from overlays for aapt2 link
-        // Following Android standard: Library (AAR) < Additional < Main
-        // Library and Additional are base resources, Main is overlay
-        let mut base_flat_files = Vec::new();
-        let mut overlay_flat_files: Vec<Vec<PathBuf>> = Vec::new();
-
-        for (priority, files, dir) in &flat_files_by_priority {
-            match priority {
-                ResourcePriority::Library(_) => {
-                    // Libraries are always base (lowest priority)
-                    debug!(
-                        "Base resources (Library): {} files from {} (priority {:?})",
-                        files.len(),
-                        dir.display(),
-                        priority
-                    );
-                    base_flat_files.extend(files.clone());
-                }
-                ResourcePriority::Additional(_) => {
-                    // Additional resources are base (medium priority)
-                    debug!(
-                        "Base resources (Additional): {} files from {} (priority {:?})",
-                        files.len(),
-                        dir.display(),
-                        priority
-                    );
-                    base_flat_files.extend(files.clone());
-                }
-                ResourcePriority::Main => {
-                    // Main resources are overlay (highest priority)
-                    debug!(
-                        "Overlay resources (Main): {} files from {} (priority {:?})",
-                        files.len(),
-                        dir.display(),
-                        priority
-                    );
-                    overlay_flat_files.push(files.clone());
-                }
-            }
+        for (priority, files) in flat_registry.by_priority() {
+            debug!(
+                "Link inputs with priority {:?}: {} files",
+                priority,
+                files.len()
+            );
         }
 
-        let total_flat_files =
-            base_flat_files.len() + overlay_flat_files.iter().map(|v| v.len()).sum::<usize>();
+        // Separate base from overlays for aapt2 link
+        let (base_flat_files, overlay_flat_files) = flat_registry.link_inputs();
 
-        if total_flat_files == 0 {
-            AarExtractor::cleanup_aars(&aar_infos)?;
+        let total_flat_files = flat_registry.len();
+
+        if flat_registry.is_empty() {
+            if !self.has_cache() {
+                AarExtractor::cleanup_aars(&aar_infos)?;
+            }
             compile_spinner.finish_and_clear();
             pb.finish_with_message("Build failed: no resources found");
 
@@ -400,7 +1977,12 @@ impl SkinBuilder {
                 success: false,
                 apk_path: None,
                 errors: vec![error_msg],
+                warnings,
                 build_duration: build_start.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
             });
         }
 
@@ -411,6 +1993,7 @@ impl SkinBuilder {
             overlay_flat_files.len()
         );
         compile_spinner.finish_with_message("Resource compilation complete");
+        let compile_duration = compile_start.elapsed();
         pb.inc(1);
 
         // Save cache
@@ -418,29 +2001,107 @@ impl SkinBuilder {
             cache.save()?;
         }
 
-        // Create minimal AndroidManifest.xml as cached file in compiled_dir
-        // According to requirements, we only need: <manifest package="[package_name]"/>
-        let processed_manifest = create_minimal_manifest(&self.config.package_name, &compiled_dir)?;
+        // Let editor tooling replay what was compiled for each file
+        match write_compile_db(&compiled_dir, &mut self.compile_commands) {
+            Ok(path) => debug!("Compile database written to {}", path.display()),
+            Err(e) => warn!("Failed to write compile database: {:#}", e),
+        }
+
+        let post_compile = self.run_hooks(HookStage::PostCompile);
+        warnings.extend(post_compile.warnings);
+        if !post_compile.errors.is_empty() {
+            if !self.has_cache() {
+                AarExtractor::cleanup_aars(&aar_infos)?;
+            }
+            pb.finish_with_message("Build failed: postCompile hook");
+            return Ok(BuildResult {
+                success: false,
+                apk_path: None,
+                errors: post_compile.errors,
+                warnings,
+                build_duration: build_start.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
+            });
+        }
 
         // Determine if we need to set min SDK version for adaptive icons
         // Use aapt2's --min-sdk-version parameter instead of modifying manifest
-        let min_sdk_version = if has_adaptive_icon_resources(&valid_resource_dirs) {
+        let min_sdk_version = if has_adaptive_icon_resources(
+            &valid_resource_dirs,
+            self.config.follow_symlinks.unwrap_or(false),
+        ) {
             warn!("Detected adaptive-icon resources, setting minimum SDK version to 26");
             Some(26)
         } else {
             None
         };
 
+        // Intermediates such as the cached manifest were produced for the previous link
+        // configuration; drop them and the old package when it changed
+        let output_apk = self.config.output_apk_path();
+        if let Some(cache) = self.cache.as_mut() {
+            let signature = link_signature(&self.config, min_sdk_version)?;
+            if cache.set_link_signature(signature) {
+                for stale in [compiled_dir.join("AndroidManifest.xml"), output_apk.clone()] {
+                    if stale.exists() {
+                        fs::remove_file(&stale)
+                            .with_context(|| format!("Failed to remove {}", stale.display()))?;
+                    }
+                }
+            }
+        }
+
+        // Pick the manifest passed to aapt2
+        let processed_manifest = match self.config.manifest_mode.unwrap_or_default() {
+            // According to requirements, we only need: <manifest package="[package_name]"/>
+            ManifestMode::Minimal => {
+                create_minimal_manifest(&self.config.package_name, &compiled_dir)?
+            }
+            ManifestMode::User => {
+                if !self.config.manifest_path.exists() {
+                    anyhow::bail!(
+                        "Manifest not found: {} (manifestMode is \"user\")",
+                        self.config.manifest_path.display()
+                    );
+                }
+                self.config.manifest_path.clone()
+            }
+            ManifestMode::Merge => create_merged_manifest(
+                &self.config.manifest_path,
+                &self.config.package_name,
+                &self
+                    .config
+                    .manifest_placeholders
+                    .clone()
+                    .unwrap_or_default(),
+                &compiled_dir,
+            )?,
+        };
+
+        // Fold AAR manifest metadata into the manifest when requested
+        let aar_manifests: Vec<PathBuf> = aar_infos
+            .iter()
+            .filter_map(|info| info.manifest_path.clone())
+            .collect();
+        let processed_manifest =
+            if self.config.merge_aar_manifests.unwrap_or(false) && !aar_manifests.is_empty() {
+                let merged_manifest = compiled_dir.join("AndroidManifest.aar-merged.xml");
+                let conflicts =
+                    merge_aar_manifests(&processed_manifest, &aar_manifests, &merged_manifest)?;
+                for conflict in &conflicts {
+                    warn!("Manifest merge conflict: {}", conflict);
+                }
+                merged_manifest
+            } else {
+                processed_manifest
+            };
+
         // Link resources into skin package using overlay strategy
         pb.set_message("Linking APK...");
         info!("Linking resources with Android resource priority strategy...");
-        let output_filename = self
-            .config
-            .output_file
-            .clone()
-            .unwrap_or_else(|| format!("{}.skin", self.config.package_name));
-
-        let output_apk = self.config.output_dir.join(output_filename);
 
         // Ensure android_jar is set
         let android_jar = self.config.android_jar.as_ref().ok_or_else(|| {
@@ -449,27 +2110,75 @@ impl SkinBuilder {
             )
         })?;
 
-        let link_result = self.aapt2.link_with_overlays(
-            &base_flat_files,
-            &overlay_flat_files,
-            &processed_manifest,
-            android_jar,
-            &output_apk,
-            Some(&self.config.package_name),
-            self.config.version_code,
-            self.config.version_name.as_deref(),
-            self.config.stable_ids_file.as_deref(),
-            self.config.package_id.as_deref(),
-            min_sdk_version,
-            Some(&compiled_dir), // Pass compiled_dir to avoid conflicts in multi-task builds
-            self.config.assets_dir.as_deref(),
-        )?;
+        // Free the build slot for another config while this one waits for a link slot
+        drop(build_permit);
+        self.enter_phase(BuildPhase::WaitForLink);
+        let link_permit = match &self.limits {
+            Some(limits) => Some(limits.links.clone().acquire_owned().await?),
+            None => None,
+        };
+
+        // In freeze mode the link keeps the frozen IDs, taking precedence over stable IDs
+        let frozen_ids = match &self.config.freeze_file {
+            Some(freeze_file) if !freeze_file.is_file() => anyhow::bail!(
+                "Freeze file {} not found; record it from the released package with asb freeze",
+                freeze_file.display()
+            ),
+            Some(freeze_file) => Some(read_ids(freeze_file)?),
+            None => None,
+        };
+        // Strict mode verifies the link kept every ID recorded in the stable IDs file
+        let recorded_ids = match self.config.stable_ids_path() {
+            Some(path) if frozen_ids.is_none() && self.config.is_strict() && path.is_file() => {
+                Some((read_ids(&path)?, path))
+            }
+            _ => None,
+        };
+        let stable_ids_file = match &frozen_ids {
+            Some(frozen) => {
+                let stable = match self.config.stable_ids_path() {
+                    Some(path) if path.is_file() => read_ids(&path)?,
+                    _ => BTreeMap::new(),
+                };
+                let working = compiled_dir.join(FREEZE_IDS_FILE);
+                write_ids(
+                    &working,
+                    &self.config.package_name,
+                    &merge_frozen_ids(frozen, stable),
+                )?;
+                Some(working)
+            }
+            None => self.config.stable_ids_path(),
+        };
+
+        self.enter_phase(BuildPhase::Link);
+        let link_start = std::time::Instant::now();
+        let link_result = self
+            .aapt2
+            .link_with_overlays(
+                &base_flat_files,
+                &overlay_flat_files,
+                &processed_manifest,
+                android_jar,
+                &output_apk,
+                Some(&self.config.package_name),
+                self.config.version_code,
+                self.config.version_name.as_deref(),
+                stable_ids_file.as_deref(),
+                self.config.package_id.as_deref(),
+                min_sdk_version,
+                Some(&compiled_dir), // Pass compiled_dir to avoid conflicts in multi-task builds
+                self.config.assets_dir.as_deref(),
+            )
+            .await?;
+        drop(link_permit);
+        let link_duration = link_start.elapsed();
 
         // Keep manifest cached in compiled_dir for reuse in subsequent builds
         // No need to cleanup - it's intentionally persisted for cache optimization
 
-        // Cleanup AAR extraction directories
-        if !aar_infos.is_empty() {
+        // Cleanup AAR extraction directories; incremental builds keep them for the next build
+        if !aar_infos.is_empty() && !self.has_cache() {
             AarExtractor::cleanup_aars(&aar_infos)?;
             if temp_dir.exists() {
                 std::fs::remove_dir_all(&temp_dir).ok();
@@ -479,18 +2188,161 @@ impl SkinBuilder {
         pb.inc(1);
 
         if !link_result.success {
+            let errors = self.suggest_missing_resources(
+                link_result.errors,
+                &resource_dirs_with_priority,
+                &precompiled_inputs,
+            );
             return Ok(BuildResult {
                 success: false,
                 apk_path: None,
-                errors: link_result.errors,
+                errors,
+                warnings,
                 build_duration: build_start.elapsed(),
+                vcs: None,
+                cached_files: 0,
+                compiled_files: 0,
+                normalization: None,
             });
         }
 
-        // Add raw resource files to the skin package
+        if let (Some(frozen), Some(emitted)) = (&frozen_ids, &stable_ids_file) {
+            let violations = check_frozen(frozen, &read_ids(emitted)?);
+            if !violations.is_empty() {
+                // A package breaking shipped hosts must not be picked up
+                std::fs::remove_file(&output_apk).ok();
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: violations,
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+            if let Some(path) = self.config.stable_ids_path() {
+                std::fs::copy(emitted, &path)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+        }
+
+        if let Some((recorded, path)) = &recorded_ids {
+            let emitted = read_ids(path)?;
+            let changed: Vec<String> = recorded
+                .iter()
+                .filter_map(|(name, id)| {
+                    let current = emitted.get(name).filter(|current| *current != id)?;
+                    Some(format!(
+                        "Resource {} changed ID from {} to {} (strict mode checks IDs against {})",
+                        name,
+                        id,
+                        current,
+                        path.display()
+                    ))
+                })
+                .collect();
+            if !changed.is_empty() {
+                // Keep the recorded IDs, so the next build is checked against them again
+                write_ids(path, &self.config.package_name, recorded)?;
+                std::fs::remove_file(&output_apk).ok();
+                return Ok(BuildResult {
+                    success: false,
+                    apk_path: None,
+                    errors: changed,
+                    warnings,
+                    build_duration: build_start.elapsed(),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+        }
+
+        if let Some(budget) = &self.config.size_budget
+            && let Some(warning) = size_budget::check_package(budget, &output_apk)
+        {
+            warn!("{}", warning);
+            warnings.push(warning);
+        }
+
+        if let Some(audit) = &rewrite_audit {
+            let audit_path = output_apk.with_extension(REWRITE_AUDIT_EXTENSION);
+            std::fs::write(&audit_path, serde_json::to_string_pretty(audit)?)
+                .with_context(|| format!("Failed to write {}", audit_path.display()))?;
+            info!("Resource rewrite audit written to {}", audit_path.display());
+        }
+
+        if let Some(renamed) = &renamed_files {
+            let mapping_path = output_apk.with_extension(RENAMED_FILES_EXTENSION);
+            std::fs::write(&mapping_path, serde_json::to_string_pretty(renamed)?)
+                .with_context(|| format!("Failed to write {}", mapping_path.display()))?;
+            info!("File rename mapping written to {}", mapping_path.display());
+        }
+
+        if let Some(report) = &owners_report {
+            let report_path = output_apk.with_extension(OWNERS_REPORT_EXTENSION);
+            std::fs::write(&report_path, serde_json::to_string_pretty(report)?)
+                .with_context(|| format!("Failed to write {}", report_path.display()))?;
+            info!(
+                "Ownership report written to {} ({} teams, {} overrides)",
+                report_path.display(),
+                report.teams.len(),
+                report.overrides.len()
+            );
+        }
+
+        if let Some(report) = &compat_report {
+            let report_path = output_apk.with_extension(COMPAT_REPORT_EXTENSION);
+            std::fs::write(&report_path, serde_json::to_string_pretty(report)?)
+                .with_context(|| format!("Failed to write {}", report_path.display()))?;
+            info!(
+                "Host compatibility report written to {} ({} of {} hosts compatible)",
+                report_path.display(),
+                report.hosts.iter().filter(|host| host.compatible).count(),
+                report.hosts.len()
+            );
+        }
+
+        events::emit(BuildEvent::PackageLinked {
+            package: self.config.package_name.clone(),
+            apk: output_apk.clone(),
+        });
+
+        // Normalize the package written by aapt2 (ordering, alignment, extra files)
         pb.set_message("Finalizing...");
-        info!("Adding resource files to skin package...");
-        self.add_resources_to_apk(&output_apk, &valid_resource_dirs)?;
+        self.enter_phase(BuildPhase::Package);
+        info!("Packaging skin package...");
+        let mut package_options = PackageOptions::from_config(&self.config);
+        if let Some(metadata) = package_options.metadata.as_mut() {
+            metadata.vcs = vcs.cloned();
+        }
+        SkinPackager::new(package_options).repackage_in_place(&output_apk)?;
+
+        if let Some(plugins) = &self.config.plugins {
+            postprocess(
+                plugins,
+                &self.config.package_name,
+                &output_apk,
+                &compiled_dir.join("plugins").join("package"),
+            )?;
+        }
+
+        self.record_durations(BuildDurations {
+            compile_ms: compile_duration.as_millis() as u64,
+            link_ms: link_duration.as_millis() as u64,
+        });
+        self.record_disk_usage(&output_apk);
+
+        let cached_files = self
+            .compile_commands
+            .iter()
+            .filter(|c| c.cache_status == CacheStatus::Cached)
+            .count();
+        let compiled_files = self.compile_commands.len() - cached_files;
 
         pb.inc(1);
         pb.finish_with_message("Build complete");
@@ -499,29 +2351,161 @@ impl SkinBuilder {
             success: true,
             apk_path: link_result.apk_path,
             errors: vec![],
+            warnings,
             build_duration: build_start.elapsed(),
+            vcs: None,
+            cached_files,
+            compiled_files,
+            normalization,
         })
     }
 
-    /// Add additional resource files to the APK if needed
-    /// Note: aapt2 already compiles and includes all resources in binary format.
-    /// This function is kept for future extensibility but currently just validates the APK.
-    fn add_resources_to_apk(&self, _apk_path: &Path, _resource_dirs: &[PathBuf]) -> Result<()> {
-        // aapt2 link already includes all compiled resources in the APK
-        // including layouts, drawables, and other resource files in binary XML format.
-        // Resources.arsc contains the resource table with IDs and references.
-        // The compiled binary XML files are what Android expects at runtime.
-
-        // No additional processing needed - aapt2 has done everything correctly
+    /// Drop the resources each extracted AAR's filters do not keep, before anything is compiled
+    fn filter_aar_resources(
+        &self,
+        aar_infos: &[AarInfo],
+        filters: &BTreeMap<String, AarResourceFilter>,
+    ) -> Result<()> {
+        let file_name = |aar_info: &AarInfo| {
+            aar_info
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        for pattern in filters.keys() {
+            if !aar_infos
+                .iter()
+                .any(|aar_info| wildcard_match(pattern, &file_name(aar_info)))
+            {
+                warn!("AAR filter '{}' matches none of the AAR files", pattern);
+            }
+        }
+        for aar_info in aar_infos {
+            let name = file_name(aar_info);
+            for (pattern, filter) in filters {
+                if wildcard_match(pattern, &name) {
+                    let removed = AarExtractor::filter_resources(aar_info, filter)?;
+                    info!("Filtered {} resource files out of {}", removed, name);
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Compile all resource files from multiple directories
+    /// Find `@type/name` references that resolve neither in the resources being built nor in
+    /// the host symbols
+    ///
+    /// Returns one "file:line: reference" error per unresolved reference. When precompiled
+    /// inputs hide what they define, unresolved references are only logged as warnings.
+    fn check_references(
+        &mut self,
+        resource_dirs: &[(PathBuf, ResourcePriority, String)],
+        precompiled_inputs: &[(PathBuf, Vec<PathBuf>)],
+    ) -> Result<Vec<String>> {
+        let (symbols, files) = self.symbol_table(resource_dirs, precompiled_inputs)?;
+        let unresolved = find_unresolved(&files, &symbols)?;
+        info!(
+            "Checked references in {} files: {} unresolved",
+            files.len(),
+            unresolved.len()
+        );
+        if unresolved.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !symbols.opaque_inputs.is_empty() {
+            for reference in &unresolved {
+                warn!("Unresolved reference {}", reference);
+            }
+            warn!(
+                "Not failing on unresolved references: {} precompiled inputs may define them",
+                symbols.opaque_inputs.len()
+            );
+            return Ok(Vec::new());
+        }
+        Ok(unresolved
+            .iter()
+            .map(|reference| {
+                let suggestions = reference
+                    .reference
+                    .trim_start_matches('@')
+                    .split_once('/')
+                    .map(|(resource_type, name)| symbols.suggestions(resource_type, name))
+                    .unwrap_or_default();
+                format!(
+                    "Unresolved reference {}{}",
+                    reference,
+                    did_you_mean(&suggestions)
+                )
+            })
+            .collect())
+    }
+
+    /// Resources the skin can reference: its own resource files, precompiled inputs and the
+    /// host symbols, with the resource files that were read
+    fn symbol_table(
+        &mut self,
+        resource_dirs: &[(PathBuf, ResourcePriority, String)],
+        precompiled_inputs: &[(PathBuf, Vec<PathBuf>)],
+    ) -> Result<(SymbolTable, Vec<PathBuf>)> {
+        let mut symbols = SymbolTable::default();
+        let mut files = Vec::new();
+        for (res_dir, _, _) in resource_dirs {
+            if !res_dir.exists() {
+                continue;
+            }
+            let dir_files = self.find_resource_files(res_dir)?;
+            symbols.add_resource_files(res_dir, &dir_files)?;
+            files.extend(dir_files);
+        }
+        for (_, inputs) in precompiled_inputs {
+            symbols.add_link_inputs(inputs);
+        }
+        if let Some(host_symbols) = &self.config.host_symbols {
+            symbols.load_host_symbols(host_symbols)?;
+        }
+        Ok((symbols, files))
+    }
+
+    /// Add "did you mean" suggestions to the `resource ... not found` errors of a failed link
+    fn suggest_missing_resources(
+        &mut self,
+        errors: Vec<String>,
+        resource_dirs: &[(PathBuf, ResourcePriority, String)],
+        precompiled_inputs: &[(PathBuf, Vec<PathBuf>)],
+    ) -> Vec<String> {
+        match self.symbol_table(resource_dirs, precompiled_inputs) {
+            Ok((symbols, _)) => errors
+                .iter()
+                .map(|error| suggest_missing_resources(error, &symbols))
+                .collect(),
+            Err(e) => {
+                debug!("No suggestions for missing resources: {:#}", e);
+                errors
+            }
+        }
+    }
+
+    /// Compile all resource files of one resource directory
+    /// Every produced or reused flat file is registered with `priority`; returns how many
     fn compile_all_resources(
         &mut self,
         resource_files: &[PathBuf],
         compiled_dir: &Path,
-    ) -> Result<Vec<PathBuf>> {
+        priority: ResourcePriority,
+        registry: &mut FlatRegistry,
+    ) -> Result<usize> {
+        // Large resource sets spread their flat files over shard subdirectories; the
+        // decision depends on the whole set so incremental builds keep the same layout
+        let sharded = resource_files.len() > FLAT_SHARD_THRESHOLD;
+        let compile = |aapt2: &Aapt2, files: &[PathBuf]| {
+            if sharded {
+                aapt2.compile_files_sharded(files, compiled_dir)
+            } else {
+                aapt2.compile_files_parallel(files, compiled_dir)
+            }
+        };
+
         // If incremental build is disabled or no cache, compile all files together
         if !self.has_cache() {
             // Clear compiled directory to avoid stale flat files
@@ -531,13 +2515,26 @@ impl SkinBuilder {
             std::fs::create_dir_all(compiled_dir)?;
 
             // Compile all files in parallel
-            let result = self
-                .aapt2
-                .compile_files_parallel(resource_files, compiled_dir)?;
+            let result = compile(&self.aapt2, resource_files)?;
             if !result.success {
-                anyhow::bail!("Compilation failed: {:?}", result.errors);
+                anyhow::bail!("Compilation failed:\n{}", result.errors.join("\n"));
             }
-            return Ok(result.flat_files);
+            for (file, flat_file) in resource_files.iter().zip(&result.flat_files) {
+                self.compile_commands.push(CompileCommand::new(
+                    &self.aapt2,
+                    file,
+                    flat_file,
+                    compiled_dir,
+                    CacheStatus::Compiled,
+                ));
+                registry.register(FlatEntry {
+                    flat_file: flat_file.clone(),
+                    source: Some(file.clone()),
+                    priority,
+                    hash: None,
+                });
+            }
+            return Ok(result.flat_files.len());
         }
 
         // For incremental builds, check each file individually
@@ -551,14 +2548,22 @@ impl SkinBuilder {
         let mut cached_results: Vec<(PathBuf, PathBuf)> = Vec::new();
 
         for resource_file in resource_files {
+            let target = expected_flat_file(resource_file, compiled_dir, sharded)
+                .or_else(|| cache.get_cached_flat_file(resource_file));
             if cache.needs_recompile(resource_file).unwrap_or(true) {
                 // Need to recompile
                 to_compile.push(resource_file.clone());
-            } else {
-                // Use cached flat file if available
-                debug!("Using cached: {}", resource_file.display());
-                if let Some(flat) = cache.get_cached_flat_file(resource_file) {
-                    cached_results.push((resource_file.clone(), flat));
+            } else if let Some(target) = target {
+                // Link the stored flat file into this build's compiled directory
+                match cache.restore_flat_file(resource_file, &target) {
+                    Ok(()) => {
+                        debug!("Using cached: {}", resource_file.display());
+                        cached_results.push((resource_file.clone(), target));
+                    }
+                    Err(e) => {
+                        debug!("Cannot reuse {}: {:#}", resource_file.display(), e);
+                        to_compile.push(resource_file.clone());
+                    }
                 }
             }
         }
@@ -566,7 +2571,16 @@ impl SkinBuilder {
         // Process recompilations in parallel
         let flat_files_results = if !to_compile.is_empty() {
             debug!("Recompiling {} files...", to_compile.len());
-            aapt2.compile_files_parallel(&to_compile, compiled_dir)?
+            // Flat files restored by an earlier build are hard links into the store; unlink
+            // them so aapt2 does not write through to the stored copy
+            for file in &to_compile {
+                if let Some(flat) = expected_flat_file(file, compiled_dir, sharded)
+                    && flat.exists()
+                {
+                    fs::remove_file(&flat)?;
+                }
+            }
+            compile(aapt2, &to_compile)?
         } else {
             CompileResult {
                 success: true,
@@ -577,37 +2591,42 @@ impl SkinBuilder {
 
         if !flat_files_results.success {
             anyhow::bail!(
-                "Parallel compilation failed: {:?}",
-                flat_files_results.errors
+                "Parallel compilation failed:\n{}",
+                flat_files_results.errors.join("\n")
             );
         }
 
-        let mut flat_files = Vec::new();
-
-        // First, handle newly compiled results
-        for (i, resource_file) in to_compile.iter().enumerate() {
-            if i < flat_files_results.flat_files.len() {
-                let flat_file = &flat_files_results.flat_files[i];
-                cache.update_entry(resource_file, flat_file)?;
-                if flat_file.exists() {
-                    flat_files.push(flat_file.clone());
-                }
-            }
-        }
-
-        // Then, handle cached results
-        for (resource_file, flat_file) in cached_results {
-            cache.update_entry(&resource_file, &flat_file)?;
-            if flat_file.exists() {
-                flat_files.push(flat_file);
-            }
+        // Newly compiled flat files were just written by aapt2 and cached ones were checked
+        // by needs_recompile, so neither needs another existence check
+        let compiled = to_compile
+            .iter()
+            .zip(&flat_files_results.flat_files)
+            .map(|(file, flat)| (file.clone(), flat.clone(), CacheStatus::Compiled));
+        let cached = cached_results
+            .into_iter()
+            .map(|(file, flat)| (file, flat, CacheStatus::Cached));
+        let mut results: Vec<(PathBuf, PathBuf, CacheStatus)> = compiled.chain(cached).collect();
+        // Sort by flat file so link inputs are ordered the same way on every build
+        results.sort_by(|a, b| a.1.cmp(&b.1));
+
+        for (resource_file, flat_file, status) in &results {
+            cache.update_entry(resource_file, flat_file)?;
+            self.compile_commands.push(CompileCommand::new(
+                aapt2,
+                resource_file,
+                flat_file,
+                compiled_dir,
+                *status,
+            ));
+            registry.register(FlatEntry {
+                flat_file: flat_file.clone(),
+                source: Some(resource_file.clone()),
+                priority,
+                hash: cache.cached_hash(resource_file).map(str::to_string),
+            });
         }
 
-        // Deduplicate flat files to avoid passing the same file multiple times to link
-        flat_files.sort();
-        flat_files.dedup();
-
-        Ok(flat_files)
+        Ok(results.len())
     }
 
     /// Compile a resource directory
@@ -627,7 +2646,7 @@ impl SkinBuilder {
 
             let result = self.aapt2.compile_dir(res_dir, compiled_dir)?;
             if !result.success {
-                anyhow::bail!("Compilation failed: {:?}", result.errors);
+                anyhow::bail!("Compilation failed:\n{}", result.errors.join("\n"));
             }
             return Ok(result.flat_files);
         }
@@ -670,8 +2689,8 @@ impl SkinBuilder {
 
         if !flat_files_results.success {
             anyhow::bail!(
-                "Parallel compilation failed: {:?}",
-                flat_files_results.errors
+                "Parallel compilation failed:\n{}",
+                flat_files_results.errors.join("\n")
             );
         }
 
@@ -703,49 +2722,93 @@ impl SkinBuilder {
         Ok(flat_files)
     }
 
-    /// Find all resource files in a directory
-    fn find_resource_files(&self, res_dir: &Path) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
-
-        // min_depth(2) skips both the root directory (depth 0) and files directly
-        // under res_dir (depth 1), ensuring only files inside subdirectories are
-        // included. This avoids per-entry canonicalize() syscalls.
-        for entry in WalkDir::new(res_dir)
-            .min_depth(2)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
-            let path = entry.path();
-
-            // Check if file is in a layout directory and skip it
-            if let Some(parent) = path.parent()
-                && let Some(parent_name) = parent.file_name().and_then(|n| n.to_str())
-            {
-                // Check for layout directories (layout, layout-land, layout-sw600dp, etc.)
-                if parent_name.starts_with("layout") {
-                    debug!("Filtering out layout file: {}", path.display());
-                    continue;
-                }
+    /// Cached flat files of the resource types `--only` leaves out, reused without checking
+    /// whether their sources changed
+    fn reuse_cached_flat_files(
+        &mut self,
+        res_dir: &Path,
+        compiled_dir: &Path,
+        types: &[String],
+        priority: ResourcePriority,
+        registry: &mut FlatRegistry,
+    ) -> usize {
+        let Some(cache) = &self.cache else {
+            return 0;
+        };
+        let mut reused = 0;
+        for (file, flat_file) in cache.cached_entries_under(res_dir) {
+            if is_selected_type(res_dir, &file, types) {
+                continue;
             }
-
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                // Skip hidden files, system files, and specific resource files
-                if name.starts_with('.') || name == "Thumbs.db" {
-                    continue;
-                }
-
-                // Filter out styles.xml, attrs.xml, and strings.xml
-                if name == "styles.xml" || name == "attrs.xml" || name == "strings.xml" {
-                    debug!("Filtering out resource file: {}", path.display());
-                    continue;
-                }
-
-                files.push(path.to_path_buf());
+            if let Err(e) = cache.restore_flat_file(&file, &flat_file) {
+                warn!("Cannot reuse cached {}: {:#}", file.display(), e);
+                continue;
             }
+            self.compile_commands.push(CompileCommand::new(
+                &self.aapt2,
+                &file,
+                &flat_file,
+                compiled_dir,
+                CacheStatus::Cached,
+            ));
+            registry.register(FlatEntry {
+                hash: cache.cached_hash(&file).map(str::to_string),
+                flat_file,
+                source: Some(file),
+                priority,
+            });
+            reused += 1;
         }
+        debug!(
+            "Reusing {} cached flat files from {}",
+            reused,
+            res_dir.display()
+        );
+        reused
+    }
+
+    /// Find all resource files in a directory
+    fn find_resource_files(&mut self, res_dir: &Path) -> Result<Vec<PathBuf>> {
+        self.find_resource_files_of_types(res_dir, None)
+    }
 
-        Ok(files)
+    /// Find the resource files in a directory, limited to `types` when given
+    fn find_resource_files_of_types(
+        &mut self,
+        res_dir: &Path,
+        types: Option<&[String]>,
+    ) -> Result<Vec<PathBuf>> {
+        let follow_symlinks = self.config.follow_symlinks.unwrap_or(false);
+        let respect_gitignore = self.config.respect_gitignore.unwrap_or(false);
+        let Some(tracked) = self
+            .tracked_files
+            .as_mut()
+            .filter(|t| t.roots.iter().any(|(root, _)| root == res_dir))
+        else {
+            return find_resource_files(res_dir, follow_symlinks, respect_gitignore, types);
+        };
+        let files = match tracked.lists.get(res_dir) {
+            Some(discovered) => discovered.files.clone(),
+            None => {
+                let ignore = ResourceIgnore::load(res_dir, respect_gitignore)?;
+                let files = walk_resource_files(res_dir, follow_symlinks, &ignore, None)?;
+                tracked.lists.insert(
+                    res_dir.to_path_buf(),
+                    DiscoveredFiles {
+                        files: files.clone(),
+                        ignore,
+                    },
+                );
+                files
+            }
+        };
+        Ok(match types {
+            Some(types) => files
+                .into_iter()
+                .filter(|file| is_selected_type(res_dir, file, types))
+                .collect(),
+            None => files,
+        })
     }
 
     /// Clean build artifacts
@@ -792,6 +2855,9 @@ impl SkinBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{
+        HookFailurePolicy, HooksConfig, LocaleQualifierForm, PluginConfig, PluginKind,
+    };
     use std::fs;
     use tempfile::TempDir;
 
@@ -846,10 +2912,61 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
-        let builder = SkinBuilder::new(config)?;
+        let mut builder = SkinBuilder::new(config)?;
         let files = builder.find_resource_files(&res_dir)?;
 
         // Should only find the valid file (colors.xml), not strings.xml or files directly under res/
@@ -924,10 +3041,61 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
-        let builder = SkinBuilder::new(config)?;
+        let mut builder = SkinBuilder::new(config)?;
         let files = builder.find_resource_files(&res_dir)?;
 
         // Should find only 2 files now: colors.xml and icon.png
@@ -992,6 +3160,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1057,6 +3276,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1203,7 +3473,7 @@ mod tests {
 
     #[test]
     fn test_has_adaptive_icon_empty_dirs() {
-        let result = super::has_adaptive_icon_resources(&[]);
+        let result = super::has_adaptive_icon_resources(&[], false);
         assert!(!result, "Empty dirs should return false");
     }
 
@@ -1215,7 +3485,7 @@ mod tests {
         fs::create_dir_all(&drawable_dir).unwrap();
         fs::write(drawable_dir.join("icon.png"), "fake png").unwrap();
 
-        let result = super::has_adaptive_icon_resources(&[res_dir]);
+        let result = super::has_adaptive_icon_resources(&[res_dir], false);
         assert!(!result, "No mipmap-anydpi dir should return false");
     }
 
@@ -1227,7 +3497,7 @@ mod tests {
         fs::create_dir_all(&anydpi_dir).unwrap();
         fs::write(anydpi_dir.join("ic_launcher.xml"), "<not-adaptive/>").unwrap();
 
-        let result = super::has_adaptive_icon_resources(&[res_dir]);
+        let result = super::has_adaptive_icon_resources(&[res_dir], false);
         assert!(
             !result,
             "mipmap-anydpi without <adaptive-icon should return false"
@@ -1246,7 +3516,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = super::has_adaptive_icon_resources(&[res_dir]);
+        let result = super::has_adaptive_icon_resources(&[res_dir], false);
         assert!(
             result,
             "mipmap-anydpi with <adaptive-icon should return true"
@@ -1265,7 +3535,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = super::has_adaptive_icon_resources(&[res_dir]);
+        let result = super::has_adaptive_icon_resources(&[res_dir], false);
         assert!(
             result,
             "mipmap-anydpi (no version) with <adaptive-icon should return true"
@@ -1274,6 +3544,18 @@ mod tests {
 
     // ========== Tests for SkinBuilder::new with various configs ==========
 
+    #[test]
+    fn test_concurrency_limits() {
+        let limits = ConcurrencyLimits::new(4, 2);
+        assert_eq!(limits.builds.available_permits(), 4);
+        assert_eq!(limits.links.available_permits(), 2);
+
+        // A zero limit would deadlock every build
+        let limits = ConcurrencyLimits::new(0, 0);
+        assert_eq!(limits.builds.available_permits(), 1);
+        assert_eq!(limits.links.available_permits(), 1);
+    }
+
     #[test]
     fn test_skin_builder_new_no_incremental() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -1300,6 +3582,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1337,6 +3670,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1374,6 +3758,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1412,6 +3847,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1451,6 +3937,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         fs::write(temp_dir.path().join("aapt2"), b"").unwrap();
@@ -1483,7 +4020,645 @@ mod tests {
         )
         .unwrap();
 
-        let result = super::has_adaptive_icon_resources(&[res_dir1.clone(), res_dir2.clone()]);
+        let result =
+            super::has_adaptive_icon_resources(&[res_dir1.clone(), res_dir2.clone()], false);
         assert!(result, "Should detect adaptive icon in second dir");
     }
+
+    #[cfg(unix)]
+    fn symlink_test_builder(temp_dir: &TempDir, follow_symlinks: bool) -> Result<SkinBuilder> {
+        let config = BuildConfig {
+            resource_dir: temp_dir.path().join("res"),
+            manifest_path: temp_dir.path().join("AndroidManifest.xml"),
+            output_dir: temp_dir.path().join("output"),
+            output_file: None,
+            package_name: "com.test".to_string(),
+            aapt2_path: Some(temp_dir.path().join("aapt2")),
+            android_jar: Some(PathBuf::from("/fake/android.jar")),
+            aar_files: None,
+            incremental: None,
+            build_dir: None,
+            cache_dir: None,
+            version_code: None,
+            version_name: None,
+            additional_resource_dirs: None,
+            compiled_dir: None,
+            stable_ids_file: None,
+            package_id: None,
+            precompiled_dependencies: None,
+            assets_dir: None,
+            follow_symlinks: Some(follow_symlinks),
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
+        };
+
+        fs::write(temp_dir.path().join("aapt2"), b"")?;
+        SkinBuilder::new(config)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_resource_files_follows_symlinked_dirs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let shared_dir = temp_dir.path().join("shared");
+        fs::create_dir_all(&res_dir)?;
+        fs::create_dir_all(&shared_dir)?;
+        fs::write(shared_dir.join("shared_icon.png"), "fake png data")?;
+        std::os::unix::fs::symlink(&shared_dir, res_dir.join("drawable"))?;
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        assert!(
+            builder.find_resource_files(&res_dir)?.is_empty(),
+            "Symlinked directories should be ignored unless followSymlinks is set"
+        );
+
+        let mut builder = symlink_test_builder(&temp_dir, true)?;
+        let files = builder.find_resource_files(&res_dir)?;
+        assert_eq!(
+            files,
+            vec![res_dir.join("drawable").join("shared_icon.png")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_resource_files_symlink_cycle() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let drawable_dir = res_dir.join("drawable");
+        fs::create_dir_all(&drawable_dir)?;
+        fs::write(drawable_dir.join("icon.png"), "fake png data")?;
+
+        // drawable/loop points back at res/, which would recurse forever if followed blindly
+        std::os::unix::fs::symlink(&res_dir, drawable_dir.join("loop"))?;
+
+        let mut builder = symlink_test_builder(&temp_dir, true)?;
+        let files = builder.find_resource_files(&res_dir)?;
+        assert_eq!(files, vec![drawable_dir.join("icon.png")]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_resource_files_of_types() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        for (dir, file) in [
+            ("values", "colors.xml"),
+            ("values-night", "colors.xml"),
+            ("drawable", "icon.png"),
+            ("drawable", "bg.xml"),
+            ("color", "text.xml"),
+        ] {
+            fs::create_dir_all(res_dir.join(dir))?;
+            fs::write(res_dir.join(dir).join(file), "data")?;
+        }
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        let types = ["values".to_string()];
+        let mut files = builder.find_resource_files_of_types(&res_dir, Some(&types))?;
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                res_dir.join("values").join("colors.xml"),
+                res_dir.join("values-night").join("colors.xml"),
+            ]
+        );
+        assert_eq!(builder.find_resource_files(&res_dir)?.len(), 5);
+
+        assert!(validate_resource_types(&["values".to_string(), "drawable".to_string()]).is_ok());
+        assert!(validate_resource_types(&["colors".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_resource_files_skips_ignored() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        for file in [
+            "drawable/icon.png",
+            "drawable/icon.png.orig",
+            "build/out/x.png",
+        ] {
+            fs::create_dir_all(res_dir.join(file).parent().unwrap())?;
+            fs::write(res_dir.join(file), "data")?;
+        }
+        fs::write(res_dir.join(".asbignore"), "*.orig\nbuild/\n")?;
+
+        let files = find_resource_files(&res_dir, false, false, None)?;
+        assert_eq!(files, vec![res_dir.join("drawable").join("icon.png")]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_tracked_resource_files_follow_changes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::write(res_dir.join("values").join("colors.xml"), "data")?;
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        let watched = builder.track_file_changes()?;
+        assert_eq!(watched, vec![std::path::absolute(&res_dir)?]);
+        assert_eq!(builder.find_resource_files(&res_dir)?.len(), 1);
+
+        // Files created without a reported change are not seen
+        fs::create_dir_all(res_dir.join("drawable"))?;
+        fs::write(res_dir.join("drawable").join("icon.png"), "data")?;
+        fs::write(res_dir.join("drawable").join("bg.png"), "data")?;
+        assert_eq!(builder.find_resource_files(&res_dir)?.len(), 1);
+
+        fs::remove_file(res_dir.join("values").join("colors.xml"))?;
+        builder.apply_file_changes(&[
+            watched[0].join("drawable").join("icon.png"),
+            watched[0].join("values").join("colors.xml"),
+        ]);
+        assert_eq!(
+            builder.find_resource_files(&res_dir)?,
+            vec![res_dir.join("drawable").join("icon.png")]
+        );
+
+        // A new directory is walked again
+        builder.apply_file_changes(&[watched[0].join("drawable")]);
+        assert_eq!(builder.find_resource_files(&res_dir)?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_missing_additional_resource_dir_fails_by_default() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let missing = temp_dir.path().join("missing").join("res");
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        builder.config.additional_resource_dirs = Some(vec![missing.clone()]);
+
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains(&missing.display().to_string()));
+        assert!(result.errors[0].contains("missingResourceDirPolicy"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_invalid_file_names_are_reported_together() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        for file in [
+            "drawable/Logo.png",
+            "drawable/icon bg.png",
+            "drawable/ok.png",
+        ] {
+            fs::create_dir_all(res_dir.join(file).parent().unwrap())?;
+            fs::write(res_dir.join(file), "data")?;
+        }
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert_eq!(result.errors.len(), 2);
+        assert!(
+            result.errors.iter().any(|e| e.contains("Logo.png")
+                && e.contains("uppercase")
+                && e.contains("logo.png"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_staged_resources_report_source_paths() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("drawable"))?;
+        fs::write(res_dir.join("drawable").join("Logo.png"), "data")?;
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        builder.config.stage_resources = Some(true);
+
+        let result = builder.build().await?;
+        assert!(!result.success);
+        let source = res_dir.join("drawable").join("Logo.png");
+        assert!(result.errors[0].starts_with(&source.display().to_string()));
+        let staged = temp_dir
+            .path()
+            .join("output/.build/com.test/staging/main/drawable/Logo.png");
+        assert!(staged.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_pre_build_hooks_run_before_discovery() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let drawable = temp_dir.path().join("res").join("drawable");
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        builder.config.hooks = Some(HooksConfig {
+            pre_build: vec![
+                format!(
+                    "mkdir -p {0} && touch {0}/Generated_$ASB_PACKAGE.png",
+                    drawable.display()
+                )
+                .as_str()
+                .into(),
+                "exit 1".into(),
+            ],
+            on_failure: Some(HookFailurePolicy::Warn),
+            ..Default::default()
+        });
+
+        // The generated file is discovered; the failing command only warns
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert!(result.errors[0].contains("Generated_com.test.png"));
+        assert!(result.warnings[0].starts_with("preBuild hook `exit 1` failed"));
+
+        builder.config.hooks.as_mut().unwrap().on_failure = None;
+        let result = builder.build().await?;
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].starts_with("preBuild hook `exit 1` failed"));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    #[cfg(unix)]
+    async fn test_running_hooks_leave_runtime_running() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let temp_dir = TempDir::new()?;
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        builder.config.hooks = Some(HooksConfig {
+            pre_build: vec!["sleep 1".into()],
+            ..Default::default()
+        });
+
+        // The build and the ticker share the runtime's only worker
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        tokio::spawn(async move { builder.build().await }).await??;
+        ticker.abort();
+        assert!(ticks.load(Ordering::Relaxed) > 20);
+        Ok(())
+    }
+
+    /// Replace the aapt2 of [`symlink_test_builder`] with one that writes a flat file per
+    /// compiled resource and links by copying `template.apk`, so builds can succeed
+    /// without the Android SDK
+    #[cfg(unix)]
+    fn fake_linking_aapt2(temp_dir: &TempDir) -> Result<()> {
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let template = temp_dir.path().join("template.apk");
+        let mut apk = zip::ZipWriter::new(fs::File::create(&template)?);
+        apk.start_file(
+            "AndroidManifest.xml",
+            zip::write::SimpleFileOptions::default(),
+        )?;
+        apk.write_all(b"manifest")?;
+        apk.start_file("resources.arsc", zip::write::SimpleFileOptions::default())?;
+        apk.write_all(b"table")?;
+        apk.finish()?;
+
+        let aapt2 = temp_dir.path().join("aapt2");
+        fs::write(
+            &aapt2,
+            format!(
+                "#!/bin/sh\n\
+                 command=$1; shift\n\
+                 while [ \"$1\" != \"-o\" ]; do shift; done\n\
+                 out=$2; shift 2\n\
+                 if [ \"$command\" = link ]; then cp {} \"$out\"; exit 0; fi\n\
+                 for f in \"$@\"; do\n\
+                 touch \"$out/$(basename $(dirname $f))_$(basename $f).flat\"\n\
+                 done\n",
+                template.display()
+            ),
+        )?;
+        fs::set_permissions(&aapt2, fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_failed_post_build_hook_leaves_package_out_of_date() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let drawable = temp_dir.path().join("res").join("drawable");
+        fs::create_dir_all(&drawable)?;
+        fs::write(drawable.join("bg.xml"), "<shape/>")?;
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        fake_linking_aapt2(&temp_dir)?;
+        builder.config.hooks = Some(HooksConfig {
+            post_build: vec!["exit 1".into()],
+            ..Default::default()
+        });
+
+        let result = builder.build().await?;
+        assert!(!result.success, "{:?}", result.errors);
+        assert!(result.errors[0].starts_with("postBuild hook `exit 1` failed"));
+        assert!(!crate::fingerprint::is_up_to_date(
+            &builder.requested_config
+        )?);
+
+        // Once the hook passes, the same inputs are up to date
+        builder.config.hooks = None;
+        let result = builder.build().await?;
+        assert!(result.success, "{:?}", result.errors);
+        assert!(crate::fingerprint::is_up_to_date(
+            &builder.requested_config
+        )?);
+
+        // A failing hook after a successful build drops the earlier fingerprint
+        builder.config.hooks = Some(HooksConfig {
+            post_build: vec!["exit 1".into()],
+            ..Default::default()
+        });
+        assert!(!builder.build().await?.success);
+        assert!(!crate::fingerprint::is_up_to_date(
+            &builder.requested_config
+        )?);
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl BuildObserver for RecordingObserver {
+        fn on_phase_start(&self, package: &str, phase: BuildPhase) {
+            let call = format!("{}: {}", package, phase);
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn on_warning(&self, package: &str, message: &str) {
+            let call = format!("{}: warning {}", package, message);
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn on_package_done(&self, package: &str, result: &Result<BuildResult>) {
+            let success = result.as_ref().is_ok_and(|r| r.success);
+            let call = format!("{}: done success={}", package, success);
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_observer_follows_build_progress() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let observer = Arc::new(RecordingObserver::default());
+        let mut builder = symlink_test_builder(&temp_dir, false)?.with_observer(observer.clone());
+        builder.config.hooks = Some(HooksConfig {
+            pre_build: vec!["exit 1".into()],
+            on_failure: Some(HookFailurePolicy::Warn),
+            ..Default::default()
+        });
+        fs::create_dir_all(temp_dir.path().join("res").join("drawable"))?;
+        fs::write(temp_dir.path().join("res/drawable/Logo.png"), "data")?;
+
+        let result = builder.build().await?;
+        assert!(!result.success);
+        let calls = observer.calls.lock().unwrap();
+        assert_eq!(calls.first().unwrap(), "com.test: preBuild hooks");
+        assert!(calls.contains(&"com.test: compiling resources".to_string()));
+        let done = calls.len() - 1;
+        assert_eq!(calls[done], "com.test: done success=false");
+        assert!(calls[done - 1].starts_with("com.test: warning preBuild hook `exit 1` failed"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locale_qualifiers_are_checked_and_normalized() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res = temp_dir.path().join("res");
+        fs::create_dir_all(res.join("values-zh-CN"))?;
+        fs::write(res.join("values-zh-CN").join("colors.xml"), "<resources/>")?;
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert!(result.errors[0].contains("values-zh-CN: invalid locale qualifier"));
+
+        fs::rename(res.join("values-zh-CN"), res.join("values-b+zh+CN"))?;
+        fs::create_dir_all(res.join("drawable-b+zh+CN"))?;
+        fs::write(res.join("drawable-b+zh+CN").join("Logo.png"), "logo")?;
+        builder.config.locale_qualifiers = Some(LocaleQualifierForm::Legacy);
+
+        // The file name check sees the copy but reports the source
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert!(result.errors[0].contains("drawable-b+zh+CN/Logo.png: file name"));
+        let locale_res = temp_dir
+            .path()
+            .join("output/.build/com.test/locale_res/main");
+        assert!(locale_res.join("values-zh-rCN/colors.xml").exists());
+        assert!(locale_res.join("drawable-zh-rCN/Logo.png").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_utf16_resources_fail_or_are_converted_when_staged() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let values = temp_dir.path().join("res").join("values");
+        fs::create_dir_all(&values)?;
+        let utf16: Vec<u8> = "<resources/>"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        fs::write(values.join("colors.xml"), &utf16)?;
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert!(result.errors[0].contains("values/colors.xml: file is UTF-16LE"));
+
+        // Converted copies get as far as aapt2, which the test doesn't have
+        builder.config.convert_encodings = Some(true);
+        let err = builder.build().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("aapt2"));
+        let staged = temp_dir
+            .path()
+            .join("output/.build/com.test/staging/main/values/colors.xml");
+        assert_eq!(fs::read(staged)?, b"<resources/>");
+        assert_eq!(fs::read(values.join("colors.xml"))?, utf16);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_preprocessor_plugins_replace_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let drawable = temp_dir.path().join("res").join("drawable");
+        fs::create_dir_all(&drawable)?;
+        fs::write(drawable.join("icon.png"), "icon")?;
+        let generated = temp_dir.path().join("generated").join("drawable");
+        fs::create_dir_all(&generated)?;
+        fs::write(generated.join("Plugin.png"), "generated")?;
+
+        let mut builder = symlink_test_builder(&temp_dir, false)?;
+        builder.config.plugins = Some(vec![PluginConfig {
+            name: None,
+            command: PathBuf::from("sh"),
+            args: vec![
+                "-c".to_string(),
+                format!(
+                    r#"echo '{{"type":"result","files":["{}"]}}'"#,
+                    generated.join("Plugin.png").display()
+                ),
+            ],
+            kind: PluginKind::Preprocessor,
+            timeout_secs: None,
+        }]);
+
+        // Only the plugin's file is compiled, and its name is checked like any other
+        let result = builder.build().await?;
+        assert!(!result.success);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("generated/drawable/Plugin.png"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_precompiled_dependency() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        let prebuilt_dir = temp_dir.path().join("prebuilt");
+        fs::create_dir_all(prebuilt_dir.join("nested"))?;
+        fs::write(prebuilt_dir.join("values_colors.arsc.flat"), "flat")?;
+        fs::write(prebuilt_dir.join("lib.apk"), "apk")?;
+        fs::write(prebuilt_dir.join("notes.txt"), "ignored")?;
+        fs::write(prebuilt_dir.join("nested").join("skipped.flat"), "flat")?;
+
+        let inputs = resolve_precompiled_dependency(&prebuilt_dir)?;
+        assert_eq!(
+            inputs,
+            vec![
+                prebuilt_dir.join("lib.apk"),
+                prebuilt_dir.join("values_colors.arsc.flat"),
+            ]
+        );
+
+        // A single file is used as-is
+        let flat = prebuilt_dir.join("values_colors.arsc.flat");
+        assert_eq!(resolve_precompiled_dependency(&flat)?, vec![flat.clone()]);
+
+        // Missing paths, unsupported files and empty directories are rejected
+        assert!(resolve_precompiled_dependency(&temp_dir.path().join("missing")).is_err());
+        assert!(resolve_precompiled_dependency(&prebuilt_dir.join("notes.txt")).is_err());
+        let empty_dir = temp_dir.path().join("empty");
+        fs::create_dir_all(&empty_dir)?;
+        assert!(resolve_precompiled_dependency(&empty_dir).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_code_strategy() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::write(temp_dir.path().join("aapt2"), b"")?;
+        let variable = "ASB_TEST_VERSION_CODE_STRATEGY";
+        let config = BuildConfig {
+            resource_dir: temp_dir.path().join("res"),
+            output_dir: temp_dir.path().join("output"),
+            aapt2_path: Some(temp_dir.path().join("aapt2")),
+            incremental: Some(true),
+            version_code: None,
+            version_code_strategy: Some(VersionCodeStrategy::FromEnv {
+                variable: variable.to_string(),
+            }),
+            ..BuildConfig::default_config()
+        };
+
+        let fixed = VersionCodeStrategy::Fixed { value: 7 };
+        assert_eq!(compute_version_code(&fixed, temp_dir.path())?, 7);
+
+        // SAFETY: the variable name is unique to this test
+        unsafe { std::env::set_var(variable, "5000000000") };
+        let mut builder = SkinBuilder::new(config.clone())?;
+        assert!(builder.resolve_version_code().is_err());
+
+        unsafe { std::env::set_var(variable, "120") };
+        builder.resolve_version_code()?;
+        assert_eq!(builder.config.version_code, Some(120));
+        builder.record_version_code()?;
+
+        // A later build may not go backwards
+        unsafe { std::env::set_var(variable, "119") };
+        let mut builder = SkinBuilder::new(config.clone())?;
+        assert!(builder.resolve_version_code().is_err());
+
+        // An explicit versionCode disables the strategy
+        let mut builder = SkinBuilder::new(BuildConfig {
+            version_code: Some(3),
+            ..config
+        })?;
+        builder.resolve_version_code()?;
+        assert_eq!(builder.config.version_code, Some(3));
+
+        unsafe { std::env::remove_var(variable) };
+        Ok(())
+    }
 }
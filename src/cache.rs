@@ -1,7 +1,7 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use tracing::debug;
 
@@ -12,12 +12,53 @@ struct CacheEntry {
     flat_file: PathBuf,
 }
 
+/// An AAR extraction kept between incremental builds
+#[derive(Debug, Serialize, Deserialize)]
+struct AarCacheEntry {
+    /// Content hash of the AAR (and the filters applied to it)
+    hash: String,
+    extracted_dir: PathBuf,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CacheData {
     version: String,
+    /// Fingerprint of the compile options the cached flat files were produced with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compile_fingerprint: Option<String>,
+    /// versionCode of the last successful build, used for monotonicity checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_version_code: Option<u32>,
+    /// Phase durations of the last successful build, used to schedule long builds first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_durations: Option<BuildDurations>,
+    /// Bytes of intermediates and package left by the last successful build, used to
+    /// estimate the disk space the next one needs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_disk_usage: Option<u64>,
+    /// Link-relevant configuration of the last build, see `set_link_signature`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link_signature: Option<String>,
+    /// Extracted AARs by AAR path
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    aars: HashMap<PathBuf, AarCacheEntry>,
     entries: HashMap<PathBuf, CacheEntry>,
 }
 
+/// Time spent compiling and linking one package
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildDurations {
+    pub compile_ms: u64,
+    pub link_ms: u64,
+}
+
+impl BuildDurations {
+    pub fn total_ms(&self) -> u64 {
+        self.compile_ms + self.link_ms
+    }
+}
+
 /// Utility for managing build cache for incremental builds
 pub struct BuildCache {
     cache_dir: PathBuf,
@@ -26,6 +67,15 @@ pub struct BuildCache {
     /// In-memory cache of recently computed hashes to avoid recomputing them
     /// when updating entries after a needs_recompile check.
     pending_hashes: HashMap<PathBuf, String>,
+    /// Set when file changes are reported through `invalidate`; `None` when every check
+    /// hashes the file
+    tracking: Option<ChangeTracking>,
+}
+
+/// Resource files below watched directories known to be unchanged since their entry was updated
+struct ChangeTracking {
+    roots: Vec<PathBuf>,
+    verified: HashSet<PathBuf>,
 }
 
 impl BuildCache {
@@ -50,12 +100,19 @@ impl BuildCache {
             cache_file,
             cache,
             pending_hashes: HashMap::new(),
+            tracking: None,
         })
     }
 
     fn empty_cache() -> CacheData {
         CacheData {
             version: "1.0".to_string(),
+            compile_fingerprint: None,
+            last_version_code: None,
+            last_durations: None,
+            last_disk_usage: None,
+            link_signature: None,
+            aars: HashMap::new(),
             entries: HashMap::new(),
         }
     }
@@ -66,6 +123,112 @@ impl BuildCache {
         Ok(())
     }
 
+    /// Content-addressed store of cached flat files, kept apart from the compiled directory
+    fn flat_store(&self) -> PathBuf {
+        self.cache_dir.join("flats")
+    }
+
+    /// Stored copy of the flat file compiled from a source with this content hash
+    fn stored_flat(&self, hash: &str, flat_file: &Path) -> PathBuf {
+        let name = flat_file.file_name().unwrap_or_default().to_string_lossy();
+        self.flat_store()
+            .join(&hash[..2.min(hash.len())])
+            .join(format!("{}-{}", hash, name))
+    }
+
+    /// Record the compile options fingerprint for this build
+    /// Cached flat files produced with different options are discarded
+    pub fn set_compile_fingerprint(&mut self, fingerprint: Option<String>) {
+        if self.cache.compile_fingerprint != fingerprint {
+            if !self.cache.entries.is_empty() {
+                debug!("Compile options changed, invalidating cached flat files");
+            }
+            self.cache.entries.clear();
+            self.pending_hashes.clear();
+            let store = self.flat_store();
+            if store.exists()
+                && let Err(e) = std::fs::remove_dir_all(&store)
+            {
+                debug!("Failed to clear flat store {}: {}", store.display(), e);
+            }
+            self.cache.compile_fingerprint = fingerprint;
+        }
+    }
+
+    /// versionCode recorded by the last successful build
+    pub fn last_version_code(&self) -> Option<u32> {
+        self.cache.last_version_code
+    }
+
+    /// Record the versionCode of a successful build
+    pub fn set_last_version_code(&mut self, version_code: u32) {
+        self.cache.last_version_code = Some(version_code);
+    }
+
+    /// Phase durations recorded by the last successful build
+    pub fn last_durations(&self) -> Option<BuildDurations> {
+        self.cache.last_durations
+    }
+
+    /// Record phase durations of a successful build
+    pub fn set_last_durations(&mut self, durations: BuildDurations) {
+        self.cache.last_durations = Some(durations);
+    }
+
+    /// Disk usage recorded by the last successful build, in bytes
+    pub fn last_disk_usage(&self) -> Option<u64> {
+        self.cache.last_disk_usage
+    }
+
+    /// Record the disk usage of a successful build
+    pub fn set_last_disk_usage(&mut self, bytes: u64) {
+        self.cache.last_disk_usage = Some(bytes);
+    }
+
+    /// Record the link signature (serialized link-relevant configuration) of this build
+    /// Returns whether it differs from the previous build's, in which case intermediates
+    /// derived from the old configuration must not be reused.
+    pub fn set_link_signature(&mut self, signature: String) -> bool {
+        let changed = self.cache.link_signature.as_ref() != Some(&signature);
+        if changed && self.cache.link_signature.is_some() {
+            debug!("Link configuration changed since the last build");
+        }
+        self.cache.link_signature = Some(signature);
+        changed
+    }
+
+    /// Whether `extracted_dir` still holds the extraction of the AAR with this hash
+    pub fn is_aar_extracted(&self, aar: &Path, hash: &str, extracted_dir: &Path) -> bool {
+        self.cache.aars.get(aar).is_some_and(|entry| {
+            entry.hash == hash && entry.extracted_dir == extracted_dir && extracted_dir.exists()
+        })
+    }
+
+    /// Record the extraction of an AAR
+    /// When the AAR changed, cached flat files of its previous extraction are dropped so its
+    /// resources are compiled again.
+    pub fn record_aar(&mut self, aar: &Path, hash: &str, extracted_dir: &Path) {
+        if let Some(previous) = self.cache.aars.get(aar) {
+            if previous.hash == hash && previous.extracted_dir == extracted_dir {
+                return;
+            }
+            debug!("AAR {} changed, invalidating its resources", aar.display());
+            let stale = [previous.extracted_dir.clone(), extracted_dir.to_path_buf()];
+            self.cache
+                .entries
+                .retain(|file, _| !stale.iter().any(|dir| file.starts_with(dir)));
+            self.pending_hashes
+                .retain(|file, _| !stale.iter().any(|dir| file.starts_with(dir)));
+        }
+        self.cache.aars.insert(
+            aar.to_path_buf(),
+            AarCacheEntry {
+                hash: hash.to_string(),
+                extracted_dir: extracted_dir.to_path_buf(),
+            },
+        );
+    }
+
     /// Calculate file hash
     fn calculate_hash(file_path: &Path) -> Result<String> {
         let content = std::fs::read(file_path)?;
@@ -74,8 +237,41 @@ impl BuildCache {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
+    /// Trust that resource files below `roots` stay unchanged until `invalidate` reports them
+    /// Used by long-running processes watching the sources, so unchanged files are not hashed
+    /// again on every build.
+    pub fn track_changes(&mut self, roots: Vec<PathBuf>) {
+        self.tracking = Some(ChangeTracking {
+            roots,
+            verified: HashSet::new(),
+        });
+    }
+
+    /// Report that a resource file (or every file below a directory) changed or was removed
+    pub fn invalidate(&mut self, path: &Path) {
+        if let Some(tracking) = self.tracking.as_mut() {
+            tracking.verified.retain(|file| !file.starts_with(path));
+        }
+    }
+
+    fn is_verified(&self, resource_file: &Path) -> bool {
+        self.tracking
+            .as_ref()
+            .is_some_and(|tracking| tracking.verified.contains(resource_file))
+    }
+
     /// Check if a file needs recompilation
     pub fn needs_recompile(&mut self, resource_file: &Path) -> Result<bool> {
+        if self.is_verified(resource_file)
+            && self
+                .cache
+                .entries
+                .get(resource_file)
+                .is_some_and(|entry| entry.flat_file.exists())
+        {
+            return Ok(false);
+        }
+
         let entry = self.cache.entries.get(resource_file);
 
         if entry.is_none() {
@@ -84,8 +280,8 @@ impl BuildCache {
 
         let entry = entry.unwrap();
 
-        // Check if flat file still exists
-        if !entry.flat_file.exists() {
+        // Check if the flat file can still be restored
+        if !self.stored_flat(&entry.hash, &entry.flat_file).exists() && !entry.flat_file.exists() {
             return Ok(true);
         }
 
@@ -111,13 +307,54 @@ impl BuildCache {
             .map(|e| e.flat_file.clone())
     }
 
+    /// Place the cached flat file of an unchanged resource at `target`
+    /// The flat file is hard-linked from the content-addressed store (copied where links
+    /// are not supported), so reuse does not depend on where it was first compiled to.
+    pub fn restore_flat_file(&self, resource_file: &Path, target: &Path) -> Result<()> {
+        let entry = self
+            .cache
+            .entries
+            .get(resource_file)
+            .ok_or_else(|| anyhow::anyhow!("No cache entry for {}", resource_file.display()))?;
+        let stored = self.stored_flat(&entry.hash, &entry.flat_file);
+        if !stored.exists() {
+            // Entries written before the store existed only have their original flat file
+            link_or_copy(&entry.flat_file, &stored)?;
+        }
+        if target != stored {
+            link_or_copy(&stored, target)?;
+        }
+        Ok(())
+    }
+
+    /// Content hash recorded for a resource file
+    pub fn cached_hash(&self, resource_file: &Path) -> Option<&str> {
+        self.cache
+            .entries
+            .get(resource_file)
+            .map(|e| e.hash.as_str())
+    }
+
     /// Update cache entry
     pub fn update_entry(&mut self, resource_file: &Path, flat_file: &Path) -> Result<()> {
+        if self.is_verified(resource_file)
+            && let Some(entry) = self.cache.entries.get(resource_file)
+            && entry.flat_file == flat_file
+        {
+            return Ok(());
+        }
         // Reuse the hash computed during needs_recompile if available
         let hash = match self.pending_hashes.remove(resource_file) {
             Some(h) => h,
             None => Self::calculate_hash(resource_file)?,
         };
+        let stored = self.stored_flat(&hash, flat_file);
+        if !stored.exists()
+            && flat_file.exists()
+            && let Err(e) = link_or_copy(flat_file, &stored)
+        {
+            debug!("Failed to store {}: {}", flat_file.display(), e);
+        }
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -131,6 +368,14 @@ impl BuildCache {
                 flat_file: flat_file.to_path_buf(),
             },
         );
+        if let Some(tracking) = self.tracking.as_mut()
+            && tracking
+                .roots
+                .iter()
+                .any(|root| resource_file.starts_with(root))
+        {
+            tracking.verified.insert(resource_file.to_path_buf());
+        }
 
         Ok(())
     }
@@ -154,6 +399,53 @@ impl BuildCache {
         Ok(())
     }
 
+    /// Delete stored flat files no cache entry refers to any more, such as those of
+    /// earlier versions of a resource
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes freed
+    pub fn prune_store(&self) -> Result<u64> {
+        let referenced: HashSet<PathBuf> = self
+            .cache
+            .entries
+            .values()
+            .map(|entry| self.stored_flat(&entry.hash, &entry.flat_file))
+            .collect();
+        let mut freed = 0;
+        for entry in walkdir::WalkDir::new(self.flat_store())
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            if !referenced.contains(entry.path()) {
+                let size = entry.metadata().map_or(0, |m| m.len());
+                std::fs::remove_file(entry.path())
+                    .with_context(|| format!("Failed to remove {}", entry.path().display()))?;
+                freed += size;
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Cached resource files under `dir` whose flat file can still be restored, with their
+    /// flat files
+    pub fn cached_entries_under(&self, dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let mut entries: Vec<(PathBuf, PathBuf)> = self
+            .cache
+            .entries
+            .iter()
+            .filter(|(file, entry)| {
+                file.starts_with(dir)
+                    && (entry.flat_file.exists()
+                        || self.stored_flat(&entry.hash, &entry.flat_file).exists())
+            })
+            .map(|(file, entry)| (file.clone(), entry.flat_file.clone()))
+            .collect();
+        entries.sort();
+        entries
+    }
+
     /// Get all cached flat files
     #[allow(dead_code)]
     pub fn get_all_cached_flat_files(&self) -> Vec<PathBuf> {
@@ -165,6 +457,22 @@ impl BuildCache {
     }
 }
 
+/// Hard-link `src` to `dst`, replacing `dst`
+/// Falls back to copying, which clones the file on copy-on-write filesystems.
+pub(crate) fn link_or_copy(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if dst.symlink_metadata().is_ok() {
+        std::fs::remove_file(dst)?;
+    }
+    if std::fs::hard_link(src, dst).is_err() {
+        std::fs::copy(src, dst)
+            .with_context(|| format!("Failed to copy {} to {}", src.display(), dst.display()))?;
+    }
+    Ok(())
+}
+
 /// Cache entry for compiled common dependencies
 #[derive(Debug, Serialize, Deserialize)]
 struct CommonDepCacheEntry {
@@ -176,6 +484,9 @@ struct CommonDepCacheEntry {
     timestamp: u64,
     /// Paths to all compiled flat files for this dependency
     flat_files: Vec<PathBuf>,
+    /// Static library linked from the flat files, if one was built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    static_lib: Option<PathBuf>,
 }
 
 /// Cache data for common dependencies
@@ -304,12 +615,30 @@ impl CommonDependencyCache {
                 directory_hash,
                 timestamp,
                 flat_files,
+                static_lib: None,
             },
         );
 
         Ok(())
     }
 
+    /// Get the cached static library for a common dependency, if it still exists
+    pub fn get_cached_static_lib(&self, resource_dir: &Path) -> Option<PathBuf> {
+        self.cache
+            .entries
+            .get(resource_dir)
+            .and_then(|e| e.static_lib.clone())
+            .filter(|lib| lib.exists())
+    }
+
+    /// Record the static library linked for a common dependency
+    /// Has no effect if the dependency has no cache entry
+    pub fn set_static_lib(&mut self, resource_dir: &Path, static_lib: PathBuf) {
+        if let Some(entry) = self.cache.entries.get_mut(resource_dir) {
+            entry.static_lib = Some(static_lib);
+        }
+    }
+
     /// Save cache to disk
     pub fn save(&self) -> Result<()> {
         let content = serde_json::to_string_pretty(&self.cache)?;
@@ -378,6 +707,88 @@ mod tests {
         assert!(result, "No entry should mean recompile is needed");
     }
 
+    #[test]
+    fn test_build_cache_compile_fingerprint_invalidates_entries() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+
+        let source = create_temp_file(tmp.path(), "icon.png", b"png");
+        let flat = create_temp_file(tmp.path(), "drawable_icon.png.flat", b"flat");
+        cache.update_entry(&source, &flat).unwrap();
+        cache.save().unwrap();
+
+        // Same (absent) fingerprint keeps entries across reloads
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.set_compile_fingerprint(None);
+        assert!(!cache.needs_recompile(&source).unwrap());
+
+        // Changed compile options drop every entry
+        cache.set_compile_fingerprint(Some("{\"drawable\":{\"noCrunch\":true}}".to_string()));
+        assert!(cache.needs_recompile(&source).unwrap());
+        assert!(cache.get_all_cached_flat_files().is_empty());
+    }
+
+    #[test]
+    fn test_build_cache_last_version_code_persists() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+        assert_eq!(cache.last_version_code(), None);
+
+        cache.set_last_version_code(42);
+        cache.save().unwrap();
+
+        // Survives reloads and compile option changes
+        let mut cache = BuildCache::new(cache_dir).unwrap();
+        cache.set_compile_fingerprint(Some("{}".to_string()));
+        assert_eq!(cache.last_version_code(), Some(42));
+    }
+
+    #[test]
+    fn test_build_cache_last_durations_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        assert_eq!(cache.last_durations(), None);
+
+        let durations = BuildDurations {
+            compile_ms: 1200,
+            link_ms: 300,
+        };
+        cache.init().unwrap();
+        cache.set_last_durations(durations);
+        cache.save().unwrap();
+
+        let cache = BuildCache::new(cache_dir).unwrap();
+        assert_eq!(cache.last_durations(), Some(durations));
+        assert_eq!(durations.total_ms(), 1500);
+    }
+
+    #[test]
+    fn test_build_cache_prune_store() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+        let file = create_temp_file(tmp.path(), "colors.xml", b"v1");
+        let flat = create_temp_file(tmp.path(), "values_colors.arsc.flat", b"flat v1");
+        cache.update_entry(&file, &flat).unwrap();
+        // Flat files may be hard links into the store, so aapt2 replaces rather than rewrites them
+        std::fs::write(&file, b"v2").unwrap();
+        std::fs::remove_file(&flat).unwrap();
+        std::fs::write(&flat, b"flat v2!").unwrap();
+        cache.update_entry(&file, &flat).unwrap();
+
+        assert_eq!(cache.prune_store().unwrap(), 7);
+        assert_eq!(cache.prune_store().unwrap(), 0);
+        let target = tmp.path().join("restored.flat");
+        cache.restore_flat_file(&file, &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"flat v2!");
+    }
+
     #[test]
     fn test_build_cache_get_cached_flat_file_none() {
         let tmp = TempDir::new().unwrap();
@@ -411,6 +822,124 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_build_cache_restores_flat_from_store() {
+        let tmp = TempDir::new().unwrap();
+        let mut cache = BuildCache::new(tmp.path().join("cache")).unwrap();
+        cache.init().unwrap();
+
+        let source = create_temp_file(tmp.path(), "colors.xml", b"<resources/>");
+        let compiled_dir = tmp.path().join("compiled");
+        fs::create_dir_all(&compiled_dir).unwrap();
+        let flat = create_temp_file(&compiled_dir, "values_colors.arsc.flat", b"flat_data");
+        cache.update_entry(&source, &flat).unwrap();
+
+        // The compiled directory is cleared between builds
+        fs::remove_dir_all(&compiled_dir).unwrap();
+        assert!(!cache.needs_recompile(&source).unwrap());
+
+        let target = tmp.path().join("other").join("values_colors.arsc.flat");
+        cache.restore_flat_file(&source, &target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"flat_data");
+    }
+
+    #[test]
+    fn test_build_cache_skips_hashing_until_invalidated() {
+        let tmp = TempDir::new().unwrap();
+        let mut cache = BuildCache::new(tmp.path().join("cache")).unwrap();
+        cache.init().unwrap();
+        let res_dir = tmp.path().join("res");
+        cache.track_changes(vec![res_dir.clone()]);
+
+        let values = res_dir.join("values");
+        fs::create_dir_all(&values).unwrap();
+        let source = create_temp_file(&values, "colors.xml", b"<resources/>");
+        let flat = create_temp_file(tmp.path(), "values_colors.arsc.flat", b"flat_data");
+        cache.update_entry(&source, &flat).unwrap();
+
+        // Without a reported change, the edit is not noticed
+        fs::write(&source, b"<resources><color/></resources>").unwrap();
+        assert!(!cache.needs_recompile(&source).unwrap());
+
+        cache.invalidate(&values);
+        assert!(cache.needs_recompile(&source).unwrap());
+    }
+
+    #[test]
+    fn test_build_cache_cached_entries_under() {
+        let tmp = TempDir::new().unwrap();
+        let mut cache = BuildCache::new(tmp.path().join("cache")).unwrap();
+        cache.init().unwrap();
+
+        let res_dir = tmp.path().join("res");
+        fs::create_dir_all(&res_dir).unwrap();
+        let colors = create_temp_file(&res_dir, "colors.xml", b"<resources/>");
+        let icon = create_temp_file(&res_dir, "icon.png", b"png");
+        let other = create_temp_file(tmp.path(), "other.xml", b"<resources/>");
+        let colors_flat = create_temp_file(tmp.path(), "colors.arsc.flat", b"flat");
+        let other_flat = create_temp_file(tmp.path(), "other.arsc.flat", b"flat");
+        cache.update_entry(&colors, &colors_flat).unwrap();
+        cache
+            .update_entry(&icon, &tmp.path().join("missing.flat"))
+            .unwrap();
+        cache.update_entry(&other, &other_flat).unwrap();
+
+        assert_eq!(
+            cache.cached_entries_under(&res_dir),
+            vec![(colors, colors_flat)],
+            "Only entries under the directory with an existing flat file"
+        );
+    }
+
+    #[test]
+    fn test_build_cache_link_signature() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+
+        assert!(cache.set_link_signature(r#"{"packageId":"0x7f"}"#.to_string()));
+        assert!(!cache.set_link_signature(r#"{"packageId":"0x7f"}"#.to_string()));
+        cache.save().unwrap();
+
+        let mut cache = BuildCache::new(cache_dir).unwrap();
+        assert!(!cache.set_link_signature(r#"{"packageId":"0x7f"}"#.to_string()));
+        assert!(cache.set_link_signature(r#"{"packageId":"0x80"}"#.to_string()));
+    }
+
+    #[test]
+    fn test_build_cache_aar_change_invalidates_its_resources() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = BuildCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+
+        let aar = tmp.path().join("lib.aar");
+        let extracted = tmp.path().join(".temp/aar_0_lib");
+        fs::create_dir_all(extracted.join("res/values")).unwrap();
+        let colors = create_temp_file(&extracted, "res/values/colors.xml", b"<resources/>");
+        let main = create_temp_file(tmp.path(), "strings.xml", b"<resources/>");
+        let flat = create_temp_file(tmp.path(), "values_colors.arsc.flat", b"flat");
+        cache.update_entry(&colors, &flat).unwrap();
+        cache.update_entry(&main, &flat).unwrap();
+
+        cache.record_aar(&aar, "v1", &extracted);
+        cache.save().unwrap();
+        let mut cache = BuildCache::new(cache_dir).unwrap();
+        assert!(cache.is_aar_extracted(&aar, "v1", &extracted));
+        assert!(!cache.is_aar_extracted(&aar, "v2", &extracted));
+
+        // Same AAR: cached resources survive
+        cache.record_aar(&aar, "v1", &extracted);
+        assert!(!cache.needs_recompile(&colors).unwrap());
+
+        // Replaced AAR: its resources are compiled again, others are untouched
+        cache.record_aar(&aar, "v2", &extracted);
+        assert!(cache.needs_recompile(&colors).unwrap());
+        assert!(!cache.needs_recompile(&main).unwrap());
+        assert!(cache.is_aar_extracted(&aar, "v2", &extracted));
+    }
+
     #[test]
     fn test_build_cache_needs_recompile_unchanged_file() {
         let tmp = TempDir::new().unwrap();
@@ -636,6 +1165,34 @@ mod tests {
         assert!(result, "No entry should mean recompile needed");
     }
 
+    #[test]
+    fn test_common_dep_cache_static_lib_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let mut cache = CommonDependencyCache::new(cache_dir.clone()).unwrap();
+        cache.init().unwrap();
+
+        let res_dir = tmp.path().join("res");
+        fs::create_dir_all(&res_dir).unwrap();
+        create_temp_file(&res_dir, "colors.xml", b"<resources/>");
+        let flat = create_temp_file(tmp.path(), "values_colors.arsc.flat", b"flat");
+        let lib = tmp.path().join("static-lib.apk");
+
+        // Recording a lib for an unknown dependency is ignored
+        cache.set_static_lib(&res_dir, lib.clone());
+        assert!(cache.get_cached_static_lib(&res_dir).is_none());
+
+        cache.update_entry(&res_dir, vec![flat]).unwrap();
+        cache.set_static_lib(&res_dir, lib.clone());
+        // Not returned until the library actually exists
+        assert!(cache.get_cached_static_lib(&res_dir).is_none());
+
+        fs::write(&lib, b"apk").unwrap();
+        cache.save().unwrap();
+        let reloaded = CommonDependencyCache::new(cache_dir).unwrap();
+        assert_eq!(reloaded.get_cached_static_lib(&res_dir), Some(lib));
+    }
+
     #[test]
     fn test_common_dep_cache_update_and_get() {
         let tmp = TempDir::new().unwrap();
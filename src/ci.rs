@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use crate::annotations::collapse;
+use crate::types::BuildResult;
+
+/// Compact CI summary of a build: one line per package, the collapsed warnings and a
+/// total line
+///
+/// `failed` counts every failed package, including builds that ended with an error
+/// instead of a result.
+pub fn summary(results: &[(&str, &BuildResult)], failed: usize, elapsed: Duration) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (package, result) in results {
+        if result.success {
+            let output = result
+                .apk_path
+                .as_ref()
+                .map(|path| format!(" {}", path.display()))
+                .unwrap_or_default();
+            lines.push(format!(
+                "ok     {} ({:.2}s){}",
+                package,
+                result.build_duration.as_secs_f64(),
+                output
+            ));
+        } else {
+            lines.push(format!(
+                "FAILED {} ({:.2}s): {} error(s)",
+                package,
+                result.build_duration.as_secs_f64(),
+                result.errors.len()
+            ));
+        }
+    }
+    let warnings = collapse(results.iter().flat_map(|(_, result)| &result.warnings));
+    for (message, count) in &warnings {
+        lines.push(format!("warning (x{}): {}", count, message));
+    }
+    let built = results.iter().filter(|(_, result)| result.success).count();
+    lines.push(format!(
+        "Built {}/{} packages in {:.2}s, {} failed, {} warning(s) ({} distinct)",
+        built,
+        built + failed,
+        elapsed.as_secs_f64(),
+        failed,
+        warnings.iter().map(|(_, count)| count).sum::<usize>(),
+        warnings.len()
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_summary_collapses_warnings() {
+        let warning = "AAR filter 'a*' matches none of the AAR files".to_string();
+        let red = BuildResult {
+            success: true,
+            apk_path: Some(PathBuf::from("out/com.red.skin")),
+            errors: Vec::new(),
+            warnings: vec![warning.clone()],
+            build_duration: Duration::from_millis(1500),
+            vcs: None,
+            cached_files: 0,
+            compiled_files: 0,
+            normalization: None,
+        };
+        let blue = BuildResult {
+            success: false,
+            apk_path: None,
+            errors: vec!["aapt2 link failed".to_string()],
+            warnings: vec![warning],
+            build_duration: Duration::from_millis(250),
+            vcs: None,
+            cached_files: 0,
+            compiled_files: 0,
+            normalization: None,
+        };
+        assert_eq!(
+            summary(
+                &[("com.red", &red), ("com.blue", &blue)],
+                2,
+                Duration::from_secs(2)
+            ),
+            vec![
+                "ok     com.red (1.50s) out/com.red.skin",
+                "FAILED com.blue (0.25s): 1 error(s)",
+                "warning (x2): AAR filter 'a*' matches none of the AAR files",
+                "Built 1/3 packages in 2.00s, 2 failed, 2 warning(s) (1 distinct)",
+            ]
+        );
+    }
+}
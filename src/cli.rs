@@ -1,14 +1,65 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
-use tracing::{error, info, warn};
-
-use crate::aapt2::Aapt2;
-use crate::builder::SkinBuilder;
+use tracing::{Instrument, error, info, warn};
+
+use crate::aapt2::{Aapt2, DEFAULT_PACKAGE_ID, LinkInputMode};
+use crate::annotations::{
+    self, AnnotationFormat, GITLAB_REPORT_FILE, build_annotations, lint_annotations,
+};
+use crate::audit::{self, AuditFilter, audit_log_path};
+use crate::bench::{
+    BenchOptions, CompileStrategy, DEFAULT_BATCH_SIZE, default_workers, format_timing, run_bench,
+    strategy_name,
+};
+use crate::builder::{
+    ConcurrencyLimits, SkinBuilder, create_minimal_manifest, run_blocking, validate_resource_types,
+};
 use crate::cache::CommonDependencyCache;
-use crate::dependency::{extract_common_dependencies, group_configs_by_dependencies};
-use crate::types::BuildConfig;
+use crate::ci;
+use crate::config_lint::{ConfigFinding, lint_config};
+use crate::config_migrate::{migrate_config, unified_diff};
+use crate::config_resolve::{FieldResolver, config_layers};
+use crate::contrast::{ContrastReport, check_contrast};
+use crate::convert::{ResourceFormat, convert_package, converted_path, run_post_convert_hooks};
+use crate::coverage::{
+    DEFAULT_SKINNABLE_TYPES, check_coverage, find_layout_files, read_apk_layouts,
+    read_layout_files, skin_symbols,
+};
+use crate::daemon::{DEFAULT_SOCKET, build_on_daemon, run_daemon, stop_daemon};
+use crate::dependency::{
+    ConfigWithIndex, config_dependencies, extract_common_dependencies,
+    group_configs_by_dependencies, order_by_build_time,
+};
+use crate::diagnostics::render_errors;
+use crate::disk_space;
+use crate::doctor::{Check, CheckStatus, run_checks};
+use crate::events;
+use crate::explain::explain_resource;
+use crate::fingerprint::is_up_to_date;
+use crate::freeze::{ids_from_dump, write_ids};
+use crate::import::import_package;
+use crate::merge::{SkinMerger, write_bundle};
+use crate::package_ids::{
+    PACKAGE_ID_LOCK_FILE, PackageIdLock, assign_package_ids, auto_assign_enabled,
+};
+use crate::package_logs;
+use crate::packaging::PackageOptions;
+use crate::remote::{build_on_workers, is_remote_buildable, run_worker};
+use crate::remote_sources::resolve_remote_sources;
+use crate::schedule;
+use crate::snapshot::Snapshot;
+use crate::stats::{self, BuildRecord, read_records, summarize};
+use crate::strings::{StringsFormat, TranslationFile, collect_translations, import_translations};
+use crate::tokens::extract_tokens;
+use crate::types::{
+    Aapt2Limits, AarFile, BuildConfig, BuildResult, CONFIG_VERSION, UserDefaults,
+    resolve_output_collisions, select_targets, validate_configs,
+};
 
 #[derive(Parser)]
 #[command(name = "asb")]
@@ -23,855 +74,3474 @@ pub struct Cli {
     #[arg(short = 'L', long, global = true)]
     pub log_file: Option<PathBuf>,
 
+    /// Disable colored output (also enabled by the NO_COLOR variable)
+    #[arg(long, global = true, env = "ASB_NO_COLOR")]
+    pub no_color: bool,
+
+    /// Plain output for containers and CI: no progress bars or spinners
+    #[arg(long, global = true, env = "ASB_NON_INTERACTIVE")]
+    pub non_interactive: bool,
+
+    /// CI mode: no colors or spinners, only errors logged, and builds end with a compact
+    /// summary and GitHub Actions annotations; repeated warnings are collapsed with counts
+    #[arg(long, global = true, env = "ASB_CI")]
+    pub ci: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Output format of build results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Build a skin package from resources
-    Build {
+    Build(BuildArgs),
+
+    /// Clean build artifacts
+    Clean {
         /// Path to configuration file
         #[arg(short, long)]
         config: Option<PathBuf>,
 
-        /// Path to resources directory
+        /// Output directory
         #[arg(short, long)]
-        resource_dir: Option<PathBuf>,
+        output: Option<PathBuf>,
+    },
+
+    /// Show aapt2 version
+    Version {
+        /// Path to aapt2 binary
+        #[arg(long)]
+        aapt2: Option<PathBuf>,
+    },
+
+    /// Summarize the local build statistics recorded with `--stats-file`
+    Stats {
+        /// Stats file (default: `statsFile` of the user defaults)
+        #[arg(short, long, env = "ASB_STATS_FILE")]
+        file: Option<PathBuf>,
+
+        /// Only summarize the most recent N builds
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// Output the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
-        /// Path to AndroidManifest.xml
+    /// Time aapt2 compile and link strategies on a project and recommend settings
+    Bench {
+        /// Path to configuration file
         #[arg(short, long)]
-        manifest: Option<PathBuf>,
+        config: Option<PathBuf>,
 
-        /// Output directory
+        /// Package to benchmark (default: the first config)
         #[arg(short, long)]
-        output: Option<PathBuf>,
+        package: Option<String>,
+
+        /// Compile strategies to compare (comma-separated)
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [
+            CompileStrategy::PerFile, CompileStrategy::Batched, CompileStrategy::Dir
+        ])]
+        strategies: Vec<CompileStrategy>,
+
+        /// Worker thread counts to compile with (comma-separated, default: CPU cores and twice that)
+        #[arg(long, value_delimiter = ',')]
+        workers: Vec<usize>,
+
+        /// Link input modes to compare (comma-separated)
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [
+            LinkInputMode::Direct, LinkInputMode::Argfile, LinkInputMode::Zip
+        ])]
+        link_modes: Vec<LinkInputMode>,
+
+        /// Files per aapt2 invocation for the batched strategy
+        #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+        batch_size: usize,
+
+        /// Runs per combination; the median is reported
+        #[arg(long, default_value_t = 1)]
+        iterations: usize,
 
-        /// Build directory for intermediate files and cache
-        /// If not specified, defaults to {output}/.build
+        /// Output the report as JSON
         #[arg(long)]
-        build_dir: Option<PathBuf>,
+        json: bool,
+    },
 
-        /// Package name for the skin
+    /// Show where a resource is defined, which definition wins and whether it was packaged
+    Explain {
+        /// Resource to explain, e.g. drawable/ic_launcher or string/app_name
+        resource: String,
+
+        /// Path to configuration file
         #[arg(short, long)]
-        package: Option<String>,
+        config: Option<PathBuf>,
 
-        /// Path to android.jar
+        /// Package to explain the resource for (default: every config)
         #[arg(short, long)]
-        android_jar: Option<PathBuf>,
+        package: Option<String>,
 
-        /// Paths to AAR files to include
+        /// Output the explanation as JSON
         #[arg(long)]
-        aar: Vec<PathBuf>,
+        json: bool,
+    },
 
-        /// Path to aapt2 binary
+    /// Report which skinnable attributes of host layouts the skin overrides, per screen
+    Coverage {
+        /// Host APK to read the compiled layouts from
         #[arg(long)]
-        aapt2: Option<PathBuf>,
+        host: Option<PathBuf>,
 
-        /// Enable incremental build
-        #[arg(long)]
-        incremental: bool,
+        /// Layouts to check (comma-separated globs, e.g. app/src/main/res/layout*/*.xml)
+        /// With --host they match entries of the APK (default: res/layout*/*.xml)
+        #[arg(long, value_delimiter = ',')]
+        layouts: Vec<String>,
 
-        /// Version code
-        #[arg(long)]
-        version_code: Option<u32>,
+        /// Resource types that count as skinnable (comma-separated)
+        #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_SKINNABLE_TYPES.map(String::from))]
+        types: Vec<String>,
 
-        /// Version name
-        #[arg(long)]
-        version_name: Option<String>,
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Package to report on (default: every config)
+        #[arg(short, long)]
+        package: Option<String>,
 
-        /// Path to stable IDs file
+        /// Path to aapt2 binary, used to read the layouts of --host
         #[arg(long)]
-        stable_ids: Option<PathBuf>,
+        aapt2: Option<PathBuf>,
+
+        /// Fail when the coverage of a package is below this percentage
+        #[arg(long, value_name = "PERCENT")]
+        min_coverage: Option<f64>,
 
-        /// Maximum number of parallel builds for multiple configurations
-        /// Controls how many configs can be built simultaneously (default: CPU core count)
+        /// Output the report as JSON
         #[arg(long)]
-        max_parallel_builds: Option<usize>,
+        json: bool,
+    },
+
+    /// Check the build environment and suggest fixes
+    Doctor {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-        /// Package ID for resources (e.g., "0x7f")
-        /// Critical for dynamic resource loading via new Resources()
+        /// Path to aapt2 binary
         #[arg(long)]
-        package_id: Option<String>,
+        aapt2: Option<PathBuf>,
+    },
+
+    /// Check skins for low color contrast and config entries the build does not use
+    Lint {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-        /// Filter packages to build (comma-separated package names)
-        /// Only build configurations matching these package names
+        /// Only lint these packages (comma-separated package names)
         #[arg(long, value_delimiter = ',')]
         packages: Vec<String>,
 
-        /// Output build result as JSON to stdout
+        /// Output the findings as JSON
         #[arg(long)]
         json: bool,
 
-        /// Path to assets directory (raw files packaged directly into APK)
-        #[arg(long)]
-        assets_dir: Option<PathBuf>,
+        /// Report the findings to the CI system
+        #[arg(long, value_enum, default_value_t = AnnotationFormat::None)]
+        annotations: AnnotationFormat,
+
+        /// File of the GitLab Code Quality report written with --annotations gitlab
+        #[arg(long, default_value = GITLAB_REPORT_FILE)]
+        annotations_file: PathBuf,
     },
 
-    /// Clean build artifacts
-    Clean {
+    /// Initialize a new skin project with sample configuration
+    Init {
+        /// Project directory
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Report color and theme design tokens as JSON
+    Tokens {
         /// Path to configuration file
         #[arg(short, long)]
         config: Option<PathBuf>,
 
-        /// Output directory
+        /// Only report these packages (comma-separated package names)
+        #[arg(long, value_delimiter = ',')]
+        packages: Vec<String>,
+
+        /// Write one <package>.tokens.json per skin into this directory instead of stdout
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
 
-    /// Show aapt2 version
-    Version {
+    /// Export or import string translations (XLIFF/CSV)
+    Strings {
+        #[command(subcommand)]
+        command: StringsCommand,
+    },
+
+    /// Inspect merged skin bundles written by `asb build --bundle`
+    Merge {
+        #[command(subcommand)]
+        command: MergeCommand,
+    },
+
+    /// Record the resource names and IDs of built packages into their freezeFile
+    Freeze {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Only freeze these packages (comma-separated package names)
+        #[arg(long, value_delimiter = ',')]
+        packages: Vec<String>,
+
         /// Path to aapt2 binary
         #[arg(long)]
         aapt2: Option<PathBuf>,
     },
 
-    /// Initialize a new skin project with sample configuration
-    Init {
-        /// Project directory
-        #[arg(short, long, default_value = ".")]
-        dir: PathBuf,
-    },
-}
+    /// Convert existing packages between the binary and proto resource formats
+    Convert {
+        /// Packages to convert
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
 
-impl Cli {
-    pub async fn run(self) -> Result<()> {
-        match self.command {
-            Commands::Build {
-                config,
-                resource_dir,
-                manifest,
-                output,
-                build_dir,
-                package,
-                android_jar,
-                aar,
-                aapt2,
-                incremental,
-                version_code,
-                version_name,
-                stable_ids,
-                max_parallel_builds,
-                package_id,
-                packages,
-                json,
-                assets_dir,
-            } => {
-                Self::run_build(
-                    config,
-                    resource_dir,
-                    manifest,
-                    output,
-                    build_dir,
-                    package,
-                    android_jar,
-                    aar,
-                    aapt2,
-                    incremental,
-                    version_code,
-                    version_name,
-                    stable_ids,
-                    max_parallel_builds,
-                    package_id,
-                    packages,
-                    json,
-                    assets_dir,
-                )
-                .await
-            }
-            Commands::Clean { config, output } => Self::run_clean(config, output),
-            Commands::Version { aapt2 } => Self::run_version(aapt2),
-            Commands::Init { dir } => Self::run_init(dir),
-        }
-    }
+        /// Resource format to convert to
+        #[arg(long, value_enum)]
+        to: ResourceFormat,
 
-    async fn run_build(
-        config_file: Option<PathBuf>,
-        resource_dir: Option<PathBuf>,
-        manifest: Option<PathBuf>,
+        /// Output file, or directory for several inputs (default: next to each input, e.g.
+        /// skin.skin becomes skin.proto.skin)
+        #[arg(short, long)]
         output: Option<PathBuf>,
-        build_dir: Option<PathBuf>,
+
+        /// Configuration whose packaging options and postBuild hooks (e.g. signing) apply
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Package whose config applies (default: the only config)
+        #[arg(short, long)]
         package: Option<String>,
-        android_jar: Option<PathBuf>,
-        aar: Vec<PathBuf>,
+
+        /// Do not run the postBuild hooks of the config
+        #[arg(long)]
+        no_hooks: bool,
+
+        /// Path to aapt2 binary
+        #[arg(long)]
         aapt2: Option<PathBuf>,
-        incremental: bool,
-        version_code: Option<u32>,
-        version_name: Option<String>,
-        stable_ids: Option<PathBuf>,
-        max_parallel_builds: Option<usize>,
-        package_id: Option<String>,
-        packages: Vec<String>,
-        json: bool,
-        assets_dir: Option<PathBuf>,
-    ) -> Result<()> {
-        // Initialize rayon thread pool with CPU cores * 2
-        // This is for resource compilation within each build
-        let worker_threads = num_cpus::get() * 2;
-        if rayon::ThreadPoolBuilder::new()
-            .num_threads(worker_threads)
-            .build_global()
-            .is_ok()
-        {
-            info!(
-                "Initialized resource compilation thread pool with {} workers (CPU cores * 2)",
-                worker_threads
-            );
-        }
+    },
 
-        // Check if CLI arguments are provided
-        let has_cli_args = resource_dir.is_some()
-            || manifest.is_some()
-            || output.is_some()
-            || build_dir.is_some()
-            || package.is_some()
-            || android_jar.is_some()
-            || !aar.is_empty()
-            || aapt2.is_some()
-            || incremental
-            || version_code.is_some()
-            || version_name.is_some()
-            || stable_ids.is_some()
-            || max_parallel_builds.is_some()
-            || package_id.is_some()
-            || assets_dir.is_some();
+    /// Decode an existing APK or skin package back into a res/ source tree and asb config
+    Import {
+        /// Package to import
+        input: PathBuf,
 
-        // Check if using defaults before moving config_file
-        let using_defaults = config_file.is_none() && !PathBuf::from("./asb.config.json").exists();
+        /// Directory to write the source tree to
+        #[arg(short, long)]
+        output: PathBuf,
 
-        // Load configs: support both single and array mode
-        let loaded = BuildConfig::load_configs(config_file, None)?;
+        /// android.jar naming framework references (default: highest platform in ANDROID_HOME)
+        #[arg(long)]
+        android_jar: Option<PathBuf>,
 
-        // Save all package names before moving configs (for error messages)
-        let all_package_names: Vec<String> = loaded
-            .configs
-            .iter()
-            .map(|c| c.package_name.clone())
-            .collect();
+        /// Import into a non-empty output directory
+        #[arg(long)]
+        force: bool,
+    },
 
-        let mut build_configs = loaded.configs;
-        let config_max_parallel = loaded.max_parallel_builds;
+    /// Record built packages as snapshots or verify builds against recorded snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
 
-        // Filter configs by package names if specified
-        if !packages.is_empty() {
-            let original_count = build_configs.len();
-            build_configs.retain(|config| packages.contains(&config.package_name));
-            let filtered_count = build_configs.len();
+    /// Query the build audit logs written when auditLog is enabled
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
 
-            if filtered_count == 0 {
-                anyhow::bail!(
-                    "No configurations found matching specified packages: {}. Available packages: {}",
-                    packages.join(", "),
-                    all_package_names.join(", ")
-                );
-            }
+    /// Inspect how configs resolve
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 
-            info!(
-                "Filtered {} out of {} configurations by package names: {}",
-                filtered_count,
-                original_count,
-                packages.join(", ")
-            );
-        }
+    /// Show every resolved build configuration after multi-app and flavor expansion
+    List {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-        info!(
-            "Config maximum parallel builds setting: {:?}",
-            config_max_parallel
-        );
-        info!(
-            "CLI maximum parallel builds setting: {:?}",
-            max_parallel_builds
-        );
+        /// Output configurations as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
-        // Get max parallel builds setting (CLI > config > default: CPU cores)
-        let max_parallel = max_parallel_builds
-            .or(config_max_parallel)
-            .unwrap_or_else(num_cpus::get);
+    /// List the build targets of a config
+    Targets {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-        info!("Maximum parallel builds: {} configs", max_parallel);
+        /// Output targets as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
-        // Show info message if using defaults
-        if using_defaults {
-            info!("Using default configuration based on standard Android project structure");
-            info!("Create asb.config.json in current directory to customize settings");
-        }
+    /// Keep builders warm in the background and run builds sent by `asb build --use-daemon`
+    Daemon {
+        /// Unix socket to listen on
+        #[arg(long, env = "ASB_DAEMON_SOCKET", default_value = DEFAULT_SOCKET)]
+        socket: PathBuf,
 
-        // If CLI arguments are provided and we have multiple configs, warn the user
-        if has_cli_args && build_configs.len() > 1 {
-            warn!(
-                "CLI arguments provided with array configuration. CLI arguments will override ALL configurations."
-            );
-        }
+        /// Maximum number of configs built at once (default: CPU core count)
+        #[arg(long)]
+        max_parallel_builds: Option<usize>,
 
-        // Override configs with CLI arguments (CLI args have highest priority)
-        if has_cli_args {
-            for build_config in &mut build_configs {
-                if let Some(ref rd) = resource_dir {
-                    build_config.resource_dir = rd.clone();
-                }
-                if let Some(ref m) = manifest {
-                    build_config.manifest_path = m.clone();
-                }
-                if let Some(ref o) = output {
-                    build_config.output_dir = o.clone();
-                }
-                if let Some(ref bd) = build_dir {
-                    build_config.build_dir = Some(bd.clone());
-                }
-                if let Some(ref p) = package {
-                    build_config.package_name = p.clone();
-                }
-                if let Some(ref aj) = android_jar {
-                    build_config.android_jar = Some(aj.clone());
-                }
-                if !aar.is_empty() {
-                    build_config.aar_files = Some(aar.clone());
-                }
-                if let Some(ref a) = aapt2 {
-                    build_config.aapt2_path = Some(a.clone());
-                }
-                if incremental {
-                    build_config.incremental = Some(true);
-                }
-                if let Some(vc) = version_code {
-                    build_config.version_code = Some(vc);
-                }
-                if let Some(ref vn) = version_name {
-                    build_config.version_name = Some(vn.clone());
-                }
-                if let Some(ref si) = stable_ids {
-                    build_config.stable_ids_file = Some(si.clone());
-                }
-                if let Some(ref pid) = package_id {
-                    build_config.package_id = Some(pid.clone());
-                }
-                if let Some(ref ad) = assets_dir {
-                    build_config.assets_dir = Some(ad.clone());
-                }
-            }
-        }
+        /// Maximum number of aapt2 links running at once (default: max parallel builds)
+        #[arg(long)]
+        max_parallel_links: Option<usize>,
 
-        // Resolve aapt2 path once for all configs if not explicitly provided
-        // This avoids repeated searches when building multiple packages
-        let resolved_aapt2_path = if build_configs.iter().all(|c| c.aapt2_path.is_none()) {
-            // Only search once if no config has an explicit aapt2 path
-            Some(Aapt2::new(None)?.into_path())
-        } else {
-            None
-        };
+        /// Watch resource directories and rehash only changed files between builds
+        #[arg(long)]
+        watch: bool,
 
-        // For array mode with multiple configs, ensure each config has a unique compiled directory
-        // to avoid conflicts when building multiple packages from the same build directory
-        if build_configs.len() > 1 {
-            for build_config in build_configs.iter_mut() {
-                // Only set if not explicitly configured
-                // Use build_dir for intermediate files, not output_dir
-                if build_config.compiled_dir.is_none() {
-                    let build_dir = build_config
-                        .build_dir
-                        .clone()
-                        .unwrap_or_else(|| build_config.output_dir.join(".build"));
-                    let unique_compiled_dir = build_dir.join(&build_config.package_name);
-                    build_config.compiled_dir = Some(unique_compiled_dir);
-                }
+        /// Stop the daemon listening on the socket instead of starting one
+        #[arg(long)]
+        stop: bool,
+    },
 
-                // Apply resolved aapt2 path if config doesn't have one
-                if build_config.aapt2_path.is_none() {
-                    build_config.aapt2_path = resolved_aapt2_path.clone();
-                }
-            }
-        } else if let Some(ref path) = resolved_aapt2_path {
-            // Single config mode - apply resolved path if needed
-            if build_configs[0].aapt2_path.is_none() {
-                build_configs[0].aapt2_path = Some(path.clone());
-            }
-        }
+    /// Run builds sent by `asb build --workers` on this machine
+    Worker {
+        /// Address to listen on; use e.g. 0.0.0.0:7878 to accept builds from other machines
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
 
-        if build_configs.len() == 1 {
-            // Single configuration mode - keep backward compatibility
-            let config = build_configs.into_iter().next().unwrap();
-            let package_name = config.package_name.clone();
-            println!("{}", "\nBuilding skin package...\n".blue().bold());
-            let start_time = std::time::Instant::now();
-            let mut builder = SkinBuilder::new(config)?;
-            let result = builder.build().await?;
-            let elapsed = start_time.elapsed();
+        /// Secret coordinators must know to send builds (`--worker-token` of `asb build`)
+        #[arg(long, env = "ASB_WORKER_TOKEN", hide_env_values = true)]
+        token: String,
 
-            if json {
-                println!("{}", serde_json::to_string_pretty(&result)?);
-                if !result.success {
-                    std::process::exit(1);
-                }
-                return Ok(());
-            }
+        /// Directory for uploaded inputs and per-package build caches
+        #[arg(long, default_value = ".asb-worker")]
+        work_dir: PathBuf,
 
-            if result.success {
-                println!("{}", "\n✓ Skin package built successfully!".green().bold());
-                if let Some(apk_path) = result.apk_path {
-                    println!("  {}: {}", "Output".cyan(), apk_path.display());
-                }
-                println!("  {}: {:.2}s", "Total time".cyan(), elapsed.as_secs_f64());
-                println!(
-                    "  {}: {:.2}s",
-                    "Build time".cyan(),
-                    result.build_duration.as_secs_f64()
-                );
-            } else {
-                println!(
-                    "{}",
-                    format!("\n✗ Build failed for package '{}'", package_name)
-                        .red()
-                        .bold()
-                );
-                for error in &result.errors {
-                    println!("  - {}", error);
-                }
+        /// Number of builds to run at once (default: CPU core count)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+}
 
-                // Save failure log
-                match Self::save_failure_log(&package_name, &result.errors, None) {
-                    Ok(log_path) => {
-                        println!("\n  {}: {}", "Log saved to".yellow(), log_path.display());
-                    }
-                    Err(e) => {
-                        warn!("Failed to save error log: {}", e);
-                    }
-                }
+/// `strings` subcommands
+#[derive(Subcommand)]
+pub enum StringsCommand {
+    /// Export string and plural resources to one translation file per locale
+    Export {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-                std::process::exit(1);
-            }
-        } else {
-            // Multiple configurations mode
-            // Keep a copy of original configs for displaying package names later
-            let original_configs = build_configs.clone();
+        /// Directory the translation files are written to
+        #[arg(short, long, default_value = "translations")]
+        output: PathBuf,
 
-            println!(
-                "{}",
-                format!("\nBuilding {} skin packages...\n", build_configs.len())
-                    .blue()
-                    .bold()
-            );
+        /// Translation file format
+        #[arg(long, value_enum, default_value_t = StringsFormat::Xliff)]
+        format: StringsFormat,
 
-            let start_time = std::time::Instant::now();
+        /// Locale qualifiers to export (e.g. "zh-rCN,fr")
+        /// Defaults to every locale that already has a values-<locale> directory
+        #[arg(long, value_delimiter = ',')]
+        locales: Vec<String>,
 
-            // Extract common dependencies
-            let common_deps = extract_common_dependencies(&build_configs);
+        /// Language of the strings in the default values directory
+        #[arg(long, default_value = "en")]
+        source_language: String,
+    },
 
-            if !common_deps.is_empty() {
-                info!(
-                    "Found {} common dependencies to compile first",
-                    common_deps.len()
-                );
+    /// Write translated files back into values-<locale> directories
+    /// Only resource directories of the configuration are written to
+    Import {
+        /// Path to configuration file
+        #[arg(short, long)]
+        config: Option<PathBuf>,
 
-                // Determine cache directory for common dependencies
-                // Use the first config's build_dir as the base, since all configs should use compatible settings
-                // for shared common dependency compilation
-                let base_build_dir = build_configs[0]
-                    .build_dir
-                    .clone()
-                    .unwrap_or_else(|| build_configs[0].output_dir.join(".build"));
-                let base_cache_dir = build_configs[0]
-                    .cache_dir
-                    .clone()
-                    .unwrap_or_else(|| base_build_dir.clone());
-                let common_cache_dir = base_cache_dir.join("common-deps");
+        /// Translation files (.xlf, .xliff or .csv)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
 
-                // Initialize common dependency cache
-                let mut common_dep_cache = CommonDependencyCache::new(common_cache_dir.clone())?;
-                common_dep_cache.init()?;
+/// `merge` subcommands
+#[derive(Subcommand)]
+pub enum MergeCommand {
+    /// Check the module hashes and checksum of a bundle; exits with 1 on mismatch
+    Verify {
+        /// Merged bundle file
+        bundle: PathBuf,
+    },
 
-                // Use aapt2 path from first config (all configs should use the same aapt2)
-                let aapt2 = Aapt2::new(build_configs[0].aapt2_path.clone())?;
+    /// List the modules of a bundle with their size, offset and hash, without extracting
+    List {
+        /// Merged bundle file
+        bundle: PathBuf,
 
-                // Map to store compiled flat files for each common dependency
-                let mut precompiled_map: std::collections::HashMap<PathBuf, Vec<PathBuf>> =
-                    std::collections::HashMap::new();
+        /// Output the bundle index as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
-                // Compile common dependencies
-                for common_dep in &common_deps {
-                    info!(
-                        "Compiling common dependency: {} (used by {} apps)",
-                        common_dep.resource_dir.display(),
-                        common_dep.dependent_configs.len()
-                    );
+    /// Write the modules of a bundle to <module>.skin files, verifying their checksums
+    Extract {
+        /// Merged bundle file
+        bundle: PathBuf,
 
-                    // Check if we need to recompile
-                    let needs_recompile =
-                        common_dep_cache.needs_recompile(&common_dep.resource_dir)?;
+        /// Directory the modules are written to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
 
-                    if needs_recompile {
-                        // Create directory name based on resource path
-                        // e.g., "common/res" -> "common_res"
-                        let dir_name = common_dep
-                            .resource_dir
-                            .to_string_lossy()
-                            .replace(['/', '\\', ':'], "_")
-                            .trim_matches('_')
-                            .to_string();
+        /// Only extract this module, without reading the rest of the bundle
+        #[arg(short, long)]
+        module: Option<String>,
+    },
+}
 
-                        // Compile each common dependency to its own directory
-                        let compiled_dir = common_cache_dir.join(&dir_name);
-                        std::fs::create_dir_all(&compiled_dir)?;
+/// `snapshot` subcommands
+#[derive(Subcommand)]
+pub enum SnapshotCommand {
+    /// Write a snapshot of every built package into the snapshot directory
+    Record(SnapshotArgs),
 
-                        let compile_result =
-                            aapt2.compile_dir(&common_dep.resource_dir, &compiled_dir)?;
+    /// Compare built packages with their snapshots; exits with 1 on differences
+    Verify(SnapshotArgs),
+}
 
-                        if compile_result.success {
-                            info!(
-                                "  ✓ Compiled {} resources into {} flat files in {}",
-                                common_dep.resource_dir.display(),
-                                compile_result.flat_files.len(),
-                                compiled_dir.display()
-                            );
+/// Arguments shared by the `snapshot` subcommands
+#[derive(Args)]
+pub struct SnapshotArgs {
+    /// Path to configuration file
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
 
-                            // Store precompiled flat files
-                            precompiled_map.insert(
-                                common_dep.resource_dir.clone(),
-                                compile_result.flat_files.clone(),
-                            );
+    /// Only snapshot these packages (comma-separated package names)
+    #[arg(long, value_delimiter = ',')]
+    pub packages: Vec<String>,
 
-                            // Update cache
-                            common_dep_cache.update_entry(
-                                &common_dep.resource_dir,
-                                compile_result.flat_files,
-                            )?;
-                        } else {
-                            error!(
-                                "  ✗ Failed to compile common dependency: {}",
-                                common_dep.resource_dir.display()
-                            );
-                            for err in &compile_result.errors {
-                                error!("    {}", err);
-                            }
-                        }
-                    } else {
-                        info!(
-                            "  ✓ Using cached compiled resources for {}",
-                            common_dep.resource_dir.display()
-                        );
+    /// Directory of the <package>[-<flavor>].snapshot.json files
+    #[arg(short, long, default_value = "snapshots")]
+    pub dir: PathBuf,
 
-                        // Get cached flat files
-                        if let Some(flat_files) =
-                            common_dep_cache.get_cached_flat_files(&common_dep.resource_dir)
-                        {
-                            precompiled_map.insert(common_dep.resource_dir.clone(), flat_files);
-                        }
-                    }
-                }
+    /// Path to aapt2 binary
+    #[arg(long)]
+    pub aapt2: Option<PathBuf>,
+}
 
-                // Save common dependency cache
-                common_dep_cache.save()?;
+/// `audit` subcommands
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// List recorded builds, oldest first
+    Show(AuditShowArgs),
+}
 
-                // Update all build configs with precompiled dependencies
-                if !precompiled_map.is_empty() {
-                    for config in &mut build_configs {
-                        config.precompiled_dependencies = Some(precompiled_map.clone());
-                    }
-                }
-            }
+/// `config` subcommands
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print every effective field of the resolved configs with where its value came from:
+    /// cli, user (user defaults), flavor, app, common, config (single or array config) or default
+    Resolve(ResolveArgs),
 
-            // Group configs by dependencies
-            let (independent_configs, dependent_groups) =
-                group_configs_by_dependencies(build_configs)?;
+    /// Upgrade config files to the latest configVersion in place, showing the changes as a diff
+    Migrate {
+        /// Config files to migrate (default: asb.config.json)
+        files: Vec<PathBuf>,
 
-            info!(
-                "Found {} independent configs and {} dependency groups",
-                independent_configs.len(),
-                dependent_groups.len()
-            );
+        /// Only show the diffs; exits with 1 when a file needs migrating
+        #[arg(long)]
+        check: bool,
+    },
+}
 
-            let mut all_results = Vec::new();
-            let mut success_count = 0;
-            let mut fail_count = 0;
+/// Arguments for `config resolve`
+#[derive(Args)]
+pub struct ResolveArgs {
+    /// Path to configuration file
+    #[arg(short, long, env = "ASB_CONFIG")]
+    pub config: Option<PathBuf>,
 
-            // Build independent configs in parallel
-            if !independent_configs.is_empty() {
-                info!(
-                    "Building {} independent configs in parallel (max {} concurrent)...",
-                    independent_configs.len(),
-                    max_parallel
-                );
+    /// Only show the config of this package
+    #[arg(short, long)]
+    pub package: Option<String>,
 
-                // Use semaphore to limit concurrent builds
-                let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(max_parallel));
-                let mut tasks: tokio::task::JoinSet<
-                    Result<(usize, String, crate::types::BuildResult), (String, anyhow::Error)>,
-                > = tokio::task::JoinSet::new();
+    /// Resolve as of this date (YYYY-MM-DD), as `asb build --as-of`
+    #[arg(long, value_name = "DATE", env = "ASB_AS_OF")]
+    pub as_of: Option<String>,
 
-                for config_with_idx in independent_configs {
-                    let idx = config_with_idx.index;
-                    let config = config_with_idx.config.clone();
-                    let package_name = config.package_name.clone();
-                    let sem = semaphore.clone();
+    /// Resolve in strict mode, as `asb build --strict`
+    #[arg(long, env = "ASB_STRICT")]
+    pub strict: bool,
 
-                    tasks.spawn(async move {
-                        // Acquire semaphore permit
-                        let _permit = sem.acquire().await.unwrap();
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
 
-                        match SkinBuilder::new(config) {
-                            Ok(mut builder) => match builder.build().await {
-                                Ok(result) => Ok((idx, package_name, result)),
-                                Err(e) => Err((package_name.clone(), e)),
-                            },
-                            Err(e) => Err((package_name.clone(), e)),
-                        }
-                    });
-                }
+    /// Output the fields as JSON
+    #[arg(long)]
+    pub json: bool,
+}
 
-                while let Some(result) = tasks.join_next().await {
-                    match result {
-                        Ok(Ok((idx, _package_name, build_result))) => {
-                            all_results.push((idx, build_result));
-                        }
-                        Ok(Err((package_name, e))) => {
-                            error!("Build error for package '{}': {}", package_name, e);
-                            // Print full error chain for debugging
-                            let mut source = e.source();
-                            let mut depth = 1;
-                            while let Some(err) = source {
-                                error!("  Caused by ({}): {}", depth, err);
-                                source = err.source();
-                                depth += 1;
-                            }
-                            fail_count += 1;
-                        }
-                        Err(e) => {
-                            error!("Task join error: {}", e);
+/// Arguments for `audit show`
+#[derive(Args)]
+pub struct AuditShowArgs {
+    /// Path to configuration file; the audit logs of its output directories are read
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Read this audit log instead of the config's
+    #[arg(long)]
+    pub log: Option<PathBuf>,
+
+    /// Only builds of this package
+    #[arg(short, long)]
+    pub package: Option<String>,
+
+    /// Only builds on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only builds that produced this file, found by its SHA-256
+    #[arg(long, conflicts_with = "hash")]
+    pub file: Option<PathBuf>,
+
+    /// Only builds that wrote a file with this SHA-256 (or prefix of it)
+    #[arg(long)]
+    pub hash: Option<String>,
+
+    /// Only failed builds
+    #[arg(long)]
+    pub failed: bool,
+
+    /// Only the last N matching builds
+    #[arg(long)]
+    pub last: Option<usize>,
+
+    /// Print the records as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Arguments for the `build` subcommand
+#[derive(Args)]
+pub struct BuildArgs {
+    /// Build targets to select, e.g. :brandA:night, :brandA:* or :*:night
+    /// Targets are listed by `asb targets`; without targets every config is built
+    pub targets: Vec<String>,
+
+    /// Path to configuration file
+    #[arg(short, long, env = "ASB_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Package name for the skin
+    #[arg(short, long, env = "ASB_PACKAGE")]
+    pub package: Option<String>,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+
+    /// Maximum number of parallel builds for multiple configurations
+    /// Controls how many configs can be built simultaneously (default: CPU core count)
+    #[arg(long, env = "ASB_MAX_PARALLEL_BUILDS")]
+    pub max_parallel_builds: Option<usize>,
+
+    /// Maximum number of aapt2 links running at once (default: max parallel builds)
+    /// Lower it to throttle memory-heavy links without slowing down compilation
+    #[arg(long, env = "ASB_MAX_PARALLEL_LINKS")]
+    pub max_parallel_links: Option<usize>,
+
+    /// Filter packages to build (comma-separated package names)
+    /// Only build configurations matching these package names
+    #[arg(long, value_delimiter = ',', env = "ASB_PACKAGES")]
+    pub packages: Vec<String>,
+
+    /// Output build result as JSON to stdout
+    #[arg(long)]
+    pub json: bool,
+
+    /// Output format of the build result; `json` is the same as --json
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Report build errors and warnings to the CI system (default: github with --ci, else none)
+    #[arg(long, value_enum, env = "ASB_ANNOTATIONS")]
+    pub annotations: Option<AnnotationFormat>,
+
+    /// File of the GitLab Code Quality report written with --annotations gitlab
+    #[arg(long, default_value = GITLAB_REPORT_FILE)]
+    pub annotations_file: PathBuf,
+
+    /// Stream NDJSON build events to "stdout" or to a Unix socket / named pipe path
+    /// With "stdout", logs are written to stderr so the stream stays parseable
+    #[arg(long, value_name = "TARGET")]
+    pub event_stream: Option<String>,
+
+    /// Append a record of this build to a local NDJSON stats file, summarized by `asb stats`
+    /// Nothing is sent anywhere; without this or `statsFile` in the user defaults no stats are kept
+    #[arg(long, value_name = "FILE", env = "ASB_STATS_FILE")]
+    pub stats_file: Option<PathBuf>,
+
+    /// Build independent configs on these workers (comma-separated host[:port])
+    /// Workers are started with `asb worker`; configs fall back to local builds on failure
+    #[arg(long, value_delimiter = ',', env = "ASB_WORKERS")]
+    pub workers: Vec<String>,
+
+    /// Secret the workers were started with (`--token` of `asb worker`)
+    #[arg(long, env = "ASB_WORKER_TOKEN", hide_env_values = true)]
+    pub worker_token: Option<String>,
+
+    /// Only check whether packages are up to date with their stored fingerprints
+    /// Exits with 0 when every package is up to date and 1 otherwise, without building
+    #[arg(long)]
+    pub check_fingerprint: bool,
+
+    /// Print the files each package build writes and exit without building
+    /// Lets build systems such as Bazel declare the outputs of an asb action up front
+    #[arg(long)]
+    pub list_outputs: bool,
+
+    /// Only compile these resource types (comma-separated, e.g. values,drawable)
+    /// Other resources are linked from the incremental cache; needs a previous full build
+    #[arg(long, value_delimiter = ',', env = "ASB_ONLY")]
+    pub only: Vec<String>,
+
+    /// Build as of this date (YYYY-MM-DD) for date-windowed resources instead of today
+    /// Overrides `asOf` in the config, so seasonal skins can be rebuilt for any date
+    #[arg(long, value_name = "DATE", env = "ASB_AS_OF")]
+    pub as_of: Option<String>,
+
+    /// Turn on strict mode: missing directories, duplicates, unresolved references,
+    /// changed IDs, size budget overruns and warnings all fail the build
+    #[arg(long, env = "ASB_STRICT")]
+    pub strict: bool,
+
+    /// Rename package files that would collide with another config's output
+    /// Later configs get their flavor or a counter appended instead of failing the build
+    #[arg(long, env = "ASB_AUTO_UNIQUE_OUTPUTS")]
+    pub auto_unique_outputs: bool,
+
+    /// Send the resolved configs to the daemon started with `asb daemon` instead of
+    /// building in this process
+    #[arg(long, env = "ASB_USE_DAEMON")]
+    pub use_daemon: bool,
+
+    /// Socket of the daemon used with `--use-daemon`
+    #[arg(long, env = "ASB_DAEMON_SOCKET", default_value = DEFAULT_SOCKET)]
+    pub daemon_socket: PathBuf,
+
+    /// Merge the built packages into the bundle configured under `bundle`
+    /// Each config becomes one module; a manifest of module versions is written next to it
+    #[arg(long, env = "ASB_BUNDLE")]
+    pub bundle: bool,
+}
+
+/// Config fields set on the command line; they override the field in every config
+#[derive(Args, Default)]
+pub struct ConfigOverrides {
+    /// Path to resources directory
+    #[arg(short, long, env = "ASB_RESOURCE_DIR")]
+    pub resource_dir: Option<PathBuf>,
+
+    /// Path to AndroidManifest.xml
+    #[arg(short, long, env = "ASB_MANIFEST")]
+    pub manifest: Option<PathBuf>,
+
+    /// Output directory
+    #[arg(short, long, env = "ASB_OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Build directory for intermediate files and cache
+    /// If not specified, defaults to {output}/.build
+    #[arg(long, env = "ASB_BUILD_DIR")]
+    pub build_dir: Option<PathBuf>,
+
+    /// Path to android.jar
+    #[arg(short, long, env = "ASB_ANDROID_JAR")]
+    pub android_jar: Option<PathBuf>,
+
+    /// Paths to AAR files to include
+    #[arg(long)]
+    pub aar: Vec<PathBuf>,
+
+    /// Path to aapt2 binary
+    #[arg(long, env = "ASB_AAPT2")]
+    pub aapt2: Option<PathBuf>,
+
+    /// Enable incremental build
+    #[arg(long, env = "ASB_INCREMENTAL")]
+    pub incremental: bool,
+
+    /// Version code
+    #[arg(long, env = "ASB_VERSION_CODE")]
+    pub version_code: Option<u32>,
+
+    /// Version name
+    #[arg(long, env = "ASB_VERSION_NAME")]
+    pub version_name: Option<String>,
+
+    /// Path to stable IDs file
+    #[arg(long, env = "ASB_STABLE_IDS")]
+    pub stable_ids: Option<PathBuf>,
+
+    /// Package ID for resources (e.g., "0x7f")
+    /// Critical for dynamic resource loading via new Resources()
+    #[arg(long, env = "ASB_PACKAGE_ID")]
+    pub package_id: Option<String>,
+
+    /// Path to assets directory (raw files packaged directly into APK)
+    #[arg(long, env = "ASB_ASSETS_DIR")]
+    pub assets_dir: Option<PathBuf>,
+
+    /// Follow symbolic links when discovering resource files
+    #[arg(long, env = "ASB_FOLLOW_SYMLINKS")]
+    pub follow_symlinks: bool,
+
+    /// Kill aapt2 invocations running longer than this many seconds
+    #[arg(long, value_name = "SECS", env = "ASB_AAPT2_TIMEOUT")]
+    pub aapt2_timeout: Option<u64>,
+}
+
+impl ConfigOverrides {
+    /// Whether no field is overridden
+    fn is_empty(&self) -> bool {
+        self.resource_dir.is_none()
+            && self.manifest.is_none()
+            && self.output.is_none()
+            && self.build_dir.is_none()
+            && self.android_jar.is_none()
+            && self.aar.is_empty()
+            && self.aapt2.is_none()
+            && !self.incremental
+            && self.version_code.is_none()
+            && self.version_name.is_none()
+            && self.stable_ids.is_none()
+            && self.package_id.is_none()
+            && self.assets_dir.is_none()
+            && !self.follow_symlinks
+            && self.aapt2_timeout.is_none()
+    }
+
+    /// Override the fields of `config`, returning the names of the overridden config fields
+    pub fn apply(&self, config: &mut BuildConfig) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if let Some(ref rd) = self.resource_dir {
+            config.resource_dir = rd.clone();
+            fields.push("resourceDir");
+        }
+        if let Some(ref m) = self.manifest {
+            config.manifest_path = m.clone();
+            fields.push("manifestPath");
+        }
+        if let Some(ref o) = self.output {
+            config.output_dir = o.clone();
+            fields.push("outputDir");
+        }
+        if let Some(ref bd) = self.build_dir {
+            config.build_dir = Some(bd.clone());
+            fields.push("buildDir");
+        }
+        if let Some(ref aj) = self.android_jar {
+            config.android_jar = Some(aj.clone());
+            fields.push("androidJar");
+        }
+        if !self.aar.is_empty() {
+            config.aar_files = Some(self.aar.iter().cloned().map(AarFile::from).collect());
+            fields.push("aarFiles");
+        }
+        if let Some(ref a) = self.aapt2 {
+            config.aapt2_path = Some(a.clone());
+            fields.push("aapt2Path");
+        }
+        if self.incremental {
+            config.incremental = Some(true);
+            fields.push("incremental");
+        }
+        if let Some(vc) = self.version_code {
+            config.version_code = Some(vc);
+            fields.push("versionCode");
+        }
+        if let Some(ref vn) = self.version_name {
+            config.version_name = Some(vn.clone());
+            fields.push("versionName");
+        }
+        if let Some(ref si) = self.stable_ids {
+            config.stable_ids_file = Some(si.clone());
+            fields.push("stableIdsFile");
+        }
+        if let Some(ref pid) = self.package_id {
+            config.package_id = Some(pid.clone());
+            fields.push("packageId");
+        }
+        if let Some(ref ad) = self.assets_dir {
+            config.assets_dir = Some(ad.clone());
+            fields.push("assetsDir");
+        }
+        if self.follow_symlinks {
+            config.follow_symlinks = Some(true);
+            fields.push("followSymlinks");
+        }
+        if let Some(timeout_secs) = self.aapt2_timeout {
+            config.aapt2_limits = Some(Aapt2Limits {
+                timeout_secs: Some(timeout_secs),
+                ..config.aapt2_limits.unwrap_or_default()
+            });
+            fields.push("aapt2Limits");
+        }
+        fields
+    }
+}
+
+/// Resolved build configuration as shown by `asb list`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfigSummary {
+    target: String,
+    package_name: String,
+    flavor: Option<String>,
+    /// Main resource directory followed by additional ones
+    resource_dirs: Vec<PathBuf>,
+    output: PathBuf,
+    /// Package ID, or "auto" when it is assigned on the first build
+    package_id: String,
+    /// Packages of other configs whose resources this one uses
+    depends_on: Vec<String>,
+    aar_files: Vec<PathBuf>,
+    precompiled_dependencies: Vec<PathBuf>,
+}
+
+/// Findings of `asb lint` for one config
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LintReport {
+    package_name: String,
+    flavor: Option<String>,
+    /// Config entries without effect; reported, but they don't fail the lint
+    config: Vec<ConfigFinding>,
+    contrast: ContrastReport,
+}
+
+/// Package ID lock of a config file: next to it, or in the current directory
+fn package_id_lock_path(config_file: Option<&Path>) -> PathBuf {
+    config_file
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new("."))
+        .join(PACKAGE_ID_LOCK_FILE)
+}
+
+/// Expand `@file` arguments of `asb build` into the options the params file lists
+///
+/// Params files hold one `key=value` per line, where `key` is a long option name such as
+/// `resource-dir`; blank lines and `#` comments are skipped. Flags take `true` or `false`, and
+/// only options that accept several values may repeat, so the line order never matters.
+pub fn expand_param_files(args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let Some(build_idx) = args
+        .iter()
+        .skip(1)
+        .position(|a| a == "build")
+        .map(|i| i + 1)
+    else {
+        return Ok(args);
+    };
+    let mut command = Cli::command();
+    command.build();
+    let build = command
+        .find_subcommand("build")
+        .expect("build subcommand exists");
+
+    let mut expanded = args[..=build_idx].to_vec();
+    for arg in &args[build_idx + 1..] {
+        match arg.to_str().and_then(|a| a.strip_prefix('@')) {
+            Some(path) => expanded.extend(read_param_file(Path::new(path), build)?),
+            None => expanded.push(arg.clone()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Turn the lines of a params file into `--key=value` arguments
+fn read_param_file(path: &Path, build: &clap::Command) -> Result<Vec<OsString>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read params file {}", path.display()))?;
+    let mut seen = std::collections::HashSet::new();
+    let mut args = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let location = || format!("{}:{}", path.display(), idx + 1);
+        let Some((key, value)) = line.split_once('=') else {
+            anyhow::bail!("Expected key=value at {}: {}", location(), line);
+        };
+        let key = key.trim();
+        let value = value.trim();
+        let Some(arg) = build
+            .get_arguments()
+            .find(|a| a.get_long() == Some(key) && a.get_long() != Some("help"))
+        else {
+            anyhow::bail!("Unknown option '{}' at {}", key, location());
+        };
+        let repeatable = matches!(arg.get_action(), clap::ArgAction::Append);
+        if !seen.insert(key.to_string()) && !repeatable {
+            anyhow::bail!("Option '{}' is set more than once at {}", key, location());
+        }
+        if arg.get_action().takes_values() {
+            args.push(format!("--{}={}", key, value).into());
+        } else {
+            match value {
+                "true" => args.push(format!("--{}", key).into()),
+                "false" => {}
+                _ => anyhow::bail!(
+                    "Flag '{}' must be true or false at {}, got '{}'",
+                    key,
+                    location(),
+                    value
+                ),
+            }
+        }
+    }
+    Ok(args)
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        match self.command {
+            Commands::Build(args) => Self::run_build(args, self.ci).await,
+            Commands::Clean { config, output } => Self::run_clean(config, output),
+            Commands::Version { aapt2 } => Self::run_version(aapt2),
+            Commands::Doctor { config, aapt2 } => Self::run_doctor(config, aapt2),
+            Commands::Lint {
+                config,
+                packages,
+                json,
+                annotations,
+                annotations_file,
+            } => Self::run_lint(config, packages, json, annotations, annotations_file),
+            Commands::Stats { file, last, json } => Self::run_stats(file, last, json),
+            Commands::Bench {
+                config,
+                package,
+                strategies,
+                workers,
+                link_modes,
+                batch_size,
+                iterations,
+                json,
+            } => {
+                let options = BenchOptions {
+                    strategies,
+                    workers: if workers.is_empty() {
+                        default_workers()
+                    } else {
+                        workers
+                    },
+                    link_modes,
+                    batch_size,
+                    iterations,
+                };
+                Self::run_bench(config, package, options, json).await
+            }
+            Commands::Explain {
+                resource,
+                config,
+                package,
+                json,
+            } => Self::run_explain(resource, config, package, json),
+            Commands::Coverage {
+                host,
+                layouts,
+                types,
+                config,
+                package,
+                aapt2,
+                min_coverage,
+                json,
+            } => Self::run_coverage(
+                host,
+                layouts,
+                types,
+                config,
+                package,
+                aapt2,
+                min_coverage,
+                json,
+            ),
+            Commands::Init { dir } => Self::run_init(dir),
+            Commands::Tokens {
+                config,
+                packages,
+                output,
+            } => Self::run_tokens(config, packages, output),
+            Commands::Strings { command } => Self::run_strings(command),
+            Commands::Merge { command } => Self::run_merge(command),
+            Commands::Snapshot { command } => Self::run_snapshot(command),
+            Commands::Audit { command } => Self::run_audit(command),
+            Commands::Freeze {
+                config,
+                packages,
+                aapt2,
+            } => Self::run_freeze(config, packages, aapt2),
+            Commands::Convert {
+                inputs,
+                to,
+                output,
+                config,
+                package,
+                no_hooks,
+                aapt2,
+            } => Self::run_convert(inputs, to, output, config, package, no_hooks, aapt2),
+            Commands::Import {
+                input,
+                output,
+                android_jar,
+                force,
+            } => Self::run_import(input, output, android_jar, force),
+            Commands::Config { command } => Self::run_config(command),
+            Commands::List { config, json } => Self::run_list(config, json),
+            Commands::Targets { config, json } => Self::run_targets(config, json),
+            Commands::Worker {
+                listen,
+                work_dir,
+                jobs,
+                token,
+            } => {
+                let jobs = jobs.unwrap_or_else(num_cpus::get);
+                run_worker(&listen, &work_dir, jobs, &token).await
+            }
+            Commands::Daemon {
+                socket,
+                max_parallel_builds,
+                max_parallel_links,
+                watch,
+                stop,
+            } => {
+                if stop {
+                    stop_daemon(&socket).await?;
+                    println!("{}", "✓ Daemon stopped".green());
+                    return Ok(());
+                }
+                let max_builds = max_parallel_builds.unwrap_or_else(num_cpus::get);
+                run_daemon(
+                    &socket,
+                    max_builds,
+                    max_parallel_links.unwrap_or(max_builds),
+                    watch,
+                )
+                .await
+            }
+        }
+    }
+
+    async fn run_build(args: BuildArgs, ci: bool) -> Result<()> {
+        let BuildArgs {
+            targets,
+            config: config_file,
+            package,
+            overrides,
+            max_parallel_builds,
+            max_parallel_links,
+            packages,
+            json,
+            format,
+            annotations,
+            annotations_file,
+            event_stream,
+            stats_file,
+            workers,
+            worker_token,
+            check_fingerprint,
+            list_outputs,
+            only,
+            as_of,
+            strict,
+            auto_unique_outputs,
+            use_daemon,
+            daemon_socket,
+            bundle,
+        } = args;
+
+        let worker_token = match worker_token {
+            Some(token) => token,
+            None if workers.is_empty() => String::new(),
+            None => anyhow::bail!(
+                "--workers needs --worker-token (or ASB_WORKER_TOKEN), the token the workers were started with"
+            ),
+        };
+        let json = json || format == OutputFormat::Json;
+        let annotations = annotations.unwrap_or(if ci {
+            AnnotationFormat::Github
+        } else {
+            AnnotationFormat::None
+        });
+        // Errors without a location are reported on the config file
+        let annotations_default_file = config_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("asb.config.json"));
+        if let Some(ref target) = event_stream {
+            events::init(target)?;
+        }
+        let stats_file = stats::stats_file(stats_file)?;
+
+        // Initialize rayon thread pool with CPU cores * 2
+        // This is for resource compilation within each build
+        let worker_threads = num_cpus::get() * 2;
+        if rayon::ThreadPoolBuilder::new()
+            .num_threads(worker_threads)
+            .build_global()
+            .is_ok()
+        {
+            info!(
+                "Initialized resource compilation thread pool with {} workers (CPU cores * 2)",
+                worker_threads
+            );
+        }
+
+        // Check if CLI arguments are provided
+        let has_cli_args = !overrides.is_empty()
+            || package.is_some()
+            || max_parallel_builds.is_some()
+            || max_parallel_links.is_some();
+
+        // Check if using defaults before moving config_file
+        let using_defaults = config_file.is_none() && !PathBuf::from("./asb.config.json").exists();
+
+        // Package ID lock lives next to the config file (or in the current directory)
+        let package_id_lock = package_id_lock_path(config_file.as_deref());
+
+        // Load configs: support both single and array mode
+        if let Some(as_of) = &as_of {
+            schedule::parse_date(as_of).context("Invalid --as-of")?;
+        }
+        let loaded = BuildConfig::load_configs_as_of(config_file, None, as_of.as_deref())?;
+
+        // Save all package names before moving configs (for error messages)
+        let all_package_names: Vec<String> = loaded
+            .configs
+            .iter()
+            .map(|c| c.package_name.clone())
+            .collect();
+
+        let bundle_output = match (bundle, loaded.bundle) {
+            (false, _) => None,
+            (true, Some(config)) => Some(config.output),
+            (true, None) => anyhow::bail!(
+                "--bundle needs a \"bundle\": {{\"output\": ...}} section in the config"
+            ),
+        };
+        if bundle_output.is_some() && use_daemon {
+            anyhow::bail!("--bundle cannot be combined with --use-daemon");
+        }
+
+        let mut build_configs = loaded.configs;
+        let config_max_parallel = loaded.max_parallel_builds;
+        let config_max_parallel_links = loaded.max_parallel_links;
+
+        // Allocate package IDs across every config before filtering so assignments stay stable
+        if auto_assign_enabled(&build_configs) {
+            assign_package_ids(&mut build_configs, &package_id_lock)?;
+        }
+
+        // Filter configs by package names if specified
+        if !packages.is_empty() {
+            let original_count = build_configs.len();
+            build_configs.retain(|config| packages.contains(&config.package_name));
+            let filtered_count = build_configs.len();
+
+            if filtered_count == 0 {
+                anyhow::bail!(
+                    "No configurations found matching specified packages: {}. Available packages: {}",
+                    packages.join(", "),
+                    all_package_names.join(", ")
+                );
+            }
+
+            info!(
+                "Filtered {} out of {} configurations by package names: {}",
+                filtered_count,
+                original_count,
+                packages.join(", ")
+            );
+        }
+
+        // Filter configs by build targets if specified
+        if !targets.is_empty() {
+            let original_count = build_configs.len();
+            select_targets(&mut build_configs, &targets)?;
+            info!(
+                "Filtered {} out of {} configurations by targets: {}",
+                build_configs.len(),
+                original_count,
+                targets.join(" ")
+            );
+        }
+
+        info!(
+            "Config maximum parallel builds setting: {:?}",
+            config_max_parallel
+        );
+        info!(
+            "CLI maximum parallel builds setting: {:?}",
+            max_parallel_builds
+        );
+
+        // Get max parallel builds setting (CLI > config > default: CPU cores)
+        let max_parallel = max_parallel_builds
+            .or(config_max_parallel)
+            .unwrap_or_else(num_cpus::get);
+
+        info!("Maximum parallel builds: {} configs", max_parallel);
+
+        // Links are limited separately (CLI > config > default: max parallel builds)
+        let max_links = max_parallel_links
+            .or(config_max_parallel_links)
+            .unwrap_or(max_parallel);
+        info!("Maximum parallel links: {}", max_links);
+
+        // Show info message if using defaults
+        if using_defaults {
+            info!("Using default configuration based on standard Android project structure");
+            info!("Create asb.config.json in current directory to customize settings");
+        }
+
+        // If CLI arguments are provided and we have multiple configs, warn the user
+        if has_cli_args && build_configs.len() > 1 {
+            warn!(
+                "CLI arguments provided with array configuration. CLI arguments will override ALL configurations."
+            );
+        }
+
+        // Override configs with CLI arguments (CLI args have highest priority)
+        if has_cli_args {
+            for build_config in &mut build_configs {
+                if let Some(ref p) = package {
+                    build_config.package_name = p.clone();
+                }
+                overrides.apply(build_config);
+            }
+        }
+
+        // Lay out output and build directories now that every override is in place
+        for build_config in &mut build_configs {
+            if strict {
+                build_config.strict = Some(true);
+                build_config.apply_strict();
+            }
+            build_config.apply_output_layout()?;
+        }
+
+        // Reject illegal package names, package IDs and versionCodes up front
+        validate_configs(&build_configs)?;
+
+        // Parallel builds writing the same package file would overwrite each other
+        resolve_output_collisions(&mut build_configs, auto_unique_outputs)?;
+
+        if !only.is_empty() {
+            validate_resource_types(&only)?;
+            for build_config in &mut build_configs {
+                build_config.only_resource_types = Some(only.clone());
+            }
+        }
+
+        // Outputs only depend on the config, so list them before looking for a toolchain
+        if list_outputs {
+            return Self::list_outputs(&build_configs, json);
+        }
+
+        // Resolve aapt2 path once for all configs if not explicitly provided
+        // This avoids repeated searches when building multiple packages
+        let resolved_aapt2_path = if build_configs.iter().all(|c| c.aapt2_path.is_none()) {
+            // Only search once if no config has an explicit aapt2 path
+            Some(Aapt2::new(None)?.into_path())
+        } else {
+            None
+        };
+
+        // For array mode with multiple configs, ensure each config has a unique compiled directory
+        // to avoid conflicts when building multiple packages from the same build directory
+        if build_configs.len() > 1 {
+            for build_config in build_configs.iter_mut() {
+                // Only set if not explicitly configured
+                // Use build_dir for intermediate files, not output_dir
+                if build_config.compiled_dir.is_none() {
+                    let build_dir = build_config
+                        .build_dir
+                        .clone()
+                        .unwrap_or_else(|| build_config.output_dir.join(".build"));
+                    let unique_compiled_dir = build_dir.join(&build_config.package_name);
+                    build_config.compiled_dir = Some(unique_compiled_dir);
+                }
+
+                // Apply resolved aapt2 path if config doesn't have one
+                if build_config.aapt2_path.is_none() {
+                    build_config.aapt2_path = resolved_aapt2_path.clone();
+                }
+            }
+        } else if let Some(ref path) = resolved_aapt2_path {
+            // Single config mode - apply resolved path if needed
+            if build_configs[0].aapt2_path.is_none() {
+                build_configs[0].aapt2_path = Some(path.clone());
+            }
+        }
+
+        // Fetch remote resource directories only for the packages actually being built
+        for build_config in &mut build_configs {
+            resolve_remote_sources(build_config).with_context(|| {
+                format!("Failed to resolve config of {}", build_config.package_name)
+            })?;
+        }
+
+        if check_fingerprint {
+            return Self::check_fingerprints(&build_configs, json);
+        }
+
+        // Fail before the first package rather than midway with an IO error
+        disk_space::check_disk_space(&build_configs)?;
+
+        if use_daemon {
+            return Self::build_with_daemon(&daemon_socket, build_configs, stats_file, json).await;
+        }
+
+        if build_configs.len() == 1 {
+            // Single configuration mode - keep backward compatibility
+            let config = build_configs.into_iter().next().unwrap();
+            let package_name = config.package_name.clone();
+            println!("{}", "\nBuilding skin package...\n".blue().bold());
+            let started_at = chrono::Local::now();
+            let start_time = std::time::Instant::now();
+            let remote = if !workers.is_empty() && is_remote_buildable(&config) {
+                let (results, _) = build_on_workers(
+                    vec![ConfigWithIndex {
+                        index: 0,
+                        config: config.clone(),
+                    }],
+                    &workers,
+                    &worker_token,
+                )
+                .await;
+                results.into_iter().next().map(|(_, result)| result)
+            } else {
+                None
+            };
+            let result = match remote {
+                Some(result) => result,
+                None => SkinBuilder::new(config.clone())?
+                    .build()
+                    .await
+                    .map_err(|e| {
+                        anyhow::anyhow!(render_errors(&[format!("{:#}", e)], true).join("\n"))
+                    })?,
+            };
+            let elapsed = start_time.elapsed();
+            Self::record_stats(stats_file.as_deref(), started_at, elapsed, 1, &[&result]);
+
+            if let Some(ref output) = bundle_output
+                && result.success
+            {
+                Self::write_bundle(&[(&config, &result)], output, json)?;
+            }
+
+            let results = [(package_name.as_str(), &result)];
+            annotations::emit(
+                annotations,
+                &build_annotations(&results),
+                &annotations_default_file,
+                &annotations_file,
+                json,
+            )?;
+            if ci && !json {
+                for line in ci::summary(&results, usize::from(!result.success), elapsed) {
+                    println!("{}", line);
+                }
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                if !result.success {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            if ci {
+                if !result.success {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+
+            if result.success {
+                println!("{}", "\n✓ Skin package built successfully!".green().bold());
+                if let Some(apk_path) = result.apk_path {
+                    println!("  {}: {}", "Output".cyan(), apk_path.display());
+                }
+                println!("  {}: {:.2}s", "Total time".cyan(), elapsed.as_secs_f64());
+                println!(
+                    "  {}: {:.2}s",
+                    "Build time".cyan(),
+                    result.build_duration.as_secs_f64()
+                );
+                for warning in &result.warnings {
+                    println!("  {} {}", "⚠".yellow(), warning);
+                }
+            } else {
+                println!(
+                    "{}",
+                    format!("\n✗ Build failed for package '{}'", package_name)
+                        .red()
+                        .bold()
+                );
+                for error in render_errors(&result.errors, true) {
+                    println!("  - {}", error.replace('\n', "\n    "));
+                }
+
+                // Save failure log
+                match Self::save_failure_log(&package_name, &result.errors, None) {
+                    Ok(log_path) => {
+                        println!("\n  {}: {}", "Log saved to".yellow(), log_path.display());
+                    }
+                    Err(e) => {
+                        warn!("Failed to save error log: {}", e);
+                    }
+                }
+
+                std::process::exit(1);
+            }
+        } else {
+            // Multiple configurations mode
+            // Keep a copy of original configs for displaying package names later
+            let original_configs = build_configs.clone();
+
+            println!(
+                "{}",
+                format!("\nBuilding {} skin packages...\n", build_configs.len())
+                    .blue()
+                    .bold()
+            );
+
+            let started_at = chrono::Local::now();
+            let start_time = std::time::Instant::now();
+
+            // Extract common dependencies
+            let common_deps = extract_common_dependencies(&build_configs);
+
+            if !common_deps.is_empty() {
+                info!(
+                    "Found {} common dependencies to compile first",
+                    common_deps.len()
+                );
+
+                // Determine cache directory for common dependencies
+                // Use the first config's build_dir as the base, since all configs should use compatible settings
+                // for shared common dependency compilation
+                let base_build_dir = build_configs[0]
+                    .build_dir
+                    .clone()
+                    .unwrap_or_else(|| build_configs[0].output_dir.join(".build"));
+                let base_cache_dir = build_configs[0]
+                    .cache_dir
+                    .clone()
+                    .unwrap_or_else(|| base_build_dir.clone());
+                let common_cache_dir = base_cache_dir.join("common-deps");
+
+                // Initialize common dependency cache
+                let mut common_dep_cache = CommonDependencyCache::new(common_cache_dir.clone())?;
+                common_dep_cache.init()?;
+
+                // Use aapt2 path from first config (all configs should use the same aapt2)
+                let aapt2 = Aapt2::new(build_configs[0].aapt2_path.clone())?
+                    .with_limits(build_configs[0].aapt2_limits.unwrap_or_default());
+
+                // Static libraries are opt-in and, like aapt2, taken from the first config
+                let static_lib_dependencies =
+                    build_configs[0].static_lib_dependencies.unwrap_or(false);
+
+                // Map to store compiled flat files for each common dependency
+                let mut precompiled_map: std::collections::HashMap<PathBuf, Vec<PathBuf>> =
+                    std::collections::HashMap::new();
+
+                // Compile common dependencies
+                for common_dep in &common_deps {
+                    info!(
+                        "Compiling common dependency: {} (used by {} apps)",
+                        common_dep.resource_dir.display(),
+                        common_dep.dependent_configs.len()
+                    );
+
+                    // Check if we need to recompile
+                    let needs_recompile =
+                        common_dep_cache.needs_recompile(&common_dep.resource_dir)?;
+
+                    // Create directory name based on resource path
+                    // e.g., "common/res" -> "common_res"
+                    let dir_name = common_dep
+                        .resource_dir
+                        .to_string_lossy()
+                        .replace(['/', '\\', ':'], "_")
+                        .trim_matches('_')
+                        .to_string();
+                    let compiled_dir = common_cache_dir.join(&dir_name);
+
+                    let mut flat_files = None;
+                    if needs_recompile {
+                        // Compile each common dependency to its own directory
+                        std::fs::create_dir_all(&compiled_dir)?;
+
+                        let compile_result = run_blocking(|| {
+                            aapt2.compile_dir(&common_dep.resource_dir, &compiled_dir)
+                        })?;
+
+                        if compile_result.success {
+                            info!(
+                                "  ✓ Compiled {} resources into {} flat files in {}",
+                                common_dep.resource_dir.display(),
+                                compile_result.flat_files.len(),
+                                compiled_dir.display()
+                            );
+
+                            // Update cache
+                            common_dep_cache.update_entry(
+                                &common_dep.resource_dir,
+                                compile_result.flat_files.clone(),
+                            )?;
+                            flat_files = Some(compile_result.flat_files);
+                        } else {
+                            error!(
+                                "  ✗ Failed to compile common dependency: {}",
+                                common_dep.resource_dir.display()
+                            );
+                            for err in &compile_result.errors {
+                                error!("    {}", err);
+                            }
+                        }
+                    } else {
+                        info!(
+                            "  ✓ Using cached compiled resources for {}",
+                            common_dep.resource_dir.display()
+                        );
+
+                        // Get cached flat files
+                        flat_files =
+                            common_dep_cache.get_cached_flat_files(&common_dep.resource_dir);
+                    }
+
+                    let Some(flat_files) = flat_files else {
+                        continue;
+                    };
+
+                    // Optionally replace the flat files with a shared static library
+                    let static_lib = if static_lib_dependencies {
+                        let cached_lib = if needs_recompile {
+                            None
+                        } else {
+                            common_dep_cache.get_cached_static_lib(&common_dep.resource_dir)
+                        };
+                        match cached_lib {
+                            Some(lib) => {
+                                info!("  ✓ Using cached static library {}", lib.display());
+                                Some(lib)
+                            }
+                            None => {
+                                Self::link_common_static_lib(
+                                    &aapt2,
+                                    &flat_files,
+                                    &compiled_dir,
+                                    build_configs[0].android_jar.as_deref(),
+                                )
+                                .await
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    match static_lib {
+                        Some(lib) => {
+                            common_dep_cache.set_static_lib(&common_dep.resource_dir, lib.clone());
+                            precompiled_map.insert(common_dep.resource_dir.clone(), vec![lib]);
+                        }
+                        None => {
+                            precompiled_map.insert(common_dep.resource_dir.clone(), flat_files);
+                        }
+                    }
+                }
+
+                // Save common dependency cache
+                common_dep_cache.save()?;
+
+                // Update all build configs with precompiled dependencies
+                if !precompiled_map.is_empty() {
+                    for config in &mut build_configs {
+                        config.precompiled_common_deps = Some(precompiled_map.clone());
+                    }
+                }
+            }
+
+            // Group configs by dependencies
+            let (mut independent_configs, dependent_groups) =
+                group_configs_by_dependencies(build_configs)?;
+
+            // Start the builds that took longest last time first
+            let timed = order_by_build_time(&mut independent_configs);
+            if timed > 0 {
+                info!(
+                    "Scheduling independent configs longest first ({} with recorded build times)",
+                    timed
+                );
+            }
+
+            info!(
+                "Found {} independent configs and {} dependency groups",
+                independent_configs.len(),
+                dependent_groups.len()
+            );
+
+            let mut all_results = Vec::new();
+            let mut success_count = 0;
+            let mut fail_count = 0;
+
+            // Hand independent configs to workers; failed dispatches come back for a local build
+            if !workers.is_empty() {
+                let (remote_configs, local_configs): (Vec<_>, Vec<_>) = independent_configs
+                    .into_iter()
+                    .partition(|c| is_remote_buildable(&c.config));
+                independent_configs = local_configs;
+                if !remote_configs.is_empty() {
+                    info!(
+                        "Building {} independent configs on {} workers...",
+                        remote_configs.len(),
+                        workers.len()
+                    );
+                    let (results, fallback) =
+                        build_on_workers(remote_configs, &workers, &worker_token).await;
+                    all_results.extend(results);
+                    independent_configs.extend(fallback);
+                }
+            }
+
+            // Build independent configs in parallel
+            if !independent_configs.is_empty() {
+                info!(
+                    "Building {} independent configs in parallel (max {} concurrent, {} linking)...",
+                    independent_configs.len(),
+                    max_parallel,
+                    max_links
+                );
+
+                // Builders share the limits and release their build slot while linking
+                let limits = ConcurrencyLimits::new(max_parallel, max_links);
+                let mut tasks: tokio::task::JoinSet<
+                    Result<(usize, String, crate::types::BuildResult), (String, anyhow::Error)>,
+                > = tokio::task::JoinSet::new();
+
+                for config_with_idx in independent_configs {
+                    let idx = config_with_idx.index;
+                    let config = config_with_idx.config.clone();
+                    let package_name = config.package_name.clone();
+                    let limits = limits.clone();
+                    let span = package_logs::package_span(&config);
+
+                    tasks.spawn(
+                        async move {
+                            let result = match SkinBuilder::new(config) {
+                                Ok(builder) => {
+                                    builder.with_concurrency_limits(limits).build().await
+                                }
+                                Err(e) => Err(e),
+                            };
+                            package_logs::finish_package_log(&package_name, result.as_ref());
+                            match result {
+                                Ok(result) => Ok((idx, package_name, result)),
+                                Err(e) => Err((package_name, e)),
+                            }
+                        }
+                        .instrument(span),
+                    );
+                }
+
+                while let Some(result) = tasks.join_next().await {
+                    match result {
+                        Ok(Ok((idx, _package_name, build_result))) => {
+                            all_results.push((idx, build_result));
+                        }
+                        Ok(Err((package_name, e))) => {
+                            error!(
+                                "Build error for package '{}': {}",
+                                package_name,
+                                render_errors(&[e.to_string()], false).join("\n")
+                            );
+                            // Print full error chain for debugging
+                            let mut source = e.source();
+                            let mut depth = 1;
+                            while let Some(err) = source {
+                                error!("  Caused by ({}): {}", depth, err);
+                                source = err.source();
+                                depth += 1;
+                            }
+                            fail_count += 1;
+                        }
+                        Err(e) => {
+                            error!("Task join error: {}", e);
+                            fail_count += 1;
+                        }
+                    }
+                }
+            }
+
+            // Build dependent groups sequentially
+            for (group_number, group) in dependent_groups.into_iter().enumerate() {
+                info!(
+                    "Building dependency group {} with {} configs sequentially...",
+                    group_number + 1,
+                    group.len()
+                );
+
+                for config_with_idx in group {
+                    let config = config_with_idx.config.clone();
+                    let package_name = config.package_name.clone();
+                    let span = package_logs::package_span(&config);
+                    let result = Self::build_single_config(config).instrument(span).await;
+                    package_logs::finish_package_log(&package_name, result.as_ref());
+                    match result {
+                        Ok(result) => {
+                            all_results.push((config_with_idx.index, result));
+                        }
+                        Err(e) => {
+                            error!(
+                                "Build error for package '{}': {}",
+                                package_name,
+                                render_errors(&[e.to_string()], false).join("\n")
+                            );
+                            // Print full error chain for debugging
+                            let mut source = e.source();
+                            let mut depth = 1;
+                            while let Some(err) = source {
+                                error!("  Caused by ({}): {}", depth, err);
+                                source = err.source();
+                                depth += 1;
+                            }
                             fail_count += 1;
                         }
                     }
                 }
             }
 
-            // Build dependent groups sequentially
-            for (group_number, group) in dependent_groups.into_iter().enumerate() {
-                info!(
-                    "Building dependency group {} with {} configs sequentially...",
-                    group_number + 1,
-                    group.len()
+            // Count successes and failures
+            for (_, result) in &all_results {
+                if result.success {
+                    success_count += 1;
+                } else {
+                    fail_count += 1;
+                }
+            }
+
+            let elapsed = start_time.elapsed();
+            let results: Vec<&BuildResult> = all_results.iter().map(|(_, r)| r).collect();
+            Self::record_stats(
+                stats_file.as_deref(),
+                started_at,
+                elapsed,
+                original_configs.len(),
+                &results,
+            );
+
+            if let Some(ref output) = bundle_output
+                && fail_count == 0
+            {
+                let mut modules: Vec<_> = all_results
+                    .iter()
+                    .map(|(idx, result)| (&original_configs[*idx], result))
+                    .collect();
+                modules.sort_by_key(|(config, _)| config.target());
+                Self::write_bundle(&modules, output, json)?;
+            }
+
+            let named_results: Vec<(&str, &BuildResult)> = all_results
+                .iter()
+                .map(|(idx, result)| {
+                    let package_name = original_configs
+                        .get(*idx)
+                        .map_or("unknown", |c| c.package_name.as_str());
+                    (package_name, result)
+                })
+                .collect();
+            annotations::emit(
+                annotations,
+                &build_annotations(&named_results),
+                &annotations_default_file,
+                &annotations_file,
+                json,
+            )?;
+            if ci && !json {
+                for line in ci::summary(&named_results, fail_count, elapsed) {
+                    println!("{}", line);
+                }
+                if fail_count > 0 {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+
+            // Display results
+            if json {
+                // Build a summary JSON with all results
+                let mut results_map = serde_json::Map::new();
+                for (idx, result) in &all_results {
+                    let package_name = original_configs
+                        .get(*idx)
+                        .map(|c| c.package_name.clone())
+                        .unwrap_or_else(|| format!("config_{}", idx));
+                    results_map.insert(
+                        package_name,
+                        serde_json::json!({
+                            "success": result.success,
+                            "apk_path": result.apk_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+                            "errors": result.errors,
+                            "warnings": result.warnings,
+                            "build_duration_secs": result.build_duration.as_secs_f64(),
+                            "vcs": result.vcs,
+                            "normalization": result.normalization,
+                        }),
+                    );
+                }
+                let summary = serde_json::json!({
+                    "total": original_configs.len(),
+                    "successful": success_count,
+                    "failed": fail_count,
+                    "total_time_secs": elapsed.as_secs_f64(),
+                    "results": results_map,
+                });
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+                if fail_count > 0 {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+
+            println!("\n{}", "Build Summary:".blue().bold());
+            println!("  {}: {}", "Successful".green(), success_count);
+            println!("  {}: {}", "Failed".red(), fail_count);
+            println!("  {}: {:.2}s", "Total time".cyan(), elapsed.as_secs_f64());
+
+            // Show individual results
+            // Create a mapping from index to package name for display
+            let package_names: std::collections::HashMap<usize, String> = original_configs
+                .iter()
+                .enumerate()
+                .map(|(idx, cfg)| (idx, cfg.package_name.clone()))
+                .collect();
+
+            println!("\n{}", "Individual Results:".blue().bold());
+            for (idx, result) in &all_results {
+                let package_name = package_names
+                    .get(idx)
+                    .map(|s| s.as_str())
+                    .unwrap_or("unknown");
+                if result.success {
+                    if let Some(ref apk_path) = result.apk_path {
+                        println!(
+                            "  {} Config #{} [{}]: {} ({:.2}s)",
+                            "✓".green(),
+                            idx + 1,
+                            package_name,
+                            apk_path.display(),
+                            result.build_duration.as_secs_f64()
+                        );
+                    } else {
+                        println!(
+                            "  {} Config #{} [{}] ({:.2}s)",
+                            "✓".green(),
+                            idx + 1,
+                            package_name,
+                            result.build_duration.as_secs_f64()
+                        );
+                    }
+                    for warning in &result.warnings {
+                        println!("      {} {}", "⚠".yellow(), warning);
+                    }
+                } else {
+                    println!(
+                        "  {} Config #{} [{}]: Build failed ({:.2}s)",
+                        "✗".red(),
+                        idx + 1,
+                        package_name,
+                        result.build_duration.as_secs_f64()
+                    );
+                    for error in render_errors(&result.errors, true) {
+                        println!("      - {}", error.replace('\n', "\n        "));
+                    }
+                    // Save failure log
+                    if let Some(_config) = original_configs.get(*idx) {
+                        match Self::save_failure_log(package_name, &result.errors, None) {
+                            Ok(log_path) => {
+                                println!(
+                                    "      {}: {}",
+                                    "Log saved to".yellow(),
+                                    log_path.display()
+                                );
+                            }
+                            Err(e) => {
+                                warn!("Failed to save error log for {}: {}", package_name, e);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if fail_count > 0 {
+                std::process::exit(1);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Link a common dependency's flat files into `compiled_dir/static-lib.apk`
+    /// Returns None (after logging why) so callers fall back to the flat files
+    async fn link_common_static_lib(
+        aapt2: &Aapt2,
+        flat_files: &[PathBuf],
+        compiled_dir: &Path,
+        android_jar: Option<&Path>,
+    ) -> Option<PathBuf> {
+        let Some(android_jar) = android_jar else {
+            warn!("  android_jar not set, using flat files instead of a static library");
+            return None;
+        };
+
+        let output_lib = compiled_dir.join("static-lib.apk");
+        let result = match create_minimal_manifest("asb.common", compiled_dir) {
+            Ok(manifest) => {
+                aapt2
+                    .link_static_lib(flat_files, &manifest, android_jar, &output_lib)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) if result.success => {
+                info!("  ✓ Linked static library {}", output_lib.display());
+                Some(output_lib)
+            }
+            Ok(result) => {
+                for err in &result.errors {
+                    warn!("    {}", err);
+                }
+                warn!("  Static library link failed, using flat files instead");
+                None
+            }
+            Err(e) => {
+                warn!("  Static library link failed ({e:#}), using flat files instead");
+                None
+            }
+        }
+    }
+
+    /// Merge the built packages into one bundle with a manifest of the module versions
+    fn write_bundle(
+        modules: &[(&BuildConfig, &BuildResult)],
+        output: &Path,
+        json: bool,
+    ) -> Result<()> {
+        let packages = modules
+            .iter()
+            .map(|(config, result)| {
+                let apk_path = result
+                    .apk_path
+                    .clone()
+                    .with_context(|| format!("No package was built for {}", config.target()))?;
+                Ok((*config, apk_path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let manifest = write_bundle(&packages, output)?;
+        info!(
+            "Merged {} modules into bundle {}",
+            manifest.modules.len(),
+            output.display()
+        );
+        if !json {
+            println!(
+                "  {}: {} ({} modules)",
+                "Bundle".cyan(),
+                output.display(),
+                manifest.modules.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Print the declared outputs of every package, one path per line
+    fn list_outputs(configs: &[BuildConfig], json: bool) -> Result<()> {
+        if json {
+            let outputs: serde_json::Map<String, serde_json::Value> = configs
+                .iter()
+                .map(|c| {
+                    (
+                        c.package_name.clone(),
+                        serde_json::json!(c.declared_outputs()),
+                    )
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&outputs)?);
+        } else {
+            for output in configs.iter().flat_map(|c| c.declared_outputs()) {
+                println!("{}", output.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Report which packages are stale and exit with 1 if any is
+    fn check_fingerprints(configs: &[BuildConfig], json: bool) -> Result<()> {
+        let mut statuses = serde_json::Map::new();
+        let mut all_up_to_date = true;
+        for config in configs {
+            let up_to_date = is_up_to_date(config)?;
+            all_up_to_date &= up_to_date;
+            if !json {
+                if up_to_date {
+                    println!("{} {}", "✓ up to date:".green(), config.package_name);
+                } else {
+                    println!("{} {}", "✗ needs rebuild:".yellow(), config.package_name);
+                }
+            }
+            statuses.insert(config.package_name.clone(), up_to_date.into());
+        }
+        if json {
+            println!("{}", serde_json::to_string_pretty(&statuses)?);
+        }
+        if !all_up_to_date {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    async fn build_single_config(config: BuildConfig) -> Result<crate::types::BuildResult> {
+        let mut builder = SkinBuilder::new(config)?;
+        builder.build().await
+    }
+
+    /// Append this build to the stats file, when statistics are enabled
+    fn record_stats(
+        stats_file: Option<&Path>,
+        started_at: chrono::DateTime<chrono::Local>,
+        elapsed: std::time::Duration,
+        packages: usize,
+        results: &[&BuildResult],
+    ) {
+        let Some(stats_file) = stats_file else {
+            return;
+        };
+        let record = BuildRecord::new(started_at, elapsed, packages, results);
+        if let Err(e) = record.append(stats_file) {
+            warn!("Failed to record build stats: {:#}", e);
+        }
+    }
+
+    fn save_failure_log(
+        package_name: &str,
+        errors: &[String],
+        base_dir: Option<&Path>,
+    ) -> Result<PathBuf> {
+        use std::io::Write;
+
+        // Create logs directory under base_dir (or cwd if None)
+        let base = base_dir.unwrap_or_else(|| Path::new("."));
+        let logs_dir = base.join("logs");
+        std::fs::create_dir_all(&logs_dir)?;
+
+        // Generate log file name with timestamp
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let log_filename = format!("build_failure_{}_{}.log", package_name, timestamp);
+        let log_path = logs_dir.join(&log_filename);
+
+        // Write log content
+        let mut log_file = std::fs::File::create(&log_path)?;
+        writeln!(log_file, "Build Failure Log")?;
+        writeln!(log_file, "==================")?;
+        writeln!(log_file, "Package: {}", package_name)?;
+        writeln!(
+            log_file,
+            "Time: {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        )?;
+        writeln!(log_file, "\nErrors:")?;
+        writeln!(log_file, "--------")?;
+
+        for (i, error) in render_errors(errors, false).iter().enumerate() {
+            writeln!(log_file, "\n{}. {}", i + 1, error)?;
+        }
+
+        Ok(log_path)
+    }
+
+    fn run_clean(config_file: Option<PathBuf>, output_dir: Option<PathBuf>) -> Result<()> {
+        let output = if let Some(config_path) = config_file {
+            let content = std::fs::read_to_string(&config_path)?;
+            let config: BuildConfig = serde_json::from_str(&content)?;
+            config.output_dir
+        } else if let Some(o) = output_dir {
+            o
+        } else {
+            error!("Please provide either --config or --output");
+            std::process::exit(1);
+        };
+
+        let compiled_dir = output.join("compiled");
+        let temp_dir = output.join(".temp");
+        let cache_dir = output.join(".build-cache");
+
+        if compiled_dir.exists() {
+            std::fs::remove_dir_all(&compiled_dir)?;
+        }
+        if temp_dir.exists() {
+            std::fs::remove_dir_all(&temp_dir)?;
+        }
+        if cache_dir.exists() {
+            std::fs::remove_dir_all(&cache_dir)?;
+        }
+
+        println!("{}", "✓ Build artifacts cleaned".green());
+        Ok(())
+    }
+
+    fn run_version(aapt2_path: Option<PathBuf>) -> Result<()> {
+        let aapt2 = Aapt2::new(aapt2_path)?;
+        let version = aapt2.version()?;
+        println!("{}", "aapt2 version:".cyan());
+        println!("{}", version);
+        Ok(())
+    }
+
+    fn run_stats(file: Option<PathBuf>, last: Option<usize>, json: bool) -> Result<()> {
+        let Some(path) = stats::stats_file(file)? else {
+            anyhow::bail!(
+                "No stats file configured. Record builds with `asb build --stats-file <FILE>` or set statsFile in the user defaults"
+            );
+        };
+        let mut records = read_records(&path)?;
+        if let Some(last) = last {
+            records.drain(..records.len().saturating_sub(last));
+        }
+        let Some(summary) = summarize(&records) else {
+            println!("No builds recorded in {}", path.display());
+            return Ok(());
+        };
+        if json {
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+            return Ok(());
+        }
+
+        let secs = |ms: u64| ms as f64 / 1000.0;
+        println!(
+            "{}",
+            format!("Build stats ({})", path.display()).blue().bold()
+        );
+        println!(
+            "  {}: {} ({} failed)",
+            "Builds".cyan(),
+            summary.builds,
+            summary.failed_builds
+        );
+        println!("  {}: {}", "Packages".cyan(), summary.packages);
+        println!(
+            "  {}: {} .. {}",
+            "Period".cyan(),
+            summary.first_build,
+            summary.last_build
+        );
+        println!(
+            "  {}: mean {:.2}s, median {:.2}s, p90 {:.2}s",
+            "Duration".cyan(),
+            secs(summary.mean_duration_ms),
+            secs(summary.median_duration_ms),
+            secs(summary.p90_duration_ms)
+        );
+        match summary.cache_hit_ratio {
+            Some(ratio) => println!("  {}: {:.1}%", "Cache hits".cyan(), ratio * 100.0),
+            None => println!("  {}: -", "Cache hits".cyan()),
+        }
+        let cores: Vec<String> = summary.cores.iter().map(|c| c.to_string()).collect();
+        println!("  {}: {}", "Machine cores".cyan(), cores.join(", "));
+        Ok(())
+    }
+
+    /// Build resolved configs on a running daemon and report the results
+    async fn build_with_daemon(
+        socket: &Path,
+        build_configs: Vec<BuildConfig>,
+        stats_file: Option<PathBuf>,
+        json: bool,
+    ) -> Result<()> {
+        let started_at = chrono::Local::now();
+        let start_time = std::time::Instant::now();
+        let packages: Vec<String> = build_configs
+            .iter()
+            .map(|c| c.package_name.clone())
+            .collect();
+        if !json {
+            println!(
+                "{}",
+                format!(
+                    "\nBuilding {} skin packages on the daemon at {}...\n",
+                    packages.len(),
+                    socket.display()
+                )
+                .blue()
+                .bold()
+            );
+        }
+        let results = build_on_daemon(socket, build_configs).await?;
+        let elapsed = start_time.elapsed();
+        let refs: Vec<&BuildResult> = results.iter().collect();
+        Self::record_stats(
+            stats_file.as_deref(),
+            started_at,
+            elapsed,
+            results.len(),
+            &refs,
+        );
+        let failed = results.iter().filter(|r| !r.success).count();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        } else {
+            for (package, result) in packages.iter().zip(&results) {
+                if result.success {
+                    let output = result
+                        .apk_path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default();
+                    println!(
+                        "{} {} {} ({:.2}s)",
+                        "✓".green(),
+                        package,
+                        output,
+                        result.build_duration.as_secs_f64()
+                    );
+                } else {
+                    println!("{} {}", "✗".red(), package);
+                    for error in &result.errors {
+                        println!("  - {}", error);
+                    }
+                }
+                for warning in &result.warnings {
+                    println!("  {} {}", "⚠".yellow(), warning);
+                }
+            }
+            println!("  {}: {:.2}s", "Total time".cyan(), elapsed.as_secs_f64());
+        }
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    fn run_explain(
+        resource: String,
+        config_file: Option<PathBuf>,
+        package: Option<String>,
+        json: bool,
+    ) -> Result<()> {
+        let configs: Vec<BuildConfig> = BuildConfig::load_built_configs(config_file)?
+            .into_iter()
+            .filter(|c| package.as_ref().is_none_or(|p| &c.package_name == p))
+            .collect();
+        if configs.is_empty() {
+            match &package {
+                Some(package) => anyhow::bail!("No matching packages found for: {}", package),
+                None => anyhow::bail!("No build configs found"),
+            }
+        }
+        let reports = configs
+            .iter()
+            .map(|config| explain_resource(config, &resource))
+            .collect::<Result<Vec<_>>>()?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+            return Ok(());
+        }
+
+        for report in &reports {
+            println!(
+                "{}",
+                format!("{} in {}", report.resource, report.package_name)
+                    .blue()
+                    .bold()
+            );
+            if report.candidates.is_empty() {
+                println!("  {}", "not defined in any source set or AAR".yellow());
+            }
+            for candidate in &report.candidates {
+                let marker = if candidate.wins {
+                    "wins".green()
+                } else {
+                    "overridden".dimmed()
+                };
+                println!(
+                    "  [{}] {} ({}, {}) {}",
+                    candidate.directory,
+                    candidate.source,
+                    candidate.source_set,
+                    candidate.priority,
+                    marker
+                );
+                if let Some(flat_file) = &candidate.flat_file {
+                    println!("      compiled to {}", flat_file.display());
+                }
+            }
+            match report.packaged {
+                Some(true) => println!("  {} {}", "packaged in".green(), report.package.display()),
+                Some(false) => println!("  {} {}", "missing from".red(), report.package.display()),
+                None => println!(
+                    "  {} {}",
+                    "not built yet:".yellow(),
+                    report.package.display()
+                ),
+            }
+            println!();
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_coverage(
+        host: Option<PathBuf>,
+        layouts: Vec<String>,
+        types: Vec<String>,
+        config_file: Option<PathBuf>,
+        package: Option<String>,
+        aapt2: Option<PathBuf>,
+        min_coverage: Option<f64>,
+        json: bool,
+    ) -> Result<()> {
+        let configs: Vec<BuildConfig> = BuildConfig::load_configs(config_file, None)?
+            .configs
+            .into_iter()
+            .filter(|c| package.as_ref().is_none_or(|p| &c.package_name == p))
+            .collect();
+        if configs.is_empty() {
+            match &package {
+                Some(package) => anyhow::bail!("No matching packages found for: {}", package),
+                None => anyhow::bail!("No build configs found"),
+            }
+        }
+
+        let host_layouts = match &host {
+            Some(apk) => {
+                let aapt2 = Aapt2::new(aapt2.or_else(|| configs[0].aapt2_path.clone()))?;
+                read_apk_layouts(&aapt2, apk, &layouts)?
+            }
+            None if layouts.is_empty() => {
+                anyhow::bail!("Pass the host layouts with --layouts <globs> or --host <apk>")
+            }
+            None => read_layout_files(&find_layout_files(&layouts)?)?,
+        };
+        if host_layouts.is_empty() {
+            anyhow::bail!("No host layouts found for {}", layouts.join(", "));
+        }
+
+        let reports = configs
+            .iter()
+            .map(|config| {
+                let skin = skin_symbols(config)?;
+                Ok(check_coverage(
+                    &config.package_name,
+                    &host_layouts,
+                    &skin,
+                    &types,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        } else {
+            for report in &reports {
+                println!(
+                    "{}",
+                    format!(
+                        "{}: {:.1}% ({}/{} skinnable references)",
+                        report.package_name, report.percent, report.covered, report.total
+                    )
+                    .blue()
+                    .bold()
                 );
-
-                for config_with_idx in group {
-                    let config = config_with_idx.config.clone();
-                    let package_name = config.package_name.clone();
-                    match Self::build_single_config(config).await {
-                        Ok(result) => {
-                            all_results.push((config_with_idx.index, result));
-                        }
-                        Err(e) => {
-                            error!("Build error for package '{}': {}", package_name, e);
-                            // Print full error chain for debugging
-                            let mut source = e.source();
-                            let mut depth = 1;
-                            while let Some(err) = source {
-                                error!("  Caused by ({}): {}", depth, err);
-                                source = err.source();
-                                depth += 1;
-                            }
-                            fail_count += 1;
-                        }
+                for screen in &report.screens {
+                    println!(
+                        "  {:>5.1}%  {} ({}/{})",
+                        screen.percent, screen.screen, screen.covered, screen.total
+                    );
+                    for missing in screen.references.iter().filter(|r| !r.covered) {
+                        println!(
+                            "          {} {} {} -> @{}",
+                            "missing".yellow(),
+                            missing.reference.view,
+                            missing.reference.attribute,
+                            missing.reference.resource
+                        );
                     }
                 }
+                println!();
             }
+        }
 
-            // Count successes and failures
-            for (_, result) in &all_results {
-                if result.success {
-                    success_count += 1;
+        if let Some(min_coverage) = min_coverage {
+            let below: Vec<String> = reports
+                .iter()
+                .filter(|r| r.percent < min_coverage)
+                .map(|r| format!("{} ({:.1}%)", r.package_name, r.percent))
+                .collect();
+            if !below.is_empty() {
+                anyhow::bail!("Coverage below {}%: {}", min_coverage, below.join(", "));
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_bench(
+        config_file: Option<PathBuf>,
+        package: Option<String>,
+        options: BenchOptions,
+        json: bool,
+    ) -> Result<()> {
+        let configs = BuildConfig::load_configs(config_file, None)?.configs;
+        let mut config = match &package {
+            Some(package) => configs
+                .into_iter()
+                .find(|c| &c.package_name == package)
+                .ok_or_else(|| anyhow::anyhow!("No matching packages found for: {}", package))?,
+            None => configs
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No build configs found"))?,
+        };
+        if !json {
+            println!(
+                "{}",
+                format!("Benchmarking {}...", config.package_name)
+                    .blue()
+                    .bold()
+            );
+        }
+        resolve_remote_sources(&mut config)?;
+        let report = run_bench(&config, &options).await?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+
+        println!(
+            "\n{} resource files in {} directories\n",
+            report.resource_files, report.resource_dirs
+        );
+        let fastest_compile = report.fastest_compile().map(|r| (r.strategy, r.workers));
+        println!("{}", "Compile".cyan().bold());
+        for run in &report.compile {
+            let label = format!("{:<9} x{:<3}", strategy_name(run.strategy), run.workers);
+            let timing = format_timing(run.median_ms, run.error.as_deref());
+            if fastest_compile == Some((run.strategy, run.workers)) {
+                println!("  {} {} {}", label, timing.green(), "fastest".green());
+            } else if run.error.is_some() {
+                println!("  {} {}", label, timing.red());
+            } else {
+                println!("  {} {}", label, timing);
+            }
+        }
+        if !report.link.is_empty() {
+            let fastest_link = report.fastest_link().map(|r| r.mode);
+            println!("{}", "Link".cyan().bold());
+            for run in &report.link {
+                let label = format!("{:<14}", format!("{:?}", run.mode).to_lowercase());
+                let timing = format_timing(run.median_ms, run.error.as_deref());
+                if fastest_link == Some(run.mode) {
+                    println!("  {} {} {}", label, timing.green(), "fastest".green());
+                } else if run.error.is_some() {
+                    println!("  {} {}", label, timing.red());
                 } else {
-                    fail_count += 1;
+                    println!("  {} {}", label, timing);
                 }
             }
+        }
+        if !report.recommended.is_empty() {
+            println!(
+                "\n{}\n{}",
+                "Recommended config:".green().bold(),
+                serde_json::to_string_pretty(&report.recommended)?
+            );
+        }
+        Ok(())
+    }
 
-            let elapsed = start_time.elapsed();
+    fn run_doctor(config_file: Option<PathBuf>, aapt2_path: Option<PathBuf>) -> Result<()> {
+        let mut checks = Vec::new();
+        let configs = match BuildConfig::load_configs(config_file, None) {
+            Ok(loaded) => loaded.configs,
+            Err(e) => {
+                checks.push(Check {
+                    name: "config",
+                    status: CheckStatus::Error,
+                    detail: format!("{:#}", e),
+                    fix: Some("Fix the config file or pass another one with --config".to_string()),
+                });
+                Vec::new()
+            }
+        };
+        checks.extend(run_checks(aapt2_path, &configs));
+
+        for check in &checks {
+            let line = format!("{}: {}", check.name, check.detail);
+            match check.status {
+                CheckStatus::Ok => println!("{} {}", "✓".green(), line),
+                CheckStatus::Warning => println!("{} {}", "⚠".yellow(), line.yellow()),
+                CheckStatus::Error => println!("{} {}", "✗".red(), line.red()),
+            }
+            if let Some(fix) = &check.fix {
+                println!("    {} {}", "fix:".cyan(), fix);
+            }
+        }
 
-            // Display results
-            if json {
-                // Build a summary JSON with all results
-                let mut results_map = serde_json::Map::new();
-                for (idx, result) in &all_results {
-                    let package_name = original_configs
-                        .get(*idx)
-                        .map(|c| c.package_name.clone())
-                        .unwrap_or_else(|| format!("config_{}", idx));
-                    results_map.insert(
-                        package_name,
-                        serde_json::json!({
-                            "success": result.success,
-                            "apk_path": result.apk_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-                            "errors": result.errors,
-                            "build_duration_secs": result.build_duration.as_secs_f64(),
-                        }),
+        let failed = checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Error)
+            .count();
+        if failed > 0 {
+            anyhow::bail!("{} environment check(s) failed", failed);
+        }
+        println!("\n{}", "✓ Environment looks good".green());
+        Ok(())
+    }
+
+    fn run_lint(
+        config_file: Option<PathBuf>,
+        packages: Vec<String>,
+        json: bool,
+        annotations: AnnotationFormat,
+        annotations_file: PathBuf,
+    ) -> Result<()> {
+        // Findings are reported on the config file
+        let annotations_default_file = config_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("asb.config.json"));
+        let configs: Vec<BuildConfig> = BuildConfig::load_configs(config_file, None)?
+            .configs
+            .into_iter()
+            .filter(|c| packages.is_empty() || packages.contains(&c.package_name))
+            .collect();
+        if configs.is_empty() {
+            anyhow::bail!("No matching packages found for: {}", packages.join(", "));
+        }
+
+        let mut reports = Vec::new();
+        for config in &configs {
+            let tokens = extract_tokens(config)?;
+            reports.push(LintReport {
+                package_name: config.package_name.clone(),
+                flavor: config.flavor.clone(),
+                config: lint_config(config)?,
+                contrast: check_contrast(&tokens, config.contrast.as_ref()),
+            });
+        }
+        let violations: usize = reports.iter().map(|r| r.contrast.violations.len()).sum();
+
+        let findings: Vec<_> = reports
+            .iter()
+            .flat_map(|report| {
+                lint_annotations(
+                    &report.package_name,
+                    &annotations_default_file,
+                    &report.config,
+                    &report.contrast.violations,
+                )
+            })
+            .collect();
+        annotations::emit(
+            annotations,
+            &findings,
+            &annotations_default_file,
+            &annotations_file,
+            json,
+        )?;
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        } else {
+            for report in &reports {
+                let name = match &report.flavor {
+                    Some(flavor) => format!("{} ({})", report.package_name, flavor),
+                    None => report.package_name.clone(),
+                };
+                for finding in &report.config {
+                    println!(
+                        "{} {}: {} {}",
+                        "⚠".yellow(),
+                        name,
+                        finding.field,
+                        finding.message.yellow()
                     );
                 }
-                let summary = serde_json::json!({
-                    "total": original_configs.len(),
-                    "successful": success_count,
-                    "failed": fail_count,
-                    "total_time_secs": elapsed.as_secs_f64(),
-                    "results": results_map,
-                });
-                println!("{}", serde_json::to_string_pretty(&summary)?);
-                if fail_count > 0 {
-                    std::process::exit(1);
+                let contrast = &report.contrast;
+                if contrast.violations.is_empty() {
+                    println!(
+                        "{} {}: {} color pairs checked",
+                        "✓".green(),
+                        name,
+                        contrast.checked
+                    );
+                    continue;
+                }
+                println!(
+                    "{} {}: {} of {} color pairs below the minimum contrast",
+                    "✗".red(),
+                    name,
+                    contrast.violations.len(),
+                    contrast.checked
+                );
+                for v in &contrast.violations {
+                    println!(
+                        "  [{}] {}: {} {} on {} {} is {:.2}:1, needs {}:1",
+                        v.configuration,
+                        v.scope,
+                        v.foreground,
+                        v.foreground_color,
+                        v.background,
+                        v.background_color,
+                        v.ratio,
+                        v.min_ratio
+                    );
                 }
-                return Ok(());
             }
+        }
 
-            println!("\n{}", "Build Summary:".blue().bold());
-            println!("  {}: {}", "Successful".green(), success_count);
-            println!("  {}: {}", "Failed".red(), fail_count);
-            println!("  {}: {:.2}s", "Total time".cyan(), elapsed.as_secs_f64());
+        if violations > 0 {
+            anyhow::bail!("{} contrast violation(s) found", violations);
+        }
+        Ok(())
+    }
 
-            // Show individual results
-            // Create a mapping from index to package name for display
-            let package_names: std::collections::HashMap<usize, String> = original_configs
+    fn run_list(config_file: Option<PathBuf>, json: bool) -> Result<()> {
+        let package_id_lock = package_id_lock_path(config_file.as_deref());
+        let configs = BuildConfig::load_built_configs(config_file)?;
+        // IDs are only read from the lock here; `asb build` assigns missing ones
+        let locked_ids = if auto_assign_enabled(&configs) {
+            PackageIdLock::load(&package_id_lock)?.package_ids
+        } else {
+            Default::default()
+        };
+        let dependencies = config_dependencies(&configs);
+
+        let summaries: Vec<ConfigSummary> = configs
+            .iter()
+            .enumerate()
+            .map(|(idx, config)| {
+                let package_id = match &config.package_id {
+                    Some(id) => id.clone(),
+                    None if config.auto_assign_package_ids.unwrap_or(false) => locked_ids
+                        .get(&config.package_name)
+                        .cloned()
+                        .unwrap_or_else(|| "auto".to_string()),
+                    None => DEFAULT_PACKAGE_ID.to_string(),
+                };
+                let mut depends_on: Vec<String> = dependencies
+                    .get(&idx)
+                    .into_iter()
+                    .flatten()
+                    .map(|&dep| configs[dep].package_name.clone())
+                    .collect();
+                depends_on.sort();
+                depends_on.dedup();
+                ConfigSummary {
+                    target: config.target(),
+                    package_name: config.package_name.clone(),
+                    flavor: config.flavor.clone(),
+                    resource_dirs: std::iter::once(config.resource_dir.clone())
+                        .chain(config.additional_resource_dirs.iter().flatten().cloned())
+                        .collect(),
+                    output: config.output_apk_path(),
+                    package_id,
+                    depends_on,
+                    aar_files: config
+                        .aar_files
+                        .iter()
+                        .flatten()
+                        .map(|aar| aar.path().to_path_buf())
+                        .collect(),
+                    precompiled_dependencies: config
+                        .precompiled_dependencies
+                        .clone()
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&summaries)?);
+            return Ok(());
+        }
+
+        let join = |paths: &[PathBuf]| {
+            paths
                 .iter()
-                .enumerate()
-                .map(|(idx, cfg)| (idx, cfg.package_name.clone()))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let header = [
+            "TARGET",
+            "PACKAGE",
+            "FLAVOR",
+            "PACKAGE ID",
+            "OUTPUT",
+            "RESOURCES",
+            "DEPENDENCIES",
+        ];
+        let rows: Vec<[String; 7]> = summaries
+            .iter()
+            .map(|summary| {
+                let dependencies: Vec<String> = summary
+                    .depends_on
+                    .iter()
+                    .cloned()
+                    .chain(
+                        summary
+                            .aar_files
+                            .iter()
+                            .chain(&summary.precompiled_dependencies)
+                            .map(|p| p.display().to_string()),
+                    )
+                    .collect();
+                [
+                    summary.target.clone(),
+                    summary.package_name.clone(),
+                    summary.flavor.clone().unwrap_or_else(|| "-".to_string()),
+                    summary.package_id.clone(),
+                    summary.output.display().to_string(),
+                    join(&summary.resource_dirs),
+                    if dependencies.is_empty() {
+                        "-".to_string()
+                    } else {
+                        dependencies.join(", ")
+                    },
+                ]
+            })
+            .collect();
+        let widths: Vec<usize> = (0..header.len())
+            .map(|col| {
+                rows.iter()
+                    .map(|row| row[col].chars().count())
+                    .chain(std::iter::once(header[col].len()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let format_row = |cells: Vec<&str>| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+        println!("{}", format_row(header.to_vec()).bold());
+        for row in &rows {
+            println!("{}", format_row(row.iter().map(String::as_str).collect()));
+        }
+        Ok(())
+    }
+
+    fn run_config(command: ConfigCommand) -> Result<()> {
+        match command {
+            ConfigCommand::Resolve(args) => Self::run_config_resolve(args),
+            ConfigCommand::Migrate { files, check } => Self::run_config_migrate(files, check),
+        }
+    }
+
+    fn run_config_resolve(args: ResolveArgs) -> Result<()> {
+        let ResolveArgs {
+            config: config_file,
+            package,
+            as_of,
+            strict,
+            overrides,
+            json,
+        } = args;
+        if let Some(as_of) = &as_of {
+            schedule::parse_date(as_of).context("Invalid --as-of")?;
+        }
+        let user_defaults = match UserDefaults::path() {
+            Some(path) => UserDefaults::load(&path)?,
+            None => UserDefaults::default(),
+        };
+        let layers = config_layers(config_file.as_deref())?;
+        let configs = BuildConfig::load_configs_as_of(config_file, None, as_of.as_deref())?.configs;
+        if layers.len() != configs.len() {
+            anyhow::bail!("Config file changed while it was being resolved");
+        }
+
+        let mut resolved = Vec::new();
+        for (mut config, layers) in configs.into_iter().zip(&layers) {
+            if package
+                .as_ref()
+                .is_some_and(|package| *package != config.package_name)
+            {
+                continue;
+            }
+            // Same order as `asb build`: CLI overrides, strict mode, then the output layout
+            let mut cli: BTreeSet<&str> = overrides.apply(&mut config).into_iter().collect();
+            if as_of.is_some() {
+                cli.insert("asOf");
+            }
+            if strict {
+                config.strict = Some(true);
+                config.apply_strict();
+                cli.insert("strict");
+            }
+            let (output_dir, build_dir) = (config.output_dir.clone(), config.build_dir.clone());
+            config.apply_output_layout()?;
+            let mut layout = BTreeSet::new();
+            if config.output_dir != output_dir {
+                layout.insert("outputDir");
+            }
+            if config.build_dir != build_dir {
+                layout.insert("buildDir");
+            }
+
+            let resolver = FieldResolver {
+                layers,
+                user_defaults: &user_defaults,
+                cli: &cli,
+                layout: &layout,
+            };
+            let fields = resolver.resolve(&config)?;
+            resolved.push((config, fields));
+        }
+        if let Some(package) = &package
+            && resolved.is_empty()
+        {
+            anyhow::bail!("No configuration found for package {}", package);
+        }
+
+        if json {
+            let report: Vec<serde_json::Value> = resolved
+                .iter()
+                .map(|(config, fields)| {
+                    serde_json::json!({
+                        "target": config.target(),
+                        "packageName": config.package_name,
+                        "fields": fields,
+                    })
+                })
                 .collect();
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
 
-            println!("\n{}", "Individual Results:".blue().bold());
-            for (idx, result) in &all_results {
-                let package_name = package_names
-                    .get(idx)
-                    .map(|s| s.as_str())
-                    .unwrap_or("unknown");
-                if result.success {
-                    if let Some(ref apk_path) = result.apk_path {
-                        println!(
-                            "  {} Config #{} [{}]: {} ({:.2}s)",
-                            "✓".green(),
-                            idx + 1,
-                            package_name,
-                            apk_path.display(),
-                            result.build_duration.as_secs_f64()
-                        );
-                    } else {
-                        println!(
-                            "  {} Config #{} [{}] ({:.2}s)",
-                            "✓".green(),
-                            idx + 1,
-                            package_name,
-                            result.build_duration.as_secs_f64()
-                        );
-                    }
+        for (idx, (config, fields)) in resolved.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            println!("{}  {}", config.target().cyan(), config.package_name);
+            let width = fields.iter().map(|f| f.name.len()).max().unwrap_or(0);
+            for field in fields {
+                let value = match &field.value {
+                    serde_json::Value::String(text) => text.clone(),
+                    value => value.to_string(),
+                };
+                println!(
+                    "  {:<width$}  {:<7}  {}",
+                    field.name,
+                    field.source.name(),
+                    value,
+                    width = width
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn run_config_migrate(files: Vec<PathBuf>, check: bool) -> Result<()> {
+        let files = if files.is_empty() {
+            vec![PathBuf::from("asb.config.json")]
+        } else {
+            files
+        };
+        let mut outdated = 0;
+        for file in &files {
+            let content = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let Some(migration) = migrate_config(&content)
+                .with_context(|| format!("Failed to migrate {}", file.display()))?
+            else {
+                println!(
+                    "{} is up to date (configVersion {})",
+                    file.display(),
+                    CONFIG_VERSION
+                );
+                continue;
+            };
+            outdated += 1;
+            let name = file.display().to_string();
+            for line in unified_diff(&content, &migration.content, &name, &name).lines() {
+                if line.starts_with("@@") {
+                    println!("{}", line.cyan());
+                } else if line.starts_with('+') {
+                    println!("{}", line.green());
+                } else if line.starts_with('-') {
+                    println!("{}", line.red());
                 } else {
-                    println!(
-                        "  {} Config #{} [{}]: Build failed ({:.2}s)",
-                        "✗".red(),
-                        idx + 1,
-                        package_name,
-                        result.build_duration.as_secs_f64()
+                    println!("{}", line);
+                }
+            }
+            for change in &migration.changes {
+                println!("  {}", change);
+            }
+            if check {
+                continue;
+            }
+            std::fs::write(file, &migration.content)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+            println!(
+                "{} Migrated {} from configVersion {} to {}",
+                "✓".green(),
+                file.display(),
+                migration.from_version,
+                CONFIG_VERSION
+            );
+        }
+        if check && outdated > 0 {
+            println!(
+                "{} of {} config file(s) need `asb config migrate`",
+                outdated,
+                files.len()
+            );
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+
+    fn run_targets(config_file: Option<PathBuf>, json: bool) -> Result<()> {
+        let configs = BuildConfig::load_configs(config_file, None)?.configs;
+        if json {
+            let targets: Vec<serde_json::Value> = configs
+                .iter()
+                .map(|c| {
+                    serde_json::json!({
+                        "target": c.target(),
+                        "packageName": c.package_name,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&targets)?);
+        } else {
+            for config in &configs {
+                println!("{}  {}", config.target().cyan(), config.package_name);
+            }
+        }
+        Ok(())
+    }
+
+    fn run_tokens(
+        config_file: Option<PathBuf>,
+        packages: Vec<String>,
+        output: Option<PathBuf>,
+    ) -> Result<()> {
+        let configs: Vec<BuildConfig> = BuildConfig::load_configs(config_file, None)?
+            .configs
+            .into_iter()
+            .filter(|c| packages.is_empty() || packages.contains(&c.package_name))
+            .collect();
+        if configs.is_empty() {
+            anyhow::bail!("No matching packages found for: {}", packages.join(", "));
+        }
+
+        let reports = configs
+            .iter()
+            .map(extract_tokens)
+            .collect::<Result<Vec<_>>>()?;
+
+        match output {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir)?;
+                for report in &reports {
+                    let file_name = match &report.flavor {
+                        Some(flavor) => format!("{}-{}.tokens.json", report.package_name, flavor),
+                        None => format!("{}.tokens.json", report.package_name),
+                    };
+                    let path = dir.join(file_name);
+                    std::fs::write(&path, serde_json::to_string_pretty(report)? + "\n")?;
+                    println!(
+                        "{} {} ({} colors, {} styles) -> {}",
+                        "✓".green(),
+                        report.package_name,
+                        report.colors.len(),
+                        report.styles.len(),
+                        path.display()
                     );
-                    for error in &result.errors {
-                        println!("      - {}", error);
-                    }
-                    // Save failure log
-                    if let Some(_config) = original_configs.get(*idx) {
-                        match Self::save_failure_log(package_name, &result.errors, None) {
-                            Ok(log_path) => {
-                                println!(
-                                    "      {}: {}",
-                                    "Log saved to".yellow(),
-                                    log_path.display()
-                                );
-                            }
-                            Err(e) => {
-                                warn!("Failed to save error log for {}: {}", package_name, e);
-                            }
-                        }
-                    }
                 }
             }
+            None => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
 
-            if fail_count > 0 {
-                std::process::exit(1);
-            }
+    fn run_freeze(
+        config_file: Option<PathBuf>,
+        packages: Vec<String>,
+        aapt2: Option<PathBuf>,
+    ) -> Result<()> {
+        let configs: Vec<BuildConfig> = BuildConfig::load_built_configs(config_file)?
+            .into_iter()
+            .filter(|c| packages.is_empty() || packages.contains(&c.package_name))
+            .collect();
+        if configs.is_empty() {
+            anyhow::bail!("No matching packages found for: {}", packages.join(", "));
         }
 
+        for config in &configs {
+            let Some(freeze_file) = &config.freeze_file else {
+                anyhow::bail!(
+                    "freezeFile is not set for {}; set it to the file the frozen IDs go to",
+                    config.package_name
+                );
+            };
+            let package = config.output_apk_path();
+            if !package.is_file() {
+                anyhow::bail!(
+                    "{} is not built: {} not found, build the released skin first",
+                    config.package_name,
+                    package.display()
+                );
+            }
+            let aapt2 = Aapt2::new(aapt2.clone().or_else(|| config.aapt2_path.clone()))?;
+            let ids = ids_from_dump(&aapt2.dump_resources(&package)?);
+            write_ids(freeze_file, &config.package_name, &ids)?;
+            println!(
+                "{} {}: {} resources frozen in {}",
+                "✓".green(),
+                config.package_name,
+                ids.len(),
+                freeze_file.display()
+            );
+        }
         Ok(())
     }
 
-    async fn build_single_config(config: BuildConfig) -> Result<crate::types::BuildResult> {
-        let mut builder = SkinBuilder::new(config)?;
-        builder.build().await
+    #[allow(clippy::too_many_arguments)]
+    fn run_convert(
+        inputs: Vec<PathBuf>,
+        to: ResourceFormat,
+        output: Option<PathBuf>,
+        config_file: Option<PathBuf>,
+        package: Option<String>,
+        no_hooks: bool,
+        aapt2: Option<PathBuf>,
+    ) -> Result<()> {
+        if inputs.len() > 1 && output.as_ref().is_some_and(|o| !o.is_dir()) {
+            anyhow::bail!(
+                "--output must be an existing directory when converting several packages"
+            );
+        }
+        let config = match config_file {
+            Some(config_file) => {
+                let mut configs: Vec<BuildConfig> =
+                    BuildConfig::load_configs(Some(config_file), None)?
+                        .configs
+                        .into_iter()
+                        .filter(|c| package.as_ref().is_none_or(|p| &c.package_name == p))
+                        .collect();
+                match (configs.len(), &package) {
+                    (1, _) => Some(configs.remove(0)),
+                    (0, Some(package)) => {
+                        anyhow::bail!("No matching packages found for: {}", package)
+                    }
+                    (0, None) => anyhow::bail!("No build configs found"),
+                    _ => anyhow::bail!(
+                        "The config has {} packages; pick the one whose packaging and hooks apply with --package",
+                        configs.len()
+                    ),
+                }
+            }
+            None => None,
+        };
+        let aapt2 =
+            Aapt2::new(aapt2.or_else(|| config.as_ref().and_then(|c| c.aapt2_path.clone())))?;
+
+        for input in &inputs {
+            let converted = converted_path(input, to, output.as_deref());
+            let options = config.as_ref().map(PackageOptions::from_config);
+            convert_package(&aapt2, input, &converted, to, options)?;
+            if let Some(config) = config.as_ref().filter(|_| !no_hooks) {
+                let outcome = run_post_convert_hooks(config, input, &converted);
+                if let Some(error) = outcome.errors.first() {
+                    anyhow::bail!("{}: {}", converted.display(), error);
+                }
+            }
+            println!(
+                "{} {} -> {} ({})",
+                "✓".green(),
+                input.display(),
+                converted.display(),
+                to.name()
+            );
+        }
+        Ok(())
     }
 
-    fn save_failure_log(
-        package_name: &str,
-        errors: &[String],
-        base_dir: Option<&Path>,
-    ) -> Result<PathBuf> {
-        use std::io::Write;
-
-        // Create logs directory under base_dir (or cwd if None)
-        let base = base_dir.unwrap_or_else(|| Path::new("."));
-        let logs_dir = base.join("logs");
-        std::fs::create_dir_all(&logs_dir)?;
-
-        // Generate log file name with timestamp
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-        let log_filename = format!("build_failure_{}_{}.log", package_name, timestamp);
-        let log_path = logs_dir.join(&log_filename);
+    fn run_import(
+        input: PathBuf,
+        output: PathBuf,
+        android_jar: Option<PathBuf>,
+        force: bool,
+    ) -> Result<()> {
+        let summary = import_package(&input, &output, android_jar.as_deref(), force)?;
+        println!(
+            "{} Imported {} into {}: {} values, {} files, {} assets",
+            "✓".green(),
+            summary.package_name.blue().bold(),
+            output.display(),
+            summary.values,
+            summary.files,
+            summary.assets
+        );
+        for skipped in &summary.skipped {
+            println!("  {} {}", "skipped".yellow(), skipped);
+        }
+        println!(
+            "\n{}\n  asb build -c {}",
+            "Build it with:".cyan(),
+            summary.config_path.display()
+        );
+        Ok(())
+    }
 
-        // Write log content
-        let mut log_file = std::fs::File::create(&log_path)?;
-        writeln!(log_file, "Build Failure Log")?;
-        writeln!(log_file, "==================")?;
-        writeln!(log_file, "Package: {}", package_name)?;
-        writeln!(
-            log_file,
-            "Time: {}",
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-        )?;
-        writeln!(log_file, "\nErrors:")?;
-        writeln!(log_file, "--------")?;
+    fn run_audit(command: AuditCommand) -> Result<()> {
+        let AuditCommand::Show(args) = command;
+        let logs: Vec<PathBuf> = match args.log {
+            Some(log) => vec![log],
+            None => {
+                let mut logs: Vec<PathBuf> = BuildConfig::load_built_configs(args.config)?
+                    .iter()
+                    .map(audit_log_path)
+                    .filter(|path| path.exists())
+                    .collect();
+                logs.sort();
+                logs.dedup();
+                logs
+            }
+        };
+        if logs.is_empty() {
+            anyhow::bail!("No audit log found; enable auditLog in the config or pass --log");
+        }
 
-        for (i, error) in errors.iter().enumerate() {
-            writeln!(log_file, "\n{}. {}", i + 1, error)?;
+        let output_hash = match &args.file {
+            Some(file) => Some(crate::fingerprint::sha256(
+                &std::fs::read(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?,
+            )),
+            None => args.hash,
+        };
+        let filter = AuditFilter {
+            package: args.package,
+            since: args
+                .since
+                .as_deref()
+                .map(schedule::parse_date)
+                .transpose()?,
+            output_hash,
+            failed_only: args.failed,
+        };
+        let mut records = Vec::new();
+        for log in &logs {
+            records.extend(audit::read_records(log)?);
+        }
+        records.retain(|record| filter.matches(record));
+        records.sort_by_key(|r| chrono::DateTime::parse_from_rfc3339(&r.timestamp).ok());
+        if let Some(last) = args.last {
+            records.drain(..records.len().saturating_sub(last));
         }
 
-        Ok(log_path)
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&records)?);
+            return Ok(());
+        }
+        if records.is_empty() {
+            println!("{}", "No matching builds".yellow());
+            return Ok(());
+        }
+        for record in &records {
+            let status = if record.success {
+                "✓".green()
+            } else {
+                "✗".red()
+            };
+            println!(
+                "{} {} {} {} by {} (config {}, asb {})",
+                status,
+                record.timestamp,
+                record.package.blue().bold(),
+                record.target,
+                record.user,
+                &record.config_hash[..record.config_hash.len().min(12)],
+                record.asb_version
+            );
+            if let Some(vcs) = &record.vcs {
+                println!(
+                    "    commit {}{}",
+                    vcs.commit,
+                    if vcs.dirty { " (dirty)" } else { "" }
+                );
+            }
+            for (path, hash) in &record.outputs {
+                println!("    {} {}", hash, path);
+            }
+        }
+        Ok(())
     }
 
-    fn run_clean(config_file: Option<PathBuf>, output_dir: Option<PathBuf>) -> Result<()> {
-        let output = if let Some(config_path) = config_file {
-            let content = std::fs::read_to_string(&config_path)?;
-            let config: BuildConfig = serde_json::from_str(&content)?;
-            config.output_dir
-        } else if let Some(o) = output_dir {
-            o
-        } else {
-            error!("Please provide either --config or --output");
-            std::process::exit(1);
+    fn run_snapshot(command: SnapshotCommand) -> Result<()> {
+        let (args, verify) = match command {
+            SnapshotCommand::Record(args) => (args, false),
+            SnapshotCommand::Verify(args) => (args, true),
         };
+        let configs: Vec<BuildConfig> = BuildConfig::load_built_configs(args.config)?
+            .into_iter()
+            .filter(|c| args.packages.is_empty() || args.packages.contains(&c.package_name))
+            .collect();
+        if configs.is_empty() {
+            anyhow::bail!(
+                "No matching packages found for: {}",
+                args.packages.join(", ")
+            );
+        }
 
-        let compiled_dir = output.join("compiled");
-        let temp_dir = output.join(".temp");
-        let cache_dir = output.join(".build-cache");
+        let mut mismatched = 0;
+        for config in &configs {
+            let (name, file_name) = match &config.flavor {
+                Some(flavor) => (
+                    format!("{} ({})", config.package_name, flavor),
+                    format!("{}-{}.snapshot.json", config.package_name, flavor),
+                ),
+                None => (
+                    config.package_name.clone(),
+                    format!("{}.snapshot.json", config.package_name),
+                ),
+            };
+            let package = config.output_apk_path();
+            if !package.is_file() {
+                anyhow::bail!(
+                    "{} is not built: {} not found, run asb build first",
+                    name,
+                    package.display()
+                );
+            }
+            let aapt2 = Aapt2::new(args.aapt2.clone().or_else(|| config.aapt2_path.clone()))?;
+            let snapshot = Snapshot::capture(&package, &aapt2)?;
+            let snapshot_file = args.dir.join(file_name);
 
-        if compiled_dir.exists() {
-            std::fs::remove_dir_all(&compiled_dir)?;
-        }
-        if temp_dir.exists() {
-            std::fs::remove_dir_all(&temp_dir)?;
+            if !verify {
+                snapshot.write(&snapshot_file)?;
+                println!(
+                    "{} {}: {} resources, {} files recorded in {}",
+                    "✓".green(),
+                    name,
+                    snapshot.resources.len(),
+                    snapshot.files.len(),
+                    snapshot_file.display()
+                );
+                continue;
+            }
+
+            let recorded = Snapshot::load(&snapshot_file)?;
+            let changes = recorded.diff(&snapshot);
+            if changes.is_empty() {
+                println!(
+                    "{} {}: matches {}",
+                    "✓".green(),
+                    name,
+                    snapshot_file.display()
+                );
+                continue;
+            }
+            mismatched += 1;
+            println!(
+                "{} {}: {} change(s) from {}",
+                "✗".red(),
+                name,
+                changes.len(),
+                snapshot_file.display()
+            );
+            for change in &changes {
+                let line = match change.chars().next() {
+                    Some('+') => change.green(),
+                    Some('-') => change.red(),
+                    _ => change.yellow(),
+                };
+                println!("  {}", line);
+            }
         }
-        if cache_dir.exists() {
-            std::fs::remove_dir_all(&cache_dir)?;
+        if mismatched > 0 {
+            anyhow::bail!(
+                "{} package(s) differ from their snapshots; run asb snapshot record to accept the changes",
+                mismatched
+            );
         }
+        Ok(())
+    }
 
-        println!("{}", "✓ Build artifacts cleaned".green());
+    fn run_merge(command: MergeCommand) -> Result<()> {
+        match command {
+            MergeCommand::Verify { bundle } => match SkinMerger::verify(&bundle) {
+                Ok(index) => {
+                    println!(
+                        "{} {}: {} modules verified (format version {})",
+                        "✓".green(),
+                        bundle.display(),
+                        index.modules.len(),
+                        index.version
+                    );
+                    for module in &index.modules {
+                        println!("  {} ({} bytes)", module.module_name, module.size);
+                    }
+                }
+                Err(e) => {
+                    println!("{} {}: {:#}", "✗".red(), bundle.display(), e);
+                    std::process::exit(1);
+                }
+            },
+            MergeCommand::List { bundle, json } => {
+                let index = SkinMerger::list_modules(&bundle)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&index)?);
+                    return Ok(());
+                }
+                println!(
+                    "{} (format version {}, {} modules)",
+                    bundle.display(),
+                    index.version,
+                    index.modules.len()
+                );
+                for module in &index.modules {
+                    println!(
+                        "  {}  {} bytes at offset {}  {}",
+                        module.module_name,
+                        module.size,
+                        module.offset,
+                        module.sha256.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+            MergeCommand::Extract {
+                bundle,
+                output,
+                module,
+            } => {
+                let packages = match module {
+                    Some(module) => vec![SkinMerger::extract_module(&bundle, &module, &output)?],
+                    None => SkinMerger::extract_modules(&bundle, &output)?,
+                };
+                for package in packages {
+                    println!(
+                        "{} {} -> {}",
+                        "✓".green(),
+                        package.module_name,
+                        package.apk_path.display()
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
-    fn run_version(aapt2_path: Option<PathBuf>) -> Result<()> {
-        let aapt2 = Aapt2::new(aapt2_path)?;
-        let version = aapt2.version()?;
-        println!("{}", "aapt2 version:".cyan());
-        println!("{}", version);
+    fn run_strings(command: StringsCommand) -> Result<()> {
+        match command {
+            StringsCommand::Export {
+                config,
+                output,
+                format,
+                locales,
+                source_language,
+            } => {
+                let res_dirs = Self::translation_res_dirs(config)?;
+                let files = collect_translations(&res_dirs, &locales)?;
+                if files.is_empty() {
+                    println!(
+                        "{}",
+                        "No values-<locale> directories found, use --locales to choose locales"
+                            .yellow()
+                    );
+                    return Ok(());
+                }
+
+                std::fs::create_dir_all(&output)?;
+                for file in &files {
+                    let path = output.join(format!("{}.{}", file.locale, format.extension()));
+                    file.write(format, &source_language, &path)?;
+                    let translated = file.units.iter().filter(|u| u.target.is_some()).count();
+                    println!(
+                        "{} {} ({}/{} translated) -> {}",
+                        "✓".green(),
+                        file.locale,
+                        translated,
+                        file.units.len(),
+                        path.display()
+                    );
+                }
+            }
+            StringsCommand::Import { config, files } => {
+                let res_dirs = Self::translation_res_dirs(config)?;
+                for path in files {
+                    let file = TranslationFile::read(&path)?;
+                    let written = import_translations(&file, &res_dirs)?;
+                    println!(
+                        "{} {}: {} values written from {}",
+                        "✓".green(),
+                        file.locale,
+                        written,
+                        path.display()
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Resource dirs of every config, each listed once even when shared between skins
+    fn translation_res_dirs(config_file: Option<PathBuf>) -> Result<Vec<PathBuf>> {
+        let mut res_dirs: Vec<PathBuf> = Vec::new();
+        for config in BuildConfig::load_configs(config_file, None)?.configs {
+            let dirs = std::iter::once(config.resource_dir)
+                .chain(config.additional_resource_dirs.into_iter().flatten());
+            for dir in dirs {
+                if dir.is_dir() && !res_dirs.contains(&dir) {
+                    res_dirs.push(dir);
+                }
+            }
+        }
+        if res_dirs.is_empty() {
+            anyhow::bail!("No resource directories found in the configuration");
+        }
+        Ok(res_dirs)
+    }
+
     fn run_init(dir: PathBuf) -> Result<()> {
         let config_path = dir.join("asb.config.json");
 
@@ -1002,6 +3672,205 @@ impl Cli {
 mod tests {
     use super::*;
 
+    // ==================== environment variable tests ====================
+
+    #[test]
+    fn test_build_args_from_environment() {
+        // SAFETY: no other test parses the CLI or reads these variables
+        unsafe {
+            std::env::set_var("ASB_PACKAGE", "com.env.skin");
+            std::env::set_var("ASB_MAX_PARALLEL_BUILDS", "3");
+            std::env::set_var("ASB_PACKAGES", "com.a,com.b");
+            std::env::set_var("ASB_NON_INTERACTIVE", "true");
+        }
+        let cli = Cli::try_parse_from(["asb", "build", "--package", "com.cli.skin"]).unwrap();
+        unsafe {
+            for name in [
+                "ASB_PACKAGE",
+                "ASB_MAX_PARALLEL_BUILDS",
+                "ASB_PACKAGES",
+                "ASB_NON_INTERACTIVE",
+            ] {
+                std::env::remove_var(name);
+            }
+        }
+
+        assert!(cli.non_interactive);
+        assert!(!cli.no_color);
+        let Commands::Build(args) = cli.command else {
+            panic!("expected the build command");
+        };
+        // Command line arguments win over the environment
+        assert_eq!(args.package.as_deref(), Some("com.cli.skin"));
+        assert_eq!(args.max_parallel_builds, Some(3));
+        assert_eq!(args.packages, vec!["com.a", "com.b"]);
+    }
+
+    #[test]
+    fn test_build_args_targets() {
+        let cli =
+            Cli::try_parse_from(["asb", "build", ":brandA:night", ":*:day", "--json"]).unwrap();
+        let Commands::Build(args) = cli.command else {
+            panic!("expected the build command");
+        };
+        assert_eq!(args.targets, vec![":brandA:night", ":*:day"]);
+        assert!(args.json);
+    }
+
+    // ==================== params file tests ====================
+
+    #[test]
+    fn test_expand_param_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let params = temp_dir.path().join("params.txt");
+        std::fs::write(
+            &params,
+            "# generated by the rule\npackage=com.example.skin\naar=a.aar\nincremental=true\n\naar=b.aar\nfollow-symlinks=false\nresource-dir=res\n",
+        )
+        .unwrap();
+
+        let args: Vec<OsString> = vec![
+            "asb".into(),
+            "-q".into(),
+            "build".into(),
+            format!("@{}", params.display()).into(),
+            "--json".into(),
+        ];
+        let cli = Cli::try_parse_from(expand_param_files(args).unwrap()).unwrap();
+        assert!(cli.quiet);
+        let Commands::Build(args) = cli.command else {
+            panic!("expected the build command");
+        };
+        assert_eq!(args.package.as_deref(), Some("com.example.skin"));
+        assert_eq!(
+            args.overrides.aar,
+            vec![PathBuf::from("a.aar"), PathBuf::from("b.aar")]
+        );
+        assert_eq!(args.overrides.resource_dir, Some(PathBuf::from("res")));
+        assert!(args.overrides.incremental && args.json && !args.overrides.follow_symlinks);
+
+        for (content, expected) in [
+            ("package com.example.skin", "Expected key=value at"),
+            ("colour=red", "Unknown option 'colour'"),
+            ("package=a\npackage=b", "set more than once"),
+            ("incremental=yes", "must be true or false"),
+        ] {
+            std::fs::write(&params, content).unwrap();
+            let args = vec![
+                "asb".into(),
+                "build".into(),
+                format!("@{}", params.display()).into(),
+            ];
+            let err = expand_param_files(args).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}", err);
+        }
+    }
+
+    // ==================== read-side subcommand tests ====================
+
+    /// Write a config for `com.example.skin` (flavor night) building into `<dir>/build`
+    /// with the given `outputLayout`
+    fn write_layout_config(dir: &tempfile::TempDir, layout: &str) -> PathBuf {
+        let path = dir.path().join("asb.config.json");
+        std::fs::write(
+            &path,
+            serde_json::json!({
+                "resourceDir": dir.path().join("res"),
+                "manifestPath": dir.path().join("AndroidManifest.xml"),
+                "outputDir": dir.path().join("build"),
+                "outputLayout": layout,
+                "packageName": "com.example.skin",
+                "flavor": "night"
+            })
+            .to_string(),
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_audit_show_reads_log_from_laid_out_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_layout_config(&dir, "gradle");
+        let config_arg = config_path.to_str().unwrap();
+        let audit_show = || {
+            let cli =
+                Cli::try_parse_from(["asb", "audit", "show", "--config", config_arg]).unwrap();
+            let Commands::Audit { command } = cli.command else {
+                panic!("expected the audit command");
+            };
+            Cli::run_audit(command)
+        };
+
+        // A log at the top of outputDir is not where `asb build` writes it
+        std::fs::create_dir_all(dir.path().join("build")).unwrap();
+        std::fs::write(dir.path().join("build").join(audit::AUDIT_LOG_FILE), "").unwrap();
+        let err = audit_show().unwrap_err();
+        assert!(err.to_string().contains("No audit log found"), "{}", err);
+
+        let laid_out = dir.path().join("build/outputs/skin/night/release");
+        std::fs::create_dir_all(&laid_out).unwrap();
+        std::fs::write(laid_out.join(audit::AUDIT_LOG_FILE), "").unwrap();
+        audit_show().unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_looks_for_package_in_laid_out_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_layout_config(&dir, "gradle");
+        let cli = Cli::try_parse_from([
+            "asb",
+            "snapshot",
+            "record",
+            "--config",
+            config_path.to_str().unwrap(),
+        ])
+        .unwrap();
+        let Commands::Snapshot { command } = cli.command else {
+            panic!("expected the snapshot command");
+        };
+        let err = Cli::run_snapshot(command).unwrap_err().to_string();
+        let expected = dir.path().join("build/outputs/skin/night/release");
+        assert!(err.contains(&expected.display().to_string()), "{}", err);
+    }
+
+    #[test]
+    fn test_freeze_looks_for_package_in_laid_out_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_layout_config(&dir, "gradle");
+        let mut config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&config_path).unwrap()).unwrap();
+        config["freezeFile"] = dir.path().join("ids.json").to_str().unwrap().into();
+        std::fs::write(&config_path, config.to_string()).unwrap();
+
+        let err = Cli::run_freeze(Some(config_path), Vec::new(), None)
+            .unwrap_err()
+            .to_string();
+        let expected = dir.path().join("build/outputs/skin/night/release");
+        assert!(err.contains(&expected.display().to_string()), "{}", err);
+    }
+
+    #[test]
+    fn test_audit_show_reads_log_from_templated_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_layout_config(&dir, "{brand}/{flavor}/");
+        let config_arg = config_path.to_str().unwrap();
+        let audit_show = || {
+            let cli =
+                Cli::try_parse_from(["asb", "audit", "show", "--config", config_arg]).unwrap();
+            let Commands::Audit { command } = cli.command else {
+                panic!("expected the audit command");
+            };
+            Cli::run_audit(command)
+        };
+        assert!(audit_show().is_err());
+
+        let laid_out = dir.path().join("build/com.example.skin/night");
+        std::fs::create_dir_all(&laid_out).unwrap();
+        std::fs::write(laid_out.join(audit::AUDIT_LOG_FILE), "").unwrap();
+        audit_show().unwrap();
+    }
+
     // ==================== save_failure_log tests ====================
 
     #[test]
@@ -1167,6 +4036,7 @@ mod tests {
                 "outputDir": "./build",
                 "androidJar": "/sdk/android.jar",
                 "maxParallelBuilds": 4,
+                "maxParallelLinks": 2,
                 "apps": [
                     {
                         "baseDir": "./app1/src/main",
@@ -1185,6 +4055,7 @@ mod tests {
         assert_eq!(loaded.configs[0].package_name, "com.example.app1");
         assert_eq!(loaded.configs[1].package_name, "com.example.app2");
         assert_eq!(loaded.max_parallel_builds, Some(4));
+        assert_eq!(loaded.max_parallel_links, Some(2));
         // Common output_dir should be applied
         assert_eq!(loaded.configs[0].output_dir, PathBuf::from("./build"));
         assert_eq!(loaded.configs[1].output_dir, PathBuf::from("./build"));
@@ -1202,6 +4073,7 @@ mod tests {
                 "versionCode": 10,
                 "versionName": "2.0.0",
                 "packageId": "0x80",
+                "precompiledDependencies": ["./prebuilt/common"],
                 "apps": [
                     {
                         "baseDir": "./skin1/src/main",
@@ -1219,6 +4091,10 @@ mod tests {
         assert_eq!(config.version_name, Some("2.0.0".to_string()));
         assert_eq!(config.package_id, Some("0x80".to_string()));
         assert_eq!(config.output_dir, PathBuf::from("./build"));
+        assert_eq!(
+            config.precompiled_dependencies,
+            Some(vec![PathBuf::from("./prebuilt/common")])
+        );
     }
 
     #[test]
@@ -1301,6 +4177,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
             BuildConfig {
                 resource_dir: PathBuf::from("./res2"),
@@ -1322,6 +4249,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
         ];
         let all_package_names: Vec<String> =
@@ -1359,6 +4337,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
             BuildConfig {
                 resource_dir: PathBuf::from("./res2"),
@@ -1380,6 +4409,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
         ];
         let packages: Vec<String> = vec!["com.example.app1".to_string()];
@@ -1413,6 +4493,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
             BuildConfig {
                 resource_dir: PathBuf::from("./res2"),
@@ -1434,6 +4565,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
         ];
         let packages: Vec<String> = vec!["com.a".to_string(), "com.b".to_string()];
@@ -1480,13 +4662,16 @@ mod tests {
             config.version_code = Some(vc);
         }
         if !aar.is_empty() {
-            config.aar_files = Some(aar.clone());
+            config.aar_files = Some(aar.iter().cloned().map(AarFile::from).collect());
         }
 
         assert_eq!(config.resource_dir, PathBuf::from("./custom_res"));
         assert_eq!(config.package_name, "com.custom.pkg");
         assert_eq!(config.version_code, Some(42));
-        assert_eq!(config.aar_files, Some(vec![PathBuf::from("./lib.aar")]));
+        assert_eq!(
+            config.aar_files,
+            Some(vec![AarFile::from(PathBuf::from("./lib.aar"))])
+        );
     }
 
     #[test]
@@ -1531,6 +4716,57 @@ mod tests {
                 package_id: None,
                 precompiled_dependencies: None,
                 assets_dir: None,
+                follow_symlinks: None,
+                respect_gitignore: None,
+                packaging: None,
+                link_input_mode: None,
+                compile_options: None,
+                static_lib_dependencies: None,
+                precompiled_common_deps: None,
+                namespaced: None,
+                auto_assign_package_ids: None,
+                manifest_mode: None,
+                manifest_placeholders: None,
+                merge_aar_manifests: None,
+                output_layout: None,
+                flavor: None,
+                build_type: None,
+                vcs: None,
+                version_code_strategy: None,
+                design_tokens: None,
+                dark_mode: None,
+                resource_prefix: None,
+                aliases: None,
+                validate_references: None,
+                auto_fix_filenames: None,
+                locale_qualifiers: None,
+                launcher_icons: None,
+                contrast: None,
+                validate_xml: None,
+                convert_encodings: None,
+                golden_images: None,
+                freeze_file: None,
+                host_versions: None,
+                feature_flags: None,
+                scheduled_files: None,
+                as_of: None,
+                audit_log: None,
+                strict: None,
+                warnings_as_errors: None,
+                size_budget: None,
+                disk_space: None,
+                conditional_resource_dirs: None,
+                stage_resources: None,
+                hooks: None,
+                plugins: None,
+                host_symbols: None,
+                owners: None,
+                only_resource_types: None,
+                app: None,
+                missing_resource_dir_policy: None,
+                aar_filters: None,
+                aapt2_limits: None,
+                heartbeat: None,
             },
         ];
 
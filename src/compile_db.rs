@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::aapt2::Aapt2;
+
+/// File name of the compile database, written to the package's compiled directory
+pub const COMPILE_DB_FILE: &str = "compile_commands.json";
+
+/// How the flat file of a resource was produced in this build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CacheStatus {
+    /// Compiled by aapt2 during this build
+    Compiled,
+    /// Reused from the incremental build cache
+    Cached,
+}
+
+/// One resource file's compilation, in the spirit of clang's `compile_commands.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileCommand {
+    /// Working directory the command runs in
+    pub directory: PathBuf,
+    /// Resource file compiled
+    pub file: PathBuf,
+    /// Flat file produced
+    pub output: PathBuf,
+    /// aapt2 command line that produces `output`, replayable from `directory`
+    pub arguments: Vec<String>,
+    pub cache_status: CacheStatus,
+}
+
+impl CompileCommand {
+    /// Record the compilation of `file` into `output` inside `compiled_dir`
+    pub fn new(
+        aapt2: &Aapt2,
+        file: &Path,
+        output: &Path,
+        compiled_dir: &Path,
+        cache_status: CacheStatus,
+    ) -> Self {
+        Self {
+            directory: std::env::current_dir().unwrap_or_default(),
+            file: file.to_path_buf(),
+            output: output.to_path_buf(),
+            // Sharded flat files are written to a subdirectory of the compiled directory
+            arguments: aapt2.compile_command_line(file, output.parent().unwrap_or(compiled_dir)),
+            cache_status,
+        }
+    }
+}
+
+/// Write the compile database of a build, sorted by resource file
+pub fn write_compile_db(dir: &Path, commands: &mut [CompileCommand]) -> Result<PathBuf> {
+    commands.sort_by(|a, b| a.file.cmp(&b.file));
+    let path = dir.join(COMPILE_DB_FILE);
+    std::fs::write(&path, serde_json::to_string_pretty(commands)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CompileFlags;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_compile_db() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let aapt2 =
+            Aapt2::new(Some(PathBuf::from("/sdk/aapt2")))?.with_compile_options(BTreeMap::from([
+                (
+                    "drawable".to_string(),
+                    CompileFlags {
+                        no_crunch: Some(true),
+                        ..CompileFlags::default()
+                    },
+                ),
+            ]));
+        let out = Path::new("build/compiled/main");
+        let mut commands = vec![
+            CompileCommand::new(
+                &aapt2,
+                Path::new("res/values/colors.xml"),
+                &out.join("values_colors.arsc.flat"),
+                out,
+                CacheStatus::Cached,
+            ),
+            CompileCommand::new(
+                &aapt2,
+                Path::new("res/drawable-hdpi/icon.png"),
+                &out.join("drawable-hdpi_icon.png.flat"),
+                out,
+                CacheStatus::Compiled,
+            ),
+        ];
+        assert_eq!(
+            commands[1].arguments,
+            vec![
+                "/sdk/aapt2",
+                "compile",
+                "--no-crunch",
+                "-o",
+                "build/compiled/main",
+                "res/drawable-hdpi/icon.png"
+            ]
+        );
+
+        let path = write_compile_db(temp_dir.path(), &mut commands)?;
+        let db: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        assert_eq!(db[0]["file"], "res/drawable-hdpi/icon.png");
+        assert_eq!(db[0]["cacheStatus"], "compiled");
+        assert_eq!(
+            db[1]["output"],
+            "build/compiled/main/values_colors.arsc.flat"
+        );
+        assert_eq!(db[1]["cacheStatus"], "cached");
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::path::Path;
+
+use crate::builder::find_resource_files;
+use crate::owners::wildcard_match;
+use crate::types::{BuildConfig, ManifestMode};
+
+/// A config entry the build does not use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigFinding {
+    /// Config key, with the index for entries of a list, e.g. `additionalResourceDirs[1]`
+    pub field: String,
+    pub message: String,
+}
+
+/// Find entries of a loaded config that have no effect on the build
+///
+/// Reports a manifest ignored by the minimal manifest mode, additional resource
+/// directories without resource files (after `.asbignore`), AARs without resources and
+/// `aarFilters` patterns that match none of the AARs.
+pub fn lint_config(config: &BuildConfig) -> Result<Vec<ConfigFinding>> {
+    let mut findings = Vec::new();
+    let mut report =
+        |field: String, message: String| findings.push(ConfigFinding { field, message });
+
+    if config.manifest_mode.unwrap_or_default() == ManifestMode::Minimal
+        && config.manifest_path.is_file()
+    {
+        report(
+            "manifestPath".to_string(),
+            format!(
+                "{} is not used: manifestMode is minimal, set it to user or merge to link \
+                 with this manifest",
+                config.manifest_path.display()
+            ),
+        );
+    }
+
+    for (idx, dir) in config.additional_resource_dirs.iter().flatten().enumerate() {
+        let field = format!("additionalResourceDirs[{}]", idx);
+        if !dir.is_dir() {
+            report(field, format!("{} does not exist", dir.display()));
+        } else if find_resource_files(
+            dir,
+            config.follow_symlinks.unwrap_or(false),
+            config.respect_gitignore.unwrap_or(false),
+            None,
+        )?
+        .is_empty()
+        {
+            report(
+                field,
+                format!("{} contains no resource files", dir.display()),
+            );
+        }
+    }
+
+    let aar_files: Vec<&Path> = config
+        .aar_files
+        .iter()
+        .flatten()
+        .map(|a| a.path())
+        .collect();
+    for (idx, aar) in aar_files.iter().enumerate() {
+        if aar.is_file() && !has_resources(aar)? {
+            report(
+                format!("aarFiles[{}]", idx),
+                format!("{} has no resources under res/", aar.display()),
+            );
+        }
+    }
+    for pattern in config
+        .aar_filters
+        .iter()
+        .flatten()
+        .map(|(pattern, _)| pattern)
+    {
+        let matched = aar_files.iter().any(|aar| {
+            aar.file_name()
+                .is_some_and(|name| wildcard_match(pattern, &name.to_string_lossy()))
+        });
+        if !matched {
+            report(
+                format!("aarFilters.{}", pattern),
+                "matches none of the AAR files".to_string(),
+            );
+        }
+    }
+    Ok(findings)
+}
+
+/// Whether an AAR has a file below `res/`
+fn has_resources(aar: &Path) -> Result<bool> {
+    let file = File::open(aar).with_context(|| format!("Failed to open {}", aar.display()))?;
+    let archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read AAR as ZIP: {}", aar.display()))?;
+    Ok(archive
+        .file_names()
+        .any(|name| name.starts_with("res/") && !name.ends_with('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AarFile, AarResourceFilter};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lint_config_reports_unused_entries() -> Result<()> {
+        let temp = TempDir::new()?;
+        let manifest = temp.path().join("AndroidManifest.xml");
+        std::fs::write(&manifest, "<manifest/>")?;
+        let used = temp.path().join("used");
+        std::fs::create_dir_all(used.join("values"))?;
+        std::fs::write(used.join("values").join("colors.xml"), "<resources/>")?;
+        let empty = temp.path().join("empty");
+        std::fs::create_dir_all(empty.join("values"))?;
+
+        let aar = temp.path().join("lib.aar");
+        let mut zip = zip::ZipWriter::new(File::create(&aar)?);
+        zip.start_file("classes.jar", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"jar")?;
+        zip.finish()?;
+
+        let mut config: BuildConfig = serde_json::from_value(serde_json::json!({
+            "resourceDir": temp.path().join("res"),
+            "manifestPath": manifest,
+            "outputDir": temp.path().join("out"),
+            "packageName": "com.test",
+            "androidJar": temp.path().join("android.jar"),
+            "additionalResourceDirs": [used, empty, temp.path().join("missing")],
+        }))?;
+        config.aar_files = Some(vec![AarFile::Path(aar)]);
+        config.aar_filters = Some(
+            [
+                ("lib.aar".to_string(), AarResourceFilter::default()),
+                ("other-*.aar".to_string(), AarResourceFilter::default()),
+            ]
+            .into(),
+        );
+
+        let fields: Vec<String> = lint_config(&config)?
+            .into_iter()
+            .map(|finding| finding.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "manifestPath",
+                "additionalResourceDirs[1]",
+                "additionalResourceDirs[2]",
+                "aarFiles[0]",
+                "aarFilters.other-*.aar",
+            ]
+        );
+
+        config.manifest_mode = Some(ManifestMode::Merge);
+        assert!(
+            !lint_config(&config)?
+                .iter()
+                .any(|finding| finding.field == "manifestPath")
+        );
+        Ok(())
+    }
+}
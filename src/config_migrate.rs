@@ -0,0 +1,545 @@
+use anyhow::{Context, Result};
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::fmt;
+
+use crate::types::{BuildConfig, CONFIG_VERSION};
+
+/// Fields a single-object or array config keeps per app when it becomes a multi-app
+/// config; the others are shared by every app
+const APP_FIELDS: &[&str] = &[
+    "packageName",
+    "baseDir",
+    "resourceDir",
+    "manifestPath",
+    "additionalResourceDirs",
+    "outputDir",
+    "outputFile",
+    "versionCode",
+    "versionName",
+    "packageId",
+    "assetsDir",
+    "designTokens",
+    "resourcePrefix",
+    "aliases",
+    "hostSymbols",
+    "owners",
+    "freezeFile",
+    "hostVersions",
+    "featureFlags",
+    "conditionalResourceDirs",
+    "scheduledFiles",
+    "strict",
+    "sizeBudget",
+    "aarFilters",
+    "followSymlinks",
+];
+
+/// Lines of unchanged context around each hunk of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// JSON value that keeps the order of object keys, so a migrated file only differs from
+/// the original where the migration changed it
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+impl Serialize for Json {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Number(value) => value.serialize(serializer),
+            Self::String(value) => serializer.serialize_str(value),
+            Self::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Self::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct JsonVisitor;
+
+        impl<'de> Visitor<'de> for JsonVisitor {
+            type Value = Json;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("any JSON value")
+            }
+
+            fn visit_unit<E>(self) -> Result<Json, E> {
+                Ok(Json::Null)
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<Json, E> {
+                Ok(Json::Bool(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Json, E> {
+                Ok(Json::Number(value.into()))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Json, E> {
+                Ok(Json::Number(value.into()))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Json, E> {
+                Ok(serde_json::Number::from_f64(value).map_or(Json::Null, Json::Number))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Json, E> {
+                Ok(Json::String(value.to_string()))
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<Json, E> {
+                Ok(Json::String(value))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Json::Array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+                let mut fields: Vec<(String, Json)> = Vec::new();
+                while let Some((key, value)) = map.next_entry::<String, Json>()? {
+                    // The last of duplicate keys wins, as in serde_json
+                    fields.retain(|(k, _)| *k != key);
+                    fields.push((key, value));
+                }
+                Ok(Json::Object(fields))
+            }
+        }
+
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+/// Migration of one version to the next, recording what it changed
+type MigrationStep = fn(&mut Json, &mut Vec<String>) -> Result<()>;
+
+/// Steps from each version to the next: `MIGRATIONS[0]` upgrades version 1 to 2, and so on
+const MIGRATIONS: &[MigrationStep] = &[to_v2];
+
+/// Version 2: the multi-app format, with `buildDir` instead of the deprecated `cacheDir`
+fn to_v2(json: &mut Json, changes: &mut Vec<String>) -> Result<()> {
+    match json {
+        Json::Array(configs) => {
+            let count = configs.len();
+            *json = to_multi_app(std::mem::take(configs))?;
+            changes.push(format!(
+                "Converted the array of {} configs into apps",
+                count
+            ));
+        }
+        Json::Object(_) if !json.has("apps") => {
+            *json = to_multi_app(vec![std::mem::replace(json, Json::Null)])?;
+            changes.push("Converted the single config into an app".to_string());
+        }
+        Json::Object(_) => {}
+        _ => anyhow::bail!("Config must be a JSON object or array"),
+    }
+
+    let Json::Object(fields) = json else {
+        unreachable!("converted to a multi-app config above");
+    };
+    let has_build_dir = fields.iter().any(|(k, _)| k == "buildDir");
+    if let Some((key, _)) = fields.iter_mut().find(|(k, _)| k == "cacheDir") {
+        if has_build_dir {
+            changes.push(
+                "Kept the deprecated cacheDir because buildDir is set too; remove it to keep \
+                 the cache in buildDir"
+                    .to_string(),
+            );
+        } else {
+            *key = "buildDir".to_string();
+            changes.push("Renamed cacheDir to buildDir".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Multi-app config with one app per single-object config
+///
+/// App fields stay with their app; the other fields must be the same in every config and
+/// become common fields. The first config's `outputDir` becomes the common one.
+fn to_multi_app(configs: Vec<Json>) -> Result<Json> {
+    let configs: Vec<Vec<(String, Json)>> = configs
+        .into_iter()
+        .map(|config| match config {
+            Json::Object(fields) => Ok(fields),
+            _ => anyhow::bail!("Array configs must only contain objects"),
+        })
+        .collect::<Result<_>>()?;
+    let Some(first) = configs.first() else {
+        anyhow::bail!("Config array is empty");
+    };
+    let get = |fields: &[(String, Json)], key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+    let output_dir = get(first, "outputDir").context("Config has no outputDir")?;
+
+    let mut keys: Vec<&String> = Vec::new();
+    for (key, _) in configs.iter().flatten() {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    let mut common = Vec::new();
+    for key in keys {
+        if key == "outputDir" {
+            common.push((key.clone(), output_dir.clone()));
+            continue;
+        }
+        if APP_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = get(first, key);
+        if configs.iter().any(|config| get(config, key) != value) {
+            anyhow::bail!(
+                "{} differs between the configs, but apps of a multi-app config share it; \
+                 make it the same in every config first",
+                key
+            );
+        }
+        common.push((key.clone(), value.unwrap_or(Json::Null)));
+    }
+    let apps = configs
+        .into_iter()
+        .map(|fields| {
+            Json::Object(
+                fields
+                    .into_iter()
+                    .filter(|(key, value)| {
+                        APP_FIELDS.contains(&key.as_str())
+                            && !(key == "outputDir" && *value == output_dir)
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    common.push(("apps".to_string(), Json::Array(apps)));
+    Ok(Json::Object(common))
+}
+
+/// A config file upgraded to the latest schema
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// Version the file was written for
+    pub from_version: u32,
+    /// Content of the migrated file
+    pub content: String,
+    /// What the migration changed, one entry per change
+    pub changes: Vec<String>,
+}
+
+/// Upgrade the content of a config file to [`CONFIG_VERSION`]; `None` when it is current
+///
+/// Fails when the migrated config would not build exactly what the original builds.
+pub fn migrate_config(content: &str) -> Result<Option<Migration>> {
+    let mut json: Json = serde_json::from_str(content).context("Config is not valid JSON")?;
+    let from_version = match json.get("configVersion") {
+        None => 1,
+        Some(Json::Number(n)) => n
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .context("configVersion must be a positive integer")?,
+        Some(_) => anyhow::bail!("configVersion must be a positive integer"),
+    };
+    if from_version > CONFIG_VERSION {
+        anyhow::bail!(
+            "configVersion {} is newer than the latest version {} this asb supports; upgrade asb",
+            from_version,
+            CONFIG_VERSION
+        );
+    }
+    if from_version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let mut changes = Vec::new();
+    for step in &MIGRATIONS[(from_version.max(1) - 1) as usize..] {
+        step(&mut json, &mut changes)?;
+    }
+    if let Json::Object(fields) = &mut json {
+        fields.retain(|(k, _)| k != "configVersion");
+        fields.insert(
+            0,
+            (
+                "configVersion".to_string(),
+                Json::Number(CONFIG_VERSION.into()),
+            ),
+        );
+    }
+
+    let mut migrated = Vec::new();
+    let indent = indent_unit(content);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    json.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut migrated,
+        formatter,
+    ))?;
+    let mut migrated = String::from_utf8(migrated)?;
+    if content.ends_with('\n') {
+        migrated.push('\n');
+    }
+    verify_equivalent(content, &migrated)?;
+    Ok(Some(Migration {
+        from_version,
+        content: migrated,
+        changes,
+    }))
+}
+
+/// Indentation of one level in `content`, two spaces when it is not indented
+fn indent_unit(content: &str) -> String {
+    content
+        .lines()
+        .find_map(|line| {
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty() && trimmed.len() < line.len())
+                .then(|| line[..line.len() - trimmed.len()].to_string())
+        })
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+/// Fail unless `migrated` resolves to the same build configs as `original`
+///
+/// `cacheDir` is compared as `buildDir`, and the app name only as part of the target,
+/// which is the package name either way.
+fn verify_equivalent(original: &str, migrated: &str) -> Result<()> {
+    let resolve = |content: &str| -> Result<(serde_json::Value, Vec<serde_json::Value>)> {
+        let loaded = BuildConfig::configs_from_str(content)?;
+        let settings = serde_json::json!({
+            "maxParallelBuilds": loaded.max_parallel_builds,
+            "maxParallelLinks": loaded.max_parallel_links,
+            "bundle": loaded.bundle,
+        });
+        let configs = loaded
+            .configs
+            .into_iter()
+            .map(|mut config| {
+                if config.build_dir.is_none() {
+                    config.build_dir = config.cache_dir.take();
+                }
+                let target = config.target();
+                config.app = None;
+                let mut value = serde_json::to_value(&config)?;
+                value["target"] = target.into();
+                Ok(value)
+            })
+            .collect::<Result<_>>()?;
+        Ok((settings, configs))
+    };
+    let (settings, configs) = resolve(original)?;
+    let (migrated_settings, migrated_configs) =
+        resolve(migrated).context("Migrated config does not load")?;
+    if settings != migrated_settings {
+        anyhow::bail!("Migration would change maxParallelBuilds, maxParallelLinks or bundle");
+    }
+    if configs.len() != migrated_configs.len() {
+        anyhow::bail!(
+            "Migration would change the number of configs from {} to {}",
+            configs.len(),
+            migrated_configs.len()
+        );
+    }
+    for (config, migrated) in configs.iter().zip(&migrated_configs) {
+        let (Some(fields), Some(migrated_fields)) = (config.as_object(), migrated.as_object())
+        else {
+            continue;
+        };
+        if let Some(field) = fields
+            .keys()
+            .chain(migrated_fields.keys())
+            .find(|key| fields.get(*key) != migrated_fields.get(*key))
+        {
+            anyhow::bail!(
+                "Migration would change {} of {}; migrate this config by hand",
+                field,
+                config["packageName"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Unified diff of two texts, empty when they are equal
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let (n, m) = (old_lines.len(), new_lines.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut ops: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_lines[i] == new_lines[j] {
+            ops.push((' ', old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', old_lines[i]));
+            i += 1;
+        } else {
+            ops.push(('+', new_lines[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+    // Line numbers before each op in the old and new text
+    let mut old_line = vec![0; ops.len() + 1];
+    let mut new_line = vec![0; ops.len() + 1];
+    for (k, (op, _)) in ops.iter().enumerate() {
+        old_line[k + 1] = old_line[k] + usize::from(*op != '+');
+        new_line[k + 1] = new_line[k] + usize::from(*op != '-');
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut next = 0;
+    while next < changed.len() {
+        let start = changed[next].saturating_sub(DIFF_CONTEXT);
+        let mut last = next;
+        while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * DIFF_CONTEXT {
+            last += 1;
+        }
+        let end = (changed[last] + 1 + DIFF_CONTEXT).min(ops.len());
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_line[start] + 1,
+            old_line[end] - old_line[start],
+            new_line[start] + 1,
+            new_line[end] - new_line[start]
+        ));
+        for (op, line) in &ops[start..end] {
+            diff.push_str(&format!("{}{}\n", op, line));
+        }
+        next = last + 1;
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_array_config() -> Result<()> {
+        let original = r#"[
+    {
+        "resourceDir": "./red/res",
+        "manifestPath": "./AndroidManifest.xml",
+        "outputDir": "./build",
+        "packageName": "com.skin.red",
+        "androidJar": "/sdk/android.jar",
+        "cacheDir": "./cache"
+    },
+    {
+        "resourceDir": "./blue/res",
+        "manifestPath": "./AndroidManifest.xml",
+        "outputDir": "./build/blue",
+        "packageName": "com.skin.blue",
+        "androidJar": "/sdk/android.jar",
+        "cacheDir": "./cache"
+    }
+]
+"#;
+        let migration = migrate_config(original)?.unwrap();
+        assert_eq!(migration.from_version, 1);
+        assert_eq!(migration.changes.len(), 2);
+        assert_eq!(
+            migration.content,
+            r#"{
+    "configVersion": 2,
+    "outputDir": "./build",
+    "androidJar": "/sdk/android.jar",
+    "buildDir": "./cache",
+    "apps": [
+        {
+            "resourceDir": "./red/res",
+            "manifestPath": "./AndroidManifest.xml",
+            "packageName": "com.skin.red"
+        },
+        {
+            "resourceDir": "./blue/res",
+            "manifestPath": "./AndroidManifest.xml",
+            "outputDir": "./build/blue",
+            "packageName": "com.skin.blue"
+        }
+    ]
+}
+"#
+        );
+        assert!(migrate_config(&migration.content)?.is_none());
+
+        // Fields shared by every app must agree
+        let conflicting = original.replacen("/sdk/android.jar", "/other/android.jar", 1);
+        let err = migrate_config(&conflicting).unwrap_err();
+        assert!(err.to_string().contains("androidJar differs"), "{}", err);
+        assert!(migrate_config(r#"{"configVersion": 9, "apps": []}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff(old, new, "old", "new"),
+            "--- old\n+++ new\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(unified_diff(old, old, "old", "new"), "");
+    }
+}
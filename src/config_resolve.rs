@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::types::{AppConfig, BuildConfig, MultiAppConfig, UserDefaults};
+
+/// Where the effective value of a config field came from, highest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldSource {
+    /// Command line option, or its `ASB_*` environment variable
+    Cli,
+    /// User defaults file
+    User,
+    /// Flavor of a multi-app config
+    Flavor,
+    /// App of a multi-app config
+    App,
+    /// Common fields of a multi-app config
+    Common,
+    /// Single-object or array config
+    Config,
+    /// Built-in default or auto-detection
+    Default,
+}
+
+impl FieldSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cli => "cli",
+            Self::User => "user",
+            Self::Flavor => "flavor",
+            Self::App => "app",
+            Self::Common => "common",
+            Self::Config => "config",
+            Self::Default => "default",
+        }
+    }
+}
+
+/// Fields the config file sets for one resolved config, per level
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    pub flavor: BTreeSet<String>,
+    pub app: BTreeSet<String>,
+    pub common: BTreeSet<String>,
+    pub config: BTreeSet<String>,
+}
+
+/// Effective value of a config field and its source
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedField {
+    pub name: String,
+    pub value: serde_json::Value,
+    pub source: FieldSource,
+}
+
+/// Names of the fields `value` serializes with a value
+fn set_fields<T: Serialize>(value: &T) -> BTreeSet<String> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::Object(fields)) => fields
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, _)| name)
+            .collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+/// Fields set at each level of the config file, for every config in the order
+/// [`BuildConfig::load_configs`] returns them
+pub fn config_layers(config_file: Option<&Path>) -> Result<Vec<ConfigLayers>> {
+    let path = config_file
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("asb.config.json"));
+    if config_file.is_none() && !path.exists() {
+        return Ok(vec![ConfigLayers::default()]);
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    if let Ok(mut multi) = serde_json::from_str::<MultiAppConfig>(&content) {
+        let apps = std::mem::take(&mut multi.apps);
+        let common = set_fields(&multi);
+        let mut layers = Vec::new();
+        for app in apps.into_iter().flat_map(AppConfig::expand_matrix) {
+            let mut app_fields = set_fields(&app);
+            app_fields.remove("flavors");
+            let layer = |flavor| ConfigLayers {
+                flavor,
+                app: app_fields.clone(),
+                common: common.clone(),
+                config: BTreeSet::new(),
+            };
+            match &app.flavors {
+                Some(flavors) => layers.extend(flavors.iter().map(|f| layer(set_fields(f)))),
+                None => layers.push(layer(BTreeSet::new())),
+            }
+        }
+        return Ok(layers);
+    }
+    let configs = match serde_json::from_str::<Vec<serde_json::Value>>(&content) {
+        Ok(configs) => configs,
+        Err(_) => vec![serde_json::from_str(&content)?],
+    };
+    Ok(configs
+        .iter()
+        .map(|config| ConfigLayers {
+            config: set_fields(config),
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// Works out the source of each field of a resolved config
+pub struct FieldResolver<'a> {
+    pub layers: &'a ConfigLayers,
+    pub user_defaults: &'a UserDefaults,
+    /// Fields overridden on the command line
+    pub cli: &'a BTreeSet<&'a str>,
+    /// Fields rewritten by `outputLayout`
+    pub layout: &'a BTreeSet<&'a str>,
+}
+
+impl FieldResolver<'_> {
+    /// Source of the effective value of `field`
+    pub fn source(&self, field: &str) -> FieldSource {
+        if self.cli.contains(field) {
+            return FieldSource::Cli;
+        }
+        if self.layout.contains(field) {
+            return self.source("outputLayout");
+        }
+        let layers = self.layers;
+        for (fields, source) in [
+            (&layers.flavor, FieldSource::Flavor),
+            (&layers.app, FieldSource::App),
+            (&layers.common, FieldSource::Common),
+            (&layers.config, FieldSource::Config),
+        ] {
+            if fields.contains(field) {
+                return source;
+            }
+        }
+        // Fields derived from other fields come from where those are set
+        match field {
+            "resourceDir" | "manifestPath" => self.source("baseDir"),
+            "app" if !layers.app.is_empty() => FieldSource::App,
+            "flavor" if layers.flavor.contains("name") => FieldSource::Flavor,
+            "additionalResourceDirs" => self.source("conditionalResourceDirs"),
+            "missingResourceDirPolicy"
+            | "warningsAsErrors"
+            | "validateReferences"
+            | "validateXml" => self.source("strict"),
+            "aapt2Path" | "androidJar" | "cacheDir"
+                if set_fields(self.user_defaults).contains(field) =>
+            {
+                FieldSource::User
+            }
+            _ => FieldSource::Default,
+        }
+    }
+
+    /// Every field `config` sets, with its effective value and source
+    pub fn resolve(&self, config: &BuildConfig) -> Result<Vec<ResolvedField>> {
+        let serde_json::Value::Object(fields) = serde_json::to_value(config)? else {
+            anyhow::bail!("Config of {} is not an object", config.package_name);
+        };
+        Ok(fields
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, value)| ResolvedField {
+                source: self.source(&name),
+                name,
+                value,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_field_sources_follow_precedence() -> Result<()> {
+        let temp = TempDir::new()?;
+        let config_path = temp.path().join("asb.config.json");
+        std::fs::write(
+            &config_path,
+            r#"{
+                "outputDir": "./build",
+                "versionName": "1.0",
+                "versionCode": 1,
+                "baseDir": "./skins",
+                "apps": [{
+                    "packageName": "com.skin.red",
+                    "versionCode": 2,
+                    "flavors": [{ "name": "night", "versionCode": 3 }, { "name": "day" }]
+                }]
+            }"#,
+        )?;
+        let configs = BuildConfig::load_configs(Some(config_path.clone()), None)?.configs;
+        let layers = config_layers(Some(&config_path))?;
+        assert_eq!(layers.len(), configs.len());
+
+        let user_defaults = UserDefaults {
+            android_jar: Some(PathBuf::from("/sdk/android.jar")),
+            ..Default::default()
+        };
+        let cli = BTreeSet::from(["versionName"]);
+        let layout = BTreeSet::new();
+        let resolver = |layers| FieldResolver {
+            layers,
+            user_defaults: &user_defaults,
+            cli: &cli,
+            layout: &layout,
+        };
+        let night = resolver(&layers[0]);
+        assert_eq!(night.source("versionCode"), FieldSource::Flavor);
+        assert_eq!(night.source("versionName"), FieldSource::Cli);
+        assert_eq!(night.source("outputDir"), FieldSource::Common);
+        assert_eq!(night.source("resourceDir"), FieldSource::Common);
+        assert_eq!(night.source("packageName"), FieldSource::App);
+        assert_eq!(night.source("androidJar"), FieldSource::User);
+        assert_eq!(night.source("incremental"), FieldSource::Default);
+        let day = resolver(&layers[1]);
+        assert_eq!(day.source("versionCode"), FieldSource::App);
+        assert_eq!(day.source("flavor"), FieldSource::Flavor);
+
+        let fields = night.resolve(&configs[0])?;
+        let version_code = fields.iter().find(|f| f.name == "versionCode").unwrap();
+        assert_eq!(version_code.value, serde_json::json!(3));
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::tokens::{ConfigValues, DEFAULT_CONFIGURATION, TokenReport, normalize_color};
+use crate::types::ContrastConfig;
+
+/// Minimum contrast ratio unless configured: WCAG AA for normal text
+pub const DEFAULT_MIN_RATIO: f64 = 4.5;
+
+/// Text and background roles checked unless configured, as Material themes name them
+const DEFAULT_PAIRS: &[(&str, &str)] = &[
+    ("colorOnPrimary", "colorPrimary"),
+    ("colorOnSecondary", "colorSecondary"),
+    ("colorOnBackground", "android:colorBackground"),
+    ("colorOnSurface", "colorSurface"),
+    ("colorOnError", "colorError"),
+    ("android:textColorPrimary", "android:colorBackground"),
+];
+
+/// Scope of pairs made of color resources rather than theme attributes
+const COLORS_SCOPE: &str = "colors";
+
+/// Outcome of the contrast lint of one skin
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContrastReport {
+    /// Pairs checked, counted once per scope and configuration
+    pub checked: usize,
+    pub violations: Vec<ContrastViolation>,
+}
+
+/// A foreground/background pair below its minimum contrast ratio
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContrastViolation {
+    /// "light", "dark" or the qualifiers of other `values-*` configurations
+    pub configuration: String,
+    /// Style defining the pair, or "colors" for color resources
+    pub scope: String,
+    pub foreground: String,
+    pub foreground_color: String,
+    pub background: String,
+    pub background_color: String,
+    /// Contrast ratio, rounded to two decimals
+    pub ratio: f64,
+    pub min_ratio: f64,
+}
+
+/// Check the WCAG contrast ratio of text/background role pairs in every configuration
+///
+/// A role is a color resource or a theme attribute (`android:` prefix optional) of a style.
+/// Pairs are checked in every style defining either role, taking the other from the color
+/// resources, and among the color resources themselves. A translucent background is
+/// composited onto white in light and onto black in night configurations, and a translucent
+/// foreground onto its background. Values that are not color literals after resolving
+/// references, such as framework colors, are skipped.
+pub fn check_contrast(tokens: &TokenReport, config: Option<&ContrastConfig>) -> ContrastReport {
+    let default_ratio = config
+        .and_then(|c| c.min_ratio)
+        .unwrap_or(DEFAULT_MIN_RATIO);
+    let pairs: Vec<(&str, &str, f64)> = match config.and_then(|c| c.pairs.as_ref()) {
+        Some(pairs) => pairs
+            .iter()
+            .map(|p| {
+                (
+                    p.foreground.as_str(),
+                    p.background.as_str(),
+                    p.min_ratio.unwrap_or(default_ratio),
+                )
+            })
+            .collect(),
+        None => DEFAULT_PAIRS
+            .iter()
+            .map(|&(foreground, background)| (foreground, background, default_ratio))
+            .collect(),
+    };
+
+    let mut report = ContrastReport::default();
+    for (foreground, background, min_ratio) in pairs {
+        let color = |role: &str| {
+            tokens
+                .colors
+                .get(role.strip_prefix("android:").unwrap_or(role))
+        };
+        let mut scopes: Vec<(&str, &ConfigValues, &ConfigValues)> = Vec::new();
+        if let (Some(fg), Some(bg)) = (color(foreground), color(background)) {
+            scopes.push((COLORS_SCOPE, fg, bg));
+        }
+        for (style, style_tokens) in &tokens.styles {
+            let item = |role: &str| {
+                style_tokens
+                    .items
+                    .iter()
+                    .find(|(name, _)| same_role(name, role))
+                    .map(|(_, values)| values)
+            };
+            let (fg, bg) = (item(foreground), item(background));
+            if fg.is_none() && bg.is_none() {
+                continue;
+            }
+            if let (Some(fg), Some(bg)) = (fg.or(color(foreground)), bg.or(color(background))) {
+                scopes.push((style, fg, bg));
+            }
+        }
+
+        for (scope, fg, bg) in scopes {
+            let configurations: BTreeSet<&str> = fg
+                .keys()
+                .chain(bg.keys())
+                .map(String::as_str)
+                .chain([DEFAULT_CONFIGURATION])
+                .collect();
+            for configuration in configurations {
+                let value = |values: &ConfigValues| {
+                    values
+                        .get(configuration)
+                        .or_else(|| values.get(DEFAULT_CONFIGURATION))
+                        .cloned()
+                };
+                let (Some(fg_value), Some(bg_value)) = (value(fg), value(bg)) else {
+                    continue;
+                };
+                let (Some(fg_color), Some(bg_color)) =
+                    (parse_color(&fg_value), parse_color(&bg_value))
+                else {
+                    continue;
+                };
+                let night = configuration.split('-').any(|q| q == "night");
+                let backdrop = if night { [0.0; 3] } else { [1.0; 3] };
+                let bg_rgb = composite(bg_color, backdrop);
+                let ratio = contrast_ratio(composite(fg_color, bg_rgb), bg_rgb);
+                report.checked += 1;
+                if ratio < min_ratio {
+                    report.violations.push(ContrastViolation {
+                        configuration: configuration_label(configuration),
+                        scope: scope.to_string(),
+                        foreground: foreground.to_string(),
+                        foreground_color: fg_value,
+                        background: background.to_string(),
+                        background_color: bg_value,
+                        ratio: (ratio * 100.0).floor() / 100.0,
+                        min_ratio,
+                    });
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Whether a style item name is the role, with or without the `android:` prefix
+fn same_role(name: &str, role: &str) -> bool {
+    name.strip_prefix("android:").unwrap_or(name) == role.strip_prefix("android:").unwrap_or(role)
+}
+
+/// "light" for the default configuration, "dark" for night and the qualifiers otherwise
+fn configuration_label(configuration: &str) -> String {
+    match configuration {
+        DEFAULT_CONFIGURATION => "light".to_string(),
+        "night" => "dark".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Alpha and RGB channels (0 to 1) of a color literal
+fn parse_color(value: &str) -> Option<[f64; 4]> {
+    let hex = normalize_color(value)?;
+    let digits = u32::from_str_radix(&hex[1..], 16).ok()?;
+    let argb = if hex.len() == 7 {
+        0xFF00_0000 | digits
+    } else {
+        digits
+    };
+    let channel = |shift: u32| f64::from((argb >> shift) & 0xFF) / 255.0;
+    Some([channel(24), channel(16), channel(8), channel(0)])
+}
+
+/// Blend a color over an opaque backdrop
+fn composite([alpha, r, g, b]: [f64; 4], backdrop: [f64; 3]) -> [f64; 3] {
+    let blend = |c: f64, under: f64| c * alpha + under * (1.0 - alpha);
+    [
+        blend(r, backdrop[0]),
+        blend(g, backdrop[1]),
+        blend(b, backdrop[2]),
+    ]
+}
+
+/// WCAG 2 relative luminance of an sRGB color
+fn luminance(rgb: [f64; 3]) -> f64 {
+    let linear = |c: f64| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(rgb[0]) + 0.7152 * linear(rgb[1]) + 0.0722 * linear(rgb[2])
+}
+
+/// WCAG 2 contrast ratio, from 1 (same luminance) to 21 (black on white)
+fn contrast_ratio(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::StyleTokens;
+    use crate::types::ContrastPair;
+    use std::collections::BTreeMap;
+
+    fn values(pairs: &[(&str, &str)]) -> ConfigValues {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let ratio = |fg: &str, bg: &str| {
+            let bg = composite(parse_color(bg).unwrap(), [1.0; 3]);
+            contrast_ratio(composite(parse_color(fg).unwrap(), bg), bg)
+        };
+        assert!((ratio("#000", "#FFF") - 21.0).abs() < 1e-9);
+        assert!((ratio("#777777", "#FFFFFF") - 4.48).abs() < 0.01);
+        assert!((ratio("#FFF", "#FFF") - 1.0).abs() < 1e-9);
+        // Half-transparent black on white is mid gray
+        assert!((ratio("#80000000", "#FFFFFF") - 4.0).abs() < 0.05);
+        assert_eq!(parse_color("@android:color/white"), None);
+    }
+
+    #[test]
+    fn test_check_contrast_per_configuration() {
+        let mut tokens = TokenReport::default();
+        tokens.colors.insert(
+            "primary".to_string(),
+            values(&[("default", "#FF6200EE"), ("night", "#FFBB86FC")]),
+        );
+        let mut items = BTreeMap::new();
+        items.insert(
+            "colorPrimary".to_string(),
+            values(&[("default", "#FF6200EE"), ("night", "#FFBB86FC")]),
+        );
+        items.insert(
+            "colorOnPrimary".to_string(),
+            values(&[("default", "#FFFFFFFF")]),
+        );
+        tokens.styles.insert(
+            "Theme.Skin".to_string(),
+            StyleTokens {
+                parent: None,
+                items,
+            },
+        );
+
+        let report = check_contrast(&tokens, None);
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.violations.len(), 1);
+        let violation = &report.violations[0];
+        assert_eq!(violation.configuration, "dark");
+        assert_eq!(violation.scope, "Theme.Skin");
+        assert_eq!(violation.foreground_color, "#FFFFFFFF");
+        assert_eq!(violation.background_color, "#FFBB86FC");
+        assert_eq!(violation.ratio, 2.64);
+
+        // Configured pairs use color resources too
+        tokens
+            .colors
+            .insert("text".to_string(), values(&[("default", "#FF6200EE")]));
+        let config = ContrastConfig {
+            pairs: Some(vec![ContrastPair {
+                foreground: "text".to_string(),
+                background: "primary".to_string(),
+                min_ratio: Some(1.0),
+            }]),
+            min_ratio: None,
+        };
+        let report = check_contrast(&tokens, Some(&config));
+        assert_eq!(report.checked, 2);
+        assert!(report.violations.is_empty());
+    }
+}
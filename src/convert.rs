@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::aapt2::Aapt2;
+use crate::hooks::{HookOutcome, HookStage, run_hooks};
+use crate::packaging::{PackageOptions, PackageSummary, SkinPackager};
+use crate::types::BuildConfig;
+
+/// Resource table format of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ResourceFormat {
+    /// `resources.arsc` and binary XML, loadable by Android at runtime
+    Binary,
+    /// `resources.pb` and protobuf XML, as used in app bundles
+    Proto,
+}
+
+impl ResourceFormat {
+    /// Value of `aapt2 convert --output-format`
+    pub fn name(self) -> &'static str {
+        match self {
+            ResourceFormat::Binary => "binary",
+            ResourceFormat::Proto => "proto",
+        }
+    }
+}
+
+/// Format of the package at `path`, told by its resource table entry
+pub fn detect_format(path: &Path) -> Result<ResourceFormat> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read {} as a package", path.display()))?;
+    let names: Vec<&str> = archive.file_names().collect();
+    if names.contains(&"resources.arsc") {
+        Ok(ResourceFormat::Binary)
+    } else if names.contains(&"resources.pb") {
+        Ok(ResourceFormat::Proto)
+    } else {
+        anyhow::bail!(
+            "{} has neither resources.arsc nor resources.pb",
+            path.display()
+        )
+    }
+}
+
+/// Where a converted package goes: `output` itself, or `output/<file name>` when it is a
+/// directory; by default next to the input, e.g. `skin.skin` becomes `skin.proto.skin`
+pub fn converted_path(input: &Path, format: ResourceFormat, output: Option<&Path>) -> PathBuf {
+    let file_name = input.file_name().unwrap_or_default();
+    match output {
+        Some(output) if output.is_dir() => output.join(file_name),
+        Some(output) => output.to_path_buf(),
+        None => {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match input.extension() {
+                Some(ext) => format!("{}.{}.{}", stem, format.name(), ext.to_string_lossy()),
+                None => format!("{}.{}", stem, format.name()),
+            };
+            input.with_file_name(name)
+        }
+    }
+}
+
+/// Convert the package at `input` to `format` with `aapt2 convert`, writing `output`
+///
+/// Binary packages are rewritten with `options` afterwards, so they get the alignment and
+/// compression the config's `packaging` asks for. Proto packages are meant for bundle
+/// tooling, which repacks them anyway.
+pub fn convert_package(
+    aapt2: &Aapt2,
+    input: &Path,
+    output: &Path,
+    format: ResourceFormat,
+    options: Option<PackageOptions>,
+) -> Result<Option<PackageSummary>> {
+    let source = detect_format(input)?;
+    if source == format {
+        anyhow::bail!("{} is already in {} format", input.display(), format.name());
+    }
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    aapt2.convert(input, output, format.name())?;
+    info!(
+        "Converted {} ({}) to {} ({})",
+        input.display(),
+        source.name(),
+        output.display(),
+        format.name()
+    );
+
+    match (format, options) {
+        (ResourceFormat::Binary, Some(options)) => {
+            let summary = SkinPackager::new(options).repackage_in_place(output)?;
+            Ok(Some(summary))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Run the `postBuild` hooks of `config` on a converted package, e.g. to sign it
+///
+/// Hooks see the converted package as `ASB_OUTPUT` and the original as `ASB_CONVERTED_FROM`.
+pub fn run_post_convert_hooks(config: &BuildConfig, input: &Path, output: &Path) -> HookOutcome {
+    let Some(hooks) = &config.hooks else {
+        return HookOutcome::default();
+    };
+    let absolute = |path: &Path| {
+        std::path::absolute(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .display()
+            .to_string()
+    };
+    let work_root = config
+        .build_dir
+        .clone()
+        .unwrap_or_else(|| config.output_dir.join(".build"))
+        .join(&config.package_name)
+        .join("convert-hooks");
+    let env = [
+        ("ASB_PACKAGE", config.package_name.clone()),
+        ("ASB_TARGET", config.target()),
+        ("ASB_OUTPUT", absolute(output)),
+        ("ASB_CONVERTED_FROM", absolute(input)),
+    ];
+    run_hooks(hooks, HookStage::PostBuild, &env, &work_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn write_package(path: &Path, entries: &[&str]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        for entry in entries {
+            writer
+                .start_file(*entry, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"data").unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_detect_format() -> Result<()> {
+        let temp = TempDir::new()?;
+        let binary = temp.path().join("binary.skin");
+        write_package(&binary, &["AndroidManifest.xml", "resources.arsc"]);
+        let proto = temp.path().join("proto.skin");
+        write_package(&proto, &["AndroidManifest.xml", "resources.pb"]);
+        let empty = temp.path().join("empty.skin");
+        write_package(&empty, &["AndroidManifest.xml"]);
+
+        assert_eq!(detect_format(&binary)?, ResourceFormat::Binary);
+        assert_eq!(detect_format(&proto)?, ResourceFormat::Proto);
+        assert!(detect_format(&empty).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_converted_path() -> Result<()> {
+        let temp = TempDir::new()?;
+        let input = Path::new("out/com.example.skin.skin");
+        assert_eq!(
+            converted_path(input, ResourceFormat::Proto, None),
+            PathBuf::from("out/com.example.skin.proto.skin")
+        );
+        assert_eq!(
+            converted_path(input, ResourceFormat::Binary, Some(temp.path())),
+            temp.path().join("com.example.skin.skin")
+        );
+        assert_eq!(
+            converted_path(input, ResourceFormat::Binary, Some(Path::new("legacy.apk"))),
+            PathBuf::from("legacy.apk")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::aapt2::Aapt2;
+use crate::builder::find_resource_files;
+use crate::owners::glob_match;
+use crate::references::SymbolTable;
+use crate::types::BuildConfig;
+
+/// Resource types counted as skinnable when `--types` is not given
+pub const DEFAULT_SKINNABLE_TYPES: [&str; 3] = ["color", "drawable", "mipmap"];
+
+/// Layouts of a host APK checked when no `--layouts` globs are given
+const DEFAULT_APK_LAYOUTS: &str = "res/layout*/*.xml";
+
+/// A view attribute of a host layout that references a resource
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LayoutReference {
+    /// Element name, with the view ID when it has one: `TextView#title`
+    pub view: String,
+    /// Attribute with its namespace prefix: `android:background`
+    pub attribute: String,
+    /// Referenced resource as `type/name`
+    pub resource: String,
+}
+
+/// A host layout file and its resource references
+#[derive(Debug, Clone)]
+pub struct HostLayout {
+    /// Layout name, with the directory when it has qualifiers: `activity_main (layout-land)`
+    pub screen: String,
+    /// Layout file, or the APK entry as `<apk>!/res/layout/...`
+    pub source: String,
+    pub references: Vec<LayoutReference>,
+}
+
+/// A skinnable reference and whether the skin overrides its resource
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoveredReference {
+    #[serde(flatten)]
+    pub reference: LayoutReference,
+    pub covered: bool,
+}
+
+/// Theming coverage of one screen
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenCoverage {
+    pub screen: String,
+    pub source: String,
+    pub references: Vec<CoveredReference>,
+    pub covered: usize,
+    pub total: usize,
+    /// Percentage of skinnable references the skin overrides; 100 for screens without any
+    pub percent: f64,
+}
+
+/// Theming coverage of a skin over the host layouts
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageReport {
+    pub package_name: String,
+    pub screens: Vec<ScreenCoverage>,
+    pub covered: usize,
+    pub total: usize,
+    pub percent: f64,
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
+
+/// Namespace prefix of an attribute as written in layouts
+fn attribute_name(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(ns) if ns.ends_with("/res/android") => format!("android:{}", name),
+        Some(_) => format!("app:{}", name),
+        None => name.to_string(),
+    }
+}
+
+/// `type/name` of a reference to a resource of the host's own package
+///
+/// Framework (`@android:`) references, theme attributes (`?attr/...`), `@+id` definitions
+/// and `@null` are skipped.
+fn host_reference(value: &str) -> Option<String> {
+    let target = value.trim().strip_prefix('@')?;
+    if target.starts_with('+') || target.contains(':') || !target.contains('/') {
+        return None;
+    }
+    Some(target.to_string())
+}
+
+/// Resource references in the source XML of a layout
+pub fn parse_layout_xml(content: &str) -> Result<Vec<LayoutReference>> {
+    let document = roxmltree::Document::parse(content)?;
+    let mut references = Vec::new();
+    for node in document.descendants().filter(|n| n.is_element()) {
+        let id = node
+            .attributes()
+            .find(|a| a.name() == "id")
+            .and_then(|a| a.value().split_once('/'))
+            .map(|(_, id)| id);
+        let tag = node.tag_name().name();
+        let view = match id {
+            Some(id) => format!("{}#{}", tag, id),
+            None => tag.to_string(),
+        };
+        for attribute in node.attributes().filter(|a| a.name() != "id") {
+            if let Some(resource) = host_reference(attribute.value()) {
+                references.push(LayoutReference {
+                    view: view.clone(),
+                    attribute: attribute_name(attribute.namespace(), attribute.name()),
+                    resource,
+                });
+            }
+        }
+    }
+    Ok(references)
+}
+
+/// Resource references in a compiled layout, as printed by `aapt2 dump xmltree`
+///
+/// Compiled layouts reference resources by ID (`@0x7f050001`); `names` maps the IDs of
+/// the host's resource table to `type/name`, as printed by `aapt2 dump resources`.
+pub fn parse_xmltree_dump(dump: &str, names: &BTreeMap<String, String>) -> Vec<LayoutReference> {
+    // Attributes of the current element, the ID attribute only known once all are read
+    let mut element: Option<(String, Option<String>, Vec<(String, String)>)> = None;
+    let mut references = Vec::new();
+    let mut flush = |element: Option<(String, Option<String>, Vec<(String, String)>)>| {
+        if let Some((tag, id, attributes)) = element {
+            let view = match id {
+                Some(id) => format!("{}#{}", tag, id),
+                None => tag,
+            };
+            for (attribute, resource) in attributes {
+                references.push(LayoutReference {
+                    view: view.clone(),
+                    attribute,
+                    resource,
+                });
+            }
+        }
+    };
+
+    for line in dump.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("E: ") {
+            flush(element.take());
+            let tag = rest.split_whitespace().next().unwrap_or(rest);
+            element = Some((tag.to_string(), None, Vec::new()));
+        } else if let Some(rest) = line.strip_prefix("A: ")
+            && let Some((_, id, attributes)) = element.as_mut()
+            && let Some((name, value)) = rest.split_once('=')
+        {
+            // `http://schemas.android.com/apk/res/android:background(0x010100d4)`
+            let name = name.split('(').next().unwrap_or(name);
+            let (namespace, name) = match name.rsplit_once(':') {
+                Some((namespace, name)) if namespace.contains('/') => (Some(namespace), name),
+                _ => (None, name),
+            };
+            let value = value.split_whitespace().next().unwrap_or(value);
+            let resource = match value.strip_prefix("@0x") {
+                Some(hex) => names
+                    .get(&format!("0x{}", hex.to_ascii_lowercase()))
+                    .cloned(),
+                None => host_reference(value),
+            };
+            let Some(resource) = resource else {
+                continue;
+            };
+            if name == "id" {
+                *id = resource.split_once('/').map(|(_, id)| id.to_string());
+            } else {
+                attributes.push((attribute_name(namespace, name), resource));
+            }
+        }
+    }
+    flush(element);
+    references
+}
+
+/// Screen name of a layout at `res/<dir>/<file>`
+fn screen_name(dir: &str, file: &str) -> String {
+    let name = file.strip_suffix(".xml").unwrap_or(file);
+    if dir == "layout" {
+        name.to_string()
+    } else {
+        format!("{} ({})", name, dir)
+    }
+}
+
+/// Directory and file name of a layout path
+fn layout_location(path: &str) -> (String, String) {
+    let mut parts = path.rsplit('/');
+    let file = parts.next().unwrap_or_default().to_string();
+    let dir = parts.next().unwrap_or("layout").to_string();
+    (dir, file)
+}
+
+/// Layout files on disk matching `patterns`, such as `app/src/main/res/layout*/*.xml`
+///
+/// Walking starts at the directory before the first wildcard; `**` matches any number of
+/// directories.
+pub fn find_layout_files(patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let pattern = pattern.replace('\\', "/");
+        let segments: Vec<&str> = pattern.split('/').collect();
+        let fixed = segments
+            .iter()
+            .position(|s| s.contains(['*', '?']))
+            .unwrap_or(segments.len());
+        if fixed == segments.len() {
+            files.push(PathBuf::from(&pattern));
+            continue;
+        }
+        let base = match segments[..fixed].join("/") {
+            base if base.is_empty() => ".".to_string(),
+            base => base,
+        };
+        let rest = segments[fixed..].join("/");
+        for entry in walkdir::WalkDir::new(&base).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("Failed to walk {}", base))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry
+                .path()
+                .strip_prefix(&base)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            if glob_match(&rest, &relative) && !files.iter().any(|f| f == entry.path()) {
+                files.push(entry.path().to_path_buf());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Read the layout XML files found by [`find_layout_files`]
+pub fn read_layout_files(files: &[PathBuf]) -> Result<Vec<HostLayout>> {
+    files
+        .iter()
+        .map(|file| {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let references = parse_layout_xml(&content)
+                .with_context(|| format!("Failed to parse {}", file.display()))?;
+            let (dir, name) = layout_location(&file.to_string_lossy().replace('\\', "/"));
+            Ok(HostLayout {
+                screen: screen_name(&dir, &name),
+                source: file.display().to_string(),
+                references,
+            })
+        })
+        .collect()
+}
+
+/// Read the compiled layouts of a host APK whose entry paths match `patterns` (default:
+/// every `res/layout*/*.xml`)
+pub fn read_apk_layouts(aapt2: &Aapt2, apk: &Path, patterns: &[String]) -> Result<Vec<HostLayout>> {
+    let file = fs::File::open(apk).with_context(|| format!("Failed to open {}", apk.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read {} as an APK", apk.display()))?;
+    let default_patterns = [DEFAULT_APK_LAYOUTS.to_string()];
+    let patterns = if patterns.is_empty() {
+        &default_patterns[..]
+    } else {
+        patterns
+    };
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        if name.ends_with(".xml") && patterns.iter().any(|p| glob_match(p, &name)) {
+            // Only layouts are dumped; reading the header catches entries that are not
+            // compiled XML early
+            let mut magic = [0u8; 2];
+            if entry.read_exact(&mut magic).is_ok() && magic == [0x03, 0x00] {
+                entries.push(name);
+            }
+        }
+    }
+    entries.sort();
+
+    let names: BTreeMap<String, String> = crate::freeze::ids_from_dump(&aapt2.dump_resources(apk)?)
+        .into_iter()
+        .map(|(name, id)| (id, name))
+        .collect();
+    entries
+        .iter()
+        .map(|entry| {
+            let dump = aapt2.dump_xmltree(apk, entry)?;
+            let (dir, name) = layout_location(entry);
+            Ok(HostLayout {
+                screen: screen_name(&dir, &name),
+                source: format!("{}!/{}", apk.display(), entry),
+                references: parse_xmltree_dump(&dump, &names),
+            })
+        })
+        .collect()
+}
+
+/// Resources the skin of a config defines in its own resource directories
+pub fn skin_symbols(config: &BuildConfig) -> Result<SymbolTable> {
+    let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+    let respect_gitignore = config.respect_gitignore.unwrap_or(false);
+    let mut symbols = SymbolTable::default();
+    let dirs = std::iter::once(&config.resource_dir)
+        .chain(config.additional_resource_dirs.iter().flatten());
+    for res_dir in dirs.filter(|dir| dir.exists()) {
+        let files = find_resource_files(res_dir, follow_symlinks, respect_gitignore, None)?;
+        symbols.add_resource_files(res_dir, &files)?;
+    }
+    Ok(symbols)
+}
+
+/// Coverage of the skinnable references of `layouts` by the resources in `skin`
+///
+/// A reference is skinnable when its resource type is one of `types`; it is covered when
+/// the skin defines a resource of the same type and name.
+pub fn check_coverage(
+    package_name: &str,
+    layouts: &[HostLayout],
+    skin: &SymbolTable,
+    types: &[String],
+) -> CoverageReport {
+    let screens: Vec<ScreenCoverage> = layouts
+        .iter()
+        .map(|layout| {
+            let references: Vec<CoveredReference> = layout
+                .references
+                .iter()
+                .filter_map(|reference| {
+                    let (resource_type, name) = reference.resource.split_once('/')?;
+                    types
+                        .iter()
+                        .any(|t| t == resource_type)
+                        .then(|| CoveredReference {
+                            reference: reference.clone(),
+                            covered: skin.contains(resource_type, name),
+                        })
+                })
+                .collect();
+            let covered = references.iter().filter(|r| r.covered).count();
+            ScreenCoverage {
+                screen: layout.screen.clone(),
+                source: layout.source.clone(),
+                total: references.len(),
+                percent: percent(covered, references.len()),
+                references,
+                covered,
+            }
+        })
+        .collect();
+    let covered = screens.iter().map(|s| s.covered).sum();
+    let total = screens.iter().map(|s| s.total).sum();
+    CoverageReport {
+        package_name: package_name.to_string(),
+        screens,
+        covered,
+        total,
+        percent: percent(covered, total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LAYOUT: &str = r#"<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:app="http://schemas.android.com/apk/res-auto"
+    android:background="@color/window_background">
+    <TextView android:id="@+id/title"
+        android:textColor="@color/title_text"
+        android:text="@string/title"
+        app:drawableTint="?attr/colorAccent" />
+    <ImageView android:src="@drawable/ic_logo"
+        android:tint="@android:color/white" />
+</LinearLayout>"#;
+
+    #[test]
+    fn test_parse_layouts() {
+        let references = parse_layout_xml(LAYOUT).unwrap();
+        let found: Vec<(&str, &str, &str)> = references
+            .iter()
+            .map(|r| (r.view.as_str(), r.attribute.as_str(), r.resource.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "LinearLayout",
+                    "android:background",
+                    "color/window_background"
+                ),
+                ("TextView#title", "android:textColor", "color/title_text"),
+                ("TextView#title", "android:text", "string/title"),
+                ("ImageView", "android:src", "drawable/ic_logo"),
+            ]
+        );
+
+        let dump = "N: android=http://schemas.android.com/apk/res/android (line=1)
+  E: LinearLayout (line=1)
+    A: http://schemas.android.com/apk/res/android:background(0x010100d4)=@0x7f050000
+    E: TextView (line=4)
+      A: http://schemas.android.com/apk/res/android:id(0x010100d0)=@0x7F080001
+      A: http://schemas.android.com/apk/res/android:textColor(0x01010098)=@0x7f050001
+      A: http://schemas.android.com/apk/res/android:textSize(0x01010095)=14.0sp
+";
+        let names = [
+            ("0x7f050000", "color/window_background"),
+            ("0x7f050001", "color/title_text"),
+            ("0x7f080001", "id/title"),
+        ]
+        .into_iter()
+        .map(|(id, name)| (id.to_string(), name.to_string()))
+        .collect();
+        assert_eq!(parse_xmltree_dump(dump, &names), references[..2].to_vec());
+    }
+
+    #[test]
+    fn test_check_coverage() -> Result<()> {
+        let temp = TempDir::new()?;
+        let host = temp.path().join("host/res/layout-land");
+        fs::create_dir_all(&host)?;
+        fs::write(host.join("activity_main.xml"), LAYOUT)?;
+        let skin = temp.path().join("skin");
+        fs::create_dir_all(skin.join("values"))?;
+        fs::write(
+            skin.join("values/colors.xml"),
+            r#"<resources><color name="title_text">#000000</color></resources>"#,
+        )?;
+        let mut symbols = SymbolTable::default();
+        symbols.add_resource_files(&skin, &[skin.join("values/colors.xml")])?;
+
+        let pattern = format!("{}/host/res/layout*/*.xml", temp.path().display());
+        let layouts = read_layout_files(&find_layout_files(&[pattern])?)?;
+        let types: Vec<String> = DEFAULT_SKINNABLE_TYPES
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let report = check_coverage("com.skin", &layouts, &symbols, &types);
+        assert_eq!(report.screens.len(), 1);
+        assert_eq!(report.screens[0].screen, "activity_main (layout-land)");
+        assert_eq!((report.covered, report.total), (1, 3));
+        assert!((report.percent - 33.33).abs() < 0.01);
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::builder::{ConcurrencyLimits, SkinBuilder};
+use crate::types::{BuildConfig, BuildResult};
+
+/// Socket the daemon listens on when none is given, relative to the project directory
+pub const DEFAULT_SOCKET: &str = ".asb-daemon.sock";
+
+/// Protocol message, written as one JSON object per line
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Message {
+    /// Client → daemon: build these resolved configs
+    /// Config paths may be relative, so they are only valid in the client's directory `cwd`
+    Build {
+        cwd: PathBuf,
+        configs: Vec<BuildConfig>,
+    },
+    /// Daemon → client: results in the order of the requested configs
+    Results { results: Vec<BuildResult> },
+    /// Client → daemon: exit after answering
+    Stop,
+    /// Daemon → client: acknowledges `Stop`
+    Stopped,
+    /// Daemon → client: the request could not be handled
+    Failed { error: String },
+}
+
+/// A builder kept between requests with the config it was created from
+struct WarmBuilder {
+    /// Serialized requested config; a different config gets a fresh builder
+    config: String,
+    builder: SkinBuilder,
+}
+
+/// File changes below the resource directories, reported to the builders with `--watch`
+struct Watch {
+    watcher: std::sync::Mutex<RecommendedWatcher>,
+    /// Changed paths not yet applied, by builder key
+    pending: Arc<std::sync::Mutex<HashMap<String, Vec<PathBuf>>>>,
+}
+
+impl Watch {
+    fn new() -> Result<Self> {
+        let pending: Arc<std::sync::Mutex<HashMap<String, Vec<PathBuf>>>> = Arc::default();
+        let watcher = notify::recommended_watcher({
+            let pending = pending.clone();
+            move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    for paths in pending.lock().unwrap().values_mut() {
+                        paths.extend(event.paths.iter().cloned());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("File watch error: {}", e),
+            }
+        })
+        .context("Failed to start watching files")?;
+        Ok(Self {
+            watcher: std::sync::Mutex::new(watcher),
+            pending,
+        })
+    }
+
+    /// Track file changes for a new builder and watch its resource directories
+    fn track(&self, key: &str, builder: &mut SkinBuilder) -> Result<()> {
+        // Changes from here on are kept for the builder; it walks its directories once first
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Vec::new());
+        let mut watcher = self.watcher.lock().unwrap();
+        for root in builder.track_file_changes()? {
+            debug!("Watching {}", root.display());
+            watcher
+                .watch(&root, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch {}", root.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Changed paths since the last build of a builder
+    fn take(&self, key: &str) -> Vec<PathBuf> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get_mut(key)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+/// Builders by package, kept warm between requests
+///
+/// A builder holds the loaded incremental cache, the resolved aapt2 and its compile settings,
+/// so repeated builds of an unchanged config skip loading them again.
+struct Builders {
+    builders: Mutex<HashMap<String, Arc<Mutex<WarmBuilder>>>>,
+    limits: ConcurrencyLimits,
+    watch: Option<Watch>,
+}
+
+fn builder_key(config: &BuildConfig) -> String {
+    match &config.flavor {
+        Some(flavor) => format!("{}-{}", config.package_name, flavor),
+        None => config.package_name.clone(),
+    }
+}
+
+impl Builders {
+    /// Builder for a config, reused when the package was last built with the same config
+    async fn get(&self, config: BuildConfig) -> Result<Arc<Mutex<WarmBuilder>>> {
+        let key = builder_key(&config);
+        let serialized = serde_json::to_string(&config)?;
+        // Waits for a running build of the same package, which has to finish first anyway
+        let existing = self.builders.lock().await.get(&key).cloned();
+        if let Some(warm) = existing
+            && warm.lock().await.config == serialized
+        {
+            debug!("Reusing warm builder for {}", key);
+            return Ok(warm);
+        }
+        debug!("Creating builder for {}", key);
+        let mut builder = SkinBuilder::new(config)?.with_concurrency_limits(self.limits.clone());
+        if let Some(watch) = &self.watch {
+            watch.track(&key, &mut builder)?;
+        }
+        let warm = Arc::new(Mutex::new(WarmBuilder {
+            config: serialized,
+            builder,
+        }));
+        self.builders.lock().await.insert(key, warm.clone());
+        Ok(warm)
+    }
+
+    /// Build configs in parallel, one builder per package
+    async fn build(self: &Arc<Self>, configs: Vec<BuildConfig>) -> Vec<BuildResult> {
+        let mut tasks = Vec::new();
+        for config in configs {
+            let builders = self.clone();
+            let package = config.package_name.clone();
+            let key = builder_key(&config);
+            tasks.push(tokio::spawn(async move {
+                let started = std::time::Instant::now();
+                let result = match builders.get(config).await {
+                    Ok(warm) => {
+                        let mut warm = warm.lock().await;
+                        if let Some(watch) = &builders.watch {
+                            warm.builder.apply_file_changes(&watch.take(&key));
+                        }
+                        warm.builder.build().await
+                    }
+                    Err(e) => Err(e),
+                };
+                result.unwrap_or_else(|e| {
+                    failed_result(format!("Build of {} failed: {:#}", package, e), started)
+                })
+            }));
+        }
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap_or_else(|e| {
+                failed_result(
+                    format!("Build task failed: {}", e),
+                    std::time::Instant::now(),
+                )
+            }));
+        }
+        results
+    }
+}
+
+fn failed_result(error: String, started: std::time::Instant) -> BuildResult {
+    BuildResult {
+        success: false,
+        apk_path: None,
+        errors: vec![error],
+        warnings: Vec::new(),
+        build_duration: started.elapsed(),
+        vcs: None,
+        cached_files: 0,
+        compiled_files: 0,
+        normalization: None,
+    }
+}
+
+#[cfg(unix)]
+mod transport {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+
+    pub(super) async fn send(stream: &mut BufReader<UnixStream>, message: &Message) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        stream.get_mut().write_all(&line).await?;
+        stream.get_mut().flush().await?;
+        Ok(())
+    }
+
+    pub(super) async fn receive(stream: &mut BufReader<UnixStream>) -> Result<Message> {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            anyhow::bail!("Connection closed");
+        }
+        serde_json::from_str(&line).context("Invalid daemon message")
+    }
+
+    pub(super) async fn connect(socket: &Path) -> Result<BufReader<UnixStream>> {
+        let stream = UnixStream::connect(socket).await.with_context(|| {
+            format!(
+                "Failed to connect to the asb daemon at {} (start it with `asb daemon`)",
+                socket.display()
+            )
+        })?;
+        Ok(BufReader::new(stream))
+    }
+
+    pub(super) async fn serve(socket: &Path, builders: Arc<Builders>) -> Result<()> {
+        if socket.exists() {
+            // A socket nobody answers on is left over from a daemon that did not shut down
+            if UnixStream::connect(socket).await.is_ok() {
+                anyhow::bail!("An asb daemon is already listening on {}", socket.display());
+            }
+            std::fs::remove_file(socket)
+                .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+        }
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+        info!("asb daemon listening on {}", socket.display());
+
+        let (stop_tx, mut stop_rx) = tokio::sync::mpsc::channel::<()>(1);
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => accepted?.0,
+                _ = stop_rx.recv() => break,
+            };
+            let builders = builders.clone();
+            let stop_tx = stop_tx.clone();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut stop = false;
+                let reply = match receive(&mut stream).await {
+                    Ok(Message::Build { cwd, configs }) => match same_directory(&cwd) {
+                        Ok(()) => {
+                            info!("Building {} configs", configs.len());
+                            Message::Results {
+                                results: builders.build(configs).await,
+                            }
+                        }
+                        Err(e) => Message::Failed {
+                            error: format!("{:#}", e),
+                        },
+                    },
+                    Ok(Message::Stop) => {
+                        stop = true;
+                        Message::Stopped
+                    }
+                    Ok(other) => Message::Failed {
+                        error: format!("Unexpected message: {:?}", other),
+                    },
+                    Err(e) => Message::Failed {
+                        error: format!("{:#}", e),
+                    },
+                };
+                if let Err(e) = send(&mut stream, &reply).await {
+                    warn!("Failed to answer daemon client: {:#}", e);
+                }
+                // Stop only once the client has its answer
+                if stop {
+                    stop_tx.send(()).await.ok();
+                }
+            });
+        }
+        std::fs::remove_file(socket).ok();
+        info!("asb daemon stopped");
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod transport {
+    use super::*;
+
+    pub(super) struct Unsupported;
+
+    pub(super) async fn send(_: &mut Unsupported, _: &Message) -> Result<()> {
+        unreachable!()
+    }
+
+    pub(super) async fn receive(_: &mut Unsupported) -> Result<Message> {
+        unreachable!()
+    }
+
+    pub(super) async fn connect(_: &Path) -> Result<Unsupported> {
+        anyhow::bail!("The asb daemon needs Unix domain sockets, which this platform lacks")
+    }
+
+    pub(super) async fn serve(_: &Path, _: Arc<Builders>) -> Result<()> {
+        anyhow::bail!("The asb daemon needs Unix domain sockets, which this platform lacks")
+    }
+}
+
+/// Check that a client runs in the daemon's directory, which its relative paths assume
+fn same_directory(cwd: &Path) -> Result<()> {
+    let own = std::env::current_dir()?;
+    if std::fs::canonicalize(cwd)? != std::fs::canonicalize(&own)? {
+        anyhow::bail!(
+            "The daemon serves {}, but the build runs in {}; start a daemon in that directory",
+            own.display(),
+            cwd.display()
+        );
+    }
+    Ok(())
+}
+
+/// Serve build requests on `socket` until a client sends a stop request
+///
+/// With `watch`, builders keep their discovered resource files and verified cache entries
+/// between builds and only rediscover and rehash the files reported as changed.
+pub async fn run_daemon(
+    socket: &Path,
+    max_builds: usize,
+    max_links: usize,
+    watch: bool,
+) -> Result<()> {
+    let builders = Arc::new(Builders {
+        builders: Mutex::new(HashMap::new()),
+        limits: ConcurrencyLimits::new(max_builds, max_links),
+        watch: if watch { Some(Watch::new()?) } else { None },
+    });
+    transport::serve(socket, builders).await
+}
+
+/// Build resolved configs on a running daemon
+pub async fn build_on_daemon(socket: &Path, configs: Vec<BuildConfig>) -> Result<Vec<BuildResult>> {
+    let mut stream = transport::connect(socket).await?;
+    let cwd = std::env::current_dir()?;
+    transport::send(&mut stream, &Message::Build { cwd, configs }).await?;
+    match transport::receive(&mut stream).await? {
+        Message::Results { results } => Ok(results),
+        Message::Failed { error } => anyhow::bail!("Daemon build failed: {}", error),
+        other => anyhow::bail!("Unexpected daemon message: {:?}", other),
+    }
+}
+
+/// Ask a running daemon to exit
+pub async fn stop_daemon(socket: &Path) -> Result<()> {
+    let mut stream = transport::connect(socket).await?;
+    transport::send(&mut stream, &Message::Stop).await?;
+    match transport::receive(&mut stream).await? {
+        Message::Stopped => Ok(()),
+        other => anyhow::bail!("Unexpected daemon message: {:?}", other),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_daemon_builds_and_stops() -> Result<()> {
+        let temp = TempDir::new()?;
+        let socket = temp.path().join("daemon.sock");
+        let daemon = tokio::spawn({
+            let socket = socket.clone();
+            async move { run_daemon(&socket, 1, 1, false).await }
+        });
+        for _ in 0..50 {
+            if socket.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        // A config without a usable aapt2 fails, and the failure comes back as a result
+        let mut config = BuildConfig::default_config();
+        config.aapt2_path = Some(temp.path().join("missing-aapt2"));
+        config.output_dir = temp.path().join("out");
+        let results = build_on_daemon(&socket, vec![config]).await?;
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].success);
+
+        stop_daemon(&socket).await?;
+        daemon.await??;
+        assert!(!socket.exists());
+        Ok(())
+    }
+}
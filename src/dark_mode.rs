@@ -0,0 +1,384 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use walkdir::WalkDir;
+
+use crate::manifest::escape_attr;
+use crate::tokens::{normalize_color, xml_files};
+use crate::types::DarkModeConfig;
+
+/// Light -> dark color mapping file
+#[derive(Debug, Default, Deserialize)]
+pub struct ColorMapping {
+    /// Color resource name -> dark value
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+    /// Light color value -> dark value, applied to colors and drawables
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+impl ColorMapping {
+    /// Load a mapping file; `values` keys are normalized so "#fff" matches "#FFFFFF"
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read color mapping {}", path.display()))?;
+        let mut mapping: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse color mapping {}", path.display()))?;
+        mapping.values = std::mem::take(&mut mapping.values)
+            .into_iter()
+            .map(|(light, dark)| match normalize_color(&light) {
+                Some(light) => Ok((light, dark)),
+                None => anyhow::bail!("Invalid color {} in {}", light, path.display()),
+            })
+            .collect::<Result<_>>()?;
+        Ok(mapping)
+    }
+
+    /// Dark value for a color literal
+    fn map_value(&self, value: &str) -> Option<&str> {
+        self.values
+            .get(&normalize_color(value)?)
+            .map(String::as_str)
+    }
+}
+
+/// Qualifiers that come after `night` in Android's qualifier order
+fn follows_night(qualifier: &str) -> bool {
+    qualifier.ends_with("dpi")
+        || qualifier
+            .strip_prefix('v')
+            .is_some_and(|api| api.parse::<u32>().is_ok())
+        || matches!(
+            qualifier,
+            "notouch"
+                | "finger"
+                | "keysexposed"
+                | "keyshidden"
+                | "keyssoft"
+                | "nokeys"
+                | "qwerty"
+                | "12key"
+                | "navexposed"
+                | "navhidden"
+                | "nonav"
+                | "dpad"
+                | "trackball"
+                | "wheel"
+        )
+}
+
+/// Night-qualified variant of a resource directory name, e.g. "drawable-hdpi" ->
+/// "drawable-night-hdpi"; None when the directory already has a night qualifier
+pub fn night_variant(dir_name: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir_name.split('-').collect();
+    if parts[1..].iter().any(|q| *q == "night" || *q == "notnight") {
+        return None;
+    }
+    let at = parts[1..]
+        .iter()
+        .position(|q| follows_night(q))
+        .map_or(parts.len(), |idx| idx + 1);
+    parts.insert(at, "night");
+    Some(parts.join("-"))
+}
+
+/// Generate dark-mode variants of `res_dirs` (lowest priority first) into `output_res_dir`
+///
+/// Unqualified colors become `values-night/colors.xml` entries when the mapping has their
+/// name or their value; references to other colors are left alone since they follow the
+/// referenced color. Listed XML drawables are copied to the matching `-night` directory with
+/// mapped color literals replaced. Anything that already has a night variant in `res_dirs`
+/// is skipped. Returns the number of generated colors and drawables.
+pub fn generate_dark_variants(
+    res_dirs: &[PathBuf],
+    config: &DarkModeConfig,
+    output_res_dir: &Path,
+) -> Result<usize> {
+    let mapping = ColorMapping::load(&config.color_mapping)?;
+
+    // Light colors, and colors the resource dirs already define for night mode
+    let mut light_colors: BTreeMap<String, String> = BTreeMap::new();
+    let mut night_colors: HashSet<String> = HashSet::new();
+    for res_dir in res_dirs {
+        for (dir, colors) in [
+            ("values", &mut light_colors),
+            ("values-night", &mut BTreeMap::new()),
+        ] {
+            let values_dir = res_dir.join(dir);
+            if !values_dir.is_dir() {
+                continue;
+            }
+            for file in xml_files(&values_dir)? {
+                read_colors(&file, colors)?;
+            }
+            if dir == "values-night" {
+                night_colors.extend(colors.keys().cloned());
+            }
+        }
+    }
+
+    let mut generated: Vec<(PathBuf, String)> = Vec::new();
+    let mut dark_colors = String::new();
+    let mut color_count = 0;
+    for (name, value) in &light_colors {
+        if night_colors.contains(name) {
+            debug!("Color {} already has a night value", name);
+            continue;
+        }
+        let dark = mapping
+            .colors
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| mapping.map_value(value));
+        if let Some(dark) = dark {
+            dark_colors.push_str(&format!(
+                "    <color name=\"{}\">{}</color>\n",
+                escape_attr(name),
+                escape_attr(dark)
+            ));
+            color_count += 1;
+        }
+    }
+    if color_count > 0 {
+        generated.push((
+            output_res_dir.join("values-night").join("colors.xml"),
+            format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- Generated by asb dark mode, do not edit -->\n<resources>\n{}</resources>\n",
+                dark_colors
+            ),
+        ));
+    }
+
+    for name in config.drawables.iter().flatten() {
+        let file_name = format!("{}.xml", name);
+        // Later resource dirs override earlier ones for the same directory name
+        let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
+        let mut existing_night: BTreeSet<String> = BTreeSet::new();
+        for res_dir in res_dirs.iter().filter(|dir| dir.is_dir()) {
+            for entry in fs::read_dir(res_dir)? {
+                let path = entry?.path();
+                let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if !(dir_name == "drawable" || dir_name.starts_with("drawable-")) {
+                    continue;
+                }
+                if !path.join(&file_name).is_file() {
+                    continue;
+                }
+                if night_variant(dir_name).is_some() {
+                    sources.insert(dir_name.to_string(), path.join(&file_name));
+                } else {
+                    existing_night.insert(dir_name.to_string());
+                }
+            }
+        }
+        if sources.is_empty() {
+            anyhow::bail!(
+                "Dark-mode drawable {} not found as an XML drawable in {}",
+                name,
+                res_dirs
+                    .iter()
+                    .map(|d| d.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        for (dir_name, source) in sources {
+            let Some(night_dir) = night_variant(&dir_name) else {
+                continue;
+            };
+            if existing_night.contains(&night_dir) {
+                debug!("Drawable {}/{} already exists", night_dir, file_name);
+                continue;
+            }
+            let content = fs::read_to_string(&source)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            generated.push((
+                output_res_dir.join(night_dir).join(&file_name),
+                recolor(&content, &mapping),
+            ));
+        }
+    }
+
+    let drawable_count = generated.len() - usize::from(color_count > 0);
+    write_generated(output_res_dir, &generated)?;
+    Ok(color_count + drawable_count)
+}
+
+/// Read `<color>` and `<item type="color">` values of a values XML file
+fn read_colors(file: &Path, colors: &mut BTreeMap<String, String>) -> Result<()> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let document = roxmltree::Document::parse(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    for node in document
+        .root_element()
+        .children()
+        .filter(|n| n.is_element())
+    {
+        let is_color = match node.tag_name().name() {
+            "color" => true,
+            "item" => node.attribute("type") == Some("color"),
+            _ => false,
+        };
+        if is_color && let Some(name) = node.attribute("name") {
+            colors.insert(
+                name.to_string(),
+                node.text().unwrap_or_default().trim().to_string(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace mapped color literals in double-quoted attribute values
+fn recolor(content: &str, mapping: &ColorMapping) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("\"#") {
+        output.push_str(&rest[..=start]);
+        let value = &rest[start + 1..];
+        let end = value.find('"').unwrap_or(value.len());
+        output.push_str(mapping.map_value(&value[..end]).unwrap_or(&value[..end]));
+        rest = &value[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Write generated files (only when changed) and remove stale ones from earlier runs
+fn write_generated(output_res_dir: &Path, files: &[(PathBuf, String)]) -> Result<()> {
+    for (path, content) in files {
+        if fs::read_to_string(path).ok().as_deref() != Some(content.as_str()) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+            debug!("Generated {}", path.display());
+        }
+    }
+
+    let expected: HashSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+    for entry in WalkDir::new(output_res_dir).into_iter().flatten() {
+        if entry.file_type().is_file() && !expected.contains(&entry.path().to_path_buf()) {
+            fs::remove_file(entry.path())?;
+            debug!("Removed stale {}", entry.path().display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_night_variant() {
+        assert_eq!(night_variant("values").as_deref(), Some("values-night"));
+        assert_eq!(
+            night_variant("drawable-hdpi").as_deref(),
+            Some("drawable-night-hdpi")
+        );
+        assert_eq!(
+            night_variant("drawable-zh-rCN-v24").as_deref(),
+            Some("drawable-zh-rCN-night-v24")
+        );
+        assert_eq!(
+            night_variant("drawable-land").as_deref(),
+            Some("drawable-land-night")
+        );
+        assert_eq!(night_variant("drawable-night-hdpi"), None);
+        assert_eq!(night_variant("drawable-notnight"), None);
+    }
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_generate_dark_variants() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let shared = temp_dir.path().join("shared");
+        let main = temp_dir.path().join("res");
+        let output = temp_dir.path().join("generated");
+        let mapping = temp_dir.path().join("dark.json");
+        write(
+            &mapping,
+            r##"{"colors": {"primary": "#BB86FC"}, "values": {"#fff": "#121212", "#000000": "#FFFFFF"}}"##,
+        );
+        write(
+            &shared.join("values").join("colors.xml"),
+            r##"<resources><color name="divider">#000000</color></resources>"##,
+        );
+        write(
+            &main.join("values").join("colors.xml"),
+            r##"<resources>
+    <color name="primary">#6200EE</color>
+    <color name="background">#FFFFFF</color>
+    <color name="accent">#FF0000</color>
+    <color name="text">@color/divider</color>
+    <color name="surface">#ffffff</color>
+</resources>"##,
+        );
+        write(
+            &main.join("values-night").join("colors.xml"),
+            r##"<resources><color name="surface">#1E1E1E</color></resources>"##,
+        );
+        write(
+            &main.join("drawable").join("bg_card.xml"),
+            r##"<shape xmlns:android="http://schemas.android.com/apk/res/android"><solid android:color="#FFF"/><stroke android:color="@color/divider"/></shape>"##,
+        );
+        write(
+            &main.join("drawable-v24").join("bg_card.xml"),
+            r##"<shape><solid android:color="#123456"/></shape>"##,
+        );
+        write(
+            &main.join("drawable-night-v24").join("bg_card.xml"),
+            r##"<shape/>"##,
+        );
+
+        let config = DarkModeConfig {
+            color_mapping: mapping,
+            drawables: Some(vec!["bg_card".to_string()]),
+        };
+        let res_dirs = vec![shared, main];
+        // primary, background and divider; surface already has a night value
+        assert_eq!(generate_dark_variants(&res_dirs, &config, &output)?, 4);
+
+        let colors = fs::read_to_string(output.join("values-night").join("colors.xml"))?;
+        assert!(colors.contains(r##"<color name="primary">#BB86FC</color>"##));
+        assert!(colors.contains(r##"<color name="background">#121212</color>"##));
+        assert!(colors.contains(r##"<color name="divider">#FFFFFF</color>"##));
+        assert!(!colors.contains("accent"));
+        assert!(!colors.contains("surface"));
+        assert!(!colors.contains("name=\"text\""));
+
+        let drawable = fs::read_to_string(output.join("drawable-night").join("bg_card.xml"))?;
+        assert!(drawable.contains(r##"android:color="#121212""##));
+        assert!(drawable.contains(r##"android:color="@color/divider""##));
+        assert!(!output.join("drawable-night-v24").exists());
+
+        // Dropping the drawable removes its generated copy
+        let config = DarkModeConfig {
+            drawables: None,
+            ..config
+        };
+        assert_eq!(generate_dark_variants(&res_dirs, &config, &output)?, 3);
+        assert!(!output.join("drawable-night").join("bg_card.xml").exists());
+
+        let config = DarkModeConfig {
+            drawables: Some(vec!["missing".to_string()]),
+            ..config
+        };
+        assert!(generate_dark_variants(&res_dirs, &config, &output).is_err());
+        Ok(())
+    }
+}
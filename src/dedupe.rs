@@ -0,0 +1,121 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::resource_priority::ResourcePriority;
+
+/// Resource files of one source set, about to be compiled
+#[derive(Debug, Clone)]
+pub struct SourceSet {
+    pub res_dir: PathBuf,
+    pub priority: ResourcePriority,
+    pub files: Vec<PathBuf>,
+}
+
+/// Resource files dropped because a higher-priority source set has an identical copy
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DedupeReport {
+    /// Dropped file with the copy that is kept in its place
+    pub duplicates: Vec<(PathBuf, PathBuf)>,
+    /// Total size of the dropped files
+    pub bytes: u64,
+}
+
+/// Drop resource files that are byte-identical to the same resource in a higher-priority
+/// source set
+///
+/// The higher-priority copy overrides the lower one at link time anyway, so compiling and
+/// linking both only costs time. A resource is the same when its path relative to the
+/// resource directory matches, e.g. `drawable-hdpi/logo.png`. Only paths present in more
+/// than one source set are hashed.
+pub fn dedupe_identical_resources(sets: &mut [SourceSet]) -> Result<DedupeReport> {
+    // Source sets holding each relative path, by set index
+    let mut by_relative: HashMap<PathBuf, Vec<(usize, PathBuf)>> = HashMap::new();
+    for (idx, set) in sets.iter().enumerate() {
+        for file in &set.files {
+            if let Ok(relative) = file.strip_prefix(&set.res_dir) {
+                by_relative
+                    .entry(relative.to_path_buf())
+                    .or_default()
+                    .push((idx, file.clone()));
+            }
+        }
+    }
+
+    let mut report = DedupeReport::default();
+    let mut dropped: HashMap<usize, Vec<PathBuf>> = HashMap::new();
+    for mut copies in by_relative.into_values().filter(|c| c.len() > 1) {
+        // Highest priority first; it is the copy that wins at link time
+        copies.sort_by_key(|(idx, _)| std::cmp::Reverse(sets[*idx].priority.value()));
+        let mut kept: Vec<(String, PathBuf)> = Vec::new();
+        for (idx, file) in copies {
+            let hash = file_hash(&file)?;
+            match kept.iter().find(|(h, _)| *h == hash) {
+                Some((_, winner)) => {
+                    report.bytes += std::fs::metadata(&file)?.len();
+                    report.duplicates.push((file.clone(), winner.clone()));
+                    dropped.entry(idx).or_default().push(file);
+                }
+                None => kept.push((hash, file)),
+            }
+        }
+    }
+
+    for (idx, files) in dropped {
+        sets[idx].files.retain(|file| !files.contains(file));
+    }
+    report.duplicates.sort();
+    Ok(report)
+}
+
+fn file_hash(path: &Path) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(std::fs::read(path)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &[u8]) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn test_dedupe_keeps_highest_priority_copy() -> Result<()> {
+        let temp = TempDir::new()?;
+        let main = temp.path().join("main");
+        let brand = temp.path().join("brand");
+
+        let main_logo = write(&main.join("drawable/logo.png"), b"logo");
+        let brand_logo = write(&brand.join("drawable/logo.png"), b"logo");
+        let main_bg = write(&main.join("drawable/bg.png"), b"new");
+        let brand_bg = write(&brand.join("drawable/bg.png"), b"old");
+        let brand_only = write(&brand.join("drawable/icon.png"), b"logo");
+
+        let mut sets = vec![
+            SourceSet {
+                res_dir: main.clone(),
+                priority: ResourcePriority::Main,
+                files: vec![main_logo.clone(), main_bg.clone()],
+            },
+            SourceSet {
+                res_dir: brand.clone(),
+                priority: ResourcePriority::Additional(0),
+                files: vec![brand_logo.clone(), brand_bg.clone(), brand_only.clone()],
+            },
+        ];
+
+        let report = dedupe_identical_resources(&mut sets)?;
+        assert_eq!(report.duplicates, vec![(brand_logo, main_logo.clone())]);
+        assert_eq!(report.bytes, 4);
+        assert_eq!(sets[0].files, vec![main_logo, main_bg]);
+        // Different content or a different resource path keeps both files
+        assert_eq!(sets[1].files, vec![brand_bg, brand_only]);
+        Ok(())
+    }
+}
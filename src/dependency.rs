@@ -1,8 +1,10 @@
 use anyhow::Result;
+use path_absolutize::Absolutize;
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
 
+use crate::cache::BuildCache;
 use crate::types::BuildConfig;
 
 /// Represents a build configuration with its index for ordering
@@ -50,12 +52,66 @@ pub fn group_configs_by_dependencies(
         ));
     }
 
+    let dependencies = config_dependencies(&configs);
+
+    // Perform topological sort to determine build order
+    let sorted_indices = topological_sort(configs.len(), &dependencies)?;
+
+    // Separate into independent and dependent groups
+    let mut independent = Vec::new();
+    let mut dependent_groups: Vec<Vec<ConfigWithIndex>> = Vec::new();
+    let mut current_group: Vec<ConfigWithIndex> = Vec::new();
+    let mut in_dependency_chain = HashSet::new();
+
+    // Mark all configs that are part of dependency chains
+    for (&config_idx, deps) in &dependencies {
+        in_dependency_chain.insert(config_idx);
+        for &dep in deps {
+            in_dependency_chain.insert(dep);
+        }
+    }
+
+    // Process sorted indices
+    for idx in sorted_indices {
+        let config = configs[idx].clone();
+        let config_with_idx = ConfigWithIndex { index: idx, config };
+
+        if in_dependency_chain.contains(&idx) {
+            current_group.push(config_with_idx);
+        } else {
+            independent.push(config_with_idx);
+        }
+    }
+
+    if !current_group.is_empty() {
+        dependent_groups.push(current_group);
+    }
+
+    Ok((independent, dependent_groups))
+}
+
+/// Find which configurations each configuration depends on
+///
+/// A configuration depends on another if one of its `additionalResourceDirs` is the main
+/// resource directory of the other configuration.
+///
+/// # Arguments
+///
+/// * `configs` - The list of build configurations to analyze
+///
+/// # Returns
+///
+/// A map where keys are dependent config indices and values are the indices of the configs
+/// they depend on. Configurations without dependencies are not included.
+pub fn config_dependencies(configs: &[BuildConfig]) -> HashMap<usize, Vec<usize>> {
     // Build a map of resource directories to config indices that use them
     let mut resource_dir_to_configs: HashMap<String, HashSet<usize>> = HashMap::new();
 
     for (idx, config) in configs.iter().enumerate() {
+        let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+
         // Normalize and register the main resource directory
-        let main_res = normalize_path(&config.resource_dir);
+        let main_res = normalize_path(&config.resource_dir, follow_symlinks);
         resource_dir_to_configs
             .entry(main_res)
             .or_default()
@@ -64,7 +120,7 @@ pub fn group_configs_by_dependencies(
         // Register additional resource directories if present
         if let Some(additional_dirs) = &config.additional_resource_dirs {
             for dir in additional_dirs {
-                let normalized = normalize_path(dir);
+                let normalized = normalize_path(dir, follow_symlinks);
                 resource_dir_to_configs
                     .entry(normalized)
                     .or_default()
@@ -78,11 +134,12 @@ pub fn group_configs_by_dependencies(
 
     for (idx, config) in configs.iter().enumerate() {
         let mut deps = Vec::new();
+        let follow_symlinks = config.follow_symlinks.unwrap_or(false);
 
         // Check if any of this config's additional resource dirs are provided by other configs
         if let Some(additional_dirs) = &config.additional_resource_dirs {
             for dir in additional_dirs {
-                let normalized = normalize_path(dir);
+                let normalized = normalize_path(dir, follow_symlinks);
 
                 // Find which configs provide this resource directory
                 if let Some(providers) = resource_dir_to_configs.get(&normalized) {
@@ -91,7 +148,11 @@ pub fn group_configs_by_dependencies(
                         // and it's not itself
                         if provider_idx != idx {
                             // Check if provider_idx's main resource_dir matches this additional dir
-                            let provider_main = normalize_path(&configs[provider_idx].resource_dir);
+                            let provider = &configs[provider_idx];
+                            let provider_main = normalize_path(
+                                &provider.resource_dir,
+                                provider.follow_symlinks.unwrap_or(false),
+                            );
                             if provider_main == normalized {
                                 deps.push(provider_idx);
                             }
@@ -106,64 +167,42 @@ pub fn group_configs_by_dependencies(
         }
     }
 
-    // Perform topological sort to determine build order
-    let sorted_indices = topological_sort(configs.len(), &dependencies)?;
-
-    // Separate into independent and dependent groups
-    let mut independent = Vec::new();
-    let mut dependent_groups: Vec<Vec<ConfigWithIndex>> = Vec::new();
-    let mut current_group: Vec<ConfigWithIndex> = Vec::new();
-    let mut in_dependency_chain = HashSet::new();
-
-    // Mark all configs that are part of dependency chains
-    for (&config_idx, deps) in &dependencies {
-        in_dependency_chain.insert(config_idx);
-        for &dep in deps {
-            in_dependency_chain.insert(dep);
-        }
-    }
-
-    // Process sorted indices
-    for idx in sorted_indices {
-        let config = configs[idx].clone();
-        let config_with_idx = ConfigWithIndex { index: idx, config };
-
-        if in_dependency_chain.contains(&idx) {
-            current_group.push(config_with_idx);
-        } else {
-            independent.push(config_with_idx);
-        }
-    }
-
-    if !current_group.is_empty() {
-        dependent_groups.push(current_group);
-    }
-
-    Ok((independent, dependent_groups))
+    dependencies
 }
 
 /// Normalize a path to a string for comparison purposes
 ///
-/// Attempts to convert the path to an absolute canonical path to ensure that different
-/// representations of the same path (e.g., relative vs absolute, with/without trailing slashes)
-/// are treated as equal. If canonicalization fails (e.g., path doesn't exist yet), falls back
-/// to normalizing the string representation by replacing backslashes with forward slashes.
+/// When `follow_symlinks` is enabled, the path is canonicalized so that a symlinked resource
+/// directory and its target are treated as the same directory. Otherwise the path is only made
+/// absolute lexically (resolving `.` and `..` without touching the filesystem), so that a
+/// symlink is treated as a directory in its own right rather than silently collapsing onto its
+/// target. Backslashes are replaced with forward slashes in both cases.
 ///
 /// # Arguments
 ///
 /// * `path` - The path to normalize
+/// * `follow_symlinks` - Whether symbolic links should be resolved
 ///
 /// # Returns
 ///
 /// A normalized string representation of the path suitable for comparison
-fn normalize_path(path: &PathBuf) -> String {
-    // Convert to absolute path if possible, otherwise use as-is
-    if let Ok(abs_path) = std::fs::canonicalize(path) {
-        abs_path.to_string_lossy().to_string()
+fn normalize_path(path: &Path, follow_symlinks: bool) -> String {
+    let normalized = if follow_symlinks {
+        std::fs::canonicalize(path).ok()
     } else {
-        // If path doesn't exist yet, just normalize the string representation
-        path.to_string_lossy().replace('\\', "/")
-    }
+        None
+    };
+
+    let normalized = match normalized {
+        Some(resolved) => resolved,
+        // Lexical normalization also covers paths that don't exist yet
+        None => path
+            .absolutize()
+            .map(|p| p.into_owned())
+            .unwrap_or_else(|_| path.to_path_buf()),
+    };
+
+    normalized.to_string_lossy().replace('\\', "/")
 }
 
 /// Perform topological sort on the dependency graph using Kahn's algorithm
@@ -258,7 +297,10 @@ pub fn extract_common_dependencies(configs: &[BuildConfig]) -> Vec<CommonDepende
 
     // First pass: collect main resource directories
     for config in configs.iter() {
-        let main_res = normalize_path(&config.resource_dir);
+        let main_res = normalize_path(
+            &config.resource_dir,
+            config.follow_symlinks.unwrap_or(false),
+        );
         main_resource_dirs.insert(main_res.clone());
     }
 
@@ -266,7 +308,7 @@ pub fn extract_common_dependencies(configs: &[BuildConfig]) -> Vec<CommonDepende
     for (idx, config) in configs.iter().enumerate() {
         if let Some(additional_dirs) = &config.additional_resource_dirs {
             for dir in additional_dirs {
-                let normalized = normalize_path(dir);
+                let normalized = normalize_path(dir, config.follow_symlinks.unwrap_or(false));
                 // Track all additional resource dirs, not just those that are main dirs
                 resource_usage
                     .entry(normalized)
@@ -286,7 +328,10 @@ pub fn extract_common_dependencies(configs: &[BuildConfig]) -> Vec<CommonDepende
             // Prefer using the main resource dir PathBuf if available, otherwise use from additional dirs
             let path_buf = configs
                 .iter()
-                .find(|c| normalize_path(&c.resource_dir) == resource_path)
+                .find(|c| {
+                    normalize_path(&c.resource_dir, c.follow_symlinks.unwrap_or(false))
+                        == resource_path
+                })
                 .map(|c| c.resource_dir.clone())
                 .unwrap_or(original_path);
 
@@ -305,9 +350,41 @@ pub fn extract_common_dependencies(configs: &[BuildConfig]) -> Vec<CommonDepende
     common_deps
 }
 
+/// Order configs longest first by the build time recorded in their cache
+///
+/// Starting the longest builds first (LPT scheduling) keeps one large skin from running alone
+/// at the end of a parallel build. Configs without recorded durations go first since their
+/// cost is unknown; equal durations keep config order.
+///
+/// # Returns
+///
+/// The number of configs with recorded durations
+pub fn order_by_build_time(configs: &mut [ConfigWithIndex]) -> usize {
+    let mut keyed: Vec<(Option<u64>, ConfigWithIndex)> = configs
+        .iter()
+        .map(|c| {
+            let total_ms = BuildCache::new(c.config.cache_path())
+                .ok()
+                .and_then(|cache| cache.last_durations())
+                .map(|d| d.total_ms());
+            (total_ms, c.clone())
+        })
+        .collect();
+    let known = keyed.iter().filter(|(ms, _)| ms.is_some()).count();
+    keyed.sort_by_key(|(ms, _)| std::cmp::Reverse(ms.unwrap_or(u64::MAX)));
+    for (slot, (ms, config)) in configs.iter_mut().zip(keyed) {
+        if let Some(ms) = ms {
+            debug!("{}: {} ms last build", config.config.package_name, ms);
+        }
+        *slot = config;
+    }
+    known
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::BuildDurations;
     use std::path::Path;
 
     // Helper function to create a test config with minimal required fields
@@ -336,6 +413,57 @@ mod tests {
             package_id: None,
             precompiled_dependencies: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_common_deps: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            owners: None,
+            only_resource_types: None,
+            app: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         }
     }
 
@@ -453,6 +581,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_order_by_build_time() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut configs: Vec<ConfigWithIndex> = ["small", "new", "large", "medium"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let mut config = test_config("./res", &format!("com.example.{}", name), None);
+                config.build_dir = Some(temp_dir.path().to_path_buf());
+                ConfigWithIndex { index, config }
+            })
+            .collect();
+        for (idx, compile_ms) in [(0, 100), (2, 5000), (3, 800)] {
+            let mut cache = BuildCache::new(configs[idx].config.cache_path()).unwrap();
+            cache.init().unwrap();
+            cache.set_last_durations(BuildDurations {
+                compile_ms,
+                link_ms: 50,
+            });
+            cache.save().unwrap();
+        }
+
+        assert_eq!(order_by_build_time(&mut configs), 3);
+        let order: Vec<usize> = configs.iter().map(|c| c.index).collect();
+        assert_eq!(order, vec![1, 2, 3, 0]);
+    }
+
     #[test]
     fn test_extract_common_dependencies_none() {
         // Single config should have no common dependencies
@@ -548,6 +703,7 @@ mod tests {
         // Base config
         let base_app = AppConfig {
             base_dir: None,
+            matrix: None,
             resource_dir: Some(PathBuf::from("./base/res")),
             manifest_path: Some(PathBuf::from("./base/AndroidManifest.xml")),
             package_name: "com.example.base".to_string(),
@@ -559,11 +715,27 @@ mod tests {
             flavors: None,
             package_id: None,
             assets_dir: None,
+            design_tokens: None,
+            resource_prefix: None,
+            aliases: None,
+            host_symbols: None,
+            owners: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            strict: None,
+            size_budget: None,
+            conditional_resource_dirs: None,
+            name: None,
+            aar_filters: None,
+            follow_symlinks: None,
         };
 
         // App with flavors that both depend on base
         let app_with_flavors = AppConfig {
             base_dir: None,
+            matrix: None,
             resource_dir: Some(PathBuf::from("./app/res")),
             manifest_path: Some(PathBuf::from("./app/AndroidManifest.xml")),
             package_name: "com.example.app".to_string(),
@@ -586,6 +758,8 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
                 FlavorConfig {
                     name: "flavor2".to_string(),
@@ -600,13 +774,31 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
             ]),
             package_id: None,
             assets_dir: None,
+            design_tokens: None,
+            resource_prefix: None,
+            aliases: None,
+            host_symbols: None,
+            owners: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            strict: None,
+            size_budget: None,
+            conditional_resource_dirs: None,
+            name: None,
+            aar_filters: None,
+            follow_symlinks: None,
         };
 
         let multi_config = MultiAppConfig {
+            config_version: None,
             base_dir: None,
             output_dir: PathBuf::from("./build"),
             output_file: None,
@@ -623,6 +815,54 @@ mod tests {
             package_id: None,
             assets_dir: None,
             apps: vec![base_app, app_with_flavors],
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_dependencies: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            max_parallel_links: None,
+            bundle: None,
+            owners: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         // Convert to BuildConfigs
@@ -655,6 +895,7 @@ mod tests {
 
         let app_a = AppConfig {
             base_dir: Some(PathBuf::from("./a/src/main")),
+            matrix: None,
             resource_dir: None,
             manifest_path: None,
             package_name: "com.a".to_string(),
@@ -677,6 +918,8 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
                 FlavorConfig {
                     name: "day".to_string(),
@@ -691,14 +934,32 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
             ]),
             package_id: None,
             assets_dir: None,
+            design_tokens: None,
+            resource_prefix: None,
+            aliases: None,
+            host_symbols: None,
+            owners: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            strict: None,
+            size_budget: None,
+            conditional_resource_dirs: None,
+            name: None,
+            aar_filters: None,
+            follow_symlinks: None,
         };
 
         let app_b = AppConfig {
             base_dir: Some(PathBuf::from("./b/src/main")),
+            matrix: None,
             resource_dir: None,
             manifest_path: None,
             package_name: "com.b".to_string(),
@@ -721,6 +982,8 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
                 FlavorConfig {
                     name: "day".to_string(),
@@ -735,13 +998,31 @@ mod tests {
                     version_name: None,
                     package_id: None,
                     assets_dir: None,
+                    design_tokens: None,
+                    follow_symlinks: None,
                 },
             ]),
             package_id: None,
             assets_dir: None,
+            design_tokens: None,
+            resource_prefix: None,
+            aliases: None,
+            host_symbols: None,
+            owners: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            strict: None,
+            size_budget: None,
+            conditional_resource_dirs: None,
+            name: None,
+            aar_filters: None,
+            follow_symlinks: None,
         };
 
         let multi_config = MultiAppConfig {
+            config_version: None,
             base_dir: None,
             output_dir: PathBuf::from("./build"),
             output_file: None,
@@ -758,6 +1039,54 @@ mod tests {
             package_id: None,
             assets_dir: None,
             apps: vec![app_a, app_b],
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            static_lib_dependencies: None,
+            precompiled_dependencies: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            host_symbols: None,
+            max_parallel_links: None,
+            bundle: None,
+            owners: None,
+            missing_resource_dir_policy: None,
+            aar_filters: None,
+            aapt2_limits: None,
+            heartbeat: None,
         };
 
         // Convert to BuildConfigs
@@ -795,4 +1124,47 @@ mod tests {
         assert_eq!(night_dep.unwrap().dependent_configs.len(), 2);
         assert_eq!(day_dep.unwrap().dependent_configs.len(), 2);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlinked_dependency_requires_follow_symlinks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let base_res = temp_dir.path().join("base").join("res");
+        let linked_res = temp_dir.path().join("linked_res");
+        std::fs::create_dir_all(&base_res).unwrap();
+        std::os::unix::fs::symlink(&base_res, &linked_res).unwrap();
+
+        let make_configs = |follow_symlinks| {
+            let mut base = test_config(base_res.to_str().unwrap(), "com.example.base", None);
+            let mut feature = test_config(
+                "./feature/res",
+                "com.example.feature",
+                Some(vec![linked_res.clone()]),
+            );
+            base.follow_symlinks = Some(follow_symlinks);
+            feature.follow_symlinks = Some(follow_symlinks);
+            vec![base, feature]
+        };
+
+        // Without following links, the symlink is a distinct directory
+        let (independent, groups) = group_configs_by_dependencies(make_configs(false)).unwrap();
+        assert_eq!(independent.len(), 2);
+        assert!(groups.is_empty());
+
+        // Following links resolves it to the base app's resource directory
+        let (independent, groups) = group_configs_by_dependencies(make_configs(true)).unwrap();
+        assert!(independent.is_empty());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0][0].config.package_name, "com.example.base");
+        assert_eq!(groups[0][1].config.package_name, "com.example.feature");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_normalize_path_lexical() {
+        assert_eq!(
+            normalize_path(Path::new("/a/b/../c/./res"), false),
+            "/a/c/res"
+        );
+    }
 }
@@ -0,0 +1,161 @@
+use colored::{ColoredString, Colorize};
+use std::path::{Path, PathBuf};
+
+use crate::events::parse_diagnostic;
+
+/// Source lines shown before and after the line an error points at
+const CONTEXT_LINES: usize = 2;
+
+/// An aapt2 error pointing at a line of a resource file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SourceError {
+    line: u32,
+    message: String,
+}
+
+/// Render build errors for people fixing them
+///
+/// aapt2 lines such as `res/values/colors.xml:12: error: ...` that point at a readable file
+/// are grouped per file and shown with the offending line, its context and a caret
+/// underline, like rustc does. Everything else in `errors` is kept as it was, after the
+/// grouped files. `color` adds ANSI styling (subject to `NO_COLOR` and `--no-color`).
+pub fn render_errors(errors: &[String], color: bool) -> Vec<String> {
+    let mut files: Vec<(PathBuf, Vec<SourceError>)> = Vec::new();
+    let mut rest = Vec::new();
+    for error in errors {
+        let mut remaining = Vec::new();
+        for line in error.lines() {
+            match parse_diagnostic(line.trim()) {
+                (Some(file), Some(number), message) if number > 0 && file.is_file() => {
+                    let error = SourceError {
+                        line: number,
+                        message: message.to_string(),
+                    };
+                    match files.iter_mut().find(|(known, _)| *known == file) {
+                        Some((_, errors)) => errors.push(error),
+                        None => files.push((file, vec![error])),
+                    }
+                }
+                _ => remaining.push(line),
+            }
+        }
+        let remaining = remaining.join("\n");
+        if !remaining.trim().is_empty() {
+            rest.push(remaining.trim_end().to_string());
+        }
+    }
+
+    let mut rendered: Vec<String> = files
+        .into_iter()
+        .map(|(file, errors)| render_file(&file, errors, color))
+        .collect();
+    rendered.extend(rest);
+    rendered
+}
+
+/// Render the errors of one file, in line order
+fn render_file(file: &Path, mut errors: Vec<SourceError>, color: bool) -> String {
+    errors.sort_by_key(|error| error.line);
+    errors.dedup();
+    let source = String::from_utf8_lossy(&std::fs::read(file).unwrap_or_default()).into_owned();
+    let lines: Vec<String> = source.lines().map(|l| l.replace('\t', "    ")).collect();
+    let paint = |text: &str, style: fn(&str) -> ColoredString| {
+        if color {
+            style(text).to_string()
+        } else {
+            text.to_string()
+        }
+    };
+
+    let last_shown = errors.last().map_or(0, |e| e.line as usize + CONTEXT_LINES);
+    let width = last_shown.min(lines.len()).max(1).to_string().len();
+    let gutter = |number: Option<usize>| {
+        let number = number.map_or(String::new(), |n| n.to_string());
+        paint(&format!("{:>width$} |", number), |s| s.blue().bold())
+    };
+
+    let count = match errors.len() {
+        1 => "1 error".to_string(),
+        n => format!("{} errors", n),
+    };
+    let mut out = paint(&format!("{} ({})", file.display(), count), |s| s.bold());
+    for error in &errors {
+        out.push_str(&format!(
+            "\n{}: {}\n{}{} {}:{}",
+            paint("error", |s| s.red().bold()),
+            error.message,
+            " ".repeat(width),
+            paint("-->", |s| s.blue().bold()),
+            file.display(),
+            error.line
+        ));
+        let index = error.line as usize - 1;
+        if index >= lines.len() {
+            continue;
+        }
+        out.push_str(&format!("\n{}", gutter(None)));
+        let first = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(lines.len());
+        for (i, text) in lines.iter().enumerate().take(end).skip(first) {
+            out.push_str(&format!("\n{} {}", gutter(Some(i + 1)), text));
+            if i == index {
+                let content = text.trim_start();
+                let indent = text.len() - content.len();
+                let carets = "^".repeat(content.trim_end().chars().count().max(1));
+                out.push_str(&format!(
+                    "\n{} {}{}",
+                    gutter(None),
+                    " ".repeat(indent),
+                    paint(&carets, |s| s.red().bold())
+                ));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_errors_groups_excerpts_per_file() {
+        let temp = TempDir::new().unwrap();
+        let colors = temp.path().join("colors.xml");
+        std::fs::write(
+            &colors,
+            "<resources>\n    <color name=\"a\">#fff</color>\n    <color name=\"b\">#ggg</color>\n</resources>\n",
+        )
+        .unwrap();
+        let errors = vec![
+            format!(
+                "aapt2 compile failed:\n{0}:3: error: invalid color.\n{0}:99: error: bad.",
+                colors.display()
+            ),
+            "missing.xml:1: error: not found.".to_string(),
+        ];
+
+        let rendered = render_errors(&errors, false);
+        assert_eq!(rendered.len(), 3);
+        let file = colors.display();
+        assert_eq!(
+            rendered[0],
+            format!(
+                "{file} (2 errors)
+error: invalid color.
+ --> {file}:3
+  |
+1 | <resources>
+2 |     <color name=\"a\">#fff</color>
+3 |     <color name=\"b\">#ggg</color>
+  |     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+4 | </resources>
+error: bad.
+ --> {file}:99"
+            )
+        );
+        assert_eq!(rendered[1], "aapt2 compile failed:");
+        assert_eq!(rendered[2], "missing.xml:1: error: not found.");
+    }
+}
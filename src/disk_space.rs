@@ -0,0 +1,242 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::cache::BuildCache;
+use crate::size_budget::format_bytes;
+use crate::types::{AarFile, BuildConfig};
+
+/// Without a previous build to go by, intermediates, staged copies and the package
+/// together take about this many times the size of the inputs
+const INPUT_SIZE_FACTOR: u64 = 3;
+
+/// Free space of the filesystem a path is on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreeSpace {
+    /// Mount point, or drive root on Windows
+    pub mount: PathBuf,
+    pub available: u64,
+}
+
+/// Total size of the files below `dir`, 0 when it does not exist
+pub fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Free space of the filesystem `path` would be created on
+///
+/// Asks `df` (PowerShell on Windows) about the nearest existing ancestor; `None` when the
+/// space cannot be determined.
+pub fn available_space(path: &Path) -> Option<FreeSpace> {
+    let absolute = std::path::absolute(path).ok()?;
+    let existing = absolute.ancestors().find(|p| p.exists())?;
+    if cfg!(windows) {
+        let script = format!(
+            "$d = (Get-Item -LiteralPath '{}').PSDrive; $d.Root; $d.Free",
+            existing.display().to_string().replace('\'', "''")
+        );
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines().map(str::trim);
+        Some(FreeSpace {
+            mount: PathBuf::from(lines.next()?),
+            available: lines.next()?.parse().ok()?,
+        })
+    } else {
+        let output = Command::new("df").arg("-Pk").arg(existing).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_df(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Parse the output of `df -Pk` for a single path
+fn parse_df(output: &str) -> Option<FreeSpace> {
+    let line = output.lines().nth(1)?;
+    let mut columns = line.split_whitespace();
+    let available_kb: u64 = columns.nth(3)?.parse().ok()?;
+    // The capacity column, then the mount point, which may contain spaces
+    columns.next()?;
+    let mount = columns.collect::<Vec<_>>().join(" ");
+    if mount.is_empty() {
+        return None;
+    }
+    Some(FreeSpace {
+        mount: PathBuf::from(mount),
+        available: available_kb * 1024,
+    })
+}
+
+/// Bytes of resources, AARs and assets a config builds from
+fn input_bytes(config: &BuildConfig) -> u64 {
+    std::iter::once(config.resource_dir.as_path())
+        .chain(
+            config
+                .additional_resource_dirs
+                .iter()
+                .flatten()
+                .map(PathBuf::as_path),
+        )
+        .chain(config.aar_files.iter().flatten().map(AarFile::path))
+        .chain(config.assets_dir.as_deref())
+        .map(dir_size)
+        .sum()
+}
+
+/// Bytes a build of `config` is expected to add to its build directory and output
+///
+/// Uses the disk usage recorded by the last successful incremental build, else a multiple
+/// of the input size, less what the build directory already holds.
+pub fn estimate_bytes(config: &BuildConfig) -> u64 {
+    let cache_path = config.cache_path();
+    let history = BuildCache::new(cache_path.clone())
+        .ok()
+        .and_then(|cache| cache.last_disk_usage());
+    let total = history.unwrap_or_else(|| input_bytes(config) * INPUT_SIZE_FACTOR);
+    total.saturating_sub(dir_size(&cache_path))
+}
+
+/// What the builds ask of one filesystem
+#[derive(Debug, Default)]
+struct DiskDemand {
+    available: u64,
+    /// Bytes the builds are estimated to write
+    estimated: u64,
+    packages: usize,
+    /// Bytes to keep free on top of the estimate
+    reserve: u64,
+    /// Incremental caches that may be pruned
+    prunable: Vec<PathBuf>,
+}
+
+impl DiskDemand {
+    fn required(&self) -> u64 {
+        self.estimated + self.reserve
+    }
+}
+
+/// Demand on the filesystem `path` is on, `None` when its free space is unknown
+fn demand_for<'a>(
+    disks: &'a mut BTreeMap<PathBuf, DiskDemand>,
+    path: &Path,
+) -> Option<&'a mut DiskDemand> {
+    let Some(free) = available_space(path) else {
+        debug!("Cannot determine free disk space of {}", path.display());
+        return None;
+    };
+    Some(disks.entry(free.mount).or_insert_with(|| DiskDemand {
+        available: free.available,
+        ..Default::default()
+    }))
+}
+
+/// Check that the disks of the output, build and temp directories have room for the
+/// builds of the configs with `diskSpace`, before any of them starts
+///
+/// When a disk is short and `pruneCache` is set, stale entries of the incremental caches
+/// on it are deleted first. Fails with the space needed and available on each disk that
+/// stays short.
+pub fn check_disk_space(configs: &[BuildConfig]) -> Result<()> {
+    let mut disks: BTreeMap<PathBuf, DiskDemand> = BTreeMap::new();
+    for config in configs {
+        let Some(disk_space) = &config.disk_space else {
+            continue;
+        };
+        let min_free = disk_space.min_free_bytes.unwrap_or(0);
+        let cache_path = config.cache_path();
+        if let Some(disk) = demand_for(&mut disks, &cache_path) {
+            disk.estimated += estimate_bytes(config);
+            disk.packages += 1;
+            disk.reserve = disk.reserve.max(min_free);
+            if disk_space.prune_cache.unwrap_or(false) {
+                disk.prunable.push(cache_path);
+            }
+        }
+        if let Some(disk) = demand_for(&mut disks, &config.output_dir) {
+            disk.reserve = disk.reserve.max(min_free);
+        }
+        if let Some(min_temp) = disk_space.min_temp_bytes
+            && let Some(disk) = demand_for(&mut disks, &std::env::temp_dir())
+        {
+            disk.reserve = disk.reserve.max(min_temp);
+        }
+    }
+
+    let mut errors = Vec::new();
+    for (mount, disk) in &mut disks {
+        if disk.available >= disk.required() {
+            debug!(
+                "{} has {} free, builds need {}",
+                mount.display(),
+                format_bytes(disk.available),
+                format_bytes(disk.required())
+            );
+            continue;
+        }
+        if !disk.prunable.is_empty() {
+            let mut freed = 0;
+            for cache_path in &disk.prunable {
+                match BuildCache::new(cache_path.clone()).and_then(|cache| cache.prune_store()) {
+                    Ok(bytes) => freed += bytes,
+                    Err(e) => warn!("Failed to prune cache {}: {:#}", cache_path.display(), e),
+                }
+            }
+            info!(
+                "Pruned {} of stale cache entries on {}",
+                format_bytes(freed),
+                mount.display()
+            );
+            disk.available += freed;
+            if disk.available >= disk.required() {
+                continue;
+            }
+        }
+        errors.push(format!(
+            "Not enough disk space on {}: {} free, {} needed ({} estimated for {} package(s) and {} to keep free)",
+            mount.display(),
+            format_bytes(disk.available),
+            format_bytes(disk.required()),
+            format_bytes(disk.estimated),
+            disk.packages,
+            format_bytes(disk.reserve)
+        ));
+    }
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "{}\nFree up space, lower diskSpace.minFreeBytes or set diskSpace.pruneCache to prune stale cache entries",
+            errors.join("\n")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                      /dev/sda1        102400000  81920000  20480000      80% /mnt/ci data\n";
+        assert_eq!(
+            parse_df(output),
+            Some(FreeSpace {
+                mount: PathBuf::from("/mnt/ci data"),
+                available: 20480000 * 1024,
+            })
+        );
+        assert_eq!(parse_df("Filesystem\n"), None);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::aapt2::Aapt2;
+use crate::types::BuildConfig;
+
+/// Oldest aapt2 release known to support every flag asb passes to it
+pub const MIN_AAPT2_VERSION: (u32, u32) = (2, 19);
+
+/// Outcome of a single environment check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Result of one `asb doctor` check, with a suggested fix when it did not pass
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn error(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Error,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Check the build environment of `configs`
+///
+/// `aapt2_path` overrides the aapt2 of the configs; without either, aapt2 is looked up
+/// the same way a build does.
+pub fn run_checks(aapt2_path: Option<PathBuf>, configs: &[BuildConfig]) -> Vec<Check> {
+    let aapt2_path = aapt2_path.or_else(|| configs.iter().find_map(|c| c.aapt2_path.clone()));
+    let mut checks = check_aapt2(aapt2_path);
+    checks.push(check_android_home(
+        std::env::var("ANDROID_HOME").ok(),
+        std::env::var("ANDROID_SDK_ROOT").ok(),
+    ));
+    checks.push(check_android_jars(configs));
+    checks.push(check_writable_dirs(configs));
+    if cfg!(windows) {
+        checks.push(check_long_paths());
+    }
+    checks
+}
+
+/// Parse the `major.minor` release out of `aapt2 version` output
+///
+/// e.g. `Android Asset Packaging Tool (aapt) 2.19-10229193`
+pub fn parse_aapt2_version(output: &str) -> Option<(u32, u32)> {
+    let release = output.split_whitespace().find(|word| {
+        word.chars().next().is_some_and(|c| c.is_ascii_digit()) && word.contains('.')
+    })?;
+    let release = release.split('-').next()?;
+    let mut parts = release.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// aapt2 can be found, runs, and is recent enough
+fn check_aapt2(aapt2_path: Option<PathBuf>) -> Vec<Check> {
+    let aapt2 = match Aapt2::new(aapt2_path) {
+        Ok(aapt2) => aapt2,
+        Err(e) => {
+            return vec![Check::error(
+                "aapt2",
+                e.to_string(),
+                "Install the Android SDK build-tools and set ANDROID_HOME, put aapt2 on PATH, or set aapt2Path / --aapt2",
+            )];
+        }
+    };
+    let version = match aapt2.version() {
+        Ok(version) if !version.is_empty() => version,
+        Ok(_) | Err(_) => {
+            return vec![Check::error(
+                "aapt2",
+                format!("{} did not report a version", aapt2.into_path().display()),
+                "Check that the binary is executable and built for this platform; reinstall the build-tools if needed",
+            )];
+        }
+    };
+    let path = aapt2.into_path();
+    let mut checks = vec![Check::ok("aapt2", path.display().to_string())];
+    checks.push(match parse_aapt2_version(&version) {
+        Some(release) if release >= MIN_AAPT2_VERSION => Check::ok("aapt2 version", version),
+        Some(_) => Check::error(
+            "aapt2 version",
+            format!(
+                "{} is older than the supported {}.{}",
+                version, MIN_AAPT2_VERSION.0, MIN_AAPT2_VERSION.1
+            ),
+            "Install a newer build-tools release with `sdkmanager \"build-tools;34.0.0\"`",
+        ),
+        None => Check::warning(
+            "aapt2 version",
+            format!("Could not parse version from \"{}\"", version),
+            format!(
+                "Make sure aapt2 is at least {}.{}",
+                MIN_AAPT2_VERSION.0, MIN_AAPT2_VERSION.1
+            ),
+        ),
+    });
+    checks
+}
+
+/// ANDROID_HOME points at an SDK with build-tools and platforms
+fn check_android_home(android_home: Option<String>, sdk_root: Option<String>) -> Check {
+    const NAME: &str = "ANDROID_HOME";
+    let Some(android_home) = android_home.filter(|home| !home.is_empty()) else {
+        return match sdk_root {
+            Some(sdk_root) => Check::warning(
+                NAME,
+                "not set, ANDROID_SDK_ROOT is ignored by asb",
+                format!("export ANDROID_HOME={}", sdk_root),
+            ),
+            None => Check::warning(
+                NAME,
+                "not set, aapt2 and android.jar must be configured explicitly",
+                "export ANDROID_HOME=/path/to/Android/sdk",
+            ),
+        };
+    };
+    let home = Path::new(&android_home);
+    if !home.is_dir() {
+        return Check::error(
+            NAME,
+            format!("{} does not exist", android_home),
+            "Point ANDROID_HOME at the Android SDK directory",
+        );
+    }
+    let missing: Vec<&str> = ["build-tools", "platforms"]
+        .into_iter()
+        .filter(|dir| !home.join(dir).is_dir())
+        .collect();
+    if !missing.is_empty() {
+        return Check::error(
+            NAME,
+            format!("{} has no {} directory", android_home, missing.join(" or ")),
+            "Install them with `sdkmanager \"build-tools;34.0.0\" \"platforms;android-34\"`",
+        );
+    }
+    if let Some(sdk_root) = sdk_root
+        && Path::new(&sdk_root) != home
+    {
+        return Check::warning(
+            NAME,
+            format!(
+                "{} differs from ANDROID_SDK_ROOT {}",
+                android_home, sdk_root
+            ),
+            "Point both variables at the same SDK; asb uses ANDROID_HOME",
+        );
+    }
+    Check::ok(NAME, android_home)
+}
+
+/// Every config has an existing android.jar
+fn check_android_jars(configs: &[BuildConfig]) -> Check {
+    const NAME: &str = "android.jar";
+    let mut missing = Vec::new();
+    let mut found = BTreeSet::new();
+    for config in configs {
+        match &config.android_jar {
+            Some(jar) if jar.is_file() => {
+                found.insert(jar.display().to_string());
+            }
+            Some(jar) => missing.push(format!("{} ({})", jar.display(), config.package_name)),
+            None => missing.push(format!("not configured ({})", config.package_name)),
+        }
+    }
+    if !missing.is_empty() {
+        return Check::error(
+            NAME,
+            missing.join(", "),
+            "Install the platform with `sdkmanager \"platforms;android-34\"` or set androidJar / --android-jar",
+        );
+    }
+    Check::ok(NAME, found.into_iter().collect::<Vec<_>>().join(", "))
+}
+
+/// Build and output directories of every config can be written
+fn check_writable_dirs(configs: &[BuildConfig]) -> Check {
+    const NAME: &str = "output directories";
+    let dirs: BTreeSet<PathBuf> = configs
+        .iter()
+        .flat_map(|config| {
+            let build_dir = config
+                .cache_dir
+                .clone()
+                .or_else(|| config.build_dir.clone())
+                .unwrap_or_else(|| config.output_dir.join(".build"));
+            [config.output_dir.clone(), build_dir]
+        })
+        .collect();
+    let unwritable: Vec<String> = dirs
+        .iter()
+        .filter(|dir| !is_writable(dir))
+        .map(|dir| dir.display().to_string())
+        .collect();
+    if !unwritable.is_empty() {
+        return Check::error(
+            NAME,
+            format!("cannot write to {}", unwritable.join(", ")),
+            "Fix the directory permissions or set outputDir / buildDir to a writable location",
+        );
+    }
+    Check::ok(
+        NAME,
+        dirs.iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+/// Whether files can be created in `dir`, or in its closest existing ancestor when it
+/// does not exist yet
+fn is_writable(dir: &Path) -> bool {
+    let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+        return false;
+    };
+    let probe = existing.join(format!(".asb-doctor-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => std::fs::remove_file(&probe).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Windows allows paths longer than 260 characters
+fn check_long_paths() -> Check {
+    const NAME: &str = "long paths";
+    let enabled = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SYSTEM\CurrentControlSet\Control\FileSystem",
+            "/v",
+            "LongPathsEnabled",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("0x1"));
+    match enabled {
+        Some(true) => Check::ok(NAME, "enabled"),
+        _ => Check::warning(
+            NAME,
+            "disabled, deep resource paths may fail to build",
+            "Run as administrator: reg add HKLM\\SYSTEM\\CurrentControlSet\\Control\\FileSystem /v LongPathsEnabled /t REG_DWORD /d 1 /f",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_aapt2_version() {
+        assert_eq!(
+            parse_aapt2_version("Android Asset Packaging Tool (aapt) 2.19-10229193"),
+            Some((2, 19))
+        );
+        assert_eq!(
+            parse_aapt2_version("Android Asset Packaging Tool (aapt) 2.3-4987181"),
+            Some((2, 3))
+        );
+        assert_eq!(parse_aapt2_version("garbage"), None);
+    }
+
+    #[test]
+    fn test_check_android_home() {
+        let sdk = TempDir::new().unwrap();
+        let home = sdk.path().to_string_lossy().into_owned();
+
+        let check = check_android_home(None, None);
+        assert_eq!(check.status, CheckStatus::Warning);
+        let check = check_android_home(Some(home.clone()), None);
+        assert_eq!(check.status, CheckStatus::Error);
+        assert!(check.detail.contains("build-tools or platforms"));
+
+        std::fs::create_dir(sdk.path().join("build-tools")).unwrap();
+        std::fs::create_dir(sdk.path().join("platforms")).unwrap();
+        let check = check_android_home(Some(home.clone()), None);
+        assert_eq!(check.status, CheckStatus::Ok);
+        let check = check_android_home(Some(home), Some("/other/sdk".to_string()));
+        assert_eq!(check.status, CheckStatus::Warning);
+    }
+
+    #[test]
+    fn test_check_android_jars_and_output_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let jar = temp_dir.path().join("android.jar");
+        std::fs::write(&jar, "").unwrap();
+        let mut config = BuildConfig::default_config();
+        config.android_jar = Some(jar);
+        config.output_dir = temp_dir.path().join("build/outputs");
+        config.build_dir = None;
+        config.cache_dir = None;
+
+        assert_eq!(
+            check_android_jars(std::slice::from_ref(&config)).status,
+            CheckStatus::Ok
+        );
+        let check = check_writable_dirs(std::slice::from_ref(&config));
+        assert_eq!(check.status, CheckStatus::Ok, "{}", check.detail);
+        // Probing must not create the directories
+        assert!(!config.output_dir.exists());
+
+        config.android_jar = Some(temp_dir.path().join("missing.jar"));
+        let check = check_android_jars(&[config]);
+        assert_eq!(check.status, CheckStatus::Error);
+        assert!(check.detail.contains("missing.jar"));
+    }
+}
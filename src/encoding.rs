@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// How a resource XML file deviates from the UTF-8 without byte order mark aapt2 expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingIssue {
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Neither UTF-8 nor UTF-16, e.g. GBK or Windows-1252; at the first invalid byte
+    InvalidUtf8 {
+        offset: usize,
+    },
+}
+
+impl EncodingIssue {
+    /// Description of the file's encoding for messages
+    pub fn describe(self) -> String {
+        match self {
+            EncodingIssue::Utf8Bom => "UTF-8 with a byte order mark".to_string(),
+            EncodingIssue::Utf16Le => "UTF-16LE".to_string(),
+            EncodingIssue::Utf16Be => "UTF-16BE".to_string(),
+            EncodingIssue::InvalidUtf8 { offset } => {
+                format!("not UTF-8 (invalid byte at offset {})", offset)
+            }
+        }
+    }
+}
+
+/// Encoding issue of an XML file, `None` for UTF-8 without BOM and for other files
+pub fn check_encoding(file: &Path) -> Result<Option<EncodingIssue>> {
+    if file.extension().is_none_or(|ext| ext != "xml") {
+        return Ok(None);
+    }
+    let bytes = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    Ok(detect(&bytes))
+}
+
+fn detect(bytes: &[u8]) -> Option<EncodingIssue> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some(EncodingIssue::Utf8Bom),
+        [0xFF, 0xFE, ..] => Some(EncodingIssue::Utf16Le),
+        [0xFE, 0xFF, ..] => Some(EncodingIssue::Utf16Be),
+        // Without a BOM, UTF-16 XML still starts with `<` next to a zero byte
+        [b'<', 0, ..] => Some(EncodingIssue::Utf16Le),
+        [0, b'<', ..] => Some(EncodingIssue::Utf16Be),
+        _ => std::str::from_utf8(bytes)
+            .err()
+            .map(|e| EncodingIssue::InvalidUtf8 {
+                offset: e.valid_up_to(),
+            }),
+    }
+}
+
+/// The content as UTF-8 without BOM, with the XML declaration saying so; `None` when the
+/// encoding is unknown or the UTF-16 content is malformed
+pub fn to_utf8(bytes: &[u8], issue: EncodingIssue) -> Option<Vec<u8>> {
+    let text = match issue {
+        EncodingIssue::Utf8Bom => std::str::from_utf8(&bytes[3..]).ok()?.to_string(),
+        EncodingIssue::Utf16Le | EncodingIssue::Utf16Be => {
+            if !bytes.len().is_multiple_of(2) {
+                return None;
+            }
+            let units = bytes.chunks_exact(2).map(|pair| match issue {
+                EncodingIssue::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            });
+            let text = char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .ok()?;
+            text.strip_prefix('\u{FEFF}')
+                .map(str::to_string)
+                .unwrap_or(text)
+        }
+        EncodingIssue::InvalidUtf8 { .. } => return None,
+    };
+    Some(utf8_declaration(&text).into_bytes())
+}
+
+/// Replace the encoding named by the XML declaration with UTF-8
+fn utf8_declaration(text: &str) -> String {
+    let declaration = match text.find("?>") {
+        Some(end) if text.starts_with("<?xml") => &text[..end],
+        _ => return text.to_string(),
+    };
+    let Some(attribute) = declaration.find("encoding") else {
+        return text.to_string();
+    };
+    let after = &declaration[attribute + "encoding".len()..];
+    let value = after.trim_start_matches([' ', '=']);
+    let value_start = declaration.len() - value.len();
+    let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+        return text.to_string();
+    };
+    let Some(len) = value[1..].find(quote) else {
+        return text.to_string();
+    };
+    format!(
+        "{}encoding=\"utf-8\"{}",
+        &text[..attribute],
+        &text[value_start + len + 2..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_convert_encodings() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?>\n<resources>é</resources>\n";
+        let utf16le: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(xml.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let utf16be: Vec<u8> = xml.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let expected = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>é</resources>\n";
+
+        assert_eq!(detect(&utf16le), Some(EncodingIssue::Utf16Le));
+        assert_eq!(detect(&utf16be), Some(EncodingIssue::Utf16Be));
+        for (bytes, issue) in [
+            (&utf16le, EncodingIssue::Utf16Le),
+            (&utf16be, EncodingIssue::Utf16Be),
+        ] {
+            assert_eq!(to_utf8(bytes, issue).unwrap(), expected.as_bytes());
+        }
+
+        let bom = b"\xEF\xBB\xBF<resources/>";
+        assert_eq!(detect(bom), Some(EncodingIssue::Utf8Bom));
+        assert_eq!(
+            to_utf8(bom, EncodingIssue::Utf8Bom).unwrap(),
+            b"<resources/>"
+        );
+
+        let latin1 = b"<resources>caf\xE9</resources>";
+        assert_eq!(
+            detect(latin1),
+            Some(EncodingIssue::InvalidUtf8 { offset: 14 })
+        );
+        assert_eq!(
+            to_utf8(latin1, EncodingIssue::InvalidUtf8 { offset: 14 }),
+            None
+        );
+        assert_eq!(detect(expected.as_bytes()), None);
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// `--event-stream` target that writes events to stdout instead of a socket
+pub const STDOUT_TARGET: &str = "stdout";
+
+/// Machine-readable build progress event, written as one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum BuildEvent {
+    #[serde(rename_all = "camelCase")]
+    BuildStarted { package: String },
+    #[serde(rename_all = "camelCase")]
+    FileCompiled { file: PathBuf },
+    #[serde(rename_all = "camelCase")]
+    PackageLinked { package: String, apk: PathBuf },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        package: Option<String>,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<PathBuf>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<u32>,
+    },
+    #[serde(rename_all = "camelCase")]
+    BuildFinished {
+        package: String,
+        success: bool,
+        duration_ms: u128,
+    },
+}
+
+/// Process-wide event sink, set once by `init`
+/// Events are emitted from rayon workers and parallel builds, so the writer is shared
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Start streaming events to stdout or to a local socket / named pipe
+pub fn init(target: &str) -> Result<()> {
+    let writer: Box<dyn Write + Send> = if target == STDOUT_TARGET {
+        Box::new(std::io::stdout())
+    } else {
+        connect(Path::new(target))?
+    };
+    if SINK.set(Mutex::new(writer)).is_err() {
+        anyhow::bail!("Event stream is already initialized");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> Result<Box<dyn Write + Send>> {
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .with_context(|| format!("Failed to connect to event socket {}", path.display()))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(windows)]
+fn connect(path: &Path) -> Result<Box<dyn Write + Send>> {
+    // Named pipes (\\.\pipe\name) are opened like regular files
+    let pipe = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open event pipe {}", path.display()))?;
+    Ok(Box::new(pipe))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn connect(path: &Path) -> Result<Box<dyn Write + Send>> {
+    anyhow::bail!(
+        "Event sockets are not supported on this platform: {}",
+        path.display()
+    )
+}
+
+/// Write an event to the stream, if one is active
+/// A consumer that went away must not fail the build, so write errors are ignored
+pub fn emit(event: BuildEvent) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let Ok(mut line) = serde_json::to_string(&event) else {
+        return;
+    };
+    line.push('\n');
+    if let Ok(mut writer) = sink.lock() {
+        let _ = writer.write_all(line.as_bytes());
+        let _ = writer.flush();
+    }
+}
+
+/// Turn error text into error events, one per line
+/// aapt2 diagnostics like `res/values/colors.xml:12: error: ...` keep their file and line
+pub fn error_events(package: Option<&str>, text: &str) -> Vec<BuildEvent> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (file, line_number, message) = parse_diagnostic(line);
+            BuildEvent::Error {
+                package: package.map(str::to_string),
+                message: message.to_string(),
+                file,
+                line: line_number,
+            }
+        })
+        .collect()
+}
+
+/// Split `path:line: error: message` (or `path: error: message`) into its parts
+pub fn parse_diagnostic(line: &str) -> (Option<PathBuf>, Option<u32>, &str) {
+    let Some((location, message)) = line.split_once(": error: ") else {
+        return (None, None, line);
+    };
+    match location.rsplit_once(':') {
+        Some((file, number)) if number.parse::<u32>().is_ok() => {
+            (Some(PathBuf::from(file)), number.parse().ok(), message)
+        }
+        _ => (Some(PathBuf::from(location)), None, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = BuildEvent::BuildFinished {
+            package: "com.example.skin".to_string(),
+            success: true,
+            duration_ms: 42,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"buildFinished","package":"com.example.skin","success":true,"durationMs":42}"#
+        );
+    }
+
+    #[test]
+    fn test_error_events_parse_locations() {
+        let events = error_events(
+            Some("com.example.skin"),
+            "res/values/colors.xml:12: error: invalid color.\n\
+             res/drawable/bg.xml: error: failed to parse.\n\
+             aapt2 link failed",
+        );
+        assert_eq!(
+            events,
+            vec![
+                BuildEvent::Error {
+                    package: Some("com.example.skin".to_string()),
+                    message: "invalid color.".to_string(),
+                    file: Some(PathBuf::from("res/values/colors.xml")),
+                    line: Some(12),
+                },
+                BuildEvent::Error {
+                    package: Some("com.example.skin".to_string()),
+                    message: "failed to parse.".to_string(),
+                    file: Some(PathBuf::from("res/drawable/bg.xml")),
+                    line: None,
+                },
+                BuildEvent::Error {
+                    package: Some("com.example.skin".to_string()),
+                    message: "aapt2 link failed".to_string(),
+                    file: None,
+                    line: None,
+                },
+            ]
+        );
+    }
+
+    // The sink is process-wide, so this is the only test that initializes it
+    #[test]
+    #[cfg(unix)]
+    fn test_events_stream_to_unix_socket() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixListener;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("asb-events.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        init(socket_path.to_str().unwrap()).unwrap();
+        emit(BuildEvent::FileCompiled {
+            file: PathBuf::from("res/values/colors.xml"),
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert_eq!(
+            line,
+            "{\"event\":\"fileCompiled\",\"file\":\"res/values/colors.xml\"}\n"
+        );
+
+        assert!(init(STDOUT_TARGET).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::aapt2::expected_flat_file;
+use crate::builder::find_resource_files;
+use crate::resource_priority::ResourcePriority;
+use crate::resource_rewrite::{collect_definitions, is_xml};
+use crate::types::BuildConfig;
+
+/// Where one definition of a resource was found
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceCandidate {
+    /// File defining the resource; AAR entries are shown as `<aar>!/res/...`
+    pub source: String,
+    /// Source set the file belongs to, e.g. "main", "additional[0]" or "aar[1]"
+    pub source_set: String,
+    /// Priority level of the source set (Library < Additional < Main)
+    pub priority: String,
+    /// Resource directory the definition lives in, e.g. "drawable-hdpi" or "values-fr"
+    pub directory: String,
+    /// Whether this definition wins over the others for its directory
+    pub wins: bool,
+    /// Flat file the definition was compiled to by the last build, when present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flat_file: Option<PathBuf>,
+    #[serde(skip)]
+    priority_value: usize,
+}
+
+/// Answer of `asb explain` for one resource of one package
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainReport {
+    pub package_name: String,
+    /// Resource as `type/name`
+    pub resource: String,
+    /// Every definition, highest priority first
+    pub candidates: Vec<ResourceCandidate>,
+    /// Package the last build wrote
+    pub package: PathBuf,
+    /// Whether the resource is in that package; `None` when it has not been built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packaged: Option<bool>,
+}
+
+/// Parse a resource query such as `drawable/ic_launcher`, `@string/app_name` or
+/// `R.color.primary` into (type, name)
+pub fn parse_resource_query(query: &str) -> Result<(String, String)> {
+    let query = query.trim().trim_start_matches('@');
+    let parsed = match query.strip_prefix("R.") {
+        Some(rest) => rest.split_once('.'),
+        None => query.split_once('/'),
+    };
+    match parsed {
+        Some((resource_type, name)) if !resource_type.is_empty() && !name.is_empty() => {
+            Ok((resource_type.to_string(), name.to_string()))
+        }
+        _ => anyhow::bail!(
+            "Invalid resource '{}', expected <type>/<name> such as drawable/ic_launcher",
+            query
+        ),
+    }
+}
+
+/// Find where a resource is defined across the source sets and AARs of a config, which
+/// definition wins, what it was compiled to and whether it made it into the package
+pub fn explain_resource(config: &BuildConfig, query: &str) -> Result<ExplainReport> {
+    let (resource_type, name) = parse_resource_query(query)?;
+    let wanted = (resource_type.clone(), name.clone());
+    let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+    let respect_gitignore = config.respect_gitignore.unwrap_or(false);
+    let compiled_dir = config.compiled_dir.clone().unwrap_or_else(|| {
+        config
+            .build_dir
+            .clone()
+            .unwrap_or_else(|| config.output_dir.join(".build"))
+            .join(&config.package_name)
+    });
+
+    // Source sets the way the builder collects them, with their compiled subdirectory
+    let mut source_sets = vec![(
+        config.resource_dir.clone(),
+        ResourcePriority::Main,
+        "main".to_string(),
+        "main".to_string(),
+    )];
+    for (idx, dir) in config.additional_resource_dirs.iter().flatten().enumerate() {
+        let dir_name = format!(
+            "additional_{}",
+            dir.to_string_lossy()
+                .replace(['/', '\\', ':'], "_")
+                .trim_matches('_')
+        );
+        source_sets.push((
+            dir.clone(),
+            ResourcePriority::Additional(idx),
+            format!("additional[{}]", idx),
+            dir_name,
+        ));
+    }
+
+    let mut candidates = Vec::new();
+    for (res_dir, priority, source_set, dir_name) in &source_sets {
+        if !res_dir.exists() {
+            continue;
+        }
+        for file in find_resource_files(res_dir, follow_symlinks, respect_gitignore, None)? {
+            let directory = resource_directory(res_dir, &file);
+            let content = if needs_content(&directory, &resource_type, &file) {
+                Some(
+                    fs::read_to_string(&file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?,
+                )
+            } else {
+                None
+            };
+            let mut names = HashSet::new();
+            collect_definitions(res_dir, &file, content.as_deref(), &mut names)?;
+            if !names.contains(&wanted) {
+                continue;
+            }
+            let module_dir = compiled_dir.join(dir_name);
+            let flat_file = [false, true]
+                .into_iter()
+                .filter_map(|sharded| expected_flat_file(&file, &module_dir, sharded))
+                .find(|flat| flat.exists());
+            candidates.push(ResourceCandidate {
+                source: file.display().to_string(),
+                source_set: source_set.clone(),
+                priority: format!("{:?}", priority),
+                directory,
+                wins: false,
+                flat_file,
+                priority_value: priority.value(),
+            });
+        }
+    }
+
+    for (idx, aar) in config.aar_files.iter().flatten().enumerate() {
+        let priority = ResourcePriority::Library(idx);
+        for (entry, directory) in aar_definitions(aar.path(), &wanted)? {
+            candidates.push(ResourceCandidate {
+                source: format!("{}!/{}", aar.path().display(), entry),
+                source_set: format!("aar[{}]", idx),
+                priority: format!("{:?}", priority),
+                directory,
+                wins: false,
+                flat_file: None,
+                priority_value: priority.value(),
+            });
+        }
+    }
+
+    // The highest priority definition of each directory overrides the others
+    candidates.sort_by(|a, b| {
+        b.priority_value
+            .cmp(&a.priority_value)
+            .then_with(|| a.directory.cmp(&b.directory))
+            .then_with(|| a.source.cmp(&b.source))
+    });
+    let mut winners: BTreeMap<String, usize> = BTreeMap::new();
+    for candidate in &mut candidates {
+        let best = winners
+            .entry(candidate.directory.clone())
+            .or_insert(candidate.priority_value);
+        candidate.wins = *best == candidate.priority_value;
+    }
+
+    let package = config.output_apk_path();
+    let packaged = if package.exists() {
+        Some(package_contains(&package, &resource_type, &name)?)
+    } else {
+        None
+    };
+
+    Ok(ExplainReport {
+        package_name: config.package_name.clone(),
+        resource: format!("{}/{}", resource_type, name),
+        candidates,
+        package,
+        packaged,
+    })
+}
+
+/// First path component of `file` below `res_dir`, e.g. "drawable-hdpi"
+fn resource_directory(res_dir: &Path, file: &Path) -> String {
+    file.strip_prefix(res_dir)
+        .ok()
+        .and_then(|relative| relative.components().next())
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Values files are parsed for their entries; other XML files only for `@+id` ids
+fn needs_content(directory: &str, resource_type: &str, file: &Path) -> bool {
+    is_xml(file) && (directory.split('-').next() == Some("values") || resource_type == "id")
+}
+
+/// Entries of an AAR's `res/` directory defining the resource, with their directory
+fn aar_definitions(aar: &Path, wanted: &(String, String)) -> Result<Vec<(String, String)>> {
+    let file = fs::File::open(aar).with_context(|| format!("Failed to open {}", aar.display()))?;
+    let mut archive =
+        zip::ZipArchive::new(file).with_context(|| format!("Failed to read {}", aar.display()))?;
+    let res_dir = Path::new("res");
+    let mut found = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let entry_name = entry.name().to_string();
+        let path = PathBuf::from(&entry_name);
+        if entry.is_dir() || !path.starts_with(res_dir) {
+            continue;
+        }
+        let directory = resource_directory(res_dir, &path);
+        let content = if needs_content(&directory, &wanted.0, &path) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            Some(content)
+        } else {
+            None
+        };
+        let mut names = HashSet::new();
+        collect_definitions(res_dir, &path, content.as_deref(), &mut names)?;
+        if names.contains(wanted) {
+            found.push((entry_name, directory));
+        }
+    }
+    Ok(found)
+}
+
+/// Whether a built package holds the resource
+///
+/// File-based resources are looked up as `res/<type>[-qualifiers]/<name>.*` entries. Other
+/// resources only exist in `resources.arsc`, whose key string pool is searched for the name.
+fn package_contains(package: &Path, resource_type: &str, name: &str) -> Result<bool> {
+    let file =
+        fs::File::open(package).with_context(|| format!("Failed to open {}", package.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read {}", package.display()))?;
+    let file_entry = archive.file_names().any(|entry| {
+        let mut parts = entry.split('/');
+        matches!(
+            (parts.next(), parts.next(), parts.next(), parts.next()),
+            (Some("res"), Some(dir), Some(file), None)
+                if dir.split('-').next() == Some(resource_type)
+                    && file.split('.').next() == Some(name)
+        )
+    });
+    if file_entry {
+        return Ok(true);
+    }
+    let mut arsc = Vec::new();
+    match archive.by_name("resources.arsc") {
+        Ok(mut entry) => entry.read_to_end(&mut arsc)?,
+        Err(_) => return Ok(false),
+    };
+    Ok(string_pool_contains(&arsc, name))
+}
+
+/// Whether `data` holds `name` as a UTF-8 or UTF-16 string pool entry
+/// Entries are length-prefixed and NUL-terminated, so other names containing `name` do not match.
+fn string_pool_contains(data: &[u8], name: &str) -> bool {
+    let utf16: Vec<u16> = name.encode_utf16().collect();
+    if name.len() < 0x80 && utf16.len() < 0x80 {
+        let mut utf8 = vec![utf16.len() as u8, name.len() as u8];
+        utf8.extend_from_slice(name.as_bytes());
+        utf8.push(0);
+        if data.windows(utf8.len()).any(|w| w == utf8.as_slice()) {
+            return true;
+        }
+    }
+    if utf16.len() < 0x8000 {
+        let mut encoded = (utf16.len() as u16).to_le_bytes().to_vec();
+        for unit in &utf16 {
+            encoded.extend_from_slice(&unit.to_le_bytes());
+        }
+        encoded.extend_from_slice(&[0, 0]);
+        return data.windows(encoded.len()).any(|w| w == encoded.as_slice());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resource_query() {
+        let expected = ("drawable".to_string(), "ic_launcher".to_string());
+        assert_eq!(
+            parse_resource_query("drawable/ic_launcher").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_resource_query("@drawable/ic_launcher").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_resource_query("R.drawable.ic_launcher").unwrap(),
+            expected
+        );
+        assert!(parse_resource_query("ic_launcher").is_err());
+        assert!(parse_resource_query("drawable/").is_err());
+    }
+
+    #[test]
+    fn test_explain_resource_picks_highest_priority() -> Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let main = temp.path().join("main");
+        let brand = temp.path().join("brand");
+        for (dir, file, content) in [
+            (&main, "drawable/logo.png", ""),
+            (&brand, "drawable/logo.png", ""),
+            (&brand, "drawable-hdpi/logo.png", ""),
+            (
+                &brand,
+                "values/colors.xml",
+                r#"<resources><color name="logo">#fff</color></resources>"#,
+            ),
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, content)?;
+        }
+        let mut config = BuildConfig::default_config();
+        config.resource_dir = main.clone();
+        config.additional_resource_dirs = Some(vec![brand.clone()]);
+        config.output_dir = temp.path().join("out");
+
+        let report = explain_resource(&config, "drawable/logo")?;
+        let found: Vec<(&str, &str, bool)> = report
+            .candidates
+            .iter()
+            .map(|c| (c.source_set.as_str(), c.directory.as_str(), c.wins))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("main", "drawable", true),
+                ("additional[0]", "drawable", false),
+                ("additional[0]", "drawable-hdpi", true),
+            ]
+        );
+        assert_eq!(report.packaged, None);
+
+        let report = explain_resource(&config, "color/logo")?;
+        assert_eq!(report.candidates.len(), 1);
+        assert_eq!(report.candidates[0].directory, "values");
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_pool_contains() {
+        let mut pool = vec![0x01, 0x00];
+        pool.extend_from_slice(&[11, 11]);
+        pool.extend_from_slice(b"app_name_v2\0");
+        assert!(string_pool_contains(&pool, "app_name_v2"));
+        assert!(!string_pool_contains(&pool, "app_name"));
+
+        let utf16: Vec<u8> = [3u16, b'a' as u16, b'b' as u16, b'c' as u16, 0]
+            .iter()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert!(string_pool_contains(&utf16, "abc"));
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Prefix of the bool resource generated for each flag
+pub const FLAG_PREFIX: &str = "flag_";
+
+/// Values file the flags are generated into
+pub const FEATURE_FLAGS_FILE: &str = "feature_flags.xml";
+
+/// Write one `bool/flag_<name>` resource per feature flag into `res_dir/values`
+///
+/// Returns the number of resources written. Flag names must be valid resource names.
+pub fn generate_flag_resources(flags: &BTreeMap<String, bool>, res_dir: &Path) -> Result<usize> {
+    let mut content = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n");
+    for (name, enabled) in flags {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!(
+                "Feature flag '{}' is not a valid resource name: use letters, digits and '_'",
+                name
+            );
+        }
+        content.push_str(&format!(
+            "    <bool name=\"{}{}\">{}</bool>\n",
+            FLAG_PREFIX, name, enabled
+        ));
+    }
+    content.push_str("</resources>\n");
+
+    let values_dir = res_dir.join("values");
+    fs::create_dir_all(&values_dir)
+        .with_context(|| format!("Failed to create {}", values_dir.display()))?;
+    let path = values_dir.join(FEATURE_FLAGS_FILE);
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(flags.len())
+}
+
+/// Evaluate an `includeIf` condition: a flag name, optionally with its `flag_` prefix, or
+/// `!name` to include when the flag is off
+pub fn is_included(flags: &BTreeMap<String, bool>, condition: &str) -> Result<bool> {
+    let (negated, name) = match condition.trim().strip_prefix('!') {
+        Some(name) => (true, name.trim()),
+        None => (false, condition.trim()),
+    };
+    let enabled = flags
+        .get(name)
+        .or_else(|| name.strip_prefix(FLAG_PREFIX).and_then(|n| flags.get(n)))
+        .with_context(|| {
+            format!(
+                "includeIf '{}' refers to an unknown feature flag; known flags: {}",
+                condition,
+                flags.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+    Ok(*enabled != negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn flags() -> BTreeMap<String, bool> {
+        [("winter".to_string(), true), ("summer".to_string(), false)].into()
+    }
+
+    #[test]
+    fn test_generate_flag_resources() -> Result<()> {
+        let temp = TempDir::new()?;
+        assert_eq!(generate_flag_resources(&flags(), temp.path())?, 2);
+        let content = fs::read_to_string(temp.path().join("values").join(FEATURE_FLAGS_FILE))?;
+        assert!(content.contains("<bool name=\"flag_summer\">false</bool>"));
+        assert!(content.contains("<bool name=\"flag_winter\">true</bool>"));
+
+        let invalid = [("new-year".to_string(), true)].into();
+        assert!(generate_flag_resources(&invalid, temp.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_included() -> Result<()> {
+        let flags = flags();
+        assert!(is_included(&flags, "winter")?);
+        assert!(is_included(&flags, "flag_winter")?);
+        assert!(!is_included(&flags, "summer")?);
+        assert!(is_included(&flags, "!summer")?);
+        assert!(is_included(&flags, "spring").is_err());
+        Ok(())
+    }
+}
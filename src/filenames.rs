@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File rename mapping extension, written next to the package with `autoFixFilenames`
+pub const RENAMED_FILES_EXTENSION: &str = "renamed-files.json";
+
+/// A resource file whose name aapt2 rejects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFileName {
+    pub file: PathBuf,
+    pub reason: &'static str,
+    /// Name the file is given by `autoFixFilenames`
+    pub fixed: String,
+}
+
+/// A resource file compiled under a fixed name from a staging copy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenamedFile {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Check the name of a file-based resource against what aapt2 accepts
+///
+/// File names become resource names, so they may only contain lowercase letters, digits,
+/// `_` and the `.` of the extension, and must not start with a digit. Files in `values`
+/// directories only hold resources and may be named freely.
+pub fn check_file_name(file: &Path) -> Option<InvalidFileName> {
+    let type_dir = file.parent()?.file_name()?.to_str()?;
+    if type_dir == "values" || type_dir.starts_with("values-") {
+        return None;
+    }
+    let name = file.file_name()?.to_str()?;
+    let reason = if name
+        .chars()
+        .any(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.'))
+    {
+        if name.chars().any(|c| c.is_ascii_uppercase()) {
+            "contains uppercase letters"
+        } else {
+            "contains characters other than [a-z0-9_.]"
+        }
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        "starts with a digit"
+    } else {
+        return None;
+    };
+    Some(InvalidFileName {
+        file: file.to_path_buf(),
+        reason,
+        fixed: fixed_file_name(name),
+    })
+}
+
+/// Lowercase a file name and replace characters aapt2 rejects with `_`
+fn fixed_file_name(name: &str) -> String {
+    let fixed: String = name
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '_' | '.') => c,
+            _ => '_',
+        })
+        .collect();
+    if fixed.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        format!("_{}", fixed)
+    } else {
+        fixed
+    }
+}
+
+/// Copy files with invalid names to `staging_dir` under their fixed names and compile the
+/// copies instead
+///
+/// The source tree is left untouched; copies keep their type directory, e.g.
+/// `drawable-hdpi/Logo.png` is staged as `drawable-hdpi/logo.png`. Copies are only
+/// rewritten when their content changes so the incremental cache keeps working, and files
+/// that already lie in `staging_dir` are renamed in place. Fails when a fixed name collides
+/// with another file of the same directory.
+pub fn stage_renamed_files(files: &mut [PathBuf], staging_dir: &Path) -> Result<Vec<RenamedFile>> {
+    // Resource names in use per type directory, to detect collisions
+    let mut taken: HashMap<(PathBuf, String), PathBuf> = HashMap::new();
+    for file in files.iter() {
+        if let (Some(parent), Some(name)) = (file.parent(), file.file_name())
+            && check_file_name(file).is_none()
+        {
+            taken.insert(
+                (parent.to_path_buf(), name.to_string_lossy().into_owned()),
+                file.clone(),
+            );
+        }
+    }
+
+    let mut renamed = Vec::new();
+    for file in files.iter_mut() {
+        let Some(invalid) = check_file_name(file) else {
+            continue;
+        };
+        let parent = file.parent().context("Resource file without a directory")?;
+        if let Some(other) =
+            taken.insert((parent.to_path_buf(), invalid.fixed.clone()), file.clone())
+        {
+            anyhow::bail!(
+                "Cannot rename {}: {} already uses the name {}",
+                file.display(),
+                other.display(),
+                invalid.fixed
+            );
+        }
+
+        let type_dir = parent.file_name().context("Resource file without a type")?;
+        let staged = staging_dir.join(type_dir).join(&invalid.fixed);
+        if file.starts_with(staging_dir) {
+            // Already a staged copy, which can be renamed in place
+            fs::rename(&*file, &staged)
+                .with_context(|| format!("Failed to rename {}", file.display()))?;
+        } else {
+            let content = fs::read(&*file)?;
+            if fs::read(&staged).ok().as_deref() != Some(content.as_slice()) {
+                fs::create_dir_all(staged.parent().unwrap())?;
+                fs::write(&staged, &content)
+                    .with_context(|| format!("Failed to write {}", staged.display()))?;
+            }
+        }
+        renamed.push(RenamedFile {
+            from: std::mem::replace(file, staged.clone()),
+            to: staged,
+        });
+    }
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_file_name() {
+        let check = |path: &str| check_file_name(Path::new(path)).map(|i| (i.reason, i.fixed));
+        assert_eq!(check("res/drawable/icon_bg.9.png"), None);
+        assert_eq!(check("res/values/Colors-Night.xml"), None);
+        assert_eq!(
+            check("res/drawable/IconBg.png"),
+            Some(("contains uppercase letters", "iconbg.png".to_string()))
+        );
+        assert_eq!(
+            check("res/drawable-hdpi/icon bg-2.png"),
+            Some((
+                "contains characters other than [a-z0-9_.]",
+                "icon_bg_2.png".to_string()
+            ))
+        );
+        assert_eq!(
+            check("res/raw/2x.json"),
+            Some(("starts with a digit", "_2x.json".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_stage_renamed_files() -> Result<()> {
+        let temp = TempDir::new()?;
+        let drawable = temp.path().join("res").join("drawable");
+        fs::create_dir_all(&drawable)?;
+        for name in ["Logo.png", "icon.png", "Icon.png"] {
+            fs::write(drawable.join(name), name)?;
+        }
+        let staging = temp.path().join("staging");
+
+        let mut files = vec![drawable.join("Logo.png")];
+        let renamed = stage_renamed_files(&mut files, &staging)?;
+        let staged = staging.join("drawable").join("logo.png");
+        assert_eq!(files, vec![staged.clone()]);
+        assert_eq!(
+            renamed,
+            vec![RenamedFile {
+                from: drawable.join("Logo.png"),
+                to: staged.clone(),
+            }]
+        );
+        assert_eq!(fs::read(&staged)?, b"Logo.png");
+        assert!(drawable.join("Logo.png").exists());
+
+        let mut files = vec![drawable.join("icon.png"), drawable.join("Icon.png")];
+        let err = stage_renamed_files(&mut files, &staging).unwrap_err();
+        assert!(err.to_string().contains("already uses the name icon.png"));
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+use crate::aapt2::Aapt2;
+use crate::types::BuildConfig;
+
+/// Fingerprint file extension, written next to the package
+pub const FINGERPRINT_EXTENSION: &str = "asb-fingerprint";
+
+/// Hashes of everything a package build reads
+///
+/// Written next to the package after a successful build so external build systems can skip
+/// invoking asb when nothing changed. Input files are hashed by content; the toolchain is
+/// identified by path, size and modification time to keep checks fast.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    /// Combined hash of all fields below
+    pub fingerprint: String,
+    pub asb_version: String,
+    /// Hash of the resolved build config
+    pub config: String,
+    /// SHA-256 of each input file, keyed by path
+    pub inputs: BTreeMap<String, String>,
+    /// Stamps of aapt2 and android.jar
+    pub toolchain: BTreeMap<String, String>,
+}
+
+pub(crate) fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Path, size and modification time of a toolchain file
+fn file_stamp(path: &Path) -> String {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            format!("{} {} {}", path.display(), metadata.len(), modified)
+        }
+        Err(_) => format!("{} missing", path.display()),
+    }
+}
+
+impl Fingerprint {
+    /// Fingerprint file of a package
+    pub fn path(config: &BuildConfig) -> PathBuf {
+        config
+            .output_apk_path()
+            .with_extension(FINGERPRINT_EXTENSION)
+    }
+
+    /// Hash the config, input files and toolchain of a package
+    pub fn compute(config: &BuildConfig) -> Result<Self> {
+        let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+        let mut paths = Vec::new();
+        config.clone().map_inputs(|path| {
+            paths.push(path.to_path_buf());
+            Ok(path.to_path_buf())
+        })?;
+        // The link writes new IDs back, so the file is read after the build
+        paths.extend(config.stable_ids_path());
+
+        let mut inputs = BTreeMap::new();
+        for path in paths {
+            if path.is_dir() {
+                for entry in WalkDir::new(&path).follow_links(follow_symlinks) {
+                    let entry = entry?;
+                    if entry.file_type().is_file() {
+                        let content = fs::read(entry.path())?;
+                        inputs.insert(entry.path().display().to_string(), sha256(&content));
+                    }
+                }
+            } else if path.is_file() {
+                let content = fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                inputs.insert(path.display().to_string(), sha256(&content));
+            } else {
+                inputs.insert(path.display().to_string(), "missing".to_string());
+            }
+        }
+
+        let aapt2 = Aapt2::new(config.aapt2_path.clone())
+            .map(|aapt2| file_stamp(&aapt2.into_path()))
+            .unwrap_or_else(|_| "missing".to_string());
+        let android_jar = config
+            .android_jar
+            .as_deref()
+            .map(file_stamp)
+            .unwrap_or_else(|| "missing".to_string());
+        let toolchain = BTreeMap::from([
+            ("aapt2".to_string(), aapt2),
+            ("androidJar".to_string(), android_jar),
+        ]);
+
+        let mut fingerprint = Self {
+            fingerprint: String::new(),
+            asb_version: env!("CARGO_PKG_VERSION").to_string(),
+            config: sha256(serde_json::to_string(config)?.as_bytes()),
+            inputs,
+            toolchain,
+        };
+        fingerprint.fingerprint = sha256(serde_json::to_string(&fingerprint)?.as_bytes());
+        Ok(fingerprint)
+    }
+
+    /// Read a stored fingerprint; a missing or unreadable file means no fingerprint
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Store the fingerprint of a package
+    pub fn write(&self, config: &BuildConfig) -> Result<PathBuf> {
+        let path = Self::path(config);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Whether the package exists and was built from the current inputs
+pub fn is_up_to_date(config: &BuildConfig) -> Result<bool> {
+    if !config.output_apk_path().exists() {
+        return Ok(false);
+    }
+    let Some(stored) = Fingerprint::load(&Fingerprint::path(config)) else {
+        return Ok(false);
+    };
+    Ok(stored.fingerprint == Fingerprint::compute(config)?.fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fingerprint_tracks_inputs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        let colors = res_dir.join("values").join("colors.xml");
+        fs::write(&colors, "<resources/>")?;
+
+        let config = BuildConfig {
+            resource_dir: res_dir,
+            manifest_path: temp_dir.path().join("AndroidManifest.xml"),
+            output_dir: temp_dir.path().join("out"),
+            package_name: "com.skin.red".to_string(),
+            ..BuildConfig::default_config()
+        };
+        assert_eq!(
+            Fingerprint::path(&config),
+            temp_dir
+                .path()
+                .join("out")
+                .join("com.skin.red.asb-fingerprint")
+        );
+
+        // No package yet
+        fs::create_dir_all(&config.output_dir)?;
+        Fingerprint::compute(&config)?.write(&config)?;
+        assert!(!is_up_to_date(&config)?);
+
+        fs::write(config.output_apk_path(), "apk")?;
+        assert!(is_up_to_date(&config)?);
+
+        fs::write(
+            &colors,
+            "<resources><color name=\"a\">#fff</color></resources>",
+        )?;
+        assert!(!is_up_to_date(&config)?);
+        Fingerprint::compute(&config)?.write(&config)?;
+        assert!(is_up_to_date(&config)?);
+
+        let changed = BuildConfig {
+            version_name: Some("2.0".to_string()),
+            ..config
+        };
+        assert!(!is_up_to_date(&changed)?);
+        Ok(())
+    }
+}
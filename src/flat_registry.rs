@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::resource_priority::ResourcePriority;
+
+/// A flat file (or precompiled link input) produced or reused by the current build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatEntry {
+    pub flat_file: PathBuf,
+    /// Resource file the flat file was compiled from; `None` for precompiled inputs
+    pub source: Option<PathBuf>,
+    pub priority: ResourcePriority,
+    /// Content hash of the source as recorded by the build cache, when known
+    pub hash: Option<String>,
+}
+
+/// In-memory record of the flat files of one build
+///
+/// Compilation registers every flat file it produces or reuses, and linking reads its
+/// inputs from here instead of walking the compiled directory or re-checking that each
+/// file exists.
+#[derive(Debug, Default)]
+pub struct FlatRegistry {
+    entries: Vec<FlatEntry>,
+    /// Index into `entries` by flat file path
+    by_flat: HashMap<PathBuf, usize>,
+}
+
+impl FlatRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a flat file; registering the same path again replaces its entry
+    pub fn register(&mut self, entry: FlatEntry) {
+        match self.by_flat.get(&entry.flat_file) {
+            Some(&idx) => self.entries[idx] = entry,
+            None => {
+                self.by_flat
+                    .insert(entry.flat_file.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// Register link inputs that were not compiled by this build, e.g. precompiled dependencies
+    pub fn register_precompiled(&mut self, inputs: Vec<PathBuf>, priority: ResourcePriority) {
+        for flat_file in inputs {
+            self.register(FlatEntry {
+                flat_file,
+                source: None,
+                priority,
+                hash: None,
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Flat files grouped by priority, lowest priority first, in registration order
+    pub fn by_priority(&self) -> Vec<(ResourcePriority, Vec<PathBuf>)> {
+        let mut groups: Vec<(ResourcePriority, Vec<PathBuf>)> = Vec::new();
+        for entry in &self.entries {
+            match groups.iter_mut().find(|(p, _)| *p == entry.priority) {
+                Some((_, files)) => files.push(entry.flat_file.clone()),
+                None => groups.push((entry.priority, vec![entry.flat_file.clone()])),
+            }
+        }
+        groups.sort_by_key(|(priority, _)| priority.value());
+        groups
+    }
+
+    /// Base inputs and overlay sets for `aapt2 link`
+    /// Following Android standard: Library (AAR) < Additional < Main
+    /// Library and Additional are base resources, Main is overlay
+    pub fn link_inputs(&self) -> (Vec<PathBuf>, Vec<Vec<PathBuf>>) {
+        let mut base = Vec::new();
+        let mut overlays = Vec::new();
+        for (priority, files) in self.by_priority() {
+            match priority {
+                ResourcePriority::Library(_) | ResourcePriority::Additional(_) => {
+                    base.extend(files)
+                }
+                ResourcePriority::Main => overlays.push(files),
+            }
+        }
+        (base, overlays)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn entry(flat: &str, source: &str, priority: ResourcePriority) -> FlatEntry {
+        FlatEntry {
+            flat_file: PathBuf::from(flat),
+            source: Some(PathBuf::from(source)),
+            priority,
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn test_link_inputs_follow_priority() {
+        let mut registry = FlatRegistry::new();
+        registry.register(entry("main/a.flat", "main/a.xml", ResourcePriority::Main));
+        registry.register(entry(
+            "extra/b.flat",
+            "extra/b.xml",
+            ResourcePriority::Additional(0),
+        ));
+        registry.register_precompiled(
+            vec![PathBuf::from("lib/c.flat")],
+            ResourcePriority::Library(0),
+        );
+        registry.register(entry(
+            "extra/d.flat",
+            "extra/d.xml",
+            ResourcePriority::Additional(0),
+        ));
+
+        let (base, overlays) = registry.link_inputs();
+        assert_eq!(
+            base,
+            vec![
+                PathBuf::from("lib/c.flat"),
+                PathBuf::from("extra/b.flat"),
+                PathBuf::from("extra/d.flat"),
+            ]
+        );
+        assert_eq!(overlays, vec![vec![PathBuf::from("main/a.flat")]]);
+        assert_eq!(registry.len(), 4);
+        assert!(
+            registry
+                .entries
+                .iter()
+                .any(|e| e.flat_file == Path::new("lib/c.flat") && e.source.is_none())
+        );
+    }
+
+    #[test]
+    fn test_register_same_flat_replaces_entry() {
+        let mut registry = FlatRegistry::new();
+        registry.register(entry("a.flat", "old.xml", ResourcePriority::Main));
+        registry.register(FlatEntry {
+            hash: Some("abc".to_string()),
+            ..entry("a.flat", "new.xml", ResourcePriority::Main)
+        });
+
+        assert_eq!(registry.len(), 1);
+        let entry = &registry.entries[0];
+        assert_eq!(entry.source, Some(PathBuf::from("new.xml")));
+        assert_eq!(entry.hash.as_deref(), Some("abc"));
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Working copy of the frozen IDs aapt2 links with, in the compiled directory
+pub const FREEZE_IDS_FILE: &str = "freeze-ids.txt";
+
+/// Read resource IDs in the aapt2 stable IDs format (`com.example:color/primary = 0x7f010000`)
+///
+/// Returns `type/name` to the lowercase ID; the package part of names is dropped so that
+/// files stay valid when a package is renamed.
+pub fn read_ids(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut ids = BTreeMap::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, id)) = line.split_once('=') else {
+            anyhow::bail!(
+                "{}:{}: expected <package>:<type>/<name> = <id>",
+                path.display(),
+                idx + 1
+            );
+        };
+        let name = name.trim();
+        let name = name.split_once(':').map_or(name, |(_, name)| name);
+        ids.insert(name.to_string(), id.trim().to_ascii_lowercase());
+    }
+    Ok(ids)
+}
+
+/// Write resource IDs in the aapt2 stable IDs format, sorted by name
+pub fn write_ids(path: &Path, package_name: &str, ids: &BTreeMap<String, String>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let content: String = ids
+        .iter()
+        .map(|(name, id)| format!("{}:{} = {}\n", package_name, name, id))
+        .collect();
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Resource IDs listed by `aapt2 dump resources`
+pub fn ids_from_dump(dump: &str) -> BTreeMap<String, String> {
+    dump.lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().strip_prefix("resource ")?.split_whitespace();
+            let id = parts.next()?;
+            let name = parts.next()?;
+            Some((name.to_string(), id.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// IDs for linking in freeze mode: the stable IDs with every frozen ID taking precedence
+pub fn merge_frozen_ids(
+    frozen: &BTreeMap<String, String>,
+    stable: BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let frozen_ids: BTreeSet<&String> = frozen.values().collect();
+    let mut ids: BTreeMap<String, String> = stable
+        .into_iter()
+        .filter(|(name, id)| !frozen.contains_key(name) && !frozen_ids.contains(id))
+        .collect();
+    ids.extend(frozen.iter().map(|(name, id)| (name.clone(), id.clone())));
+    ids
+}
+
+/// Frozen resources the build removed, retyped or linked with another ID
+///
+/// Resources added since the freeze are allowed.
+pub fn check_frozen(
+    frozen: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut by_name: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for name in current.keys() {
+        if let Some((kind, entry)) = name.split_once('/') {
+            by_name.entry(entry).or_default().push(kind);
+        }
+    }
+
+    let mut violations = Vec::new();
+    for (name, id) in frozen {
+        match current.get(name) {
+            Some(current_id) if current_id == id => {}
+            Some(current_id) => violations.push(format!(
+                "Frozen resource {} changed ID from {} to {}",
+                name, id, current_id
+            )),
+            None => {
+                let entry = name
+                    .split_once('/')
+                    .map_or(name.as_str(), |(_, entry)| entry);
+                match by_name.get(entry) {
+                    Some(kinds) => violations.push(format!(
+                        "Frozen resource {} was retyped to {}",
+                        name,
+                        kinds
+                            .iter()
+                            .map(|kind| format!("{}/{}", kind, entry))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                    None => violations.push(format!("Frozen resource {} was removed", name)),
+                }
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ids(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, id)| (name.to_string(), id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_and_write_ids() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("freeze").join("com.test.txt");
+        let frozen = ids(&[
+            ("color/primary", "0x7f010000"),
+            ("drawable/bg", "0x7f020000"),
+        ]);
+        write_ids(&path, "com.test", &frozen)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "com.test:color/primary = 0x7f010000\ncom.test:drawable/bg = 0x7f020000\n"
+        );
+        assert_eq!(read_ids(&path)?, frozen);
+
+        let dump = "Package name=com.test id=7f\n  type color id=01 entryCount=1\n    \
+                    resource 0x7F010000 color/primary\n      () #ffffffff\n";
+        assert_eq!(ids_from_dump(dump), ids(&[("color/primary", "0x7f010000")]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_frozen_allows_additions_only() {
+        let frozen = ids(&[
+            ("color/primary", "0x7f010000"),
+            ("color/accent", "0x7f010001"),
+            ("drawable/bg", "0x7f020000"),
+            ("string/title", "0x7f030000"),
+        ]);
+        let current = ids(&[
+            ("color/primary", "0x7f010000"),
+            ("color/secondary", "0x7f010002"),
+            ("dimen/accent", "0x7f040000"),
+            ("drawable/bg", "0x7f020001"),
+        ]);
+        assert_eq!(
+            check_frozen(&frozen, &current),
+            vec![
+                "Frozen resource color/accent was retyped to dimen/accent",
+                "Frozen resource drawable/bg changed ID from 0x7f020000 to 0x7f020001",
+                "Frozen resource string/title was removed",
+            ]
+        );
+        assert!(check_frozen(&frozen, &frozen).is_empty());
+
+        // Stable IDs give way to frozen ones, by name and by ID
+        let stable = ids(&[
+            ("color/primary", "0x7f010005"),
+            ("color/other", "0x7f010001"),
+            ("color/extra", "0x7f010006"),
+        ]);
+        let merged = merge_frozen_ids(&frozen, stable);
+        assert_eq!(merged["color/primary"], "0x7f010000");
+        assert!(!merged.contains_key("color/other"));
+        assert_eq!(merged["color/extra"], "0x7f010006");
+    }
+}
@@ -0,0 +1,1137 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::launcher_icons::{encode_png, read_rgba, scale};
+use crate::tokens::{ConfigValues, DEFAULT_CONFIGURATION, normalize_color};
+use crate::types::GoldenImagesConfig;
+
+/// Extension of the default directory for images of failing drawables, next to the package
+pub const GOLDEN_DIFF_EXTENSION: &str = "golden-diff";
+
+/// Color distance a pixel may differ by unless configured, from 0 (exact) to 1
+pub const DEFAULT_TOLERANCE: f64 = 0.1;
+
+/// Scale of drawables in directories without a density qualifier: xxhdpi
+const DEFAULT_SCALE: f64 = 3.0;
+
+/// Size of shape drawables without a `<size>`, in dp
+const DEFAULT_SHAPE_SIZE: f64 = 48.0;
+
+/// Largest width or height a drawable is rasterized at, in pixels; each pixel takes 32 bytes
+const MAX_CANVAS_SIZE: f64 = 2048.0;
+
+/// Subpixel samples per pixel along each axis
+const SAMPLES: usize = 4;
+
+/// Largest YIQ distance between two colors, between black and white
+const MAX_YIQ_DELTA: f64 = 35215.0;
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+type Point = (f64, f64);
+
+/// RGBA color with channels from 0 to 1, not premultiplied
+type Color = [f64; 4];
+
+/// Outcome of comparing drawables with their golden images
+#[derive(Debug, Default)]
+pub struct GoldenCheck {
+    /// Drawables that differ from, or could not be compared with, their golden image
+    pub errors: Vec<String>,
+    /// Drawables compared with a golden image
+    pub compared: usize,
+}
+
+/// Compare the configured drawables with their golden PNGs
+///
+/// Each drawable is taken from the highest-priority resource directory providing it and
+/// rasterized: PNGs are decoded, vector drawables and solid `<shape>`s are drawn at the
+/// density of their directory (xxhdpi without one). A pixel differs when its YIQ color
+/// distance, over white and over black, exceeds the tolerance; a drawable fails when more
+/// than the allowed fraction of its pixels differ. The rasterized image of a failing or
+/// new drawable, and a diff image marking differing pixels red, go to `diff_dir`.
+pub fn compare_golden_images(
+    sets: &[(&Path, &[PathBuf])],
+    config: &GoldenImagesConfig,
+    colors: &BTreeMap<String, ConfigValues>,
+    diff_dir: &Path,
+) -> Result<GoldenCheck> {
+    let tolerance = config.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+    let max_diff_ratio = config.max_diff_ratio.unwrap_or(0.0);
+    let mut check = GoldenCheck::default();
+
+    for entry in &config.drawables {
+        let Some((dir_name, name)) = entry.split_once('/') else {
+            check.errors.push(format!(
+                "goldenImages entry '{}' is not <directory>/<name>, e.g. drawable/ic_logo",
+                entry
+            ));
+            continue;
+        };
+        let Some(file) = find_drawable(sets, dir_name, name) else {
+            check.errors.push(format!(
+                "Drawable {} of goldenImages is not in the resource directories",
+                entry
+            ));
+            continue;
+        };
+        let (width, height, pixels) = match rasterize(file, dir_name, colors) {
+            Ok(image) => image,
+            Err(e) => {
+                check
+                    .errors
+                    .push(format!("Cannot rasterize drawable {}: {:#}", entry, e));
+                continue;
+            }
+        };
+
+        let golden_path = config.dir.join(format!("{}.png", entry));
+        let actual_path = diff_dir.join(format!("{}.actual.png", entry));
+        let diff_path = diff_dir.join(format!("{}.diff.png", entry));
+        for stale in [&actual_path, &diff_path] {
+            if stale.exists() {
+                fs::remove_file(stale)
+                    .with_context(|| format!("Failed to remove {}", stale.display()))?;
+            }
+        }
+
+        if !golden_path.is_file() {
+            write_image(&actual_path, width, height, &pixels)?;
+            check.errors.push(format!(
+                "Drawable {} has no golden image {}; it was rasterized to {}, review it and \
+                 copy it there",
+                entry,
+                golden_path.display(),
+                actual_path.display()
+            ));
+            continue;
+        }
+        let (golden_width, golden_height, golden) = read_rgba(&golden_path)
+            .with_context(|| format!("Failed to decode {}", golden_path.display()))?;
+        check.compared += 1;
+        if (golden_width, golden_height) != (width, height) {
+            write_image(&actual_path, width, height, &pixels)?;
+            check.errors.push(format!(
+                "Drawable {} is {}x{} but its golden image {} is {}x{}; rasterized to {}",
+                entry,
+                width,
+                height,
+                golden_path.display(),
+                golden_width,
+                golden_height,
+                actual_path.display()
+            ));
+            continue;
+        }
+
+        let (differing, diff) = diff_images(&golden, &pixels, tolerance);
+        let ratio = differing as f64 / (width as f64 * height as f64);
+        if ratio > max_diff_ratio {
+            write_image(&actual_path, width, height, &pixels)?;
+            write_image(&diff_path, width, height, &diff)?;
+            check.errors.push(format!(
+                "Drawable {}: {:.2}% of pixels differ from {} (allowed {:.2}%), diff image \
+                 written to {}",
+                entry,
+                ratio * 100.0,
+                golden_path.display(),
+                max_diff_ratio * 100.0,
+                diff_path.display()
+            ));
+        }
+    }
+    Ok(check)
+}
+
+/// The drawable file named `name` in a `dir_name` directory, from the last set providing it
+fn find_drawable<'a>(
+    sets: &[(&Path, &'a [PathBuf])],
+    dir_name: &str,
+    name: &str,
+) -> Option<&'a Path> {
+    sets.iter().rev().find_map(|(_, files)| {
+        files
+            .iter()
+            .find(|file| {
+                file.parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|dir| dir == dir_name)
+                    && file
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .and_then(|n| n.split('.').next())
+                        == Some(name)
+            })
+            .map(PathBuf::as_path)
+    })
+}
+
+fn write_image(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, encode_png(width, height, pixels)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Count the pixels differing by more than the tolerance and draw the diff image: differing
+/// pixels red over a faded copy of the golden image
+fn diff_images(golden: &[u8], actual: &[u8], tolerance: f64) -> (usize, Vec<u8>) {
+    let max_delta = MAX_YIQ_DELTA * tolerance * tolerance;
+    let mut differing = 0;
+    let mut diff = Vec::with_capacity(golden.len());
+    for (expected, pixel) in golden.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let delta = [0.0, 255.0]
+            .into_iter()
+            .map(|backdrop| yiq_delta(blend(expected, backdrop), blend(pixel, backdrop)))
+            .fold(0.0, f64::max);
+        if delta > max_delta {
+            differing += 1;
+            diff.extend([255, 0, 0, 255]);
+        } else {
+            let luma = yiq(blend(expected, 255.0))[0];
+            let faded = (255.0 - 0.1 * (255.0 - luma)).round() as u8;
+            diff.extend([faded, faded, faded, 255]);
+        }
+    }
+    (differing, diff)
+}
+
+/// An RGBA8 pixel composited over a gray backdrop
+fn blend(pixel: &[u8], backdrop: f64) -> [f64; 3] {
+    let alpha = pixel[3] as f64 / 255.0;
+    [0, 1, 2].map(|c| pixel[c] as f64 * alpha + backdrop * (1.0 - alpha))
+}
+
+fn yiq([r, g, b]: [f64; 3]) -> [f64; 3] {
+    [
+        r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+        r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+        r * 0.21147017 - g * 0.52261711 + b * 0.31114694,
+    ]
+}
+
+/// Perceptual distance of two colors, weighting brightness over hue
+fn yiq_delta(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (a, b) = (yiq(a), yiq(b));
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    0.5053 * d[0] * d[0] + 0.299 * d[1] * d[1] + 0.1957 * d[2] * d[2]
+}
+
+/// Render a drawable file into RGBA8 pixels
+fn rasterize(
+    file: &Path,
+    dir_name: &str,
+    colors: &BTreeMap<String, ConfigValues>,
+) -> Result<(u32, u32, Vec<u8>)> {
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    if file_name.ends_with(".png") {
+        return read_rgba(file).with_context(|| format!("Failed to decode {}", file.display()));
+    }
+    if !file_name.ends_with(".xml") {
+        anyhow::bail!("{} is neither a PNG nor an XML drawable", file.display());
+    }
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let document = roxmltree::Document::parse(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+
+    let qualifiers: Vec<&str> = dir_name.split('-').skip(1).collect();
+    let density = qualifiers.iter().find_map(|q| scale(q));
+    let configuration = qualifiers
+        .iter()
+        .filter(|q| scale(q).is_none())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("-");
+    let context = RenderContext {
+        scale: density.unwrap_or(DEFAULT_SCALE),
+        configuration: if configuration.is_empty() {
+            DEFAULT_CONFIGURATION.to_string()
+        } else {
+            configuration
+        },
+        colors,
+    };
+
+    let root = document.root_element();
+    let canvas = match root.tag_name().name() {
+        "vector" => context.render_vector(root)?,
+        "shape" => context.render_shape(root)?,
+        other => anyhow::bail!("<{}> drawables are not rasterized", other),
+    };
+    Ok((canvas.width as u32, canvas.height as u32, canvas.to_rgba()))
+}
+
+struct RenderContext<'a> {
+    /// Pixels per dp
+    scale: f64,
+    /// Qualifiers other than the density, to pick color resources by
+    configuration: String,
+    colors: &'a BTreeMap<String, ConfigValues>,
+}
+
+impl RenderContext<'_> {
+    fn render_vector(&self, root: roxmltree::Node) -> Result<Canvas> {
+        let width = self.dimension(&required(root, "width")?)?;
+        let height = self.dimension(&required(root, "height")?)?;
+        let viewport_width = number(root, "viewportWidth")?.unwrap_or(width);
+        let viewport_height = number(root, "viewportHeight")?.unwrap_or(height);
+        let mut canvas = Canvas::new(width, height)?;
+        let matrix = Matrix::scale(
+            canvas.width as f64 / viewport_width,
+            canvas.height as f64 / viewport_height,
+        );
+        let alpha = number(root, "alpha")?.unwrap_or(1.0);
+        self.render_group(&mut canvas, root, matrix, alpha)?;
+        Ok(canvas)
+    }
+
+    fn render_group(
+        &self,
+        canvas: &mut Canvas,
+        group: roxmltree::Node,
+        matrix: Matrix,
+        alpha: f64,
+    ) -> Result<()> {
+        for child in group.children().filter(|c| c.is_element()) {
+            match child.tag_name().name() {
+                "group" => {
+                    let attr = |name: &str| number(child, name).map(|v| v.unwrap_or(0.0));
+                    let (pivot_x, pivot_y) = (attr("pivotX")?, attr("pivotY")?);
+                    let local = Matrix::translate(-pivot_x, -pivot_y)
+                        .then(Matrix::scale(
+                            number(child, "scaleX")?.unwrap_or(1.0),
+                            number(child, "scaleY")?.unwrap_or(1.0),
+                        ))
+                        .then(Matrix::rotate(attr("rotation")?))
+                        .then(Matrix::translate(
+                            attr("translateX")? + pivot_x,
+                            attr("translateY")? + pivot_y,
+                        ));
+                    self.render_group(canvas, child, local.then(matrix), alpha)?;
+                }
+                "path" => self.render_path(canvas, child, matrix, alpha)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn render_path(
+        &self,
+        canvas: &mut Canvas,
+        path: roxmltree::Node,
+        matrix: Matrix,
+        alpha: f64,
+    ) -> Result<()> {
+        let Some(data) = path.attribute((ANDROID_NS, "pathData")) else {
+            return Ok(());
+        };
+        let subpaths: Vec<(Vec<Point>, bool)> = parse_path_data(data)
+            .with_context(|| format!("Invalid pathData '{}'", data))?
+            .into_iter()
+            .map(|(points, closed)| {
+                (
+                    points.into_iter().map(|p| matrix.apply(p)).collect(),
+                    closed,
+                )
+            })
+            .collect();
+
+        if let Some(fill) = path.attribute((ANDROID_NS, "fillColor")) {
+            let mut color = self.color(fill)?;
+            color[3] *= alpha * number(path, "fillAlpha")?.unwrap_or(1.0);
+            let polygons: Vec<Vec<Point>> = subpaths.iter().map(|(p, _)| p.clone()).collect();
+            let even_odd = path.attribute((ANDROID_NS, "fillType")) == Some("evenOdd");
+            canvas.fill(&polygons, even_odd, color);
+        }
+        if let Some(stroke) = path.attribute((ANDROID_NS, "strokeColor")) {
+            let mut color = self.color(stroke)?;
+            color[3] *= alpha * number(path, "strokeAlpha")?.unwrap_or(1.0);
+            let width = number(path, "strokeWidth")?.unwrap_or(0.0) * matrix.scale_factor();
+            canvas.stroke(&subpaths, width, color);
+        }
+        Ok(())
+    }
+
+    fn render_shape(&self, root: roxmltree::Node) -> Result<Canvas> {
+        let child = |name: &str| root.children().find(|c| c.tag_name().name() == name);
+        if child("gradient").is_some() {
+            anyhow::bail!("gradient shapes are not rasterized");
+        }
+        let (width, height) = match child("size") {
+            Some(size) => (
+                self.dimension(&required(size, "width")?)?,
+                self.dimension(&required(size, "height")?)?,
+            ),
+            None => (
+                DEFAULT_SHAPE_SIZE * self.scale,
+                DEFAULT_SHAPE_SIZE * self.scale,
+            ),
+        };
+        let mut canvas = Canvas::new(width, height)?;
+        let stroke = match child("stroke") {
+            Some(stroke) => Some((
+                self.dimension(&required(stroke, "width")?)?,
+                self.color(&required(stroke, "color")?)?,
+            )),
+            None => None,
+        };
+        // The shape is inset by half the stroke so that the stroke stays inside
+        let inset = stroke.map_or(0.0, |(width, _)| width / 2.0);
+        let (x0, y0) = (inset, inset);
+        let (x1, y1) = (canvas.width as f64 - inset, canvas.height as f64 - inset);
+
+        let outline = match root.attribute((ANDROID_NS, "shape")).unwrap_or("rectangle") {
+            "rectangle" => {
+                let corners = child("corners");
+                let radius = |name: &str| -> Result<f64> {
+                    let Some(corners) = corners else {
+                        return Ok(0.0);
+                    };
+                    match corners
+                        .attribute((ANDROID_NS, name))
+                        .or_else(|| corners.attribute((ANDROID_NS, "radius")))
+                    {
+                        Some(value) => self.dimension(value),
+                        None => Ok(0.0),
+                    }
+                };
+                rounded_rect(
+                    (x0, y0, x1, y1),
+                    [
+                        radius("topLeftRadius")?,
+                        radius("topRightRadius")?,
+                        radius("bottomRightRadius")?,
+                        radius("bottomLeftRadius")?,
+                    ],
+                )
+            }
+            "oval" => ellipse(
+                ((x0 + x1) / 2.0, (y0 + y1) / 2.0),
+                (x1 - x0) / 2.0,
+                (y1 - y0) / 2.0,
+            ),
+            other => anyhow::bail!("<shape android:shape=\"{}\"> is not rasterized", other),
+        };
+
+        if let Some(solid) = child("solid") {
+            let color = self.color(&required(solid, "color")?)?;
+            canvas.fill(std::slice::from_ref(&outline), false, color);
+        }
+        if let Some((width, color)) = stroke {
+            canvas.stroke(&[(outline, true)], width, color);
+        }
+        Ok(canvas)
+    }
+
+    /// Pixels of a `dp`/`dip`, `px` or unitless dimension
+    fn dimension(&self, value: &str) -> Result<f64> {
+        let (number, scale) = if let Some(dp) = value.strip_suffix("dip") {
+            (dp, self.scale)
+        } else if let Some(dp) = value.strip_suffix("dp") {
+            (dp, self.scale)
+        } else if let Some(px) = value.strip_suffix("px") {
+            (px, 1.0)
+        } else {
+            (value, 1.0)
+        };
+        let number: f64 = number
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("unsupported dimension '{}'", value))?;
+        Ok(number * scale)
+    }
+
+    /// A color literal, or a color resource in the drawable's configuration
+    fn color(&self, value: &str) -> Result<Color> {
+        let literal = match value {
+            "@android:color/transparent" => "#00000000".to_string(),
+            "@android:color/black" => "#FF000000".to_string(),
+            "@android:color/white" => "#FFFFFFFF".to_string(),
+            _ => match value.strip_prefix("@color/") {
+                Some(name) => self
+                    .colors
+                    .get(name)
+                    .and_then(|values| {
+                        values
+                            .get(&self.configuration)
+                            .or_else(|| values.get(DEFAULT_CONFIGURATION))
+                    })
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("unknown color {}", value))?,
+                None => value.to_string(),
+            },
+        };
+        let hex = normalize_color(&literal)
+            .ok_or_else(|| anyhow::anyhow!("unsupported color '{}'", value))?;
+        let digits = u32::from_str_radix(&hex[1..], 16)?;
+        let argb = if hex.len() == 7 {
+            0xFF00_0000 | digits
+        } else {
+            digits
+        };
+        let channel = |shift: u32| f64::from((argb >> shift) & 0xFF) / 255.0;
+        Ok([channel(16), channel(8), channel(0), channel(24)])
+    }
+}
+
+fn required(node: roxmltree::Node, name: &str) -> Result<String> {
+    node.attribute((ANDROID_NS, name))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("<{}> has no android:{}", node.tag_name().name(), name))
+}
+
+fn number(node: roxmltree::Node, name: &str) -> Result<Option<f64>> {
+    node.attribute((ANDROID_NS, name))
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("android:{} '{}' is not a number", name, value))
+        })
+        .transpose()
+}
+
+/// Affine transform mapping (x, y) to (a·x + c·y + e, b·x + d·y + f)
+#[derive(Clone, Copy)]
+struct Matrix([f64; 6]);
+
+impl Matrix {
+    fn translate(x: f64, y: f64) -> Self {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    fn scale(x: f64, y: f64) -> Self {
+        Matrix([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    fn rotate(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix([cos, sin, -sin, cos, 0.0, 0.0])
+    }
+
+    /// This transform followed by `next`
+    fn then(self, next: Matrix) -> Self {
+        let [a, b, c, d, e, f] = self.0;
+        let [na, nb, nc, nd, ne, nf] = next.0;
+        Matrix([
+            na * a + nc * b,
+            nb * a + nd * b,
+            na * c + nc * d,
+            nb * c + nd * d,
+            na * e + nc * f + ne,
+            nb * e + nd * f + nf,
+        ])
+    }
+
+    fn apply(&self, (x, y): Point) -> Point {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Average scale of lengths, for stroke widths
+    fn scale_factor(&self) -> f64 {
+        let [a, b, c, d, _, _] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+}
+
+/// Premultiplied RGBA pixels
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f64; 4]>,
+}
+
+impl Canvas {
+    fn new(width: f64, height: f64) -> Result<Self> {
+        // Also rejects NaN, which would otherwise become an empty canvas
+        if !(width <= MAX_CANVAS_SIZE && height <= MAX_CANVAS_SIZE) {
+            anyhow::bail!(
+                "{}x{} px is larger than the {} px golden images are rasterized at",
+                width.round(),
+                height.round(),
+                MAX_CANVAS_SIZE
+            );
+        }
+        let width = (width.round() as usize).max(1);
+        let height = (height.round() as usize).max(1);
+        Ok(Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+        })
+    }
+
+    /// Fill closed polygons, with the non-zero winding rule unless `even_odd`
+    fn fill(&mut self, polygons: &[Vec<Point>], even_odd: bool, color: Color) {
+        let edges: Vec<(Point, Point)> = polygons
+            .iter()
+            .filter(|polygon| polygon.len() > 2)
+            .flat_map(|polygon| {
+                polygon
+                    .iter()
+                    .zip(polygon.iter().cycle().skip(1))
+                    .map(|(&from, &to)| (from, to))
+            })
+            .collect();
+        let samples = SAMPLES as f64;
+        let weight = 1.0 / (samples * samples);
+        let mut coverage = vec![0.0; self.width * self.height];
+        let mut crossings: Vec<(f64, i32)> = Vec::new();
+
+        for row in 0..self.height {
+            for k in 0..SAMPLES {
+                let y = row as f64 + (k as f64 + 0.5) / samples;
+                crossings.clear();
+                for &((x0, y0), (x1, y1)) in &edges {
+                    let direction = if y0 <= y && y < y1 {
+                        1
+                    } else if y1 <= y && y < y0 {
+                        -1
+                    } else {
+                        continue;
+                    };
+                    crossings.push((x0 + (y - y0) * (x1 - x0) / (y1 - y0), direction));
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    let inside = if even_odd {
+                        winding % 2 != 0
+                    } else {
+                        winding != 0
+                    };
+                    if !inside {
+                        continue;
+                    }
+                    let max = (self.width * SAMPLES) as f64;
+                    let first = (pair[0].0 * samples - 0.5).ceil().clamp(0.0, max) as usize;
+                    let last = (pair[1].0 * samples - 0.5).ceil().clamp(0.0, max) as usize;
+                    for sample in first..last {
+                        coverage[row * self.width + sample / SAMPLES] += weight;
+                    }
+                }
+            }
+        }
+
+        for (pixel, coverage) in self.pixels.iter_mut().zip(coverage) {
+            let alpha = color[3] * coverage.min(1.0);
+            if alpha > 0.0 {
+                for c in 0..3 {
+                    pixel[c] = color[c] * alpha + pixel[c] * (1.0 - alpha);
+                }
+                pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+            }
+        }
+    }
+
+    /// Stroke polylines with round joins and butt caps
+    fn stroke(&mut self, subpaths: &[(Vec<Point>, bool)], width: f64, color: Color) {
+        if width <= 0.0 {
+            return;
+        }
+        let half = width / 2.0;
+        let mut polygons = Vec::new();
+        for (points, closed) in subpaths {
+            let mut segments: Vec<(Point, Point)> =
+                points.windows(2).map(|pair| (pair[0], pair[1])).collect();
+            if *closed && points.len() > 2 {
+                segments.push((points[points.len() - 1], points[0]));
+            }
+            for &((x0, y0), (x1, y1)) in &segments {
+                let length = (x1 - x0).hypot(y1 - y0);
+                if length == 0.0 {
+                    continue;
+                }
+                let (nx, ny) = (-(y1 - y0) / length * half, (x1 - x0) / length * half);
+                polygons.push(vec![
+                    (x0 + nx, y0 + ny),
+                    (x1 + nx, y1 + ny),
+                    (x1 - nx, y1 - ny),
+                    (x0 - nx, y0 - ny),
+                ]);
+            }
+            let joins = if *closed {
+                &points[..]
+            } else {
+                &points[1..points.len().saturating_sub(1).max(1)]
+            };
+            for &center in joins {
+                polygons.push(ellipse(center, half, half));
+            }
+        }
+        // Same orientation everywhere, so that overlapping pieces don't cancel out
+        for polygon in &mut polygons {
+            if signed_area(polygon) < 0.0 {
+                polygon.reverse();
+            }
+        }
+        self.fill(&polygons, false, color);
+    }
+
+    fn to_rgba(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                let channel = |c: f64| {
+                    if a > 0.0 {
+                        (c / a * 255.0).round().clamp(0.0, 255.0) as u8
+                    } else {
+                        0
+                    }
+                };
+                [
+                    channel(r),
+                    channel(g),
+                    channel(b),
+                    (a * 255.0).round() as u8,
+                ]
+            })
+            .collect()
+    }
+}
+
+fn signed_area(polygon: &[Point]) -> f64 {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum::<f64>()
+        / 2.0
+}
+
+fn ellipse((cx, cy): Point, rx: f64, ry: f64) -> Vec<Point> {
+    (0..64)
+        .map(|i| {
+            let angle = i as f64 * PI / 32.0;
+            (cx + rx * angle.cos(), cy + ry * angle.sin())
+        })
+        .collect()
+}
+
+/// Rectangle with corner radii, clockwise from the top left
+fn rounded_rect((x0, y0, x1, y1): (f64, f64, f64, f64), radii: [f64; 4]) -> Vec<Point> {
+    let max = ((x1 - x0) / 2.0).min((y1 - y0) / 2.0).max(0.0);
+    let corners = [
+        ((x0, y0), PI),
+        ((x1, y0), 1.5 * PI),
+        ((x1, y1), 0.0),
+        ((x0, y1), 0.5 * PI),
+    ];
+    let mut points = Vec::new();
+    for (((x, y), start), radius) in corners.into_iter().zip(radii) {
+        let radius = radius.min(max);
+        if radius <= 0.0 {
+            points.push((x, y));
+            continue;
+        }
+        // Corner arc around the center inset by the radius
+        let cx = if x == x0 { x + radius } else { x - radius };
+        let cy = if y == y0 { y + radius } else { y - radius };
+        for i in 0..=8 {
+            let angle = start + i as f64 * PI / 16.0;
+            points.push((cx + radius * angle.cos(), cy + radius * angle.sin()));
+        }
+    }
+    points
+}
+
+/// Subpaths of vector `pathData` as polylines, with whether each is closed
+fn parse_path_data(data: &str) -> Result<Vec<(Vec<Point>, bool)>> {
+    let mut lexer = PathLexer { data, pos: 0 };
+    let mut subpaths: Vec<(Vec<Point>, bool)> = Vec::new();
+    let mut command = None;
+    let (mut current, mut start) = ((0.0, 0.0), (0.0, 0.0));
+    // Second control point of the last cubic and control point of the last quadratic curve
+    let (mut last_cubic, mut last_quad): (Option<Point>, Option<Point>) = (None, None);
+
+    loop {
+        lexer.skip_separators();
+        if lexer.at_end() {
+            break;
+        }
+        if let Some(next) = lexer.command() {
+            command = Some(next);
+        }
+        let Some(cmd) = command else {
+            anyhow::bail!("expected a command at offset {}", lexer.pos);
+        };
+        let origin = if cmd.is_ascii_lowercase() {
+            current
+        } else {
+            (0.0, 0.0)
+        };
+        let point = |lexer: &mut PathLexer| -> Result<Point> {
+            Ok((lexer.number()? + origin.0, lexer.number()? + origin.1))
+        };
+        let (mut cubic, mut quad) = (None, None);
+        match cmd.to_ascii_uppercase() {
+            'M' => {
+                current = point(&mut lexer)?;
+                start = current;
+                subpaths.push((vec![current], false));
+                // Further coordinate pairs are line segments
+                command = Some(if cmd == 'm' { 'l' } else { 'L' });
+            }
+            'L' => {
+                let to = point(&mut lexer)?;
+                line_to(&mut subpaths, current, to);
+                current = to;
+            }
+            'H' => {
+                let to = (lexer.number()? + origin.0, current.1);
+                line_to(&mut subpaths, current, to);
+                current = to;
+            }
+            'V' => {
+                let to = (current.0, lexer.number()? + origin.1);
+                line_to(&mut subpaths, current, to);
+                current = to;
+            }
+            'C' | 'S' => {
+                let c1 = if cmd.eq_ignore_ascii_case(&'C') {
+                    point(&mut lexer)?
+                } else {
+                    reflect(last_cubic, current)
+                };
+                let c2 = point(&mut lexer)?;
+                let to = point(&mut lexer)?;
+                for i in 1..=16 {
+                    let t = i as f64 / 16.0;
+                    let mt = 1.0 - t;
+                    let at = |a: f64, b: f64, c: f64, d: f64| {
+                        mt * mt * mt * a
+                            + 3.0 * mt * mt * t * b
+                            + 3.0 * mt * t * t * c
+                            + t * t * t * d
+                    };
+                    let p = (
+                        at(current.0, c1.0, c2.0, to.0),
+                        at(current.1, c1.1, c2.1, to.1),
+                    );
+                    line_to(&mut subpaths, current, p);
+                }
+                cubic = Some(c2);
+                current = to;
+            }
+            'Q' | 'T' => {
+                let control = if cmd.eq_ignore_ascii_case(&'Q') {
+                    point(&mut lexer)?
+                } else {
+                    reflect(last_quad, current)
+                };
+                let to = point(&mut lexer)?;
+                for i in 1..=12 {
+                    let t = i as f64 / 12.0;
+                    let mt = 1.0 - t;
+                    let at = |a: f64, b: f64, c: f64| mt * mt * a + 2.0 * mt * t * b + t * t * c;
+                    let p = (
+                        at(current.0, control.0, to.0),
+                        at(current.1, control.1, to.1),
+                    );
+                    line_to(&mut subpaths, current, p);
+                }
+                quad = Some(control);
+                current = to;
+            }
+            'A' => {
+                let (rx, ry, rotation) = (lexer.number()?, lexer.number()?, lexer.number()?);
+                let (large_arc, sweep) = (lexer.flag()?, lexer.flag()?);
+                let to = point(&mut lexer)?;
+                for p in arc_points(current, (rx, ry), rotation, large_arc, sweep, to) {
+                    line_to(&mut subpaths, current, p);
+                }
+                current = to;
+            }
+            'Z' => {
+                if let Some((_, closed @ false)) = subpaths.last_mut() {
+                    *closed = true;
+                }
+                current = start;
+                command = None;
+            }
+            other => anyhow::bail!("unknown command '{}'", other),
+        }
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    Ok(subpaths)
+}
+
+/// Continue the open subpath to `to`, or start one at `from` after a closed one
+fn line_to(subpaths: &mut Vec<(Vec<Point>, bool)>, from: Point, to: Point) {
+    match subpaths.last_mut() {
+        Some((points, false)) => points.push(to),
+        _ => subpaths.push((vec![from, to], false)),
+    }
+}
+
+/// Control point mirrored around the current point, or the current point itself
+fn reflect(control: Option<Point>, current: Point) -> Point {
+    control.map_or(current, |(x, y)| (2.0 * current.0 - x, 2.0 * current.1 - y))
+}
+
+/// Points along an SVG elliptical arc, ending at `to`
+fn arc_points(
+    from: Point,
+    (rx, ry): (f64, f64),
+    rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    to: Point,
+) -> Vec<Point> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if from == to {
+        return Vec::new();
+    }
+    if rx == 0.0 || ry == 0.0 {
+        return vec![to];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    let lambda = x1 * x1 / (rx * rx) + y1 * y1 / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let (cx1, cy1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+    let center = (
+        cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0,
+        sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0,
+    );
+
+    let angle = |(ux, uy): Point, (vx, vy): Point| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start_vector = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let end_vector = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let start = angle((1.0, 0.0), start_vector);
+    let mut delta = angle(start_vector, end_vector);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    }
+
+    let steps = ((delta.abs() / (PI / 18.0)).ceil() as usize).max(1);
+    let mut points: Vec<Point> = (1..steps)
+        .map(|i| {
+            let t = start + delta * i as f64 / steps as f64;
+            let (x, y) = (rx * t.cos(), ry * t.sin());
+            (center.0 + cos * x - sin * y, center.1 + sin * x + cos * y)
+        })
+        .collect();
+    points.push(to);
+    points
+}
+
+/// Numbers, flags and commands of path data
+struct PathLexer<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl PathLexer<'_> {
+    fn rest(&self) -> &str {
+        &self.data[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn skip_separators(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+                .len();
+    }
+
+    /// The next command letter, consumed
+    fn command(&mut self) -> Option<char> {
+        let next = self
+            .rest()
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)?;
+        self.pos += 1;
+        Some(next)
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        self.skip_separators();
+        let bytes = self.rest().as_bytes();
+        let mut end = 0;
+        if matches!(bytes.first(), Some(b'-' | b'+')) {
+            end += 1;
+        }
+        let mut seen_dot = false;
+        while let Some(&b) = bytes.get(end) {
+            match b {
+                b'0'..=b'9' => end += 1,
+                // A second dot starts the next number, as in `1.5.5`
+                b'.' if !seen_dot => {
+                    seen_dot = true;
+                    end += 1;
+                }
+                b'e' | b'E' => {
+                    end += 1;
+                    if matches!(bytes.get(end), Some(b'-' | b'+')) {
+                        end += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let text = &self.rest()[..end];
+        let value = text
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expected a number at offset {}", self.pos))?;
+        self.pos += end;
+        Ok(value)
+    }
+
+    /// Arc flags are single digits and may be written without separators, as in `0 01`
+    fn flag(&mut self) -> Result<bool> {
+        self.skip_separators();
+        let flag = match self.rest().as_bytes().first() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => anyhow::bail!("expected an arc flag at offset {}", self.pos),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pixel(image: &(u32, u32, Vec<u8>), x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * image.0 + x) * 4) as usize;
+        image.2[i..i + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_rasterize_vector_drawable() -> Result<()> {
+        let temp = TempDir::new()?;
+        let drawable = temp.path().join("drawable-night").join("ic_half.xml");
+        fs::create_dir_all(drawable.parent().unwrap())?;
+        // Left half filled with a color resource, a circle of arcs in the right half
+        fs::write(
+            &drawable,
+            r##"<vector xmlns:android="http://schemas.android.com/apk/res/android"
+    android:width="24dp" android:height="24dp"
+    android:viewportWidth="24" android:viewportHeight="24">
+    <path android:fillColor="@color/brand" android:pathData="M0,0h12v24H0z"/>
+    <group android:translateX="12">
+        <path android:fillColor="#8000FF00" android:pathData="M2,12a4,4 0 1,0 8,0a4,4 0 1,0 -8,0"/>
+    </group>
+</vector>"##,
+        )?;
+        let colors = [(
+            "brand".to_string(),
+            [
+                ("default".to_string(), "#FFFF0000".to_string()),
+                ("night".to_string(), "#FF0000FF".to_string()),
+            ]
+            .into(),
+        )]
+        .into();
+
+        let image = rasterize(&drawable, "drawable-night", &colors)?;
+        assert_eq!((image.0, image.1), (72, 72));
+        assert_eq!(pixel(&image, 10, 10), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 54, 36), [0, 255, 0, 128]);
+        assert_eq!(pixel(&image, 70, 2), [0, 0, 0, 0]);
+
+        // Density qualifiers set the scale
+        let image = rasterize(&drawable, "drawable-mdpi", &colors)?;
+        assert_eq!((image.0, image.1), (24, 24));
+        assert_eq!(pixel(&image, 3, 3), [255, 0, 0, 255]);
+
+        // Huge declared sizes are refused instead of allocating the canvas
+        let huge = temp.path().join("drawable").join("bg_huge.xml");
+        fs::create_dir_all(huge.parent().unwrap())?;
+        fs::write(
+            &huge,
+            r##"<shape xmlns:android="http://schemas.android.com/apk/res/android">
+    <size android:width="100000dp" android:height="16dp"/>
+    <solid android:color="#FF000000"/>
+</shape>"##,
+        )?;
+        let err = rasterize(&huge, "drawable", &colors).unwrap_err();
+        assert!(format!("{:#}", err).contains("larger than"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_golden_images() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res = temp.path().join("res");
+        let shape = res.join("drawable").join("bg_card.xml");
+        fs::create_dir_all(shape.parent().unwrap())?;
+        let write_shape = |color: &str| {
+            fs::write(
+                &shape,
+                format!(
+                    r#"<shape xmlns:android="http://schemas.android.com/apk/res/android">
+    <size android:width="16dp" android:height="16dp"/>
+    <corners android:radius="4dp"/>
+    <solid android:color="{}"/>
+</shape>"#,
+                    color
+                ),
+            )
+        };
+        write_shape("#FF6200EE")?;
+        let files = vec![shape.clone()];
+        let sets: Vec<(&Path, &[PathBuf])> = vec![(res.as_path(), files.as_slice())];
+        let config = GoldenImagesConfig {
+            drawables: vec!["drawable/bg_card".to_string()],
+            dir: temp.path().join("golden"),
+            ..Default::default()
+        };
+        let diff_dir = temp.path().join("diff");
+        let colors = BTreeMap::new();
+
+        // Without a golden image, the rasterized drawable is written for review
+        let check = compare_golden_images(&sets, &config, &colors, &diff_dir)?;
+        assert_eq!(check.errors.len(), 1);
+        let actual = diff_dir.join("drawable").join("bg_card.actual.png");
+        let golden = config.dir.join("drawable").join("bg_card.png");
+        fs::create_dir_all(golden.parent().unwrap())?;
+        fs::copy(&actual, &golden)?;
+
+        let check = compare_golden_images(&sets, &config, &colors, &diff_dir)?;
+        assert!(check.errors.is_empty(), "{:?}", check.errors);
+        assert_eq!(check.compared, 1);
+        assert!(!actual.exists());
+
+        // A slightly different shade passes the tolerance, another color does not
+        write_shape("#FF6300EE")?;
+        assert!(
+            compare_golden_images(&sets, &config, &colors, &diff_dir)?
+                .errors
+                .is_empty()
+        );
+        write_shape("#FF03DAC5")?;
+        let check = compare_golden_images(&sets, &config, &colors, &diff_dir)?;
+        assert_eq!(check.errors.len(), 1);
+        assert!(check.errors[0].contains("of pixels differ"));
+        let diff = read_rgba(&diff_dir.join("drawable").join("bg_card.diff.png"))?;
+        assert_eq!(diff.2[24 * 48 * 4 + 24 * 4..][..4], [255, 0, 0, 255]);
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::types::HeartbeatConfig;
+
+/// Seconds between heartbeat logs unless configured
+pub const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+/// Seconds without progress after which a build is reported as stalled unless configured
+pub const DEFAULT_STALL_SECS: u64 = 120;
+
+/// How often the monitor checks the activity of a build
+const TICK: Duration = Duration::from_secs(1);
+
+/// What a build is doing, updated by the builder and by every aapt2 invocation
+///
+/// Progress is a phase change, a finished aapt2 process or a line of aapt2 output.
+#[derive(Debug)]
+pub struct BuildActivity {
+    package: String,
+    started: Instant,
+    state: Mutex<ActivityState>,
+}
+
+#[derive(Debug)]
+struct ActivityState {
+    phase: String,
+    processed: usize,
+    total: Option<usize>,
+    last_progress: Instant,
+    /// Running aapt2 processes by PID
+    processes: BTreeMap<u32, RunningProcess>,
+}
+
+#[derive(Debug)]
+struct RunningProcess {
+    subject: String,
+    last_line: Option<String>,
+}
+
+impl BuildActivity {
+    pub fn new(package: &str) -> Self {
+        let now = Instant::now();
+        Self {
+            package: package.to_string(),
+            started: now,
+            state: Mutex::new(ActivityState {
+                phase: "starting".to_string(),
+                processed: 0,
+                total: None,
+                last_progress: now,
+                processes: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ActivityState> {
+        // Activity is only reported, so whatever a panicking thread left behind is fine
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Enter a pipeline phase, with the number of files it processes when known
+    pub fn set_phase(&self, phase: &str, total: Option<usize>) {
+        let mut state = self.state();
+        state.phase = phase.to_string();
+        state.processed = 0;
+        state.total = total;
+        state.last_progress = Instant::now();
+    }
+
+    /// An aapt2 process started working on `subject`
+    pub fn process_started(&self, pid: u32, subject: &str) {
+        let mut state = self.state();
+        state.processes.insert(
+            pid,
+            RunningProcess {
+                subject: subject.to_string(),
+                last_line: None,
+            },
+        );
+    }
+
+    /// An aapt2 process wrote a line of output
+    pub fn process_output(&self, pid: u32, line: &str) {
+        let mut state = self.state();
+        if let Some(process) = state.processes.get_mut(&pid) {
+            process.last_line = Some(line.to_string());
+        }
+        state.last_progress = Instant::now();
+    }
+
+    /// An aapt2 process exited, which counts as one processed file
+    pub fn process_finished(&self, pid: u32) {
+        let mut state = self.state();
+        state.processes.remove(&pid);
+        state.processed += 1;
+        state.last_progress = Instant::now();
+    }
+
+    /// Heartbeat line: phase, elapsed time and files processed
+    pub fn status(&self) -> String {
+        let state = self.state();
+        let processed = match state.total {
+            Some(total) => format!("{}/{}", state.processed.min(total), total),
+            None => state.processed.to_string(),
+        };
+        format!(
+            "{}: {}, {}s elapsed, {} files processed",
+            self.package,
+            state.phase,
+            self.started.elapsed().as_secs(),
+            processed
+        )
+    }
+
+    /// Warning for a build without progress for at least `after`, naming the running
+    /// aapt2 processes and the last line each of them wrote
+    pub fn stall(&self, after: Duration) -> Option<String> {
+        let state = self.state();
+        let idle = state.last_progress.elapsed();
+        if idle < after {
+            return None;
+        }
+        let mut message = format!(
+            "{}: no progress in {} for {}s",
+            self.package,
+            state.phase,
+            idle.as_secs()
+        );
+        for (pid, process) in &state.processes {
+            message.push_str(&format!(
+                "\n  aapt2 (pid {}) on {}, last output: {}",
+                pid,
+                process.subject,
+                process.last_line.as_deref().unwrap_or("none")
+            ));
+        }
+        Some(message)
+    }
+
+    fn last_progress(&self) -> Instant {
+        self.state().last_progress
+    }
+}
+
+/// Background thread logging heartbeats and stall warnings for one build; stops when dropped
+pub struct HeartbeatMonitor {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeartbeatMonitor {
+    /// Watch `activity`; `None` when both heartbeats and stall detection are disabled
+    pub fn start(activity: Arc<BuildActivity>, config: HeartbeatConfig) -> Option<Self> {
+        let every = |secs: Option<u64>, default| match secs.unwrap_or(default) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let interval = every(config.interval_secs, DEFAULT_INTERVAL_SECS);
+        let stall_after = every(config.stall_secs, DEFAULT_STALL_SECS);
+        if interval.is_none() && stall_after.is_none() {
+            return None;
+        }
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut last_heartbeat = Instant::now();
+            // Progress time of the stall already reported, so each stall warns once
+            let mut reported_stall = None;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(TICK) {
+                if let Some(interval) = interval
+                    && last_heartbeat.elapsed() >= interval
+                {
+                    info!("{}", activity.status());
+                    last_heartbeat = Instant::now();
+                }
+                if let Some(stall_after) = stall_after
+                    && let Some(message) = activity.stall(stall_after)
+                {
+                    let progress = activity.last_progress();
+                    if reported_stall != Some(progress) {
+                        warn!("{}", message);
+                        reported_stall = Some(progress);
+                    }
+                }
+            }
+        });
+        Some(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for HeartbeatMonitor {
+    fn drop(&mut self) {
+        // Disconnecting the channel ends the thread at its next tick
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_reports_progress_and_stalls() {
+        let activity = BuildActivity::new("com.test");
+        activity.set_phase("compiling", Some(3));
+        activity.process_started(42, "icon.png");
+        activity.process_finished(42);
+        assert!(activity.status().starts_with("com.test: compiling, "));
+        assert!(activity.status().ends_with("1/3 files processed"));
+        assert_eq!(activity.stall(Duration::from_secs(60)), None);
+
+        activity.set_phase("linking", None);
+        activity.process_started(7, "skin.apk");
+        activity.process_output(7, "note: including 12 files");
+        let stall = activity.stall(Duration::ZERO).unwrap();
+        assert!(stall.starts_with("com.test: no progress in linking for 0s"));
+        assert!(
+            stall.ends_with("aapt2 (pid 7) on skin.apk, last output: note: including 12 files")
+        );
+        assert!(activity.status().ends_with(", 0 files processed"));
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::types::{HookCommand, HookFailurePolicy, HooksConfig};
+
+/// Lines of a failed command's output quoted in the error
+const FAILURE_OUTPUT_LINES: usize = 10;
+
+/// Held while an `exclusive` hook runs, so packages building in parallel take turns
+static EXCLUSIVE_HOOKS: Mutex<()> = Mutex::new(());
+
+/// Point of the build pipeline a hook runs at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    PreBuild,
+    PostCompile,
+    PostBuild,
+}
+
+impl HookStage {
+    /// Name of the stage in the config, also passed as `ASB_HOOK`
+    pub fn name(self) -> &'static str {
+        match self {
+            HookStage::PreBuild => "preBuild",
+            HookStage::PostCompile => "postCompile",
+            HookStage::PostBuild => "postBuild",
+        }
+    }
+}
+
+/// Outcome of running the commands of one stage
+#[derive(Debug, Default)]
+pub struct HookOutcome {
+    /// Failures that fail the build
+    pub errors: Vec<String>,
+    /// Failures reported under the `warn` policy
+    pub warnings: Vec<String>,
+}
+
+/// Run the commands of a stage in order with `env` added to the environment
+///
+/// Each command gets an empty scratch directory below `work_root`, passed as
+/// `ASB_WORK_DIR` and as the temp directory, so hooks of packages building in parallel do
+/// not share files. Under the `fail` policy the first failing command ends the stage;
+/// under `warn` every command runs and failures are returned as warnings.
+pub fn run_hooks(
+    hooks: &HooksConfig,
+    stage: HookStage,
+    env: &[(&str, String)],
+    work_root: &Path,
+) -> HookOutcome {
+    let commands = match stage {
+        HookStage::PreBuild => &hooks.pre_build,
+        HookStage::PostCompile => &hooks.post_compile,
+        HookStage::PostBuild => &hooks.post_build,
+    };
+    let policy = hooks.on_failure.unwrap_or_default();
+    let timeout = hooks.timeout_secs.map(Duration::from_secs);
+
+    let mut outcome = HookOutcome::default();
+    for (idx, command) in commands.iter().enumerate() {
+        info!("Running {} hook: {}", stage.name(), command.command());
+        let work_dir = work_root.join(stage.name()).join(idx.to_string());
+        let Err(e) = run_command(command, stage, env, &work_dir, timeout) else {
+            continue;
+        };
+        let message = format!(
+            "{} hook `{}` failed: {:#}",
+            stage.name(),
+            command.command(),
+            e
+        );
+        match policy {
+            HookFailurePolicy::Fail => {
+                outcome.errors.push(message);
+                break;
+            }
+            HookFailurePolicy::Warn => {
+                warn!("{}", message);
+                outcome.warnings.push(message);
+            }
+        }
+    }
+    outcome
+}
+
+/// Run one command through the platform shell, logging its output line by line
+fn run_command(
+    command: &HookCommand,
+    stage: HookStage,
+    env: &[(&str, String)],
+    work_dir: &Path,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    // Start from an empty scratch directory so nothing leaks between builds
+    if work_dir.exists() {
+        std::fs::remove_dir_all(work_dir)
+            .with_context(|| format!("Failed to clear {}", work_dir.display()))?;
+    }
+    std::fs::create_dir_all(work_dir)
+        .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+    let work_dir = std::path::absolute(work_dir)?;
+
+    let _exclusive = if command.exclusive() {
+        debug!("Waiting for other exclusive hooks: {}", command.command());
+        // A panicking hook thread leaves nothing to clean up, so a poisoned lock is fine
+        Some(
+            EXCLUSIVE_HOOKS
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    } else {
+        None
+    };
+
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command.command())
+        .env("ASB_HOOK", stage.name())
+        .env("ASB_WORK_DIR", &work_dir)
+        .env("TMPDIR", &work_dir)
+        .env("TMP", &work_dir)
+        .env("TEMP", &work_dir)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().context("Failed to start the shell")?;
+    let stdout = log_output(child.stdout.take(), stage);
+    let stderr = log_output(child.stderr.take(), stage);
+
+    let started = Instant::now();
+    let mut interval = Duration::from_millis(1);
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(timeout) = timeout
+            && started.elapsed() >= timeout
+        {
+            child.kill().ok();
+            timed_out = true;
+            break child.wait()?;
+        }
+        std::thread::sleep(interval);
+        interval = (interval * 2).min(Duration::from_millis(50));
+    };
+
+    // Processes started by a killed command may keep its pipes open, so its output is
+    // only collected when it exited by itself
+    if timed_out {
+        anyhow::bail!(
+            "killed after exceeding the {}s timeout",
+            timeout.unwrap_or_default().as_secs()
+        );
+    }
+    let mut output = stdout.join().unwrap_or_default();
+    output.extend(stderr.join().unwrap_or_default());
+    let output = output
+        .iter()
+        .skip(output.len().saturating_sub(FAILURE_OUTPUT_LINES))
+        .map(|line| format!("\n  {}", line))
+        .collect::<String>();
+    if !status.success() {
+        anyhow::bail!("exited with {}{}", status, output);
+    }
+    Ok(())
+}
+
+/// Log a child's output pipe in the background, returning its lines
+fn log_output(
+    pipe: Option<impl Read + Send + 'static>,
+    stage: HookStage,
+) -> std::thread::JoinHandle<Vec<String>> {
+    std::thread::spawn(move || {
+        let Some(pipe) = pipe else {
+            return Vec::new();
+        };
+        BufReader::new(pipe)
+            .lines()
+            .map_while(|line| line.ok())
+            .inspect(|line| info!("{}: {}", stage.name(), line))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_hooks_applies_failure_policy() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let mut hooks = HooksConfig {
+            pre_build: vec![
+                "test \"$ASB_PACKAGE\" = com.test && test \"$ASB_HOOK\" = preBuild".into(),
+                "echo generating >&2; exit 3".into(),
+                "true".into(),
+            ],
+            ..Default::default()
+        };
+        let env = [("ASB_PACKAGE", "com.test".to_string())];
+
+        let outcome = run_hooks(&hooks, HookStage::PreBuild, &env, temp.path());
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(outcome.errors[0].contains("exited with exit status: 3\n  generating"));
+        assert!(outcome.warnings.is_empty());
+
+        hooks.on_failure = Some(HookFailurePolicy::Warn);
+        hooks.pre_build.push("sleep 5".into());
+        hooks.timeout_secs = Some(1);
+        let outcome = run_hooks(&hooks, HookStage::PreBuild, &env, temp.path());
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.warnings.len(), 2);
+        assert!(outcome.warnings[1].contains("exceeding the 1s timeout"));
+
+        // Other stages have no commands
+        let outcome = run_hooks(&hooks, HookStage::PostBuild, &env, temp.path());
+        assert!(outcome.errors.is_empty() && outcome.warnings.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hooks_get_own_work_dirs_and_exclusive_hooks_take_turns() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let marker = temp.path().join("running");
+        // Fails when another exclusive hook is running at the same time
+        let exclusive = HookCommand::Detailed {
+            command: format!(
+                "test ! -e {0} && touch {0} && touch \"$TMPDIR/scratch\" && sleep 0.2 && rm {0}",
+                marker.display()
+            ),
+            exclusive: true,
+        };
+        let hooks = HooksConfig {
+            post_build: vec![exclusive],
+            ..Default::default()
+        };
+
+        let outcomes: Vec<HookOutcome> = std::thread::scope(|scope| {
+            let runs: Vec<_> = ["a", "b", "c"]
+                .iter()
+                .map(|package| {
+                    let hooks = &hooks;
+                    let work_root = temp.path().join(package);
+                    scope.spawn(move || run_hooks(hooks, HookStage::PostBuild, &[], &work_root))
+                })
+                .collect();
+            runs.into_iter().map(|run| run.join().unwrap()).collect()
+        });
+        for outcome in outcomes {
+            assert!(outcome.errors.is_empty(), "{:?}", outcome.errors);
+        }
+        assert!(temp.path().join("b/postBuild/0/scratch").exists());
+    }
+}
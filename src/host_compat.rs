@@ -0,0 +1,124 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::references::SymbolTable;
+use crate::types::HostVersion;
+
+/// Extension of the compatibility report written next to the package
+pub const COMPAT_REPORT_EXTENSION: &str = "compat.json";
+
+/// Compatibility of a skin with each supported host version
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatReport {
+    /// Resources the skin defines
+    pub resources: usize,
+    pub hosts: Vec<HostCompat>,
+}
+
+/// Compatibility of a skin with one host version
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostCompat {
+    pub version: String,
+    pub symbols: PathBuf,
+    /// Whether the host defines every resource of the skin
+    pub compatible: bool,
+    /// Skin resources the host version does not define, as `type/name`
+    pub missing: Vec<String>,
+}
+
+impl HostCompat {
+    /// `compatible` or the number of missing resources, for the build log
+    pub fn summary(&self) -> String {
+        if self.compatible {
+            "compatible".to_string()
+        } else {
+            format!("{} missing resource(s)", self.missing.len())
+        }
+    }
+}
+
+/// Check the resources a skin defines against the symbols of every host version
+///
+/// A skin overrides host resources by name, so a resource the host version doesn't define
+/// is never looked up by it: the skin was made for a different version of the host.
+pub fn check_host_versions(surface: &SymbolTable, hosts: &[HostVersion]) -> Result<CompatReport> {
+    let mut resources: Vec<&(String, String)> = surface.symbols().collect();
+    resources.sort();
+
+    let mut report = CompatReport {
+        resources: resources.len(),
+        hosts: Vec::new(),
+    };
+    for host in hosts {
+        let mut symbols = SymbolTable::default();
+        symbols.load_host_symbols(&host.symbols)?;
+        let missing: Vec<String> = resources
+            .iter()
+            .filter(|(resource_type, name)| !symbols.contains(resource_type, name))
+            .map(|(resource_type, name)| format!("{}/{}", resource_type, name))
+            .collect();
+        report.hosts.push(HostCompat {
+            version: host.version.clone(),
+            symbols: host.symbols.clone(),
+            compatible: missing.is_empty(),
+            missing,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_host_versions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res = temp.path().join("res");
+        let colors = res.join("values").join("colors.xml");
+        fs::create_dir_all(colors.parent().unwrap())?;
+        fs::write(
+            &colors,
+            r##"<resources>
+    <color name="primary">#6200EE</color>
+    <color name="banner">#03DAC5</color>
+</resources>"##,
+        )?;
+        let logo = res.join("drawable").join("logo.png");
+        fs::create_dir_all(logo.parent().unwrap())?;
+        fs::write(&logo, b"png")?;
+        let mut surface = SymbolTable::default();
+        surface.add_resource_files(&res, &[colors, logo])?;
+
+        let old_host = temp.path().join("1.0.txt");
+        fs::write(
+            &old_host,
+            "int color primary 0x7f010000\nint drawable logo 0x7f020000\n",
+        )?;
+        let new_host = temp.path().join("2.0.txt");
+        fs::write(&new_host, "@color/primary\n@color/banner\n@drawable/logo\n")?;
+        let hosts = [
+            HostVersion {
+                version: "1.0".to_string(),
+                symbols: old_host,
+            },
+            HostVersion {
+                version: "2.0".to_string(),
+                symbols: new_host,
+            },
+        ];
+
+        let report = check_host_versions(&surface, &hosts)?;
+        assert_eq!(report.resources, 3);
+        assert!(!report.hosts[0].compatible);
+        assert_eq!(report.hosts[0].missing, vec!["color/banner"]);
+        assert_eq!(report.hosts[0].summary(), "1 missing resource(s)");
+        assert!(report.hosts[1].compatible);
+        Ok(())
+    }
+}
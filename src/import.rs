@@ -0,0 +1,563 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::arsc::{
+    EntryValue, ResValue, ResourceEntry, ResourceNames, ResourceTable, TYPE_STRING, format_value,
+};
+use crate::axml::{decode_xml, is_binary_xml};
+use crate::types::BuildConfig;
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+// Bag keys of attributes and plurals (ResTable_map)
+const ATTR_TYPE: u32 = 0x0100_0000;
+const ATTR_MIN: u32 = 0x0100_0001;
+const ATTR_MAX: u32 = 0x0100_0002;
+const ATTR_L10N: u32 = 0x0100_0003;
+const PLURAL_QUANTITIES: [(u32, &str); 6] = [
+    (0x0100_0004, "other"),
+    (0x0100_0005, "zero"),
+    (0x0100_0006, "one"),
+    (0x0100_0007, "two"),
+    (0x0100_0008, "few"),
+    (0x0100_0009, "many"),
+];
+
+// Formats of an attribute, in `format="..."` order; enum and flags show as child elements
+const ATTR_FORMATS: [(u32, &str); 8] = [
+    (1 << 0, "reference"),
+    (1 << 1, "string"),
+    (1 << 2, "integer"),
+    (1 << 3, "boolean"),
+    (1 << 4, "color"),
+    (1 << 5, "float"),
+    (1 << 6, "dimension"),
+    (1 << 7, "fraction"),
+];
+const ATTR_ENUM: u32 = 1 << 16;
+const ATTR_FLAGS: u32 = 1 << 17;
+
+/// What an import wrote
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub package_name: String,
+    /// Values resources written to `values*/` files
+    pub values: usize,
+    /// File resources written below `res/`
+    pub files: usize,
+    pub assets: usize,
+    /// Resources that could not be written back as source, with the reason
+    pub skipped: Vec<String>,
+    pub config_path: PathBuf,
+}
+
+/// Names of the framework resources in the `resources.arsc` of `android_jar`
+pub fn framework_names(android_jar: &Path) -> Result<HashMap<u32, (String, String)>> {
+    let data = read_entry(android_jar, "resources.arsc")?
+        .with_context(|| format!("{} has no resources.arsc", android_jar.display()))?;
+    Ok(ResourceTable::parse(&data)?.names())
+}
+
+fn read_entry(archive_path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open {}", archive_path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read {} as a package", archive_path.display()))?;
+    let Ok(mut entry) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Escape text for the content of a `<string>` or string item, as aapt2 reads it back
+fn escape_string(value: &str) -> String {
+    let mut escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\'', "\\'")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    if escaped.starts_with('@') || escaped.starts_with('?') {
+        escaped.insert(0, '\\');
+    }
+    escaped
+}
+
+/// Text of a value inside a values file; strings are escaped as string resources
+fn value_text(value: &ResValue, strings: &[String], names: &ResourceNames) -> String {
+    let text = format_value(value, strings, names);
+    if value.data_type == TYPE_STRING {
+        escape_string(&text)
+    } else {
+        text.replace('&', "&amp;").replace('<', "&lt;")
+    }
+}
+
+/// Values file a resource type is written to
+fn values_file(type_name: &str) -> String {
+    match type_name {
+        "array" | "string-array" | "integer-array" => "arrays.xml".to_string(),
+        "plurals" => "plurals.xml".to_string(),
+        other => format!("{}s.xml", other),
+    }
+}
+
+/// Source XML of a values resource, or `None` for bags of unknown types
+fn value_element(
+    entry: &ResourceEntry,
+    strings: &[String],
+    names: &ResourceNames,
+) -> Option<String> {
+    let name = &entry.name;
+    let type_name = entry.type_name.as_str();
+    match &entry.value {
+        EntryValue::Simple(value) => {
+            let text = value_text(value, strings, names);
+            Some(match type_name {
+                "id" => format!("<item type=\"id\" name=\"{}\" />", name),
+                "string" | "color" | "dimen" | "bool" | "integer" | "fraction" => {
+                    format!("<{0} name=\"{1}\">{2}</{0}>", type_name, name, text)
+                }
+                _ => format!(
+                    "<item type=\"{}\" name=\"{}\">{}</item>",
+                    type_name, name, text
+                ),
+            })
+        }
+        EntryValue::Complex { parent, items } => {
+            let mut lines = Vec::new();
+            let open = match type_name {
+                "style" => {
+                    // A dotted name without a parent would otherwise inherit from its prefix
+                    let parent = if *parent != 0 {
+                        format!(" parent=\"{}\"", names.reference('@', *parent))
+                    } else if name.contains('.') {
+                        " parent=\"\"".to_string()
+                    } else {
+                        String::new()
+                    };
+                    for (key, value) in items {
+                        let attribute = names
+                            .attribute_name(*key)
+                            .unwrap_or_else(|| format!("0x{:08x}", key));
+                        lines.push(format!(
+                            "<item name=\"{}\">{}</item>",
+                            attribute,
+                            value_text(value, strings, names)
+                        ));
+                    }
+                    format!("<style name=\"{}\"{}>", name, parent)
+                }
+                "attr" => {
+                    let mut format_bits = 0;
+                    let mut extra = String::new();
+                    for (key, value) in items {
+                        match *key {
+                            ATTR_TYPE => format_bits = value.data,
+                            ATTR_MIN => extra.push_str(&format!(" min=\"{}\"", value.data as i32)),
+                            ATTR_MAX => extra.push_str(&format!(" max=\"{}\"", value.data as i32)),
+                            ATTR_L10N => {}
+                            key => {
+                                let child = if format_bits & ATTR_FLAGS != 0 {
+                                    "flag"
+                                } else {
+                                    "enum"
+                                };
+                                let child_name = names
+                                    .own
+                                    .get(&key)
+                                    .or_else(|| names.framework.get(&key))
+                                    .map(|(_, n)| n.clone())
+                                    .unwrap_or_else(|| format!("0x{:08x}", key));
+                                let child_value = if child == "flag" {
+                                    format!("0x{:08x}", value.data)
+                                } else {
+                                    (value.data as i32).to_string()
+                                };
+                                lines.push(format!(
+                                    "<{} name=\"{}\" value=\"{}\" />",
+                                    child, child_name, child_value
+                                ));
+                            }
+                        }
+                    }
+                    let formats: Vec<&str> = ATTR_FORMATS
+                        .iter()
+                        .filter(|(bit, _)| format_bits & bit != 0)
+                        .map(|(_, f)| *f)
+                        .collect();
+                    if !formats.is_empty() && format_bits & (ATTR_ENUM | ATTR_FLAGS) == 0 {
+                        extra.insert_str(0, &format!(" format=\"{}\"", formats.join("|")));
+                    }
+                    if lines.is_empty() {
+                        return Some(format!("<attr name=\"{}\"{} />", name, extra));
+                    }
+                    format!("<attr name=\"{}\"{}>", name, extra)
+                }
+                "array" | "string-array" | "integer-array" => {
+                    for (_, value) in items {
+                        lines.push(format!(
+                            "<item>{}</item>",
+                            value_text(value, strings, names)
+                        ));
+                    }
+                    format!("<{} name=\"{}\">", type_name, name)
+                }
+                "plurals" => {
+                    for (key, value) in items {
+                        let quantity = PLURAL_QUANTITIES
+                            .iter()
+                            .find(|(k, _)| k == key)
+                            .map(|(_, q)| *q)?;
+                        lines.push(format!(
+                            "<item quantity=\"{}\">{}</item>",
+                            quantity,
+                            value_text(value, strings, names)
+                        ));
+                    }
+                    format!("<plurals name=\"{}\">", name)
+                }
+                _ => return None,
+            };
+            let mut element = open;
+            for line in lines {
+                element.push_str("\n        ");
+                element.push_str(&line);
+            }
+            element.push_str(&format!("\n    </{}>", type_name));
+            Some(element)
+        }
+    }
+}
+
+/// Path of a file resource below `res/`, named after the resource rather than the
+/// (possibly obfuscated) file in the package; `.9.png` is kept intact
+fn file_resource_path(entry: &ResourceEntry, source: &str) -> PathBuf {
+    let dir = if entry.qualifiers.is_empty() {
+        entry.type_name.clone()
+    } else {
+        format!("{}-{}", entry.type_name, entry.qualifiers)
+    };
+    let extension = if source.ends_with(".9.png") {
+        ".9.png".to_string()
+    } else {
+        Path::new(source)
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default()
+    };
+    PathBuf::from(dir).join(format!("{}{}", entry.name, extension))
+}
+
+/// Decode a built APK or skin package into a source tree at `output`
+///
+/// Writes `res/`, `AndroidManifest.xml`, `assets/` and an `asb.config.json` building them.
+/// References to framework resources are named through `android_jar` (by default the
+/// highest platform in `ANDROID_HOME`); without it they stay numeric. Styled text spans
+/// (`<b>`, `<i>`) are not kept, the text is imported plain.
+pub fn import_package(
+    input: &Path,
+    output: &Path,
+    android_jar: Option<&Path>,
+    force: bool,
+) -> Result<ImportSummary> {
+    if output.exists() && fs::read_dir(output)?.next().is_some() && !force {
+        anyhow::bail!(
+            "{} is not empty; pass --force to import into it anyway",
+            output.display()
+        );
+    }
+
+    let Some(table_data) = read_entry(input, "resources.arsc")? else {
+        if read_entry(input, "resources.pb")?.is_some() {
+            anyhow::bail!(
+                "{} is in proto format; convert it first with 'asb convert --to binary'",
+                input.display()
+            );
+        }
+        anyhow::bail!("{} has no resources.arsc", input.display());
+    };
+    let table = ResourceTable::parse(&table_data)
+        .with_context(|| format!("Failed to decode resources.arsc of {}", input.display()))?;
+    let (package_id, table_package) = table
+        .packages
+        .iter()
+        .find(|(id, _)| *id != 0x01)
+        .cloned()
+        .with_context(|| format!("{} has no resource package", input.display()))?;
+
+    let framework = match android_jar {
+        Some(jar) => framework_names(jar)?,
+        None => match crate::types::find_highest_android_jar().map(|jar| framework_names(&jar)) {
+            Some(Ok(names)) => names,
+            Some(Err(e)) => {
+                warn!("{:#}: framework references are imported as IDs", e);
+                HashMap::new()
+            }
+            None => {
+                warn!("No android.jar found: framework references are imported as IDs");
+                HashMap::new()
+            }
+        },
+    };
+    let names = ResourceNames {
+        own: table.names(),
+        framework,
+    };
+
+    let res_dir = output.join("res");
+    let mut summary = ImportSummary::default();
+    let file = File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    // (qualifiers, file name) -> elements
+    let mut values: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for entry in table
+        .entries
+        .iter()
+        .filter(|e| (e.id >> 24) as u8 == package_id)
+    {
+        if let EntryValue::Simple(value) = &entry.value
+            && value.data_type == TYPE_STRING
+            && entry.type_name != "string"
+            && let Some(source) = table.strings.get(value.data as usize)
+            && source.starts_with("res/")
+        {
+            let Ok(mut packaged) = archive.by_name(source) else {
+                summary.skipped.push(format!(
+                    "{}/{}: {} is missing",
+                    entry.type_name, entry.name, source
+                ));
+                continue;
+            };
+            let mut data = Vec::new();
+            packaged.read_to_end(&mut data)?;
+            let target = res_dir.join(file_resource_path(entry, source));
+            fs::create_dir_all(target.parent().expect("resource file has a directory"))?;
+            if is_binary_xml(&data) {
+                fs::write(&target, decode_xml(&data, &names)?)
+            } else {
+                fs::write(&target, data)
+            }
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+            summary.files += 1;
+            continue;
+        }
+
+        match value_element(entry, &table.strings, &names) {
+            Some(element) => {
+                values
+                    .entry((entry.qualifiers.clone(), values_file(&entry.type_name)))
+                    .or_default()
+                    .push(element);
+                summary.values += 1;
+            }
+            None => summary.skipped.push(format!(
+                "{}/{}: unsupported bag type",
+                entry.type_name, entry.name
+            )),
+        }
+    }
+
+    for ((qualifiers, file_name), elements) in &values {
+        let dir = if qualifiers.is_empty() {
+            res_dir.join("values")
+        } else {
+            res_dir.join(format!("values-{}", qualifiers))
+        };
+        fs::create_dir_all(&dir)?;
+        let mut content = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n");
+        for element in elements {
+            content.push_str(&format!("    {}\n", element));
+        }
+        content.push_str("</resources>\n");
+        fs::write(dir.join(file_name), content)?;
+    }
+
+    // Manifest: package name and version come from it, falling back to the table
+    let mut config = BuildConfig::default_config();
+    config.resource_dir = PathBuf::from("./res");
+    config.manifest_path = PathBuf::from("./AndroidManifest.xml");
+    config.output_dir = PathBuf::from("./build");
+    config.package_name = table_package;
+    config.package_id = Some(format!("0x{:02x}", package_id));
+    config.version_code = None;
+    config.version_name = None;
+    if let Ok(mut manifest) = archive.by_name("AndroidManifest.xml") {
+        let mut data = Vec::new();
+        manifest.read_to_end(&mut data)?;
+        let text = if is_binary_xml(&data) {
+            decode_xml(&data, &names)?
+        } else {
+            String::from_utf8_lossy(&data).into_owned()
+        };
+        if let Ok(document) = roxmltree::Document::parse(&text) {
+            let root = document.root_element();
+            if let Some(package) = root.attribute("package") {
+                config.package_name = package.to_string();
+            }
+            config.version_code = root
+                .attribute((ANDROID_NS, "versionCode"))
+                .and_then(|v| v.parse().ok());
+            config.version_name = root
+                .attribute((ANDROID_NS, "versionName"))
+                .map(str::to_string);
+        }
+        fs::write(output.join("AndroidManifest.xml"), text)?;
+    }
+    summary.package_name = config.package_name.clone();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        if !entry.is_file() || !relative.starts_with("assets") {
+            continue;
+        }
+        let target = output.join(&relative);
+        fs::create_dir_all(target.parent().expect("asset has a directory"))?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        fs::write(&target, data)?;
+        summary.assets += 1;
+    }
+    if summary.assets > 0 {
+        config.assets_dir = Some(PathBuf::from("./assets"));
+    }
+
+    summary.config_path = output.join("asb.config.json");
+    fs::write(&summary.config_path, serde_json::to_string_pretty(&config)?)?;
+    for skipped in &summary.skipped {
+        warn!("Not imported: {}", skipped);
+    }
+    info!(
+        "Imported {} ({} values, {} files, {} assets) into {}",
+        summary.package_name,
+        summary.values,
+        summary.files,
+        summary.assets,
+        output.display()
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arsc::test_support::{bag_entry, resource_table, simple_entry, type_chunk};
+    use crate::arsc::{TYPE_INT_COLOR_ARGB8, TYPE_INT_COLOR_RGB8, TYPE_REFERENCE};
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn legacy_skin(path: &Path) {
+        let table = resource_table(
+            &["res/a1.9.png", "Skin & 'Theme'"],
+            0x7f,
+            "com.legacy",
+            &["color", "drawable", "string", "style"],
+            &["primary", "panel", "title", "Skin.Dark"],
+            &[
+                type_chunk(
+                    1,
+                    0,
+                    &[Some(simple_entry(0, TYPE_INT_COLOR_RGB8, 0xff6200ee))],
+                ),
+                type_chunk(
+                    1,
+                    0x20,
+                    &[Some(simple_entry(0, TYPE_INT_COLOR_ARGB8, 0x80000000))],
+                ),
+                type_chunk(2, 0, &[Some(simple_entry(1, TYPE_STRING, 0))]),
+                type_chunk(3, 0, &[Some(simple_entry(2, TYPE_STRING, 1))]),
+                type_chunk(
+                    4,
+                    0,
+                    &[Some(bag_entry(
+                        3,
+                        0,
+                        &[(0x0101_0000, TYPE_REFERENCE, 0x7f01_0000)],
+                    ))],
+                ),
+            ],
+        );
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        let mut add = |name: &str, data: &[u8]| {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        };
+        add(
+            "AndroidManifest.xml",
+            format!(
+                "<manifest xmlns:android=\"{}\" package=\"com.legacy.skin\" \
+                 android:versionCode=\"3\" android:versionName=\"1.2\" />",
+                ANDROID_NS
+            )
+            .as_bytes(),
+        );
+        add("resources.arsc", &table);
+        add("res/a1.9.png", b"png");
+        add("assets/fonts/title.ttf", b"ttf");
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_import_package() -> Result<()> {
+        let temp = TempDir::new()?;
+        let input = temp.path().join("legacy.skin");
+        legacy_skin(&input);
+        let output = temp.path().join("src");
+        let jar = temp.path().join("android.jar");
+        let framework = resource_table(
+            &[],
+            0x01,
+            "android",
+            &["attr"],
+            &["textColor"],
+            &[type_chunk(
+                1,
+                0,
+                &[Some(simple_entry(0, TYPE_INT_COLOR_RGB8, 0))],
+            )],
+        );
+        let mut writer = ZipWriter::new(File::create(&jar)?);
+        writer.start_file("resources.arsc", SimpleFileOptions::default())?;
+        writer.write_all(&framework)?;
+        writer.finish()?;
+        let summary = import_package(&input, &output, Some(&jar), false)?;
+
+        assert_eq!(summary.package_name, "com.legacy.skin");
+        assert_eq!((summary.values, summary.files, summary.assets), (4, 1, 1));
+        assert!(summary.skipped.is_empty());
+        assert_eq!(fs::read(output.join("res/drawable/panel.9.png"))?, b"png");
+        let colors = fs::read_to_string(output.join("res/values-night/colors.xml"))?;
+        assert!(colors.contains("<color name=\"primary\">#80000000</color>"));
+        let strings = fs::read_to_string(output.join("res/values/strings.xml"))?;
+        assert!(strings.contains("<string name=\"title\">Skin &amp; \\'Theme\\'</string>"));
+        let styles = fs::read_to_string(output.join("res/values/styles.xml"))?;
+        assert!(styles.contains("<style name=\"Skin.Dark\" parent=\"\">"));
+        assert!(styles.contains("<item name=\"android:textColor\">@color/primary</item>"));
+
+        let config: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&summary.config_path)?)?;
+        assert_eq!(config["packageName"], "com.legacy.skin");
+        assert_eq!(config["resourceDir"], "./res");
+        assert_eq!(config["versionCode"], 3);
+        assert_eq!(config["assetsDir"], "./assets");
+
+        assert!(import_package(&input, &output, Some(&jar), false).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::types::LauncherIconsConfig;
+
+/// Bitmap densities with their scale relative to mdpi
+const DENSITIES: &[(&str, f64)] = &[
+    ("ldpi", 0.75),
+    ("mdpi", 1.0),
+    ("hdpi", 1.5),
+    ("xhdpi", 2.0),
+    ("xxhdpi", 3.0),
+    ("xxxhdpi", 4.0),
+];
+
+/// Densities a launcher icon must provide unless configured
+pub const DEFAULT_DENSITIES: &[&str] = &["mdpi", "hdpi", "xhdpi", "xxhdpi", "xxxhdpi"];
+
+/// Outcome of checking the launcher icons of a skin
+#[derive(Debug, Default)]
+pub struct IconCheck {
+    /// Icons missing densities or adaptive icons missing a layer
+    pub errors: Vec<String>,
+    /// Generated icons, with the resource directory of the icon they were generated from
+    pub generated: Vec<(PathBuf, PathBuf)>,
+}
+
+/// One bitmap icon across densities, e.g. `mipmap-*/ic_launcher.png`
+#[derive(Debug, Default)]
+struct IconGroup {
+    /// Density → (resource directory, file)
+    files: BTreeMap<&'static str, (PathBuf, PathBuf)>,
+}
+
+/// Check the launcher icons in the `mipmap` directories of the skin's resource directories
+///
+/// Every bitmap icon must exist in each configured density, and every `<adaptive-icon>`
+/// needs both a `<foreground>` and a `<background>`. With `generate`, missing densities
+/// below the highest provided PNG are scaled down from it into `generated_dir`.
+pub fn check_launcher_icons(
+    sets: &[(&Path, &[PathBuf])],
+    config: &LauncherIconsConfig,
+    generated_dir: &Path,
+) -> Result<IconCheck> {
+    let densities: Vec<String> = match &config.densities {
+        Some(densities) => densities.clone(),
+        None => DEFAULT_DENSITIES.iter().map(|d| d.to_string()).collect(),
+    };
+    for density in &densities {
+        if scale(density).is_none() {
+            anyhow::bail!("Unknown launcher icon density `{}`", density);
+        }
+    }
+
+    let mut check = IconCheck::default();
+    // (directory name with `*` for the density, icon name) → icon
+    let mut groups: BTreeMap<(String, String), IconGroup> = BTreeMap::new();
+    for (res_dir, files) in sets {
+        for file in files.iter() {
+            let Some(dir_name) = file
+                .parent()
+                .and_then(|d| d.file_name())
+                .and_then(|n| n.to_str())
+            else {
+                continue;
+            };
+            if dir_name != "mipmap" && !dir_name.starts_with("mipmap-") {
+                continue;
+            }
+            let Some(name) = file.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.ends_with(".xml") {
+                check.errors.extend(check_adaptive_icon(file)?);
+                continue;
+            }
+            let segments: Vec<&str> = dir_name.split('-').collect();
+            let Some((position, density)) = segments
+                .iter()
+                .enumerate()
+                .find_map(|(i, s)| DENSITIES.iter().find(|(d, _)| d == s).map(|(d, _)| (i, *d)))
+            else {
+                continue;
+            };
+            let mut base = segments.clone();
+            base[position] = "*";
+            let stem = name.split('.').next().unwrap_or(name);
+            groups
+                .entry((base.join("-"), stem.to_string()))
+                .or_default()
+                .files
+                .insert(density, (res_dir.to_path_buf(), file.clone()));
+        }
+    }
+
+    for ((base, name), group) in &groups {
+        let missing: Vec<&String> = densities
+            .iter()
+            .filter(|d| !group.files.contains_key(d.as_str()))
+            .collect();
+        if missing.is_empty() {
+            continue;
+        }
+        // The highest density present is the best source for the others
+        let (source_density, (source_dir, source)) = group
+            .files
+            .iter()
+            .max_by(|a, b| scale(a.0).partial_cmp(&scale(b.0)).unwrap())
+            .unwrap();
+        let mut not_generated = Vec::new();
+        for density in missing {
+            let can_generate = config.generate.unwrap_or(false)
+                && scale(density) < scale(source_density)
+                && source.extension().is_some_and(|e| e == "png");
+            if !can_generate {
+                not_generated.push(density.as_str());
+                continue;
+            }
+            let target = generated_dir
+                .join(density_dir(base, density))
+                .join(source.file_name().unwrap());
+            let ratio = scale(density).unwrap() / scale(source_density).unwrap();
+            generate_icon(source, &target, ratio)?;
+            debug!("Generated {} from {}", target.display(), source.display());
+            check.generated.push((source_dir.clone(), target));
+        }
+        if !not_generated.is_empty() {
+            let present: Vec<&str> = group.files.keys().copied().collect();
+            let mut message = format!(
+                "Launcher icon {}/{} is missing densities {} (provided: {})",
+                base,
+                name,
+                not_generated.join(", "),
+                present.join(", ")
+            );
+            if config.generate.unwrap_or(false) {
+                message.push_str(&format!(
+                    "; only lower densities are generated, from PNG {}",
+                    source.display()
+                ));
+            }
+            check.errors.push(message);
+        }
+    }
+    Ok(check)
+}
+
+/// Scale of a density qualifier relative to mdpi
+pub(crate) fn scale(density: &str) -> Option<f64> {
+    DENSITIES
+        .iter()
+        .find(|(d, _)| *d == density)
+        .map(|(_, scale)| *scale)
+}
+
+/// `mipmap-night-*-v4` with `xhdpi` → `mipmap-night-xhdpi-v4`
+fn density_dir(base: &str, density: &str) -> String {
+    base.replace('*', density)
+}
+
+/// Errors for an `<adaptive-icon>` without both layers
+fn check_adaptive_icon(file: &Path) -> Result<Vec<String>> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let document = roxmltree::Document::parse(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "adaptive-icon" {
+        return Ok(Vec::new());
+    }
+    Ok(["foreground", "background"]
+        .iter()
+        .filter(|layer| !root.children().any(|c| c.tag_name().name() == **layer))
+        .map(|layer| {
+            format!(
+                "Adaptive launcher icon {} has no <{}>",
+                file.display(),
+                layer
+            )
+        })
+        .collect())
+}
+
+/// Write `source` scaled by `ratio` (below 1) to `target`, keeping an identical file
+fn generate_icon(source: &Path, target: &Path, ratio: f64) -> Result<()> {
+    let (width, height, pixels) =
+        read_rgba(source).with_context(|| format!("Failed to decode {}", source.display()))?;
+    let target_width = ((width as f64 * ratio).round() as u32).max(1);
+    let target_height = ((height as f64 * ratio).round() as u32).max(1);
+    let scaled = downscale(&pixels, width, height, target_width, target_height);
+
+    let encoded = encode_png(target_width, target_height, &scaled)?;
+    if fs::read(target).ok().as_deref() != Some(encoded.as_slice()) {
+        fs::create_dir_all(target.parent().unwrap())?;
+        fs::write(target, &encoded)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+    }
+    Ok(())
+}
+
+/// Encode 8-bit RGBA pixels as a PNG
+pub(crate) fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut encoded, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(pixels)?;
+    }
+    Ok(encoded)
+}
+
+/// Decode a PNG into 8-bit RGBA pixels
+pub(crate) fn read_rgba(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => anyhow::bail!("unexpanded palette image"),
+    };
+    Ok((info.width, info.height, pixels))
+}
+
+/// Scale RGBA pixels down by averaging the source pixels each target pixel covers
+///
+/// Colors are averaged weighted by alpha so transparent pixels don't darken the edges.
+fn downscale(pixels: &[u8], width: u32, height: u32, to_width: u32, to_height: u32) -> Vec<u8> {
+    let span = |target: u32, from: u32, to: u32| {
+        let start = (target as u64 * from as u64 / to as u64) as u32;
+        let end = (((target + 1) as u64 * from as u64).div_ceil(to as u64) as u32).max(start + 1);
+        start..end.min(from)
+    };
+    let mut out = Vec::with_capacity((to_width * to_height * 4) as usize);
+    for ty in 0..to_height {
+        let rows = span(ty, height, to_height);
+        for tx in 0..to_width {
+            let columns = span(tx, width, to_width);
+            let (mut rgb, mut alpha, mut count) = ([0u64; 3], 0u64, 0u64);
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    let i = ((y * width + x) * 4) as usize;
+                    let a = pixels[i + 3] as u64;
+                    for c in 0..3 {
+                        rgb[c] += pixels[i + c] as u64 * a;
+                    }
+                    alpha += a;
+                    count += 1;
+                }
+            }
+            for channel in rgb {
+                out.push(channel.checked_div(alpha).unwrap_or(0) as u8);
+            }
+            out.push((alpha / count.max(1)) as u8);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_png(path: &Path, size: u32) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut encoder = png::Encoder::new(fs::File::create(path).unwrap(), size, size);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels = vec![200u8; (size * size * 4) as usize];
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&pixels)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_missing_densities_and_adaptive_layers() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res = temp.path().join("res");
+        let xhdpi = res.join("mipmap-xhdpi").join("ic_launcher.png");
+        write_png(&xhdpi, 8);
+        let adaptive = res.join("mipmap-anydpi-v26").join("ic_launcher.xml");
+        fs::create_dir_all(adaptive.parent().unwrap())?;
+        fs::write(
+            &adaptive,
+            r#"<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android">
+    <foreground android:drawable="@mipmap/ic_launcher_foreground"/>
+</adaptive-icon>"#,
+        )?;
+        let files = vec![xhdpi, adaptive.clone()];
+        let config = LauncherIconsConfig {
+            densities: Some(vec!["hdpi".to_string(), "xhdpi".to_string()]),
+            generate: None,
+        };
+
+        let check = check_launcher_icons(&[(&res, &files)], &config, temp.path())?;
+        assert_eq!(
+            check.errors,
+            vec![
+                format!(
+                    "Adaptive launcher icon {} has no <background>",
+                    adaptive.display()
+                ),
+                "Launcher icon mipmap-*/ic_launcher is missing densities hdpi (provided: xhdpi)"
+                    .to_string(),
+            ]
+        );
+        assert!(check.generated.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_lower_densities() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res = temp.path().join("res");
+        let xxxhdpi = res.join("mipmap-night-xxxhdpi").join("ic_launcher.png");
+        write_png(&xxxhdpi, 16);
+        let generated = temp.path().join("generated");
+        let config = LauncherIconsConfig {
+            densities: None,
+            generate: Some(true),
+        };
+
+        let files = vec![xxxhdpi];
+        let check = check_launcher_icons(&[(&res, &files)], &config, &generated)?;
+        assert!(check.errors.is_empty(), "{:?}", check.errors);
+        assert_eq!(check.generated.len(), 4);
+        for (density, size) in [("mdpi", 4), ("hdpi", 6), ("xhdpi", 8), ("xxhdpi", 12)] {
+            let icon = generated
+                .join(format!("mipmap-night-{}", density))
+                .join("ic_launcher.png");
+            assert!(check.generated.contains(&(res.clone(), icon.clone())));
+            let (width, height, pixels) = read_rgba(&icon)?;
+            assert_eq!((width, height), (size, size));
+            assert!(pixels.iter().all(|&p| p == 200));
+        }
+        Ok(())
+    }
+}
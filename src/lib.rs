@@ -5,6 +5,12 @@
 )]
 pub mod aapt2;
 pub mod aar;
+pub mod annotations;
+pub mod archive;
+pub mod arsc;
+pub mod audit;
+pub mod axml;
+pub mod bench;
 #[allow(
     clippy::collapsible_if,
     clippy::unnecessary_map_or,
@@ -13,16 +19,64 @@ pub mod aar;
 )]
 pub mod builder;
 pub mod cache;
+pub mod ci;
+pub mod compile_db;
+pub mod config_lint;
+pub mod config_migrate;
+pub mod config_resolve;
+pub mod contrast;
+pub mod convert;
+pub mod coverage;
+pub mod daemon;
+pub mod dark_mode;
+pub mod dedupe;
 #[allow(clippy::cmp_owned, clippy::unwrap_or_default)]
 pub mod dependency;
+pub mod diagnostics;
+pub mod disk_space;
+pub mod doctor;
+pub mod encoding;
 pub mod error;
+pub mod events;
+pub mod explain;
+pub mod feature_flags;
+pub mod filenames;
+pub mod fingerprint;
+pub mod flat_registry;
+pub mod freeze;
+pub mod golden;
+pub mod heartbeat;
+pub mod hooks;
+pub mod host_compat;
+pub mod import;
+pub mod launcher_icons;
+pub mod locales;
+pub mod manifest;
 pub mod merge;
+pub mod observer;
+pub mod owners;
+pub mod package_ids;
+pub mod package_logs;
+pub mod packaging;
+pub mod plugins;
+pub mod references;
+pub mod remote;
+pub mod remote_sources;
+pub mod resource_ignore;
 #[allow(
     clippy::collapsible_if,
     clippy::new_without_default,
     clippy::unwrap_or_default
 )]
 pub mod resource_priority;
+pub mod resource_rewrite;
+pub mod schedule;
+pub mod size_budget;
+pub mod snapshot;
+pub mod staging;
+pub mod stats;
+pub mod strings;
+pub mod tokens;
 #[allow(
     clippy::collapsible_if,
     clippy::needless_borrow,
@@ -30,3 +84,5 @@ pub mod resource_priority;
     clippy::too_many_arguments
 )]
 pub mod types;
+pub mod vcs;
+pub mod xml_schema;
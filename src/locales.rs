@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::types::LocaleQualifierForm;
+
+/// Two- or three-letter qualifiers that are not languages
+const NON_LANGUAGE_QUALIFIERS: &[&str] = &["car", "tv"];
+
+/// Locale of a resource directory, e.g. `zh-rCN` or `b+zh+Hans+CN`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub language: String,
+    pub script: Option<String>,
+    pub region: Option<String>,
+    pub variants: Vec<String>,
+}
+
+impl Locale {
+    /// The locale as a directory qualifier in `form`
+    ///
+    /// Scripts and variants have no legacy form, so such locales stay in BCP-47 form.
+    pub fn qualifier(&self, form: LocaleQualifierForm) -> String {
+        if form == LocaleQualifierForm::Legacy && self.script.is_none() && self.variants.is_empty()
+        {
+            return match &self.region {
+                Some(region) => format!("{}-r{}", self.language, region),
+                None => self.language.clone(),
+            };
+        }
+        let mut parts = vec![self.language.as_str()];
+        parts.extend(self.script.as_deref());
+        parts.extend(self.region.as_deref());
+        parts.extend(self.variants.iter().map(String::as_str));
+        format!("b+{}", parts.join("+"))
+    }
+}
+
+/// Locale qualifier of a resource directory name, as the range of `-` separated segments it
+/// takes and the locale; `Err` explains a locale aapt2 would reject
+pub fn find_locale(dir_name: &str) -> std::result::Result<Option<(usize, usize, Locale)>, String> {
+    let segments: Vec<&str> = dir_name.split('-').collect();
+    // Mobile country and network codes are the only qualifiers before the locale
+    let start = 1 + segments
+        .iter()
+        .skip(1)
+        .take_while(|s| is_network_code(s, "mcc") || is_network_code(s, "mnc"))
+        .count();
+    let Some(segment) = segments.get(start) else {
+        return Ok(None);
+    };
+
+    if let Some(tag) = segment.strip_prefix("b+") {
+        return parse_bcp47(tag).map(|locale| Some((start, start + 1, locale)));
+    }
+    if !is_language(segment) {
+        return Ok(None);
+    }
+    let mut locale = Locale {
+        language: segment.to_string(),
+        script: None,
+        region: None,
+        variants: Vec::new(),
+    };
+    match segments.get(start + 1) {
+        Some(next) if next.len() > 1 && next.starts_with('r') && is_region(&next[1..]) => {
+            locale.region = Some(next[1..].to_ascii_uppercase());
+            Ok(Some((start, start + 2, locale)))
+        }
+        Some(next) if is_region(next) && !next.chars().any(|c| c.is_ascii_lowercase()) => {
+            Err(format!(
+                "region `{0}` needs an `r` prefix: `{1}-r{0}` or `b+{1}+{0}`",
+                next, segment
+            ))
+        }
+        _ => Ok(Some((start, start + 1, locale))),
+    }
+}
+
+/// Parse the `+` separated subtags of a `b+` qualifier
+fn parse_bcp47(tag: &str) -> std::result::Result<Locale, String> {
+    let mut subtags = tag.split('+');
+    let language = subtags.next().unwrap_or_default();
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("`b+{}` does not start with a language", tag));
+    }
+    let mut locale = Locale {
+        language: language.to_ascii_lowercase(),
+        script: None,
+        region: None,
+        variants: Vec::new(),
+    };
+    for subtag in subtags {
+        let alphabetic = subtag.chars().all(|c| c.is_ascii_alphabetic());
+        if subtag.len() == 4 && alphabetic && locale.script.is_none() && locale.region.is_none() {
+            let mut script = subtag.to_ascii_lowercase();
+            script[..1].make_ascii_uppercase();
+            locale.script = Some(script);
+        } else if is_region(subtag) && locale.region.is_none() && locale.variants.is_empty() {
+            locale.region = Some(subtag.to_ascii_uppercase());
+        } else if is_variant(subtag) {
+            locale.variants.push(subtag.to_ascii_lowercase());
+        } else {
+            return Err(format!(
+                "`{}` in `b+{}` is not a script, region or variant",
+                subtag, tag
+            ));
+        }
+    }
+    Ok(locale)
+}
+
+fn is_network_code(segment: &str, prefix: &str) -> bool {
+    segment
+        .strip_prefix(prefix)
+        .is_some_and(|code| !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_language(segment: &str) -> bool {
+    (2..=3).contains(&segment.len())
+        && segment.chars().all(|c| c.is_ascii_lowercase())
+        && !NON_LANGUAGE_QUALIFIERS.contains(&segment)
+}
+
+fn is_region(segment: &str) -> bool {
+    (segment.len() == 2 && segment.chars().all(|c| c.is_ascii_alphabetic()))
+        || (segment.len() == 3 && segment.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_variant(segment: &str) -> bool {
+    let alphanumeric = segment.chars().all(|c| c.is_ascii_alphanumeric());
+    ((5..=8).contains(&segment.len()) && alphanumeric)
+        || (segment.len() == 4 && alphanumeric && segment.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// The directory name with its locale qualifier in `form`, or `None` when it already is
+pub fn normalize_dir_name(dir_name: &str, form: LocaleQualifierForm) -> Option<String> {
+    let (start, end, locale) = find_locale(dir_name).ok()??;
+    let mut segments: Vec<String> = dir_name.split('-').map(str::to_string).collect();
+    segments.splice(start..end, [locale.qualifier(form)]);
+    let normalized = segments.join("-");
+    (normalized != dir_name).then_some(normalized)
+}
+
+/// One error per resource directory of `files` whose locale qualifier aapt2 would reject
+pub fn check_locales(files: &[PathBuf]) -> Vec<String> {
+    let mut invalid = BTreeMap::new();
+    for file in files {
+        let Some(dir) = file.parent() else {
+            continue;
+        };
+        if let Some(name) = dir.file_name().and_then(|n| n.to_str())
+            && let Err(reason) = find_locale(name)
+        {
+            invalid.insert(dir.to_path_buf(), reason);
+        }
+    }
+    invalid
+        .into_iter()
+        .map(|(dir, reason)| format!("{}: invalid locale qualifier, {}", dir.display(), reason))
+        .collect()
+}
+
+/// Copy files from directories whose locale qualifier is not in `form` to `staging_dir`,
+/// under the directory name in `form`, and compile the copies instead
+///
+/// Copies are only rewritten when their content changes so the incremental cache keeps
+/// working. Fails when two files end up at the same path, e.g. `values-zh-rCN/strings.xml`
+/// and `values-b+zh+CN/strings.xml`. Returns the (source, copy) pairs.
+pub fn stage_normalized_locales(
+    files: &mut [PathBuf],
+    form: LocaleQualifierForm,
+    staging_dir: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let target = |file: &Path| -> Option<(String, String)> {
+        let dir = file.parent()?.file_name()?.to_str()?;
+        let name = file.file_name()?.to_str()?.to_string();
+        Some((
+            normalize_dir_name(dir, form).unwrap_or_else(|| dir.to_string()),
+            name,
+        ))
+    };
+    let mut taken: HashMap<(String, String), PathBuf> = HashMap::new();
+    for file in files.iter() {
+        if let Some(key) = target(file)
+            && let Some(other) = taken.insert(key.clone(), file.clone())
+        {
+            anyhow::bail!(
+                "{} and {} are both {}/{} with {} locale qualifiers",
+                other.display(),
+                file.display(),
+                key.0,
+                key.1,
+                form.name()
+            );
+        }
+    }
+
+    let mut staged = Vec::new();
+    for file in files.iter_mut() {
+        let Some(dir) = file
+            .parent()
+            .and_then(|d| d.file_name())
+            .and_then(|n| n.to_str())
+        else {
+            continue;
+        };
+        let Some(normalized) = normalize_dir_name(dir, form) else {
+            continue;
+        };
+        let name = file.file_name().context("Resource file without a name")?;
+        let copy = staging_dir.join(normalized).join(name);
+        let content = fs::read(&*file)?;
+        if fs::read(&copy).ok().as_deref() != Some(content.as_slice()) {
+            fs::create_dir_all(copy.parent().unwrap())?;
+            fs::write(&copy, &content)
+                .with_context(|| format!("Failed to write {}", copy.display()))?;
+        }
+        staged.push((std::mem::replace(file, copy.clone()), copy));
+    }
+    Ok(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_and_normalize_locales() {
+        use LocaleQualifierForm::{Bcp47, Legacy};
+        assert_eq!(
+            normalize_dir_name("values-zh-rCN", Bcp47).as_deref(),
+            Some("values-b+zh+CN")
+        );
+        assert_eq!(
+            normalize_dir_name("values-b+ZH+cn", Legacy).as_deref(),
+            Some("values-zh-rCN")
+        );
+        assert_eq!(
+            normalize_dir_name("values-mcc310-b+es+419-night", Legacy).as_deref(),
+            Some("values-mcc310-es-r419-night")
+        );
+        // Scripts have no legacy form
+        assert_eq!(
+            normalize_dir_name("values-b+zh+hans+CN", Legacy).as_deref(),
+            Some("values-b+zh+Hans+CN")
+        );
+        assert_eq!(normalize_dir_name("values-zh-rCN", Legacy), None);
+        assert_eq!(normalize_dir_name("drawable-hdpi", Bcp47), None);
+        assert_eq!(normalize_dir_name("values-car", Bcp47), None);
+
+        assert!(
+            find_locale("values-zh-CN")
+                .unwrap_err()
+                .contains("`zh-rCN`")
+        );
+        assert!(find_locale("values-b+zh+H").is_err());
+        assert_eq!(
+            check_locales(&[PathBuf::from("res/values-en-US/strings.xml")]),
+            vec![
+                "res/values-en-US: invalid locale qualifier, region `US` needs an `r` prefix: \
+                 `en-rUS` or `b+en+US`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stage_normalized_locales() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res = temp.path().join("res");
+        for dir in ["values", "values-b+zh+CN", "values-zh-rTW"] {
+            fs::create_dir_all(res.join(dir))?;
+            fs::write(res.join(dir).join("strings.xml"), dir)?;
+        }
+        let staging = temp.path().join("staging");
+        let mut files = vec![
+            res.join("values/strings.xml"),
+            res.join("values-b+zh+CN/strings.xml"),
+            res.join("values-zh-rTW/strings.xml"),
+        ];
+
+        let staged = stage_normalized_locales(&mut files, LocaleQualifierForm::Legacy, &staging)?;
+        let copy = staging.join("values-zh-rCN").join("strings.xml");
+        assert_eq!(
+            staged,
+            vec![(res.join("values-b+zh+CN/strings.xml"), copy.clone())]
+        );
+        assert_eq!(files[1], copy);
+        assert_eq!(fs::read_to_string(&copy)?, "values-b+zh+CN");
+
+        let mut files = vec![
+            res.join("values-b+zh+TW/strings.xml"),
+            res.join("values-zh-rTW/strings.xml"),
+        ];
+        let err = stage_normalized_locales(&mut files, LocaleQualifierForm::Legacy, &staging)
+            .unwrap_err();
+        assert!(err.to_string().contains("both values-zh-rTW/strings.xml"));
+        Ok(())
+    }
+}
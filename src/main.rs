@@ -1,33 +1,110 @@
 mod aapt2;
 mod aar;
+mod annotations;
+mod archive;
+mod arsc;
+mod audit;
+mod axml;
+mod bench;
 mod builder;
 mod cache;
+mod ci;
 mod cli;
+mod compile_db;
+mod config_lint;
+mod config_migrate;
+mod config_resolve;
+mod contrast;
+mod convert;
+mod coverage;
+mod daemon;
+mod dark_mode;
+mod dedupe;
 mod dependency;
+mod diagnostics;
+mod disk_space;
+mod doctor;
+mod encoding;
 mod error;
+mod events;
+mod explain;
+mod feature_flags;
+mod filenames;
+mod fingerprint;
+mod flat_registry;
+mod freeze;
+mod golden;
+mod heartbeat;
+mod hooks;
+mod host_compat;
+mod import;
+mod launcher_icons;
+mod locales;
+mod manifest;
+mod merge;
+mod observer;
+mod owners;
+mod package_ids;
+mod package_logs;
+mod packaging;
+mod plugins;
+mod references;
+mod remote;
+mod remote_sources;
+mod resource_ignore;
 mod resource_priority;
+mod resource_rewrite;
+mod schedule;
+mod size_budget;
+mod snapshot;
+mod staging;
+mod stats;
+mod strings;
+mod tokens;
 mod types;
+mod vcs;
+mod xml_schema;
 
 use anyhow::Result;
 use clap::Parser;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
-use cli::Cli;
+use cli::{Cli, Commands};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse CLI first to check for quiet mode
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(cli::expand_param_files(std::env::args_os().collect())?);
 
     // Initialize logging - output to both console and file
-    // In quiet mode, only show error level logs
-    let log_level = if cli.quiet { "error" } else { "info" };
+    // In quiet and CI mode, only show error level logs
+    let log_level = if cli.quiet || cli.ci { "error" } else { "info" };
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
 
     // Build subscriber layers
-    let console_layer = fmt::layer().with_writer(std::io::stdout).with_ansi(true);
-    let subscriber = tracing_subscriber::registry().with(env_filter);
+    // Logs move to stderr when build events are streamed to stdout
+    let events_on_stdout = matches!(
+        &cli.command,
+        Commands::Build(args) if args.event_stream.as_deref() == Some(events::STDOUT_TARGET)
+    );
+    let console_writer = if events_on_stdout {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    // Plain output: no ANSI colors and no progress bars
+    let color = !cli.no_color && !cli.ci && std::env::var_os("NO_COLOR").is_none();
+    if !color {
+        colored::control::set_override(false);
+    }
+    builder::set_progress_bars(!cli.non_interactive && !cli.ci);
+    let console_layer = fmt::layer().with_writer(console_writer).with_ansi(color);
+    // Events of multi-config builds are also written to per-package logs
+    let subscriber = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(package_logs::PackageLogLayer);
 
     // Add file layer if --log-file is specified
     if let Some(ref log_path) = cli.log_file {
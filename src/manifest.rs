@@ -0,0 +1,573 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::error::AsbError;
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+/// Manifest elements never carried over in merge mode; skins must not request permissions
+const EXCLUDED_MANIFEST_ELEMENTS: &[&str] = &[
+    "uses-permission",
+    "uses-permission-sdk-23",
+    "permission",
+    "permission-group",
+    "permission-tree",
+];
+
+/// Expand `${name}` placeholders; unknown placeholders are left untouched
+fn expand_manifest_placeholders(
+    content: &str,
+    package_name: &str,
+    placeholders: &BTreeMap<String, String>,
+) -> String {
+    let mut values = BTreeMap::from([
+        ("applicationId", package_name),
+        ("packageName", package_name),
+    ]);
+    for (name, value) in placeholders {
+        values.insert(name.as_str(), value.as_str());
+    }
+
+    let mut result = content.to_string();
+    for (name, value) in values {
+        result = result.replace(&format!("${{{}}}", name), value);
+    }
+    result
+}
+
+/// Create a manifest in compiled_dir that carries the user manifest's elements
+/// The root is generated like the minimal manifest; only the user's namespace
+/// declarations and child elements (minus permissions) are copied over
+pub(crate) fn create_merged_manifest(
+    user_manifest: &Path,
+    package_name: &str,
+    placeholders: &BTreeMap<String, String>,
+    compiled_dir: &Path,
+) -> Result<PathBuf> {
+    let content = fs::read_to_string(user_manifest)
+        .with_context(|| format!("Failed to read manifest {}", user_manifest.display()))?;
+    let content = expand_manifest_placeholders(&content, package_name, placeholders);
+    let document = roxmltree::Document::parse(&content)
+        .with_context(|| format!("Failed to parse manifest {}", user_manifest.display()))?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "manifest" {
+        anyhow::bail!(
+            "Expected <manifest> as the root element of {}",
+            user_manifest.display()
+        );
+    }
+
+    let mut merged = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest");
+    let mut has_android_ns = false;
+    for ns in root.namespaces() {
+        match ns.name() {
+            Some(prefix) => {
+                has_android_ns |= prefix == "android";
+                merged.push_str(&format!(" xmlns:{}=\"{}\"", prefix, ns.uri()));
+            }
+            None => merged.push_str(&format!(" xmlns=\"{}\"", ns.uri())),
+        }
+    }
+    if !has_android_ns {
+        merged.push_str(" xmlns:android=\"http://schemas.android.com/apk/res/android\"");
+    }
+    merged.push_str(&format!(" package=\"{}\">\n", package_name));
+
+    for child in root.children().filter(|n| n.is_element()) {
+        let name = child.tag_name().name();
+        if EXCLUDED_MANIFEST_ELEMENTS.contains(&name) {
+            debug!("Skipping <{}> from {}", name, user_manifest.display());
+            continue;
+        }
+        merged.push_str("    ");
+        merged.push_str(&content[child.range()]);
+        merged.push('\n');
+    }
+    merged.push_str("</manifest>\n");
+
+    fs::create_dir_all(compiled_dir)?;
+    let merged_manifest = compiled_dir.join("AndroidManifest.merged.xml");
+    // Only rewrite when changed so the manifest's mtime stays stable across builds
+    if fs::read_to_string(&merged_manifest).ok().as_deref() != Some(merged.as_str()) {
+        fs::write(&merged_manifest, merged)?;
+        info!("Created merged manifest at: {}", merged_manifest.display());
+    }
+
+    Ok(merged_manifest)
+}
+
+/// Escape a value for a double-quoted XML attribute
+pub(crate) fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Rebuild an element's start tag from its parsed attributes
+/// Namespace declarations are only emitted for the root element
+fn start_tag(node: roxmltree::Node, is_root: bool) -> String {
+    let mut tag = format!("<{}", node.tag_name().name());
+    if is_root {
+        for ns in node.namespaces() {
+            match ns.name() {
+                Some(prefix) => tag.push_str(&format!(" xmlns:{}=\"{}\"", prefix, ns.uri())),
+                None => tag.push_str(&format!(" xmlns=\"{}\"", ns.uri())),
+            }
+        }
+        if !node.namespaces().any(|ns| ns.uri() == ANDROID_NS) {
+            tag.push_str(&format!(" xmlns:android=\"{}\"", ANDROID_NS));
+        }
+    }
+    for attr in node.attributes() {
+        let prefix = attr.namespace().and_then(|uri| node.lookup_prefix(uri));
+        let name = match prefix {
+            Some(prefix) => format!("{}:{}", prefix, attr.name()),
+            None => attr.name().to_string(),
+        };
+        tag.push_str(&format!(" {}=\"{}\"", name, escape_attr(attr.value())));
+    }
+    tag.push('>');
+    tag
+}
+
+fn android_attr<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((ANDROID_NS, name))
+}
+
+fn child_elements<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// Whitespace-insensitive key used to de-duplicate copied elements
+fn element_key(raw: &str) -> String {
+    raw.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Key identifying a `<uses-feature>` (by name, or by GL ES version)
+fn feature_key(node: roxmltree::Node) -> Option<String> {
+    android_attr(node, "name")
+        .map(str::to_string)
+        .or_else(|| android_attr(node, "glEsVersion").map(|v| format!("glEsVersion={}", v)))
+}
+
+fn meta_data_value<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    android_attr(node, "value").or_else(|| android_attr(node, "resource"))
+}
+
+fn parse_manifest<'input>(
+    path: &Path,
+    content: &'input str,
+) -> Result<roxmltree::Document<'input>> {
+    let document = roxmltree::Document::parse(content)
+        .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+    if document.root_element().tag_name().name() != "manifest" {
+        anyhow::bail!(
+            "Expected <manifest> as the root element of {}",
+            path.display()
+        );
+    }
+    Ok(document)
+}
+
+/// Merge AAR manifests into the app manifest and write the result to `output`
+///
+/// Only the subset of AGP's merge rules that matters for resource-only packages is applied:
+/// - `<uses-sdk>`: a library minSdkVersion above the app's is an error; when the app
+///   declares none, the highest library value is used
+/// - `<queries>` children and `<uses-feature>` elements are unioned
+/// - `<application>` `<meta-data>` is unioned by name
+///
+/// The app manifest wins every other conflict; those are returned for reporting.
+/// Permissions and components are never taken from libraries.
+pub fn merge_aar_manifests(
+    app_manifest: &Path,
+    aar_manifests: &[PathBuf],
+    output: &Path,
+) -> Result<Vec<String>> {
+    let app_content = fs::read_to_string(app_manifest)
+        .with_context(|| format!("Failed to read manifest {}", app_manifest.display()))?;
+    let app_document = parse_manifest(app_manifest, &app_content)?;
+    let app_root = app_document.root_element();
+
+    let app_uses_sdk = child_elements(app_root, "uses-sdk").next();
+    let app_min_sdk = app_uses_sdk
+        .and_then(|n| android_attr(n, "minSdkVersion"))
+        .and_then(|v| v.parse::<u32>().ok());
+
+    let mut query_keys: HashSet<String> = HashSet::new();
+    for queries in child_elements(app_root, "queries") {
+        for query in queries.children().filter(|n| n.is_element()) {
+            query_keys.insert(element_key(&app_content[query.range()]));
+        }
+    }
+    let mut features: HashMap<String, Option<String>> = HashMap::new();
+    for feature in child_elements(app_root, "uses-feature") {
+        if let Some(key) = feature_key(feature) {
+            features.insert(key, android_attr(feature, "required").map(str::to_string));
+        }
+    }
+    let mut meta_data: HashMap<String, Option<String>> = HashMap::new();
+    for application in child_elements(app_root, "application") {
+        for meta in child_elements(application, "meta-data") {
+            if let Some(name) = android_attr(meta, "name") {
+                meta_data.insert(name.to_string(), meta_data_value(meta).map(str::to_string));
+            }
+        }
+    }
+
+    let mut library_min_sdk: Option<u32> = None;
+    let mut added_queries = Vec::new();
+    let mut added_features = Vec::new();
+    let mut added_meta_data = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for aar_manifest in aar_manifests {
+        let content = fs::read_to_string(aar_manifest)
+            .with_context(|| format!("Failed to read manifest {}", aar_manifest.display()))?;
+        let document = parse_manifest(aar_manifest, &content)?;
+        let root = document.root_element();
+
+        for uses_sdk in child_elements(root, "uses-sdk") {
+            let Some(min_sdk) =
+                android_attr(uses_sdk, "minSdkVersion").and_then(|v| v.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            match app_min_sdk {
+                Some(app_min) if min_sdk > app_min => {
+                    return Err(AsbError::ManifestMergeError(format!(
+                        "uses-sdk:minSdkVersion {} cannot be smaller than version {} declared in {}",
+                        app_min,
+                        min_sdk,
+                        aar_manifest.display()
+                    ))
+                    .into());
+                }
+                Some(_) => {}
+                None => library_min_sdk = library_min_sdk.max(Some(min_sdk)),
+            }
+        }
+
+        for queries in child_elements(root, "queries") {
+            for query in queries.children().filter(|n| n.is_element()) {
+                let raw = &content[query.range()];
+                if query_keys.insert(element_key(raw)) {
+                    added_queries.push(raw.to_string());
+                }
+            }
+        }
+
+        for feature in child_elements(root, "uses-feature") {
+            let Some(key) = feature_key(feature) else {
+                continue;
+            };
+            let required = android_attr(feature, "required").map(str::to_string);
+            match features.get(&key) {
+                Some(existing) if *existing != required => conflicts.push(format!(
+                    "uses-feature {} has android:required={} in {} but {} elsewhere; keeping {}",
+                    key,
+                    required.as_deref().unwrap_or("(unset)"),
+                    aar_manifest.display(),
+                    existing.as_deref().unwrap_or("(unset)"),
+                    existing.as_deref().unwrap_or("(unset)")
+                )),
+                Some(_) => {}
+                None => {
+                    features.insert(key, required);
+                    added_features.push(content[feature.range()].to_string());
+                }
+            }
+        }
+
+        for application in child_elements(root, "application") {
+            for meta in child_elements(application, "meta-data") {
+                let Some(name) = android_attr(meta, "name") else {
+                    continue;
+                };
+                let value = meta_data_value(meta).map(str::to_string);
+                match meta_data.get(name) {
+                    Some(existing) if *existing != value => conflicts.push(format!(
+                        "meta-data {} is {} in {} but {} elsewhere; keeping {}",
+                        name,
+                        value.as_deref().unwrap_or("(unset)"),
+                        aar_manifest.display(),
+                        existing.as_deref().unwrap_or("(unset)"),
+                        existing.as_deref().unwrap_or("(unset)")
+                    )),
+                    Some(_) => {}
+                    None => {
+                        meta_data.insert(name.to_string(), value);
+                        added_meta_data.push(content[meta.range()].to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    // Rebuild the app manifest, splicing library elements into existing containers
+    let mut merged = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    merged.push_str(&start_tag(app_root, true));
+    merged.push('\n');
+    if app_uses_sdk.is_none()
+        && let Some(min_sdk) = library_min_sdk
+    {
+        merged.push_str(&format!(
+            "    <uses-sdk android:minSdkVersion=\"{}\" />\n",
+            min_sdk
+        ));
+    }
+
+    let splice = |merged: &mut String, node: roxmltree::Node, added: &mut Vec<String>| {
+        merged.push_str("    ");
+        merged.push_str(&start_tag(node, false));
+        merged.push('\n');
+        for child in node.children().filter(|n| n.is_element()) {
+            merged.push_str("        ");
+            merged.push_str(&app_content[child.range()]);
+            merged.push('\n');
+        }
+        for raw in added.drain(..) {
+            merged.push_str("        ");
+            merged.push_str(&raw);
+            merged.push('\n');
+        }
+        merged.push_str(&format!("    </{}>\n", node.tag_name().name()));
+    };
+
+    for child in app_root.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "queries" if !added_queries.is_empty() => {
+                splice(&mut merged, child, &mut added_queries)
+            }
+            "application" if !added_meta_data.is_empty() => {
+                splice(&mut merged, child, &mut added_meta_data)
+            }
+            _ => {
+                merged.push_str("    ");
+                merged.push_str(&app_content[child.range()]);
+                merged.push('\n');
+            }
+        }
+    }
+
+    // Containers the app manifest doesn't have yet
+    for (container, added) in [
+        ("queries", &added_queries),
+        ("application", &added_meta_data),
+    ] {
+        if added.is_empty() {
+            continue;
+        }
+        merged.push_str(&format!("    <{}>\n", container));
+        for raw in added {
+            merged.push_str("        ");
+            merged.push_str(raw);
+            merged.push('\n');
+        }
+        merged.push_str(&format!("    </{}>\n", container));
+    }
+    for raw in &added_features {
+        merged.push_str("    ");
+        merged.push_str(raw);
+        merged.push('\n');
+    }
+    merged.push_str("</manifest>\n");
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Only rewrite when changed so the manifest's mtime stays stable across builds
+    if fs::read_to_string(output).ok().as_deref() != Some(merged.as_str()) {
+        fs::write(output, merged)?;
+        info!(
+            "Merged {} AAR manifests into {}",
+            aar_manifests.len(),
+            output.display()
+        );
+    }
+
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_merged_manifest() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let user_manifest = temp_dir.path().join("AndroidManifest.xml");
+        fs::write(
+            &user_manifest,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:tools="http://schemas.android.com/tools"
+    package="com.example.original">
+    <uses-permission android:name="android.permission.INTERNET" />
+    <!-- comments are dropped -->
+    <application android:label="${appLabel}" tools:ignore="MissingApplicationIcon">
+        <meta-data android:name="skin.id" android:value="${applicationId}" />
+    </application>
+</manifest>
+"#,
+        )?;
+
+        let placeholders = BTreeMap::from([("appLabel".to_string(), "Red Skin".to_string())]);
+        let compiled_dir = temp_dir.path().join("compiled");
+        let merged = create_merged_manifest(
+            &user_manifest,
+            "com.example.skin.red",
+            &placeholders,
+            &compiled_dir,
+        )?;
+
+        let content = fs::read_to_string(&merged)?;
+        let document = roxmltree::Document::parse(&content)?;
+        let root = document.root_element();
+        assert_eq!(root.attribute("package"), Some("com.example.skin.red"));
+        assert!(!content.contains("uses-permission"));
+        assert!(!content.contains("comments are dropped"));
+        assert!(content.contains(r#"android:label="Red Skin""#));
+        assert!(content.contains(r#"android:value="com.example.skin.red""#));
+        assert!(content.contains("xmlns:tools="));
+
+        // Unchanged output is not rewritten
+        let modified = fs::metadata(&merged)?.modified()?;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        create_merged_manifest(
+            &user_manifest,
+            "com.example.skin.red",
+            &placeholders,
+            &compiled_dir,
+        )?;
+        assert_eq!(fs::metadata(&merged)?.modified()?, modified);
+
+        Ok(())
+    }
+
+    fn write_aar_manifest(dir: &Path, name: &str, body: &str) -> Result<PathBuf> {
+        let path = dir.join(name);
+        fs::write(
+            &path,
+            format!(
+                r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.lib.{}">
+{}
+</manifest>"#,
+                name.trim_end_matches(".xml"),
+                body
+            ),
+        )?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_merge_aar_manifests() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let app_manifest = temp_dir.path().join("AndroidManifest.xml");
+        fs::write(
+            &app_manifest,
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.skin">
+    <application android:label="Skin">
+        <meta-data android:name="theme.version" android:value="2" />
+    </application>
+</manifest>"#,
+        )?;
+
+        let first = write_aar_manifest(
+            temp_dir.path(),
+            "first.xml",
+            r#"    <uses-sdk android:minSdkVersion="21" />
+    <uses-permission android:name="android.permission.INTERNET" />
+    <queries>
+        <package android:name="com.example.host" />
+    </queries>
+    <uses-feature android:name="android.hardware.touchscreen" android:required="false" />
+    <application>
+        <meta-data android:name="theme.version" android:value="1" />
+        <meta-data android:name="lib.flag" android:value="on" />
+    </application>"#,
+        )?;
+        let second = write_aar_manifest(
+            temp_dir.path(),
+            "second.xml",
+            r#"    <uses-sdk android:minSdkVersion="24" />
+    <queries>
+        <package   android:name="com.example.host" />
+        <intent>
+            <action android:name="android.intent.action.VIEW" />
+        </intent>
+    </queries>
+    <uses-feature android:name="android.hardware.touchscreen" android:required="true" />"#,
+        )?;
+
+        let output = temp_dir.path().join("merged.xml");
+        let conflicts = merge_aar_manifests(&app_manifest, &[first, second], &output)?;
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts[0].contains("theme.version"));
+        assert!(conflicts[1].contains("android.hardware.touchscreen"));
+
+        let content = fs::read_to_string(&output)?;
+        let document = roxmltree::Document::parse(&content)?;
+        let root = document.root_element();
+        assert_eq!(root.attribute("package"), Some("com.example.skin"));
+
+        let uses_sdk = child_elements(root, "uses-sdk").next().unwrap();
+        assert_eq!(android_attr(uses_sdk, "minSdkVersion"), Some("24"));
+        assert!(!content.contains("uses-permission"));
+
+        // Duplicate queries are collapsed, distinct ones kept
+        let queries = child_elements(root, "queries").next().unwrap();
+        assert_eq!(queries.children().filter(|n| n.is_element()).count(), 2);
+
+        // The app keeps its meta-data value; library-only entries are added
+        let application = child_elements(root, "application").next().unwrap();
+        assert_eq!(android_attr(application, "label"), Some("Skin"));
+        let meta: Vec<_> = child_elements(application, "meta-data")
+            .map(|n| (android_attr(n, "name"), android_attr(n, "value")))
+            .collect();
+        assert_eq!(
+            meta,
+            vec![
+                (Some("theme.version"), Some("2")),
+                (Some("lib.flag"), Some("on"))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_aar_manifests_min_sdk_conflict() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let app_manifest = temp_dir.path().join("AndroidManifest.xml");
+        fs::write(
+            &app_manifest,
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example.skin">
+    <uses-sdk android:minSdkVersion="21" />
+</manifest>"#,
+        )?;
+        let library = write_aar_manifest(
+            temp_dir.path(),
+            "library.xml",
+            r#"    <uses-sdk android:minSdkVersion="26" />"#,
+        )?;
+
+        let output = temp_dir.path().join("merged.xml");
+        let err = merge_aar_manifests(&app_manifest, &[library], &output).unwrap_err();
+        assert!(err.to_string().contains("minSdkVersion 21"));
+        assert!(!output.exists());
+
+        Ok(())
+    }
+}
@@ -1,9 +1,78 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use tracing::info;
 
+use crate::types::BuildConfig;
+
+/// Bundle manifest extension, written next to the bundle
+pub const BUNDLE_MANIFEST_EXTENSION: &str = "manifest.json";
+
+/// Module of a bundle with the version it was built with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleModule {
+    pub name: String,
+    pub package_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_code: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_name: Option<String>,
+    /// Size of the module's skin package in bytes
+    pub size: u64,
+}
+
+/// Modules of a bundle, in bundle order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub modules: Vec<BundleModule>,
+}
+
+/// Module name of a config in a bundle: its build target, e.g. `brandA` or `brandA-night`
+pub fn module_name(config: &BuildConfig) -> String {
+    config.target()[1..].replace(':', "-")
+}
+
+/// Merge the packages built from `configs` into a bundle at `output`, with a manifest of
+/// the module versions next to it
+pub fn write_bundle(modules: &[(&BuildConfig, PathBuf)], output: &Path) -> Result<BundleManifest> {
+    let mut packages = Vec::new();
+    let mut manifest = BundleManifest {
+        modules: Vec::new(),
+    };
+    for (config, apk_path) in modules {
+        let name = module_name(config);
+        if manifest.modules.iter().any(|m| m.name == name) {
+            anyhow::bail!("Two configs are bundled as module '{}'", name);
+        }
+        manifest.modules.push(BundleModule {
+            name: name.clone(),
+            package_name: config.package_name.clone(),
+            version_code: config.version_code,
+            version_name: config.version_name.clone(),
+            size: std::fs::metadata(apk_path)
+                .with_context(|| format!("Failed to read {}", apk_path.display()))?
+                .len(),
+        });
+        packages.push(ModuleSkinPackage {
+            module_name: name,
+            apk_path: apk_path.clone(),
+        });
+    }
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    SkinMerger::merge_packages(&packages, output)?;
+    let manifest_path = output.with_extension(BUNDLE_MANIFEST_EXTENSION);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(manifest)
+}
+
 /// Module skin package info
 #[derive(Debug)]
 pub struct ModuleSkinPackage {
@@ -11,7 +80,40 @@ pub struct ModuleSkinPackage {
     pub apk_path: PathBuf,
 }
 
+/// Version of the merged bundle format written by [`SkinMerger::merge_packages`]
+///
+/// Version 1 stores `name|size` per module; version 2 adds each module's SHA-256 and a
+/// trailing checksum of the whole bundle.
+pub const FORMAT_VERSION: u32 = 2;
+
+const FORMAT_MAGIC: &str = "ASB_MERGED_V";
+const CHECKSUM_PREFIX: &str = "checksum|";
+
+/// Module of a merged bundle as listed in its header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEntry {
+    pub module_name: String,
+    /// Offset of the module's data from the start of the bundle
+    pub offset: u64,
+    pub size: u64,
+    /// SHA-256 of the module's data (format version 2 and later)
+    pub sha256: Option<String>,
+}
+
+/// Parsed header of a merged bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BundleIndex {
+    pub version: u32,
+    pub modules: Vec<BundleEntry>,
+    /// SHA-256 of everything before the checksum line (format version 2 and later)
+    pub checksum: Option<String>,
+}
+
 /// Utility for merging multiple module skin packages
+///
+/// Bundles are streamed through bounded buffers in both directions, so their size is not
+/// limited by memory.
 pub struct SkinMerger;
 
 impl SkinMerger {
@@ -32,111 +134,603 @@ impl SkinMerger {
             }
         }
 
-        // Create a merged structure
-        let mut merged_data = Vec::new();
+        let output_file = File::create(output_path)
+            .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+        let mut writer = HashingWriter {
+            inner: BufWriter::new(output_file),
+            hasher: Sha256::new(),
+        };
 
         // Write header
-        let header = format!("ASB_MERGED_V1\n{}\n", packages.len());
-        merged_data.extend_from_slice(header.as_bytes());
+        write!(
+            writer,
+            "{}{}\n{}\n",
+            FORMAT_MAGIC,
+            FORMAT_VERSION,
+            packages.len()
+        )?;
 
-        // For each module, write: module_name|size|data
+        // For each module, write: module_name|size|sha256 followed by the data
         for package in packages {
-            let mut apk_data = Vec::new();
-            let mut file = File::open(&package.apk_path).with_context(|| {
-                format!(
-                    "Failed to open skin package: {}",
-                    package.apk_path.display()
-                )
-            })?;
-            file.read_to_end(&mut apk_data)?;
+            let open = || {
+                File::open(&package.apk_path).with_context(|| {
+                    format!(
+                        "Failed to open skin package: {}",
+                        package.apk_path.display()
+                    )
+                })
+            };
 
-            // Write module metadata
-            let metadata = format!("{}|{}\n", package.module_name, apk_data.len());
-            merged_data.extend_from_slice(metadata.as_bytes());
+            // The hash precedes the data, so each package is read twice
+            let mut hasher = HashingWriter {
+                inner: std::io::sink(),
+                hasher: Sha256::new(),
+            };
+            let size = std::io::copy(&mut open()?, &mut hasher)?;
+            writeln!(
+                writer,
+                "{}|{}|{:x}",
+                package.module_name,
+                size,
+                hasher.hasher.finalize()
+            )?;
 
-            // Write skin package data
-            merged_data.extend_from_slice(&apk_data);
+            let copied = std::io::copy(&mut open()?, &mut writer)?;
+            if copied != size {
+                anyhow::bail!(
+                    "Skin package changed while merging: {}",
+                    package.apk_path.display()
+                );
+            }
         }
 
-        // Write merged file
-        let mut output_file = File::create(output_path)
-            .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
-        output_file.write_all(&merged_data)?;
+        // Close the bundle with the checksum of everything written so far
+        let checksum = writer.hasher.finalize();
+        writeln!(writer.inner, "{}{:x}", CHECKSUM_PREFIX, checksum)?;
+        writer.inner.flush()?;
 
         info!("Merged package created: {}", output_path.display());
         Ok(())
     }
 
+    /// Check every module and the bundle checksum of a merged package
+    ///
+    /// Fails with every mismatch found, or when the bundle predates checksums.
+    pub fn verify(merged_path: &Path) -> Result<BundleIndex> {
+        let index = Self::scan(merged_path, |_, _| Ok(()))?;
+        if index.version < 2 {
+            anyhow::bail!(
+                "{} uses bundle format version {}, which has no checksums",
+                merged_path.display(),
+                index.version
+            );
+        }
+        Ok(index)
+    }
+
     /// Extract individual modules from a merged package
+    ///
+    /// Checksums are verified while extracting; on a mismatch the extracted files are
+    /// removed again.
     pub fn extract_modules(
         merged_path: &Path,
         output_dir: &Path,
     ) -> Result<Vec<ModuleSkinPackage>> {
-        let mut file = File::open(merged_path)?;
-        let mut content = Vec::new();
-        file.read_to_end(&mut content)?;
+        std::fs::create_dir_all(output_dir)?;
 
-        // Read header line (text)
-        let header_end = content
-            .iter()
-            .position(|&b| b == b'\n')
-            .context("Missing header line")?;
-        let header = std::str::from_utf8(&content[..header_end])?;
+        let mut packages = Vec::new();
+        let scanned = Self::scan(merged_path, |entry, data| {
+            let apk_path = output_dir.join(format!("{}.skin", entry.module_name));
+            let mut apk_file = BufWriter::new(File::create(&apk_path)?);
+            std::io::copy(data, &mut apk_file)?;
+            apk_file.flush()?;
+            packages.push(ModuleSkinPackage {
+                module_name: entry.module_name.clone(),
+                apk_path,
+            });
+            Ok(())
+        });
+        if let Err(e) = scanned {
+            for package in &packages {
+                let _ = std::fs::remove_file(&package.apk_path);
+            }
+            return Err(e).with_context(|| format!("Corrupted bundle {}", merged_path.display()));
+        }
+
+        info!("Extracted {} modules from merged package", packages.len());
+        Ok(packages)
+    }
 
-        if !header.starts_with("ASB_MERGED_V1") {
-            anyhow::bail!("Invalid merged package format");
+    /// Extract one module from a merged package without reading the others
+    ///
+    /// Only the module's own hash is verified; use [`SkinMerger::verify`] to check the
+    /// whole bundle.
+    pub fn extract_module(
+        merged_path: &Path,
+        module_name: &str,
+        output_dir: &Path,
+    ) -> Result<ModuleSkinPackage> {
+        let mut module = Self::open_module(merged_path, module_name)?;
+
+        std::fs::create_dir_all(output_dir)?;
+        let apk_path = output_dir.join(format!("{}.skin", module_name));
+        let mut apk_file = BufWriter::new(File::create(&apk_path)?);
+        if let Err(e) = std::io::copy(&mut module, &mut apk_file).and_then(|_| apk_file.flush()) {
+            drop(apk_file);
+            let _ = std::fs::remove_file(&apk_path);
+            return Err(e).with_context(|| format!("Corrupted bundle {}", merged_path.display()));
         }
-        let mut offset = header_end + 1;
 
-        // Read count line (text)
-        let count_end = content[offset..]
+        info!("Extracted module {} from merged package", module_name);
+        Ok(ModuleSkinPackage {
+            module_name: module_name.to_string(),
+            apk_path,
+        })
+    }
+
+    /// List the modules of a merged package from its metadata, without reading module data
+    pub fn list_modules(merged_path: &Path) -> Result<BundleIndex> {
+        let mut reader = BufReader::new(
+            File::open(merged_path)
+                .with_context(|| format!("Failed to open {}", merged_path.display()))?,
+        );
+        Self::read_index(&mut reader)
+            .with_context(|| format!("Invalid bundle {}", merged_path.display()))
+    }
+
+    /// Open one module of a merged package as a stream over its bytes in the bundle
+    ///
+    /// Nothing else of the bundle is read beyond its metadata. The module's hash is
+    /// checked once the stream is read to the end.
+    pub fn open_module(merged_path: &Path, module_name: &str) -> Result<ModuleReader> {
+        let mut reader = BufReader::new(
+            File::open(merged_path)
+                .with_context(|| format!("Failed to open {}", merged_path.display()))?,
+        );
+        let index = Self::read_index(&mut reader)
+            .with_context(|| format!("Invalid bundle {}", merged_path.display()))?;
+        let entry = index
+            .modules
             .iter()
-            .position(|&b| b == b'\n')
-            .context("Missing count line")?;
-        let count_str = std::str::from_utf8(&content[offset..offset + count_end])?;
-        let count: usize = count_str.parse().context("Invalid package count")?;
-        offset += count_end + 1;
+            .find(|m| m.module_name == module_name)
+            .with_context(|| {
+                format!(
+                    "No module '{}' in {} (modules: {})",
+                    module_name,
+                    merged_path.display(),
+                    index
+                        .modules
+                        .iter()
+                        .map(|m| m.module_name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?
+            .clone();
 
-        std::fs::create_dir_all(output_dir)?;
+        reader.seek(SeekFrom::Start(entry.offset))?;
+        Ok(ModuleReader {
+            data: reader.take(entry.size),
+            hasher: Sha256::new(),
+            entry,
+        })
+    }
 
-        let mut packages = Vec::new();
+    /// Read the header and module metadata of a merged package, seeking past module data
+    fn read_index<R: Read + Seek>(reader: &mut R) -> Result<BundleIndex> {
+        let (version, count) = read_header(reader)?;
 
+        let mut modules = Vec::new();
         for _ in 0..count {
-            // Read metadata line (text)
-            let metadata_end = content[offset..]
-                .iter()
-                .position(|&b| b == b'\n')
-                .context("Missing module metadata")?;
-            let metadata = std::str::from_utf8(&content[offset..offset + metadata_end])?;
-            let parts: Vec<&str> = metadata.split('|').collect();
-
-            if parts.len() != 2 {
-                anyhow::bail!("Invalid module metadata format");
+            let mut entry = read_module_line(reader, version)?;
+            entry.offset = reader.stream_position()?;
+            let end = reader.seek(SeekFrom::Current(entry.size as i64))?;
+            if end > reader.seek(SeekFrom::End(0))? {
+                anyhow::bail!("Invalid skin package data size");
             }
+            reader.seek(SeekFrom::Start(end))?;
+            modules.push(entry);
+        }
+
+        let checksum = if version >= 2 {
+            Some(read_checksum_line(reader)?)
+        } else {
+            None
+        };
+        Ok(BundleIndex {
+            version,
+            modules,
+            checksum,
+        })
+    }
+
+    /// Read a merged package front to back, handing each module's data to `visit`
+    ///
+    /// Module hashes and the bundle checksum are checked on the way; fails with every
+    /// mismatch found after the whole bundle has been read.
+    fn scan(
+        merged_path: &Path,
+        mut visit: impl FnMut(&BundleEntry, &mut dyn Read) -> Result<()>,
+    ) -> Result<BundleIndex> {
+        let file = File::open(merged_path)
+            .with_context(|| format!("Failed to open {}", merged_path.display()))?;
+        let mut reader = HashingReader {
+            inner: BufReader::new(file),
+            hasher: Sha256::new(),
+            position: 0,
+        };
+        let (version, count) = read_header(&mut reader)?;
 
-            let module_name = parts[0];
-            let size: usize = parts[1].parse().context("Invalid module size")?;
-            offset += metadata_end + 1;
+        let mut modules = Vec::new();
+        let mut mismatches = Vec::new();
+        for _ in 0..count {
+            let mut entry = read_module_line(&mut reader, version)?;
+            entry.offset = reader.position;
 
-            // Extract skin package data (binary)
-            if offset + size > content.len() {
+            let mut data = HashingReader {
+                inner: (&mut reader).take(entry.size),
+                hasher: Sha256::new(),
+                position: 0,
+            };
+            visit(&entry, &mut data)?;
+            // Hash whatever the visitor left unread
+            std::io::copy(&mut data, &mut std::io::sink())?;
+            if data.position != entry.size {
                 anyhow::bail!("Invalid skin package data size");
             }
-            let apk_data = &content[offset..offset + size];
-            let apk_path = output_dir.join(format!("{}.skin", module_name));
 
-            let mut apk_file = File::create(&apk_path)?;
-            apk_file.write_all(apk_data)?;
+            let actual = format!("{:x}", data.hasher.finalize());
+            if let Some(ref expected) = entry.sha256
+                && *expected != actual
+            {
+                mismatches.push(format!(
+                    "module '{}': expected sha256 {}, got {}",
+                    entry.module_name, expected, actual
+                ));
+            }
+            modules.push(entry);
+        }
 
+        let checksum = if version >= 2 {
+            let actual = format!("{:x}", reader.hasher.clone().finalize());
+            let expected = read_checksum_line(&mut reader)?;
+            if expected != actual {
+                mismatches.push(format!(
+                    "bundle: expected checksum {}, got {}",
+                    expected, actual
+                ));
+            }
+            Some(expected)
+        } else {
+            None
+        };
+        if !mismatches.is_empty() {
+            anyhow::bail!("Checksum mismatch:\n  {}", mismatches.join("\n  "));
+        }
+
+        Ok(BundleIndex {
+            version,
+            modules,
+            checksum,
+        })
+    }
+}
+
+/// Data of one bundle module, read straight out of the bundle
+///
+/// Reading to the end checks the module's SHA-256 (format version 2 and later); a mismatch
+/// or a truncated bundle fails the final read with [`std::io::ErrorKind::InvalidData`].
+pub struct ModuleReader {
+    data: std::io::Take<BufReader<File>>,
+    hasher: Sha256,
+    entry: BundleEntry,
+}
+
+impl ModuleReader {
+    /// Metadata of the module being read
+    #[allow(dead_code)]
+    pub fn entry(&self) -> &BundleEntry {
+        &self.entry
+    }
+}
+
+impl Read for ModuleReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.data.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        if read == 0 && !buf.is_empty() {
+            let invalid =
+                |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+            if self.data.limit() > 0 {
+                return Err(invalid(format!(
+                    "module '{}' is truncated",
+                    self.entry.module_name
+                )));
+            }
+            let actual = format!("{:x}", self.hasher.clone().finalize());
+            if let Some(ref expected) = self.entry.sha256
+                && *expected != actual
+            {
+                return Err(invalid(format!(
+                    "module '{}': expected sha256 {}, got {}",
+                    self.entry.module_name, expected, actual
+                )));
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Read the format version and module count of a merged package
+fn read_header(reader: &mut impl Read) -> Result<(u32, usize)> {
+    // Read header line (text)
+    let header = read_line(reader, "header line")?;
+    let version: u32 = header
+        .strip_prefix(FORMAT_MAGIC)
+        .and_then(|v| v.parse().ok())
+        .context("Invalid merged package format")?;
+    if version == 0 || version > FORMAT_VERSION {
+        anyhow::bail!(
+            "Unsupported bundle format version {} (supported: 1-{})",
+            version,
+            FORMAT_VERSION
+        );
+    }
+
+    // Read count line (text)
+    let count = read_line(reader, "count line")?
+        .parse()
+        .context("Invalid package count")?;
+    Ok((version, count))
+}
+
+/// Read the metadata line preceding a module's data; the offset is left for the caller
+fn read_module_line(reader: &mut impl Read, version: u32) -> Result<BundleEntry> {
+    let metadata = read_line(reader, "module metadata")?;
+    let parts: Vec<&str> = metadata.split('|').collect();
+    if parts.len() != if version >= 2 { 3 } else { 2 } {
+        anyhow::bail!("Invalid module metadata format");
+    }
+    Ok(BundleEntry {
+        module_name: parts[0].to_string(),
+        offset: 0,
+        size: parts[1].parse().context("Invalid module size")?,
+        sha256: parts.get(2).map(|hash| hash.to_string()),
+    })
+}
+
+/// Read the checksum line that closes a merged package
+fn read_checksum_line(reader: &mut impl Read) -> Result<String> {
+    let line = read_line(reader, "bundle checksum")?;
+    let checksum = line
+        .strip_prefix(CHECKSUM_PREFIX)
+        .context("Invalid bundle checksum line")?
+        .to_string();
+    if reader.read(&mut [0])? != 0 {
+        anyhow::bail!("Unexpected data after the bundle checksum");
+    }
+    Ok(checksum)
+}
+
+/// Longest header line accepted, so garbage input cannot grow a line without bound
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Read a text line of a bundle header
+///
+/// Reads byte by byte so no module data is consumed; callers pass buffered readers.
+fn read_line(reader: &mut impl Read, what: &str) -> Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            anyhow::bail!("Missing {}", what);
+        }
+        if byte[0] == b'\n' {
+            break;
+        }
+        line.push(byte[0]);
+        if line.len() > MAX_LINE_LENGTH {
+            anyhow::bail!("Invalid {}", what);
+        }
+    }
+    String::from_utf8(line).with_context(|| format!("Invalid {}", what))
+}
+
+/// Reader that hashes and counts everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    position: u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// Writer that hashes everything written through it
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_bundle_round_trip() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut red = BuildConfig::default_config();
+        red.app = Some("brand".to_string());
+        red.flavor = Some("red".to_string());
+        red.package_name = "com.brand.red".to_string();
+        red.version_code = Some(3);
+        let mut blue = red.clone();
+        blue.flavor = Some("blue".to_string());
+        blue.package_name = "com.brand.blue".to_string();
+
+        let red_apk = temp.path().join("red.skin");
+        let blue_apk = temp.path().join("blue.skin");
+        std::fs::write(&red_apk, b"red skin")?;
+        std::fs::write(&blue_apk, b"blue")?;
+
+        let output = temp.path().join("dist").join("skins.bundle");
+        let manifest = write_bundle(&[(&red, red_apk.clone()), (&blue, blue_apk)], &output)?;
+        assert_eq!(
+            manifest.modules[0],
+            BundleModule {
+                name: "brand-red".to_string(),
+                package_name: "com.brand.red".to_string(),
+                version_code: Some(3),
+                version_name: Some("1.0.0".to_string()),
+                size: 8,
+            }
+        );
+        let written: BundleManifest = serde_json::from_str(&std::fs::read_to_string(
+            output.with_extension(BUNDLE_MANIFEST_EXTENSION),
+        )?)?;
+        assert_eq!(written, manifest);
+
+        let extracted = SkinMerger::extract_modules(&output, &temp.path().join("out"))?;
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[1].module_name, "brand-blue");
+        assert_eq!(std::fs::read(&extracted[1].apk_path)?, b"blue");
+
+        // Module names have to be unique
+        assert!(write_bundle(&[(&red, red_apk.clone()), (&red, red_apk)], &output).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_detects_corrupted_modules() -> Result<()> {
+        let temp = TempDir::new()?;
+        let apk = temp.path().join("red.skin");
+        std::fs::write(&apk, b"red skin")?;
+        let bundle = temp.path().join("skins.bundle");
+        SkinMerger::merge_packages(
+            &[ModuleSkinPackage {
+                module_name: "red".to_string(),
+                apk_path: apk,
+            }],
+            &bundle,
+        )?;
+
+        let index = SkinMerger::verify(&bundle)?;
+        assert_eq!(index.version, FORMAT_VERSION);
+        assert_eq!(index.modules[0].size, 8);
+
+        // Flip one byte of the module data
+        let mut content = std::fs::read(&bundle)?;
+        content[index.modules[0].offset as usize] ^= 0xff;
+        std::fs::write(&bundle, &content)?;
+        let err = format!("{:#}", SkinMerger::verify(&bundle).unwrap_err());
+        assert!(err.contains("module 'red': expected sha256"));
+        assert!(err.contains("bundle: expected checksum"));
+        assert!(SkinMerger::extract_modules(&bundle, &temp.path().join("out")).is_err());
+
+        // Bundles from before checksums still extract, but cannot be verified
+        let v1 = temp.path().join("v1.bundle");
+        std::fs::write(&v1, b"ASB_MERGED_V1\n1\nred|3\nred")?;
+        let extracted = SkinMerger::extract_modules(&v1, &temp.path().join("v1"))?;
+        assert_eq!(std::fs::read(&extracted[0].apk_path)?, b"red");
+        assert!(SkinMerger::verify(&v1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_single_module() -> Result<()> {
+        let temp = TempDir::new()?;
+        let packages: Vec<ModuleSkinPackage> = ["red", "blue"]
+            .iter()
+            .map(|name| {
+                let apk_path = temp.path().join(format!("{}.apk", name));
+                // Larger than the copy buffers
+                std::fs::write(&apk_path, name.repeat(20_000)).unwrap();
+                ModuleSkinPackage {
+                    module_name: name.to_string(),
+                    apk_path,
+                }
+            })
+            .collect();
+        let bundle = temp.path().join("skins.bundle");
+        SkinMerger::merge_packages(&packages, &bundle)?;
+
+        // Corrupt the first module; the second one is extracted without reading it
+        let index = SkinMerger::verify(&bundle)?;
+        let mut content = std::fs::read(&bundle)?;
+        content[index.modules[0].offset as usize] ^= 0xff;
+        std::fs::write(&bundle, &content)?;
+
+        let out = temp.path().join("out");
+        let blue = SkinMerger::extract_module(&bundle, "blue", &out)?;
+        assert_eq!(
+            std::fs::read(&blue.apk_path)?,
+            "blue".repeat(20_000).as_bytes()
+        );
+        assert!(SkinMerger::extract_module(&bundle, "red", &out).is_err());
+        assert!(!out.join("red.skin").exists());
+        let err = SkinMerger::extract_module(&bundle, "green", &out).unwrap_err();
+        assert!(err.to_string().contains("modules: red, blue"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_and_open_modules() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut packages = Vec::new();
+        for name in ["red", "blue"] {
+            let apk_path = temp.path().join(format!("{}.apk", name));
+            std::fs::write(&apk_path, name)?;
             packages.push(ModuleSkinPackage {
-                module_name: module_name.to_string(),
+                module_name: name.to_string(),
                 apk_path,
             });
-
-            offset += size;
         }
+        let bundle = temp.path().join("skins.bundle");
+        SkinMerger::merge_packages(&packages, &bundle)?;
 
-        info!("Extracted {} modules from merged package", packages.len());
-        Ok(packages)
+        let index = SkinMerger::list_modules(&bundle)?;
+        assert_eq!(index, SkinMerger::verify(&bundle)?);
+        let blue = &index.modules[1];
+        assert_eq!((blue.module_name.as_str(), blue.size), ("blue", 4));
+        assert_eq!(
+            blue.sha256.as_deref(),
+            Some(format!("{:x}", Sha256::digest(b"blue")).as_str())
+        );
+        let content = std::fs::read(&bundle)?;
+        assert_eq!(&content[blue.offset as usize..][..4], b"blue");
+
+        let mut module = SkinMerger::open_module(&bundle, "blue")?;
+        assert_eq!(module.entry(), blue);
+        let mut data = String::new();
+        module.read_to_string(&mut data)?;
+        assert_eq!(data, "blue");
+
+        // A corrupted module fails once it has been read to the end
+        let mut corrupted = content.clone();
+        corrupted[blue.offset as usize] = b'g';
+        std::fs::write(&bundle, &corrupted)?;
+        let mut module = SkinMerger::open_module(&bundle, "blue")?;
+        let err = module.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        Ok(())
     }
 }
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::hooks::HookStage;
+use crate::types::BuildResult;
+
+/// Step of the package build pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildPhase {
+    /// Running the hooks of a stage
+    Hooks(HookStage),
+    /// Compiling resource files with aapt2
+    Compile,
+    /// Waiting for a slot among the links running in parallel
+    WaitForLink,
+    /// Linking the compiled resources into a package
+    Link,
+    /// Normalizing and finishing the linked package
+    Package,
+}
+
+impl fmt::Display for BuildPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Hooks(stage) => write!(f, "{} hooks", stage.name()),
+            Self::Compile => f.write_str("compiling resources"),
+            Self::WaitForLink => f.write_str("waiting for a link slot"),
+            Self::Link => f.write_str("linking"),
+            Self::Package => f.write_str("packaging"),
+        }
+    }
+}
+
+/// Progress of package builds, for embedders such as IDE plugins that present it
+/// themselves instead of reading the logs
+///
+/// Set with [`SkinBuilder::with_observer`](crate::builder::SkinBuilder::with_observer).
+/// Files are compiled in parallel, so methods are called from several threads; they should
+/// return quickly. Every method does nothing unless implemented.
+pub trait BuildObserver: Send + Sync {
+    /// The build of `package` entered `phase`
+    fn on_phase_start(&self, _package: &str, _phase: BuildPhase) {}
+
+    /// aapt2 compiled a resource file of `package`; files taken from the incremental
+    /// cache are not reported
+    fn on_file_compiled(&self, _package: &str, _file: &Path) {}
+
+    /// The build of `package` reported a warning; called when the build finishes, before
+    /// [`on_package_done`](Self::on_package_done)
+    fn on_warning(&self, _package: &str, _message: &str) {}
+
+    /// The build of `package` finished; `result` is what
+    /// [`SkinBuilder::build`](crate::builder::SkinBuilder::build) returns
+    fn on_package_done(&self, _package: &str, _result: &anyhow::Result<BuildResult>) {}
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::resource_rewrite::{collect_definitions, is_xml};
+
+/// Ownership report file extension, written next to the package
+pub const OWNERS_REPORT_EXTENSION: &str = "owners.json";
+
+/// Team name used for files no rule matches
+pub const UNOWNED: &str = "(unowned)";
+
+/// Owners mapping: resource path globs to the teams owning them
+///
+/// One rule per line, `<glob> <team> [<team>...]`; blank lines and `#` comments are skipped.
+/// As in CODEOWNERS, the last matching rule wins. `*` matches within a path segment, `**`
+/// across segments and `?` a single character; globs without a `/` match the file name.
+/// Paths are matched relative to the owners file's directory.
+#[derive(Debug, Default)]
+pub struct Owners {
+    rules: Vec<(String, Vec<String>)>,
+    root: Option<PathBuf>,
+}
+
+impl Owners {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read owners file {}", path.display()))?;
+        let mut owners = Self::parse(&content)
+            .with_context(|| format!("Invalid owners file {}", path.display()))?;
+        owners.root = Some(
+            std::path::absolute(path)?
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+        );
+        Ok(owners)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            let pattern = tokens.next().unwrap_or_default();
+            let teams: Vec<String> = tokens.map(str::to_string).collect();
+            if teams.is_empty() {
+                anyhow::bail!("Line {} has no team: {}", idx + 1, line);
+            }
+            rules.push((pattern.trim_start_matches("./").to_string(), teams));
+        }
+        Ok(Self { rules, root: None })
+    }
+
+    /// Teams owning `path`, empty when no rule matches
+    pub fn owners_of(&self, path: &Path) -> &[String] {
+        let relative = self.root.as_ref().and_then(|root| {
+            let path = std::path::absolute(path).ok()?;
+            path.strip_prefix(root).ok().map(Path::to_path_buf)
+        });
+        let path = relative.as_deref().unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        let path = path.trim_start_matches("./");
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| {
+                if pattern.contains('/') {
+                    glob_match(pattern.trim_start_matches('/'), path)
+                } else {
+                    glob_match(pattern, file_name)
+                }
+            })
+            .map(|(_, teams)| teams.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Match a `/`-separated path against a glob
+pub(crate) fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path_rest)| {
+            match_segment(segment.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+        }),
+    }
+}
+
+/// Match a single name against a pattern with `*` and `?` wildcards
+pub(crate) fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match_segment(pattern.as_bytes(), name.as_bytes())
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+/// Resource directory handed to the ownership report
+pub struct OwnedDir {
+    pub res_dir: PathBuf,
+    pub files: Vec<PathBuf>,
+    /// Path matched against the owners rules instead of each file, for resources that come
+    /// from elsewhere (AARs, generated resources)
+    pub source: Option<PathBuf>,
+}
+
+/// A resource file and its owners
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnedFile {
+    pub file: PathBuf,
+    pub owners: Vec<String>,
+}
+
+/// A resource defined in several directories; the highest-priority definition wins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnershipOverride {
+    /// `<resource dir>/<type>/<name>`, e.g. `values-night/color/primary`
+    pub resource: String,
+    pub winner: OwnedFile,
+    pub overridden: Vec<OwnedFile>,
+}
+
+/// Which teams' resources a skin contains and whose resources were overridden
+#[derive(Debug, Default, Serialize)]
+pub struct OwnershipReport {
+    /// Resource files per team
+    pub teams: BTreeMap<String, usize>,
+    pub overrides: Vec<OwnershipOverride>,
+}
+
+/// Build the ownership report of a skin from its resource directories, lowest priority first
+pub fn ownership_report(dirs: &[OwnedDir], owners: &Owners) -> Result<OwnershipReport> {
+    let mut report = OwnershipReport::default();
+    let mut definitions: BTreeMap<String, Vec<OwnedFile>> = BTreeMap::new();
+
+    for dir in dirs {
+        for file in &dir.files {
+            let teams = owners.owners_of(dir.source.as_deref().unwrap_or(file));
+            let teams = if teams.is_empty() {
+                vec![UNOWNED.to_string()]
+            } else {
+                teams.to_vec()
+            };
+            for team in &teams {
+                *report.teams.entry(team.clone()).or_default() += 1;
+            }
+
+            let content = if is_xml(file) {
+                Some(
+                    fs::read_to_string(file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?,
+                )
+            } else {
+                None
+            };
+            let mut names = HashSet::new();
+            collect_definitions(&dir.res_dir, file, content.as_deref(), &mut names)?;
+            let qualifier_dir = file
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            // IDs are declared in many layouts without overriding each other
+            for (resource_type, name) in names.into_iter().filter(|(t, _)| t != "id") {
+                definitions
+                    .entry(format!("{}/{}/{}", qualifier_dir, resource_type, name))
+                    .or_default()
+                    .push(OwnedFile {
+                        file: dir.source.clone().unwrap_or_else(|| file.clone()),
+                        owners: teams.clone(),
+                    });
+            }
+        }
+    }
+
+    for (resource, mut files) in definitions {
+        if files.len() < 2 {
+            continue;
+        }
+        let winner = files.pop().expect("at least two definitions");
+        report.overrides.push(OwnershipOverride {
+            resource,
+            winner,
+            overridden: files,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_owners_rules() -> Result<()> {
+        let owners = Owners::parse(
+            "# skin owners\n\
+             **/res/** @design\n\
+             common/res/values*/** @platform @design\n\
+             *.9.png @icons\n\
+             vendor.aar @vendor\n",
+        )?;
+        assert_eq!(
+            owners.owners_of(Path::new("app/res/layout/main.xml")),
+            ["@design"]
+        );
+        assert_eq!(
+            owners.owners_of(Path::new("./common/res/values-night/colors.xml")),
+            ["@platform", "@design"]
+        );
+        assert_eq!(
+            owners.owners_of(Path::new("common/res/drawable/bg.9.png")),
+            ["@icons"]
+        );
+        assert_eq!(owners.owners_of(Path::new("libs/vendor.aar")), ["@vendor"]);
+        assert!(owners.owners_of(Path::new("other/colors.xml")).is_empty());
+        assert!(Owners::parse("res/** \n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ownership_report() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let write = |path: &Path, content: &str| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        let common = temp_dir.path().join("common/res");
+        let skin = temp_dir.path().join("skin/res");
+        let common_colors = common.join("values/colors.xml");
+        let skin_colors = skin.join("values/colors.xml");
+        let icon = skin.join("drawable/icon.png");
+        write(
+            &common_colors,
+            r#"<resources><color name="primary">#000</color><color name="accent">#111</color></resources>"#,
+        );
+        write(
+            &skin_colors,
+            r#"<resources><color name="primary">#fff</color></resources>"#,
+        );
+        write(&icon, "png");
+
+        let owners = Owners::parse("common/** @platform\nskin/** @skins\n")?;
+        let relative = |p: &Path| p.strip_prefix(temp_dir.path()).unwrap().to_path_buf();
+        let dirs = [
+            OwnedDir {
+                res_dir: common.clone(),
+                files: vec![common_colors.clone()],
+                source: Some(relative(&common_colors)),
+            },
+            OwnedDir {
+                res_dir: skin.clone(),
+                files: vec![skin_colors.clone(), icon.clone()],
+                source: None,
+            },
+        ];
+        let report = ownership_report(&dirs, &owners)?;
+        assert_eq!(
+            report.teams,
+            BTreeMap::from([("@platform".to_string(), 1), (UNOWNED.to_string(), 2)])
+        );
+        assert_eq!(report.overrides.len(), 1);
+        let primary = &report.overrides[0];
+        assert_eq!(primary.resource, "values/color/primary");
+        assert_eq!(primary.winner.file, skin_colors);
+        assert_eq!(primary.overridden[0].owners, ["@platform"]);
+        Ok(())
+    }
+}
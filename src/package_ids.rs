@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::types::{BuildConfig, parse_package_id};
+
+/// Lock file recording automatically assigned package IDs, kept next to the config file
+pub const PACKAGE_ID_LOCK_FILE: &str = "asb.package-ids.lock";
+
+/// Persisted package name -> package ID assignments
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PackageIdLock {
+    #[serde(rename = "packageIds", default)]
+    pub package_ids: BTreeMap<String, String>,
+}
+
+impl PackageIdLock {
+    /// Load the lock file, or an empty lock if it does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read package ID lock {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse package ID lock {}", path.display()))
+    }
+
+    /// Save the lock file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content + "\n")
+            .with_context(|| format!("Failed to write package ID lock {}", path.display()))
+    }
+}
+
+/// Candidate IDs in allocation order: 0x7f..=0xff first, then 0x02..=0x7e
+/// (0x00 is reserved for shared libraries and 0x01 for the Android framework)
+fn candidate_ids() -> impl Iterator<Item = u8> {
+    (0x7f..=0xff).chain(0x02..0x7f)
+}
+
+fn format_package_id(id: u8) -> String {
+    format!("0x{:02x}", id)
+}
+
+/// Give every package in the build a unique package ID
+///
+/// Explicit `packageId` values are kept and must not conflict with each other; configs
+/// sharing a package name share its ID, so they may not set different ones.
+/// Other packages reuse their locked ID when it is still free, and new packages get
+/// the first free candidate. Entries for packages missing from this build stay in the
+/// lock and keep their IDs reserved, so filtered builds never reshuffle assignments.
+pub fn assign_package_ids(configs: &mut [BuildConfig], lock_path: &Path) -> Result<()> {
+    let mut lock = PackageIdLock::load(lock_path)?;
+
+    // Explicit IDs win; two packages claiming the same one, or one package (e.g. its
+    // flavors) claiming two, is an error
+    let mut explicit: HashMap<String, u8> = HashMap::new();
+    let mut owners: HashMap<u8, String> = HashMap::new();
+    for config in configs.iter() {
+        let Some(package_id) = &config.package_id else {
+            continue;
+        };
+        let id = parse_package_id(package_id)?;
+        if let Some(&other) = explicit.get(&config.package_name)
+            && other != id
+        {
+            anyhow::bail!(
+                "Package IDs {} and {} are both set explicitly for '{}'",
+                format_package_id(other),
+                format_package_id(id),
+                config.package_name
+            );
+        }
+        match owners.get(&id) {
+            Some(owner) if *owner != config.package_name => anyhow::bail!(
+                "Package ID {} is set explicitly for both '{}' and '{}'",
+                format_package_id(id),
+                owner,
+                config.package_name
+            ),
+            _ => {
+                owners.insert(id, config.package_name.clone());
+                explicit.insert(config.package_name.clone(), id);
+            }
+        }
+    }
+
+    // Unique package names in config order (flavors may share a package name)
+    let mut package_names: Vec<String> = Vec::new();
+    for config in configs.iter() {
+        if !package_names.contains(&config.package_name) {
+            package_names.push(config.package_name.clone());
+        }
+    }
+
+    // Keep locked IDs that are still valid and unclaimed, including those of
+    // packages that are not part of this build
+    let mut assigned: BTreeMap<String, u8> = BTreeMap::new();
+    for (package_name, package_id) in &lock.package_ids {
+        if explicit.contains_key(package_name) {
+            continue;
+        }
+        let id = match parse_package_id(package_id) {
+            Ok(id) => id,
+            Err(e) => {
+                warn!("Ignoring locked package ID for {}: {}", package_name, e);
+                continue;
+            }
+        };
+        if let Some(owner) = owners.get(&id) {
+            warn!(
+                "Locked package ID {} of {} is now used by {}, reassigning",
+                format_package_id(id),
+                package_name,
+                owner
+            );
+            continue;
+        }
+        owners.insert(id, package_name.clone());
+        assigned.insert(package_name.clone(), id);
+    }
+
+    for package_name in &package_names {
+        if let Some(id) = explicit.get(package_name) {
+            assigned.insert(package_name.clone(), *id);
+            continue;
+        }
+        if assigned.contains_key(package_name) {
+            continue;
+        }
+        let id = candidate_ids()
+            .find(|id| !owners.contains_key(id))
+            .ok_or_else(|| anyhow::anyhow!("No free package IDs left for {}", package_name))?;
+        info!(
+            "Assigned package ID {} to {}",
+            format_package_id(id),
+            package_name
+        );
+        owners.insert(id, package_name.clone());
+        assigned.insert(package_name.clone(), id);
+    }
+
+    for config in configs.iter_mut() {
+        if let Some(id) = assigned.get(&config.package_name) {
+            config.package_id = Some(format_package_id(*id));
+        }
+    }
+
+    let package_ids: BTreeMap<String, String> = assigned
+        .into_iter()
+        .map(|(name, id)| (name, format_package_id(id)))
+        .collect();
+    if package_ids != lock.package_ids {
+        lock.package_ids = package_ids;
+        lock.save(lock_path)?;
+        info!("Updated package ID lock at {}", lock_path.display());
+    }
+
+    Ok(())
+}
+
+/// Whether any config asks for automatic package ID assignment
+pub fn auto_assign_enabled(configs: &[BuildConfig]) -> bool {
+    configs
+        .iter()
+        .any(|c| c.auto_assign_package_ids.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config(package_name: &str, package_id: Option<&str>) -> BuildConfig {
+        BuildConfig {
+            package_name: package_name.to_string(),
+            package_id: package_id.map(str::to_string),
+            auto_assign_package_ids: Some(true),
+            ..BuildConfig::default_config()
+        }
+    }
+
+    fn ids(configs: &[BuildConfig]) -> Vec<&str> {
+        configs
+            .iter()
+            .map(|c| c.package_id.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_assign_package_ids_allocates_and_locks() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(PACKAGE_ID_LOCK_FILE);
+
+        let mut configs = vec![
+            config("com.skin.a", None),
+            config("com.skin.b", Some("0x80")),
+            config("com.skin.c", None),
+        ];
+        assign_package_ids(&mut configs, &lock_path).unwrap();
+        assert_eq!(ids(&configs), vec!["0x7f", "0x80", "0x81"]);
+
+        let lock = PackageIdLock::load(&lock_path).unwrap();
+        assert_eq!(lock.package_ids["com.skin.c"], "0x81");
+
+        // A new package and a reordered config keep the locked assignments
+        let mut configs = vec![
+            config("com.skin.new", None),
+            config("com.skin.c", None),
+            config("com.skin.a", None),
+        ];
+        assign_package_ids(&mut configs, &lock_path).unwrap();
+        assert_eq!(ids(&configs), vec!["0x82", "0x81", "0x7f"]);
+
+        // Packages left out of a filtered build keep their reserved IDs
+        let lock = PackageIdLock::load(&lock_path).unwrap();
+        assert_eq!(lock.package_ids["com.skin.b"], "0x80");
+    }
+
+    #[test]
+    fn test_assign_package_ids_explicit_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(PACKAGE_ID_LOCK_FILE);
+
+        let mut configs = vec![
+            config("com.skin.a", Some("0x80")),
+            config("com.skin.b", Some("128")),
+        ];
+        assert!(assign_package_ids(&mut configs, &lock_path).is_err());
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_assign_package_ids_flavors_with_different_explicit_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(PACKAGE_ID_LOCK_FILE);
+
+        // Two flavors of one package
+        let mut configs = vec![
+            config("com.skin.a", Some("0x80")),
+            config("com.skin.a", Some("0x81")),
+        ];
+        let err = assign_package_ids(&mut configs, &lock_path).unwrap_err();
+        assert!(err.to_string().contains("com.skin.a"));
+        assert!(!lock_path.exists());
+
+        // Flavors agreeing on the explicit ID, or leaving it out, share it
+        let mut configs = vec![
+            config("com.skin.a", Some("0x80")),
+            config("com.skin.a", None),
+            config("com.skin.a", Some("128")),
+        ];
+        assign_package_ids(&mut configs, &lock_path).unwrap();
+        assert_eq!(ids(&configs), vec!["0x80", "0x80", "0x80"]);
+    }
+
+    #[test]
+    fn test_assign_package_ids_explicit_id_takes_locked_slot() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(PACKAGE_ID_LOCK_FILE);
+
+        let mut configs = vec![config("com.skin.a", None)];
+        assign_package_ids(&mut configs, &lock_path).unwrap();
+        assert_eq!(ids(&configs), vec!["0x7f"]);
+
+        // com.skin.b now claims 0x7f explicitly, so com.skin.a moves
+        let mut configs = vec![
+            config("com.skin.a", None),
+            config("com.skin.b", Some("0x7f")),
+        ];
+        assign_package_ids(&mut configs, &lock_path).unwrap();
+        assert_eq!(ids(&configs), vec!["0x80", "0x7f"]);
+    }
+}
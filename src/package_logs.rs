@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber, warn};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::types::{BuildConfig, BuildResult};
+
+/// Directory below the output directory holding one log directory per package
+pub const LOGS_DIR: &str = ".logs";
+
+/// Open package logs by package name
+static LOG_FILES: Mutex<BTreeMap<String, File>> = Mutex::new(BTreeMap::new());
+
+/// Log file of a package: `<outputDir>/.logs/<package>/build.log`
+pub fn package_log_path(config: &BuildConfig) -> PathBuf {
+    config
+        .output_dir
+        .join(LOGS_DIR)
+        .join(&config.package_name)
+        .join("build.log")
+}
+
+/// Span to build a package in during multi-config builds
+///
+/// Starts a new log file for the package; every event inside the span, including those of
+/// aapt2 threads started from it, is also written to that file by [`PackageLogLayer`].
+pub fn package_span(config: &BuildConfig) -> tracing::Span {
+    let path = package_log_path(config);
+    let created = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| File::create(&path));
+    match created {
+        Ok(file) => {
+            if let Ok(mut files) = LOG_FILES.lock() {
+                files.insert(config.package_name.clone(), file);
+            }
+        }
+        Err(e) => warn!("Failed to create package log {}: {}", path.display(), e),
+    }
+    tracing::info_span!("package", package = %config.package_name)
+}
+
+/// Write the outcome of a package build to its log and close the log
+///
+/// Errors of failed builds are only printed in the build summary, so they are added here
+/// for the log to be complete on its own.
+pub fn finish_package_log(package: &str, outcome: Result<&BuildResult, &anyhow::Error>) {
+    let Ok(mut files) = LOG_FILES.lock() else {
+        return;
+    };
+    let Some(mut file) = files.remove(package) else {
+        return;
+    };
+    let mut text = String::new();
+    match outcome {
+        Ok(result) if result.success => {
+            let _ = writeln!(text, "{} Build succeeded", timestamp());
+        }
+        Ok(result) => {
+            let _ = writeln!(text, "{} Build failed", timestamp());
+            for error in &result.errors {
+                let _ = writeln!(text, "  {}", error);
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(text, "{} Build failed: {:#}", timestamp(), e);
+        }
+    }
+    let _ = file.write_all(text.as_bytes());
+}
+
+/// Layer writing the events of each package span to the log of the package
+pub struct PackageLogLayer;
+
+/// Package name recorded on a package span
+struct PackageName(String);
+
+impl<S> Layer<S> for PackageLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "package" {
+            return;
+        }
+        let mut visitor = PackageVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(package), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(PackageName(package));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let Some(package) = scope
+            .into_iter()
+            .find_map(|span| span.extensions().get::<PackageName>().map(|p| p.0.clone()))
+        else {
+            return;
+        };
+
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {:>5} {}: {}{}\n",
+            timestamp(),
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        );
+        if let Ok(mut files) = LOG_FILES.lock()
+            && let Some(file) = files.get_mut(&package)
+        {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+fn timestamp() -> String {
+    chrono::Local::now()
+        .format("%Y-%m-%dT%H:%M:%S%.3f")
+        .to_string()
+}
+
+/// Reads the `package` field of a package span
+struct PackageVisitor(Option<String>);
+
+impl Visit for PackageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "package" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "package" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// Collects the message and the other fields of an event
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn config(temp: &TempDir, package_name: &str) -> BuildConfig {
+        serde_json::from_value(serde_json::json!({
+            "resourceDir": temp.path().join("res"),
+            "manifestPath": temp.path().join("AndroidManifest.xml"),
+            "outputDir": temp.path().join("out"),
+            "packageName": package_name,
+            "androidJar": temp.path().join("android.jar"),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_package_logs_only_contain_their_package() -> anyhow::Result<()> {
+        let temp = TempDir::new()?;
+        let red = config(&temp, "com.test.logs.red");
+        let blue = config(&temp, "com.test.logs.blue");
+        let subscriber = tracing_subscriber::registry().with(PackageLogLayer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let red_span = package_span(&red);
+            let blue_span = package_span(&blue);
+            info!("Building 2 configs");
+            red_span.in_scope(|| info!(files = 3, "Compiling red"));
+            blue_span.in_scope(|| info!("Compiling blue"));
+            let _entered = red_span.enter();
+            info!("Linking red");
+        });
+        let failure = anyhow::anyhow!("aapt2 link failed");
+        finish_package_log("com.test.logs.red", Err(&failure));
+
+        let red_log = fs::read_to_string(package_log_path(&red))?;
+        let lines: Vec<&str> = red_log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("INFO asb::package_logs::tests: Compiling red files=3"));
+        assert!(lines[1].ends_with("Linking red"));
+        assert!(lines[2].ends_with("Build failed: aapt2 link failed"));
+        let blue_log = fs::read_to_string(package_log_path(&blue))?;
+        assert_eq!(blue_log.lines().count(), 1);
+        assert!(blue_log.contains("Compiling blue"));
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use zip::write::{FileOptions, ZipWriter};
+use zip::{CompressionMethod, DateTime, ZipArchive};
+
+use crate::types::{BuildConfig, CompressionPolicy, PackagingConfig};
+use crate::vcs::VcsInfo;
+
+/// Default byte alignment for uncompressed entries (matches `zipalign -p 4`)
+pub const DEFAULT_ALIGNMENT: u16 = 4;
+
+/// Path of the embedded skin metadata inside the package
+pub const SKIN_METADATA_ENTRY: &str = "assets/asb-skin.json";
+
+/// Entries that must stay uncompressed so Android can mmap them
+const ALWAYS_STORED: &[&str] = &["resources.arsc"];
+
+/// Skin metadata embedded into the package when enabled
+#[derive(Debug, Clone, Serialize)]
+pub struct SkinMetadata {
+    #[serde(rename = "packageName")]
+    pub package_name: String,
+    #[serde(rename = "versionCode", skip_serializing_if = "Option::is_none")]
+    pub version_code: Option<u32>,
+    #[serde(rename = "versionName", skip_serializing_if = "Option::is_none")]
+    pub version_name: Option<String>,
+    #[serde(rename = "builtWith")]
+    pub built_with: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsInfo>,
+}
+
+impl SkinMetadata {
+    /// Build metadata from a build configuration
+    pub fn from_config(config: &BuildConfig) -> Self {
+        Self {
+            package_name: config.package_name.clone(),
+            version_code: config.version_code,
+            version_name: config.version_name.clone(),
+            built_with: format!("asb {}", env!("CARGO_PKG_VERSION")),
+            vcs: None,
+        }
+    }
+}
+
+/// Resolved packaging options
+#[derive(Debug, Clone)]
+pub struct PackageOptions {
+    /// Byte alignment for uncompressed entries (0 or 1 disables alignment)
+    pub alignment: u16,
+    /// Compression policy for rewritten entries
+    pub compression: CompressionPolicy,
+    /// Use fixed timestamps and permissions
+    pub reproducible: bool,
+    /// Extra files to add, keyed by their path inside the package
+    pub extra_files: BTreeMap<String, PathBuf>,
+    /// Skin metadata to embed, if any
+    pub metadata: Option<SkinMetadata>,
+}
+
+impl Default for PackageOptions {
+    fn default() -> Self {
+        Self {
+            alignment: DEFAULT_ALIGNMENT,
+            compression: CompressionPolicy::Preserve,
+            reproducible: false,
+            extra_files: BTreeMap::new(),
+            metadata: None,
+        }
+    }
+}
+
+impl PackageOptions {
+    /// Resolve packaging options from a build configuration
+    pub fn from_config(config: &BuildConfig) -> Self {
+        let packaging = config.packaging.clone().unwrap_or_default();
+        let PackagingConfig {
+            alignment,
+            compression,
+            reproducible,
+            extra_files,
+            skin_metadata,
+        } = packaging;
+
+        Self {
+            alignment: alignment.unwrap_or(DEFAULT_ALIGNMENT),
+            compression: compression.unwrap_or_default(),
+            reproducible: reproducible.unwrap_or(false),
+            extra_files: extra_files.unwrap_or_default(),
+            metadata: skin_metadata
+                .unwrap_or(false)
+                .then(|| SkinMetadata::from_config(config)),
+        }
+    }
+}
+
+/// Summary of a packaging pass
+#[derive(Debug, Clone, Default)]
+pub struct PackageSummary {
+    /// Number of entries written
+    pub entries: usize,
+    /// Number of entries written uncompressed
+    pub stored_entries: usize,
+}
+
+/// Rewrites the package produced by aapt2 link in a single pass
+///
+/// Entries are written in a stable order (AndroidManifest.xml, resources.arsc, then by
+/// name), uncompressed entries are aligned like zipalign does, and extra files and skin
+/// metadata are appended. Entries are streamed from the source archive, never buffered whole.
+pub struct SkinPackager {
+    options: PackageOptions,
+}
+
+impl SkinPackager {
+    pub fn new(options: PackageOptions) -> Self {
+        Self { options }
+    }
+
+    /// Rewrite `apk_path` in place
+    pub fn repackage_in_place(&self, apk_path: &Path) -> Result<PackageSummary> {
+        let file_name = apk_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "package".to_string());
+        let temp_path = apk_path.with_file_name(format!(".{}.tmp", file_name));
+
+        let summary = match self.repackage(apk_path, &temp_path) {
+            Ok(summary) => summary,
+            Err(e) => {
+                std::fs::remove_file(&temp_path).ok();
+                return Err(e);
+            }
+        };
+
+        std::fs::rename(&temp_path, apk_path).with_context(|| {
+            format!(
+                "Failed to replace {} with repackaged output",
+                apk_path.display()
+            )
+        })?;
+
+        Ok(summary)
+    }
+
+    /// Rewrite the package at `input` into `output`
+    pub fn repackage(&self, input: &Path, output: &Path) -> Result<PackageSummary> {
+        let source = File::open(input)
+            .with_context(|| format!("Failed to open package: {}", input.display()))?;
+        let mut archive = ZipArchive::new(source)
+            .with_context(|| format!("Failed to read package as ZIP: {}", input.display()))?;
+
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let target = File::create(output)
+            .with_context(|| format!("Failed to create package: {}", output.display()))?;
+        let mut writer = ZipWriter::new(BufWriter::new(target));
+
+        // Extra files and metadata replace entries of the same name produced by aapt2
+        let mut replaced: HashSet<&str> = self
+            .options
+            .extra_files
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        if self.options.metadata.is_some() {
+            replaced.insert(SKIN_METADATA_ENTRY);
+        }
+
+        let mut names: Vec<String> = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| name.to_string())
+            .collect();
+        names.sort_by(|a, b| entry_rank(a).cmp(&entry_rank(b)).then_with(|| a.cmp(b)));
+
+        let mut summary = PackageSummary::default();
+
+        for name in &names {
+            if replaced.contains(name.as_str()) {
+                warn!("Replacing packaged entry with configured file: {}", name);
+                continue;
+            }
+
+            let mut entry = archive
+                .by_name(name)
+                .with_context(|| format!("Failed to read entry {} from package", name))?;
+            let method = self.compression_for(name, Some(entry.compression()));
+            let timestamp = if self.options.reproducible {
+                DateTime::default()
+            } else {
+                entry.last_modified().unwrap_or_default()
+            };
+
+            writer.start_file::<_, ()>(name.as_str(), self.entry_options(method, timestamp))?;
+            std::io::copy(&mut entry, &mut writer)
+                .with_context(|| format!("Failed to copy entry {} into package", name))?;
+            summary.record(method);
+        }
+
+        for (name, source_path) in &self.options.extra_files {
+            let method = self.compression_for(name, None);
+            writer.start_file::<_, ()>(
+                name.as_str(),
+                self.entry_options(method, self.new_entry_timestamp()),
+            )?;
+            let mut source = File::open(source_path)
+                .with_context(|| format!("Failed to open extra file: {}", source_path.display()))?;
+            std::io::copy(&mut source, &mut writer)?;
+            debug!("Added extra file {} as {}", source_path.display(), name);
+            summary.record(method);
+        }
+
+        if let Some(metadata) = &self.options.metadata {
+            let method = self.compression_for(SKIN_METADATA_ENTRY, None);
+            writer.start_file::<_, ()>(
+                SKIN_METADATA_ENTRY,
+                self.entry_options(method, self.new_entry_timestamp()),
+            )?;
+            serde_json::to_writer_pretty(&mut writer, metadata)?;
+            summary.record(method);
+        }
+
+        writer
+            .finish()
+            .with_context(|| format!("Failed to finalize package: {}", output.display()))?;
+
+        info!(
+            "Packaged {} entries ({} stored) into {}",
+            summary.entries,
+            summary.stored_entries,
+            output.display()
+        );
+
+        Ok(summary)
+    }
+
+    /// Pick the compression method for an entry
+    /// `original` is the method used by aapt2, or None for entries added by asb
+    fn compression_for(
+        &self,
+        name: &str,
+        original: Option<CompressionMethod>,
+    ) -> CompressionMethod {
+        if ALWAYS_STORED.contains(&name) {
+            return CompressionMethod::Stored;
+        }
+
+        match self.options.compression {
+            CompressionPolicy::Preserve => original.unwrap_or(CompressionMethod::Deflated),
+            CompressionPolicy::Store => CompressionMethod::Stored,
+            CompressionPolicy::Deflate => CompressionMethod::Deflated,
+        }
+    }
+
+    fn entry_options(&self, method: CompressionMethod, timestamp: DateTime) -> FileOptions<'_, ()> {
+        let mut options = FileOptions::default()
+            .compression_method(method)
+            .last_modified_time(timestamp);
+
+        if self.options.reproducible {
+            options = options.unix_permissions(0o644);
+        }
+
+        // Only uncompressed entries benefit from alignment
+        if method == CompressionMethod::Stored && self.options.alignment > 1 {
+            options = options.with_alignment(self.options.alignment);
+        }
+
+        options
+    }
+
+    fn new_entry_timestamp(&self) -> DateTime {
+        if self.options.reproducible {
+            DateTime::default()
+        } else {
+            DateTime::default_for_write()
+        }
+    }
+}
+
+impl PackageSummary {
+    fn record(&mut self, method: CompressionMethod) {
+        self.entries += 1;
+        if method == CompressionMethod::Stored {
+            self.stored_entries += 1;
+        }
+    }
+}
+
+/// Sort rank for package entries: manifest first, then the resource table, then everything else
+fn entry_rank(name: &str) -> u8 {
+    match name {
+        "AndroidManifest.xml" => 0,
+        "resources.arsc" => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use tempfile::TempDir;
+
+    /// Write a package the way aapt2 would: unordered, mixed compression
+    fn write_fake_apk(path: &Path) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        let entries: &[(&str, CompressionMethod, &[u8])] = &[
+            ("res/drawable/icon.png", CompressionMethod::Stored, b"png"),
+            ("resources.arsc", CompressionMethod::Deflated, b"arsc-data"),
+            (
+                "res/color/primary.xml",
+                CompressionMethod::Deflated,
+                b"<x/>",
+            ),
+            (
+                "AndroidManifest.xml",
+                CompressionMethod::Deflated,
+                b"<manifest/>",
+            ),
+        ];
+        for (name, method, data) in entries {
+            writer
+                .start_file::<_, ()>(*name, FileOptions::default().compression_method(*method))
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn entry_names(path: &Path) -> Vec<String> {
+        let archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        (0..archive.len())
+            .map(|i| archive.name_for_index(i).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_repackage_orders_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("in.skin");
+        let output = temp_dir.path().join("out.skin");
+        write_fake_apk(&input);
+
+        let summary = SkinPackager::new(PackageOptions::default())
+            .repackage(&input, &output)
+            .unwrap();
+
+        assert_eq!(summary.entries, 4);
+        assert_eq!(
+            entry_names(&output),
+            vec![
+                "AndroidManifest.xml",
+                "resources.arsc",
+                "res/color/primary.xml",
+                "res/drawable/icon.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repackage_stores_and_aligns_resource_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("in.skin");
+        let output = temp_dir.path().join("out.skin");
+        write_fake_apk(&input);
+
+        SkinPackager::new(PackageOptions::default())
+            .repackage(&input, &output)
+            .unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        for name in ["resources.arsc", "res/drawable/icon.png"] {
+            let entry = archive.by_name(name).unwrap();
+            assert_eq!(entry.compression(), CompressionMethod::Stored);
+            assert_eq!(entry.data_start() % DEFAULT_ALIGNMENT as u64, 0);
+        }
+        // Preserve policy keeps aapt2's choice for other entries
+        let entry = archive.by_name("res/color/primary.xml").unwrap();
+        assert_eq!(entry.compression(), CompressionMethod::Deflated);
+    }
+
+    #[test]
+    fn test_repackage_extra_files_and_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("in.skin");
+        let output = temp_dir.path().join("out.skin");
+        write_fake_apk(&input);
+        let license = temp_dir.path().join("LICENSE");
+        std::fs::write(&license, "license text").unwrap();
+
+        let options = PackageOptions {
+            extra_files: BTreeMap::from([("assets/LICENSE".to_string(), license)]),
+            metadata: Some(SkinMetadata {
+                package_name: "com.example.skin".to_string(),
+                version_code: Some(3),
+                version_name: None,
+                built_with: "asb test".to_string(),
+                vcs: Some(VcsInfo {
+                    commit: "1a2b3c4d5e6f".to_string(),
+                    branch: Some("main".to_string()),
+                    dirty: true,
+                }),
+            }),
+            ..PackageOptions::default()
+        };
+        SkinPackager::new(options)
+            .repackage(&input, &output)
+            .unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut content = String::new();
+        archive
+            .by_name("assets/LICENSE")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "license text");
+
+        content.clear();
+        archive
+            .by_name(SKIN_METADATA_ENTRY)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(metadata["packageName"], "com.example.skin");
+        assert_eq!(metadata["versionCode"], 3);
+        assert_eq!(metadata["vcs"]["commit"], "1a2b3c4d5e6f");
+        assert_eq!(metadata["vcs"]["dirty"], true);
+    }
+
+    #[test]
+    fn test_repackage_reproducible_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("in.skin");
+        write_fake_apk(&input);
+
+        let options = PackageOptions {
+            reproducible: true,
+            compression: CompressionPolicy::Deflate,
+            ..PackageOptions::default()
+        };
+        let packager = SkinPackager::new(options);
+        let first = temp_dir.path().join("first.skin");
+        let second = temp_dir.path().join("second.skin");
+        packager.repackage(&input, &first).unwrap();
+        packager.repackage(&input, &second).unwrap();
+
+        assert_eq!(
+            std::fs::read(&first).unwrap(),
+            std::fs::read(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_repackage_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let apk = temp_dir.path().join("app.skin");
+        write_fake_apk(&apk);
+
+        SkinPackager::new(PackageOptions::default())
+            .repackage_in_place(&apk)
+            .unwrap();
+
+        assert_eq!(entry_names(&apk)[0], "AndroidManifest.xml");
+        assert!(!temp_dir.path().join(".app.skin.tmp").exists());
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::types::{PluginConfig, PluginKind};
+
+/// Version of the plugin protocol, sent with every request
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Request written to a plugin's stdin as a single JSON line
+///
+/// Plugins write files they produce below `workDir`, which is kept between builds and
+/// belongs to the plugin and the resource directory (or package) being processed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PluginRequest {
+    /// Files of one resource directory, before compilation
+    #[serde(rename_all = "camelCase")]
+    Preprocess {
+        protocol: u32,
+        package: String,
+        res_dir: PathBuf,
+        work_dir: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    /// The finished package
+    #[serde(rename_all = "camelCase")]
+    Postprocess {
+        protocol: u32,
+        package: String,
+        work_dir: PathBuf,
+        files: Vec<PathBuf>,
+    },
+}
+
+/// Message a plugin writes to stdout, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PluginMessage {
+    /// Progress shown in the build log
+    Log { message: String },
+    /// Final answer: the files that replace the request's files
+    Result { files: Vec<PathBuf> },
+    /// The plugin failed; fails the build
+    Error { message: String },
+}
+
+/// Let each preprocessor replace the files of a resource directory, in config order
+///
+/// Returned files must keep the `<type>/<name>` layout resources are compiled from, e.g.
+/// `{workDir}/drawable/icon.png`.
+pub fn preprocess(
+    plugins: &[PluginConfig],
+    package: &str,
+    res_dir: &Path,
+    files: Vec<PathBuf>,
+    work_root: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut files = files;
+    for (idx, plugin) in plugins.iter().enumerate() {
+        if plugin.kind != PluginKind::Preprocessor {
+            continue;
+        }
+        let work_dir = work_root.join(format!("{}_{}", idx, plugin_dir_name(plugin)));
+        let request = PluginRequest::Preprocess {
+            protocol: PROTOCOL_VERSION,
+            package: package.to_string(),
+            res_dir: res_dir.to_path_buf(),
+            work_dir: work_dir.clone(),
+            files,
+        };
+        files = run_plugin(plugin, &request, &work_dir)?;
+        for file in &files {
+            if !file.is_file() || file.parent().and_then(|p| p.file_name()).is_none() {
+                anyhow::bail!(
+                    "Plugin {} returned {}, which is not a file in a resource type directory",
+                    plugin.display_name(),
+                    file.display()
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Let each postprocessor transform the finished package, in config order
+///
+/// A plugin returning a different file has it copied over the package.
+pub fn postprocess(
+    plugins: &[PluginConfig],
+    package: &str,
+    output_apk: &Path,
+    work_root: &Path,
+) -> Result<()> {
+    for (idx, plugin) in plugins.iter().enumerate() {
+        if plugin.kind != PluginKind::Postprocessor {
+            continue;
+        }
+        let work_dir = work_root.join(format!("{}_{}", idx, plugin_dir_name(plugin)));
+        let request = PluginRequest::Postprocess {
+            protocol: PROTOCOL_VERSION,
+            package: package.to_string(),
+            work_dir: work_dir.clone(),
+            files: vec![output_apk.to_path_buf()],
+        };
+        let files = run_plugin(plugin, &request, &work_dir)?;
+        let [file] = files.as_slice() else {
+            anyhow::bail!(
+                "Plugin {} must return exactly one package, got {}",
+                plugin.display_name(),
+                files.len()
+            );
+        };
+        if file != output_apk {
+            std::fs::copy(file, output_apk).with_context(|| {
+                format!(
+                    "Failed to copy {} from plugin {}",
+                    file.display(),
+                    plugin.display_name()
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// File-system friendly name of a plugin's work directory
+fn plugin_dir_name(plugin: &PluginConfig) -> String {
+    plugin
+        .display_name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Send a request to a plugin and read its messages until it exits
+fn run_plugin(
+    plugin: &PluginConfig,
+    request: &PluginRequest,
+    work_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let name = plugin.display_name();
+    std::fs::create_dir_all(work_dir)?;
+    debug!("Running plugin {}", name);
+
+    let mut child = Command::new(&plugin.command)
+        .args(&plugin.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start plugin {}", name))?;
+
+    // Read output before sending the request so neither side blocks on a full pipe
+    let stdout = child.stdout.take().context("Plugin stdout not captured")?;
+    let stderr = child.stderr.take().context("Plugin stderr not captured")?;
+    let reader_name = name.clone();
+    let messages = std::thread::spawn(move || -> Result<Vec<PluginMessage>> {
+        let mut messages = Vec::new();
+        let mut invalid = None;
+        // Keep draining after an invalid line so the plugin can finish writing
+        for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+            if line.trim().is_empty() || invalid.is_some() {
+                continue;
+            }
+            match serde_json::from_str::<PluginMessage>(&line) {
+                Ok(message) => {
+                    if let PluginMessage::Log { message } = &message {
+                        info!("{}: {}", reader_name, message);
+                    }
+                    messages.push(message);
+                }
+                Err(e) => invalid = Some(format!("invalid message `{}`: {}", line, e)),
+            }
+        }
+        match invalid {
+            Some(invalid) => anyhow::bail!(invalid),
+            None => Ok(messages),
+        }
+    });
+    let stderr_name = name.clone();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(|line| line.ok()) {
+            debug!("{} stderr: {}", stderr_name, line);
+        }
+    });
+
+    // Closing stdin after the request tells the plugin there is nothing more
+    let mut stdin = child.stdin.take().context("Plugin stdin not captured")?;
+    if let Err(e) = writeln!(stdin, "{}", serde_json::to_string(request)?) {
+        // A plugin that exits without reading is reported by its exit status below
+        debug!("Failed to send the request to plugin {}: {}", name, e);
+    }
+    drop(stdin);
+
+    let timeout = plugin.timeout_secs.map(Duration::from_secs);
+    let started = Instant::now();
+    let mut interval = Duration::from_millis(1);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(timeout) = timeout
+            && started.elapsed() >= timeout
+        {
+            child.kill().ok();
+            child.wait().ok();
+            anyhow::bail!(
+                "Plugin {} killed after exceeding the {}s timeout",
+                name,
+                timeout.as_secs()
+            );
+        }
+        std::thread::sleep(interval);
+        interval = (interval * 2).min(Duration::from_millis(50));
+    };
+
+    let messages = messages
+        .join()
+        .map_err(|_| anyhow::anyhow!("Plugin {} output reader panicked", name))?
+        .with_context(|| format!("Plugin {} broke the protocol", name))?;
+    let mut result = None;
+    for message in messages {
+        match message {
+            PluginMessage::Log { .. } => {}
+            PluginMessage::Error { message } => {
+                anyhow::bail!("Plugin {} failed: {}", name, message)
+            }
+            PluginMessage::Result { files } => {
+                if result.replace(files).is_some() {
+                    warn!("Plugin {} sent more than one result, using the last", name);
+                }
+            }
+        }
+    }
+    if !status.success() {
+        anyhow::bail!("Plugin {} exited with {}", name, status);
+    }
+    result.with_context(|| format!("Plugin {} exited without sending a result", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn shell_plugin(kind: PluginKind, script: &str) -> PluginConfig {
+        PluginConfig {
+            name: Some("test plugin".to_string()),
+            command: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), script.to_string()],
+            kind,
+            timeout_secs: Some(10),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_preprocess_replaces_files() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res_dir = temp.path().join("res");
+        std::fs::create_dir_all(res_dir.join("drawable"))?;
+        let icon = res_dir.join("drawable").join("icon.png");
+        std::fs::write(&icon, "icon")?;
+        let work_root = temp.path().join("plugins");
+
+        // Copies every requested file into its work dir and returns the copies
+        let copy = shell_plugin(
+            PluginKind::Preprocessor,
+            r#"read request
+work=$(echo "$request" | sed 's/.*"workDir":"\([^"]*\)".*/\1/')
+mkdir -p "$work/drawable" && echo optimized > "$work/drawable/icon.png"
+echo '{"type":"log","message":"optimized 1 file"}'
+echo "{\"type\":\"result\",\"files\":[\"$work/drawable/icon.png\"]}""#,
+        );
+        let postprocessor = shell_plugin(PluginKind::Postprocessor, "exit 1");
+        let files = preprocess(
+            &[copy, postprocessor],
+            "com.test",
+            &res_dir,
+            vec![icon.clone()],
+            &work_root,
+        )?;
+        let copied = work_root
+            .join("0_test_plugin")
+            .join("drawable")
+            .join("icon.png");
+        assert_eq!(files, vec![copied.clone()]);
+        assert_eq!(std::fs::read_to_string(&copied)?, "optimized\n");
+
+        let failing = shell_plugin(
+            PluginKind::Preprocessor,
+            r#"echo '{"type":"error","message":"bad input"}'"#,
+        );
+        let err = preprocess(&[failing], "com.test", &res_dir, vec![icon], &work_root).unwrap_err();
+        assert_eq!(err.to_string(), "Plugin test plugin failed: bad input");
+        Ok(())
+    }
+}
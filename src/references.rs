@@ -0,0 +1,466 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::resource_rewrite::{collect_definitions, is_xml};
+
+/// Most suggestions offered for one unknown reference
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Resources references can resolve to, as (type, name)
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: HashSet<(String, String)>,
+    /// Inputs whose symbols could not be read (compiled values, static libraries)
+    pub opaque_inputs: Vec<PathBuf>,
+}
+
+impl SymbolTable {
+    /// Add the resources defined by resource files below `res_dir`
+    pub fn add_resource_files(&mut self, res_dir: &Path, files: &[PathBuf]) -> Result<()> {
+        for file in files {
+            let content = if is_xml(file) {
+                Some(
+                    fs::read_to_string(file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?,
+                )
+            } else {
+                None
+            };
+            collect_definitions(res_dir, file, content.as_deref(), &mut self.symbols)?;
+        }
+        Ok(())
+    }
+
+    /// Add the file-based resources of precompiled link inputs
+    ///
+    /// Flat files of file-based resources are named `<dir>_<file>.flat`, so their resource is
+    /// known. Compiled values files and static libraries don't reveal what they define and are
+    /// recorded in `opaque_inputs` instead.
+    pub fn add_link_inputs(&mut self, inputs: &[PathBuf]) {
+        for input in inputs {
+            let parsed = input
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_suffix(".flat"))
+                .and_then(|n| n.split_once('_'))
+                .filter(|(dir, _)| *dir != "values" && !dir.starts_with("values-"));
+            match parsed {
+                Some((dir, file)) => {
+                    let resource_type = dir.split('-').next().unwrap_or(dir);
+                    let name = file.split('.').next().unwrap_or(file);
+                    self.symbols
+                        .insert((resource_type.to_string(), name.to_string()));
+                }
+                None => self.opaque_inputs.push(input.clone()),
+            }
+        }
+    }
+
+    /// Load a host symbol list
+    ///
+    /// Accepts aapt2 `R.txt` lines (`int color primary 0x7f010000`) and plain
+    /// `@type/name` or `type/name` lines; blank lines and `#` comments are ignored.
+    pub fn load_host_symbols(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read host symbols {}", path.display()))?;
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            let symbol = match tokens.next() {
+                Some("int" | "int[]") => tokens.next().zip(tokens.next()),
+                Some(token) => token.trim_start_matches('@').split_once('/'),
+                None => None,
+            };
+            let Some((resource_type, name)) = symbol else {
+                anyhow::bail!(
+                    "Invalid host symbol at {}:{}: {}",
+                    path.display(),
+                    idx + 1,
+                    line
+                );
+            };
+            self.symbols
+                .insert((resource_type.to_string(), name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Every known resource, as (type, name)
+    pub fn symbols(&self) -> impl Iterator<Item = &(String, String)> {
+        self.symbols.iter()
+    }
+
+    pub fn contains(&self, resource_type: &str, name: &str) -> bool {
+        self.symbols
+            .contains(&(resource_type.to_string(), name.to_string()))
+    }
+
+    /// Known resources whose name is close to an unknown `@type/name`, best first
+    ///
+    /// Names of the same type within an edit distance of a third of the name's length (at
+    /// least 1) are suggested, as is the same name under another type.
+    pub fn suggestions(&self, resource_type: &str, name: &str) -> Vec<String> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        let mut candidates: Vec<(usize, String)> = self
+            .symbols
+            .iter()
+            .filter_map(|(candidate_type, candidate)| {
+                let distance = if candidate_type == resource_type {
+                    edit_distance(name, candidate)
+                } else if candidate == name {
+                    1
+                } else {
+                    return None;
+                };
+                (distance > 0 && distance <= max_distance)
+                    .then(|| (distance, format!("@{}/{}", candidate_type, candidate)))
+            })
+            .collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, reference)| reference)
+            .collect()
+    }
+}
+
+/// Levenshtein distance between two names, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// ` (did you mean @a or @b?)` for a non-empty list of suggestions
+pub fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" (did you mean {}?)", only),
+        [rest @ .., last] => format!(" (did you mean {} or {}?)", rest.join(", "), last),
+    }
+}
+
+/// Append suggestions to every aapt2 `resource type/name ... not found` line of `text`
+pub fn suggest_missing_resources(text: &str, symbols: &SymbolTable) -> String {
+    text.lines()
+        .map(|line| {
+            let hint = missing_resource(line)
+                .map(|(resource_type, name)| {
+                    did_you_mean(&symbols.suggestions(resource_type, name))
+                })
+                .unwrap_or_default();
+            format!("{}{}", line, hint)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Type and name of the resource an aapt2 `resource [pkg:]type/name ... not found` error
+/// is about
+fn missing_resource(line: &str) -> Option<(&str, &str)> {
+    let (_, rest) = line.split_once("error: resource ")?;
+    if !rest.contains("not found") {
+        return None;
+    }
+    let reference = rest.split_whitespace().next()?;
+    let reference = reference.rsplit(':').next()?;
+    reference.trim_start_matches('@').split_once('/')
+}
+
+/// A `@type/name` reference that resolves to nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedReference {
+    pub file: PathBuf,
+    pub line: u32,
+    pub reference: String,
+}
+
+impl fmt::Display for UnresolvedReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.file.display(),
+            self.line,
+            self.reference
+        )
+    }
+}
+
+/// Type and name of a `@type/name` reference that must resolve in the skin's own package
+///
+/// Framework (`@android:`) and other package-qualified references, `@+id` definitions and
+/// `@null`/`@empty` are skipped.
+fn local_reference(value: &str) -> Option<(&str, &str)> {
+    let target = value.strip_prefix('@')?;
+    if target.starts_with('+') || target.contains(':') || target.starts_with('*') {
+        return None;
+    }
+    target.split_once('/')
+}
+
+/// Find `@type/name` references in XML attributes and element text of `files` that don't
+/// resolve in `symbols`
+pub fn find_unresolved(
+    files: &[PathBuf],
+    symbols: &SymbolTable,
+) -> Result<Vec<UnresolvedReference>> {
+    let mut unresolved = Vec::new();
+    for file in files.iter().filter(|f| is_xml(f)) {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let document = roxmltree::Document::parse(&content)
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+
+        for node in document.descendants() {
+            let mut values: Vec<(&str, usize)> = Vec::new();
+            if node.is_text() {
+                let raw = &content[node.range()];
+                let leading = raw.len() - raw.trim_start().len();
+                values.push((raw.trim(), node.range().start + leading));
+            } else if node.is_element() {
+                values.extend(
+                    node.attributes()
+                        .map(|a| (a.value(), a.range_value().start)),
+                );
+            }
+            for (value, position) in values {
+                if let Some((resource_type, name)) = local_reference(value)
+                    && !symbols.contains(resource_type, name)
+                {
+                    unresolved.push(UnresolvedReference {
+                        file: file.clone(),
+                        line: document.text_pos_at(position).row,
+                        reference: value.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(unresolved)
+}
+
+/// Resources defined in more than one file of the same resource directory, such as
+/// `color/primary` in both `values/colors.xml` and `values/theme.xml`, or
+/// `drawable/logo` as both `logo.png` and `logo.xml`
+///
+/// Such duplicates are usually copy-paste mistakes. `@+id` ids and styleable attrs are
+/// declared repeatedly by design and are not reported.
+pub fn find_duplicate_definitions(res_dir: &Path, files: &[PathBuf]) -> Result<Vec<String>> {
+    let mut definitions: BTreeMap<(PathBuf, String, String), Vec<&PathBuf>> = BTreeMap::new();
+    for file in files {
+        let content = if is_xml(file) {
+            Some(
+                fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?,
+            )
+        } else {
+            None
+        };
+        let mut names = HashSet::new();
+        collect_definitions(res_dir, file, content.as_deref(), &mut names)?;
+        let dir = file.parent().unwrap_or(res_dir).to_path_buf();
+        for (resource_type, name) in names {
+            if resource_type != "id" && resource_type != "attr" {
+                definitions
+                    .entry((dir.clone(), resource_type, name))
+                    .or_default()
+                    .push(file);
+            }
+        }
+    }
+    Ok(definitions
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((_, resource_type, name), files)| {
+            format!(
+                "{}/{} is defined more than once: {}",
+                resource_type,
+                name,
+                files
+                    .iter()
+                    .map(|f| f.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_find_unresolved() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let colors = res_dir.join("values").join("colors.xml");
+        write(
+            &colors,
+            r#"<resources>
+    <color name="primary">#6200EE</color>
+    <color name="accent">@color/primary</color>
+    <color name="text">@color/missing_text</color>
+</resources>"#,
+        );
+        let selector = res_dir.join("drawable").join("selector.xml");
+        write(
+            &selector,
+            r#"<selector xmlns:android="http://schemas.android.com/apk/res/android">
+    <item android:id="@+id/pressed" android:drawable="@drawable/host_pressed"/>
+    <item android:drawable="@android:drawable/btn_default"/>
+    <item android:drawable="@drawable/icon"/>
+    <item android:drawable="@null"/>
+    <item android:drawable="@drawable/from_flat"/>
+    <item android:drawable="@drawable/unknown"/>
+</selector>"#,
+        );
+        write(&res_dir.join("drawable-hdpi").join("icon.png"), "png");
+        let host_symbols = temp_dir.path().join("R.txt");
+        write(
+            &host_symbols,
+            "# host app\nint drawable host_pressed 0x7f020001\nint[] styleable Skin { 0x7f010000 }\n",
+        );
+
+        let files = vec![
+            colors.clone(),
+            selector.clone(),
+            res_dir.join("drawable-hdpi").join("icon.png"),
+        ];
+        let mut symbols = SymbolTable::default();
+        symbols.add_resource_files(&res_dir, &files)?;
+        symbols.add_link_inputs(&[
+            PathBuf::from("deps/drawable-xhdpi_from_flat.png.flat"),
+            PathBuf::from("deps/values_colors.arsc.flat"),
+        ]);
+        assert_eq!(
+            symbols.opaque_inputs,
+            vec![PathBuf::from("deps/values_colors.arsc.flat")]
+        );
+
+        let unresolved: Vec<String> = find_unresolved(&files, &symbols)?
+            .iter()
+            .map(|r| format!("{}:{}", r.line, r.reference))
+            .collect();
+        assert_eq!(
+            unresolved,
+            vec![
+                "4:@color/missing_text",
+                "2:@drawable/host_pressed",
+                "7:@drawable/unknown"
+            ]
+        );
+
+        symbols.load_host_symbols(&host_symbols)?;
+        assert!(symbols.contains("styleable", "Skin"));
+        let unresolved = find_unresolved(&files, &symbols)?;
+        assert_eq!(unresolved.len(), 2);
+        assert_eq!(
+            unresolved[1].to_string(),
+            format!("{}:7: @drawable/unknown", selector.display())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_suggestions_for_missing_resources() {
+        let mut symbols = SymbolTable::default();
+        for (resource_type, name) in [
+            ("color", "primary"),
+            ("color", "primary_dark"),
+            ("drawable", "icon"),
+            ("drawable", "icon_bg"),
+            ("mipmap", "icn"),
+        ] {
+            symbols
+                .symbols
+                .insert((resource_type.to_string(), name.to_string()));
+        }
+        assert_eq!(
+            symbols.suggestions("color", "primry"),
+            vec!["@color/primary"]
+        );
+        assert_eq!(
+            symbols.suggestions("drawable", "icn"),
+            vec!["@drawable/icon", "@mipmap/icn"]
+        );
+        assert!(symbols.suggestions("color", "secondary").is_empty());
+
+        let errors = "aapt2 link failed:\n\
+            res/layout/main.xml:5: error: resource drawable/icn (aka com.test:drawable/icn) not found.\n\
+            error: failed linking file resources.";
+        assert_eq!(
+            suggest_missing_resources(errors, &symbols),
+            "aapt2 link failed:\n\
+            res/layout/main.xml:5: error: resource drawable/icn (aka com.test:drawable/icn) not found. \
+            (did you mean @drawable/icon or @mipmap/icn?)\n\
+            error: failed linking file resources."
+        );
+    }
+
+    #[test]
+    fn test_find_duplicate_definitions() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let colors = res_dir.join("values/colors.xml");
+        let theme = res_dir.join("values/theme.xml");
+        let night = res_dir.join("values-night/colors.xml");
+        let logo_png = res_dir.join("drawable/logo.png");
+        let logo_xml = res_dir.join("drawable/logo.xml");
+        write(
+            &colors,
+            r#"<resources><color name="primary">#000000</color><item type="id" name="row"/></resources>"#,
+        );
+        write(
+            &theme,
+            r#"<resources><color name="primary">#111111</color><item type="id" name="row"/></resources>"#,
+        );
+        write(
+            &night,
+            r#"<resources><color name="primary">#ffffff</color></resources>"#,
+        );
+        write(&logo_png, "png");
+        write(&logo_xml, "<shape />");
+
+        let duplicates =
+            find_duplicate_definitions(&res_dir, &[colors, theme, night, logo_png, logo_xml])?;
+        assert_eq!(duplicates.len(), 2);
+        assert!(duplicates[0].starts_with("drawable/logo is defined more than once: "));
+        assert!(duplicates[1].starts_with("color/primary is defined more than once: "));
+        assert!(duplicates[1].contains("theme.xml") && !duplicates[1].contains("values-night"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_host_symbols_rejects_garbage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("symbols.txt");
+        fs::write(&path, "@color/primary\ndrawable/icon\nnot a symbol\n")?;
+        let mut symbols = SymbolTable::default();
+        let err = symbols.load_host_symbols(&path).unwrap_err();
+        assert!(err.to_string().contains(":3:"));
+        Ok(())
+    }
+}
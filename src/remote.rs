@@ -0,0 +1,826 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+use crate::builder::SkinBuilder;
+use crate::dependency::ConfigWithIndex;
+use crate::fingerprint::Fingerprint;
+use crate::types::{BuildConfig, BuildResult, find_highest_android_jar};
+
+/// Port used when a worker address has none
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// Directory of a job holding its uploaded inputs
+const INPUT_DIR: &str = "in";
+
+/// Largest protocol message line accepted from the other side
+const MAX_MESSAGE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Largest blob or output file accepted from the other side
+const MAX_PAYLOAD_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Protocol message, written as one JSON object per line
+/// `Blob` and `Output` headers are followed by `size` raw bytes
+///
+/// A job is a short conversation that streams raw file contents after their headers, so
+/// plain JSON lines over TCP are used rather than HTTP or gRPC, which would add a server
+/// stack and code generation to a crate that has neither (remote archives are fetched
+/// with curl) without simplifying the exchange.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Message {
+    /// Worker → coordinator on connect: how many builds the worker runs at once, and a
+    /// random challenge the coordinator answers to prove it knows the shared token
+    Hello { jobs: usize, challenge: String },
+    /// Coordinator → worker: SHA-256 of `<challenge>:<token>`
+    Auth { proof: String },
+    /// Worker → coordinator: the proof matches; the worker accepts a job
+    Authenticated,
+    /// Coordinator → worker: build `config`, whose input paths point into `inputs`
+    /// (job-relative path -> SHA-256 of the content)
+    Build {
+        config: Box<BuildConfig>,
+        inputs: BTreeMap<String, String>,
+    },
+    /// Worker → coordinator: input blobs the worker does not have yet
+    Missing { hashes: Vec<String> },
+    /// Coordinator → worker: content of an input blob
+    Blob { hash: String, size: u64 },
+    /// Worker → coordinator: a file from the job's output directory
+    Output { path: String, size: u64 },
+    /// Worker → coordinator: the build finished; `apk` is relative to the output directory
+    #[serde(rename_all = "camelCase")]
+    Finished {
+        success: bool,
+        apk: Option<String>,
+        errors: Vec<String>,
+        #[serde(default)]
+        warnings: Vec<String>,
+        build_duration_ms: u64,
+    },
+    /// Worker → coordinator: the job could not be run
+    Failed { error: String },
+}
+
+/// Buffered connection speaking the line-delimited protocol
+struct Connection {
+    stream: BufReader<TcpStream>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    async fn send(&mut self, message: &Message, payload: Option<&[u8]>) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        let stream = self.stream.get_mut();
+        stream.write_all(&line).await?;
+        if let Some(payload) = payload {
+            stream.write_all(payload).await?;
+        }
+        stream.flush().await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Message> {
+        let mut line = Vec::new();
+        let read = (&mut self.stream)
+            .take(MAX_MESSAGE_SIZE)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 {
+            anyhow::bail!("Connection closed");
+        }
+        if !line.ends_with(b"\n") && read as u64 == MAX_MESSAGE_SIZE {
+            anyhow::bail!("Protocol message larger than {} bytes", MAX_MESSAGE_SIZE);
+        }
+        serde_json::from_slice(&line).context("Invalid protocol message")
+    }
+
+    async fn receive_payload(&mut self, size: u64) -> Result<Vec<u8>> {
+        if size > MAX_PAYLOAD_SIZE {
+            anyhow::bail!(
+                "File of {} bytes is larger than the {} byte limit",
+                size,
+                MAX_PAYLOAD_SIZE
+            );
+        }
+        let mut payload = vec![0; usize::try_from(size)?];
+        self.stream.read_exact(&mut payload).await?;
+        Ok(payload)
+    }
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Whether `hash` is a SHA-256 as this protocol writes it: 64 lowercase hex digits
+fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Fresh challenge for the handshake of one connection
+fn challenge() -> String {
+    static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+    let seed = format!(
+        "{:?}:{}:{}",
+        std::time::SystemTime::now(),
+        std::process::id(),
+        CONNECTIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    sha256(seed.as_bytes())
+}
+
+/// Answer to `challenge` proving knowledge of `token` without sending it
+fn auth_proof(challenge: &str, token: &str) -> String {
+    sha256(format!("{}:{}", challenge, token).as_bytes())
+}
+
+/// Compare two proofs in time independent of where they differ
+fn proofs_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Add the default port to a worker address without one
+pub fn worker_address(address: &str) -> String {
+    if address
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
+
+/// Whether a config can be built on a worker
+/// Git state and computed versionCodes depend on the coordinator's checkout, owners rules
+/// match paths in it and hooks and plugins run commands against it, so those configs are
+/// built locally.
+pub fn is_remote_buildable(config: &BuildConfig) -> bool {
+    config.vcs.is_none()
+        && config.owners.is_none()
+        && config.hooks.is_none()
+        && config.plugins.is_none()
+        && (config.version_code_strategy.is_none() || config.version_code.is_some())
+}
+
+/// Parse a job-relative path sent by the other side, rejecting anything that escapes the job
+fn relative_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        anyhow::bail!("Invalid job path: {}", path);
+    }
+    Ok(relative)
+}
+
+/// Local files of a job, keyed by their job-relative path
+#[derive(Default)]
+struct JobInputs {
+    files: BTreeMap<String, PathBuf>,
+    roots: usize,
+}
+
+impl JobInputs {
+    /// Register a local input path and return its job-relative replacement
+    /// Files keep their name since aapt2 and the builder look at extensions
+    fn add(&mut self, path: &Path, follow_symlinks: bool) -> Result<PathBuf> {
+        let root = format!("{}/{}", INPUT_DIR, self.roots);
+        self.roots += 1;
+        if path.is_file() {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .with_context(|| format!("Invalid input file name: {}", path.display()))?;
+            let remote = format!("{}/{}", root, name);
+            self.files.insert(remote.clone(), path.to_path_buf());
+            return Ok(PathBuf::from(remote));
+        }
+        if path.is_dir() {
+            for entry in WalkDir::new(path).follow_links(follow_symlinks) {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative: Vec<String> = entry
+                    .path()
+                    .strip_prefix(path)?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                self.files.insert(
+                    format!("{}/{}", root, relative.join("/")),
+                    entry.path().to_path_buf(),
+                );
+            }
+        }
+        // Missing inputs stay missing on the worker, with the same outcome as a local build
+        Ok(PathBuf::from(root))
+    }
+}
+
+/// Build configs on remote workers
+///
+/// Each worker runs as many builds at once as it announces; configs are handed out in order
+/// as slots free up. Returns the results of remote builds and the configs that could not be
+/// built remotely (unreachable worker, connection lost) so the caller can build them locally.
+/// `token` is the secret the workers were started with.
+pub async fn build_on_workers(
+    configs: Vec<ConfigWithIndex>,
+    workers: &[String],
+    token: &str,
+) -> (Vec<(usize, BuildResult)>, Vec<ConfigWithIndex>) {
+    let mut slots = Vec::new();
+    for worker in workers {
+        let address = worker_address(worker);
+        match probe(&address, token).await {
+            Ok(jobs) => {
+                info!("Worker {} runs {} builds at once", address, jobs);
+                slots.extend(std::iter::repeat_n(address, jobs.max(1)));
+            }
+            Err(e) => warn!("Worker {} is unavailable: {:#}", address, e),
+        }
+    }
+    if slots.is_empty() {
+        warn!("No worker is available, building locally");
+        return (Vec::new(), configs);
+    }
+
+    let queue = Arc::new(Mutex::new(VecDeque::from(configs)));
+    let fallback = Arc::new(Mutex::new(Vec::new()));
+    let mut tasks = tokio::task::JoinSet::new();
+    for address in slots {
+        let queue = queue.clone();
+        let fallback = fallback.clone();
+        let token = token.to_string();
+        tasks.spawn(async move {
+            let mut results = Vec::new();
+            loop {
+                let next = queue.lock().unwrap().pop_front();
+                let Some(config) = next else {
+                    break;
+                };
+                let package = config.config.package_name.clone();
+                match build_remote(&config.config, &address, &token).await {
+                    Ok(result) => results.push((config.index, result)),
+                    Err(e) => {
+                        warn!(
+                            "Remote build of {} on {} failed, building locally: {:#}",
+                            package, address, e
+                        );
+                        fallback.lock().unwrap().push(config);
+                        // Stop using a worker that dropped a job
+                        break;
+                    }
+                }
+            }
+            results
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(task) = tasks.join_next().await {
+        match task {
+            Ok(task_results) => results.extend(task_results),
+            Err(e) => error!("Remote build task failed: {}", e),
+        }
+    }
+    // Configs left when every slot gave up are built locally as well
+    let mut fallback = std::mem::take(&mut *fallback.lock().unwrap());
+    fallback.extend(queue.lock().unwrap().drain(..));
+    (results, fallback)
+}
+
+/// Connect to a worker and authenticate with `token`
+/// Returns the connection and the number of builds the worker runs at once.
+async fn connect(address: &str, token: &str) -> Result<(Connection, usize)> {
+    let stream = tokio::time::timeout(Duration::from_secs(10), TcpStream::connect(address))
+        .await
+        .context("Timed out connecting")?
+        .with_context(|| format!("Failed to connect to worker {}", address))?;
+    let mut connection = Connection::new(stream);
+    let (jobs, challenge) = match connection.receive().await? {
+        Message::Hello { jobs, challenge } => (jobs, challenge),
+        other => anyhow::bail!("Unexpected greeting: {:?}", other),
+    };
+    let proof = auth_proof(&challenge, token);
+    connection.send(&Message::Auth { proof }, None).await?;
+    match connection.receive().await? {
+        Message::Authenticated => Ok((connection, jobs)),
+        Message::Failed { error } => anyhow::bail!("Worker refused the connection: {}", error),
+        other => anyhow::bail!("Unexpected message: {:?}", other),
+    }
+}
+
+/// Check that a worker accepts `token` and read how many builds it runs at once
+async fn probe(address: &str, token: &str) -> Result<usize> {
+    connect(address, token).await.map(|(_, jobs)| jobs)
+}
+
+/// Build one config on a worker and write its outputs to the config's output directory
+pub async fn build_remote(config: &BuildConfig, address: &str, token: &str) -> Result<BuildResult> {
+    let mut remote_config = config.clone();
+    let mut inputs = JobInputs::default();
+    let follow_symlinks = config.follow_symlinks.unwrap_or(false);
+
+    // Upload the package's own stable IDs file under the name the worker will look for
+    let stable_ids_file = remote_config.stable_ids_file.take();
+    if let (Some(configured), Some(local)) = (&stable_ids_file, config.stable_ids_path()) {
+        if local.exists() {
+            let root = format!("{}/{}", INPUT_DIR, inputs.roots);
+            inputs.roots += 1;
+            let name = |p: &Path| {
+                p.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            inputs
+                .files
+                .insert(format!("{}/{}", root, name(&local)), local.clone());
+            remote_config.stable_ids_file = Some(PathBuf::from(root).join(name(configured)));
+        } else {
+            warn!(
+                "Stable IDs file {} does not exist yet; IDs emitted by the worker are not kept",
+                local.display()
+            );
+        }
+    }
+    remote_config.map_inputs(|path| inputs.add(path, follow_symlinks))?;
+
+    let mut hashes: HashMap<String, PathBuf> = HashMap::new();
+    let mut manifest = BTreeMap::new();
+    for (remote_path, local_path) in &inputs.files {
+        let content = std::fs::read(local_path)
+            .with_context(|| format!("Failed to read {}", local_path.display()))?;
+        let hash = sha256(&content);
+        manifest.insert(remote_path.clone(), hash.clone());
+        hashes.insert(hash, local_path.clone());
+    }
+
+    let (mut connection, _) = connect(address, token).await?;
+    info!(
+        "Building {} on {} ({} input files)",
+        config.package_name,
+        address,
+        manifest.len()
+    );
+    connection
+        .send(
+            &Message::Build {
+                config: Box::new(remote_config),
+                inputs: manifest,
+            },
+            None,
+        )
+        .await?;
+
+    let missing = match connection.receive().await? {
+        Message::Missing { hashes } => hashes,
+        Message::Failed { error } => anyhow::bail!("Worker rejected the job: {}", error),
+        other => anyhow::bail!("Unexpected message: {:?}", other),
+    };
+    debug!("Uploading {} blobs to {}", missing.len(), address);
+    for hash in missing {
+        let local_path = hashes
+            .get(&hash)
+            .with_context(|| format!("Worker asked for unknown blob {}", hash))?;
+        let content = std::fs::read(local_path)?;
+        connection
+            .send(
+                &Message::Blob {
+                    hash,
+                    size: content.len() as u64,
+                },
+                Some(&content),
+            )
+            .await?;
+    }
+
+    loop {
+        match connection.receive().await? {
+            Message::Output { path, size } => {
+                let content = connection.receive_payload(size).await?;
+                let output = config.output_dir.join(relative_path(&path)?);
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&output, content)
+                    .with_context(|| format!("Failed to write {}", output.display()))?;
+            }
+            Message::Finished {
+                success,
+                apk,
+                errors,
+                warnings,
+                build_duration_ms,
+            } => {
+                // The worker's fingerprint hashes its own paths; record the local inputs instead
+                if success {
+                    match Fingerprint::compute(config).and_then(|f| f.write(config)) {
+                        Ok(path) => debug!("Build fingerprint written to {}", path.display()),
+                        Err(e) => warn!("Failed to write build fingerprint: {:#}", e),
+                    }
+                }
+                return Ok(BuildResult {
+                    success,
+                    apk_path: apk
+                        .map(|apk| relative_path(&apk).map(|p| config.output_dir.join(p)))
+                        .transpose()?,
+                    errors,
+                    warnings,
+                    build_duration: Duration::from_millis(build_duration_ms),
+                    vcs: None,
+                    cached_files: 0,
+                    compiled_files: 0,
+                    normalization: None,
+                });
+            }
+            Message::Failed { error } => anyhow::bail!("Worker failed: {}", error),
+            other => anyhow::bail!("Unexpected message: {:?}", other),
+        }
+    }
+}
+
+/// Serve builds to coordinators until the process is stopped
+///
+/// Only coordinators proving knowledge of `token` are served. Jobs are built without
+/// hooks, plugins or anything else that runs commands or reads files outside the job.
+/// Uploaded inputs are stored by content hash under `work_dir/blobs`, so unchanged files are
+/// only transferred once. Each package builds in its own `work_dir/jobs/<package>` directory,
+/// which keeps incremental caches between builds.
+pub async fn run_worker(listen: &str, work_dir: &Path, jobs: usize, token: &str) -> Result<()> {
+    if token.is_empty() {
+        anyhow::bail!("The worker token must not be empty");
+    }
+    std::fs::create_dir_all(work_dir.join("blobs"))?;
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    info!(
+        "Worker listening on {} ({} builds at once, work dir {})",
+        listener.local_addr()?,
+        jobs,
+        work_dir.display()
+    );
+
+    let permits = Arc::new(Semaphore::new(jobs.max(1)));
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let work_dir = work_dir.to_path_buf();
+        let permits = permits.clone();
+        let token = token.to_string();
+        tokio::spawn(async move {
+            let mut connection = Connection::new(stream);
+            if let Err(e) = serve_job(&mut connection, &work_dir, jobs, permits, &token).await {
+                warn!("Job from {} failed: {:#}", peer, e);
+                let failed = Message::Failed {
+                    error: format!("{:#}", e),
+                };
+                connection.send(&failed, None).await.ok();
+            }
+        });
+    }
+}
+
+/// Config a worker builds for a job received in `job_dir`
+///
+/// Input paths must stay inside the job and are resolved there; output, build and
+/// toolchain paths are the worker's own. Paths are not interpolated again, so the
+/// coordinator cannot read the worker's environment through `${env:...}`.
+fn job_config(mut config: BuildConfig, job_dir: &Path) -> Result<BuildConfig> {
+    if !is_remote_buildable(&config) {
+        anyhow::bail!(
+            "Jobs with hooks, plugins, vcs, owners or a versionCodeStrategy are built locally"
+        );
+    }
+    config.map_inputs(|path| Ok(job_dir.join(relative_path(&path.to_string_lossy())?)))?;
+    if let Some(stable_ids) = config.stable_ids_file.as_mut() {
+        *stable_ids = job_dir.join(relative_path(&stable_ids.to_string_lossy())?);
+    }
+    if let Some(output_file) = &config.output_file {
+        relative_path(output_file)?;
+    }
+    if let Some(golden_images) = config.golden_images.as_mut() {
+        golden_images.diff_dir = None;
+    }
+    config.output_dir = job_dir.join("out");
+    config.build_dir = Some(job_dir.join("build"));
+    config.cache_dir = None;
+    config.compiled_dir = None;
+    config.aapt2_path = None;
+    config.android_jar = find_highest_android_jar();
+    Ok(config)
+}
+
+/// Handle one connection: receive a job and its inputs, build it and return the outputs
+async fn serve_job(
+    connection: &mut Connection,
+    work_dir: &Path,
+    jobs: usize,
+    permits: Arc<Semaphore>,
+    token: &str,
+) -> Result<()> {
+    let challenge = challenge();
+    let hello = Message::Hello {
+        jobs,
+        challenge: challenge.clone(),
+    };
+    connection.send(&hello, None).await?;
+    match connection.receive().await? {
+        Message::Auth { proof } if proofs_match(&proof, &auth_proof(&challenge, token)) => {}
+        Message::Auth { .. } => anyhow::bail!("Invalid worker token"),
+        other => anyhow::bail!("Unexpected message: {:?}", other),
+    }
+    connection.send(&Message::Authenticated, None).await?;
+    let (config, inputs) = match connection.receive().await {
+        Ok(Message::Build { config, inputs }) => (*config, inputs),
+        Ok(other) => anyhow::bail!("Unexpected message: {:?}", other),
+        // Coordinators probe workers by connecting and hanging up
+        Err(_) => return Ok(()),
+    };
+    if let Some(hash) = inputs.values().find(|hash| !is_sha256(hash)) {
+        anyhow::bail!("Invalid input hash: {}", hash);
+    }
+    let job_name = match &config.flavor {
+        Some(flavor) => format!("{}-{}", config.package_name, flavor),
+        None => config.package_name.clone(),
+    };
+    let job_dir = work_dir.join("jobs").join(relative_path(&job_name)?);
+    let config = job_config(config, &job_dir)?;
+
+    let blobs_dir = work_dir.join("blobs");
+    let mut missing: Vec<String> = inputs
+        .values()
+        .filter(|hash| !blobs_dir.join(hash).exists())
+        .cloned()
+        .collect();
+    missing.sort();
+    missing.dedup();
+    connection
+        .send(
+            &Message::Missing {
+                hashes: missing.clone(),
+            },
+            None,
+        )
+        .await?;
+    for _ in 0..missing.len() {
+        let Message::Blob { hash, size } = connection.receive().await? else {
+            anyhow::bail!("Expected an input blob");
+        };
+        let content = connection.receive_payload(size).await?;
+        if sha256(&content) != hash || !missing.contains(&hash) {
+            anyhow::bail!("Blob {} does not match its content", hash);
+        }
+        let temp = blobs_dir.join(format!("{}.tmp", hash));
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, blobs_dir.join(&hash))?;
+    }
+
+    let _permit = permits.acquire_owned().await?;
+    let input_dir = job_dir.join(INPUT_DIR);
+    let output_dir = job_dir.join("out");
+    for dir in [&input_dir, &output_dir] {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+    }
+    for (path, hash) in &inputs {
+        let target = job_dir.join(relative_path(path)?);
+        if !target.starts_with(&input_dir) {
+            anyhow::bail!("Input outside of the input directory: {}", path);
+        }
+        std::fs::create_dir_all(target.parent().unwrap_or(&job_dir))?;
+        std::fs::copy(blobs_dir.join(hash), &target)?;
+    }
+
+    info!("Building {} ({} inputs)", job_name, inputs.len());
+    let result = SkinBuilder::new(config)?.build().await?;
+
+    for entry in WalkDir::new(&output_dir).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let content = std::fs::read(entry.path())?;
+        let path = entry
+            .path()
+            .strip_prefix(&output_dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        connection
+            .send(
+                &Message::Output {
+                    path,
+                    size: content.len() as u64,
+                },
+                Some(&content),
+            )
+            .await?;
+    }
+    let apk = result
+        .apk_path
+        .as_deref()
+        .and_then(|apk| apk.strip_prefix(&output_dir).ok())
+        .map(|apk| apk.to_string_lossy().replace('\\', "/"));
+    connection
+        .send(
+            &Message::Finished {
+                success: result.success,
+                apk,
+                errors: result.errors,
+                warnings: result.warnings,
+                build_duration_ms: result.build_duration.as_millis() as u64,
+            },
+            None,
+        )
+        .await?;
+    info!("Finished {} (success: {})", job_name, result.success);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AarFile;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_worker_address_and_paths() {
+        assert_eq!(worker_address("build1"), "build1:7878");
+        assert_eq!(worker_address("build1:9000"), "build1:9000");
+        assert_eq!(worker_address("10.0.0.5"), "10.0.0.5:7878");
+
+        assert_eq!(
+            relative_path("in/0/res").unwrap(),
+            PathBuf::from("in/0/res")
+        );
+        assert!(relative_path("../etc/passwd").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+        assert!(relative_path("").is_err());
+    }
+
+    #[test]
+    fn test_map_inputs_collects_job_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        std::fs::create_dir_all(res_dir.join("values"))?;
+        std::fs::write(res_dir.join("values").join("colors.xml"), "<resources/>")?;
+        let aar = temp_dir.path().join("lib.aar");
+        std::fs::write(&aar, "aar")?;
+
+        let mut config = BuildConfig::default_config();
+        config.resource_dir = res_dir.clone();
+        config.manifest_path = temp_dir.path().join("missing.xml");
+        config.aar_files = Some(vec![AarFile::from(aar.clone())]);
+
+        let mut inputs = JobInputs::default();
+        config.map_inputs(|path| inputs.add(path, false))?;
+        assert_eq!(config.resource_dir, PathBuf::from("in/0"));
+        assert_eq!(config.manifest_path, PathBuf::from("in/1"));
+        assert_eq!(
+            config.aar_files,
+            Some(vec![AarFile::from(PathBuf::from("in/2/lib.aar"))])
+        );
+        assert_eq!(
+            inputs.files,
+            BTreeMap::from([
+                (
+                    "in/0/values/colors.xml".to_string(),
+                    res_dir.join("values").join("colors.xml")
+                ),
+                ("in/2/lib.aar".to_string(), aar),
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inputs_are_uploaded_by_hash() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        std::fs::create_dir_all(res_dir.join("values"))?;
+        let colors = r#"<resources><color name="primary">#6200EE</color></resources>"#;
+        std::fs::write(res_dir.join("values").join("colors.xml"), colors)?;
+        let work_dir = temp_dir.path().join("worker");
+        std::fs::create_dir_all(work_dir.join("blobs"))?;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+        let worker = tokio::spawn({
+            let work_dir = work_dir.clone();
+            async move {
+                let (stream, _) = listener.accept().await?;
+                let mut connection = Connection::new(stream);
+                // Without an SDK the build itself may fail; the inputs arrive either way
+                let permits = Arc::new(Semaphore::new(1));
+                serve_job(&mut connection, &work_dir, 1, permits, "secret")
+                    .await
+                    .ok();
+                Ok::<_, anyhow::Error>(())
+            }
+        });
+
+        let mut config = BuildConfig::default_config();
+        config.resource_dir = res_dir;
+        config.output_dir = temp_dir.path().join("out");
+        build_remote(&config, &address, "secret").await.ok();
+        worker.await??;
+
+        let blob = work_dir.join("blobs").join(sha256(colors.as_bytes()));
+        assert_eq!(std::fs::read_to_string(blob)?, colors);
+        let job_input = work_dir
+            .join("jobs")
+            .join(&config.package_name)
+            .join("in/0/values/colors.xml");
+        assert_eq!(std::fs::read_to_string(job_input)?, colors);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_worker_rejects_wrong_token() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+        let work_dir = temp_dir.path().to_path_buf();
+        let worker = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut connection = Connection::new(stream);
+            let permits = Arc::new(Semaphore::new(1));
+            let served = serve_job(&mut connection, &work_dir, 1, permits, "secret").await;
+            if let Err(e) = &served {
+                let failed = Message::Failed {
+                    error: format!("{:#}", e),
+                };
+                connection.send(&failed, None).await.ok();
+            }
+            served
+        });
+
+        let err = probe(&address, "guess").await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Invalid worker token"),
+            "{:#}",
+            err
+        );
+        assert!(worker.await?.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_job_config_stays_inside_the_job() -> Result<()> {
+        let job_dir = Path::new("/work/jobs/com.test");
+        let mut config = BuildConfig::default_config();
+        config.resource_dir = PathBuf::from("in/0");
+        config.manifest_path = PathBuf::from("in/1");
+        let job = job_config(config.clone(), job_dir)?;
+        assert_eq!(job.resource_dir, job_dir.join("in/0"));
+        assert_eq!(job.output_dir, job_dir.join("out"));
+
+        for escape in ["/etc", "in/../../.."] {
+            let mut untrusted = config.clone();
+            untrusted.resource_dir = PathBuf::from(escape);
+            assert!(job_config(untrusted, job_dir).is_err(), "{}", escape);
+        }
+        // Not interpolated with the worker's environment
+        let mut untrusted = config.clone();
+        untrusted.resource_dir = PathBuf::from("${env:HOME}/res");
+        let job = job_config(untrusted, job_dir)?;
+        assert_eq!(job.resource_dir, job_dir.join("${env:HOME}/res"));
+        let mut untrusted = config.clone();
+        untrusted.output_file = Some("../../victim.skin".to_string());
+        assert!(job_config(untrusted, job_dir).is_err());
+        let mut untrusted = config;
+        untrusted.hooks = Some(Default::default());
+        assert!(job_config(untrusted, job_dir).is_err());
+
+        assert!(is_sha256(&sha256(b"colors")));
+        assert!(!is_sha256("/etc/shadow"));
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::archive::{extract_archive, is_archive};
+use crate::fingerprint::sha256;
+use crate::types::BuildConfig;
+
+/// A resource directory fetched from elsewhere instead of read from the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteSource {
+    /// `git+<url>[#<ref>][:<subdir>]`: a directory of a git repository at a branch, tag
+    /// or commit (default: the remote's HEAD)
+    Git {
+        url: String,
+        reference: Option<String>,
+        subdir: Option<PathBuf>,
+    },
+    /// `https://.../<name>.zip` (or `.tar`, `.tar.gz`, `.tgz`): a resource archive
+    Http { url: String },
+}
+
+impl RemoteSource {
+    /// Parse a resource directory entry; `None` for local paths
+    pub fn parse(path: &Path) -> Result<Option<Self>> {
+        let text = path.to_string_lossy();
+        if let Some(rest) = text.strip_prefix("git+") {
+            let (url, fragment) = rest.split_once('#').unwrap_or((rest, ""));
+            let (reference, subdir) = fragment.split_once(':').unwrap_or((fragment, ""));
+            if url.is_empty() {
+                anyhow::bail!("Missing repository URL in {}", text);
+            }
+            // Passed to git as arguments, where a leading dash would be read as an option
+            if url.starts_with('-') || reference.starts_with('-') {
+                anyhow::bail!(
+                    "Repository URL and reference in {} must not start with '-'",
+                    text
+                );
+            }
+            let subdir = PathBuf::from(subdir);
+            if !subdir
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                anyhow::bail!(
+                    "Subdirectory in {} must be a relative path inside the repository",
+                    text
+                );
+            }
+            return Ok(Some(Self::Git {
+                url: url.to_string(),
+                reference: Some(reference.to_string()).filter(|r| !r.is_empty()),
+                subdir: Some(subdir).filter(|s| !s.as_os_str().is_empty()),
+            }));
+        }
+        if text.starts_with("http://") {
+            anyhow::bail!("Remote resource URL {} must use https://", text);
+        }
+        if text.starts_with("https://") {
+            let url_path = text.split(['?', '#']).next().unwrap_or(&text);
+            if !is_archive(Path::new(url_path)) {
+                anyhow::bail!(
+                    "Remote resource URL {} must point to a .zip, .tar, .tar.gz or .tgz archive",
+                    text
+                );
+            }
+            return Ok(Some(Self::Http {
+                url: text.to_string(),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Fetch the source into `cache_dir`, reusing an earlier fetch when it is still current,
+    /// and return its local resource directory
+    pub fn fetch(&self, cache_dir: &Path) -> Result<PathBuf> {
+        match self {
+            Self::Git {
+                url,
+                reference,
+                subdir,
+            } => {
+                let checkout = fetch_git(url, reference.as_deref(), &cache_dir.join("git"))?;
+                let dir = match subdir {
+                    Some(subdir) => checkout.join(subdir),
+                    None => checkout,
+                };
+                if !dir.is_dir() {
+                    anyhow::bail!("{} is not a directory of {}", dir.display(), url);
+                }
+                Ok(dir)
+            }
+            Self::Http { url } => {
+                let dir = cache_dir.join("http").join(&sha256(url.as_bytes())[..16]);
+                let archive = download(url, &dir)?;
+                extract_archive(&archive, &dir)
+            }
+        }
+    }
+}
+
+/// Directory remote sources are cached in, shared by every build of the user
+/// `$ASB_REMOTE_CACHE`, else `$XDG_CACHE_HOME/asb/remote`, else `~/.cache/asb/remote`
+/// (`%LOCALAPPDATA%\asb\remote` on Windows)
+pub fn remote_cache_dir() -> PathBuf {
+    let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    if let Some(path) = var("ASB_REMOTE_CACHE") {
+        return PathBuf::from(path);
+    }
+    var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| var("LOCALAPPDATA").map(PathBuf::from))
+        .map(|cache_home| cache_home.join("asb").join("remote"))
+        .unwrap_or_else(|| PathBuf::from(".asb-remote"))
+}
+
+/// Replace remote entries of `resourceDir` and `additionalResourceDirs` with their fetched
+/// local directories
+pub fn resolve_remote_sources(config: &mut BuildConfig) -> Result<()> {
+    let cache_dir = remote_cache_dir();
+    for dir in std::iter::once(&mut config.resource_dir)
+        .chain(config.additional_resource_dirs.iter_mut().flatten())
+    {
+        if let Some(source) = RemoteSource::parse(dir)? {
+            let local = source
+                .fetch(&cache_dir)
+                .with_context(|| format!("Failed to fetch {}", dir.display()))?;
+            debug!("Using {} for {}", local.display(), dir.display());
+            *dir = local;
+        }
+    }
+    Ok(())
+}
+
+/// Run git and return its trimmed stdout
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .context("Failed to run git; is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn is_commit(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Commit `reference` points to on the remote, preferring the commit of an annotated tag
+/// over the tag object
+fn resolve_commit(url: &str, reference: &str) -> Result<String> {
+    let peeled = format!("{}^{{}}", reference);
+    let refs = git(None, &["ls-remote", "--", url, reference, &peeled])?;
+    let mut commit = None;
+    for line in refs.lines() {
+        let Some((sha, name)) = line.split_once('\t') else {
+            continue;
+        };
+        if name.ends_with("^{}") {
+            return Ok(sha.to_string());
+        }
+        commit.get_or_insert_with(|| sha.to_string());
+    }
+    commit.with_context(|| format!("{} has no branch or tag {}", url, reference))
+}
+
+/// Shallow checkout of `url` at `reference`, cached by commit
+fn fetch_git(url: &str, reference: Option<&str>, cache_dir: &Path) -> Result<PathBuf> {
+    let reference = reference.unwrap_or("HEAD");
+    let commit = if is_commit(reference) {
+        reference.to_string()
+    } else {
+        resolve_commit(url, reference)?
+    };
+    let dest = cache_dir.join(&sha256(url.as_bytes())[..16]).join(&commit);
+    if dest.is_dir() {
+        debug!("Reusing checkout of {} at {}", url, commit);
+        return Ok(dest);
+    }
+
+    // Checked out next to the cache entry and moved into place once complete
+    let partial = dest.with_extension("partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+    let fetch_ref = if is_commit(reference) {
+        &commit
+    } else {
+        reference
+    };
+    git(Some(&partial), &["init", "-q"])?;
+    git(
+        Some(&partial),
+        &["fetch", "-q", "--depth", "1", "--", url, fetch_ref],
+    )?;
+    git(
+        Some(&partial),
+        &[
+            "-c",
+            "advice.detachedHead=false",
+            "checkout",
+            "-q",
+            "FETCH_HEAD",
+        ],
+    )?;
+    let fetched = git(Some(&partial), &["rev-parse", "HEAD"])?;
+    if fetched != commit {
+        anyhow::bail!(
+            "{} moved from {} to {} while fetching; try again",
+            reference,
+            commit,
+            fetched
+        );
+    }
+    // The checkout is keyed by commit and never updated, so its history is not needed
+    fs::remove_dir_all(partial.join(".git"))?;
+    fs::rename(&partial, &dest)?;
+    info!("Fetched {} at {} ({})", url, reference, &commit[..12]);
+    Ok(dest)
+}
+
+/// Download `url` into `dir`, skipping the transfer when the remote file is not newer
+/// than an earlier download
+fn download(url: &str, dir: &Path) -> Result<PathBuf> {
+    let url_path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = url_path.rsplit('/').next().unwrap_or("archive");
+    let path = dir.join(name);
+    let partial = dir.join(format!("{}.partial", name));
+    fs::create_dir_all(dir)?;
+    fs::remove_file(&partial).ok();
+
+    let mut command = Command::new("curl");
+    command.args(["-fsSL", "--retry", "2", "-o"]).arg(&partial);
+    if path.is_file() {
+        command.arg("-z").arg(&path);
+    }
+    let output = command
+        .arg(url)
+        .output()
+        .context("Failed to run curl; is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Download failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    if partial.metadata().is_ok_and(|m| m.len() > 0) {
+        fs::rename(&partial, &path)?;
+        info!("Downloaded {}", url);
+    } else {
+        debug!("{} is not newer than {}", url, path.display());
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_remote_sources() -> Result<()> {
+        assert_eq!(RemoteSource::parse(Path::new("./brand/res"))?, None);
+        assert_eq!(
+            RemoteSource::parse(Path::new(
+                "git+https://github.com/acme/brand-assets.git#v2.1:skins/red/res"
+            ))?,
+            Some(RemoteSource::Git {
+                url: "https://github.com/acme/brand-assets.git".to_string(),
+                reference: Some("v2.1".to_string()),
+                subdir: Some(PathBuf::from("skins/red/res")),
+            })
+        );
+        assert_eq!(
+            RemoteSource::parse(Path::new("git+git@github.com:acme/brand.git"))?,
+            Some(RemoteSource::Git {
+                url: "git@github.com:acme/brand.git".to_string(),
+                reference: None,
+                subdir: None,
+            })
+        );
+        assert!(matches!(
+            RemoteSource::parse(Path::new("https://cdn.example.com/red.tar.gz?v=3"))?,
+            Some(RemoteSource::Http { .. })
+        ));
+        assert!(RemoteSource::parse(Path::new("https://cdn.example.com/red")).is_err());
+        assert!(RemoteSource::parse(Path::new("http://cdn.example.com/red.zip")).is_err());
+        for unsafe_source in [
+            "git+--upload-pack=touch /tmp/pwned",
+            "git+https://github.com/acme/brand.git#--upload-pack=evil",
+            "git+https://github.com/acme/brand.git#main:../../etc",
+            "git+https://github.com/acme/brand.git#main:/etc",
+        ] {
+            assert!(
+                RemoteSource::parse(Path::new(unsafe_source)).is_err(),
+                "{} should be rejected",
+                unsafe_source
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_git_source_by_tag() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("brand");
+        let run = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=asb", "-c", "user.email=asb@example.com"])
+                .args(["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        fs::create_dir_all(repo.join("res/values"))?;
+        if !run(&["init", "-q"]) {
+            eprintln!("Skipping test: git not available");
+            return Ok(());
+        }
+        fs::write(repo.join("res/values/colors.xml"), "<resources/>")?;
+        assert!(run(&["add", "."]));
+        assert!(run(&["commit", "-q", "-m", "v1"]));
+        assert!(run(&["tag", "-a", "v1", "-m", "v1"]));
+
+        let url = format!("file://{}", repo.display());
+        let source = RemoteSource::parse(Path::new(&format!("git+{}#v1:res", url)))?.unwrap();
+        let cache = temp.path().join("cache");
+        let res = source.fetch(&cache)?;
+        assert!(res.join("values/colors.xml").is_file());
+        assert!(!res.parent().unwrap().join(".git").exists());
+        // Cached by commit: later commits on other branches do not touch the checkout
+        fs::write(
+            repo.join("res/values/colors.xml"),
+            "<resources></resources>",
+        )?;
+        assert!(run(&["commit", "-q", "-am", "v2"]));
+        assert_eq!(source.fetch(&cache)?, res);
+        assert_eq!(
+            fs::read_to_string(res.join("values/colors.xml"))?,
+            "<resources/>"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Ignore file read for every resource directory
+pub const ASBIGNORE: &str = ".asbignore";
+
+/// Whether a file name is an ignore file that affects resource discovery
+pub fn is_ignore_file(name: &str) -> bool {
+    name == ASBIGNORE || name == ".gitignore"
+}
+
+/// Exclusion rules for one resource directory, in gitignore syntax
+///
+/// Rules come from `.asbignore` (and `.gitignore` with `respect_gitignore`) in the resource
+/// directory and its parents up to the root of the enclosing git repository. As in git, a
+/// file closer to the resource directory overrides its parents, and `!pattern` re-includes.
+#[derive(Debug)]
+pub struct ResourceIgnore {
+    res_dir: PathBuf,
+    absolute_res_dir: PathBuf,
+    /// Innermost directory first
+    matchers: Vec<Gitignore>,
+}
+
+impl ResourceIgnore {
+    pub fn load(res_dir: &Path, respect_gitignore: bool) -> Result<Self> {
+        let absolute_res_dir = std::path::absolute(res_dir)?;
+        let mut names = vec![ASBIGNORE];
+        if respect_gitignore {
+            names.push(".gitignore");
+        }
+
+        // Outside a git repository only the resource directory's own files apply
+        let dirs: Vec<&Path> = match absolute_res_dir
+            .ancestors()
+            .position(|dir| dir.join(".git").exists())
+        {
+            Some(root) => absolute_res_dir.ancestors().take(root + 1).collect(),
+            None => vec![absolute_res_dir.as_path()],
+        };
+
+        let mut matchers = Vec::new();
+        for dir in dirs {
+            let mut builder = GitignoreBuilder::new(dir);
+            let mut found = false;
+            for name in &names {
+                let file = dir.join(name);
+                if file.is_file() {
+                    if let Some(e) = builder.add(&file) {
+                        return Err(e).with_context(|| format!("Invalid {}", file.display()));
+                    }
+                    found = true;
+                }
+            }
+            if found {
+                matchers.push(
+                    builder
+                        .build()
+                        .with_context(|| format!("Invalid ignore rules in {}", dir.display()))?,
+                );
+            }
+        }
+
+        Ok(Self {
+            res_dir: res_dir.to_path_buf(),
+            absolute_res_dir,
+            matchers,
+        })
+    }
+
+    /// Whether a file or directory below the resource directory is excluded, directly or
+    /// through one of its parent directories
+    ///
+    /// Only the part of the path below the resource directory is matched, so rules such as
+    /// `build/` do not exclude a resource directory that itself lies in a build directory.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.matchers.is_empty() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.res_dir) else {
+            return false;
+        };
+        let components: Vec<_> = relative.components().collect();
+        let mut candidate = self.absolute_res_dir.clone();
+        for (idx, component) in components.iter().enumerate() {
+            candidate.push(component);
+            let last = idx + 1 == components.len();
+            if self.matched(&candidate, !last || is_dir) {
+                if !last {
+                    debug!("Ignoring {} with its directory", path.display());
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn matched(&self, path: &Path, is_dir: bool) -> bool {
+        for matcher in &self.matchers {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(glob) => {
+                    debug!(
+                        "Ignoring {} (pattern `{}` in {})",
+                        path.display(),
+                        glob.original(),
+                        glob.from()
+                            .map_or(String::new(), |f| f.display().to_string())
+                    );
+                    return true;
+                }
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ignore_rules_from_resource_dir_and_repository() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        fs::create_dir(repo.join(".git"))?;
+        fs::write(repo.join(".gitignore"), "*.orig\nbuild/\n")?;
+        fs::write(repo.join(ASBIGNORE), "*~\n")?;
+        let res_dir = repo.join("skins").join("res");
+        fs::create_dir_all(&res_dir)?;
+        fs::write(res_dir.join(ASBIGNORE), "drawable/*.psd\n!*~\n")?;
+
+        let ignore = ResourceIgnore::load(&res_dir, false)?;
+        assert!(ignore.is_ignored(&res_dir.join("drawable/logo.psd"), false));
+        assert!(!ignore.is_ignored(&res_dir.join("drawable/logo.png"), false));
+        // The resource directory's own rules override the repository's
+        assert!(!ignore.is_ignored(&res_dir.join("values/colors.xml~"), false));
+        // .gitignore is only read on request
+        assert!(!ignore.is_ignored(&res_dir.join("values/colors.xml.orig"), false));
+
+        let ignore = ResourceIgnore::load(&res_dir, true)?;
+        assert!(ignore.is_ignored(&res_dir.join("values/colors.xml.orig"), false));
+        assert!(ignore.is_ignored(&res_dir.join("drawable/build"), true));
+        assert!(ignore.is_ignored(&res_dir.join("drawable/build/x.png"), false));
+
+        // Rules only apply below the resource directory
+        let build_res = repo.join("build").join("res");
+        fs::create_dir_all(&build_res)?;
+        let ignore = ResourceIgnore::load(&build_res, true)?;
+        assert!(!ignore.is_ignored(&build_res.join("drawable/x.png"), false));
+        Ok(())
+    }
+}
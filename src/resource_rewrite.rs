@@ -0,0 +1,641 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use walkdir::WalkDir;
+
+/// A resource renamed by the prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenamedResource {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Number of references rewritten in one source file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileRewrite {
+    pub file: PathBuf,
+    pub references: usize,
+}
+
+/// Rewrite audit file extension, written next to the package
+pub const REWRITE_AUDIT_EXTENSION: &str = "rewrite-audit.json";
+
+/// Audit of a rewrite pass
+#[derive(Debug, Default, Serialize)]
+pub struct RewriteAudit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    pub renamed: Vec<RenamedResource>,
+    pub rewritten: Vec<FileRewrite>,
+}
+
+/// Rewrites applied to the skin's own resources before compiling
+#[derive(Debug, Default)]
+pub struct RewriteRules {
+    /// Prefix for every resource name the skin defines
+    pub prefix: Option<String>,
+    /// Whole-reference replacements, e.g. "@drawable/old_name" -> "@drawable/new_name"
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl RewriteRules {
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_none() && self.aliases.is_empty()
+    }
+}
+
+/// Resource directory handed to the rewrite pass: source root, its resource files, and the
+/// directory the rewritten copy is written to
+pub type RewriteInput = (PathBuf, Vec<PathBuf>, PathBuf);
+
+/// Check that `prefix` can start a file-based resource name
+pub fn validate_prefix(prefix: &str) -> Result<()> {
+    let mut chars = prefix.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "Invalid resourcePrefix \"{}\": use lowercase letters, digits and underscores, starting with a letter",
+            prefix
+        );
+    }
+    Ok(())
+}
+
+/// Check that an alias maps a resource reference to another one
+pub fn validate_alias(from: &str, to: &str) -> Result<()> {
+    for reference in [from, to] {
+        let target = reference
+            .strip_prefix('@')
+            .or_else(|| reference.strip_prefix('?'));
+        let valid = target
+            .and_then(|t| t.split_once('/'))
+            .is_some_and(|(resource_type, name)| {
+                !resource_type.is_empty()
+                    && !name.is_empty()
+                    && !reference.contains(char::is_whitespace)
+            });
+        if !valid {
+            anyhow::bail!(
+                "Invalid alias \"{}\" -> \"{}\": both sides must be references like @drawable/name",
+                from,
+                to
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Rules plus the resources defined by the inputs, as (type, name)
+struct Rewriter<'a> {
+    rules: &'a RewriteRules,
+    names: HashSet<(String, String)>,
+}
+
+impl Rewriter<'_> {
+    /// Prefixed name for a resource defined by the inputs; None without a prefix, for
+    /// resources defined elsewhere (host, framework, libraries) and for names that already
+    /// carry the prefix
+    fn rename(&self, resource_type: &str, name: &str) -> Option<String> {
+        let prefix = self.rules.prefix.as_deref()?;
+        if name.starts_with(prefix)
+            || !self
+                .names
+                .contains(&(resource_type.to_string(), name.to_string()))
+        {
+            return None;
+        }
+        Some(format!("{}{}", prefix, name))
+    }
+
+    /// Apply aliases, then the prefix, to a reference
+    fn rewrite_reference(&self, value: &str) -> Option<String> {
+        match self.rules.aliases.get(value) {
+            Some(aliased) => Some(
+                self.prefix_reference(aliased)
+                    .unwrap_or_else(|| aliased.clone()),
+            ),
+            None => self.prefix_reference(value),
+        }
+    }
+
+    /// Prefix a `@type/name`, `@+id/name`, `?attr/name` or `?name` reference
+    fn prefix_reference(&self, value: &str) -> Option<String> {
+        let (marker, target) = if let Some(rest) = value.strip_prefix("@+") {
+            ("@+", rest)
+        } else if let Some(rest) = value.strip_prefix('@') {
+            ("@", rest)
+        } else {
+            ("?", value.strip_prefix('?')?)
+        };
+        // Package-qualified references (android:, other packages) are never ours
+        if target.contains(':') {
+            return None;
+        }
+        let (resource_type, name) = match target.split_once('/') {
+            Some(parts) => parts,
+            None if marker == "?" => ("attr", target),
+            None => return None,
+        };
+        let renamed = self.rename(resource_type, name)?;
+        Some(match target.split_once('/') {
+            Some(_) => format!("{}{}/{}", marker, resource_type, renamed),
+            None => format!("?{}", renamed),
+        })
+    }
+}
+
+/// Resource type of a directory below res/, e.g. "drawable-hdpi" -> "drawable"
+fn resource_type(res_dir: &Path, file: &Path) -> Option<String> {
+    let dir = file.strip_prefix(res_dir).ok()?.components().next()?;
+    let dir = dir.as_os_str().to_str()?;
+    Some(dir.split('-').next().unwrap_or(dir).to_string())
+}
+
+/// Resource name of a file-based resource, e.g. "btn.9.png" -> "btn"
+fn file_resource_name(file: &Path) -> Option<&str> {
+    let name = file.file_name()?.to_str()?;
+    Some(name.split('.').next().unwrap_or(name))
+}
+
+/// Resource type declared by a top-level element of a values file
+fn values_element_type(node: roxmltree::Node) -> Option<String> {
+    Some(match node.tag_name().name() {
+        "item" => node.attribute("type")?.to_string(),
+        "string-array" | "integer-array" | "array" => "array".to_string(),
+        "declare-styleable" => "styleable".to_string(),
+        "eat-comment" | "skip" | "public" => return None,
+        tag => tag.to_string(),
+    })
+}
+
+/// Add the resources `file` (below `res_dir`) defines to `names`, as (type, name)
+///
+/// `content` is the text of XML files; other files only define their file-based resource.
+pub(crate) fn collect_definitions(
+    res_dir: &Path,
+    file: &Path,
+    content: Option<&str>,
+    names: &mut HashSet<(String, String)>,
+) -> Result<()> {
+    let Some(resource_type) = resource_type(res_dir, file) else {
+        return Ok(());
+    };
+    if let Some(content) = content {
+        let mut rest = content;
+        while let Some(at) = rest.find("@+id/") {
+            rest = &rest[at + 5..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            names.insert(("id".to_string(), rest[..end].to_string()));
+        }
+    }
+    if resource_type != "values" {
+        if let Some(name) = file_resource_name(file) {
+            names.insert((resource_type, name.to_string()));
+        }
+        return Ok(());
+    }
+    let Some(content) = content else {
+        return Ok(());
+    };
+    let document = roxmltree::Document::parse(content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    for node in document
+        .root_element()
+        .children()
+        .filter(|n| n.is_element())
+    {
+        if let (Some(element_type), Some(name)) =
+            (values_element_type(node), node.attribute("name"))
+        {
+            names.insert((element_type, name.to_string()));
+        }
+        if node.tag_name().name() == "declare-styleable" {
+            for attr in node.children().filter(|n| n.has_tag_name("attr")) {
+                if let Some(name) = attr.attribute("name").filter(|n| !n.contains(':')) {
+                    names.insert(("attr".to_string(), name.to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_xml(file: &Path) -> bool {
+    file.extension().and_then(|e| e.to_str()) == Some("xml")
+}
+
+/// Copy resource directories with aliases and the prefix applied
+///
+/// Aliases replace whole references in XML attributes and element text, so skins can follow
+/// resources the host renamed. With a prefix, file-based resources are renamed, values
+/// entries, styleable attrs and `@+id` ids get the prefix, and references to any of them are
+/// rewritten. References to resources not defined by the inputs are left untouched, as are
+/// names that already start with the prefix. Output files are only rewritten when their
+/// content changes so the incremental cache keeps working.
+pub fn rewrite_resources(inputs: &[RewriteInput], rules: &RewriteRules) -> Result<RewriteAudit> {
+    if let Some(prefix) = &rules.prefix {
+        validate_prefix(prefix)?;
+    }
+    for (from, to) in &rules.aliases {
+        validate_alias(from, to)?;
+    }
+
+    let mut sources: Vec<(&Path, &Path, &Path, Option<String>)> = Vec::new();
+    for (res_dir, files, output_dir) in inputs {
+        for file in files {
+            let content = if is_xml(file) {
+                Some(
+                    fs::read_to_string(file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?,
+                )
+            } else {
+                None
+            };
+            sources.push((res_dir, file, output_dir, content));
+        }
+    }
+
+    // Collect everything the inputs define
+    let mut names: HashSet<(String, String)> = HashSet::new();
+    for (res_dir, file, _, content) in &sources {
+        collect_definitions(res_dir, file, content.as_deref(), &mut names)?;
+    }
+    let rewriter = Rewriter { rules, names };
+
+    let mut audit = RewriteAudit {
+        prefix: rules.prefix.clone(),
+        ..Default::default()
+    };
+    let mut outputs: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    for (res_dir, file, output_dir, content) in &sources {
+        let resource_type = resource_type(res_dir, file).unwrap_or_default();
+        let relative = file.strip_prefix(res_dir)?;
+        let mut output = output_dir.join(relative);
+        if resource_type != "values"
+            && let Some(name) = file_resource_name(file)
+            && let Some(new_name) = rewriter.rename(&resource_type, name)
+        {
+            let file_name = file.file_name().and_then(|n| n.to_str()).unwrap_or(name);
+            output.set_file_name(format!("{}{}", new_name, &file_name[name.len()..]));
+        }
+
+        let bytes = match content {
+            Some(content) => {
+                let (edits, references) =
+                    rewrite_xml(content, resource_type == "values", &rewriter)
+                        .with_context(|| format!("Failed to parse {}", file.display()))?;
+                if references > 0 {
+                    audit.rewritten.push(FileRewrite {
+                        file: file.to_path_buf(),
+                        references,
+                    });
+                }
+                let mut content = content.clone();
+                for (range, text) in edits.into_iter().rev() {
+                    content.replace_range(range, &text);
+                }
+                content.into_bytes()
+            }
+            None => fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?,
+        };
+        outputs.push((output, bytes));
+    }
+
+    let mut renamed: Vec<RenamedResource> = rewriter
+        .names
+        .iter()
+        .filter_map(|(resource_type, name)| {
+            Some(RenamedResource {
+                to: rewriter.rename(resource_type, name)?,
+                resource_type: resource_type.clone(),
+                from: name.clone(),
+            })
+        })
+        .collect();
+    renamed.sort_by(|a, b| (&a.resource_type, &a.from).cmp(&(&b.resource_type, &b.from)));
+    audit.renamed = renamed;
+
+    let output_dirs: Vec<&Path> = inputs.iter().map(|(_, _, dir)| dir.as_path()).collect();
+    write_outputs(&output_dirs, &outputs)?;
+    Ok(audit)
+}
+
+/// Edits (sorted by position) that rewrite definitions and references in one XML file, plus
+/// the number of rewritten references
+fn rewrite_xml(
+    content: &str,
+    is_values: bool,
+    rewriter: &Rewriter,
+) -> Result<(Vec<(Range<usize>, String)>, usize)> {
+    let document = roxmltree::Document::parse(content)?;
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut references = 0;
+
+    for node in document.descendants() {
+        if node.is_text() {
+            let raw = &content[node.range()];
+            let trimmed = raw.trim();
+            if let Some(new_value) = rewriter.rewrite_reference(trimmed) {
+                let start = node.range().start + (raw.len() - raw.trim_start().len());
+                edits.push((start..start + trimmed.len(), new_value));
+                references += 1;
+            }
+            continue;
+        }
+        if !node.is_element() {
+            continue;
+        }
+
+        let parent_tag = node
+            .parent_element()
+            .map(|p| p.tag_name().name())
+            .unwrap_or_default();
+        let top_level = node.parent_element() == Some(document.root_element());
+        for attribute in node.attributes() {
+            let range = attribute.range_value();
+            // Values containing entities can't be matched against the raw text
+            if content[range.clone()] != *attribute.value() {
+                continue;
+            }
+            let value = attribute.value();
+            let is_name = attribute.name() == "name" && attribute.namespace().is_none();
+
+            // Definitions in values files
+            if is_values && is_name {
+                let defined_type = if top_level {
+                    values_element_type(node)
+                } else if parent_tag == "declare-styleable" && node.has_tag_name("attr") {
+                    Some("attr".to_string())
+                } else {
+                    None
+                };
+                if let Some(defined_type) = defined_type {
+                    if let Some(new_name) = rewriter.rename(&defined_type, value) {
+                        edits.push((range, new_name));
+                    }
+                    continue;
+                }
+                // Style items name the attribute they set
+                if parent_tag == "style" && node.has_tag_name("item") {
+                    if let Some(new_name) = rewriter.rename("attr", value) {
+                        edits.push((range, new_name));
+                        references += 1;
+                    }
+                    continue;
+                }
+            }
+
+            // Bare style parents, e.g. parent="Theme.Skin"
+            let new_value = if is_values
+                && node.has_tag_name("style")
+                && attribute.name() == "parent"
+                && !value.starts_with('@')
+            {
+                rewriter.rename("style", value)
+            } else {
+                rewriter.rewrite_reference(value)
+            };
+            if let Some(new_value) = new_value {
+                edits.push((range, new_value));
+                references += 1;
+            }
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    Ok((edits, references))
+}
+
+/// Write outputs that changed and remove files left over from earlier runs
+fn write_outputs(output_dirs: &[&Path], outputs: &[(PathBuf, Vec<u8>)]) -> Result<()> {
+    for (path, bytes) in outputs {
+        if fs::read(path).ok().as_deref() != Some(bytes.as_slice()) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, bytes)?;
+            debug!("Rewrote {}", path.display());
+        }
+    }
+
+    let expected: HashSet<&Path> = outputs.iter().map(|(path, _)| path.as_path()).collect();
+    for output_dir in output_dirs {
+        for entry in WalkDir::new(output_dir).into_iter().flatten() {
+            if entry.file_type().is_file() && !expected.contains(entry.path()) {
+                fs::remove_file(entry.path())?;
+                debug!("Removed stale {}", entry.path().display());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_validate_prefix() {
+        assert!(validate_prefix("brandx_").is_ok());
+        assert!(validate_prefix("b2").is_ok());
+        assert!(validate_prefix("").is_err());
+        assert!(validate_prefix("_brand").is_err());
+        assert!(validate_prefix("Brand_").is_err());
+        assert!(validate_prefix("brand-x").is_err());
+    }
+
+    #[test]
+    fn test_prefix_resources() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let output_dir = temp_dir.path().join("prefixed");
+        write(
+            &res_dir.join("values").join("colors.xml"),
+            r#"<resources>
+    <color name="primary">#6200EE</color>
+    <color name="accent">@color/primary</color>
+    <color name="brandx_done">#000000</color>
+    <color name="host">@color/host_color</color>
+    <item name="spacer" type="id"/>
+</resources>"#,
+        );
+        write(
+            &res_dir.join("drawable").join("bg.xml"),
+            r#"<layer-list xmlns:android="http://schemas.android.com/apk/res/android">
+    <item android:id="@+id/layer" android:drawable="@drawable/icon"/>
+    <item android:drawable="@android:drawable/btn_default">
+        <shape><solid android:color="@color/accent"/></shape>
+    </item>
+    <item android:drawable="@drawable/host_icon" android:top="?spacing"/>
+</layer-list>"#,
+        );
+        write(&res_dir.join("drawable-hdpi").join("icon.9.png"), "png");
+        write(
+            &res_dir.join("drawable-hdpi").join("brandx_logo.png"),
+            "png",
+        );
+
+        let files = vec![
+            res_dir.join("values").join("colors.xml"),
+            res_dir.join("drawable").join("bg.xml"),
+            res_dir.join("drawable-hdpi").join("icon.9.png"),
+            res_dir.join("drawable-hdpi").join("brandx_logo.png"),
+        ];
+        let rules = RewriteRules {
+            prefix: Some("brandx_".to_string()),
+            ..Default::default()
+        };
+        let audit = rewrite_resources(
+            &[(res_dir.clone(), files.clone(), output_dir.clone())],
+            &rules,
+        )?;
+
+        let colors = fs::read_to_string(output_dir.join("values").join("colors.xml"))?;
+        assert!(colors.contains(r#"<color name="brandx_primary">#6200EE</color>"#));
+        assert!(colors.contains(r#"<color name="brandx_accent">@color/brandx_primary</color>"#));
+        assert!(colors.contains(r#"<color name="brandx_done">"#));
+        assert!(colors.contains(r#"<color name="brandx_host">@color/host_color</color>"#));
+        assert!(colors.contains(r#"<item name="brandx_spacer" type="id"/>"#));
+
+        let drawable = fs::read_to_string(output_dir.join("drawable").join("brandx_bg.xml"))?;
+        assert!(drawable.contains(r#"android:id="@+id/brandx_layer""#));
+        assert!(drawable.contains(r#"android:drawable="@drawable/brandx_icon""#));
+        assert!(drawable.contains(r#"android:drawable="@android:drawable/btn_default""#));
+        assert!(drawable.contains(r#"android:color="@color/brandx_accent""#));
+        assert!(drawable.contains(r#"android:drawable="@drawable/host_icon""#));
+        assert!(drawable.contains(r#"android:top="?spacing""#));
+
+        let hdpi = output_dir.join("drawable-hdpi");
+        assert!(hdpi.join("brandx_icon.9.png").exists());
+        assert!(hdpi.join("brandx_logo.png").exists());
+        assert!(!hdpi.join("icon.9.png").exists());
+
+        let renamed: Vec<(&str, &str)> = audit
+            .renamed
+            .iter()
+            .map(|r| (r.resource_type.as_str(), r.from.as_str()))
+            .collect();
+        assert_eq!(
+            renamed,
+            vec![
+                ("color", "accent"),
+                ("color", "host"),
+                ("color", "primary"),
+                ("drawable", "bg"),
+                ("drawable", "icon"),
+                ("id", "layer"),
+                ("id", "spacer"),
+            ]
+        );
+        assert_eq!(
+            audit.rewritten,
+            vec![
+                FileRewrite {
+                    file: files[0].clone(),
+                    references: 1
+                },
+                FileRewrite {
+                    file: files[1].clone(),
+                    references: 3
+                },
+            ]
+        );
+
+        // Removing a source removes its prefixed copy
+        rewrite_resources(&[(res_dir, files[..2].to_vec(), output_dir)], &rules)?;
+        assert!(!hdpi.join("brandx_icon.9.png").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let output_dir = temp_dir.path().join("rewritten");
+        let selector = res_dir.join("drawable").join("selector.xml");
+        write(
+            &selector,
+            r#"<selector xmlns:android="http://schemas.android.com/apk/res/android">
+    <item android:drawable="@drawable/old_pressed" android:state_pressed="true"/>
+    <item android:drawable="@drawable/normal"/>
+</selector>"#,
+        );
+        write(&res_dir.join("drawable").join("normal.png"), "png");
+        let colors = res_dir.join("values").join("colors.xml");
+        write(
+            &colors,
+            r#"<resources><color name="text">@color/old_text</color></resources>"#,
+        );
+
+        let rules = RewriteRules {
+            prefix: None,
+            aliases: BTreeMap::from([
+                (
+                    "@drawable/old_pressed".to_string(),
+                    "@drawable/pressed".to_string(),
+                ),
+                (
+                    "@color/old_text".to_string(),
+                    "@color/text_primary".to_string(),
+                ),
+            ]),
+        };
+        let files = vec![
+            selector,
+            colors,
+            res_dir.join("drawable").join("normal.png"),
+        ];
+        let audit = rewrite_resources(
+            &[(res_dir.clone(), files.clone(), output_dir.clone())],
+            &rules,
+        )?;
+
+        let selector = fs::read_to_string(output_dir.join("drawable").join("selector.xml"))?;
+        assert!(selector.contains(r#"android:drawable="@drawable/pressed""#));
+        assert!(selector.contains(r#"android:drawable="@drawable/normal""#));
+        let colors = fs::read_to_string(output_dir.join("values").join("colors.xml"))?;
+        assert!(colors.contains(r#"<color name="text">@color/text_primary</color>"#));
+        assert!(output_dir.join("drawable").join("normal.png").exists());
+        assert!(audit.renamed.is_empty());
+        assert_eq!(audit.rewritten.len(), 2);
+
+        // Aliases are applied before the prefix, which only touches the skin's own resources
+        let rules = RewriteRules {
+            prefix: Some("skin_".to_string()),
+            aliases: BTreeMap::from([(
+                "@drawable/old_pressed".to_string(),
+                "@drawable/normal".to_string(),
+            )]),
+        };
+        rewrite_resources(&[(res_dir, files, output_dir.clone())], &rules)?;
+        let selector = fs::read_to_string(output_dir.join("drawable").join("skin_selector.xml"))?;
+        assert_eq!(selector.matches("@drawable/skin_normal").count(), 2);
+        let colors = fs::read_to_string(output_dir.join("values").join("colors.xml"))?;
+        assert!(colors.contains(r#"<color name="skin_text">@color/old_text</color>"#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_alias() {
+        assert!(validate_alias("@drawable/old", "@mipmap/new").is_ok());
+        assert!(validate_alias("?attr/old", "?attr/new").is_ok());
+        assert!(validate_alias("drawable/old", "@drawable/new").is_err());
+        assert!(validate_alias("@drawable/old", "@drawable/").is_err());
+        assert!(validate_alias("@null", "@drawable/new").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate};
+use std::path::{Path, PathBuf};
+
+use crate::owners::wildcard_match;
+use crate::types::ScheduledFiles;
+
+/// Format of `asOf`, `--as-of` and full window bounds
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Parse a `YYYY-MM-DD` date
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), DATE_FORMAT)
+        .with_context(|| format!("Invalid date '{}': expected YYYY-MM-DD", value))
+}
+
+/// The local date of today, as `YYYY-MM-DD`
+pub fn today() -> String {
+    chrono::Local::now()
+        .date_naive()
+        .format(DATE_FORMAT)
+        .to_string()
+}
+
+/// A window bound: a date, or a month and day repeating every year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Date(NaiveDate),
+    Yearly(u32, u32),
+}
+
+fn parse_bound(value: &str) -> Result<Bound> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, DATE_FORMAT) {
+        return Ok(Bound::Date(date));
+    }
+    // Validated against a leap year so that 02-29 is accepted
+    let yearly = format!("2000-{}", value);
+    match NaiveDate::parse_from_str(&yearly, DATE_FORMAT) {
+        Ok(date) if value.len() == 5 => Ok(Bound::Yearly(date.month(), date.day())),
+        _ => anyhow::bail!(
+            "Invalid date '{}': expected YYYY-MM-DD, or MM-DD for every year",
+            value
+        ),
+    }
+}
+
+/// Whether `as_of` lies in the window from `from` to `until`, both inclusive and optional
+///
+/// Bounds are `YYYY-MM-DD`, or `MM-DD` for a window that repeats every year; a yearly
+/// window may wrap around the new year (`12-20` to `01-06`). Both bounds of a window must
+/// use the same form.
+pub fn in_window(as_of: NaiveDate, from: Option<&str>, until: Option<&str>) -> Result<bool> {
+    let from = from.map(parse_bound).transpose()?;
+    let until = until.map(parse_bound).transpose()?;
+    let day = (as_of.month(), as_of.day());
+    Ok(match (from, until) {
+        (Some(Bound::Yearly(..)), Some(Bound::Date(_)))
+        | (Some(Bound::Date(_)), Some(Bound::Yearly(..))) => {
+            anyhow::bail!("Window bounds must both be dates or both be MM-DD")
+        }
+        (Some(Bound::Yearly(fm, fd)), Some(Bound::Yearly(um, ud))) => {
+            if (fm, fd) <= (um, ud) {
+                (fm, fd) <= day && day <= (um, ud)
+            } else {
+                (fm, fd) <= day || day <= (um, ud)
+            }
+        }
+        (from, until) => {
+            let after_start = match from {
+                Some(Bound::Date(date)) => as_of >= date,
+                Some(Bound::Yearly(m, d)) => day >= (m, d),
+                None => true,
+            };
+            let before_end = match until {
+                Some(Bound::Date(date)) => as_of <= date,
+                Some(Bound::Yearly(m, d)) => day <= (m, d),
+                None => true,
+            };
+            after_start && before_end
+        }
+    })
+}
+
+/// Drop the files of a resource directory matched by a `scheduledFiles` rule whose window
+/// does not contain `as_of`, returning the dropped files
+///
+/// Patterns are matched against the path below the resource directory, such as
+/// `drawable-xxhdpi/holiday_banner.png`; `*` and `?` are wildcards.
+pub fn exclude_unscheduled(
+    res_dir: &Path,
+    files: &mut Vec<PathBuf>,
+    rules: &[ScheduledFiles],
+    as_of: NaiveDate,
+) -> Result<Vec<PathBuf>> {
+    let mut active = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let included = in_window(as_of, rule.from.as_deref(), rule.until.as_deref())
+            .with_context(|| format!("In scheduledFiles {}", rule.pattern))?;
+        active.push((rule.pattern.as_str(), included));
+    }
+
+    let mut dropped = Vec::new();
+    files.retain(|file| {
+        let relative = file
+            .strip_prefix(res_dir)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let excluded = active
+            .iter()
+            .any(|(pattern, included)| !included && wildcard_match(pattern, &relative));
+        if excluded {
+            dropped.push(file.clone());
+        }
+        !excluded
+    });
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        parse_date(value).unwrap()
+    }
+
+    #[test]
+    fn test_in_window() -> Result<()> {
+        let christmas = date("2026-12-25");
+        assert!(in_window(
+            christmas,
+            Some("2026-12-01"),
+            Some("2026-12-31")
+        )?);
+        assert!(!in_window(christmas, Some("2027-12-01"), None)?);
+        assert!(in_window(christmas, None, Some("2026-12-25"))?);
+        assert!(in_window(christmas, Some("12-20"), Some("01-06"))?);
+        assert!(in_window(date("2027-01-03"), Some("12-20"), Some("01-06"))?);
+        assert!(!in_window(
+            date("2027-02-01"),
+            Some("12-20"),
+            Some("01-06")
+        )?);
+        assert!(in_window(date("2028-02-29"), Some("02-29"), Some("03-01"))?);
+        assert!(in_window(christmas, Some("12-01"), Some("2026-12-31")).is_err());
+        assert!(in_window(christmas, Some("December"), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_unscheduled() -> Result<()> {
+        let res_dir = Path::new("skin/res");
+        let mut files = vec![
+            res_dir.join("drawable/holiday_banner.png"),
+            res_dir.join("drawable/logo.png"),
+            res_dir.join("values/holiday_strings.xml"),
+        ];
+        let rules = vec![ScheduledFiles {
+            pattern: "*/holiday_*".to_string(),
+            from: Some("12-01".to_string()),
+            until: Some("12-31".to_string()),
+        }];
+
+        let mut december = files.clone();
+        assert!(
+            exclude_unscheduled(res_dir, &mut december, &rules, date("2026-12-24"))?.is_empty()
+        );
+        assert_eq!(december.len(), 3);
+
+        let dropped = exclude_unscheduled(res_dir, &mut files, &rules, date("2026-06-01"))?;
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(files, vec![res_dir.join("drawable/logo.png")]);
+        Ok(())
+    }
+}
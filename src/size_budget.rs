@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use crate::types::SizeBudget;
+
+/// Size in bytes as a short human-readable text, e.g. `1.5 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Warnings for the resource files larger than the budget's `maxFileBytes`
+pub fn check_files(budget: &SizeBudget, files: &[PathBuf]) -> Vec<String> {
+    let Some(max) = budget.max_file_bytes else {
+        return Vec::new();
+    };
+    files
+        .iter()
+        .filter_map(|file| {
+            let size = std::fs::metadata(file).ok()?.len();
+            (size > max).then(|| {
+                format!(
+                    "{} is {}, over the file size budget of {}",
+                    file.display(),
+                    format_bytes(size),
+                    format_bytes(max)
+                )
+            })
+        })
+        .collect()
+}
+
+/// Warning for a package larger than the budget's `maxPackageBytes`
+pub fn check_package(budget: &SizeBudget, package: &Path) -> Option<String> {
+    let max = budget.max_package_bytes?;
+    let size = std::fs::metadata(package).ok()?.len();
+    (size > max).then(|| {
+        format!(
+            "Package {} is {}, over the package size budget of {}",
+            package.display(),
+            format_bytes(size),
+            format_bytes(max)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_size_budget() {
+        let temp = TempDir::new().unwrap();
+        let small = temp.path().join("small.png");
+        let large = temp.path().join("large.png");
+        std::fs::write(&small, vec![0u8; 100]).unwrap();
+        std::fs::write(&large, vec![0u8; 3000]).unwrap();
+        let budget = SizeBudget {
+            max_package_bytes: Some(2048),
+            max_file_bytes: Some(1024),
+        };
+
+        let warnings = check_files(&budget, &[small.clone(), large.clone()]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("large.png is 2.9 KB, over the file size budget of 1.0 KB"));
+        assert!(check_package(&budget, &small).is_none());
+        assert!(check_package(&budget, &large).is_some());
+        assert!(check_files(&SizeBudget::default(), &[large]).is_empty());
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MB");
+    }
+}
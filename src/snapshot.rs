@@ -0,0 +1,301 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::aapt2::Aapt2;
+use crate::tokens::DEFAULT_CONFIGURATION;
+
+/// Format version of snapshot files
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Canonical manifest of a built skin package
+///
+/// Holds what the package resolves to rather than its bytes: resource values per
+/// configuration with resource IDs replaced by names, and hashes of the files under `res/`.
+/// The manifest and the metadata written by reproducible builds are left out, so
+/// snapshots only change when resources do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub version: u32,
+    pub package_name: String,
+    /// `type/name` to its value per configuration ("default" without qualifiers)
+    pub resources: BTreeMap<String, BTreeMap<String, String>>,
+    /// Package entry below `res/` to the SHA-256 of its content
+    pub files: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Snapshot a built package using `aapt2 dump resources`
+    pub fn capture(package: &Path, aapt2: &Aapt2) -> Result<Self> {
+        let dump = aapt2.dump_resources(package)?;
+        let (package_name, resources) = parse_dump(&dump);
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            package_name: package_name.unwrap_or_default(),
+            resources,
+            files: hash_resource_files(package)?,
+        })
+    }
+
+    /// Read a snapshot file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+        let snapshot: Snapshot = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid snapshot {}", path.display()))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            anyhow::bail!(
+                "Snapshot {} has format version {}, expected {}; record it again",
+                path.display(),
+                snapshot.version,
+                SNAPSHOT_VERSION
+            );
+        }
+        Ok(snapshot)
+    }
+
+    /// Write the snapshot as pretty JSON, creating parent directories
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let content = serde_json::to_string_pretty(self)? + "\n";
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write snapshot {}", path.display()))
+    }
+
+    /// Changes from this (recorded) snapshot to `actual`, one line each
+    ///
+    /// Lines start with `+` for added, `-` for removed and `~` for changed resources,
+    /// configurations and files; an empty list means the snapshots match.
+    pub fn diff(&self, actual: &Snapshot) -> Vec<String> {
+        let mut lines = Vec::new();
+        let names: BTreeSet<&String> = self
+            .resources
+            .keys()
+            .chain(actual.resources.keys())
+            .collect();
+        for name in names {
+            match (self.resources.get(name), actual.resources.get(name)) {
+                (Some(_), None) => lines.push(format!("- {}", name)),
+                (None, Some(values)) => {
+                    for (config, value) in values {
+                        lines.push(format!("+ {} ({}) {}", name, config, value));
+                    }
+                }
+                (Some(expected), Some(values)) => {
+                    let configs: BTreeSet<&String> = expected.keys().chain(values.keys()).collect();
+                    for config in configs {
+                        match (expected.get(config), values.get(config)) {
+                            (Some(_), None) => lines.push(format!("- {} ({})", name, config)),
+                            (None, Some(value)) => {
+                                lines.push(format!("+ {} ({}) {}", name, config, value))
+                            }
+                            (Some(old), Some(new)) if old != new => {
+                                lines.push(format!("~ {} ({}) {} -> {}", name, config, old, new))
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+
+        let files: BTreeSet<&String> = self.files.keys().chain(actual.files.keys()).collect();
+        for file in files {
+            match (self.files.get(file), actual.files.get(file)) {
+                (Some(_), None) => lines.push(format!("- {}", file)),
+                (None, Some(_)) => lines.push(format!("+ {}", file)),
+                (Some(old), Some(new)) if old != new => {
+                    lines.push(format!("~ {} content changed", file))
+                }
+                _ => {}
+            }
+        }
+        lines
+    }
+}
+
+/// Package name and resource values from the output of `aapt2 dump resources`
+///
+/// Each `resource 0x7f010000 type/name` line starts a resource, followed by one
+/// `(config) value` line per configuration; deeper lines such as style items and array
+/// elements are joined onto the value. References by ID are replaced by the name of the
+/// resource, since IDs shift whenever resources are added or removed.
+pub fn parse_dump(dump: &str) -> (Option<String>, BTreeMap<String, BTreeMap<String, String>>) {
+    let mut package_name = None;
+    let mut entries: Vec<(String, String, Vec<String>)> = Vec::new();
+    let mut ids = HashMap::new();
+    // Resource being read, its indent and the indent of its configuration lines
+    let mut current: Option<(String, usize, Option<usize>)> = None;
+
+    for line in dump.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let text = line.trim();
+        if let Some(rest) = text.strip_prefix("Package name=") {
+            package_name = rest.split_whitespace().next().map(str::to_string);
+        } else if let Some(rest) = text.strip_prefix("resource ") {
+            let mut parts = rest.split_whitespace();
+            current = match (parts.next(), parts.next()) {
+                (Some(id), Some(name)) => {
+                    ids.insert(id.to_string(), name.to_string());
+                    Some((name.to_string(), indent, None))
+                }
+                _ => None,
+            };
+        } else if let Some((name, resource_indent, value_indent)) = &mut current {
+            if indent <= *resource_indent {
+                current = None;
+                continue;
+            }
+            if value_indent.is_some_and(|value_indent| indent > value_indent) {
+                if let Some((_, _, values)) = entries.last_mut() {
+                    values.push(text.to_string());
+                }
+                continue;
+            }
+            *value_indent = Some(indent);
+            let (config, value) = match text.strip_prefix('(').and_then(|t| t.split_once(')')) {
+                Some(("", value)) => (DEFAULT_CONFIGURATION, value.trim()),
+                Some((config, value)) => (config, value.trim()),
+                None => (DEFAULT_CONFIGURATION, text),
+            };
+            entries.push((name.clone(), config.to_string(), vec![value.to_string()]));
+        }
+    }
+
+    let mut resources: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    for (name, config, values) in entries {
+        let value = values
+            .iter()
+            .map(|v| replace_ids(v, &ids))
+            .collect::<Vec<_>>()
+            .join("; ");
+        resources.entry(name).or_default().insert(config, value);
+    }
+    (package_name, resources)
+}
+
+/// Replace `0x7f010000` resource IDs with `type/name` where known
+fn replace_ids(value: &str, ids: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find("0x") {
+        out.push_str(&rest[..pos]);
+        let len = rest[pos + 2..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len() - pos - 2);
+        let id = &rest[pos..pos + 2 + len];
+        match ids.get(id) {
+            Some(name) => out.push_str(name),
+            None => out.push_str(id),
+        }
+        rest = &rest[pos + 2 + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// SHA-256 of every file below `res/` in a package
+fn hash_resource_files(package: &Path) -> Result<BTreeMap<String, String>> {
+    use sha2::{Digest, Sha256};
+
+    let file =
+        File::open(package).with_context(|| format!("Failed to open {}", package.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read package as ZIP: {}", package.display()))?;
+    let mut files = BTreeMap::new();
+    for idx in 0..archive.len() {
+        let mut entry = archive.by_index(idx)?;
+        if entry.is_dir() || !entry.name().starts_with("res/") {
+            continue;
+        }
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).with_context(|| {
+            format!("Failed to read {} from {}", entry.name(), package.display())
+        })?;
+        files.insert(
+            entry.name().to_string(),
+            format!("{:x}", Sha256::digest(&content)),
+        );
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "\
+Binary APK
+Package name=com.test id=7f
+  type color id=01 entryCount=2
+    resource 0x7f010000 color/primary
+      () #ff6200ee
+      (night) #ffbb86fc
+    resource 0x7f010001 color/accent
+      () @0x7f010000
+  type style id=02 entryCount=1
+    resource 0x7f020000 style/Theme.Skin PUBLIC
+      () (style) parent=@android:style/Theme.Material
+        colorAccent(0x7f030000)=@0x7f010001
+        android:textColor(0x01010098)=#ff000000
+";
+
+    #[test]
+    fn test_parse_dump_names_references() {
+        let (package_name, resources) = parse_dump(DUMP);
+        assert_eq!(package_name.as_deref(), Some("com.test"));
+        assert_eq!(resources["color/primary"]["default"], "#ff6200ee");
+        assert_eq!(resources["color/primary"]["night"], "#ffbb86fc");
+        assert_eq!(resources["color/accent"]["default"], "@color/primary");
+        assert_eq!(
+            resources["style/Theme.Skin"]["default"],
+            "(style) parent=@android:style/Theme.Material; \
+             colorAccent(0x7f030000)=@color/accent; android:textColor(0x01010098)=#ff000000"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let (_, resources) = parse_dump(DUMP);
+        let recorded = Snapshot {
+            version: SNAPSHOT_VERSION,
+            package_name: "com.test".to_string(),
+            resources,
+            files: [
+                ("res/drawable/bg.xml".to_string(), "aa".to_string()),
+                ("res/drawable/old.png".to_string(), "bb".to_string()),
+            ]
+            .into(),
+        };
+        assert!(recorded.diff(&recorded).is_empty());
+
+        let mut actual = recorded.clone();
+        actual.resources.remove("color/accent");
+        let primary = actual.resources.get_mut("color/primary").unwrap();
+        primary.insert("night".to_string(), "#ff000000".to_string());
+        primary.insert("land".to_string(), "#ffffffff".to_string());
+        actual
+            .files
+            .insert("res/drawable/bg.xml".to_string(), "cc".to_string());
+        actual.files.remove("res/drawable/old.png");
+
+        assert_eq!(
+            recorded.diff(&actual),
+            vec![
+                "- color/accent",
+                "+ color/primary (land) #ffffffff",
+                "~ color/primary (night) #ffbb86fc -> #ff000000",
+                "~ res/drawable/bg.xml content changed",
+                "- res/drawable/old.png",
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use walkdir::WalkDir;
+
+use crate::cache::link_or_copy;
+
+/// Staged copy of one source set, by staged file with the source file it was copied from
+pub type StagedFiles = HashMap<PathBuf, PathBuf>;
+
+/// Staged files whose line endings or permissions differ from their source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationStats {
+    /// XML files staged with LF instead of CRLF line endings
+    pub line_endings: usize,
+    /// Files staged without the executable bits of their source
+    pub permissions: usize,
+}
+
+/// Materialize the discovered files of a resource directory in `staging_dir`
+///
+/// Files keep their path relative to the resource directory and are hard-linked where
+/// possible. Staged files are only replaced when their source changed, and files left over
+/// from earlier builds are removed, so the staging tree mirrors the discovered files. Passes
+/// that rewrite resources must write new files instead of modifying staged ones in place,
+/// as those may share their content with the source tree.
+pub fn stage_resources(
+    res_dir: &Path,
+    files: &[PathBuf],
+    staging_dir: &Path,
+) -> Result<StagedFiles> {
+    let mut staged = StagedFiles::new();
+    for file in files {
+        let Ok(relative) = file.strip_prefix(res_dir) else {
+            continue;
+        };
+        let target = staging_dir.join(relative);
+        if !is_current(file, &target) {
+            debug!("Staging {}", file.display());
+            link_or_copy(file, &target)?;
+        }
+        staged.insert(target, file.clone());
+    }
+
+    let stale: Vec<PathBuf> = WalkDir::new(staging_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir() && !staged.contains_key(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect();
+    for file in stale {
+        debug!("Removing stale staged file {}", file.display());
+        fs::remove_file(&file)?;
+    }
+    Ok(staged)
+}
+
+/// Stage UTF-8 XML files with LF line endings and all files without executable bits, so
+/// the staging tree is the same whatever OS or checkout settings the sources come from
+///
+/// Files that need it are replaced by normalized copies; the others stay linked to their
+/// source.
+pub fn normalize_staged_files(staged: &StagedFiles) -> Result<NormalizationStats> {
+    let mut stats = NormalizationStats::default();
+    let mut files: Vec<(&PathBuf, &PathBuf)> = staged.iter().collect();
+    files.sort();
+    for (file, source) in files {
+        let mut content = None;
+        if file.extension().is_some_and(|ext| ext == "xml") {
+            let bytes = fs::read(file)?;
+            // Other encodings may hold the CRLF bytes inside a character
+            if bytes.windows(2).any(|pair| pair == b"\r\n") && std::str::from_utf8(&bytes).is_ok() {
+                content = Some(String::from_utf8(bytes)?.replace("\r\n", "\n").into_bytes());
+                stats.line_endings += 1;
+            }
+        }
+        // Copies normalized by an earlier build are kept while their source is unchanged
+        if is_executable(source)? {
+            stats.permissions += 1;
+        }
+        let executable = is_executable(file)?;
+        if content.is_none() && !executable {
+            continue;
+        }
+        debug!("Normalizing staged {}", file.display());
+        let content = match content {
+            Some(content) => content,
+            None => fs::read(file)?,
+        };
+        // Staged files may be hard links, which share content and permissions with the source
+        fs::remove_file(file)?;
+        fs::write(file, content).with_context(|| format!("Failed to write {}", file.display()))?;
+    }
+    Ok(stats)
+}
+
+#[cfg(unix)]
+fn is_executable(file: &Path) -> Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::metadata(file)?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_file: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// Whether a staged file still matches its source
+fn is_current(source: &Path, staged: &Path) -> bool {
+    let (Ok(source_meta), Ok(staged_meta)) = (fs::metadata(source), fs::metadata(staged)) else {
+        return false;
+    };
+    if source_meta.len() != staged_meta.len() {
+        return false;
+    }
+    // Hard links share their modification time; copies are compared by content
+    if source_meta.modified().ok() == staged_meta.modified().ok() {
+        return true;
+    }
+    fs::read(source).ok() == fs::read(staged).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stage_resources_tracks_source_changes() -> Result<()> {
+        let temp = TempDir::new()?;
+        let res_dir = temp.path().join("res");
+        let staging = temp.path().join("staging");
+        fs::create_dir_all(res_dir.join("drawable"))?;
+        let icon = res_dir.join("drawable").join("icon.png");
+        let logo = res_dir.join("drawable").join("logo.png");
+        fs::write(&icon, "icon")?;
+        fs::write(&logo, "logo")?;
+
+        let staged = stage_resources(&res_dir, &[icon.clone(), logo.clone()], &staging)?;
+        let staged_icon = staging.join("drawable").join("icon.png");
+        assert_eq!(staged.get(&staged_icon), Some(&icon));
+        assert_eq!(fs::read(&staged_icon)?, b"icon");
+
+        // A source replaced by an editor gets staged again; dropped files are removed
+        fs::remove_file(&icon)?;
+        fs::write(&icon, "new icon")?;
+        let staged = stage_resources(&res_dir, std::slice::from_ref(&icon), &staging)?;
+        assert_eq!(staged.len(), 1);
+        assert_eq!(fs::read(&staged_icon)?, b"new icon");
+        assert!(!staging.join("drawable").join("logo.png").exists());
+        assert_eq!(fs::read(&logo)?, b"logo");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_normalize_staged_files() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new()?;
+        let res_dir = temp.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("raw"))?;
+        let colors = res_dir.join("values").join("colors.xml");
+        let script = res_dir.join("raw").join("data.txt");
+        fs::write(&colors, "<resources>\r\n</resources>\r\n")?;
+        fs::write(&script, "a\r\nb")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+        let staging = temp.path().join("staging");
+        let staged = stage_resources(&res_dir, &[colors.clone(), script.clone()], &staging)?;
+        let stats = normalize_staged_files(&staged)?;
+        assert_eq!(
+            stats,
+            NormalizationStats {
+                line_endings: 1,
+                permissions: 1
+            }
+        );
+        let staged_script = staging.join("raw").join("data.txt");
+        assert_eq!(
+            fs::read_to_string(staging.join("values").join("colors.xml"))?,
+            "<resources>\n</resources>\n"
+        );
+        assert_eq!(fs::read_to_string(&staged_script)?, "a\r\nb");
+        assert_eq!(
+            fs::metadata(&staged_script)?.permissions().mode() & 0o111,
+            0
+        );
+        // Sources are untouched
+        assert_eq!(
+            fs::read_to_string(&colors)?,
+            "<resources>\r\n</resources>\r\n"
+        );
+        assert_eq!(fs::metadata(&script)?.permissions().mode() & 0o777, 0o755);
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::types::{BuildResult, UserDefaults};
+
+/// One `asb build` invocation, appended as a line of the local stats file
+///
+/// Records never leave the machine; they let build owners compare build habits over time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildRecord {
+    /// Start of the build, RFC 3339
+    pub timestamp: String,
+    pub asb_version: String,
+    pub duration_ms: u64,
+    /// Packages the invocation built
+    pub packages: usize,
+    /// Packages that failed to build
+    pub failed: usize,
+    /// Resource files reused from the incremental build cache
+    pub cached_files: usize,
+    /// Resource files compiled by aapt2
+    pub compiled_files: usize,
+    /// CPU cores of the machine
+    pub cores: usize,
+}
+
+impl BuildRecord {
+    /// Record an invocation that built `packages` packages, of which `results` finished
+    pub fn new(
+        started: chrono::DateTime<chrono::Local>,
+        duration: Duration,
+        packages: usize,
+        results: &[&BuildResult],
+    ) -> Self {
+        let finished_ok = results.iter().filter(|r| r.success).count();
+        Self {
+            timestamp: started.to_rfc3339(),
+            asb_version: env!("CARGO_PKG_VERSION").to_string(),
+            duration_ms: duration.as_millis() as u64,
+            packages,
+            failed: packages.saturating_sub(finished_ok),
+            cached_files: results.iter().map(|r| r.cached_files).sum(),
+            compiled_files: results.iter().map(|r| r.compiled_files).sum(),
+            cores: num_cpus::get(),
+        }
+    }
+
+    /// Append the record as one NDJSON line to `path`
+    pub fn append(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open stats file {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Resolve the stats file: `explicit` (`--stats-file` / `ASB_STATS_FILE`), else the
+/// `statsFile` of the user defaults; `None` means statistics are disabled
+pub fn stats_file(explicit: Option<PathBuf>) -> Result<Option<PathBuf>> {
+    if explicit.is_some() {
+        return Ok(explicit);
+    }
+    let defaults = match UserDefaults::path() {
+        Some(path) => UserDefaults::load(&path)?,
+        None => return Ok(None),
+    };
+    defaults
+        .stats_file
+        .map(|path| {
+            let expanded = shellexpand::full(&path.to_string_lossy())
+                .with_context(|| format!("Invalid statsFile {}", path.display()))?
+                .into_owned();
+            Ok(PathBuf::from(expanded))
+        })
+        .transpose()
+}
+
+/// Read every record of the stats file at `path`, skipping lines that do not parse
+pub fn read_records(path: &Path) -> Result<Vec<BuildRecord>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read stats file {}", path.display()))?;
+    let mut records = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Aggregate view of a set of build records
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsSummary {
+    pub builds: usize,
+    /// Builds in which at least one package failed
+    pub failed_builds: usize,
+    pub packages: usize,
+    pub first_build: String,
+    pub last_build: String,
+    pub mean_duration_ms: u64,
+    pub median_duration_ms: u64,
+    pub p90_duration_ms: u64,
+    /// Share of resource files reused from the cache, when any file was considered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hit_ratio: Option<f64>,
+    /// Core counts of the machines that built, ascending
+    pub cores: Vec<usize>,
+}
+
+/// Summarize `records`; `None` when there are none
+pub fn summarize(records: &[BuildRecord]) -> Option<StatsSummary> {
+    let first = records.first()?;
+    let last = records.last()?;
+    let mut durations: Vec<u64> = records.iter().map(|r| r.duration_ms).collect();
+    durations.sort_unstable();
+    let percentile = |p: usize| durations[(durations.len() - 1) * p / 100];
+    let cached: usize = records.iter().map(|r| r.cached_files).sum();
+    let compiled: usize = records.iter().map(|r| r.compiled_files).sum();
+    let mut cores: Vec<usize> = records.iter().map(|r| r.cores).collect();
+    cores.sort_unstable();
+    cores.dedup();
+    Some(StatsSummary {
+        builds: records.len(),
+        failed_builds: records.iter().filter(|r| r.failed > 0).count(),
+        packages: records.iter().map(|r| r.packages).sum(),
+        first_build: first.timestamp.clone(),
+        last_build: last.timestamp.clone(),
+        mean_duration_ms: durations.iter().sum::<u64>() / durations.len() as u64,
+        median_duration_ms: percentile(50),
+        p90_duration_ms: percentile(90),
+        cache_hit_ratio: (cached + compiled > 0)
+            .then(|| cached as f64 / (cached + compiled) as f64),
+        cores,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(duration_ms: u64, failed: usize, cached_files: usize) -> BuildRecord {
+        BuildRecord {
+            timestamp: format!("2024-01-01T00:00:{:02}+00:00", duration_ms / 100),
+            asb_version: "1.0.0".to_string(),
+            duration_ms,
+            packages: 2,
+            failed,
+            cached_files,
+            compiled_files: 10 - cached_files,
+            cores: 8,
+        }
+    }
+
+    #[test]
+    fn test_append_and_summarize_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("stats/builds.ndjson");
+        for r in [record(100, 0, 0), record(300, 1, 5), record(200, 0, 10)] {
+            r.append(&path).unwrap();
+        }
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.push_str("not json\n");
+        std::fs::write(&path, content).unwrap();
+
+        let records = read_records(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        let summary = summarize(&records).unwrap();
+        assert_eq!(summary.builds, 3);
+        assert_eq!(summary.failed_builds, 1);
+        assert_eq!(summary.packages, 6);
+        assert_eq!(summary.mean_duration_ms, 200);
+        assert_eq!(summary.median_duration_ms, 200);
+        assert_eq!(summary.p90_duration_ms, 200);
+        assert_eq!(summary.cache_hit_ratio, Some(0.5));
+        assert_eq!(summary.first_build, records[0].timestamp);
+        assert_eq!(summary.cores, vec![8]);
+        assert!(summarize(&[]).is_none());
+    }
+}
@@ -0,0 +1,838 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::manifest::escape_attr;
+
+/// Separates a plurals name from an item quantity in translation keys, e.g. "songs#one"
+const QUANTITY_SEPARATOR: char = '#';
+
+/// Three-letter UI mode and display qualifiers that look like language codes
+const NON_LOCALE_QUALIFIERS: &[&str] = &["car", "hdr"];
+
+/// Translation file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StringsFormat {
+    /// XLIFF 1.2 (.xlf)
+    Xliff,
+    /// CSV with `resDir,key,source,<locale>` columns (.csv)
+    Csv,
+}
+
+impl StringsFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Xliff => "xlf",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("xlf" | "xliff") => Ok(Self::Xliff),
+            Some("csv") => Ok(Self::Csv),
+            _ => anyhow::bail!(
+                "Unsupported translation file (expected .xlf, .xliff or .csv): {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// A translatable string or plural item
+/// Values are kept exactly as written in the XML, including Android escapes and markup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationUnit {
+    /// Resource directory the value belongs to
+    pub res_dir: PathBuf,
+    /// `name` for strings, `name#quantity` for plural items
+    pub key: String,
+    /// Value in the default `values` directory
+    pub source: String,
+    /// Value in the locale's `values-<locale>` directory, if translated
+    pub target: Option<String>,
+}
+
+/// All translation units of one locale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationFile {
+    /// Locale qualifier, e.g. "fr", "zh-rCN" or "b+sr+Latn"
+    pub locale: String,
+    pub units: Vec<TranslationUnit>,
+}
+
+impl TranslationFile {
+    /// Write the file in the given format
+    pub fn write(&self, format: StringsFormat, source_language: &str, path: &Path) -> Result<()> {
+        match format {
+            StringsFormat::Xliff => self.write_xliff(source_language, path),
+            StringsFormat::Csv => self.write_csv(path),
+        }
+        .with_context(|| format!("Failed to write translation file {}", path.display()))
+    }
+
+    /// Read a translation file, detecting the format from its extension
+    pub fn read(path: &Path) -> Result<Self> {
+        match StringsFormat::from_path(path)? {
+            StringsFormat::Xliff => Self::read_xliff(path),
+            StringsFormat::Csv => Self::read_csv(path),
+        }
+        .with_context(|| format!("Failed to read translation file {}", path.display()))
+    }
+
+    fn write_xliff(&self, source_language: &str, path: &Path) -> Result<()> {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n",
+        );
+        for (res_dir, units) in group_by_res_dir(&self.units) {
+            xml.push_str(&format!(
+                "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"x-android-res\">\n    <body>\n",
+                escape_attr(&res_dir.to_string_lossy()),
+                escape_attr(source_language),
+                escape_attr(&qualifier_to_language_tag(&self.locale)),
+            ));
+            for unit in units {
+                xml.push_str(&format!(
+                    "      <trans-unit id=\"{}\">\n        <source>{}</source>\n",
+                    escape_attr(&unit.key),
+                    escape_attr(&unit.source)
+                ));
+                if let Some(target) = &unit.target {
+                    xml.push_str(&format!(
+                        "        <target>{}</target>\n",
+                        escape_attr(target)
+                    ));
+                }
+                xml.push_str("      </trans-unit>\n");
+            }
+            xml.push_str("    </body>\n  </file>\n");
+        }
+        xml.push_str("</xliff>\n");
+        fs::write(path, xml)?;
+        Ok(())
+    }
+
+    fn read_xliff(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let document = roxmltree::Document::parse(&content)?;
+
+        let mut locale: Option<String> = None;
+        let mut units = Vec::new();
+        for file in document
+            .root_element()
+            .children()
+            .filter(|n| n.tag_name().name() == "file")
+        {
+            let res_dir = file
+                .attribute("original")
+                .context("<file> is missing the original attribute")?;
+            let language_tag = file
+                .attribute("target-language")
+                .context("<file> is missing the target-language attribute")?;
+            let file_locale = language_tag_to_qualifier(language_tag)?;
+            match &locale {
+                Some(locale) if *locale != file_locale => anyhow::bail!(
+                    "Translation file mixes locales {} and {}",
+                    locale,
+                    file_locale
+                ),
+                Some(_) => {}
+                None => locale = Some(file_locale),
+            }
+
+            for unit in file
+                .descendants()
+                .filter(|n| n.tag_name().name() == "trans-unit")
+            {
+                let key = unit
+                    .attribute("id")
+                    .context("<trans-unit> is missing the id attribute")?;
+                let child_text = |name: &str| {
+                    unit.children()
+                        .find(|n| n.tag_name().name() == name)
+                        .map(element_text)
+                };
+                units.push(TranslationUnit {
+                    res_dir: PathBuf::from(res_dir),
+                    key: key.to_string(),
+                    source: child_text("source").unwrap_or_default(),
+                    target: child_text("target").filter(|t| !t.is_empty()),
+                });
+            }
+        }
+
+        Ok(Self {
+            locale: locale.context("Translation file contains no <file> elements")?,
+            units,
+        })
+    }
+
+    fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["resDir", "key", "source", self.locale.as_str()])?;
+        for unit in &self.units {
+            writer.write_record([
+                unit.res_dir.to_string_lossy().as_ref(),
+                &unit.key,
+                &unit.source,
+                unit.target.as_deref().unwrap_or_default(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn read_csv(path: &Path) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let locale = reader
+            .headers()?
+            .get(3)
+            .context("Expected resDir,key,source,<locale> columns")?
+            .to_string();
+        if !is_locale_qualifier(&locale) {
+            anyhow::bail!("Invalid locale qualifier in CSV header: {}", locale);
+        }
+
+        let mut units = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let field = |idx: usize| record.get(idx).unwrap_or_default().to_string();
+            units.push(TranslationUnit {
+                res_dir: PathBuf::from(field(0)),
+                key: field(1),
+                source: field(2),
+                target: Some(field(3)).filter(|t| !t.is_empty()),
+            });
+        }
+        Ok(Self { locale, units })
+    }
+}
+
+/// Locale qualifier of a values directory name, e.g. "values-zh-rCN" -> "zh-rCN"
+/// Directories with other qualifiers, like "values-night" or "values-fr-land", are not locale dirs
+pub fn locale_qualifier(dir_name: &str) -> Option<&str> {
+    let qualifier = dir_name.strip_prefix("values-")?;
+    is_locale_qualifier(qualifier).then_some(qualifier)
+}
+
+/// Whether `qualifier` is a locale qualifier on its own ("fr", "zh-rCN", "b+sr+Latn")
+pub fn is_locale_qualifier(qualifier: &str) -> bool {
+    if let Some(tag) = qualifier.strip_prefix("b+") {
+        return tag
+            .split('+')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    let mut parts = qualifier.split('-');
+    let language = parts.next().unwrap_or_default();
+    let language_ok = (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && !NON_LOCALE_QUALIFIERS.contains(&language);
+    let region_ok = parts.next().is_none_or(|region| {
+        region.strip_prefix('r').is_some_and(|r| {
+            (r.len() == 2 && r.chars().all(|c| c.is_ascii_uppercase()))
+                || (r.len() == 3 && r.chars().all(|c| c.is_ascii_digit()))
+        })
+    });
+    language_ok && region_ok && parts.next().is_none()
+}
+
+/// BCP 47 language tag of a locale qualifier: "zh-rCN" -> "zh-CN", "b+sr+Latn" -> "sr-Latn"
+pub fn qualifier_to_language_tag(qualifier: &str) -> String {
+    match qualifier.strip_prefix("b+") {
+        Some(tag) => tag.replace('+', "-"),
+        None => qualifier.replacen("-r", "-", 1),
+    }
+}
+
+/// Locale qualifier of a BCP 47 language tag: "zh-CN" -> "zh-rCN", "sr-Latn" -> "b+sr+Latn"
+pub fn language_tag_to_qualifier(tag: &str) -> Result<String> {
+    let parts: Vec<&str> = tag.split(['-', '_']).collect();
+    let is_region = |r: &str| {
+        (r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()))
+            || (r.len() == 3 && r.chars().all(|c| c.is_ascii_digit()))
+    };
+    let qualifier = match parts.as_slice() {
+        [language] => language.to_ascii_lowercase(),
+        [language, region] if is_region(region) => format!(
+            "{}-r{}",
+            language.to_ascii_lowercase(),
+            region.to_ascii_uppercase()
+        ),
+        _ => format!("b+{}", parts.join("+")),
+    };
+    if !is_locale_qualifier(&qualifier) {
+        anyhow::bail!("Unsupported language tag: {}", tag);
+    }
+    Ok(qualifier)
+}
+
+/// Collect string and plural resources of `res_dirs` for translation, one file per locale
+///
+/// With no `locales` given, every locale that already has a `values-<locale>` directory in
+/// one of the resource dirs is exported. Plural quantities that only exist in a locale (e.g.
+/// "few") are exported too, with the source's "other" value as their source text.
+pub fn collect_translations(
+    res_dirs: &[PathBuf],
+    locales: &[String],
+) -> Result<Vec<TranslationFile>> {
+    let mut locales = locales.to_vec();
+    if let Some(invalid) = locales.iter().find(|l| !is_locale_qualifier(l)) {
+        anyhow::bail!(
+            "Invalid locale qualifier: {} (expected e.g. \"fr\", \"zh-rCN\" or \"b+sr+Latn\")",
+            invalid
+        );
+    }
+    if locales.is_empty() {
+        let mut found = BTreeSet::new();
+        for res_dir in res_dirs {
+            for entry in fs::read_dir(res_dir)
+                .with_context(|| format!("Failed to read {}", res_dir.display()))?
+            {
+                let path = entry?.path();
+                if let Some(locale) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(locale_qualifier)
+                    && path.is_dir()
+                {
+                    found.insert(locale.to_string());
+                }
+            }
+        }
+        locales = found.into_iter().collect();
+    }
+
+    let mut sources = Vec::new();
+    for res_dir in res_dirs {
+        sources.push((res_dir, read_values_dir(&res_dir.join("values"))?));
+    }
+
+    let mut files = Vec::new();
+    for locale in locales {
+        let mut units = Vec::new();
+        for (res_dir, source) in &sources {
+            let translated = read_values_dir(&res_dir.join(format!("values-{}", locale)))?;
+            let targets: HashMap<&str, &str> = translated
+                .entries
+                .iter()
+                .map(|e| (e.key.as_str(), e.value.as_str()))
+                .collect();
+            let source_values: HashMap<&str, &str> = source
+                .entries
+                .iter()
+                .map(|e| (e.key.as_str(), e.value.as_str()))
+                .collect();
+
+            for entry in &source.entries {
+                units.push(TranslationUnit {
+                    res_dir: (*res_dir).clone(),
+                    key: entry.key.clone(),
+                    source: entry.value.clone(),
+                    target: targets
+                        .get(entry.key.as_str())
+                        .filter(|t| !t.is_empty())
+                        .map(|t| t.to_string()),
+                });
+            }
+            for entry in &translated.entries {
+                let Some((name, _)) = entry.key.split_once(QUANTITY_SEPARATOR) else {
+                    continue;
+                };
+                if source.plurals.contains_key(name)
+                    && !source_values.contains_key(entry.key.as_str())
+                {
+                    let other = format!("{}{}other", name, QUANTITY_SEPARATOR);
+                    units.push(TranslationUnit {
+                        res_dir: (*res_dir).clone(),
+                        key: entry.key.clone(),
+                        source: source_values
+                            .get(other.as_str())
+                            .map(|s| s.to_string())
+                            .unwrap_or_default(),
+                        target: Some(entry.value.clone()),
+                    });
+                }
+            }
+        }
+        files.push(TranslationFile { locale, units });
+    }
+    Ok(files)
+}
+
+/// Write translated values into the `values-<locale>` directory of each resource dir
+///
+/// Values that already exist in any file of the locale directory are updated in place; new
+/// strings and plurals are appended to `values-<locale>/strings.xml`, and new quantities are
+/// added to the existing `<plurals>`. Units without a target, or whose target is unchanged,
+/// are left alone. Returns the number of values written.
+///
+/// The resource dir of each unit comes from the translation file, so it must be one of
+/// `res_dirs`, the resource dirs of the configuration.
+pub fn import_translations(file: &TranslationFile, res_dirs: &[PathBuf]) -> Result<usize> {
+    if !is_locale_qualifier(&file.locale) {
+        anyhow::bail!("Invalid locale qualifier: {}", file.locale);
+    }
+    let allowed: Vec<PathBuf> = res_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    let groups = group_by_res_dir(&file.units);
+    for (res_dir, _) in &groups {
+        let resolved = res_dir
+            .canonicalize()
+            .with_context(|| format!("Resource directory not found: {}", res_dir.display()))?;
+        if !allowed.contains(&resolved) {
+            anyhow::bail!(
+                "{} is not a resource directory of the configuration",
+                res_dir.display()
+            );
+        }
+    }
+
+    let mut written = 0;
+    for (res_dir, units) in groups {
+        let locale_dir = res_dir.join(format!("values-{}", file.locale));
+        let existing = read_values_dir(&locale_dir)?;
+        let existing_entries: HashMap<&str, &ValueEntry> = existing
+            .entries
+            .iter()
+            .map(|e| (e.key.as_str(), e))
+            .collect();
+
+        let mut edits: BTreeMap<PathBuf, Vec<(Range<usize>, String)>> = BTreeMap::new();
+        let mut new_strings = String::new();
+        let mut new_plurals: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+        for unit in units {
+            let Some(target) = unit.target.as_deref().filter(|t| !t.trim().is_empty()) else {
+                continue;
+            };
+            roxmltree::Document::parse(&format!("<string>{}</string>", target)).with_context(
+                || format!("Translation of {} is not valid XML: {}", unit.key, target),
+            )?;
+
+            match existing_entries.get(unit.key.as_str()) {
+                Some(entry) if entry.value == target => continue,
+                Some(entry) => edits.entry(entry.file.clone()).or_default().push((
+                    entry.replace.clone(),
+                    format!("{}{}{}", entry.prefix, target, entry.suffix),
+                )),
+                None => match unit.key.split_once(QUANTITY_SEPARATOR) {
+                    Some((name, quantity)) => match existing.plurals.get(name) {
+                        Some((plurals_file, at)) => {
+                            edits.entry(plurals_file.clone()).or_default().push((
+                                *at..*at,
+                                format!(
+                                    "\n        <item quantity=\"{}\">{}</item>",
+                                    escape_attr(quantity),
+                                    target
+                                ),
+                            ))
+                        }
+                        None => match new_plurals.iter_mut().find(|(n, _)| *n == name) {
+                            Some((_, items)) => items.push((quantity, target)),
+                            None => new_plurals.push((name, vec![(quantity, target)])),
+                        },
+                    },
+                    None => new_strings.push_str(&format!(
+                        "    <string name=\"{}\">{}</string>\n",
+                        escape_attr(&unit.key),
+                        target
+                    )),
+                },
+            }
+            written += 1;
+        }
+
+        for (name, items) in new_plurals {
+            new_strings.push_str(&format!("    <plurals name=\"{}\">\n", escape_attr(name)));
+            for (quantity, value) in items {
+                new_strings.push_str(&format!(
+                    "        <item quantity=\"{}\">{}</item>\n",
+                    escape_attr(quantity),
+                    value
+                ));
+            }
+            new_strings.push_str("    </plurals>\n");
+        }
+
+        if !new_strings.is_empty() {
+            let strings_xml = locale_dir.join("strings.xml");
+            if strings_xml.exists() {
+                let content = fs::read_to_string(&strings_xml)?;
+                let at = content
+                    .rfind("</resources>")
+                    .with_context(|| format!("No </resources> in {}", strings_xml.display()))?;
+                edits
+                    .entry(strings_xml)
+                    .or_default()
+                    .push((at..at, new_strings));
+            } else {
+                fs::create_dir_all(&locale_dir)?;
+                fs::write(
+                    &strings_xml,
+                    format!(
+                        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n{}</resources>\n",
+                        new_strings
+                    ),
+                )?;
+                debug!("Created {}", strings_xml.display());
+            }
+        }
+
+        for (path, mut file_edits) in edits {
+            let mut content = fs::read_to_string(&path)?;
+            // Apply back to front so earlier ranges stay valid
+            file_edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+            for (range, text) in file_edits {
+                content.replace_range(range, &text);
+            }
+            fs::write(&path, content)?;
+            debug!("Updated {}", path.display());
+        }
+    }
+    Ok(written)
+}
+
+/// A string or plural item in a values XML file
+struct ValueEntry {
+    key: String,
+    /// Raw XML between the element's tags
+    value: String,
+    file: PathBuf,
+    /// Byte range replaced by `prefix + new value + suffix` when the value is updated
+    replace: Range<usize>,
+    prefix: String,
+    suffix: String,
+}
+
+/// Translatable entries of one values directory
+#[derive(Default)]
+struct ValuesDir {
+    entries: Vec<ValueEntry>,
+    /// Plurals name -> (file, byte offset where new items are inserted)
+    plurals: HashMap<String, (PathBuf, usize)>,
+}
+
+/// Read `<string>` and `<plurals>` resources from the XML files of a values directory
+/// Entries marked `translatable="false"` are skipped
+fn read_values_dir(dir: &Path) -> Result<ValuesDir> {
+    let mut values = ValuesDir::default();
+    if !dir.is_dir() {
+        return Ok(values);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+    files.sort();
+
+    for file in files {
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let document = roxmltree::Document::parse(&content)
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "resources" {
+            continue;
+        }
+
+        for node in root.children().filter(|n| n.is_element()) {
+            if node.attribute("translatable") == Some("false") {
+                continue;
+            }
+            let Some(name) = node.attribute("name") else {
+                continue;
+            };
+            match node.tag_name().name() {
+                "string" => {
+                    values
+                        .entries
+                        .push(value_entry(name.to_string(), node, &content, &file))
+                }
+                "plurals" => {
+                    let mut insert_at = None;
+                    for item in node
+                        .children()
+                        .filter(|n| n.is_element() && n.tag_name().name() == "item")
+                    {
+                        let Some(quantity) = item.attribute("quantity") else {
+                            continue;
+                        };
+                        let key = format!("{}{}{}", name, QUANTITY_SEPARATOR, quantity);
+                        values.entries.push(value_entry(key, item, &content, &file));
+                        insert_at = Some(item.range().end);
+                    }
+                    let end = node.range();
+                    let insert_at = insert_at.or_else(|| {
+                        content[end.clone()]
+                            .rfind("</")
+                            .map(|offset| end.start + offset)
+                    });
+                    if let Some(at) = insert_at {
+                        values.plurals.insert(name.to_string(), (file.clone(), at));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn value_entry(key: String, node: roxmltree::Node, content: &str, file: &Path) -> ValueEntry {
+    let element = node.range();
+    let raw = &content[element.clone()];
+    let (replace, prefix, suffix) = match (node.first_child(), node.last_child()) {
+        (Some(first), Some(last)) => (
+            first.range().start..last.range().end,
+            String::new(),
+            String::new(),
+        ),
+        // `<string name="a"/>` is rewritten with an end tag
+        _ if raw.ends_with("/>") => (
+            element.clone(),
+            format!("{}>", raw[..raw.len() - 2].trim_end()),
+            format!("</{}>", node.tag_name().name()),
+        ),
+        // `<string name="a"></string>`
+        _ => {
+            let at = element.start + raw.rfind("</").unwrap_or(raw.len());
+            (at..at, String::new(), String::new())
+        }
+    };
+    ValueEntry {
+        key,
+        value: if prefix.is_empty() {
+            content[replace.clone()].to_string()
+        } else {
+            String::new()
+        },
+        file: file.to_path_buf(),
+        replace,
+        prefix,
+        suffix,
+    }
+}
+
+/// Concatenated text of an element and its descendants
+fn element_text(node: roxmltree::Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+/// Group consecutive units by resource directory, keeping their order
+fn group_by_res_dir(units: &[TranslationUnit]) -> Vec<(&Path, Vec<&TranslationUnit>)> {
+    let mut groups: Vec<(&Path, Vec<&TranslationUnit>)> = Vec::new();
+    for unit in units {
+        match groups
+            .iter_mut()
+            .find(|(res_dir, _)| *res_dir == unit.res_dir)
+        {
+            Some((_, group)) => group.push(unit),
+            None => groups.push((&unit.res_dir, vec![unit])),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_locale_qualifiers() {
+        assert_eq!(locale_qualifier("values-fr"), Some("fr"));
+        assert_eq!(locale_qualifier("values-zh-rCN"), Some("zh-rCN"));
+        assert_eq!(locale_qualifier("values-es-r419"), Some("es-r419"));
+        assert_eq!(locale_qualifier("values-b+sr+Latn"), Some("b+sr+Latn"));
+        assert_eq!(locale_qualifier("values"), None);
+        assert_eq!(locale_qualifier("values-night"), None);
+        assert_eq!(locale_qualifier("values-car"), None);
+        assert_eq!(locale_qualifier("values-fr-land"), None);
+        assert_eq!(locale_qualifier("values-v21"), None);
+
+        assert_eq!(qualifier_to_language_tag("zh-rCN"), "zh-CN");
+        assert_eq!(qualifier_to_language_tag("b+sr+Latn"), "sr-Latn");
+        assert_eq!(language_tag_to_qualifier("zh-CN").unwrap(), "zh-rCN");
+        assert_eq!(language_tag_to_qualifier("pt_br").unwrap(), "pt-rBR");
+        assert_eq!(language_tag_to_qualifier("sr-Latn").unwrap(), "b+sr+Latn");
+        assert!(language_tag_to_qualifier("not a tag").is_err());
+    }
+
+    fn write_res(res_dir: &Path, dir: &str, content: &str) {
+        fs::create_dir_all(res_dir.join(dir)).unwrap();
+        fs::write(res_dir.join(dir).join("strings.xml"), content).unwrap();
+    }
+
+    fn translations(file: &TranslationFile) -> Vec<(&str, Option<&str>)> {
+        file.units
+            .iter()
+            .map(|u| (u.key.as_str(), u.target.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_and_import_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        write_res(
+            &res_dir,
+            "values",
+            r#"<resources>
+    <string name="title">Skin &amp; Theme</string>
+    <string name="greeting">Hello <b>%s</b></string>
+    <string name="empty"/>
+    <string name="brand" translatable="false">ASB</string>
+    <plurals name="songs">
+        <item quantity="one">%d song</item>
+        <item quantity="other">%d songs</item>
+    </plurals>
+</resources>
+"#,
+        );
+        write_res(
+            &res_dir,
+            "values-ru",
+            r#"<resources>
+    <string name="title">Old</string>
+    <string name="empty"/>
+    <plurals name="songs">
+        <item quantity="few">%d песни</item>
+    </plurals>
+</resources>
+"#,
+        );
+        fs::create_dir_all(res_dir.join("values-night"))?;
+
+        let files = collect_translations(std::slice::from_ref(&res_dir), &[])?;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].locale, "ru");
+        assert_eq!(
+            translations(&files[0]),
+            vec![
+                ("title", Some("Old")),
+                ("greeting", None),
+                ("empty", None),
+                ("songs#one", None),
+                ("songs#other", None),
+                ("songs#few", Some("%d песни")),
+            ]
+        );
+        assert_eq!(files[0].units[0].source, "Skin &amp; Theme");
+        assert_eq!(files[0].units[5].source, "%d songs");
+
+        // Translators fill in targets; both formats round-trip the units
+        let mut file = files[0].clone();
+        let targets = [
+            "Скин &amp; тема",
+            "Привет <b>%s</b>",
+            "Пусто",
+            "%d песня",
+            "",
+            "%d песни",
+        ];
+        for (unit, target) in file.units.iter_mut().zip(targets) {
+            unit.target = Some(target.to_string()).filter(|t| !t.is_empty());
+        }
+        for format in [StringsFormat::Xliff, StringsFormat::Csv] {
+            let path = temp_dir.path().join(format!("ru.{}", format.extension()));
+            file.write(format, "en", &path)?;
+            assert_eq!(TranslationFile::read(&path)?, file);
+        }
+
+        // title and empty are updated in place, greeting is appended, the new quantity joins
+        // the existing plurals, and unchanged or empty targets are skipped
+        let res_dirs = [res_dir.clone()];
+        assert_eq!(import_translations(&file, &res_dirs)?, 4);
+        let files = collect_translations(std::slice::from_ref(&res_dir), &["ru".to_string()])?;
+        assert_eq!(
+            translations(&files[0]),
+            vec![
+                ("title", Some("Скин &amp; тема")),
+                ("greeting", Some("Привет <b>%s</b>")),
+                ("empty", Some("Пусто")),
+                ("songs#one", Some("%d песня")),
+                ("songs#other", None),
+                ("songs#few", Some("%d песни")),
+            ]
+        );
+
+        // Importing into a locale without a directory creates values-<locale>/strings.xml
+        file.locale = "zh-rCN".to_string();
+        assert_eq!(import_translations(&file, &res_dirs)?, 5);
+        let content = fs::read_to_string(res_dir.join("values-zh-rCN").join("strings.xml"))?;
+        assert!(content.contains("<string name=\"title\">Скин &amp; тема</string>"));
+        assert!(content.contains("<plurals name=\"songs\">"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_xml() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        write_res(
+            &res_dir,
+            "values",
+            "<resources><string name=\"a\">A</string></resources>",
+        );
+
+        let file = TranslationFile {
+            locale: "fr".to_string(),
+            units: vec![TranslationUnit {
+                res_dir: res_dir.clone(),
+                key: "a".to_string(),
+                source: "A".to_string(),
+                target: Some("Tom & Jerry".to_string()),
+            }],
+        };
+        assert!(import_translations(&file, std::slice::from_ref(&res_dir)).is_err());
+        assert!(!res_dir.join("values-fr").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_unconfigured_res_dir() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let res_dir = temp_dir.path().join("res");
+        let elsewhere = temp_dir.path().join("elsewhere");
+        write_res(
+            &res_dir,
+            "values",
+            "<resources><string name=\"a\">A</string></resources>",
+        );
+        fs::create_dir_all(&elsewhere)?;
+
+        // The destination comes from the file, so a path outside the config is refused
+        let mut file = TranslationFile {
+            locale: "fr".to_string(),
+            units: vec![TranslationUnit {
+                res_dir: elsewhere.clone(),
+                key: "a".to_string(),
+                source: "A".to_string(),
+                target: Some("Un".to_string()),
+            }],
+        };
+        assert!(import_translations(&file, std::slice::from_ref(&res_dir)).is_err());
+        assert!(!elsewhere.join("values-fr").exists());
+
+        // Spellings that resolve to a configured dir are accepted
+        file.units[0].res_dir = elsewhere.join("..").join("res");
+        assert_eq!(
+            import_translations(&file, std::slice::from_ref(&res_dir))?,
+            1
+        );
+        assert!(res_dir.join("values-fr").join("strings.xml").is_file());
+        Ok(())
+    }
+}
@@ -0,0 +1,741 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::manifest::escape_attr;
+use crate::types::BuildConfig;
+
+/// Configuration key used for the unqualified `values` directory
+pub const DEFAULT_CONFIGURATION: &str = "default";
+
+/// Maximum `@color/` reference chain followed when resolving a value
+const MAX_REFERENCE_DEPTH: usize = 16;
+
+/// Configuration ("default", "night", "v31", ...) -> value
+pub type ConfigValues = BTreeMap<String, String>;
+
+/// Color and theme tokens of one skin
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenReport {
+    pub package_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flavor: Option<String>,
+    /// Color name -> configuration -> resolved color
+    pub colors: BTreeMap<String, ConfigValues>,
+    /// Style name -> color-valued items
+    pub styles: BTreeMap<String, StyleTokens>,
+}
+
+/// Color-valued items of a style
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StyleTokens {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Item name -> configuration -> resolved color
+    pub items: BTreeMap<String, ConfigValues>,
+}
+
+/// Collect colors and color-valued style items from a skin's source set
+///
+/// Additional resource directories are read first and the main resource directory last,
+/// so main values override them like they do at link time. `@color/` references are
+/// resolved within the same configuration, falling back to the default configuration;
+/// framework colors and unresolvable references are reported as written.
+pub fn extract_tokens(config: &BuildConfig) -> Result<TokenReport> {
+    let source_set: Vec<&PathBuf> = config
+        .additional_resource_dirs
+        .iter()
+        .flatten()
+        .chain(std::iter::once(&config.resource_dir))
+        .collect();
+
+    let mut colors: BTreeMap<String, ConfigValues> = BTreeMap::new();
+    let mut styles: BTreeMap<String, (Option<String>, BTreeMap<String, ConfigValues>)> =
+        BTreeMap::new();
+    for res_dir in source_set {
+        if !res_dir.is_dir() {
+            continue;
+        }
+        for (configuration, values_dir) in values_dirs(res_dir)? {
+            for file in xml_files(&values_dir)? {
+                read_values_file(&file, &configuration, &mut colors, &mut styles)?;
+            }
+        }
+    }
+
+    let configurations: BTreeSet<String> = colors
+        .values()
+        .chain(styles.values().flat_map(|(_, items)| items.values()))
+        .flat_map(|values| values.keys().cloned())
+        .collect();
+
+    let resolved_colors = colors
+        .iter()
+        .map(|(name, values)| (name.clone(), resolve_all(values, &configurations, &colors)))
+        .collect();
+
+    let mut resolved_styles = BTreeMap::new();
+    for (name, (parent, items)) in &styles {
+        let items: BTreeMap<String, ConfigValues> = items
+            .iter()
+            .filter(|(_, values)| values.values().any(|v| is_color_value(v)))
+            .map(|(item, values)| (item.clone(), resolve_all(values, &configurations, &colors)))
+            .collect();
+        if !items.is_empty() {
+            resolved_styles.insert(
+                name.clone(),
+                StyleTokens {
+                    parent: parent.clone(),
+                    items,
+                },
+            );
+        }
+    }
+
+    Ok(TokenReport {
+        package_name: config.package_name.clone(),
+        flavor: config.flavor.clone(),
+        colors: resolved_colors,
+        styles: resolved_styles,
+    })
+}
+
+/// `values` and `values-*` directories of a resource dir with their configuration keys
+fn values_dirs(res_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in
+        fs::read_dir(res_dir).with_context(|| format!("Failed to read {}", res_dir.display()))?
+    {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let configuration = match name {
+            "values" => DEFAULT_CONFIGURATION.to_string(),
+            _ => match name.strip_prefix("values-") {
+                Some(qualifiers) => qualifiers.to_string(),
+                None => continue,
+            },
+        };
+        if path.is_dir() {
+            dirs.push((configuration, path));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// XML files directly inside `dir`, sorted
+pub(crate) fn xml_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn read_values_file(
+    file: &Path,
+    configuration: &str,
+    colors: &mut BTreeMap<String, ConfigValues>,
+    styles: &mut BTreeMap<String, (Option<String>, BTreeMap<String, ConfigValues>)>,
+) -> Result<()> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let document = roxmltree::Document::parse(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "resources" {
+        return Ok(());
+    }
+
+    for node in root.children().filter(|n| n.is_element()) {
+        let Some(name) = node.attribute("name") else {
+            continue;
+        };
+        let is_color = match node.tag_name().name() {
+            "color" => true,
+            "item" => node.attribute("type") == Some("color"),
+            _ => false,
+        };
+        if is_color {
+            colors
+                .entry(name.to_string())
+                .or_default()
+                .insert(configuration.to_string(), element_text(node));
+        } else if node.tag_name().name() == "style" {
+            let (parent, items) = styles.entry(name.to_string()).or_default();
+            if let Some(style_parent) = node.attribute("parent") {
+                parent.get_or_insert_with(|| style_parent.to_string());
+            }
+            for item in node
+                .children()
+                .filter(|n| n.is_element() && n.tag_name().name() == "item")
+            {
+                if let Some(item_name) = item.attribute("name") {
+                    items
+                        .entry(item_name.to_string())
+                        .or_default()
+                        .insert(configuration.to_string(), element_text(item));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn element_text(node: roxmltree::Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Whether a style item value is a color literal or a color resource reference
+fn is_color_value(value: &str) -> bool {
+    value.starts_with('#') || value.starts_with("@color/") || value.starts_with("@android:color/")
+}
+
+/// Resolve a value for every configuration
+/// Configurations that don't define the value themselves are included when a color it
+/// references differs there, e.g. `@color/primary` in "night" when only `primary` has a
+/// night variant
+fn resolve_all(
+    values: &ConfigValues,
+    configurations: &BTreeSet<String>,
+    colors: &BTreeMap<String, ConfigValues>,
+) -> ConfigValues {
+    let default = values
+        .get(DEFAULT_CONFIGURATION)
+        .map(|value| resolve(value, DEFAULT_CONFIGURATION, colors, 0));
+    configurations
+        .iter()
+        .filter_map(|configuration| {
+            let value = values
+                .get(configuration)
+                .or_else(|| values.get(DEFAULT_CONFIGURATION))?;
+            let resolved = resolve(value, configuration, colors, 0);
+            (values.contains_key(configuration) || Some(&resolved) != default.as_ref())
+                .then(|| (configuration.clone(), resolved))
+        })
+        .collect()
+}
+
+/// Resolve `@color/` references and normalize color literals
+fn resolve(
+    value: &str,
+    configuration: &str,
+    colors: &BTreeMap<String, ConfigValues>,
+    depth: usize,
+) -> String {
+    if let Some(name) = value.strip_prefix("@color/")
+        && depth < MAX_REFERENCE_DEPTH
+        && let Some(values) = colors.get(name)
+        && let Some(target) = values
+            .get(configuration)
+            .or_else(|| values.get(DEFAULT_CONFIGURATION))
+    {
+        return resolve(target, configuration, colors, depth + 1);
+    }
+    normalize_color(value).unwrap_or_else(|| value.to_string())
+}
+
+/// Expand `#RGB`/`#ARGB` to `#RRGGBB`/`#AARRGGBB` and uppercase the hex digits
+pub fn normalize_color(value: &str) -> Option<String> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let expanded = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return None,
+    };
+    Some(format!("#{}", expanded.to_ascii_uppercase()))
+}
+
+/// An Android resource generated from a design token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenResource {
+    /// Resource type, "color" or "dimen"
+    pub kind: &'static str,
+    pub name: String,
+    pub value: String,
+}
+
+/// Parse a design-token file into color and dimen resources
+///
+/// Two formats are accepted:
+/// - simple JSON: `{"colors": {"primary": "#6200EE"}, "dimens": {"spacing": "8dp"}}`,
+///   with values used as written
+/// - W3C design tokens: nested groups of `$value`/`$type` tokens. `color` and `dimension`
+///   tokens are converted (CSS `#RRGGBBAA` to `#AARRGGBB`, `px` to `dp`, `rem` to 16dp),
+///   aliases such as `{color.brand}` become resource references, and names are the token
+///   path joined with `_`. Other token types are skipped.
+pub fn parse_design_tokens(content: &str) -> Result<Vec<TokenResource>> {
+    let root: Value = serde_json::from_str(content)?;
+    let object = root
+        .as_object()
+        .context("Design tokens must be a JSON object")?;
+
+    let mut resources = Vec::new();
+    if is_simple_format(object) {
+        for (section, kind) in [("colors", "color"), ("dimens", "dimen")] {
+            for (name, value) in object
+                .get(section)
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+            {
+                resources.push(TokenResource {
+                    kind,
+                    name: resource_name(name),
+                    value: value.as_str().unwrap_or_default().trim().to_string(),
+                });
+            }
+        }
+    } else {
+        let mut tokens = BTreeMap::new();
+        collect_tokens(object, &mut Vec::new(), None, &mut tokens);
+        for (path, (_, value)) in &tokens {
+            let Some(kind) = token_kind(path, &tokens, 0) else {
+                debug!("Skipping design token {} (not a color or dimension)", path);
+                continue;
+            };
+            let value = match alias_target(value) {
+                Some(target) if tokens.contains_key(target) => {
+                    format!("@{}/{}", kind, resource_name(target))
+                }
+                Some(target) => {
+                    anyhow::bail!("Design token {} references unknown token {}", path, target)
+                }
+                None if kind == "color" => {
+                    convert_color(value).with_context(|| format!("Invalid color token {}", path))?
+                }
+                None => convert_dimension(value)
+                    .with_context(|| format!("Invalid dimension token {}", path))?,
+            };
+            resources.push(TokenResource {
+                kind,
+                name: resource_name(path),
+                value,
+            });
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for resource in &resources {
+        if !seen.insert((resource.kind, resource.name.as_str())) {
+            anyhow::bail!(
+                "Several design tokens map to the {} resource {}",
+                resource.kind,
+                resource.name
+            );
+        }
+    }
+    Ok(resources)
+}
+
+/// Generate `values/colors.xml` and `values/dimens.xml` under `res_dir` from a design-token file
+/// Files are only rewritten when their content changes so incremental builds can skip them
+/// Returns the number of generated resources
+pub fn generate_token_resources(tokens_file: &Path, res_dir: &Path) -> Result<usize> {
+    let content = fs::read_to_string(tokens_file)
+        .with_context(|| format!("Failed to read design tokens {}", tokens_file.display()))?;
+    let resources = parse_design_tokens(&content)
+        .with_context(|| format!("Invalid design tokens {}", tokens_file.display()))?;
+    if resources.is_empty() {
+        warn!(
+            "No color or dimension tokens found in {}",
+            tokens_file.display()
+        );
+    }
+
+    let values_dir = res_dir.join("values");
+    fs::create_dir_all(&values_dir)?;
+    let source_name = tokens_file
+        .file_name()
+        .map(|name| name.to_string_lossy().replace("--", "-"))
+        .unwrap_or_default();
+    for (kind, file_name) in [("color", "colors.xml"), ("dimen", "dimens.xml")] {
+        let path = values_dir.join(file_name);
+        let entries: Vec<&TokenResource> = resources.iter().filter(|r| r.kind == kind).collect();
+        if entries.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            continue;
+        }
+
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- Generated by asb from {}, do not edit -->\n<resources>\n",
+            source_name
+        );
+        for entry in entries {
+            xml.push_str(&format!(
+                "    <{kind} name=\"{}\">{}</{kind}>\n",
+                escape_attr(&entry.name),
+                escape_attr(&entry.value),
+            ));
+        }
+        xml.push_str("</resources>\n");
+        if fs::read_to_string(&path).ok().as_deref() != Some(xml.as_str()) {
+            fs::write(&path, xml)?;
+            debug!("Generated {}", path.display());
+        }
+    }
+    Ok(resources.len())
+}
+
+/// Simple format: only `colors`/`dimens` objects with string values
+fn is_simple_format(object: &Map<String, Value>) -> bool {
+    !object.is_empty()
+        && object.iter().all(|(key, value)| {
+            matches!(key.as_str(), "colors" | "dimens")
+                && value
+                    .as_object()
+                    .is_some_and(|entries| entries.values().all(Value::is_string))
+        })
+}
+
+/// Collect `$value` tokens by dotted path, with `$type` inherited from enclosing groups
+fn collect_tokens(
+    group: &Map<String, Value>,
+    path: &mut Vec<String>,
+    inherited_type: Option<&str>,
+    tokens: &mut BTreeMap<String, (Option<String>, Value)>,
+) {
+    let group_type = group
+        .get("$type")
+        .and_then(Value::as_str)
+        .or(inherited_type);
+    for (key, child) in group {
+        if key.starts_with('$') {
+            continue;
+        }
+        let Some(child) = child.as_object() else {
+            continue;
+        };
+        path.push(key.clone());
+        match child.get("$value") {
+            Some(value) => {
+                let token_type = child
+                    .get("$type")
+                    .and_then(Value::as_str)
+                    .or(group_type)
+                    .map(str::to_string);
+                tokens.insert(path.join("."), (token_type, value.clone()));
+            }
+            None => collect_tokens(child, path, group_type, tokens),
+        }
+        path.pop();
+    }
+}
+
+/// Resource type of a token, following aliases for tokens without their own `$type`
+fn token_kind(
+    path: &str,
+    tokens: &BTreeMap<String, (Option<String>, Value)>,
+    depth: usize,
+) -> Option<&'static str> {
+    let (token_type, value) = tokens.get(path)?;
+    match token_type.as_deref() {
+        Some("color") => Some("color"),
+        Some("dimension") => Some("dimen"),
+        Some(_) => None,
+        None if depth < MAX_REFERENCE_DEPTH => token_kind(alias_target(value)?, tokens, depth + 1),
+        None => None,
+    }
+}
+
+/// Target path of an alias value like "{color.brand.primary}"
+fn alias_target(value: &Value) -> Option<&str> {
+    value.as_str()?.strip_prefix('{')?.strip_suffix('}')
+}
+
+/// Android resource name for a token name or path
+fn resource_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Convert a W3C color token (`"#RRGGBB"`, `"#RRGGBBAA"` or `{"hex": ..., "alpha": ...}`)
+fn convert_color(value: &Value) -> Result<String> {
+    let (hex, alpha) = match value {
+        Value::String(hex) => (hex.as_str(), None),
+        Value::Object(color) => (
+            color
+                .get("hex")
+                .and_then(Value::as_str)
+                .context("Color objects need a hex value")?,
+            color.get("alpha").and_then(Value::as_f64),
+        ),
+        _ => anyhow::bail!("Unsupported color value {}", value),
+    };
+    let digits = hex
+        .trim()
+        .strip_prefix('#')
+        .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()))
+        .with_context(|| format!("Expected a hex color, got {}", hex))?;
+    let expanded: String = match digits.len() {
+        3 | 4 => digits.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => digits.to_string(),
+        _ => anyhow::bail!("Expected a hex color, got {}", hex),
+    };
+
+    // CSS puts alpha last, Android first
+    let (rgb, css_alpha) = expanded.split_at(6);
+    let alpha = match alpha {
+        Some(alpha) => format!("{:02X}", (alpha.clamp(0.0, 1.0) * 255.0).round() as u8),
+        None => css_alpha.to_string(),
+    };
+    Ok(format!("#{}{}", alpha, rgb).to_ascii_uppercase())
+}
+
+/// Convert a W3C dimension token (`"16px"`, `"1.5rem"`, `16` or `{"value": 16, "unit": "px"}`)
+fn convert_dimension(value: &Value) -> Result<String> {
+    let (number, unit) = match value {
+        Value::Number(number) => (number.as_f64().context("Invalid number")?, "px"),
+        Value::String(dimension) => {
+            let dimension = dimension.trim();
+            let split = dimension
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                .unwrap_or(dimension.len());
+            let number = dimension[..split]
+                .parse()
+                .with_context(|| format!("Expected a dimension, got {}", dimension))?;
+            (number, dimension[split..].trim())
+        }
+        Value::Object(dimension) => (
+            dimension
+                .get("value")
+                .and_then(Value::as_f64)
+                .context("Dimension objects need a numeric value")?,
+            dimension
+                .get("unit")
+                .and_then(Value::as_str)
+                .unwrap_or("px"),
+        ),
+        _ => anyhow::bail!("Unsupported dimension value {}", value),
+    };
+
+    // Design tools work in px at 1x, which is what dp means on Android
+    let (number, unit) = match unit {
+        "" | "px" | "dp" | "dip" => (number, "dp"),
+        "rem" => (number * 16.0, "dp"),
+        "sp" | "pt" | "mm" | "in" => (number, unit),
+        _ => anyhow::bail!("Unsupported dimension unit {}", unit),
+    };
+    Ok(format!("{}{}", number, unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_values(res_dir: &Path, dir: &str, file: &str, content: &str) {
+        fs::create_dir_all(res_dir.join(dir)).unwrap();
+        fs::write(res_dir.join(dir).join(file), content).unwrap();
+    }
+
+    #[test]
+    fn test_normalize_color() {
+        assert_eq!(normalize_color("#fff").as_deref(), Some("#FFFFFF"));
+        assert_eq!(normalize_color("#8f00").as_deref(), Some("#88FF0000"));
+        assert_eq!(normalize_color("#6200ee").as_deref(), Some("#6200EE"));
+        assert_eq!(normalize_color("#12345"), None);
+        assert_eq!(normalize_color("@color/primary"), None);
+    }
+
+    #[test]
+    fn test_extract_tokens() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let main = temp_dir.path().join("res");
+        let shared = temp_dir.path().join("shared");
+        write_values(
+            &shared,
+            "values",
+            "colors.xml",
+            r##"<resources>
+    <color name="brand">#111</color>
+    <color name="accent">#ff0000</color>
+</resources>"##,
+        );
+        write_values(
+            &main,
+            "values",
+            "colors.xml",
+            r##"<resources>
+    <color name="brand">#6200ee</color>
+    <color name="primary">@color/brand</color>
+    <item name="surface" type="color">#ffffff</item>
+    <string name="ignored">#000</string>
+</resources>"##,
+        );
+        write_values(
+            &main,
+            "values-night",
+            "colors.xml",
+            r##"<resources>
+    <color name="brand">#bb86fc</color>
+</resources>"##,
+        );
+        write_values(
+            &main,
+            "values",
+            "themes.xml",
+            r##"<resources>
+    <style name="Theme.Skin" parent="Theme.Material3.DayNight">
+        <item name="colorPrimary">@color/primary</item>
+        <item name="colorError">@android:color/holo_red_dark</item>
+        <item name="android:windowNoTitle">true</item>
+    </style>
+    <style name="Widget.Skin.Text">
+        <item name="android:textSize">14sp</item>
+    </style>
+</resources>"##,
+        );
+
+        let config = BuildConfig {
+            resource_dir: main,
+            additional_resource_dirs: Some(vec![shared]),
+            package_name: "com.example.skin".to_string(),
+            ..BuildConfig::default_config()
+        };
+        let report = extract_tokens(&config)?;
+
+        let values = |pairs: &[(&str, &str)]| -> ConfigValues {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            report.colors["brand"],
+            values(&[("default", "#6200EE"), ("night", "#BB86FC")])
+        );
+        assert_eq!(report.colors["accent"], values(&[("default", "#FF0000")]));
+        // primary has no night value of its own, but the color it references does
+        assert_eq!(
+            report.colors["primary"],
+            values(&[("default", "#6200EE"), ("night", "#BB86FC")])
+        );
+        assert_eq!(report.colors["surface"], values(&[("default", "#FFFFFF")]));
+        assert!(!report.colors.contains_key("ignored"));
+
+        // Only color-valued items are reported, and styles without any are dropped
+        let theme = &report.styles["Theme.Skin"];
+        assert_eq!(theme.parent.as_deref(), Some("Theme.Material3.DayNight"));
+        assert_eq!(
+            theme.items.keys().collect::<Vec<_>>(),
+            vec!["colorError", "colorPrimary"]
+        );
+        assert_eq!(
+            theme.items["colorPrimary"],
+            values(&[("default", "#6200EE"), ("night", "#BB86FC")])
+        );
+        assert_eq!(
+            theme.items["colorError"],
+            values(&[("default", "@android:color/holo_red_dark")])
+        );
+        assert!(!report.styles.contains_key("Widget.Skin.Text"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_design_tokens() -> Result<()> {
+        let simple = parse_design_tokens(
+            r##"{"colors": {"primary": "#FF6200EE"}, "dimens": {"spacing-small": "8dp"}}"##,
+        )?;
+        assert_eq!(
+            simple,
+            vec![
+                TokenResource {
+                    kind: "color",
+                    name: "primary".to_string(),
+                    value: "#FF6200EE".to_string(),
+                },
+                TokenResource {
+                    kind: "dimen",
+                    name: "spacing_small".to_string(),
+                    value: "8dp".to_string(),
+                },
+            ]
+        );
+
+        let w3c = parse_design_tokens(
+            r##"{
+                "color": {
+                    "$type": "color",
+                    "brand": { "$value": "#6200ee80" },
+                    "surface": { "$value": { "colorSpace": "srgb", "hex": "#fff", "alpha": 0.5 } },
+                    "primary": { "$value": "{color.brand}" }
+                },
+                "spacing": {
+                    "$type": "dimension",
+                    "small": { "$value": "8px" },
+                    "large": { "$value": { "value": 1.5, "unit": "rem" } },
+                    "text": { "$value": "14sp" }
+                },
+                "font": { "body": { "$type": "fontFamily", "$value": "Roboto" } }
+            }"##,
+        )?;
+        let values: Vec<(&str, &str, &str)> = w3c
+            .iter()
+            .map(|r| (r.kind, r.name.as_str(), r.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("color", "color_brand", "#806200EE"),
+                ("color", "color_primary", "@color/color_brand"),
+                ("color", "color_surface", "#80FFFFFF"),
+                ("dimen", "spacing_large", "24dp"),
+                ("dimen", "spacing_small", "8dp"),
+                ("dimen", "spacing_text", "14sp"),
+            ]
+        );
+
+        assert!(parse_design_tokens(r#"{"a": {"$type": "color", "$value": "{b}"}}"#).is_err());
+        assert!(parse_design_tokens(r#"{"a": {"$type": "dimension", "$value": "3vw"}}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_token_resources() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let tokens_file = temp_dir.path().join("tokens.json");
+        let res_dir = temp_dir.path().join("generated");
+        fs::write(
+            &tokens_file,
+            r##"{"colors": {"primary": "#6200EE"}, "dimens": {"gap": "4dp"}}"##,
+        )?;
+
+        assert_eq!(generate_token_resources(&tokens_file, &res_dir)?, 2);
+        let colors = fs::read_to_string(res_dir.join("values").join("colors.xml"))?;
+        assert!(colors.contains("<color name=\"primary\">#6200EE</color>"));
+        assert!(res_dir.join("values").join("dimens.xml").exists());
+
+        // Dropping every dimension removes the stale dimens.xml
+        fs::write(&tokens_file, r##"{"colors": {"primary": "#6200EE"}}"##)?;
+        assert_eq!(generate_token_resources(&tokens_file, &res_dir)?, 1);
+        assert!(!res_dir.join("values").join("dimens.xml").exists());
+        Ok(())
+    }
+}
@@ -1,12 +1,19 @@
+use anyhow::Context;
+use path_absolutize::Absolutize;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
+use tracing::{debug, info};
 
-use crate::aapt2::DEFAULT_PACKAGE_ID;
+use crate::aapt2::{DEFAULT_PACKAGE_ID, LinkInputMode};
+use crate::owners::{glob_match, wildcard_match};
+use crate::staging::NormalizationStats;
+use crate::vcs::VcsInfo;
 
 /// Find the highest version android.jar in ANDROID_HOME/platforms
 /// Returns None if ANDROID_HOME is not set or no android.jar is found
-fn find_highest_android_jar() -> Option<PathBuf> {
+pub(crate) fn find_highest_android_jar() -> Option<PathBuf> {
     let android_home = std::env::var("ANDROID_HOME").ok()?;
     let platforms_dir = PathBuf::from(android_home).join("platforms");
 
@@ -43,6 +50,13 @@ fn find_highest_android_jar() -> Option<PathBuf> {
     versions.last().map(|(_, path)| path.clone())
 }
 
+/// Latest config schema version, written by `asb config migrate`
+///
+/// Configs without `configVersion` are version 1: single-object, array or multi-app
+/// configs that may use the deprecated `cacheDir`. Version 2 configs are multi-app
+/// configs that use `buildDir`.
+pub const CONFIG_VERSION: u32 = 2;
+
 /// Loaded configurations with metadata
 #[derive(Debug, Clone)]
 pub struct LoadedConfigs {
@@ -50,6 +64,77 @@ pub struct LoadedConfigs {
     pub configs: Vec<BuildConfig>,
     /// Maximum parallel builds (from MultiAppConfig or None)
     pub max_parallel_builds: Option<usize>,
+    /// Maximum parallel links (from MultiAppConfig or None)
+    pub max_parallel_links: Option<usize>,
+    /// Bundle settings (from MultiAppConfig or None)
+    pub bundle: Option<BundleConfig>,
+}
+
+/// Merged bundle of every app's skin, written by `asb build --bundle`
+///
+/// Each app (and flavor) becomes one module of the bundle, named after its build target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleConfig {
+    /// Path of the merged bundle file
+    pub output: PathBuf,
+}
+
+/// Machine-specific settings from the user defaults file
+/// They fill in what project configs leave unset, so projects don't need local SDK paths
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserDefaults {
+    #[serde(rename = "aapt2Path", skip_serializing_if = "Option::is_none")]
+    pub aapt2_path: Option<PathBuf>,
+
+    #[serde(rename = "androidJar", skip_serializing_if = "Option::is_none")]
+    pub android_jar: Option<PathBuf>,
+
+    #[serde(rename = "cacheDir", skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Local file receiving one record per `asb build`; statistics are off without it
+    #[serde(rename = "statsFile", skip_serializing_if = "Option::is_none")]
+    pub stats_file: Option<PathBuf>,
+}
+
+impl UserDefaults {
+    /// Location of the user defaults file
+    /// `$ASB_USER_CONFIG`, else `$XDG_CONFIG_HOME/asb/config.json`, else
+    /// `~/.config/asb/config.json` (`%APPDATA%\asb\config.json` on Windows)
+    pub fn path() -> Option<PathBuf> {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(path) = var("ASB_USER_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| var("APPDATA").map(PathBuf::from))?;
+        Some(config_home.join("asb").join("config.json"))
+    }
+
+    /// Load the defaults file at `path`; a missing file means no defaults
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid user defaults file {}", path.display()))
+    }
+
+    /// Fill the settings `config` leaves unset
+    pub fn apply(&self, config: &mut BuildConfig) {
+        if config.aapt2_path.is_none() {
+            config.aapt2_path = self.aapt2_path.clone();
+        }
+        if config.android_jar.is_none() {
+            config.android_jar = self.android_jar.clone();
+        }
+        if config.cache_dir.is_none() {
+            config.cache_dir = self.cache_dir.clone();
+        }
+    }
 }
 
 /// Flavor-specific configuration for multi-flavor builds
@@ -107,12 +192,30 @@ pub struct FlavorConfig {
     /// Flavor-specific assets directory override (optional)
     #[serde(rename = "assetsDir", skip_serializing_if = "Option::is_none")]
     pub assets_dir: Option<PathBuf>,
+
+    /// Flavor-specific design-token file override (optional)
+    #[serde(rename = "designTokens", skip_serializing_if = "Option::is_none")]
+    pub design_tokens: Option<PathBuf>,
+
+    /// Flavor-specific symlink following override (optional)
+    #[serde(rename = "followSymlinks", skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
 }
 
 /// App-specific configuration in multi-app mode
 /// Contains only app-specific fields, common fields are inherited from parent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// App name used in build targets such as `:brandA:night` (optional, default: packageName)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Variable axes such as `{"brand": ["red", "blue"]}` (optional)
+    /// The app expands into one app per combination of values, with `{brand}` in its
+    /// strings replaced by the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<BTreeMap<String, Vec<String>>>,
+
     /// Base directory for app (optional, provides defaults for resourceDir and manifestPath)
     /// If specified, resourceDir defaults to $baseDir/res and manifestPath defaults to $baseDir/AndroidManifest.xml
     #[serde(rename = "baseDir", skip_serializing_if = "Option::is_none")]
@@ -169,12 +272,146 @@ pub struct AppConfig {
     /// App-specific assets directory override (optional)
     #[serde(rename = "assetsDir", skip_serializing_if = "Option::is_none")]
     pub assets_dir: Option<PathBuf>,
+
+    /// App-specific design-token file (optional)
+    #[serde(rename = "designTokens", skip_serializing_if = "Option::is_none")]
+    pub design_tokens: Option<PathBuf>,
+
+    /// App-specific resource name prefix override (optional)
+    #[serde(rename = "resourcePrefix", skip_serializing_if = "Option::is_none")]
+    pub resource_prefix: Option<String>,
+
+    /// App-specific reference aliases override (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<BTreeMap<String, String>>,
+
+    /// App-specific host symbol list override (optional)
+    #[serde(rename = "hostSymbols", skip_serializing_if = "Option::is_none")]
+    pub host_symbols: Option<PathBuf>,
+
+    /// App-specific owners file override (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<PathBuf>,
+
+    /// App-specific freeze file override (optional)
+    #[serde(rename = "freezeFile", skip_serializing_if = "Option::is_none")]
+    pub freeze_file: Option<PathBuf>,
+
+    /// App-specific host versions override (optional)
+    #[serde(rename = "hostVersions", skip_serializing_if = "Option::is_none")]
+    pub host_versions: Option<Vec<HostVersion>>,
+
+    /// App-specific feature flags, overriding common flags of the same name (optional)
+    #[serde(rename = "featureFlags", skip_serializing_if = "Option::is_none")]
+    pub feature_flags: Option<BTreeMap<String, bool>>,
+
+    /// Resource directories gated by feature flags or date windows (optional)
+    #[serde(
+        rename = "conditionalResourceDirs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub conditional_resource_dirs: Option<Vec<ConditionalResourceDir>>,
+
+    /// App-specific scheduled resource files override (optional)
+    #[serde(rename = "scheduledFiles", skip_serializing_if = "Option::is_none")]
+    pub scheduled_files: Option<Vec<ScheduledFiles>>,
+
+    /// App-specific strict mode override (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+
+    /// App-specific size budget override (optional)
+    #[serde(rename = "sizeBudget", skip_serializing_if = "Option::is_none")]
+    pub size_budget: Option<SizeBudget>,
+
+    /// App-specific AAR resource filters override (optional)
+    #[serde(rename = "aarFilters", skip_serializing_if = "Option::is_none")]
+    pub aar_filters: Option<BTreeMap<String, AarResourceFilter>>,
+
+    /// App-specific symlink following override (optional)
+    #[serde(rename = "followSymlinks", skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+}
+
+impl AppConfig {
+    /// Name of the app in build targets
+    pub fn target_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.package_name)
+    }
+}
+
+impl AppConfig {
+    /// One app per combination of the matrix values, with `{axis}` placeholders in its
+    /// strings replaced; the app itself when it has no matrix
+    ///
+    /// Axes are combined in name order, the first axis varying slowest; values keep their
+    /// order.
+    pub fn expand_matrix(self) -> Vec<AppConfig> {
+        let Some(matrix) = self.matrix.clone() else {
+            return vec![self];
+        };
+        let mut combinations: Vec<Vec<(&str, &str)>> = vec![Vec::new()];
+        for (axis, values) in &matrix {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((axis.as_str(), value.as_str()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        let template = serde_json::to_value(AppConfig {
+            matrix: None,
+            ..self
+        })
+        .expect("app config serializes to JSON");
+        combinations
+            .into_iter()
+            .map(|combination| {
+                let mut value = template.clone();
+                substitute_matrix(&mut value, &combination);
+                serde_json::from_value(value)
+                    .expect("replacing placeholders in strings keeps the app config valid")
+            })
+            .collect()
+    }
+}
+
+/// Replace `{axis}` placeholders in every string of `value`
+fn substitute_matrix(value: &mut serde_json::Value, combination: &[(&str, &str)]) {
+    match value {
+        serde_json::Value::String(text) => {
+            for (axis, axis_value) in combination {
+                *text = text.replace(&format!("{{{}}}", axis), axis_value);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                substitute_matrix(item, combination);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for field in fields.values_mut() {
+                substitute_matrix(field, combination);
+            }
+        }
+        _ => {}
+    }
 }
 
 /// Multi-app configuration wrapper
 /// Supports multiple apps with common configuration extracted to top level
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiAppConfig {
+    /// Schema version the config is written for (optional, default: 1)
+    /// `asb config migrate` upgrades older configs to [`CONFIG_VERSION`]
+    #[serde(rename = "configVersion", skip_serializing_if = "Option::is_none")]
+    pub config_version: Option<u32>,
+
     /// Common base directory for all apps (optional)
     /// Provides defaults for resourceDir and manifestPath if not specified per app
     #[serde(rename = "baseDir", skip_serializing_if = "Option::is_none")]
@@ -200,7 +437,7 @@ pub struct MultiAppConfig {
 
     /// Common AAR files (optional)
     #[serde(rename = "aarFiles", skip_serializing_if = "Option::is_none", default)]
-    pub aar_files: Option<Vec<PathBuf>>,
+    pub aar_files: Option<Vec<AarFile>>,
 
     /// Common incremental build setting (optional)
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -234,6 +471,16 @@ pub struct MultiAppConfig {
     #[serde(rename = "maxParallelBuilds", skip_serializing_if = "Option::is_none")]
     pub max_parallel_builds: Option<usize>,
 
+    /// Maximum number of aapt2 links running at once, independent of maxParallelBuilds
+    /// A build waiting to link gives up its build slot so other configs can compile meanwhile
+    /// Default: maxParallelBuilds
+    #[serde(rename = "maxParallelLinks", skip_serializing_if = "Option::is_none")]
+    pub max_parallel_links: Option<usize>,
+
+    /// Merged bundle of the app skins written by `asb build --bundle` (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<BundleConfig>,
+
     /// Common package ID setting (optional)
     /// e.g., "0x7f" for standard apps, custom values for dynamic loading
     #[serde(rename = "packageId", skip_serializing_if = "Option::is_none")]
@@ -243,6 +490,208 @@ pub struct MultiAppConfig {
     #[serde(rename = "assetsDir", skip_serializing_if = "Option::is_none")]
     pub assets_dir: Option<PathBuf>,
 
+    /// Follow symbolic links when discovering resources (optional)
+    #[serde(rename = "followSymlinks", skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+
+    /// Also exclude files matched by `.gitignore` when discovering resources (optional)
+    #[serde(rename = "respectGitignore", skip_serializing_if = "Option::is_none")]
+    pub respect_gitignore: Option<bool>,
+
+    /// Common packaging options for the final skin package (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packaging: Option<PackagingConfig>,
+
+    /// Common link input mode (optional)
+    #[serde(rename = "linkInputMode", skip_serializing_if = "Option::is_none")]
+    pub link_input_mode: Option<LinkInputMode>,
+
+    /// Common per-resource-type compile options (optional)
+    #[serde(rename = "compileOptions", skip_serializing_if = "Option::is_none")]
+    pub compile_options: Option<BTreeMap<String, CompileFlags>>,
+
+    /// Common aapt2 process limits (optional)
+    #[serde(rename = "aapt2Limits", skip_serializing_if = "Option::is_none")]
+    pub aapt2_limits: Option<Aapt2Limits>,
+
+    /// Common heartbeat and stall detection settings (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HeartbeatConfig>,
+
+    /// Link common dependencies into a shared static library (optional)
+    #[serde(
+        rename = "staticLibDependencies",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub static_lib_dependencies: Option<bool>,
+
+    /// Common prebuilt `.flat` files, static-lib APKs or directories of them (optional)
+    #[serde(
+        rename = "precompiledDependencies",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub precompiled_dependencies: Option<Vec<PathBuf>>,
+
+    /// Common namespaced linking setting (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespaced: Option<bool>,
+
+    /// Allocate unique package IDs across all apps (optional)
+    #[serde(
+        rename = "autoAssignPackageIds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub auto_assign_package_ids: Option<bool>,
+
+    /// Common manifest mode (optional)
+    #[serde(rename = "manifestMode", skip_serializing_if = "Option::is_none")]
+    pub manifest_mode: Option<ManifestMode>,
+
+    /// Common manifest placeholders (optional)
+    #[serde(
+        rename = "manifestPlaceholders",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub manifest_placeholders: Option<BTreeMap<String, String>>,
+
+    /// Common AAR manifest merge setting (optional)
+    #[serde(rename = "mergeAarManifests", skip_serializing_if = "Option::is_none")]
+    pub merge_aar_manifests: Option<bool>,
+
+    /// Common output layout (optional)
+    #[serde(rename = "outputLayout", skip_serializing_if = "Option::is_none")]
+    pub output_layout: Option<OutputLayout>,
+
+    /// Common build type used by the gradle output layout (optional)
+    #[serde(rename = "buildType", skip_serializing_if = "Option::is_none")]
+    pub build_type: Option<String>,
+
+    /// Common git metadata settings (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsConfig>,
+
+    /// Common versionCode strategy (optional)
+    #[serde(
+        rename = "versionCodeStrategy",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version_code_strategy: Option<VersionCodeStrategy>,
+
+    /// Common dark-mode variant generation (optional)
+    #[serde(rename = "darkMode", skip_serializing_if = "Option::is_none")]
+    pub dark_mode: Option<DarkModeConfig>,
+
+    /// Common resource name prefix (optional)
+    #[serde(rename = "resourcePrefix", skip_serializing_if = "Option::is_none")]
+    pub resource_prefix: Option<String>,
+
+    /// Common reference aliases (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<BTreeMap<String, String>>,
+
+    /// Common reference validation setting (optional)
+    #[serde(rename = "validateReferences", skip_serializing_if = "Option::is_none")]
+    pub validate_references: Option<bool>,
+
+    /// Common XML schema validation setting (optional)
+    #[serde(rename = "validateXml", skip_serializing_if = "Option::is_none")]
+    pub validate_xml: Option<bool>,
+
+    /// Common file name auto-fixing setting (optional)
+    #[serde(rename = "autoFixFilenames", skip_serializing_if = "Option::is_none")]
+    pub auto_fix_filenames: Option<bool>,
+
+    /// Common locale qualifier form (optional)
+    #[serde(rename = "localeQualifiers", skip_serializing_if = "Option::is_none")]
+    pub locale_qualifiers: Option<LocaleQualifierForm>,
+
+    /// Common launcher icon checks (optional)
+    #[serde(rename = "launcherIcons", skip_serializing_if = "Option::is_none")]
+    pub launcher_icons: Option<LauncherIconsConfig>,
+
+    /// Common color contrast lint settings (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contrast: Option<ContrastConfig>,
+
+    /// Common golden-image comparison of drawables (optional)
+    #[serde(rename = "goldenImages", skip_serializing_if = "Option::is_none")]
+    pub golden_images: Option<GoldenImagesConfig>,
+
+    /// Common staging setting (optional)
+    #[serde(rename = "stageResources", skip_serializing_if = "Option::is_none")]
+    pub stage_resources: Option<bool>,
+
+    /// Common encoding conversion setting (optional)
+    #[serde(rename = "convertEncodings", skip_serializing_if = "Option::is_none")]
+    pub convert_encodings: Option<bool>,
+
+    /// Common build hooks (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
+
+    /// Common plugins (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<PluginConfig>>,
+
+    /// Common missing additional resource directory policy (optional)
+    #[serde(
+        rename = "missingResourceDirPolicy",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub missing_resource_dir_policy: Option<MissingResourceDirPolicy>,
+
+    /// Common host symbol list (optional)
+    #[serde(rename = "hostSymbols", skip_serializing_if = "Option::is_none")]
+    pub host_symbols: Option<PathBuf>,
+
+    /// Common owners file (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<PathBuf>,
+
+    /// Common freeze file, usually with `${config:packageName}` in the path (optional)
+    #[serde(rename = "freezeFile", skip_serializing_if = "Option::is_none")]
+    pub freeze_file: Option<PathBuf>,
+
+    /// Common host versions (optional)
+    #[serde(rename = "hostVersions", skip_serializing_if = "Option::is_none")]
+    pub host_versions: Option<Vec<HostVersion>>,
+
+    /// Common feature flags (optional)
+    #[serde(rename = "featureFlags", skip_serializing_if = "Option::is_none")]
+    pub feature_flags: Option<BTreeMap<String, bool>>,
+
+    /// Common scheduled resource files (optional)
+    #[serde(rename = "scheduledFiles", skip_serializing_if = "Option::is_none")]
+    pub scheduled_files: Option<Vec<ScheduledFiles>>,
+
+    /// Common build date for date windows, `YYYY-MM-DD` (optional)
+    #[serde(rename = "asOf", skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<String>,
+
+    /// Common build audit log setting (optional)
+    #[serde(rename = "auditLog", skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<bool>,
+
+    /// Common strict mode setting (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+
+    /// Common warnings-as-errors setting (optional)
+    #[serde(rename = "warningsAsErrors", skip_serializing_if = "Option::is_none")]
+    pub warnings_as_errors: Option<bool>,
+
+    /// Common size budget (optional)
+    #[serde(rename = "sizeBudget", skip_serializing_if = "Option::is_none")]
+    pub size_budget: Option<SizeBudget>,
+
+    /// Common disk space check (optional)
+    #[serde(rename = "diskSpace", skip_serializing_if = "Option::is_none")]
+    pub disk_space: Option<DiskSpaceConfig>,
+
+    /// Common AAR resource filters (optional)
+    #[serde(rename = "aarFilters", skip_serializing_if = "Option::is_none")]
+    pub aar_filters: Option<BTreeMap<String, AarResourceFilter>>,
+
     /// Array of app-specific configurations
     pub apps: Vec<AppConfig>,
 }
@@ -250,67 +699,23 @@ pub struct MultiAppConfig {
 impl MultiAppConfig {
     /// Convert multi-app config to individual BuildConfig instances
     /// Merges common fields with app-specific fields and expands flavors
-    pub fn into_build_configs(self) -> Vec<BuildConfig> {
+    pub fn into_build_configs(mut self) -> Vec<BuildConfig> {
         let mut result = Vec::new();
 
-        // Store common config fields that will be shared
-        let common_base_dir = self.base_dir.clone();
-        let common_output_dir = self.output_dir.clone();
-        let common_output_file = self.output_file.clone();
-        let common_android_jar = self.android_jar.clone();
-        let common_aapt2_path = self.aapt2_path.clone();
-        let common_aar_files = self.aar_files.clone();
-        let common_incremental = self.incremental;
-        let common_build_dir = self.build_dir.clone();
-        let common_cache_dir = self.cache_dir.clone();
-        let common_version_code = self.version_code;
-        let common_version_name = self.version_name.clone();
-        let common_stable_ids_file = self.stable_ids_file.clone();
-        let common_package_id = self.package_id.clone();
-        let common_assets_dir = self.assets_dir.clone();
-
-        for app in self.apps {
+        // Apps are taken out so the remaining common fields can be shared by reference
+        let apps = std::mem::take(&mut self.apps);
+
+        for app in apps.into_iter().flat_map(AppConfig::expand_matrix) {
             // If app has flavors, create a BuildConfig for each flavor
             if let Some(ref flavors) = app.flavors {
                 for flavor in flavors {
                     result.push(Self::create_build_config_for_flavor_static(
-                        &app,
-                        flavor,
-                        &common_base_dir,
-                        &common_output_dir,
-                        &common_output_file,
-                        &common_android_jar,
-                        &common_aapt2_path,
-                        &common_aar_files,
-                        common_incremental,
-                        &common_build_dir,
-                        &common_cache_dir,
-                        common_version_code,
-                        &common_version_name,
-                        &common_stable_ids_file,
-                        &common_package_id,
-                        &common_assets_dir,
+                        &app, flavor, &self,
                     ));
                 }
             } else {
                 // No flavors, create a single BuildConfig
-                result.push(Self::create_build_config_static(
-                    &app,
-                    &common_base_dir,
-                    &common_output_dir,
-                    &common_output_file,
-                    &common_android_jar,
-                    &common_aapt2_path,
-                    &common_aar_files,
-                    common_incremental,
-                    &common_build_dir,
-                    &common_cache_dir,
-                    common_version_code,
-                    &common_version_name,
-                    &common_stable_ids_file,
-                    &common_package_id,
-                    &common_assets_dir,
-                ));
+                result.push(Self::create_build_config_static(&app, &self));
             }
         }
 
@@ -318,26 +723,9 @@ impl MultiAppConfig {
     }
 
     /// Create a BuildConfig from app config without flavor (static version)
-    #[allow(clippy::too_many_arguments)]
-    fn create_build_config_static(
-        app: &AppConfig,
-        common_base_dir: &Option<PathBuf>,
-        common_output_dir: &Path,
-        common_output_file: &Option<String>,
-        common_android_jar: &Option<PathBuf>,
-        common_aapt2_path: &Option<PathBuf>,
-        common_aar_files: &Option<Vec<PathBuf>>,
-        common_incremental: Option<bool>,
-        common_build_dir: &Option<PathBuf>,
-        common_cache_dir: &Option<PathBuf>,
-        common_version_code: Option<u32>,
-        common_version_name: &Option<String>,
-        common_stable_ids_file: &Option<PathBuf>,
-        common_package_id: &Option<String>,
-        common_assets_dir: &Option<PathBuf>,
-    ) -> BuildConfig {
+    fn create_build_config_static(app: &AppConfig, common: &MultiAppConfig) -> BuildConfig {
         // Determine base_dir: app-specific > common
-        let base_dir = app.base_dir.clone().or_else(|| common_base_dir.clone());
+        let base_dir = app.base_dir.clone().or_else(|| common.base_dir.clone());
 
         // Determine resource_dir with defaults
         let resource_dir = app
@@ -359,58 +747,119 @@ impl MultiAppConfig {
             output_dir: app
                 .output_dir
                 .clone()
-                .unwrap_or_else(|| common_output_dir.to_path_buf()),
+                .unwrap_or_else(|| common.output_dir.clone()),
             output_file: app
                 .output_file
                 .clone()
-                .or_else(|| common_output_file.clone()),
+                .or_else(|| common.output_file.clone()),
             package_name: app.package_name.clone(),
-            aapt2_path: common_aapt2_path.clone(),
-            android_jar: common_android_jar.clone(),
-            aar_files: common_aar_files.clone(),
-            incremental: common_incremental,
-            build_dir: common_build_dir.clone(),
-            cache_dir: common_cache_dir.clone(),
-            version_code: app.version_code.or(common_version_code),
+            aapt2_path: common.aapt2_path.clone(),
+            android_jar: common.android_jar.clone(),
+            aar_files: common.aar_files.clone(),
+            incremental: common.incremental,
+            build_dir: common.build_dir.clone(),
+            cache_dir: common.cache_dir.clone(),
+            version_code: app.version_code.or(common.version_code),
             version_name: app
                 .version_name
                 .clone()
-                .or_else(|| common_version_name.clone()),
+                .or_else(|| common.version_name.clone()),
             additional_resource_dirs: app.additional_resource_dirs.clone(),
             compiled_dir: None,
-            stable_ids_file: common_stable_ids_file.clone(),
-            package_id: app.package_id.clone().or_else(|| common_package_id.clone()),
-            precompiled_dependencies: None,
-            assets_dir: app.assets_dir.clone().or_else(|| common_assets_dir.clone()),
+            stable_ids_file: common.stable_ids_file.clone(),
+            package_id: app.package_id.clone().or_else(|| common.package_id.clone()),
+            precompiled_dependencies: common.precompiled_dependencies.clone(),
+            namespaced: common.namespaced,
+            auto_assign_package_ids: common.auto_assign_package_ids,
+            manifest_mode: common.manifest_mode,
+            manifest_placeholders: common.manifest_placeholders.clone(),
+            merge_aar_manifests: common.merge_aar_manifests,
+            output_layout: common.output_layout.clone(),
+            app: Some(app.target_name().to_string()),
+            flavor: None,
+            build_type: common.build_type.clone(),
+            vcs: common.vcs.clone(),
+            version_code_strategy: common.version_code_strategy.clone(),
+            design_tokens: app.design_tokens.clone(),
+            dark_mode: common.dark_mode.clone(),
+            resource_prefix: app
+                .resource_prefix
+                .clone()
+                .or_else(|| common.resource_prefix.clone()),
+            aliases: app.aliases.clone().or_else(|| common.aliases.clone()),
+            validate_references: common.validate_references,
+            validate_xml: common.validate_xml,
+            auto_fix_filenames: common.auto_fix_filenames,
+            locale_qualifiers: common.locale_qualifiers,
+            launcher_icons: common.launcher_icons.clone(),
+            contrast: common.contrast.clone(),
+            golden_images: common.golden_images.clone(),
+            stage_resources: common.stage_resources,
+            convert_encodings: common.convert_encodings,
+            hooks: common.hooks.clone(),
+            plugins: common.plugins.clone(),
+            missing_resource_dir_policy: common.missing_resource_dir_policy,
+            host_symbols: app
+                .host_symbols
+                .clone()
+                .or_else(|| common.host_symbols.clone()),
+            owners: app.owners.clone().or_else(|| common.owners.clone()),
+            freeze_file: app
+                .freeze_file
+                .clone()
+                .or_else(|| common.freeze_file.clone()),
+            host_versions: app
+                .host_versions
+                .clone()
+                .or_else(|| common.host_versions.clone()),
+            feature_flags: merge_feature_flags(
+                common.feature_flags.as_ref(),
+                app.feature_flags.as_ref(),
+            ),
+            conditional_resource_dirs: app.conditional_resource_dirs.clone(),
+            scheduled_files: app
+                .scheduled_files
+                .clone()
+                .or_else(|| common.scheduled_files.clone()),
+            as_of: common.as_of.clone(),
+            audit_log: common.audit_log,
+            strict: app.strict.or(common.strict),
+            warnings_as_errors: common.warnings_as_errors,
+            size_budget: app
+                .size_budget
+                .clone()
+                .or_else(|| common.size_budget.clone()),
+            disk_space: common.disk_space.clone(),
+            aar_filters: app
+                .aar_filters
+                .clone()
+                .or_else(|| common.aar_filters.clone()),
+            precompiled_common_deps: None,
+            only_resource_types: None,
+            assets_dir: app.assets_dir.clone().or_else(|| common.assets_dir.clone()),
+            follow_symlinks: app.follow_symlinks.or(common.follow_symlinks),
+            respect_gitignore: common.respect_gitignore,
+            packaging: common.packaging.clone(),
+            link_input_mode: common.link_input_mode,
+            compile_options: common.compile_options.clone(),
+            aapt2_limits: common.aapt2_limits,
+            heartbeat: common.heartbeat,
+            static_lib_dependencies: common.static_lib_dependencies,
         }
     }
 
     /// Create a BuildConfig from app config with a specific flavor (static version)
-    #[allow(clippy::too_many_arguments)]
     fn create_build_config_for_flavor_static(
         app: &AppConfig,
         flavor: &FlavorConfig,
-        common_base_dir: &Option<PathBuf>,
-        common_output_dir: &Path,
-        common_output_file: &Option<String>,
-        common_android_jar: &Option<PathBuf>,
-        common_aapt2_path: &Option<PathBuf>,
-        common_aar_files: &Option<Vec<PathBuf>>,
-        common_incremental: Option<bool>,
-        common_build_dir: &Option<PathBuf>,
-        common_cache_dir: &Option<PathBuf>,
-        common_version_code: Option<u32>,
-        common_version_name: &Option<String>,
-        common_stable_ids_file: &Option<PathBuf>,
-        common_package_id: &Option<String>,
-        common_assets_dir: &Option<PathBuf>,
+        common: &MultiAppConfig,
     ) -> BuildConfig {
         // Determine base_dir: flavor > app > common
         let base_dir = flavor
             .base_dir
             .clone()
             .or_else(|| app.base_dir.clone())
-            .or_else(|| common_base_dir.clone());
+            .or_else(|| common.base_dir.clone());
 
         // Determine resource_dir: flavor > app > base_dir default
         let resource_dir = flavor
@@ -439,7 +888,7 @@ impl MultiAppConfig {
             .output_file
             .clone()
             .or_else(|| app.output_file.clone())
-            .or_else(|| common_output_file.clone());
+            .or_else(|| common.output_file.clone());
 
         // Determine additional_resource_dirs: flavor overrides app (not merged)
         let additional_resource_dirs = flavor
@@ -454,145 +903,1604 @@ impl MultiAppConfig {
                 .output_dir
                 .clone()
                 .or_else(|| app.output_dir.clone())
-                .unwrap_or_else(|| common_output_dir.to_path_buf()),
+                .unwrap_or_else(|| common.output_dir.clone()),
             output_file,
             package_name,
-            aapt2_path: common_aapt2_path.clone(),
-            android_jar: common_android_jar.clone(),
-            aar_files: common_aar_files.clone(),
-            incremental: common_incremental,
-            build_dir: common_build_dir.clone(),
-            cache_dir: common_cache_dir.clone(),
+            aapt2_path: common.aapt2_path.clone(),
+            android_jar: common.android_jar.clone(),
+            aar_files: common.aar_files.clone(),
+            incremental: common.incremental,
+            build_dir: common.build_dir.clone(),
+            cache_dir: common.cache_dir.clone(),
             version_code: flavor
                 .version_code
                 .or(app.version_code)
-                .or(common_version_code),
+                .or(common.version_code),
             version_name: flavor
                 .version_name
                 .clone()
                 .or_else(|| app.version_name.clone())
-                .or_else(|| common_version_name.clone()),
+                .or_else(|| common.version_name.clone()),
             additional_resource_dirs,
             compiled_dir: None,
-            stable_ids_file: common_stable_ids_file.clone(),
+            stable_ids_file: common.stable_ids_file.clone(),
             package_id: flavor
                 .package_id
                 .clone()
                 .or_else(|| app.package_id.clone())
-                .or_else(|| common_package_id.clone()),
-            precompiled_dependencies: None,
+                .or_else(|| common.package_id.clone()),
+            precompiled_dependencies: common.precompiled_dependencies.clone(),
+            namespaced: common.namespaced,
+            auto_assign_package_ids: common.auto_assign_package_ids,
+            manifest_mode: common.manifest_mode,
+            manifest_placeholders: common.manifest_placeholders.clone(),
+            merge_aar_manifests: common.merge_aar_manifests,
+            output_layout: common.output_layout.clone(),
+            app: Some(app.target_name().to_string()),
+            flavor: Some(flavor.name.clone()),
+            build_type: common.build_type.clone(),
+            vcs: common.vcs.clone(),
+            version_code_strategy: common.version_code_strategy.clone(),
+            design_tokens: flavor
+                .design_tokens
+                .clone()
+                .or_else(|| app.design_tokens.clone()),
+            dark_mode: common.dark_mode.clone(),
+            resource_prefix: app
+                .resource_prefix
+                .clone()
+                .or_else(|| common.resource_prefix.clone()),
+            aliases: app.aliases.clone().or_else(|| common.aliases.clone()),
+            validate_references: common.validate_references,
+            validate_xml: common.validate_xml,
+            auto_fix_filenames: common.auto_fix_filenames,
+            locale_qualifiers: common.locale_qualifiers,
+            launcher_icons: common.launcher_icons.clone(),
+            contrast: common.contrast.clone(),
+            golden_images: common.golden_images.clone(),
+            stage_resources: common.stage_resources,
+            convert_encodings: common.convert_encodings,
+            hooks: common.hooks.clone(),
+            plugins: common.plugins.clone(),
+            missing_resource_dir_policy: common.missing_resource_dir_policy,
+            host_symbols: app
+                .host_symbols
+                .clone()
+                .or_else(|| common.host_symbols.clone()),
+            owners: app.owners.clone().or_else(|| common.owners.clone()),
+            freeze_file: app
+                .freeze_file
+                .clone()
+                .or_else(|| common.freeze_file.clone()),
+            host_versions: app
+                .host_versions
+                .clone()
+                .or_else(|| common.host_versions.clone()),
+            feature_flags: merge_feature_flags(
+                common.feature_flags.as_ref(),
+                app.feature_flags.as_ref(),
+            ),
+            conditional_resource_dirs: app.conditional_resource_dirs.clone(),
+            scheduled_files: app
+                .scheduled_files
+                .clone()
+                .or_else(|| common.scheduled_files.clone()),
+            as_of: common.as_of.clone(),
+            audit_log: common.audit_log,
+            strict: app.strict.or(common.strict),
+            warnings_as_errors: common.warnings_as_errors,
+            size_budget: app
+                .size_budget
+                .clone()
+                .or_else(|| common.size_budget.clone()),
+            disk_space: common.disk_space.clone(),
+            aar_filters: app
+                .aar_filters
+                .clone()
+                .or_else(|| common.aar_filters.clone()),
+            precompiled_common_deps: None,
+            only_resource_types: None,
             assets_dir: flavor
                 .assets_dir
                 .clone()
                 .or_else(|| app.assets_dir.clone())
-                .or_else(|| common_assets_dir.clone()),
+                .or_else(|| common.assets_dir.clone()),
+            follow_symlinks: flavor
+                .follow_symlinks
+                .or(app.follow_symlinks)
+                .or(common.follow_symlinks),
+            respect_gitignore: common.respect_gitignore,
+            packaging: common.packaging.clone(),
+            link_input_mode: common.link_input_mode,
+            compile_options: common.compile_options.clone(),
+            aapt2_limits: common.aapt2_limits,
+            heartbeat: common.heartbeat,
+            static_lib_dependencies: common.static_lib_dependencies,
         }
     }
 }
 
-/// Configuration for building Android skin packages
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BuildConfig {
-    /// Path to the resources directory (res/)
-    #[serde(rename = "resourceDir")]
-    pub resource_dir: PathBuf,
+/// Git commit/branch/dirty capture settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VcsConfig {
+    /// Capture git state into the skin metadata and build report (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Append "-<commit>[-dirty]" to versionName (default: false)
+    #[serde(rename = "versionNameSuffix", skip_serializing_if = "Option::is_none")]
+    pub version_name_suffix: Option<bool>,
+}
 
-    /// Path to the Android manifest file
-    #[serde(rename = "manifestPath")]
-    pub manifest_path: PathBuf,
+/// How versionCode is computed when it is not set explicitly
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum VersionCodeStrategy {
+    /// A constant value
+    Fixed { value: u32 },
+    /// Seconds since the Unix epoch at build time
+    Timestamp,
+    /// Number of commits reachable from HEAD, plus an optional offset
+    GitCommitCount {
+        #[serde(default)]
+        offset: u32,
+    },
+    /// Parsed from an environment variable, e.g. a CI build number
+    FromEnv { variable: String },
+}
 
-    /// Output directory for the skin package
-    #[serde(rename = "outputDir")]
-    pub output_dir: PathBuf,
+/// Directory layout for build outputs and intermediates
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OutputLayout {
+    /// Packages go straight into `outputDir`, intermediates into `buildDir`
+    #[default]
+    Flat,
+    /// AGP conventions: `outputDir` is treated as Gradle's `build/` directory
+    Gradle,
+    /// Subdirectory of `outputDir` per package, e.g. `{brand}/{flavor}/`, holding its
+    /// package, reports and logs; intermediates stay shared in `outputDir/.build`
+    Template(String),
+}
 
-    /// Output file name for the skin package (optional)
-    /// If not specified, defaults to {packageName}.skin
-    #[serde(rename = "outputFile", skip_serializing_if = "Option::is_none")]
-    pub output_file: Option<String>,
+impl TryFrom<String> for OutputLayout {
+    type Error = String;
 
-    /// Package name for the skin package
-    #[serde(rename = "packageName")]
-    pub package_name: String,
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "flat" => Ok(Self::Flat),
+            "gradle" => Ok(Self::Gradle),
+            _ if value.trim().is_empty() => {
+                Err("outputLayout must be flat, gradle or a path template".to_string())
+            }
+            _ => Ok(Self::Template(value)),
+        }
+    }
+}
 
-    /// Path to aapt2 binary (optional, will auto-detect if not provided)
-    #[serde(rename = "aapt2Path", skip_serializing_if = "Option::is_none")]
-    pub aapt2_path: Option<PathBuf>,
+impl From<OutputLayout> for String {
+    fn from(layout: OutputLayout) -> Self {
+        match layout {
+            OutputLayout::Flat => "flat".to_string(),
+            OutputLayout::Gradle => "gradle".to_string(),
+            OutputLayout::Template(template) => template,
+        }
+    }
+}
 
-    /// Path to Android platform JAR (android.jar) (optional)
-    /// If not specified, will auto-detect the highest version from ANDROID_HOME/platforms
-    #[serde(rename = "androidJar", skip_serializing_if = "Option::is_none")]
-    pub android_jar: Option<PathBuf>,
+/// Resources taken from an AAR, matched relative to its `res/` directory
+///
+/// Globs without a `/` match the resource directory name (`drawable*`), others the file path
+/// (`values/colors.xml`, `layout/**`). A file is kept when it matches an `include` glob (or
+/// `include` is empty) and no `exclude` glob.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AarResourceFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
 
-    /// Additional AAR files to include resources from
-    #[serde(rename = "aarFiles", skip_serializing_if = "Option::is_none", default)]
-    pub aar_files: Option<Vec<PathBuf>>,
+impl AarResourceFilter {
+    /// Whether the file at `relative_path` (e.g. `values/colors.xml`) is kept
+    pub fn keeps(&self, relative_path: &str) -> bool {
+        let dir_name = relative_path.split('/').next().unwrap_or(relative_path);
+        let matches = |pattern: &String| {
+            if pattern.contains('/') {
+                glob_match(pattern, relative_path)
+            } else {
+                wildcard_match(pattern, dir_name)
+            }
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
 
-    /// Enable incremental build
-    #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub incremental: Option<bool>,
+/// What a build does when an additional resource directory does not exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingResourceDirPolicy {
+    /// Fail the build
+    #[default]
+    Error,
+    /// Build without the directory and report a warning
+    Warn,
+    /// Build without the directory
+    Ignore,
+}
 
-    /// Build directory for intermediate files and cache
-    /// Used to store compiled resources and build cache
-    /// If not specified, defaults to {outputDir}/.build
-    #[serde(rename = "buildDir", skip_serializing_if = "Option::is_none")]
-    pub build_dir: Option<PathBuf>,
+/// Which AndroidManifest.xml is passed to aapt2 link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestMode {
+    /// Generate `<manifest package="..."/>` and ignore `manifestPath`
+    #[default]
+    Minimal,
+    /// Link with the manifest at `manifestPath` as-is
+    User,
+    /// Merge the user manifest's elements (permissions excluded) into the generated one
+    Merge,
+}
 
-    /// Build cache directory (deprecated, use buildDir instead)
-    /// If specified, takes precedence over buildDir for cache location
-    #[serde(rename = "cacheDir", skip_serializing_if = "Option::is_none")]
-    pub cache_dir: Option<PathBuf>,
+/// Compression applied to entries when the skin package is rewritten
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionPolicy {
+    /// Keep the compression method aapt2 chose for each entry
+    #[default]
+    Preserve,
+    /// Store every entry uncompressed
+    Store,
+    /// Deflate every entry (resources.arsc is always stored)
+    Deflate,
+}
 
-    /// Version code for the skin package
-    #[serde(rename = "versionCode", skip_serializing_if = "Option::is_none")]
-    pub version_code: Option<u32>,
+/// aapt2 compile flags applied to one resource type
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileFlags {
+    /// Pass `--no-crunch` (skip PNG crunching for pre-optimized images)
+    #[serde(rename = "noCrunch", skip_serializing_if = "Option::is_none")]
+    pub no_crunch: Option<bool>,
 
-    /// Version name for the skin package
-    #[serde(rename = "versionName", skip_serializing_if = "Option::is_none")]
-    pub version_name: Option<String>,
+    /// Pass `--pseudo-localize` (generate en-XA / ar-XB pseudo locales)
+    #[serde(rename = "pseudoLocalize", skip_serializing_if = "Option::is_none")]
+    pub pseudo_localize: Option<bool>,
 
-    /// Additional resource directories
-    #[serde(
-        rename = "additionalResourceDirs",
-        skip_serializing_if = "Option::is_none",
-        default
-    )]
-    pub additional_resource_dirs: Option<Vec<PathBuf>>,
+    /// Pass `--legacy` (treat some errors as warnings, like aapt did)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legacy: Option<bool>,
+}
 
-    /// Compiled resource directory (for intermediate .flat files)
-    #[serde(rename = "compiledDir", skip_serializing_if = "Option::is_none")]
-    pub compiled_dir: Option<PathBuf>,
+/// Periodic progress logs and stall warnings during a build
+///
+/// Either interval can be set to 0 to turn it off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatConfig {
+    /// Seconds between heartbeat logs (optional, default: 30)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+
+    /// Seconds without progress before the build is reported as stalled (optional, default: 120)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stall_secs: Option<u64>,
+}
 
-    /// Path to stable IDs file for consistent resource IDs
-    #[serde(rename = "stableIdsFile", skip_serializing_if = "Option::is_none")]
-    pub stable_ids_file: Option<PathBuf>,
+/// Limits applied to each aapt2 process; a process exceeding them is killed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aapt2Limits {
+    /// Seconds a single aapt2 invocation may run (optional, default: unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// Resident memory in MiB an aapt2 process may use (optional, Linux only)
+    #[serde(rename = "maxMemoryMB", skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+}
 
-    /// Package ID for resources (e.g., "0x7f" for standard apps)
-    /// This is critical for dynamic resource loading via new Resources()
-    /// If not specified, defaults to "0x7f"
-    #[serde(rename = "packageId", skip_serializing_if = "Option::is_none")]
-    pub package_id: Option<String>,
+impl Aapt2Limits {
+    /// Which limit the process `pid`, running for `elapsed`, has exceeded, if any
+    pub fn exceeded(&self, pid: u32, elapsed: Duration) -> Option<String> {
+        if let Some(timeout_secs) = self.timeout_secs
+            && elapsed >= Duration::from_secs(timeout_secs)
+        {
+            return Some(format!("exceeding the {}s timeout", timeout_secs));
+        }
+        if let Some(max_memory_mb) = self.max_memory_mb
+            && let Some(resident_mb) = resident_memory_mb(pid)
+            && resident_mb > max_memory_mb
+        {
+            return Some(format!(
+                "using {} MiB, above the {} MiB memory limit",
+                resident_mb, max_memory_mb
+            ));
+        }
+        None
+    }
+}
 
-    /// Path to the assets directory (optional)
-    /// Assets are raw files that are packaged directly into the APK
-    /// without compilation, accessible via Android's AssetManager
-    #[serde(rename = "assetsDir", skip_serializing_if = "Option::is_none")]
-    pub assets_dir: Option<PathBuf>,
+/// Resident memory of a process in MiB
+#[cfg(target_os = "linux")]
+fn resident_memory_mb(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb / 1024)
+}
 
-    /// Pre-compiled common dependencies (runtime only, not serialized)
-    /// Map from resource directory path to compiled flat files
-    #[serde(skip, default)]
-    #[allow(dead_code)]
-    pub precompiled_dependencies: Option<std::collections::HashMap<PathBuf, Vec<PathBuf>>>,
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_mb(_pid: u32) -> Option<u64> {
+    None
 }
 
-impl BuildConfig {
-    /// Create default configuration based on standard Android project structure
-    pub fn default_config() -> Self {
-        Self {
-            resource_dir: PathBuf::from("./src/main/res"),
-            manifest_path: PathBuf::from("./src/main/AndroidManifest.xml"),
-            output_dir: PathBuf::from("./build/outputs/skin"),
-            output_file: None,
+/// Automatic dark-mode (`-night`) variant generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DarkModeConfig {
+    /// JSON file mapping light colors to dark ones, by resource name and by value
+    #[serde(rename = "colorMapping")]
+    pub color_mapping: PathBuf,
+
+    /// XML drawables (by name) that get a recolored `-night` copy (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drawables: Option<Vec<String>>,
+}
+
+/// Packaging options for the final skin package
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackagingConfig {
+    /// Byte alignment for uncompressed entries, as zipalign does (default: 4, 0 disables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alignment: Option<u16>,
+
+    /// Compression policy for entries (default: preserve)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionPolicy>,
+
+    /// Use fixed timestamps and permissions so identical inputs produce identical bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reproducible: Option<bool>,
+
+    /// Extra files to add, keyed by their path inside the package
+    #[serde(rename = "extraFiles", skip_serializing_if = "Option::is_none")]
+    pub extra_files: Option<BTreeMap<String, PathBuf>>,
+
+    /// Embed skin metadata (package name, version) as assets/asb-skin.json
+    #[serde(rename = "skinMetadata", skip_serializing_if = "Option::is_none")]
+    pub skin_metadata: Option<bool>,
+}
+
+/// Shell commands run at points of the build pipeline
+///
+/// Commands run through `sh -c` (`cmd /C` on Windows) in the current directory, in order,
+/// with the package's environment (`ASB_PACKAGE`, `ASB_OUTPUT`, ...) and a scratch
+/// directory of their own.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HooksConfig {
+    /// Run before resources are discovered, e.g. asset generators writing into `resourceDir`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_build: Vec<HookCommand>,
+
+    /// Run after resources are compiled, before linking
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_compile: Vec<HookCommand>,
+
+    /// Run after the package is written, e.g. upload scripts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_build: Vec<HookCommand>,
+
+    /// Seconds each command may run before it is killed (optional, default: unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// What a failing command does to the build (optional, default: fail)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<HookFailurePolicy>,
+}
+
+/// A hook entry: a command, or `{ "command": ..., "exclusive": true }` to keep it from
+/// running while another package runs an exclusive hook
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HookCommand {
+    Command(String),
+    Detailed {
+        command: String,
+        #[serde(default)]
+        exclusive: bool,
+    },
+}
+
+impl HookCommand {
+    pub fn command(&self) -> &str {
+        match self {
+            Self::Command(command) | Self::Detailed { command, .. } => command,
+        }
+    }
+
+    /// Whether the command must not overlap with other exclusive hooks
+    pub fn exclusive(&self) -> bool {
+        matches!(
+            self,
+            Self::Detailed {
+                exclusive: true,
+                ..
+            }
+        )
+    }
+}
+
+impl From<&str> for HookCommand {
+    fn from(command: &str) -> Self {
+        Self::Command(command.to_string())
+    }
+}
+
+/// Launcher icon checks; bitmap icons are the files of `mipmap` density directories
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LauncherIconsConfig {
+    /// Densities every bitmap icon must provide (optional, default: mdpi to xxxhdpi)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub densities: Option<Vec<String>>,
+
+    /// Generate missing densities below the highest-resolution PNG from it (optional,
+    /// default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generate: Option<bool>,
+}
+
+/// Color contrast lint settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContrastConfig {
+    /// Pairs to check (optional, default: the Material "on" color pairs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pairs: Option<Vec<ContrastPair>>,
+
+    /// Minimum contrast ratio of pairs without their own (optional, default: 4.5, WCAG AA
+    /// for normal text)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ratio: Option<f64>,
+}
+
+/// Symbol list of one supported host version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostVersion {
+    /// Version name used in the report, e.g. "3.2"
+    pub version: String,
+
+    /// aapt2 `R.txt` or `@type/name` list of the host version, as for `hostSymbols`
+    pub symbols: PathBuf,
+}
+
+/// A resource directory gated by a feature flag and/or a date window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalResourceDir {
+    pub path: PathBuf,
+
+    /// Flag name (the `flag_` prefix is optional), or `!name` to include when the flag is off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_if: Option<String>,
+
+    /// First day the directory is included, `YYYY-MM-DD` or `MM-DD` for every year
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Last day the directory is included, in the same form as `from`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+/// Resource files included only within a date window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledFiles {
+    /// Path below a resource directory, e.g. `drawable*/holiday_*` (`*` and `?` allowed)
+    pub pattern: String,
+
+    /// First day the files are included, `YYYY-MM-DD` or `MM-DD` for every year
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Last day the files are included, in the same form as `from`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+/// Size limits checked after a build
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeBudget {
+    /// Largest package file, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_package_bytes: Option<u64>,
+
+    /// Largest single resource file of the skin's own resource directories, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_bytes: Option<u64>,
+}
+
+/// Free disk space checked before a build starts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceConfig {
+    /// Space to keep free on the disks of the output and build directories on top of
+    /// what the build is estimated to write, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_free_bytes: Option<u64>,
+
+    /// Free space the system temp directory needs, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_temp_bytes: Option<u64>,
+
+    /// Delete the incremental caches of the packages being built when space runs short
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_cache: Option<bool>,
+}
+
+/// Common feature flags with the flags of an app taking precedence
+fn merge_feature_flags(
+    common: Option<&BTreeMap<String, bool>>,
+    app: Option<&BTreeMap<String, bool>>,
+) -> Option<BTreeMap<String, bool>> {
+    match (common, app) {
+        (None, None) => None,
+        _ => Some(
+            common
+                .into_iter()
+                .chain(app)
+                .flat_map(|flags| flags.iter().map(|(name, on)| (name.clone(), *on)))
+                .collect(),
+        ),
+    }
+}
+
+/// Golden-image comparison of drawables
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoldenImagesConfig {
+    /// Drawables to compare as `<directory>/<name>`, e.g. `drawable/ic_logo` or
+    /// `mipmap-xxhdpi/ic_launcher`
+    pub drawables: Vec<String>,
+
+    /// Directory of the golden images, `<dir>/<directory>/<name>.png`
+    pub dir: PathBuf,
+
+    /// Color distance a pixel may differ by, from 0 (exact) to 1 (optional, default: 0.1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+
+    /// Fraction of pixels that may differ (optional, default: 0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_diff_ratio: Option<f64>,
+
+    /// Directory the rasterized and diff images of failing drawables are written to
+    /// (optional, default: the package path with a `.golden-diff` extension)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_dir: Option<PathBuf>,
+}
+
+/// Text (foreground) and background roles, each a color resource or theme attribute name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContrastPair {
+    pub foreground: String,
+    pub background: String,
+
+    /// Minimum contrast ratio of this pair (optional), e.g. 3.0 for large text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ratio: Option<f64>,
+}
+
+/// Form of the locale qualifier in resource directory names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocaleQualifierForm {
+    /// `values-zh-rCN`; locales with a script or variant keep the BCP-47 form
+    Legacy,
+    /// `values-b+zh+CN`
+    Bcp47,
+}
+
+impl LocaleQualifierForm {
+    /// Name of the form in the config
+    pub fn name(self) -> &'static str {
+        match self {
+            LocaleQualifierForm::Legacy => "legacy",
+            LocaleQualifierForm::Bcp47 => "bcp47",
+        }
+    }
+}
+
+/// Effect of a failing hook command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailurePolicy {
+    /// Fail the build; later commands of the hook are skipped
+    #[default]
+    Fail,
+    /// Report a warning and continue with the next command
+    Warn,
+}
+
+/// External executable speaking the plugin protocol (NDJSON over stdio)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginConfig {
+    /// Name shown in logs and errors (optional, default: the command)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Executable to run, looked up in PATH when it has no directory
+    pub command: PathBuf,
+
+    /// Arguments passed to the executable (optional)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Pipeline point the plugin runs at
+    pub kind: PluginKind,
+
+    /// Seconds a single run may take before the plugin is killed (optional, default: unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl PluginConfig {
+    /// Name used in logs and errors
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.command.display().to_string())
+    }
+}
+
+/// Pipeline point of a plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// Receives the files of each resource directory before compilation and returns the
+    /// files to compile instead
+    Preprocessor,
+    /// Receives the finished package and returns the file to ship instead
+    Postprocessor,
+}
+
+/// Configuration for building Android skin packages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildConfig {
+    /// Path to the resources directory (res/)
+    #[serde(rename = "resourceDir")]
+    pub resource_dir: PathBuf,
+
+    /// Path to the Android manifest file
+    #[serde(rename = "manifestPath")]
+    pub manifest_path: PathBuf,
+
+    /// Output directory for the skin package
+    #[serde(rename = "outputDir")]
+    pub output_dir: PathBuf,
+
+    /// Output file name for the skin package (optional)
+    /// If not specified, defaults to {packageName}.skin
+    #[serde(rename = "outputFile", skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+
+    /// Package name for the skin package
+    #[serde(rename = "packageName")]
+    pub package_name: String,
+
+    /// Path to aapt2 binary (optional, will auto-detect if not provided)
+    #[serde(rename = "aapt2Path", skip_serializing_if = "Option::is_none")]
+    pub aapt2_path: Option<PathBuf>,
+
+    /// Path to Android platform JAR (android.jar) (optional)
+    /// If not specified, will auto-detect the highest version from ANDROID_HOME/platforms
+    #[serde(rename = "androidJar", skip_serializing_if = "Option::is_none")]
+    pub android_jar: Option<PathBuf>,
+
+    /// Additional AAR files to include resources from
+    #[serde(rename = "aarFiles", skip_serializing_if = "Option::is_none", default)]
+    pub aar_files: Option<Vec<AarFile>>,
+
+    /// Enable incremental build
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub incremental: Option<bool>,
+
+    /// Build directory for intermediate files and cache
+    /// Used to store compiled resources and build cache
+    /// If not specified, defaults to {outputDir}/.build
+    #[serde(rename = "buildDir", skip_serializing_if = "Option::is_none")]
+    pub build_dir: Option<PathBuf>,
+
+    /// Build cache directory (deprecated, use buildDir instead)
+    /// If specified, takes precedence over buildDir for cache location
+    #[serde(rename = "cacheDir", skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Version code for the skin package
+    #[serde(rename = "versionCode", skip_serializing_if = "Option::is_none")]
+    pub version_code: Option<u32>,
+
+    /// Version name for the skin package
+    #[serde(rename = "versionName", skip_serializing_if = "Option::is_none")]
+    pub version_name: Option<String>,
+
+    /// Additional resource directories
+    #[serde(
+        rename = "additionalResourceDirs",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub additional_resource_dirs: Option<Vec<PathBuf>>,
+
+    /// Compiled resource directory (for intermediate .flat files)
+    #[serde(rename = "compiledDir", skip_serializing_if = "Option::is_none")]
+    pub compiled_dir: Option<PathBuf>,
+
+    /// Path to stable IDs file for consistent resource IDs
+    #[serde(rename = "stableIdsFile", skip_serializing_if = "Option::is_none")]
+    pub stable_ids_file: Option<PathBuf>,
+
+    /// Package ID for resources (e.g., "0x7f" for standard apps)
+    /// This is critical for dynamic resource loading via new Resources()
+    /// If not specified, defaults to "0x7f"
+    #[serde(rename = "packageId", skip_serializing_if = "Option::is_none")]
+    pub package_id: Option<String>,
+
+    /// Path to the assets directory (optional)
+    /// Assets are raw files that are packaged directly into the APK
+    /// without compilation, accessible via Android's AssetManager
+    #[serde(rename = "assetsDir", skip_serializing_if = "Option::is_none")]
+    pub assets_dir: Option<PathBuf>,
+
+    /// Follow symbolic links when discovering resource files
+    /// Linked directories are visited once; cycles are detected and skipped
+    /// Also makes dependency detection compare resource dirs by their link targets
+    #[serde(rename = "followSymlinks", skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+
+    /// Exclude files matched by `.gitignore` files during resource discovery, in addition
+    /// to `.asbignore`, which is always read
+    #[serde(rename = "respectGitignore", skip_serializing_if = "Option::is_none")]
+    pub respect_gitignore: Option<bool>,
+
+    /// Options for rewriting the package produced by aapt2 link
+    /// (entry ordering, alignment, compression, extra files, skin metadata)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packaging: Option<PackagingConfig>,
+
+    /// How flat files are passed to aapt2 link: "auto" (default), "direct", "argfile" or "zip"
+    /// "auto" passes files directly for small builds and uses argument files above 100 files
+    #[serde(rename = "linkInputMode", skip_serializing_if = "Option::is_none")]
+    pub link_input_mode: Option<LinkInputMode>,
+
+    /// aapt2 compile flags keyed by resource type ("drawable", "values", ...)
+    /// The "*" key applies to every type; type-specific entries override it
+    #[serde(rename = "compileOptions", skip_serializing_if = "Option::is_none")]
+    pub compile_options: Option<BTreeMap<String, CompileFlags>>,
+
+    /// Timeout and memory limit for each aapt2 process, e.g. `{ "timeoutSecs": 300 }` (optional)
+    #[serde(rename = "aapt2Limits", skip_serializing_if = "Option::is_none")]
+    pub aapt2_limits: Option<Aapt2Limits>,
+
+    /// Progress heartbeat logs and stall warnings for long builds (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HeartbeatConfig>,
+
+    /// Link shared dependencies once into an aapt2 static library (multi-app builds)
+    /// Each skin then links against the library instead of the dependency's flat files
+    #[serde(
+        rename = "staticLibDependencies",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub static_lib_dependencies: Option<bool>,
+
+    /// Prebuilt resources produced outside ASB (optional)
+    /// Each entry is a `.flat` file, a static-lib `.apk`, or a directory containing them;
+    /// they are linked at Library priority, above AAR resources
+    #[serde(
+        rename = "precompiledDependencies",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub precompiled_dependencies: Option<Vec<PathBuf>>,
+
+    /// Link with full resource namespacing (optional)
+    /// Overlays may only override existing resources, static libraries keep their own
+    /// packages, and stable IDs are kept in a separate file per package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespaced: Option<bool>,
+
+    /// Allocate unique package IDs across every config in the build (optional)
+    /// Assignments are recorded in `asb.package-ids.lock` next to the config file
+    #[serde(
+        rename = "autoAssignPackageIds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub auto_assign_package_ids: Option<bool>,
+
+    /// How the manifest passed to aapt2 is produced (default: minimal)
+    #[serde(rename = "manifestMode", skip_serializing_if = "Option::is_none")]
+    pub manifest_mode: Option<ManifestMode>,
+
+    /// `${name}` placeholders expanded in merged manifests (optional)
+    /// `${applicationId}` and `${packageName}` default to the package name
+    #[serde(
+        rename = "manifestPlaceholders",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub manifest_placeholders: Option<BTreeMap<String, String>>,
+
+    /// Merge `<uses-sdk>`, `<queries>`, `<uses-feature>` and application `<meta-data>`
+    /// from AAR manifests into the linked manifest (optional)
+    #[serde(rename = "mergeAarManifests", skip_serializing_if = "Option::is_none")]
+    pub merge_aar_manifests: Option<bool>,
+
+    /// Output directory layout (default: flat)
+    #[serde(rename = "outputLayout", skip_serializing_if = "Option::is_none")]
+    pub output_layout: Option<OutputLayout>,
+
+    /// App this config was expanded from (optional, set automatically for multi-app configs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+
+    /// Flavor this config was expanded from (optional, set automatically for flavors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flavor: Option<String>,
+
+    /// Build type used by the gradle output layout (default: "release")
+    #[serde(rename = "buildType", skip_serializing_if = "Option::is_none")]
+    pub build_type: Option<String>,
+
+    /// Embed git commit, branch and dirty state into outputs (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsConfig>,
+
+    /// How versionCode is computed when `versionCode` is not set (optional)
+    #[serde(
+        rename = "versionCodeStrategy",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version_code_strategy: Option<VersionCodeStrategy>,
+
+    /// Design-token JSON (W3C design tokens or `{"colors": {...}, "dimens": {...}}`)
+    /// turned into values/colors.xml and values/dimens.xml before compiling (optional)
+    #[serde(rename = "designTokens", skip_serializing_if = "Option::is_none")]
+    pub design_tokens: Option<PathBuf>,
+
+    /// Generate `values-night` colors and `-night` drawables from a color mapping (optional)
+    #[serde(rename = "darkMode", skip_serializing_if = "Option::is_none")]
+    pub dark_mode: Option<DarkModeConfig>,
+
+    /// Prefix applied to every resource name of the skin, with references rewritten (optional)
+    #[serde(rename = "resourcePrefix", skip_serializing_if = "Option::is_none")]
+    pub resource_prefix: Option<String>,
+
+    /// Reference aliases applied to XML resources before compiling, e.g.
+    /// "@drawable/old_name" -> "@drawable/new_name" (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<BTreeMap<String, String>>,
+
+    /// Check that `@type/name` references resolve before compiling (optional, default: false)
+    #[serde(rename = "validateReferences", skip_serializing_if = "Option::is_none")]
+    pub validate_references: Option<bool>,
+
+    /// Check values, drawable and color XML against built-in schemas before compiling,
+    /// reporting every violation with its file and line (optional, default: false)
+    #[serde(rename = "validateXml", skip_serializing_if = "Option::is_none")]
+    pub validate_xml: Option<bool>,
+
+    /// Compile resource files whose names aapt2 rejects from renamed staging copies instead
+    /// of failing the build (optional, default: false)
+    /// The original → staged mapping is written next to the package
+    #[serde(rename = "autoFixFilenames", skip_serializing_if = "Option::is_none")]
+    pub auto_fix_filenames: Option<bool>,
+
+    /// Compile resources from directories whose locale qualifier uses one form, `legacy`
+    /// (`values-zh-rCN`) or `bcp47` (`values-b+zh+CN`), so source sets mixing both forms
+    /// end up with the same configurations (optional, default: keep directory names)
+    #[serde(rename = "localeQualifiers", skip_serializing_if = "Option::is_none")]
+    pub locale_qualifiers: Option<LocaleQualifierForm>,
+
+    /// Check that overridden launcher icons come in every density and that adaptive icons
+    /// have both layers, optionally generating missing densities (optional)
+    #[serde(rename = "launcherIcons", skip_serializing_if = "Option::is_none")]
+    pub launcher_icons: Option<LauncherIconsConfig>,
+
+    /// Foreground/background color pairs `asb lint` checks for WCAG contrast (optional,
+    /// default: the Material "on" color pairs at 4.5:1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contrast: Option<ContrastConfig>,
+
+    /// Compare key drawables, rasterized, with golden PNGs and fail the build when they
+    /// differ (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub golden_images: Option<GoldenImagesConfig>,
+
+    /// Copy the discovered resources into a staging tree under the package's build
+    /// directory and compile from there (optional, default: false)
+    /// Rewrites and renames then work on the staged files; the source tree is never touched
+    #[serde(rename = "stageResources", skip_serializing_if = "Option::is_none")]
+    pub stage_resources: Option<bool>,
+
+    /// Convert resource XML saved as UTF-16 or with a UTF-8 byte order mark to plain UTF-8
+    /// in the staging tree instead of failing the build; implies `stageResources`
+    /// (optional, default: false)
+    #[serde(rename = "convertEncodings", skip_serializing_if = "Option::is_none")]
+    pub convert_encodings: Option<bool>,
+
+    /// Commands run before the build, after compilation and after the package is written
+    /// (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
+
+    /// External executables that preprocess resources or post-process the package, run in
+    /// order (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<PluginConfig>>,
+
+    /// What to do when an `additionalResourceDirs` entry does not exist (optional, default: error)
+    #[serde(
+        rename = "missingResourceDirPolicy",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub missing_resource_dir_policy: Option<MissingResourceDirPolicy>,
+
+    /// Host app symbols (aapt2 R.txt or `@type/name` lines) that references may resolve to
+    /// when validating references (optional)
+    #[serde(rename = "hostSymbols", skip_serializing_if = "Option::is_none")]
+    pub host_symbols: Option<PathBuf>,
+
+    /// Owners file mapping resource path globs to teams; when set, each build writes an
+    /// ownership report next to the package (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<PathBuf>,
+
+    /// Resource names and IDs of a released skin, recorded by `asb freeze`; when set, the
+    /// build fails if a frozen resource is removed, retyped or gets another ID (optional)
+    #[serde(rename = "freezeFile", skip_serializing_if = "Option::is_none")]
+    pub freeze_file: Option<PathBuf>,
+
+    /// Symbol lists of the supported host versions; each build checks the skin's resources
+    /// against every one and writes a compatibility report next to the package (optional)
+    #[serde(rename = "hostVersions", skip_serializing_if = "Option::is_none")]
+    pub host_versions: Option<Vec<HostVersion>>,
+
+    /// Build-time feature flags; each becomes a `bool/flag_<name>` resource and can gate
+    /// `conditionalResourceDirs` (optional)
+    #[serde(rename = "featureFlags", skip_serializing_if = "Option::is_none")]
+    pub feature_flags: Option<BTreeMap<String, bool>>,
+
+    /// Resource directories included only when their `includeIf` flag condition holds and
+    /// the build date is within their `from`/`until` window; included ones are added to
+    /// `additionalResourceDirs` when the config is loaded (optional)
+    #[serde(
+        rename = "conditionalResourceDirs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub conditional_resource_dirs: Option<Vec<ConditionalResourceDir>>,
+
+    /// Resource files, matched by pattern, that are only compiled when the build date is
+    /// within their `from`/`until` window (optional)
+    #[serde(rename = "scheduledFiles", skip_serializing_if = "Option::is_none")]
+    pub scheduled_files: Option<Vec<ScheduledFiles>>,
+
+    /// Build date for date windows, `YYYY-MM-DD`; defaults to today and is overridden by
+    /// `--as-of`. Recorded when the config is loaded so that it is part of the cache key
+    /// (optional)
+    #[serde(rename = "asOf", skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<String>,
+
+    /// Append a record of every build (user, config hash, input and output hashes, toolchain)
+    /// to `asb-audit.log` in the output directory (optional, default: false)
+    #[serde(rename = "auditLog", skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<bool>,
+
+    /// Turn on every validation at once: missing resource directories and warnings fail the
+    /// build, references, XML and frozen IDs are checked, and duplicate definitions are
+    /// reported; settings given explicitly keep their value (optional, default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+
+    /// Fail the build when it reports warnings (optional, default: false)
+    #[serde(rename = "warningsAsErrors", skip_serializing_if = "Option::is_none")]
+    pub warnings_as_errors: Option<bool>,
+
+    /// Largest allowed package and resource file sizes; a build over budget reports a
+    /// warning (optional)
+    #[serde(rename = "sizeBudget", skip_serializing_if = "Option::is_none")]
+    pub size_budget: Option<SizeBudget>,
+
+    /// Fail early when the output, build or temp directory's disk is short of space for
+    /// the build (optional)
+    #[serde(rename = "diskSpace", skip_serializing_if = "Option::is_none")]
+    pub disk_space: Option<DiskSpaceConfig>,
+
+    /// Resource filters for AARs, keyed by AAR file name (`*` and `?` allowed), applied after
+    /// extraction; files an AAR's filters drop are not compiled (optional)
+    #[serde(rename = "aarFilters", skip_serializing_if = "Option::is_none")]
+    pub aar_filters: Option<BTreeMap<String, AarResourceFilter>>,
+
+    /// Pre-compiled common dependencies (runtime only, not serialized)
+    /// Map from resource directory path to compiled flat files
+    #[serde(skip, default)]
+    #[allow(dead_code)]
+    pub precompiled_common_deps: Option<std::collections::HashMap<PathBuf, Vec<PathBuf>>>,
+
+    /// Resource types to compile, e.g. `values` (runtime only, set by `--only`)
+    /// Files of other types are taken from the incremental cache as they are
+    #[serde(skip, default)]
+    pub only_resource_types: Option<Vec<String>>,
+}
+
+/// Parse a resource package ID such as "0x7f" (hex) or "127" (decimal)
+/// 0x00 is reserved for shared libraries and 0x01 for the Android framework
+pub fn parse_package_id(package_id: &str) -> anyhow::Result<u8> {
+    let trimmed = package_id.trim();
+    let parsed = match trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => trimmed.parse::<u8>(),
+    };
+
+    match parsed {
+        Ok(id) if id >= 0x02 => Ok(id),
+        Ok(id) => anyhow::bail!(
+            "Package ID {} (0x{:02x}) is reserved; use a value between 0x02 and 0xff",
+            package_id,
+            id
+        ),
+        Err(_) => anyhow::bail!(
+            "Invalid package ID '{}': expected a value between 0x02 and 0xff",
+            package_id
+        ),
+    }
+}
+
+/// Check that every configured package ID is legal and that namespaced
+/// packages, which may be loaded side by side, do not share an ID
+pub fn validate_package_ids(configs: &[BuildConfig]) -> anyhow::Result<()> {
+    let mut namespaced_ids: BTreeMap<u8, &str> = BTreeMap::new();
+
+    for config in configs {
+        let id = parse_package_id(config.package_id.as_deref().unwrap_or(DEFAULT_PACKAGE_ID))?;
+        if !config.namespaced.unwrap_or(false) {
+            continue;
+        }
+        match namespaced_ids.get(&id) {
+            Some(other) if *other != config.package_name => anyhow::bail!(
+                "Package ID 0x{:02x} is used by both '{}' and '{}'; namespaced packages need distinct IDs",
+                id,
+                other,
+                config.package_name
+            ),
+            Some(_) => {}
+            None => {
+                namespaced_ids.insert(id, &config.package_name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Largest versionCode Google Play accepts
+pub const MAX_VERSION_CODE: u32 = 2_100_000_000;
+
+/// Check an Android package name: two or more dot-separated segments, each starting with a
+/// letter and made of letters, digits and underscores
+fn validate_package_name(package_name: &str) -> Result<(), String> {
+    let segments: Vec<&str> = package_name.split('.').collect();
+    if segments.len() < 2 {
+        return Err(format!(
+            "packageName '{}' needs at least two segments, e.g. com.example.skin",
+            package_name
+        ));
+    }
+    for segment in segments {
+        let mut chars = segment.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "packageName '{}' has an invalid segment '{}'; segments start with a letter and contain only letters, digits and underscores",
+                package_name, segment
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check package names, package IDs and versionCodes of every config before building
+///
+/// All violations are reported in one error, each labelled with the target and package of its
+/// config, so a broken app or flavor does not fail deep inside aapt2.
+pub fn validate_configs(configs: &[BuildConfig]) -> anyhow::Result<()> {
+    let mut violations = Vec::new();
+    for config in configs {
+        let mut violation = |message: String| {
+            violations.push(format!(
+                "{} [{}]: {}",
+                config.target(),
+                config.package_name,
+                message
+            ))
+        };
+        if let Err(message) = validate_package_name(&config.package_name) {
+            violation(message);
+        }
+        if let Some(package_id) = &config.package_id
+            && let Err(e) = parse_package_id(package_id)
+        {
+            violation(e.to_string());
+        }
+        match config.version_code {
+            Some(0) => violation("versionCode must be greater than 0".to_string()),
+            Some(version_code) if version_code > MAX_VERSION_CODE => violation(format!(
+                "versionCode {} exceeds the maximum of {}",
+                version_code, MAX_VERSION_CODE
+            )),
+            _ => {}
+        }
+        for aar in config.aar_files.iter().flatten() {
+            if let Some(sha256) = aar.sha256()
+                && (sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()))
+            {
+                violation(format!(
+                    "sha256 of AAR {} must be 64 hex digits, got '{}'",
+                    aar.path().display(),
+                    sha256
+                ));
+            }
+        }
+    }
+
+    // ID clashes between namespaced packages only make sense once every ID is legal
+    if violations.is_empty()
+        && let Err(e) = validate_package_ids(configs)
+    {
+        violations.push(e.to_string());
+    }
+
+    if !violations.is_empty() {
+        anyhow::bail!(
+            "Invalid configuration ({} problems):\n  - {}",
+            violations.len(),
+            violations.join("\n  - ")
+        );
+    }
+    Ok(())
+}
+
+/// Find configs that would write the same package file
+///
+/// Parallel builds of such configs overwrite each other's output. Without `auto_unique` the
+/// first collision is an error naming both configs; with it, later configs get a suffix (their
+/// flavor, or a counter) on the package file name.
+pub fn resolve_output_collisions(
+    configs: &mut [BuildConfig],
+    auto_unique: bool,
+) -> anyhow::Result<()> {
+    let normalize = |path: &Path| -> PathBuf {
+        path.absolutize()
+            .map(|p| p.into_owned())
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let mut outputs: HashMap<PathBuf, usize> = HashMap::new();
+    for idx in 0..configs.len() {
+        let output = normalize(&configs[idx].output_apk_path());
+        let Some(&other) = outputs.get(&output) else {
+            outputs.insert(output, idx);
+            continue;
+        };
+        if !auto_unique {
+            anyhow::bail!(
+                "{} [{}] and {} [{}] both write {}; set outputFile or outputDir for one of them, or pass --auto-unique-outputs",
+                configs[other].target(),
+                configs[other].package_name,
+                configs[idx].target(),
+                configs[idx].package_name,
+                output.display()
+            );
+        }
+
+        let config = &configs[idx];
+        let file_name = config.output_apk_path();
+        let stem = file_name
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let extension = file_name
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let suffixes = config
+            .flavor
+            .clone()
+            .into_iter()
+            .chain((2..).map(|n| n.to_string()));
+        for suffix in suffixes {
+            let candidate = format!("{}-{}{}", stem, suffix, extension);
+            let candidate_path = normalize(&config.output_dir.join(&candidate));
+            if let std::collections::hash_map::Entry::Vacant(entry) = outputs.entry(candidate_path)
+            {
+                info!(
+                    "Output of {} collides with {}, writing {} instead",
+                    config.target(),
+                    configs[other].target(),
+                    candidate
+                );
+                entry.insert(idx);
+                configs[idx].output_file = Some(candidate);
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep the configs selected by any of the target patterns
+/// Fails on malformed patterns and on patterns that select nothing, listing the known targets.
+pub fn select_targets(configs: &mut Vec<BuildConfig>, patterns: &[String]) -> anyhow::Result<()> {
+    for pattern in patterns {
+        let segments = pattern
+            .strip_prefix(':')
+            .map(|p| p.split(':').collect::<Vec<_>>());
+        if !segments.is_some_and(|s| s.len() <= 2 && s.iter().all(|s| !s.is_empty())) {
+            anyhow::bail!(
+                "Invalid target '{}', expected :<app> or :<app>:<flavor>",
+                pattern
+            );
+        }
+        if !configs.iter().any(|c| c.matches_target(pattern)) {
+            let targets: Vec<String> = configs.iter().map(BuildConfig::target).collect();
+            anyhow::bail!(
+                "No configurations found matching target {}. Available targets: {}",
+                pattern,
+                targets.join(", ")
+            );
+        }
+    }
+    configs.retain(|c| patterns.iter().any(|p| c.matches_target(p)));
+    Ok(())
+}
+
+/// Directory `${workspaceRoot}` refers to: the one containing the config file
+fn workspace_root(config_path: &Path) -> &Path {
+    match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Values `${...}` variables in config strings resolve to
+struct Interpolation {
+    /// The config as written, before any variable is resolved
+    config: serde_json::Value,
+    workspace_root: PathBuf,
+    flavor: Option<String>,
+}
+
+impl Interpolation {
+    /// Replace every `${...}` in `value` of config field `field`
+    fn expand(&self, field: &str, value: &str) -> anyhow::Result<String> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                anyhow::bail!("Unterminated variable in {}: {}", field, value);
+            };
+            let variable = &rest[start + 2..start + end];
+            result.push_str(&rest[..start]);
+            result.push_str(
+                &self
+                    .lookup(variable)
+                    .map_err(|e| anyhow::anyhow!("{} in {}: {}", e, field, value))?,
+            );
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    fn lookup(&self, variable: &str) -> anyhow::Result<String> {
+        match variable.split_once(':') {
+            None if variable == "workspaceRoot" => {
+                Ok(self.workspace_root.to_string_lossy().into_owned())
+            }
+            None if variable == "flavor" => self
+                .flavor
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("${{flavor}} is only available in flavor configs")),
+            // Bare names are environment variables, as before `env:` existed
+            None | Some(("env", _)) => {
+                let name = variable.strip_prefix("env:").unwrap_or(variable);
+                std::env::var(name)
+                    .map_err(|_| anyhow::anyhow!("Environment variable {} is not set", name))
+            }
+            Some(("config", name)) => match self.config.get(name) {
+                Some(serde_json::Value::String(value)) if value.contains("${") => {
+                    anyhow::bail!("${{config:{}}} refers to a value with variables", name)
+                }
+                Some(serde_json::Value::String(value)) => Ok(value.clone()),
+                Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                    Ok(value.to_string())
+                }
+                Some(_) => anyhow::bail!("${{config:{}}} is not a plain value", name),
+                None => anyhow::bail!("Unknown or unset config field ${{config:{}}}", name),
+            },
+            Some(_) => anyhow::bail!("Unknown variable ${{{}}}", variable),
+        }
+    }
+}
+
+impl BuildConfig {
+    /// Whether strict mode (`strict` / `--strict`) is on
+    pub fn is_strict(&self) -> bool {
+        self.strict == Some(true)
+    }
+
+    /// Turn on the checks strict mode bundles: missing resource directories are errors,
+    /// warnings fail the build, references and XML are validated
+    ///
+    /// Settings the config gives explicitly keep their value. Duplicate definitions, IDs
+    /// and size budgets are checked by the builder via [`BuildConfig::is_strict`].
+    pub fn apply_strict(&mut self) {
+        if !self.is_strict() {
+            return;
+        }
+        self.missing_resource_dir_policy
+            .get_or_insert(MissingResourceDirPolicy::Error);
+        self.warnings_as_errors.get_or_insert(true);
+        self.validate_references.get_or_insert(true);
+        self.validate_xml.get_or_insert(true);
+    }
+
+    /// Rewrite output and build directories for the configured output layout
+    /// The gradle layout treats `outputDir` as Gradle's `build/` directory:
+    /// packages go to `outputs/skin/<flavor>/<buildType>/` and intermediates to
+    /// `intermediates/asb/` unless `buildDir` is set explicitly.
+    /// A template layout moves each package to its own subdirectory of `outputDir`.
+    /// Call once, after all overrides have been applied.
+    pub fn apply_output_layout(&mut self) -> anyhow::Result<()> {
+        match self.output_layout.clone().unwrap_or_default() {
+            OutputLayout::Flat => {}
+            OutputLayout::Gradle => {
+                let root = self.output_dir.clone();
+                if self.build_dir.is_none() {
+                    self.build_dir = Some(root.join("intermediates").join("asb"));
+                }
+
+                let mut output_dir = root.join("outputs").join("skin");
+                if let Some(flavor) = &self.flavor {
+                    output_dir.push(flavor);
+                }
+                output_dir.push(self.build_type.as_deref().unwrap_or("release"));
+                self.output_dir = output_dir;
+            }
+            OutputLayout::Template(template) => {
+                let subdir = self
+                    .render_output_layout(&template)
+                    .with_context(|| format!("Invalid outputLayout '{}'", template))?;
+                // Packages share the common dependency cache of the output root
+                if self.build_dir.is_none() {
+                    self.build_dir = Some(self.output_dir.join(".build"));
+                }
+                self.output_dir = self.output_dir.join(subdir);
+            }
+        }
+        Ok(())
+    }
+
+    /// Output subdirectory of an `outputLayout` template
+    ///
+    /// `{brand}` and `{app}` are the app name (the package name outside multi-app configs),
+    /// `{flavor}` the flavor and `{buildType}` the build type (default `release`); other
+    /// names are top-level config fields such as `{packageName}` or `{versionName}`.
+    /// Segments that end up empty, such as `{flavor}/` of a config without flavor, are
+    /// left out.
+    fn render_output_layout(&self, template: &str) -> anyhow::Result<PathBuf> {
+        let config = serde_json::to_value(self)?;
+        let value = |name: &str| -> anyhow::Result<String> {
+            Ok(match name {
+                "brand" | "app" => self
+                    .app
+                    .clone()
+                    .unwrap_or_else(|| self.package_name.clone()),
+                "flavor" => self.flavor.clone().unwrap_or_default(),
+                "buildType" => self.build_type.as_deref().unwrap_or("release").to_string(),
+                _ => match config.get(name) {
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                        value.to_string()
+                    }
+                    Some(serde_json::Value::Null) | None => String::new(),
+                    Some(_) => anyhow::bail!("{{{}}} is not a plain value", name),
+                },
+            })
+        };
+
+        let mut rendered = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .with_context(|| format!("Unclosed '{{' in {}", template))?;
+            rendered.push_str(&value(&rest[start + 1..start + end])?);
+            rest = &rest[start + end + 1..];
+        }
+        rendered.push_str(rest);
+
+        let mut subdir = PathBuf::new();
+        for segment in rendered.split(['/', '\\']).filter(|s| !s.is_empty()) {
+            if segment == "." || segment == ".." || segment.contains(':') {
+                anyhow::bail!("'{}' must stay inside outputDir", rendered);
+            }
+            subdir.push(segment);
+        }
+        Ok(subdir)
+    }
+
+    /// Stable IDs file used for linking
+    /// In namespaced mode each package keeps its own file next to the configured one,
+    /// e.g. `ids.txt` becomes `ids.com.example.skin.txt`
+    pub fn stable_ids_path(&self) -> Option<PathBuf> {
+        let path = self.stable_ids_file.as_ref()?;
+        if !self.namespaced.unwrap_or(false) {
+            return Some(path.clone());
+        }
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, self.package_name, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, self.package_name),
+        };
+        Some(path.with_file_name(file_name))
+    }
+
+    /// Build target of this config: `:<app>` or `:<app>:<flavor>`
+    /// Configs outside a multi-app config are named after their package
+    pub fn target(&self) -> String {
+        let app = self.app.as_deref().unwrap_or(&self.package_name);
+        match &self.flavor {
+            Some(flavor) => format!(":{}:{}", app, flavor),
+            None => format!(":{}", app),
+        }
+    }
+
+    /// Whether this config is selected by a target pattern such as `:brandA:*` or `:*:night`
+    /// `*` and `?` are wildcards within a segment; an app pattern selects all its flavors.
+    pub fn matches_target(&self, pattern: &str) -> bool {
+        let target = self.target();
+        let target: Vec<&str> = target[1..].split(':').collect();
+        let Some(pattern) = pattern.strip_prefix(':') else {
+            return false;
+        };
+        let pattern: Vec<&str> = pattern.split(':').collect();
+        pattern.len() <= target.len()
+            && pattern
+                .iter()
+                .zip(&target)
+                .all(|(pattern, name)| wildcard_match(pattern, name))
+    }
+
+    /// Package file the build writes
+    pub fn output_apk_path(&self) -> PathBuf {
+        let file_name = self
+            .output_file
+            .clone()
+            .unwrap_or_else(|| format!("{}.skin", self.package_name));
+        self.output_dir.join(file_name)
+    }
+
+    /// Files a successful build writes: the package, its fingerprint, the rewrite audit when
+    /// resources are prefixed or aliased, the ownership report when an owners file is set and
+    /// the compatibility report when host versions are set
+    pub fn declared_outputs(&self) -> Vec<PathBuf> {
+        let output_apk = self.output_apk_path();
+        let mut outputs = vec![
+            output_apk.clone(),
+            output_apk.with_extension(crate::fingerprint::FINGERPRINT_EXTENSION),
+        ];
+        let rewrites =
+            self.resource_prefix.is_some() || self.aliases.as_ref().is_some_and(|a| !a.is_empty());
+        if rewrites {
+            outputs
+                .push(output_apk.with_extension(crate::resource_rewrite::REWRITE_AUDIT_EXTENSION));
+        }
+        if self.owners.is_some() {
+            outputs.push(output_apk.with_extension(crate::owners::OWNERS_REPORT_EXTENSION));
+        }
+        if self.host_versions.is_some() {
+            outputs.push(output_apk.with_extension(crate::host_compat::COMPAT_REPORT_EXTENSION));
+        }
+        outputs
+    }
+
+    /// Apply `map` to every input path: resources, manifest, AARs, assets and other files the
+    /// build reads. Toolchain, output and intermediate paths are not inputs.
+    pub(crate) fn map_inputs(
+        &mut self,
+        mut map: impl FnMut(&Path) -> anyhow::Result<PathBuf>,
+    ) -> anyhow::Result<()> {
+        self.resource_dir = map(&self.resource_dir)?;
+        self.manifest_path = map(&self.manifest_path)?;
+        for aar in self.aar_files.iter_mut().flatten() {
+            *aar.path_mut() = map(aar.path())?;
+        }
+        for paths in [
+            &mut self.additional_resource_dirs,
+            &mut self.precompiled_dependencies,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            for path in paths.iter_mut() {
+                *path = map(path)?;
+            }
+        }
+        for path in [
+            &mut self.assets_dir,
+            &mut self.design_tokens,
+            &mut self.host_symbols,
+            &mut self.owners,
+            &mut self.freeze_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = map(path)?;
+        }
+        if let Some(dark_mode) = self.dark_mode.as_mut() {
+            dark_mode.color_mapping = map(&dark_mode.color_mapping)?;
+        }
+        if let Some(golden_images) = self.golden_images.as_mut() {
+            golden_images.dir = map(&golden_images.dir)?;
+        }
+        for host in self.host_versions.iter_mut().flatten() {
+            host.symbols = map(&host.symbols)?;
+        }
+        for dir in self.conditional_resource_dirs.iter_mut().flatten() {
+            dir.path = map(&dir.path)?;
+        }
+        if let Some(extra_files) = self.packaging.as_mut().and_then(|p| p.extra_files.as_mut()) {
+            for path in extra_files.values_mut() {
+                *path = map(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Incremental build cache directory of this package
+    /// Base directory priority: cache_dir (deprecated) > build_dir > output_dir/.build
+    pub fn cache_path(&self) -> PathBuf {
+        self.cache_dir
+            .clone()
+            .or_else(|| self.build_dir.clone())
+            .unwrap_or_else(|| self.output_dir.join(".build"))
+            .join(&self.package_name)
+    }
+
+    /// Create default configuration based on standard Android project structure
+    pub fn default_config() -> Self {
+        Self {
+            resource_dir: PathBuf::from("./src/main/res"),
+            manifest_path: PathBuf::from("./src/main/AndroidManifest.xml"),
+            output_dir: PathBuf::from("./build/outputs/skin"),
+            output_file: None,
             package_name: "com.example.skin".to_string(),
             android_jar: None,
             aar_files: None,
@@ -607,105 +2515,146 @@ impl BuildConfig {
             stable_ids_file: None,
             package_id: Some(DEFAULT_PACKAGE_ID.to_string()),
             precompiled_dependencies: None,
+            namespaced: None,
+            auto_assign_package_ids: None,
+            manifest_mode: None,
+            manifest_placeholders: None,
+            merge_aar_manifests: None,
+            output_layout: None,
+            app: None,
+            flavor: None,
+            build_type: None,
+            vcs: None,
+            version_code_strategy: None,
+            design_tokens: None,
+            dark_mode: None,
+            resource_prefix: None,
+            aliases: None,
+            validate_references: None,
+            auto_fix_filenames: None,
+            locale_qualifiers: None,
+            launcher_icons: None,
+            contrast: None,
+            validate_xml: None,
+            convert_encodings: None,
+            golden_images: None,
+            freeze_file: None,
+            host_versions: None,
+            feature_flags: None,
+            scheduled_files: None,
+            as_of: None,
+            audit_log: None,
+            strict: None,
+            warnings_as_errors: None,
+            size_budget: None,
+            disk_space: None,
+            conditional_resource_dirs: None,
+            stage_resources: None,
+            hooks: None,
+            plugins: None,
+            missing_resource_dir_policy: None,
+            host_symbols: None,
+            owners: None,
+            aar_filters: None,
+            precompiled_common_deps: None,
+            only_resource_types: None,
             assets_dir: None,
+            follow_symlinks: None,
+            respect_gitignore: None,
+            packaging: None,
+            link_input_mode: None,
+            compile_options: None,
+            aapt2_limits: None,
+            heartbeat: None,
+            static_lib_dependencies: None,
         }
     }
 
-    /// Expand environment variables in path strings
-    fn expand_env_vars(path: &str) -> String {
-        let mut result = path.to_string();
-
-        // Find all ${VAR} patterns and replace them
-        while let Some(start) = result.find("${") {
-            if let Some(end) = result[start..].find('}') {
-                let end = start + end;
-                let var_name = &result[start + 2..end];
-
-                if let Ok(value) = std::env::var(var_name) {
-                    result.replace_range(start..=end, &value);
-                } else {
-                    // If variable is not set, leave it as is
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
-
-        result
-    }
-
-    /// Expand environment variables in all path fields
-    pub fn expand_paths(&mut self) {
-        // Expand environment variables in paths
-        self.resource_dir =
-            PathBuf::from(Self::expand_env_vars(&self.resource_dir.to_string_lossy()));
-        self.manifest_path =
-            PathBuf::from(Self::expand_env_vars(&self.manifest_path.to_string_lossy()));
-        self.output_dir = PathBuf::from(Self::expand_env_vars(&self.output_dir.to_string_lossy()));
-        if let Some(android_jar) = &self.android_jar {
-            self.android_jar = Some(PathBuf::from(Self::expand_env_vars(
-                &android_jar.to_string_lossy(),
-            )));
-        }
+    /// Resolve `${...}` variables in all path and string fields
+    ///
+    /// Supported variables are `${env:VAR}` (and the older `${VAR}`), `${config:field}` for
+    /// any plain config value such as `packageName`, `${workspaceRoot}` (`workspace_root`,
+    /// normally the config file's directory) and `${flavor}`. Unknown or unset variables are
+    /// errors. Auto-detects android.jar when none is configured.
+    pub fn expand_paths(&mut self, workspace_root: &Path) -> anyhow::Result<()> {
+        let vars = Interpolation {
+            config: serde_json::to_value(&*self)?,
+            workspace_root: std::path::absolute(workspace_root)?,
+            flavor: self.flavor.clone(),
+        };
+        let path = |field: &str, path: &mut PathBuf| -> anyhow::Result<()> {
+            *path = PathBuf::from(vars.expand(field, &path.to_string_lossy())?);
+            Ok(())
+        };
 
-        // If android_jar is still None after expansion, try to auto-detect
-        if self.android_jar.is_none() {
-            self.android_jar = find_highest_android_jar();
+        path("resourceDir", &mut self.resource_dir)?;
+        path("manifestPath", &mut self.manifest_path)?;
+        path("outputDir", &mut self.output_dir)?;
+        for (field, value) in [
+            ("androidJar", &mut self.android_jar),
+            ("aapt2Path", &mut self.aapt2_path),
+            ("buildDir", &mut self.build_dir),
+            ("cacheDir", &mut self.cache_dir),
+            ("compiledDir", &mut self.compiled_dir),
+            ("stableIdsFile", &mut self.stable_ids_file),
+            ("assetsDir", &mut self.assets_dir),
+            ("designTokens", &mut self.design_tokens),
+            ("hostSymbols", &mut self.host_symbols),
+            ("owners", &mut self.owners),
+            ("freezeFile", &mut self.freeze_file),
+        ] {
+            if let Some(value) = value {
+                path(field, value)?;
+            }
         }
-
-        if let Some(aapt2) = &self.aapt2_path {
-            self.aapt2_path = Some(PathBuf::from(Self::expand_env_vars(
-                &aapt2.to_string_lossy(),
-            )));
+        for aar in self.aar_files.iter_mut().flatten() {
+            path("aarFiles", aar.path_mut())?;
         }
-
-        if let Some(build) = &self.build_dir {
-            self.build_dir = Some(PathBuf::from(Self::expand_env_vars(
-                &build.to_string_lossy(),
-            )));
+        for (field, values) in [
+            (
+                "precompiledDependencies",
+                &mut self.precompiled_dependencies,
+            ),
+            ("additionalResourceDirs", &mut self.additional_resource_dirs),
+        ] {
+            for value in values.iter_mut().flatten() {
+                path(field, value)?;
+            }
         }
-
-        if let Some(cache) = &self.cache_dir {
-            self.cache_dir = Some(PathBuf::from(Self::expand_env_vars(
-                &cache.to_string_lossy(),
-            )));
+        if let Some(dark_mode) = self.dark_mode.as_mut() {
+            path("darkMode.colorMapping", &mut dark_mode.color_mapping)?;
         }
-
-        if let Some(compiled) = &self.compiled_dir {
-            self.compiled_dir = Some(PathBuf::from(Self::expand_env_vars(
-                &compiled.to_string_lossy(),
-            )));
+        for host in self.host_versions.iter_mut().flatten() {
+            path("hostVersions", &mut host.symbols)?;
         }
-
-        if let Some(stable) = &self.stable_ids_file {
-            self.stable_ids_file = Some(PathBuf::from(Self::expand_env_vars(
-                &stable.to_string_lossy(),
-            )));
+        for dir in self.conditional_resource_dirs.iter_mut().flatten() {
+            path("conditionalResourceDirs", &mut dir.path)?;
         }
-
-        if let Some(aars) = &self.aar_files {
-            self.aar_files = Some(
-                aars.iter()
-                    .map(|p| PathBuf::from(Self::expand_env_vars(&p.to_string_lossy())))
-                    .collect(),
-            );
+        if let Some(golden_images) = self.golden_images.as_mut() {
+            path("goldenImages.dir", &mut golden_images.dir)?;
+            if let Some(diff_dir) = golden_images.diff_dir.as_mut() {
+                path("goldenImages.diffDir", diff_dir)?;
+            }
         }
-
-        if let Some(additional) = &self.additional_resource_dirs {
-            self.additional_resource_dirs = Some(
-                additional
-                    .iter()
-                    .map(|p| PathBuf::from(Self::expand_env_vars(&p.to_string_lossy())))
-                    .collect(),
-            );
+        if let Some(extra_files) = self.packaging.as_mut().and_then(|p| p.extra_files.as_mut()) {
+            for source in extra_files.values_mut() {
+                path("packaging.extraFiles", source)?;
+            }
+        }
+        for (field, value) in [
+            ("outputFile", &mut self.output_file),
+            ("versionName", &mut self.version_name),
+        ] {
+            if let Some(value) = value {
+                *value = vars.expand(field, value)?;
+            }
         }
 
-        if let Some(assets) = &self.assets_dir {
-            self.assets_dir = Some(PathBuf::from(Self::expand_env_vars(
-                &assets.to_string_lossy(),
-            )));
+        // If android_jar is still None after expansion, try to auto-detect
+        if self.android_jar.is_none() {
+            self.android_jar = find_highest_android_jar();
         }
+        Ok(())
     }
 
     /// Load configuration from file or use defaults
@@ -719,7 +2668,7 @@ impl BuildConfig {
         if let Some(config_path) = config_file {
             let content = std::fs::read_to_string(&config_path)?;
             let mut config: Self = serde_json::from_str(&content)?;
-            config.expand_paths();
+            config.expand_paths(workspace_root(&config_path))?;
             return Ok(config);
         }
 
@@ -729,7 +2678,7 @@ impl BuildConfig {
         if default_config_path.exists() {
             let content = std::fs::read_to_string(&default_config_path)?;
             let mut config: Self = serde_json::from_str(&content)?;
-            config.expand_paths();
+            config.expand_paths(base)?;
             return Ok(config);
         }
 
@@ -746,55 +2695,183 @@ impl BuildConfig {
         config_file: Option<PathBuf>,
         base_dir: Option<&Path>,
     ) -> anyhow::Result<LoadedConfigs> {
+        Self::load_configs_as_of(config_file, base_dir, None)
+    }
+
+    /// Load configurations as [`BuildConfig::load_configs`] with their output layout
+    /// applied, so output and build directories are where `asb build` writes them
+    /// For subcommands that read build outputs; `asb build` lays out directories itself
+    /// once its CLI overrides are in place.
+    pub fn load_built_configs(config_file: Option<PathBuf>) -> anyhow::Result<Vec<Self>> {
+        let mut configs = Self::load_configs(config_file, None)?.configs;
+        for config in &mut configs {
+            config.apply_output_layout()?;
+        }
+        Ok(configs)
+    }
+
+    /// Load multiple configurations as [`BuildConfig::load_configs`], with `as_of`
+    /// (`--as-of`) overriding the build date of every config
+    pub fn load_configs_as_of(
+        config_file: Option<PathBuf>,
+        base_dir: Option<&Path>,
+        as_of: Option<&str>,
+    ) -> anyhow::Result<LoadedConfigs> {
+        let user_defaults = match UserDefaults::path() {
+            Some(path) => UserDefaults::load(&path)?,
+            None => UserDefaults::default(),
+        };
+        let (mut loaded, root) = Self::parse_configs(config_file, base_dir)?;
+        for config in &mut loaded.configs {
+            user_defaults.apply(config);
+            if let Some(as_of) = as_of {
+                config.as_of = Some(as_of.to_string());
+            }
+            config
+                .expand_paths(&root)
+                .and_then(|_| config.resolve_conditional_dirs())
+                .with_context(|| format!("Failed to resolve config of {}", config.package_name))?;
+            config.apply_strict();
+        }
+        Ok(loaded)
+    }
+
+    /// Move the conditional resource directories whose `includeIf` holds for the feature
+    /// flags and whose window contains the build date to the additional resource
+    /// directories, dropping the others
+    ///
+    /// When the config has date windows but no `asOf`, today is recorded as `asOf` so that
+    /// the date the build was resolved for is part of the cache key.
+    pub fn resolve_conditional_dirs(&mut self) -> anyhow::Result<()> {
+        let windows = self
+            .conditional_resource_dirs
+            .iter()
+            .flatten()
+            .any(|dir| dir.from.is_some() || dir.until.is_some())
+            || self.scheduled_files.as_ref().is_some_and(|s| !s.is_empty());
+        if windows && self.as_of.is_none() {
+            self.as_of = Some(crate::schedule::today());
+        }
+        let as_of = self.as_of_date()?;
+        for rule in self.scheduled_files.iter().flatten() {
+            crate::schedule::in_window(as_of, rule.from.as_deref(), rule.until.as_deref())
+                .with_context(|| format!("In scheduledFiles {}", rule.pattern))?;
+        }
+
+        let Some(dirs) = self.conditional_resource_dirs.take() else {
+            return Ok(());
+        };
+        let flags = self.feature_flags.clone().unwrap_or_default();
+        for dir in dirs {
+            let context = || format!("In conditionalResourceDirs {}", dir.path.display());
+            let flag_holds = match &dir.include_if {
+                Some(condition) => {
+                    crate::feature_flags::is_included(&flags, condition).with_context(context)?
+                }
+                None => true,
+            };
+            let in_window =
+                crate::schedule::in_window(as_of, dir.from.as_deref(), dir.until.as_deref())
+                    .with_context(context)?;
+            if flag_holds && in_window {
+                self.additional_resource_dirs
+                    .get_or_insert_with(Vec::new)
+                    .push(dir.path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build date for date windows: `asOf`, or today
+    pub fn as_of_date(&self) -> anyhow::Result<chrono::NaiveDate> {
+        match &self.as_of {
+            Some(as_of) => crate::schedule::parse_date(as_of).context("Invalid asOf"),
+            None => Ok(chrono::Local::now().date_naive()),
+        }
+    }
+
+    /// Parse the config file without resolving variables, returning the workspace root too
+    fn parse_configs(
+        config_file: Option<PathBuf>,
+        base_dir: Option<&Path>,
+    ) -> anyhow::Result<(LoadedConfigs, PathBuf)> {
+        let base = base_dir.unwrap_or_else(|| Path::new("."));
         // Determine which config file to use
         let config_path = if let Some(path) = config_file {
             path
         } else {
-            let base = base_dir.unwrap_or_else(|| Path::new("."));
             let default_path = base.join("asb.config.json");
             if default_path.exists() {
                 default_path
             } else {
                 // No config file, use default single config
-                return Ok(LoadedConfigs {
+                let loaded = LoadedConfigs {
                     configs: vec![Self::default_config()],
                     max_parallel_builds: None,
-                });
+                    max_parallel_links: None,
+                    bundle: None,
+                };
+                return Ok((loaded, base.to_path_buf()));
             }
         };
 
         let content = std::fs::read_to_string(&config_path)?;
+        let root = workspace_root(&config_path).to_path_buf();
+        let loaded = Self::configs_from_str(&content)
+            .with_context(|| format!("Invalid config file {}", config_path.display()))?;
+        Ok((loaded, root))
+    }
+
+    /// Parse the content of a config file in any of the supported formats
+    pub fn configs_from_str(content: &str) -> anyhow::Result<LoadedConfigs> {
+        let version = serde_json::from_str::<serde_json::Value>(content)
+            .ok()
+            .and_then(|value| value.get("configVersion")?.as_u64())
+            .unwrap_or(1);
+        if version > u64::from(CONFIG_VERSION) {
+            anyhow::bail!(
+                "configVersion {} is newer than the latest version {} this asb supports; upgrade asb",
+                version,
+                CONFIG_VERSION
+            );
+        }
+        if version < u64::from(CONFIG_VERSION) {
+            debug!(
+                "Config is written for configVersion {}; `asb config migrate` upgrades it to {}",
+                version, CONFIG_VERSION
+            );
+        }
 
         // Try to parse as multi-app config first (new format)
-        if let Ok(multi_config) = serde_json::from_str::<MultiAppConfig>(&content) {
+        if let Ok(multi_config) = serde_json::from_str::<MultiAppConfig>(content) {
             let max_parallel_builds = multi_config.max_parallel_builds;
-            let mut configs = multi_config.into_build_configs();
-            for config in &mut configs {
-                config.expand_paths();
-            }
+            let max_parallel_links = multi_config.max_parallel_links;
+            let bundle = multi_config.bundle.clone();
             return Ok(LoadedConfigs {
-                configs,
+                configs: multi_config.into_build_configs(),
                 max_parallel_builds,
+                max_parallel_links,
+                bundle,
             });
         }
 
         // Try to parse as array (previous format)
-        if let Ok(mut configs) = serde_json::from_str::<Vec<Self>>(&content) {
-            for config in &mut configs {
-                config.expand_paths();
-            }
+        if let Ok(configs) = serde_json::from_str::<Vec<Self>>(content) {
             return Ok(LoadedConfigs {
                 configs,
                 max_parallel_builds: None,
+                max_parallel_links: None,
+                bundle: None,
             });
         }
 
         // Fall back to single object (original format for backward compatibility)
-        let mut config: Self = serde_json::from_str(&content)?;
-        config.expand_paths();
+        let config: Self = serde_json::from_str(content)?;
         Ok(LoadedConfigs {
             configs: vec![config],
             max_parallel_builds: None,
+            max_parallel_links: None,
+            bundle: None,
         })
     }
 }
@@ -878,6 +2955,651 @@ mod tests {
             1
         );
     }
+
+    #[test]
+    fn test_parse_package_id() {
+        assert_eq!(parse_package_id("0x7f").unwrap(), 0x7f);
+        assert_eq!(parse_package_id("0X80").unwrap(), 0x80);
+        assert_eq!(parse_package_id("126").unwrap(), 0x7e);
+        assert!(parse_package_id("0x01").is_err());
+        assert!(parse_package_id("0x00").is_err());
+        assert!(parse_package_id("0x100").is_err());
+        assert!(parse_package_id("skin").is_err());
+    }
+
+    #[test]
+    fn test_validate_package_ids_namespaced_conflicts() {
+        let config = |package_name: &str, package_id: &str, namespaced: bool| BuildConfig {
+            package_name: package_name.to_string(),
+            package_id: Some(package_id.to_string()),
+            namespaced: Some(namespaced),
+            ..BuildConfig::default_config()
+        };
+
+        // Non-namespaced skins replace each other and may share an ID
+        assert!(
+            validate_package_ids(&[
+                config("com.skin.a", "0x7f", false),
+                config("com.skin.b", "0x7f", false)
+            ])
+            .is_ok()
+        );
+        assert!(
+            validate_package_ids(&[
+                config("com.skin.a", "0x80", true),
+                config("com.skin.b", "0x7e", true)
+            ])
+            .is_ok()
+        );
+        assert!(
+            validate_package_ids(&[
+                config("com.skin.a", "0x80", true),
+                config("com.skin.b", "128", true)
+            ])
+            .is_err()
+        );
+        assert!(validate_package_ids(&[config("com.skin.a", "0x01", false)]).is_err());
+    }
+
+    #[test]
+    fn test_aar_resource_filter() {
+        let filter: AarResourceFilter = serde_json::from_str(
+            r#"{"include": ["drawable*", "values/colors.xml"], "exclude": ["drawable-ldpi"]}"#,
+        )
+        .unwrap();
+        assert!(filter.keeps("drawable/icon.png"));
+        assert!(filter.keeps("drawable-night-v21/icon.xml"));
+        assert!(!filter.keeps("drawable-ldpi/icon.png"));
+        assert!(filter.keeps("values/colors.xml"));
+        assert!(!filter.keeps("values/strings.xml"));
+        assert!(!filter.keeps("values-night/colors.xml"));
+
+        let exclude_only = AarResourceFilter {
+            exclude: vec!["values*/**".to_string()],
+            ..Default::default()
+        };
+        assert!(exclude_only.keeps("layout/main.xml"));
+        assert!(!exclude_only.keeps("values-zh/strings.xml"));
+    }
+
+    #[test]
+    fn test_resolve_output_collisions() {
+        let config = |package_name: &str, flavor: Option<&str>| BuildConfig {
+            package_name: package_name.to_string(),
+            output_dir: PathBuf::from("./build"),
+            flavor: flavor.map(str::to_string),
+            ..BuildConfig::default_config()
+        };
+        let mut configs = vec![
+            config("com.skin", Some("day")),
+            config("com.skin", Some("night")),
+            config("com.other", None),
+            BuildConfig {
+                output_file: Some("com.other.skin".to_string()),
+                ..config("com.third", None)
+            },
+        ];
+
+        let error = resolve_output_collisions(&mut configs.clone(), false)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(":com.skin:day [com.skin] and :com.skin:night [com.skin] both write"),
+            "{}",
+            error
+        );
+
+        resolve_output_collisions(&mut configs, true).unwrap();
+        let outputs: Vec<PathBuf> = configs.iter().map(BuildConfig::output_apk_path).collect();
+        assert_eq!(
+            outputs,
+            vec![
+                PathBuf::from("./build/com.skin.skin"),
+                PathBuf::from("./build/com.skin-night.skin"),
+                PathBuf::from("./build/com.other.skin"),
+                PathBuf::from("./build/com.other-2.skin"),
+            ]
+        );
+        assert!(resolve_output_collisions(&mut configs, false).is_ok());
+    }
+
+    #[test]
+    fn test_validate_configs_reports_every_violation() {
+        let config =
+            |package_name: &str, package_id: Option<&str>, version_code: u32| BuildConfig {
+                package_name: package_name.to_string(),
+                package_id: package_id.map(str::to_string),
+                version_code: Some(version_code),
+                ..BuildConfig::default_config()
+            };
+
+        assert!(
+            validate_configs(&[
+                config("com.skin.red", Some("0x7f"), 1),
+                config("com.Skin_2.blue", None, MAX_VERSION_CODE),
+            ])
+            .is_ok()
+        );
+
+        let error = validate_configs(&[
+            config("skin", Some("0x7f"), 1),
+            config("com.2skin", Some("0x01"), 0),
+            config("com.skin-red", None, MAX_VERSION_CODE + 1),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("(6 problems)"), "{}", error);
+        assert!(error.contains(":skin [skin]: packageName 'skin' needs at least two segments"));
+        assert!(error.contains(
+            ":com.2skin [com.2skin]: packageName 'com.2skin' has an invalid segment '2skin'"
+        ));
+        assert!(error.contains(":com.2skin [com.2skin]: Package ID 0x01 (0x01) is reserved"));
+        assert!(error.contains("versionCode must be greater than 0"));
+        assert!(error.contains("versionCode 2100000001 exceeds the maximum of 2100000000"));
+
+        // Namespaced clashes are reported once the IDs themselves are legal
+        let namespaced = |package_name: &str| BuildConfig {
+            namespaced: Some(true),
+            ..config(package_name, Some("0x80"), 1)
+        };
+        assert!(validate_configs(&[namespaced("com.skin.a"), namespaced("com.skin.b")]).is_err());
+    }
+
+    #[test]
+    fn test_pinned_aar_files() {
+        let digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let json = format!(
+            r#"["./libs/a.aar", {{"path": "./libs/b.aar", "sha256": "{}"}}]"#,
+            digest
+        );
+        let aar_files: Vec<AarFile> = serde_json::from_str(&json).unwrap();
+        assert_eq!(aar_files[0], AarFile::from(PathBuf::from("./libs/a.aar")));
+        assert_eq!(aar_files[1].path(), Path::new("./libs/b.aar"));
+        assert_eq!(aar_files[1].sha256(), Some(digest));
+        assert_eq!(
+            serde_json::to_value(&aar_files).unwrap(),
+            serde_json::json!(["./libs/a.aar", {"path": "./libs/b.aar", "sha256": digest}])
+        );
+
+        let invalid = BuildConfig {
+            package_name: "com.skin".to_string(),
+            aar_files: Some(vec![AarFile::Pinned {
+                path: PathBuf::from("b.aar"),
+                sha256: "abc".to_string(),
+            }]),
+            ..BuildConfig::default_config()
+        };
+        let error = validate_configs(&[invalid]).unwrap_err().to_string();
+        assert!(
+            error.contains("sha256 of AAR b.aar must be 64 hex digits, got 'abc'"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_matrix_expands_apps() {
+        let json = r#"{
+            "outputDir": "./build",
+            "apps": [
+                {
+                    "name": "{brand}-{tier}",
+                    "matrix": { "tier": ["free", "pro"], "brand": ["red", "blue"] },
+                    "packageName": "com.skin.{brand}.{tier}",
+                    "resourceDir": "./brands/{brand}/res",
+                    "manifestPath": "./AndroidManifest.xml",
+                    "additionalResourceDirs": ["./tiers/{tier}/res"]
+                },
+                { "packageName": "com.plain", "baseDir": "./plain" }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let configs = multi.into_build_configs();
+        let targets: Vec<String> = configs.iter().map(BuildConfig::target).collect();
+        assert_eq!(
+            targets,
+            vec![
+                ":red-free",
+                ":red-pro",
+                ":blue-free",
+                ":blue-pro",
+                ":com.plain"
+            ]
+        );
+        assert_eq!(configs[1].package_name, "com.skin.red.pro");
+        assert_eq!(configs[1].resource_dir, PathBuf::from("./brands/red/res"));
+        assert_eq!(
+            configs[1].additional_resource_dirs,
+            Some(vec![PathBuf::from("./tiers/pro/res")])
+        );
+    }
+
+    #[test]
+    fn test_build_targets() {
+        let json = r#"{
+            "outputDir": "./build",
+            "baseDir": "./skin",
+            "apps": [
+                {
+                    "name": "brandA",
+                    "packageName": "com.brand.a",
+                    "flavors": [{ "name": "day" }, { "name": "night" }]
+                },
+                {
+                    "packageName": "com.brand.b",
+                    "flavors": [{ "name": "night" }]
+                },
+                { "name": "plain", "packageName": "com.plain" }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let configs = multi.into_build_configs();
+        let targets: Vec<String> = configs.iter().map(BuildConfig::target).collect();
+        assert_eq!(
+            targets,
+            vec![
+                ":brandA:day",
+                ":brandA:night",
+                ":com.brand.b:night",
+                ":plain"
+            ]
+        );
+
+        let selected = |patterns: &[&str]| {
+            let mut configs = configs.clone();
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            select_targets(&mut configs, &patterns)
+                .map(|_| configs.iter().map(BuildConfig::target).collect::<Vec<_>>())
+        };
+        assert_eq!(selected(&[":brandA:night"]).unwrap(), vec![":brandA:night"]);
+        assert_eq!(selected(&[":brandA"]).unwrap().len(), 2);
+        assert_eq!(selected(&[":brandA:*"]).unwrap().len(), 2);
+        assert_eq!(
+            selected(&[":*:night"]).unwrap(),
+            vec![":brandA:night", ":com.brand.b:night"]
+        );
+        assert_eq!(
+            selected(&[":plain", ":com.brand.?:*"]).unwrap(),
+            vec![":com.brand.b:night", ":plain"]
+        );
+        assert!(selected(&[":plain:night"]).is_err());
+        assert!(selected(&["brandA"]).is_err());
+        assert!(selected(&[":brandA:night:x"]).is_err());
+    }
+
+    #[test]
+    fn test_follow_symlinks_per_app_and_flavor() {
+        let json = r#"{
+            "outputDir": "./build",
+            "baseDir": "./skin",
+            "followSymlinks": true,
+            "apps": [
+                {
+                    "name": "linked",
+                    "packageName": "com.linked",
+                    "followSymlinks": false,
+                    "flavors": [{ "name": "day" }, { "name": "night", "followSymlinks": true }]
+                },
+                { "name": "plain", "packageName": "com.plain" }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let follow: Vec<(String, Option<bool>)> = multi
+            .into_build_configs()
+            .iter()
+            .map(|c| (c.target(), c.follow_symlinks))
+            .collect();
+        assert_eq!(
+            follow,
+            vec![
+                (":linked:day".to_string(), Some(false)),
+                (":linked:night".to_string(), Some(true)),
+                (":plain".to_string(), Some(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_feature_flags_gate_resource_dirs() {
+        let json = r#"{
+            "outputDir": "./build",
+            "baseDir": "./skin",
+            "featureFlags": { "winter": true, "sale": false },
+            "apps": [
+                {
+                    "packageName": "com.brand.a",
+                    "featureFlags": { "winter": false },
+                    "conditionalResourceDirs": [
+                        { "path": "./seasonal/winter", "includeIf": "flag_winter" },
+                        { "path": "./seasonal/regular", "includeIf": "!sale" }
+                    ]
+                },
+                {
+                    "packageName": "com.brand.b",
+                    "conditionalResourceDirs": [
+                        { "path": "./seasonal/winter", "includeIf": "winter" },
+                        { "path": "./seasonal/spring", "includeIf": "spring" }
+                    ]
+                }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let mut configs = multi.into_build_configs();
+        let flags = configs[0].feature_flags.clone().unwrap();
+        assert!(!flags["winter"]);
+        assert!(!flags["sale"]);
+
+        configs[0].resolve_conditional_dirs().unwrap();
+        assert_eq!(
+            configs[0].additional_resource_dirs,
+            Some(vec![PathBuf::from("./seasonal/regular")])
+        );
+        assert!(configs[0].conditional_resource_dirs.is_none());
+        let err = configs[1].resolve_conditional_dirs().unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown feature flag"));
+    }
+
+    #[test]
+    fn test_date_windows_gate_resource_dirs() {
+        let json = r#"{
+            "outputDir": "./build",
+            "baseDir": "./skin",
+            "featureFlags": { "sale": true },
+            "scheduledFiles": [{ "pattern": "*/holiday_*", "from": "12-01", "until": "12-31" }],
+            "apps": [
+                {
+                    "packageName": "com.brand.a",
+                    "conditionalResourceDirs": [
+                        { "path": "./seasonal/christmas", "from": "12-20", "until": "01-06" },
+                        { "path": "./seasonal/sale", "includeIf": "sale", "until": "2026-11-30" }
+                    ]
+                }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let configs = multi.into_build_configs();
+        assert_eq!(configs[0].scheduled_files.as_ref().unwrap().len(), 1);
+
+        let resolved = |as_of: Option<&str>| {
+            let mut config = configs[0].clone();
+            config.as_of = as_of.map(str::to_string);
+            config.resolve_conditional_dirs().map(|_| config)
+        };
+        assert_eq!(
+            resolved(Some("2026-12-24"))
+                .unwrap()
+                .additional_resource_dirs,
+            Some(vec![PathBuf::from("./seasonal/christmas")])
+        );
+        assert_eq!(
+            resolved(Some("2026-11-02"))
+                .unwrap()
+                .additional_resource_dirs,
+            Some(vec![PathBuf::from("./seasonal/sale")])
+        );
+        // Without asOf the build date is recorded so that it is part of the cache key
+        assert_eq!(
+            resolved(None).unwrap().as_of,
+            Some(crate::schedule::today())
+        );
+        assert!(resolved(Some("24.12.2026")).is_err());
+    }
+
+    #[test]
+    fn test_apply_output_layout_gradle() {
+        let json = r#"{
+            "outputDir": "./build",
+            "outputLayout": "gradle",
+            "buildType": "debug",
+            "apps": [
+                {
+                    "baseDir": "./skin",
+                    "packageName": "com.skin",
+                    "flavors": [{ "name": "red" }]
+                },
+                {
+                    "baseDir": "./plain",
+                    "packageName": "com.plain"
+                }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let mut configs = multi.into_build_configs();
+        for config in &mut configs {
+            config.apply_output_layout().unwrap();
+        }
+
+        assert_eq!(configs[0].flavor.as_deref(), Some("red"));
+        assert_eq!(
+            configs[0].output_dir,
+            PathBuf::from("./build/outputs/skin/red/debug")
+        );
+        assert_eq!(
+            configs[0].build_dir,
+            Some(PathBuf::from("./build/intermediates/asb"))
+        );
+        assert_eq!(
+            configs[1].output_dir,
+            PathBuf::from("./build/outputs/skin/debug")
+        );
+        assert_eq!(
+            configs[1].build_dir,
+            Some(PathBuf::from("./build/intermediates/asb"))
+        );
+
+        // An explicit buildDir is kept
+        let mut config = BuildConfig {
+            output_layout: Some(OutputLayout::Gradle),
+            output_dir: PathBuf::from("./build"),
+            build_dir: Some(PathBuf::from("./tmp")),
+            ..BuildConfig::default_config()
+        };
+        config.apply_output_layout().unwrap();
+        assert_eq!(config.build_dir, Some(PathBuf::from("./tmp")));
+        assert_eq!(
+            config.output_dir,
+            PathBuf::from("./build/outputs/skin/release")
+        );
+    }
+
+    #[test]
+    fn test_apply_output_layout_template() {
+        let json = r#"{
+            "outputDir": "./build",
+            "outputLayout": "{brand}/{flavor}/",
+            "apps": [
+                {
+                    "name": "brandA",
+                    "baseDir": "./skin",
+                    "packageName": "com.brand.a",
+                    "flavors": [{ "name": "night" }]
+                },
+                { "baseDir": "./plain", "packageName": "com.plain" }
+            ]
+        }"#;
+
+        let multi: MultiAppConfig = serde_json::from_str(json).unwrap();
+        let mut configs = multi.into_build_configs();
+        for config in &mut configs {
+            config.apply_output_layout().unwrap();
+        }
+        assert_eq!(configs[0].output_dir, PathBuf::from("./build/brandA/night"));
+        assert_eq!(configs[1].output_dir, PathBuf::from("./build/com.plain"));
+        assert_eq!(configs[1].build_dir, Some(PathBuf::from("./build/.build")));
+
+        let layout = |template: &str| {
+            let mut config = BuildConfig {
+                output_layout: Some(OutputLayout::Template(template.to_string())),
+                version_name: Some("2.1".to_string()),
+                ..BuildConfig::default_config()
+            };
+            config.apply_output_layout().map(|_| config.output_dir)
+        };
+        assert_eq!(
+            layout("{packageName}/v{versionName}").unwrap(),
+            PathBuf::from("./build/outputs/skin/com.example.skin/v2.1")
+        );
+        assert!(layout("{brand}/../..").is_err());
+        assert!(layout("{brand").is_err());
+    }
+
+    #[test]
+    fn test_load_built_configs_apply_output_layout() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("asb.config.json");
+        std::fs::write(
+            &config_path,
+            r#"{
+                "outputDir": "./build",
+                "outputLayout": "gradle",
+                "baseDir": "./skin",
+                "apps": [{ "packageName": "com.brand.a", "flavors": [{ "name": "night" }] }]
+            }"#,
+        )
+        .unwrap();
+
+        // Subcommands reading build outputs see the directories `asb build` wrote to
+        let configs = BuildConfig::load_built_configs(Some(config_path.clone())).unwrap();
+        assert!(
+            configs[0]
+                .output_apk_path()
+                .ends_with("build/outputs/skin/night/release/com.brand.a.night.skin")
+        );
+        assert!(
+            configs[0]
+                .build_dir
+                .as_ref()
+                .unwrap()
+                .ends_with("build/intermediates/asb")
+        );
+        let unlaid = BuildConfig::load_configs(Some(config_path), None).unwrap();
+        assert!(unlaid.configs[0].output_dir.ends_with("build"));
+    }
+
+    #[test]
+    fn test_stable_ids_path_per_namespace() {
+        let mut config = BuildConfig {
+            stable_ids_file: Some(PathBuf::from("ids/stable.txt")),
+            package_name: "com.skin.red".to_string(),
+            ..BuildConfig::default_config()
+        };
+        assert_eq!(
+            config.stable_ids_path(),
+            Some(PathBuf::from("ids/stable.txt"))
+        );
+
+        config.namespaced = Some(true);
+        assert_eq!(
+            config.stable_ids_path(),
+            Some(PathBuf::from("ids/stable.com.skin.red.txt"))
+        );
+    }
+
+    #[test]
+    fn test_apply_strict_keeps_explicit_settings() {
+        let mut lenient = BuildConfig::default_config();
+        lenient.apply_strict();
+        assert_eq!(lenient.warnings_as_errors, None);
+        assert_eq!(lenient.validate_references, None);
+
+        let mut config = BuildConfig {
+            strict: Some(true),
+            validate_xml: Some(false),
+            ..BuildConfig::default_config()
+        };
+        config.apply_strict();
+        assert_eq!(config.warnings_as_errors, Some(true));
+        assert_eq!(config.validate_references, Some(true));
+        assert_eq!(config.validate_xml, Some(false));
+        assert_eq!(
+            config.missing_resource_dir_policy,
+            Some(MissingResourceDirPolicy::Error)
+        );
+    }
+
+    #[test]
+    fn test_user_defaults_fill_unset_fields() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        assert!(UserDefaults::load(&path).unwrap().android_jar.is_none());
+
+        std::fs::write(
+            &path,
+            r#"{"aapt2Path": "/opt/sdk/aapt2", "androidJar": "/opt/sdk/android.jar", "cacheDir": "/var/cache/asb"}"#,
+        )
+        .unwrap();
+        let defaults = UserDefaults::load(&path).unwrap();
+        let mut config = BuildConfig {
+            android_jar: Some(PathBuf::from("project/android.jar")),
+            ..BuildConfig::default_config()
+        };
+        defaults.apply(&mut config);
+        assert_eq!(config.aapt2_path, Some(PathBuf::from("/opt/sdk/aapt2")));
+        assert_eq!(
+            config.android_jar,
+            Some(PathBuf::from("project/android.jar"))
+        );
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/var/cache/asb")));
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(UserDefaults::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_expand_paths_variables() {
+        let root = std::path::absolute("workspace").unwrap();
+        // SAFETY: the variable name is unique to this test
+        unsafe { std::env::set_var("ASB_TEST_SKIN_ROOT", "/skins") };
+        let mut config = BuildConfig {
+            resource_dir: PathBuf::from("${workspaceRoot}/${flavor}/res"),
+            output_dir: PathBuf::from("${env:ASB_TEST_SKIN_ROOT}/out"),
+            aar_files: Some(vec![AarFile::from(PathBuf::from(
+                "${ASB_TEST_SKIN_ROOT}/lib.aar",
+            ))]),
+            output_file: Some("${config:packageName}-${config:versionCode}.skin".to_string()),
+            package_name: "com.skin.red".to_string(),
+            version_code: Some(7),
+            flavor: Some("red".to_string()),
+            android_jar: Some(PathBuf::from("/sdk/android.jar")),
+            ..BuildConfig::default_config()
+        };
+        config.expand_paths(Path::new("workspace")).unwrap();
+        assert_eq!(config.resource_dir, root.join("red").join("res"));
+        assert_eq!(config.output_dir, PathBuf::from("/skins/out"));
+        assert_eq!(
+            config.aar_files,
+            Some(vec![AarFile::from(PathBuf::from("/skins/lib.aar"))])
+        );
+        assert_eq!(config.output_file.as_deref(), Some("com.skin.red-7.skin"));
+
+        for (path, expected) in [
+            ("${env:ASB_TEST_UNSET_VARIABLE}/res", "is not set"),
+            ("${flavor}/res", "only available in flavor configs"),
+            ("${config:buildType}/res", "Unknown or unset config field"),
+            ("${config:resourceDir}", "refers to a value with variables"),
+            ("${project:name}/res", "Unknown variable"),
+            ("${workspaceRoot/res", "Unterminated variable"),
+        ] {
+            let mut config = BuildConfig {
+                resource_dir: PathBuf::from(path),
+                android_jar: Some(PathBuf::from("/sdk/android.jar")),
+                ..BuildConfig::default_config()
+            };
+            let err = config.expand_paths(Path::new(".")).unwrap_err();
+            assert!(
+                err.to_string().contains(expected) && err.to_string().contains("resourceDir"),
+                "{}: {}",
+                path,
+                err
+            );
+        }
+    }
 }
 
 /// Result of aapt2 compile operation
@@ -896,6 +3618,42 @@ pub struct LinkResult {
     pub errors: Vec<String>,
 }
 
+/// An `aarFiles` entry: a path, or `{ "path": ..., "sha256": ... }` to pin the AAR's content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AarFile {
+    Path(PathBuf),
+    Pinned { path: PathBuf, sha256: String },
+}
+
+impl AarFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Path(path) | Self::Pinned { path, .. } => path,
+        }
+    }
+
+    pub fn path_mut(&mut self) -> &mut PathBuf {
+        match self {
+            Self::Path(path) | Self::Pinned { path, .. } => path,
+        }
+    }
+
+    /// Expected SHA-256 of the AAR, verified before extraction
+    pub fn sha256(&self) -> Option<&str> {
+        match self {
+            Self::Path(_) => None,
+            Self::Pinned { sha256, .. } => Some(sha256),
+        }
+    }
+}
+
+impl From<PathBuf> for AarFile {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
 /// AAR file information
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -904,13 +3662,30 @@ pub struct AarInfo {
     pub resource_dir: Option<PathBuf>,
     pub manifest_path: Option<PathBuf>,
     pub extracted_dir: PathBuf,
+    /// SHA-256 of the AAR file, empty when extracted on its own
+    pub sha256: String,
+    /// Whether the extraction was kept from an earlier incremental build
+    pub reused: bool,
 }
 
 /// Build result
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct BuildResult {
     pub success: bool,
     pub apk_path: Option<PathBuf>,
     pub errors: Vec<String>,
+    /// Problems the build continued past, e.g. skipped resource directories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
     pub build_duration: Duration,
+    /// Git state the package was built from, when VCS capture is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsInfo>,
+    /// Resource files reused from the incremental build cache
+    pub cached_files: usize,
+    /// Resource files compiled by aapt2 in this build
+    pub compiled_files: usize,
+    /// Staged files normalized for a reproducible package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<NormalizationStats>,
 }
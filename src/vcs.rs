@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use tracing::{debug, warn};
+
+/// Source control state captured at build time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VcsInfo {
+    /// Full commit hash of HEAD
+    pub commit: String,
+    /// Current branch, or None for a detached HEAD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Whether tracked files have uncommitted changes
+    pub dirty: bool,
+}
+
+impl VcsInfo {
+    /// Capture git state for the repository containing `dir`
+    /// Returns None (with a warning) when git is unavailable or `dir` is not in a repository
+    pub fn capture(dir: &Path) -> Option<Self> {
+        let dir = existing_ancestor(dir);
+
+        let commit = match git(dir, &["rev-parse", "HEAD"]) {
+            Some(commit) => commit,
+            None => {
+                warn!(
+                    "Could not read git commit for {}, skipping VCS info",
+                    dir.display()
+                );
+                return None;
+            }
+        };
+        let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD");
+        // Untracked files don't count, matching `git describe --dirty`
+        let dirty = git(dir, &["status", "--porcelain", "--untracked-files=no"])
+            .is_some_and(|status| !status.is_empty());
+
+        Some(Self {
+            commit,
+            branch,
+            dirty,
+        })
+    }
+
+    /// Abbreviated commit hash
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(7)]
+    }
+
+    /// Suffix appended to versionName, e.g. "-1a2b3c4" or "-1a2b3c4-dirty"
+    pub fn version_suffix(&self) -> String {
+        if self.dirty {
+            format!("-{}-dirty", self.short_commit())
+        } else {
+            format!("-{}", self.short_commit())
+        }
+    }
+}
+
+/// Number of commits reachable from HEAD in the repository containing `dir`
+pub fn commit_count(dir: &Path) -> Option<u64> {
+    git(existing_ancestor(dir), &["rev-list", "--count", "HEAD"])?
+        .parse()
+        .ok()
+}
+
+/// Resource dirs may not exist yet; ask git from the closest existing ancestor
+fn existing_ancestor(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|p| p.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Run a git command in `dir` and return its trimmed stdout on success
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        debug!(
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) -> bool {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=asb", "-c", "user.email=asb@example.com"])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    #[test]
+    fn test_capture_git_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        if !run_git(repo, &["init", "-q", "-b", "skins"]) {
+            eprintln!("Skipping test: git not available");
+            return;
+        }
+        let res_dir = repo.join("res").join("values");
+        fs::create_dir_all(&res_dir).unwrap();
+        fs::write(res_dir.join("colors.xml"), "<resources/>").unwrap();
+        assert!(run_git(repo, &["add", "."]));
+        assert!(run_git(repo, &["commit", "-q", "-m", "init"]));
+
+        let info = VcsInfo::capture(&repo.join("res")).unwrap();
+        assert_eq!(commit_count(&repo.join("res")), Some(1));
+        assert_eq!(info.commit.len(), 40);
+        assert_eq!(info.branch.as_deref(), Some("skins"));
+        assert!(!info.dirty);
+        assert_eq!(info.version_suffix(), format!("-{}", &info.commit[..7]));
+
+        // Untracked files are ignored, edits to tracked files are not
+        fs::write(repo.join("notes.txt"), "scratch").unwrap();
+        assert!(!VcsInfo::capture(repo).unwrap().dirty);
+        fs::write(res_dir.join("colors.xml"), "<resources></resources>").unwrap();
+        let info = VcsInfo::capture(repo).unwrap();
+        assert!(info.dirty);
+        assert!(info.version_suffix().ends_with("-dirty"));
+    }
+}
@@ -0,0 +1,645 @@
+use roxmltree::{Document, Node};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::tokens::normalize_color;
+
+/// Child elements of `<resources>` aapt2 knows
+const VALUES_TAGS: &[&str] = &[
+    "add-resource",
+    "array",
+    "attr",
+    "bool",
+    "color",
+    "declare-styleable",
+    "dimen",
+    "drawable",
+    "eat-comment",
+    "fraction",
+    "id",
+    "integer",
+    "integer-array",
+    "item",
+    "java-symbol",
+    "macro",
+    "overlayable",
+    "plurals",
+    "public",
+    "public-group",
+    "skip",
+    "staging-public-group",
+    "staging-public-group-final",
+    "string",
+    "string-array",
+    "style",
+];
+
+/// Values elements that declare a resource and need a `name`
+const NAMED_VALUES_TAGS: &[&str] = &[
+    "array",
+    "attr",
+    "bool",
+    "color",
+    "declare-styleable",
+    "dimen",
+    "drawable",
+    "fraction",
+    "id",
+    "integer",
+    "integer-array",
+    "item",
+    "macro",
+    "plurals",
+    "string",
+    "string-array",
+    "style",
+];
+
+/// Root elements of drawable XML, besides custom drawable classes
+const DRAWABLE_ROOTS: &[&str] = &[
+    "adaptive-icon",
+    "animated-rotate",
+    "animated-selector",
+    "animated-vector",
+    "animation-list",
+    "bitmap",
+    "clip",
+    "color",
+    "drawable",
+    "inset",
+    "layer-list",
+    "level-list",
+    "nine-patch",
+    "ripple",
+    "rotate",
+    "scale",
+    "selector",
+    "shape",
+    "transition",
+    "vector",
+];
+
+const SHAPE_CHILDREN: &[&str] = &["corners", "gradient", "padding", "size", "solid", "stroke"];
+const SHAPE_TYPES: &[&str] = &["rectangle", "oval", "line", "ring"];
+const VECTOR_CHILDREN: &[&str] = &["path", "group", "clip-path"];
+const PLURAL_QUANTITIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+const ATTR_FORMATS: &[&str] = &[
+    "boolean",
+    "color",
+    "dimension",
+    "enum",
+    "flags",
+    "float",
+    "fraction",
+    "integer",
+    "reference",
+    "string",
+];
+const DIMENSION_UNITS: &[&str] = &["dp", "dip", "sp", "px", "pt", "in", "mm"];
+
+/// `android:` attributes of drawable XML that take a color
+const COLOR_ATTRIBUTES: &[&str] = &[
+    "centerColor",
+    "color",
+    "endColor",
+    "fillColor",
+    "startColor",
+    "strokeColor",
+    "tint",
+];
+
+/// `android:` attributes of drawable XML that take a dimension
+const DIMENSION_ATTRIBUTES: &[&str] = &[
+    "bottomLeftRadius",
+    "bottomRightRadius",
+    "dashGap",
+    "dashWidth",
+    "height",
+    "innerRadius",
+    "radius",
+    "thickness",
+    "topLeftRadius",
+    "topRightRadius",
+    "width",
+];
+
+/// A resource XML element that does not match its built-in schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlViolation {
+    pub file: PathBuf,
+    pub line: u32,
+    pub message: String,
+}
+
+/// Check values, drawable, mipmap and color XML files against built-in schemas
+///
+/// Every violation of every file is reported, where aapt2 stops at the first error of a
+/// file: values files need a `<resources>` root with known, named children whose values
+/// match their type, and drawable XML needs a known root with the children and attribute
+/// formats its kind allows. Files of other resource types are not checked.
+pub fn validate_xml(files: &[PathBuf]) -> Vec<XmlViolation> {
+    let mut violations = Vec::new();
+    for file in files {
+        if file.extension().is_none_or(|ext| ext != "xml") {
+            continue;
+        }
+        let Some(kind) = file
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('-').next())
+        else {
+            continue;
+        };
+        if !matches!(kind, "values" | "drawable" | "mipmap" | "color") {
+            continue;
+        }
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                violations.push(XmlViolation {
+                    file: file.clone(),
+                    line: 1,
+                    message: format!("failed to read: {}", e),
+                });
+                continue;
+            }
+        };
+        let document = match Document::parse(&content) {
+            Ok(document) => document,
+            Err(e) => {
+                violations.push(XmlViolation {
+                    file: file.clone(),
+                    line: e.pos().row,
+                    message: format!("invalid XML: {}", e),
+                });
+                continue;
+            }
+        };
+        let mut checker = Checker {
+            file,
+            document: &document,
+            violations: &mut violations,
+        };
+        match kind {
+            "values" => checker.values(document.root_element()),
+            "color" => checker.color_state_list(document.root_element()),
+            _ => checker.drawable(document.root_element()),
+        }
+    }
+    violations
+}
+
+struct Checker<'a, 'input> {
+    file: &'a Path,
+    document: &'a Document<'input>,
+    violations: &'a mut Vec<XmlViolation>,
+}
+
+impl Checker<'_, '_> {
+    fn report(&mut self, node: Node, message: String) {
+        self.violations.push(XmlViolation {
+            file: self.file.to_path_buf(),
+            line: self.document.text_pos_at(node.range().start).row,
+            message,
+        });
+    }
+
+    fn values(&mut self, root: Node) {
+        if root.tag_name().name() != "resources" {
+            self.report(
+                root,
+                format!(
+                    "root element is <{}>, expected <resources>",
+                    root.tag_name().name()
+                ),
+            );
+            return;
+        }
+        let mut declared = HashSet::new();
+        for node in root.children().filter(Node::is_element) {
+            let tag = node.tag_name().name();
+            if !VALUES_TAGS.contains(&tag) {
+                self.report(node, format!("unknown element <{}> in <resources>", tag));
+                continue;
+            }
+            if !NAMED_VALUES_TAGS.contains(&tag) {
+                continue;
+            }
+            let Some(name) = node.attribute("name") else {
+                self.report(node, format!("<{}> has no name attribute", tag));
+                continue;
+            };
+            if !is_resource_name(name) {
+                self.report(node, format!("invalid resource name '{}'", name));
+            }
+            let kind = match tag {
+                "item" => match node.attribute("type") {
+                    Some(kind) => kind,
+                    None => {
+                        self.report(node, format!("<item> '{}' has no type attribute", name));
+                        continue;
+                    }
+                },
+                "string-array" | "integer-array" => "array",
+                other => other,
+            };
+            if !declared.insert((kind, name)) {
+                self.report(node, format!("duplicate {} resource '{}'", kind, name));
+            }
+            self.value(node, tag, name);
+        }
+    }
+
+    /// Check the value and children of a named values element
+    fn value(&mut self, node: Node, tag: &str, name: &str) {
+        let text = node.text().unwrap_or_default().trim();
+        let expected = match tag {
+            "color" => (!is_reference(text) && normalize_color(text).is_none())
+                .then_some("a color (#RGB, #ARGB, #RRGGBB or #AARRGGBB)"),
+            "dimen" => (!is_reference(text)
+                && !is_dimension(text)
+                && !is_float(text)
+                && !is_fraction(text))
+            .then_some("a dimension such as 16dp"),
+            "bool" => (!is_reference(text)
+                && !text.eq_ignore_ascii_case("true")
+                && !text.eq_ignore_ascii_case("false"))
+            .then_some("true or false"),
+            "integer" => (!is_reference(text) && !is_integer(text)).then_some("an integer"),
+            "fraction" => {
+                (!is_reference(text) && !is_fraction(text)).then_some("a fraction such as 50%")
+            }
+            _ => None,
+        };
+        if let Some(expected) = expected {
+            self.report(
+                node,
+                format!(
+                    "{} '{}' has value '{}', expected {}",
+                    tag, name, text, expected
+                ),
+            );
+        }
+
+        let children = node.children().filter(Node::is_element);
+        match tag {
+            "plurals" => {
+                for child in children {
+                    if child.tag_name().name() != "item" {
+                        self.unexpected_child(child, tag);
+                    } else if !child
+                        .attribute("quantity")
+                        .is_some_and(|q| PLURAL_QUANTITIES.contains(&q))
+                    {
+                        self.report(
+                            child,
+                            format!(
+                                "plurals '{}' item needs a quantity of {}",
+                                name,
+                                PLURAL_QUANTITIES.join(", ")
+                            ),
+                        );
+                    }
+                }
+            }
+            "array" | "string-array" | "integer-array" => {
+                for child in children.filter(|c| c.tag_name().name() != "item") {
+                    self.unexpected_child(child, tag);
+                }
+            }
+            "style" => {
+                for child in children {
+                    if child.tag_name().name() != "item" {
+                        self.unexpected_child(child, tag);
+                    } else if child.attribute("name").is_none() {
+                        self.report(
+                            child,
+                            format!("style '{}' item has no name attribute", name),
+                        );
+                    }
+                }
+            }
+            "declare-styleable" => {
+                for child in children {
+                    if child.tag_name().name() != "attr" {
+                        self.unexpected_child(child, tag);
+                    } else if child.attribute("name").is_none() {
+                        self.report(child, format!("<attr> in '{}' has no name attribute", name));
+                    } else {
+                        self.attr_format(child);
+                    }
+                }
+            }
+            "attr" => self.attr_format(node),
+            _ => {}
+        }
+    }
+
+    fn attr_format(&mut self, node: Node) {
+        let Some(format) = node.attribute("format") else {
+            return;
+        };
+        for part in format.split('|').map(str::trim) {
+            if !ATTR_FORMATS.contains(&part) {
+                self.report(node, format!("unknown attr format '{}'", part));
+            }
+        }
+    }
+
+    fn unexpected_child(&mut self, child: Node, parent: &str) {
+        self.report(
+            child,
+            format!("unexpected <{}> in <{}>", child.tag_name().name(), parent),
+        );
+    }
+
+    /// `color` directories hold color state lists (and gradients since API 31)
+    fn color_state_list(&mut self, root: Node) {
+        match root.tag_name().name() {
+            "selector" => {
+                for child in root.children().filter(Node::is_element) {
+                    if child.tag_name().name() != "item" {
+                        self.unexpected_child(child, "selector");
+                    } else if android_attribute(child, "color").is_none() {
+                        self.report(
+                            child,
+                            "color selector item has no android:color".to_string(),
+                        );
+                    }
+                }
+                self.attribute_formats(root);
+            }
+            "gradient" => self.attribute_formats(root),
+            other => self.report(
+                root,
+                format!("root element is <{}>, expected <selector>", other),
+            ),
+        }
+    }
+
+    fn drawable(&mut self, root: Node) {
+        let tag = root.tag_name().name();
+        // Custom drawable classes are named by their fully qualified class
+        if !DRAWABLE_ROOTS.contains(&tag) && !tag.contains('.') {
+            self.report(root, format!("unknown drawable root element <{}>", tag));
+            return;
+        }
+        let children: Vec<Node> = root.children().filter(Node::is_element).collect();
+        match tag {
+            "selector" | "animated-selector" | "layer-list" | "level-list" | "transition"
+            | "animation-list" => {
+                for child in &children {
+                    let child_tag = child.tag_name().name();
+                    if child_tag == "item" {
+                        if android_attribute(*child, "drawable").is_none()
+                            && !child.children().any(|c| c.is_element())
+                        {
+                            self.report(
+                                *child,
+                                format!("<{}> item has no android:drawable or child drawable", tag),
+                            );
+                        }
+                    } else if !(tag == "animated-selector" && child_tag == "transition") {
+                        self.unexpected_child(*child, tag);
+                    }
+                }
+            }
+            "shape" => {
+                if let Some(shape) = android_attribute(root, "shape")
+                    && !SHAPE_TYPES.contains(&shape)
+                {
+                    self.report(
+                        root,
+                        format!(
+                            "unknown android:shape '{}', expected one of {}",
+                            shape,
+                            SHAPE_TYPES.join(", ")
+                        ),
+                    );
+                }
+                for child in &children {
+                    if !SHAPE_CHILDREN.contains(&child.tag_name().name()) {
+                        self.unexpected_child(*child, tag);
+                    }
+                }
+            }
+            "vector" => {
+                for attribute in ["width", "height", "viewportWidth", "viewportHeight"] {
+                    if android_attribute(root, attribute).is_none() {
+                        self.report(root, format!("<vector> has no android:{}", attribute));
+                    }
+                }
+                self.vector_children(root);
+            }
+            _ => {}
+        }
+        for node in root.descendants().filter(Node::is_element) {
+            self.attribute_formats(node);
+        }
+    }
+
+    fn vector_children(&mut self, parent: Node) {
+        for child in parent.children().filter(Node::is_element) {
+            if !VECTOR_CHILDREN.contains(&child.tag_name().name()) {
+                self.unexpected_child(child, parent.tag_name().name());
+            } else if child.tag_name().name() == "group" {
+                self.vector_children(child);
+            }
+        }
+    }
+
+    /// Color and dimension `android:` attributes of an element
+    fn attribute_formats(&mut self, node: Node) {
+        for attribute in node.attributes() {
+            if attribute.namespace() != Some(ANDROID_NS) {
+                continue;
+            }
+            let (name, value) = (attribute.name(), attribute.value().trim());
+            if is_reference(value) {
+                continue;
+            }
+            if COLOR_ATTRIBUTES.contains(&name) && normalize_color(value).is_none() {
+                self.report(
+                    node,
+                    format!("android:{} '{}' is not a color or reference", name, value),
+                );
+            } else if DIMENSION_ATTRIBUTES.contains(&name) && !is_dimension(value) {
+                self.report(
+                    node,
+                    format!(
+                        "android:{} '{}' is not a dimension or reference",
+                        name, value
+                    ),
+                );
+            }
+        }
+    }
+}
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+fn android_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((ANDROID_NS, name))
+}
+
+/// Resource names are identifiers, with `.` allowed for styles
+fn is_resource_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// A resource (`@`) or theme attribute (`?`) reference, or `@null`
+fn is_reference(value: &str) -> bool {
+    value.starts_with('@') || value.starts_with('?')
+}
+
+fn is_float(value: &str) -> bool {
+    !value.is_empty() && value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+fn is_dimension(value: &str) -> bool {
+    DIMENSION_UNITS.iter().any(|unit| {
+        value
+            .strip_suffix(unit)
+            .is_some_and(|number| is_float(number.trim_end()))
+    })
+}
+
+fn is_fraction(value: &str) -> bool {
+    value
+        .strip_suffix("%p")
+        .or_else(|| value.strip_suffix('%'))
+        .is_some_and(is_float)
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(temp: &TempDir, path: &str, content: &str) -> PathBuf {
+        let file = temp.path().join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, content).unwrap();
+        file
+    }
+
+    fn messages(violations: &[XmlViolation]) -> Vec<(u32, &str)> {
+        violations
+            .iter()
+            .map(|v| (v.line, v.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_values_reports_every_violation() {
+        let temp = TempDir::new().unwrap();
+        let colors = write(
+            &temp,
+            "values/colors.xml",
+            r#"<resources>
+    <color name="ok">#FF0000</color>
+    <color name="bad">red</color>
+    <dimen name="gap">8</dimen>
+    <dimen name="margin">8dq</dimen>
+    <colour name="typo">#fff</colour>
+    <color>#fff</color>
+    <color name="ok">@color/bad</color>
+    <bool name="flag">yes</bool>
+    <plurals name="items"><item quantity="several">x</item></plurals>
+</resources>
+"#,
+        );
+        let broken = write(&temp, "values-night/colors.xml", "<resources>\n<color>");
+        let ignored = write(&temp, "raw/data.xml", "<anything/>");
+
+        let violations = validate_xml(&[colors, broken.clone(), ignored]);
+        assert_eq!(
+            messages(&violations[..7]),
+            vec![
+                (
+                    3,
+                    "color 'bad' has value 'red', expected a color (#RGB, #ARGB, #RRGGBB or #AARRGGBB)"
+                ),
+                (
+                    5,
+                    "dimen 'margin' has value '8dq', expected a dimension such as 16dp"
+                ),
+                (6, "unknown element <colour> in <resources>"),
+                (7, "<color> has no name attribute"),
+                (8, "duplicate color resource 'ok'"),
+                (9, "bool 'flag' has value 'yes', expected true or false"),
+                (
+                    10,
+                    "plurals 'items' item needs a quantity of zero, one, two, few, many, other"
+                ),
+            ]
+        );
+        assert_eq!(violations.len(), 8);
+        assert_eq!(violations[7].file, broken);
+        assert!(violations[7].message.starts_with("invalid XML: "));
+    }
+
+    #[test]
+    fn test_validate_drawables() {
+        let temp = TempDir::new().unwrap();
+        let ns = r#"xmlns:android="http://schemas.android.com/apk/res/android""#;
+        let files = vec![
+            write(
+                &temp,
+                "drawable/bg.xml",
+                &format!(
+                    "<shape {ns} android:shape=\"square\">\n  <solid android:color=\"blue\"/>\n  \
+                     <corners android:radius=\"4dp\"/>\n  <item/>\n</shape>"
+                ),
+            ),
+            write(
+                &temp,
+                "drawable-v24/icon.xml",
+                &format!(
+                    "<vector {ns} android:width=\"24dp\" android:height=\"24dp\">\n  <path/>\n</vector>"
+                ),
+            ),
+            write(
+                &temp,
+                "color/text.xml",
+                &format!("<selector {ns}>\n  <item android:state_enabled=\"false\"/>\n</selector>"),
+            ),
+            write(
+                &temp,
+                "drawable/ok.xml",
+                &format!("<selector {ns}>\n  <item android:drawable=\"@null\"/>\n</selector>"),
+            ),
+        ];
+
+        let violations = validate_xml(&files);
+        assert_eq!(
+            messages(&violations),
+            vec![
+                (
+                    1,
+                    "unknown android:shape 'square', expected one of rectangle, oval, line, ring"
+                ),
+                (4, "unexpected <item> in <shape>"),
+                (2, "android:color 'blue' is not a color or reference"),
+                (1, "<vector> has no android:viewportWidth"),
+                (1, "<vector> has no android:viewportHeight"),
+                (2, "color selector item has no android:color"),
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use asb::types::BuildConfig;
+use asb::types::{AarFile, BuildConfig};
 use std::path::PathBuf;
 
 /// Integration tests for array mode configuration
@@ -145,7 +145,7 @@ fn test_config_with_all_optional_fields() {
     assert_eq!(config.aapt2_path, Some(PathBuf::from("/path/to/aapt2")));
     assert_eq!(
         config.aar_files,
-        Some(vec![PathBuf::from("/path/to/lib.aar")])
+        Some(vec![AarFile::from(PathBuf::from("/path/to/lib.aar"))])
     );
     assert_eq!(config.incremental, Some(true));
     assert_eq!(config.cache_dir, Some(PathBuf::from("./cache")));
@@ -527,3 +527,102 @@ fn test_multi_app_with_max_parallel_builds() {
     assert_eq!(multi_config.max_parallel_builds, Some(4));
     assert_eq!(multi_config.apps.len(), 2);
 }
+
+/// Link the same inputs through every link input mode and compare the resource tables.
+/// Requires a real aapt2 and android.jar (ANDROID_HOME); skipped otherwise.
+#[tokio::test]
+async fn test_link_input_modes_produce_identical_tables() {
+    use asb::aapt2::{Aapt2, LinkInputMode};
+    use std::io::Read;
+
+    let Ok(aapt2) = Aapt2::new(None) else {
+        eprintln!("skipping: aapt2 not found");
+        return;
+    };
+    let mut defaults = BuildConfig::default_config();
+    defaults.expand_paths(std::path::Path::new(".")).unwrap();
+    let Some(android_jar) = defaults.android_jar else {
+        eprintln!("skipping: android.jar not found");
+        return;
+    };
+    let aapt2_path = aapt2.into_path();
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let write_colors = |dir: &str, body: &str| {
+        let values = temp_dir.path().join(dir).join("values");
+        std::fs::create_dir_all(&values).unwrap();
+        std::fs::write(
+            values.join("colors.xml"),
+            format!("<resources>{}</resources>", body),
+        )
+        .unwrap();
+        temp_dir.path().join(dir)
+    };
+    let base_res = write_colors("base", r##"<color name="a">#111111</color>"##);
+    let main_res = write_colors(
+        "main",
+        r##"<color name="a">#222222</color><color name="b">#222222</color>"##,
+    );
+    let flavor_res = write_colors("flavor", r##"<color name="b">#333333</color>"##);
+
+    let compiler = Aapt2::new(Some(aapt2_path.clone())).unwrap();
+    let compile = |res: &PathBuf, name: &str| {
+        compiler
+            .compile_dir(res, &temp_dir.path().join("compiled").join(name))
+            .unwrap()
+            .flat_files
+    };
+    let base = compile(&base_res, "base");
+    let overlays = vec![compile(&main_res, "main"), compile(&flavor_res, "flavor")];
+
+    let manifest = temp_dir.path().join("AndroidManifest.xml");
+    std::fs::write(&manifest, r#"<manifest package="com.example.skin"/>"#).unwrap();
+
+    let mut tables = Vec::new();
+    for mode in [
+        LinkInputMode::Direct,
+        LinkInputMode::Argfile,
+        LinkInputMode::Zip,
+    ] {
+        let out_dir = temp_dir.path().join(format!("{:?}", mode));
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let apk = out_dir.join("skin.apk");
+        let result = Aapt2::new(Some(aapt2_path.clone()))
+            .unwrap()
+            .with_link_input_mode(mode)
+            .link_with_overlays(
+                &base,
+                &overlays,
+                &manifest,
+                &android_jar,
+                &apk,
+                Some("com.example.skin"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(&out_dir),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(
+            result.success,
+            "{:?} link failed: {:?}",
+            mode, result.errors
+        );
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&apk).unwrap()).unwrap();
+        let mut table = Vec::new();
+        archive
+            .by_name("resources.arsc")
+            .unwrap()
+            .read_to_end(&mut table)
+            .unwrap();
+        tables.push(table);
+    }
+
+    assert_eq!(tables[0], tables[1], "argfile differs from direct args");
+    assert_eq!(tables[0], tables[2], "zip differs from direct args");
+}
@@ -69,6 +69,57 @@ fn test_config(
         package_id: None,
         precompiled_dependencies: None,
         assets_dir: None,
+        follow_symlinks: None,
+        respect_gitignore: None,
+        packaging: None,
+        link_input_mode: None,
+        compile_options: None,
+        static_lib_dependencies: None,
+        precompiled_common_deps: None,
+        namespaced: None,
+        auto_assign_package_ids: None,
+        manifest_mode: None,
+        manifest_placeholders: None,
+        merge_aar_manifests: None,
+        output_layout: None,
+        flavor: None,
+        build_type: None,
+        vcs: None,
+        version_code_strategy: None,
+        design_tokens: None,
+        dark_mode: None,
+        resource_prefix: None,
+        aliases: None,
+        validate_references: None,
+        auto_fix_filenames: None,
+        locale_qualifiers: None,
+        launcher_icons: None,
+        contrast: None,
+        validate_xml: None,
+        convert_encodings: None,
+        golden_images: None,
+        freeze_file: None,
+        host_versions: None,
+        feature_flags: None,
+        scheduled_files: None,
+        as_of: None,
+        audit_log: None,
+        strict: None,
+        warnings_as_errors: None,
+        size_budget: None,
+        disk_space: None,
+        conditional_resource_dirs: None,
+        stage_resources: None,
+        hooks: None,
+        plugins: None,
+        host_symbols: None,
+        owners: None,
+        only_resource_types: None,
+        app: None,
+        missing_resource_dir_policy: None,
+        aar_filters: None,
+        aapt2_limits: None,
+        heartbeat: None,
     }
 }
 
@@ -630,6 +681,57 @@ fn test_skin_builder_new_missing_resource_dir() {
         package_id: None,
         precompiled_dependencies: None,
         assets_dir: None,
+        follow_symlinks: None,
+        respect_gitignore: None,
+        packaging: None,
+        link_input_mode: None,
+        compile_options: None,
+        static_lib_dependencies: None,
+        precompiled_common_deps: None,
+        namespaced: None,
+        auto_assign_package_ids: None,
+        manifest_mode: None,
+        manifest_placeholders: None,
+        merge_aar_manifests: None,
+        output_layout: None,
+        flavor: None,
+        build_type: None,
+        vcs: None,
+        version_code_strategy: None,
+        design_tokens: None,
+        dark_mode: None,
+        resource_prefix: None,
+        aliases: None,
+        validate_references: None,
+        auto_fix_filenames: None,
+        locale_qualifiers: None,
+        launcher_icons: None,
+        contrast: None,
+        validate_xml: None,
+        convert_encodings: None,
+        golden_images: None,
+        freeze_file: None,
+        host_versions: None,
+        feature_flags: None,
+        scheduled_files: None,
+        as_of: None,
+        audit_log: None,
+        strict: None,
+        warnings_as_errors: None,
+        size_budget: None,
+        disk_space: None,
+        conditional_resource_dirs: None,
+        stage_resources: None,
+        hooks: None,
+        plugins: None,
+        host_symbols: None,
+        owners: None,
+        only_resource_types: None,
+        app: None,
+        missing_resource_dir_policy: None,
+        aar_filters: None,
+        aapt2_limits: None,
+        heartbeat: None,
     };
 
     // Should still succeed to create builder even without existing res dir